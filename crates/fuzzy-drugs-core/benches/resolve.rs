@@ -0,0 +1,121 @@
+//! Resolve-heavy workload, with and without the prepared statement cache.
+//!
+//! Run with `cargo bench -p fuzzy-drugs-core --bench resolve`. The
+//! `uncached` case sets the cache capacity to 0, which makes every
+//! `prepare_cached` call re-prepare its SQL as before.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use fuzzy_drugs_core::db::STATEMENT_CACHE_CAPACITY;
+use fuzzy_drugs_core::models::{CatalogItem, DrugMention, EncounterDraft, EscalationRule, Patient};
+use fuzzy_drugs_core::{Database, Resolver};
+
+/// Drugs dictated in the workload, with the catalog aliases that match them.
+const DRUGS: &[(&str, &str, &[&str])] = &[
+    ("CARP-100", "Carprofen 100mg tablets", &["rimadyl", "novox"]),
+    (
+        "MELOX-15",
+        "Meloxicam 1.5mg/mL oral suspension",
+        &["metacam"],
+    ),
+    (
+        "ACE-10",
+        "Acepromazine 10mg/mL injection",
+        &["ace", "promace"],
+    ),
+    ("BUPR-03", "Buprenorphine 0.3mg/mL injection", &["buprenex"]),
+    ("CEF-100", "Cefazolin 1g vial", &[]),
+    (
+        "DEX-SP",
+        "Dexmedetomidine 0.5mg/mL injection",
+        &["dexdomitor"],
+    ),
+    ("GABA-100", "Gabapentin 100mg capsules", &["neurontin"]),
+    ("AMOX-250", "Amoxicillin 250mg tablets", &[]),
+];
+
+/// A catalog of the dictated drugs plus filler items for FTS to rank past.
+fn seeded_db() -> Database {
+    let db = Database::open_in_memory().unwrap();
+    for (sku, name, aliases) in DRUGS {
+        let mut item = CatalogItem::new(sku.to_string(), name.to_string());
+        item.aliases = aliases.iter().map(|a| a.to_string()).collect();
+        item.species = vec!["canine".into(), "feline".into()];
+        db.upsert_catalog_item(&item).unwrap();
+    }
+    for i in 0..500 {
+        let name = format!("Compound {i} {}mg tablets", i % 50);
+        let item = CatalogItem::new(format!("FILL-{i}"), name);
+        db.upsert_catalog_item(&item).unwrap();
+    }
+    let rule = EscalationRule::new("buprenorphine", "dvm", "Controlled opioid");
+    db.upsert_escalation_rule(&rule).unwrap();
+    db
+}
+
+/// Forty mentions, cycling through the dictated drugs by name and alias.
+fn mentions() -> Vec<DrugMention> {
+    let names: Vec<&str> = DRUGS
+        .iter()
+        .flat_map(|(_, name, aliases)| {
+            std::iter::once(name.split(' ').next().unwrap()).chain(aliases.iter().copied())
+        })
+        .collect();
+    (0..40)
+        .map(|i| {
+            let name = names[i % names.len()];
+            DrugMention {
+                raw_text: format!("{name} 2 mg/kg"),
+                drug_name: name.to_lowercase(),
+                dose: Some(2.0),
+                unit: Some("mg/kg".into()),
+                route: None,
+                species: None,
+                start_offset: 0,
+                end_offset: 0,
+                field_spans: Default::default(),
+                extraction_confidence: None,
+                speaker: None,
+            }
+        })
+        .collect()
+}
+
+fn bench_resolve_all(c: &mut Criterion) {
+    let mentions = mentions();
+    let mut group = c.benchmark_group("resolve_all");
+    for (label, capacity) in [("uncached", 0), ("cached", STATEMENT_CACHE_CAPACITY)] {
+        let db = seeded_db();
+        db.conn().set_prepared_statement_cache_capacity(capacity);
+        let resolver = Resolver::new(&db);
+        group.bench_function(label, |b| {
+            b.iter(|| resolver.resolve_all(&mentions, Some("canine"), Some(25.0), None))
+        });
+    }
+    group.finish();
+}
+
+fn bench_draft_round_trip(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draft_round_trip");
+    for (label, capacity) in [("uncached", 0), ("cached", STATEMENT_CACHE_CAPACITY)] {
+        let db = seeded_db();
+        db.conn().set_prepared_statement_cache_capacity(capacity);
+        let patient = Patient::new("Max".into(), "canine".into());
+        db.insert_patient(&patient).unwrap();
+        group.bench_function(label, |b| {
+            b.iter_batched(
+                || EncounterDraft::new(patient.local_id.clone()),
+                |draft| {
+                    db.insert_draft(&draft).unwrap();
+                    let mut stored = db.get_draft(&draft.draft_id).unwrap().unwrap();
+                    stored.transcript = "Gave rimadyl 100 mg PO".into();
+                    db.update_draft(&stored).unwrap();
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_resolve_all, bench_draft_round_trip);
+criterion_main!(benches);
@@ -1,3 +1,109 @@
+//! Build-time FFI compatibility check.
+//!
+//! Compares the methods exported from `src/lib.rs` with the shipped surface
+//! in `ffi-api.txt` and writes `ffi_compat_report.txt` to `OUT_DIR`. The build
+//! fails if a shipped method disappears without a deprecation shim, or if a
+//! new method is exported without being added to `ffi-api.txt`.
+
+#[allow(dead_code)]
+#[path = "src/compat.rs"]
+mod compat;
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
 fn main() {
     // Using proc macros, no UDL scaffolding needed
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=src/compat.rs");
+    println!("cargo:rerun-if-changed=ffi-api.txt");
+
+    let lib = std::fs::read_to_string("src/lib.rs").expect("read src/lib.rs");
+    let shipped = std::fs::read_to_string("ffi-api.txt").expect("read ffi-api.txt");
+    let exported = exported_methods(&lib);
+    let shipped: BTreeSet<&str> = shipped
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    let mut report = String::new();
+    let mut errors = Vec::new();
+    let _ = writeln!(
+        report,
+        "FFI API version {} (supports bindings from {})",
+        compat::API_VERSION,
+        compat::MIN_SUPPORTED_API_VERSION
+    );
+    let _ = writeln!(report, "{} exported methods", exported.len());
+
+    for method in &shipped {
+        if exported.contains(*method) {
+            continue;
+        }
+        match compat::deprecation(method) {
+            Some(d) if d.removable_in() <= compat::API_VERSION => {
+                let _ = writeln!(report, "removed: {} (use {})", method, d.replacement);
+            }
+            _ => errors.push(format!(
+                "{} is in ffi-api.txt but no longer exported; keep a deprecation shim",
+                method
+            )),
+        }
+    }
+    for method in exported.iter().filter(|m| !shipped.contains(m.as_str())) {
+        errors.push(format!(
+            "{} is exported but missing from ffi-api.txt",
+            method
+        ));
+    }
+    for d in compat::DEPRECATIONS {
+        if !exported.contains(d.replacement) {
+            errors.push(format!(
+                "{} replaces {} but is not exported",
+                d.replacement, d.method
+            ));
+        }
+        if exported.contains(d.method) {
+            let _ = writeln!(
+                report,
+                "deprecated: {} -> {} (since {}, removable in {})",
+                d.method,
+                d.replacement,
+                d.deprecated_in,
+                d.removable_in()
+            );
+            if d.removable_in() <= compat::API_VERSION {
+                println!(
+                    "cargo:warning={} shim can be removed (deprecated in API {})",
+                    d.method, d.deprecated_in
+                );
+            }
+        }
+    }
+    for error in &errors {
+        let _ = writeln!(report, "ERROR: {}", error);
+    }
+
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR");
+    std::fs::write(Path::new(&out_dir).join("ffi_compat_report.txt"), &report)
+        .expect("write compatibility report");
+    if !errors.is_empty() {
+        panic!("FFI compatibility check failed:\n{}", errors.join("\n"));
+    }
+}
+
+/// Names of `pub fn`s in lib.rs outside the test module. Everything public
+/// there is exported; private helpers are plain `fn`.
+fn exported_methods(lib: &str) -> BTreeSet<String> {
+    let source = lib.split("#[cfg(test)]\nmod tests").next().unwrap_or(lib);
+    source
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("pub fn "))
+        .filter_map(|rest| {
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+            Some(rest[..end].to_string())
+        })
+        .collect()
 }
@@ -0,0 +1,83 @@
+//! FFI compatibility between the core and host app bindings.
+//!
+//! Swift and Kotlin apps ship on their own schedule, so an app can run
+//! against a newer core than the bindings it was built with. The API version
+//! is bumped whenever the exported surface changes; renamed methods keep a
+//! shim under the old name for at least one release so older bindings still
+//! work.
+//!
+//! This module is also compiled into `build.rs`, which checks the exported
+//! methods against `ffi-api.txt` and writes a compatibility report, so it must
+//! not depend on other crates.
+
+/// Version of the exported FFI surface.
+pub const API_VERSION: u32 = 3;
+
+/// Oldest binding API version this core still serves.
+pub const MIN_SUPPORTED_API_VERSION: u32 = 2;
+
+/// An exported method kept under an old name after a rename.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deprecation {
+    /// Old method name, still exported as a shim
+    pub method: &'static str,
+    /// Method to call instead
+    pub replacement: &'static str,
+    /// API version that deprecated the old name
+    pub deprecated_in: u32,
+}
+
+impl Deprecation {
+    /// First API version allowed to drop the shim (one release later).
+    pub fn removable_in(&self) -> u32 {
+        self.deprecated_in + 1
+    }
+}
+
+/// Renamed methods and their shims.
+pub const DEPRECATIONS: &[Deprecation] = &[Deprecation {
+    method: "get_pending_review_drafts",
+    replacement: "list_pending_review_drafts",
+    deprecated_in: 2,
+}];
+
+/// Deprecation entry for an old method name.
+pub fn deprecation(method: &str) -> Option<&'static Deprecation> {
+    DEPRECATIONS.iter().find(|d| d.method == method)
+}
+
+/// Whether bindings generated for `api_version` can call this core.
+pub fn is_supported(api_version: u32) -> bool {
+    (MIN_SUPPORTED_API_VERSION..=API_VERSION).contains(&api_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported_versions() {
+        assert!(is_supported(API_VERSION));
+        assert!(is_supported(MIN_SUPPORTED_API_VERSION));
+        assert!(!is_supported(0));
+        assert!(!is_supported(API_VERSION + 1));
+    }
+
+    #[test]
+    fn test_deprecations_outlive_one_release() {
+        for deprecation in DEPRECATIONS {
+            assert!(deprecation.deprecated_in <= API_VERSION);
+            assert_ne!(deprecation.method, deprecation.replacement);
+            // Bindings from before the rename are still supported, so the
+            // shim must still be exported
+            if deprecation.deprecated_in > MIN_SUPPORTED_API_VERSION {
+                assert!(deprecation.removable_in() > API_VERSION);
+            }
+        }
+        assert_eq!(
+            deprecation("get_pending_review_drafts").map(|d| d.replacement),
+            Some("list_pending_review_drafts")
+        );
+        assert!(deprecation("list_pending_review_drafts").is_none());
+    }
+}
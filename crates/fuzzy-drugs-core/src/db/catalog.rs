@@ -1,9 +1,18 @@
 //! Catalog database operations.
 
+use std::collections::HashMap;
+
 use rusqlite::{params, OptionalExtension};
 
 use super::{Database, DbError, DbResult};
-use crate::models::CatalogItem;
+use crate::models::{
+    CatalogItem, CatalogOrigin, CatalogSuggestion, CatalogUpsertFailure, CatalogUpsertReport,
+    ControlledSchedule, Pricing,
+};
+use crate::progress::Progress;
+
+/// Largest page accepted by [`Database::list_catalog_items_page`].
+pub const MAX_CATALOG_PAGE_SIZE: usize = 500;
 
 impl Database {
     /// Insert or update a catalog item.
@@ -11,18 +20,25 @@ impl Database {
         let aliases_json = serde_json::to_string(&item.aliases)?;
         let species_json = serde_json::to_string(&item.species)?;
         let routes_json = serde_json::to_string(&item.routes)?;
+        let components_json = serde_json::to_string(&item.components)?;
+        let withdrawal_times_json = serde_json::to_string(&item.withdrawal_times)?;
         let dose_range_json = item
             .dose_range
             .as_ref()
-            .map(|dr| serde_json::to_string(dr))
+            .map(serde_json::to_string)
             .transpose()?;
 
-        self.conn.execute(
+        let mut stmt = self.conn.prepare_cached(
             r#"
             INSERT INTO inventory_catalog (
                 sku, name, aliases, concentration, package_size,
-                species, routes, dose_range, active, server_id, last_synced, updated_at
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, datetime('now'))
+                species, routes, dose_range, active, server_id, last_synced,
+                components, controlled_schedule, unit_price, markup, minimum_charge,
+                tax_code, origin, dirty, withdrawal_times, updated_at
+            ) VALUES (
+                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                ?17, ?18, ?19, ?20, datetime('now')
+            )
             ON CONFLICT(sku) DO UPDATE SET
                 name = excluded.name,
                 aliases = excluded.aliases,
@@ -34,53 +50,108 @@ impl Database {
                 active = excluded.active,
                 server_id = excluded.server_id,
                 last_synced = excluded.last_synced,
+                components = excluded.components,
+                controlled_schedule = excluded.controlled_schedule,
+                unit_price = excluded.unit_price,
+                markup = excluded.markup,
+                minimum_charge = excluded.minimum_charge,
+                tax_code = excluded.tax_code,
+                origin = excluded.origin,
+                dirty = excluded.dirty,
+                withdrawal_times = excluded.withdrawal_times,
                 updated_at = datetime('now')
             "#,
-            params![
-                item.sku,
-                item.name,
-                aliases_json,
-                item.concentration,
-                item.package_size,
-                species_json,
-                routes_json,
-                dose_range_json,
-                item.active,
-                item.server_id,
-                item.last_synced,
-            ],
         )?;
+        stmt.execute(params![
+            item.sku,
+            item.name,
+            aliases_json,
+            item.concentration,
+            item.package_size,
+            species_json,
+            routes_json,
+            dose_range_json,
+            item.active,
+            item.server_id,
+            item.last_synced,
+            components_json,
+            item.controlled_schedule.map(|s| s.as_str()),
+            item.unit_price,
+            item.markup,
+            item.minimum_charge,
+            item.tax_code,
+            item.origin.as_str(),
+            item.dirty,
+            withdrawal_times_json,
+        ])?;
         Ok(())
     }
 
+    /// Upsert many catalog items in one transaction (the caller's, if one is
+    /// open), reporting progress after each.
+    ///
+    /// An item that fails validation or can't be written is skipped and
+    /// recorded in the report; the rest are written. Cancelling through
+    /// `progress` writes nothing.
+    pub fn upsert_catalog_items(
+        &self,
+        items: &[CatalogItem],
+        progress: &dyn Progress,
+    ) -> DbResult<CatalogUpsertReport> {
+        let tx = if self.conn.is_autocommit() {
+            Some(self.conn.unchecked_transaction()?)
+        } else {
+            None
+        };
+        let mut report = CatalogUpsertReport::default();
+        progress.step(0, items.len())?;
+        for (i, item) in items.iter().enumerate() {
+            let result = item
+                .validate()
+                .and_then(|()| self.upsert_catalog_item(item).map_err(|e| e.to_string()));
+            match result {
+                Ok(()) => report.upserted += 1,
+                Err(error) => report.failures.push(CatalogUpsertFailure {
+                    sku: item.sku.clone(),
+                    error,
+                }),
+            }
+            progress.step(i + 1, items.len())?;
+        }
+        if let Some(tx) = tx {
+            tx.commit()?;
+        }
+        Ok(report)
+    }
+
+    /// Upsert many catalog items all-or-nothing, reporting progress after
+    /// each. Nothing is written if any item fails (the error lists every
+    /// failed item) or `progress` cancels. Returns the number of items
+    /// imported.
+    pub fn import_catalog_items(
+        &self,
+        items: &[CatalogItem],
+        progress: &dyn Progress,
+    ) -> DbResult<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let report = self.upsert_catalog_items(items, progress)?;
+        if let Some(summary) = report.failure_summary() {
+            return Err(DbError::Constraint(summary));
+        }
+        tx.commit()?;
+        Ok(report.upserted)
+    }
+
     /// Get a catalog item by SKU.
     pub fn get_catalog_item(&self, sku: &str) -> DbResult<Option<CatalogItem>> {
+        let sql = format!(
+            "SELECT {} FROM inventory_catalog WHERE sku = ?",
+            CATALOG_COLUMNS
+        );
         let result = self
             .conn
-            .query_row(
-                r#"
-                SELECT sku, name, aliases, concentration, package_size,
-                       species, routes, dose_range, active, server_id, last_synced
-                FROM inventory_catalog
-                WHERE sku = ?
-                "#,
-                [sku],
-                |row| {
-                    Ok(CatalogItemRow {
-                        sku: row.get(0)?,
-                        name: row.get(1)?,
-                        aliases: row.get(2)?,
-                        concentration: row.get(3)?,
-                        package_size: row.get(4)?,
-                        species: row.get(5)?,
-                        routes: row.get(6)?,
-                        dose_range: row.get(7)?,
-                        active: row.get(8)?,
-                        server_id: row.get(9)?,
-                        last_synced: row.get(10)?,
-                    })
-                },
-            )
+            .prepare_cached(&sql)?
+            .query_row([sku], catalog_item_row)
             .optional()?;
 
         result.map(|row| row.try_into()).transpose()
@@ -91,11 +162,9 @@ impl Database {
         // Escape special FTS5 characters and add prefix matching
         let escaped_query = escape_fts_query(query);
 
-        let mut stmt = self.conn.prepare(
+        let sql = format!(
             r#"
-            SELECT c.sku, c.name, c.aliases, c.concentration, c.package_size,
-                   c.species, c.routes, c.dose_range, c.active, c.server_id, c.last_synced,
-                   bm25(inventory_catalog_fts) as rank
+            SELECT {}, bm25(inventory_catalog_fts) as rank
             FROM inventory_catalog c
             JOIN inventory_catalog_fts fts ON c.rowid = fts.rowid
             WHERE inventory_catalog_fts MATCH ?
@@ -103,23 +172,11 @@ impl Database {
             ORDER BY rank
             LIMIT ?
             "#,
-        )?;
+            CATALOG_COLUMNS_PREFIXED
+        );
 
-        let rows = stmt.query_map(params![escaped_query, limit as i64], |row| {
-            Ok(CatalogItemRow {
-                sku: row.get(0)?,
-                name: row.get(1)?,
-                aliases: row.get(2)?,
-                concentration: row.get(3)?,
-                package_size: row.get(4)?,
-                species: row.get(5)?,
-                routes: row.get(6)?,
-                dose_range: row.get(7)?,
-                active: row.get(8)?,
-                server_id: row.get(9)?,
-                last_synced: row.get(10)?,
-            })
-        })?;
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let rows = stmt.query_map(params![escaped_query, limit as i64], catalog_item_row)?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -128,42 +185,92 @@ impl Database {
         Ok(items)
     }
 
-    /// Get all active catalog items.
-    pub fn list_catalog_items(&self, active_only: bool) -> DbResult<Vec<CatalogItem>> {
-        let sql = if active_only {
-            r#"
-            SELECT sku, name, aliases, concentration, package_size,
-                   species, routes, dose_range, active, server_id, last_synced
-            FROM inventory_catalog
-            WHERE active = 1
-            ORDER BY name
-            "#
-        } else {
-            r#"
-            SELECT sku, name, aliases, concentration, package_size,
-                   species, routes, dose_range, active, server_id, last_synced
-            FROM inventory_catalog
-            ORDER BY name
-            "#
-        };
+    /// Unit price, markup, minimum charge, and tax code for the given SKUs.
+    ///
+    /// SKUs without a price or minimum charge (or not in the catalog) are
+    /// left out.
+    pub fn catalog_pricing(&self, skus: &[&str]) -> DbResult<HashMap<String, Pricing>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT unit_price, markup, minimum_charge, tax_code \
+             FROM inventory_catalog WHERE sku = ?",
+        )?;
 
-        let mut stmt = self.conn.prepare(sql)?;
-        let rows = stmt.query_map([], |row| {
-            Ok(CatalogItemRow {
+        let mut prices = HashMap::new();
+        for sku in skus {
+            let pricing = stmt
+                .query_row([sku], |row| {
+                    Ok(Pricing {
+                        unit_price: row.get(0)?,
+                        markup: row.get(1)?,
+                        minimum_charge: row.get(2)?,
+                        tax_code: row.get(3)?,
+                    })
+                })
+                .optional()?;
+            if let Some(pricing) = pricing {
+                if pricing.unit_price.is_some() || pricing.minimum_charge.is_some() {
+                    prices.insert(sku.to_string(), pricing);
+                }
+            }
+        }
+        Ok(prices)
+    }
+
+    /// Type-ahead suggestions for a manual picker.
+    ///
+    /// Built for per-keystroke calls: only sku/name/strength are read (no JSON
+    /// columns), names starting with the prefix come first via the NOCASE name
+    /// index, then word-prefix matches on names/aliases/SKUs by BM25.
+    pub fn suggest_catalog(&self, prefix: &str, limit: usize) -> DbResult<Vec<CatalogSuggestion>> {
+        let fts_query = escape_fts_query(prefix);
+        if fts_query.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        let like_pattern = format!("{}%", escape_like(prefix.trim()));
+
+        let mut stmt = self.conn.prepare_cached(
+            r#"
+            SELECT sku, name, concentration FROM (
+                SELECT sku, name, concentration, 0 AS tier, 0.0 AS rank
+                FROM inventory_catalog
+                WHERE name LIKE ?1 ESCAPE '\' AND active = 1
+                UNION ALL
+                SELECT c.sku, c.name, c.concentration, 1 AS tier, bm25(inventory_catalog_fts) AS rank
+                FROM inventory_catalog c
+                JOIN inventory_catalog_fts fts ON c.rowid = fts.rowid
+                WHERE inventory_catalog_fts MATCH ?2 AND c.active = 1
+            )
+            GROUP BY sku
+            ORDER BY MIN(tier), MIN(rank), length(name), name
+            LIMIT ?3
+            "#,
+        )?;
+        let rows = stmt.query_map(params![like_pattern, fts_query, limit as i64], |row| {
+            Ok(CatalogSuggestion {
                 sku: row.get(0)?,
                 name: row.get(1)?,
-                aliases: row.get(2)?,
-                concentration: row.get(3)?,
-                package_size: row.get(4)?,
-                species: row.get(5)?,
-                routes: row.get(6)?,
-                dose_range: row.get(7)?,
-                active: row.get(8)?,
-                server_id: row.get(9)?,
-                last_synced: row.get(10)?,
+                strength: row.get(2)?,
             })
         })?;
 
+        let mut suggestions = Vec::new();
+        for row in rows {
+            suggestions.push(row?);
+        }
+        Ok(suggestions)
+    }
+
+    /// Get all active catalog items.
+    pub fn list_catalog_items(&self, active_only: bool) -> DbResult<Vec<CatalogItem>> {
+        let filter = if active_only { "WHERE active = 1" } else { "" };
+        let sql = format!(
+            "SELECT {} FROM inventory_catalog {} ORDER BY name",
+            CATALOG_COLUMNS, filter
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map([], catalog_item_row)?;
+
         let mut items = Vec::new();
         for row in rows {
             items.push(row?.try_into()?);
@@ -171,8 +278,68 @@ impl Database {
         Ok(items)
     }
 
+    /// One page of catalog items ordered by name, plus the total count.
+    pub fn list_catalog_items_page(
+        &self,
+        active_only: bool,
+        limit: usize,
+        offset: usize,
+    ) -> DbResult<(Vec<CatalogItem>, usize)> {
+        let filter = if active_only { "WHERE active = 1" } else { "" };
+        let total: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM inventory_catalog {}", filter),
+            [],
+            |row| row.get(0),
+        )?;
+        let sql = format!(
+            "SELECT {} FROM inventory_catalog {} ORDER BY name, sku LIMIT ? OFFSET ?",
+            CATALOG_COLUMNS, filter
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![limit as i64, offset as i64], catalog_item_row)?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            items.push(row?.try_into()?);
+        }
+        Ok((items, total as usize))
+    }
+
+    /// Number of committed encounters with a line item for `sku`.
+    pub fn count_committed_encounters_for_sku(&self, sku: &str) -> DbResult<usize> {
+        let count: i64 = self.conn.query_row(
+            r#"
+            SELECT COUNT(DISTINCT n.hash)
+            FROM merkle_nodes n,
+                 json_each(
+                     CASE WHEN json_valid(n.payload) THEN n.payload ELSE '{}' END,
+                     '$.line_items'
+                 ) li
+            WHERE n.node_type = 'leaf' AND json_extract(li.value, '$.sku') = ?
+            "#,
+            [sku],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
     /// Delete a catalog item.
+    ///
+    /// Fails with [`DbError::InUse`] if committed encounters bill the SKU;
+    /// deactivate it instead so their line items still resolve.
     pub fn delete_catalog_item(&self, sku: &str) -> DbResult<bool> {
+        let encounters = self.count_committed_encounters_for_sku(sku)?;
+        if encounters > 0 {
+            return Err(DbError::InUse {
+                subject_type: "catalog_item".into(),
+                subject_id: sku.to_string(),
+                message: format!(
+                    "Catalog item {} is referenced by {} committed encounter(s); deactivate it instead",
+                    sku, encounters
+                ),
+            });
+        }
         let rows_affected = self
             .conn
             .execute("DELETE FROM inventory_catalog WHERE sku = ?", [sku])?;
@@ -187,6 +354,89 @@ impl Database {
         )?;
         Ok(rows_affected > 0)
     }
+
+    /// Flag an item as changed locally, to go out in the next catalog push.
+    pub fn mark_catalog_item_dirty(&self, sku: &str) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            "UPDATE inventory_catalog SET dirty = 1, updated_at = datetime('now') WHERE sku = ?",
+            [sku],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Items changed locally since the last catalog push, by SKU.
+    pub fn list_dirty_catalog_items(&self) -> DbResult<Vec<CatalogItem>> {
+        let sql = format!(
+            "SELECT {} FROM inventory_catalog WHERE dirty = 1 ORDER BY sku",
+            CATALOG_COLUMNS
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map([], catalog_item_row)?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            items.push(row?.try_into()?);
+        }
+        Ok(items)
+    }
+
+    /// Record that the PIMS accepted a pushed item: clear its dirty flag and
+    /// link it to `server_id` (keeping the current link if `None`).
+    pub fn mark_catalog_item_pushed(
+        &self,
+        sku: &str,
+        server_id: Option<&str>,
+        pushed_at: &str,
+    ) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            r#"
+            UPDATE inventory_catalog
+            SET dirty = 0, server_id = COALESCE(?2, server_id), last_synced = ?3
+            WHERE sku = ?1
+            "#,
+            params![sku, server_id, pushed_at],
+        )?;
+        Ok(rows_affected > 0)
+    }
+}
+
+/// Columns selected for a catalog item, in [`catalog_item_row`] order.
+const CATALOG_COLUMNS: &str = "sku, name, aliases, concentration, package_size, \
+    species, routes, dose_range, active, server_id, last_synced, components, \
+    controlled_schedule, unit_price, markup, minimum_charge, tax_code, origin, dirty, \
+    withdrawal_times";
+
+/// [`CATALOG_COLUMNS`] qualified with the `c` table alias (for FTS joins).
+const CATALOG_COLUMNS_PREFIXED: &str = "c.sku, c.name, c.aliases, c.concentration, \
+    c.package_size, c.species, c.routes, c.dose_range, c.active, c.server_id, \
+    c.last_synced, c.components, c.controlled_schedule, c.unit_price, c.markup, \
+    c.minimum_charge, c.tax_code, c.origin, c.dirty, c.withdrawal_times";
+
+/// Map a row selected with [`CATALOG_COLUMNS`].
+fn catalog_item_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<CatalogItemRow> {
+    Ok(CatalogItemRow {
+        sku: row.get(0)?,
+        name: row.get(1)?,
+        aliases: row.get(2)?,
+        concentration: row.get(3)?,
+        package_size: row.get(4)?,
+        species: row.get(5)?,
+        routes: row.get(6)?,
+        dose_range: row.get(7)?,
+        active: row.get(8)?,
+        server_id: row.get(9)?,
+        last_synced: row.get(10)?,
+        components: row.get(11)?,
+        controlled_schedule: row.get(12)?,
+        unit_price: row.get(13)?,
+        markup: row.get(14)?,
+        minimum_charge: row.get(15)?,
+        tax_code: row.get(16)?,
+        origin: row.get(17)?,
+        dirty: row.get(18)?,
+        withdrawal_times: row.get(19)?,
+    })
 }
 
 /// Intermediate row struct for database mapping.
@@ -202,6 +452,15 @@ struct CatalogItemRow {
     active: bool,
     server_id: Option<String>,
     last_synced: Option<String>,
+    components: String,
+    controlled_schedule: Option<String>,
+    unit_price: Option<f64>,
+    markup: Option<f64>,
+    minimum_charge: Option<f64>,
+    tax_code: Option<String>,
+    origin: String,
+    dirty: bool,
+    withdrawal_times: String,
 }
 
 impl TryFrom<CatalogItemRow> for CatalogItem {
@@ -220,19 +479,38 @@ impl TryFrom<CatalogItemRow> for CatalogItem {
                 .dose_range
                 .map(|s| serde_json::from_str(&s))
                 .transpose()?,
+            withdrawal_times: serde_json::from_str(&row.withdrawal_times)?,
             active: row.active,
             server_id: row.server_id,
             last_synced: row.last_synced,
+            components: serde_json::from_str(&row.components)?,
+            controlled_schedule: row
+                .controlled_schedule
+                .map(|s| {
+                    ControlledSchedule::parse(&s).ok_or_else(|| {
+                        DbError::Constraint(format!("Unknown controlled schedule: {}", s))
+                    })
+                })
+                .transpose()?,
+            unit_price: row.unit_price,
+            markup: row.markup,
+            minimum_charge: row.minimum_charge,
+            tax_code: row.tax_code,
+            origin: CatalogOrigin::parse(&row.origin).ok_or_else(|| {
+                DbError::Constraint(format!("Unknown catalog origin: {}", row.origin))
+            })?,
+            dirty: row.dirty,
         })
     }
 }
 
 /// Escape special FTS5 characters and prepare query for prefix matching.
-fn escape_fts_query(query: &str) -> String {
-    // Remove special FTS5 operators and add wildcard for prefix matching
+pub fn escape_fts_query(query: &str) -> String {
+    // Replace special FTS5 operators and separators with spaces, so
+    // "amoxicillin-clavulanate" searches both tokens like the FTS tokenizer indexed them
     let cleaned: String = query
         .chars()
-        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
         .collect();
 
     // Add prefix matching operator
@@ -243,10 +521,17 @@ fn escape_fts_query(query: &str) -> String {
         .join(" ")
 }
 
+/// Escape LIKE wildcards so user input matches literally (escape char `\`).
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::DoseRange;
+    use crate::models::{DoseRange, DoseUnit};
 
     fn setup_db() -> Database {
         Database::open_in_memory().unwrap()
@@ -311,6 +596,43 @@ mod tests {
         assert_eq!(results.len(), 1);
     }
 
+    #[test]
+    fn test_search_by_component() {
+        let db = setup_db();
+
+        let mut item = CatalogItem::new("CLAV-250".into(), "Clavamox 250mg tablets".into());
+        item.components = vec!["amoxicillin".into(), "clavulanate".into()];
+        db.upsert_catalog_item(&item).unwrap();
+
+        let retrieved = db.get_catalog_item("CLAV-250").unwrap().unwrap();
+        assert_eq!(retrieved.components, vec!["amoxicillin", "clavulanate"]);
+
+        // Either component, or the hyphenated combination, finds the product
+        assert_eq!(db.search_catalog("clavulanate", 10).unwrap().len(), 1);
+        assert_eq!(
+            db.search_catalog("amoxicillin-clavulanate", 10)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_controlled_schedule_roundtrip() {
+        let db = setup_db();
+
+        let mut item = CatalogItem::new("BUP-03".into(), "Buprenorphine 0.3mg/mL".into());
+        item.controlled_schedule = Some(ControlledSchedule::CIII);
+        db.upsert_catalog_item(&item).unwrap();
+
+        let retrieved = db.get_catalog_item("BUP-03").unwrap().unwrap();
+        assert_eq!(
+            retrieved.controlled_schedule,
+            Some(ControlledSchedule::CIII)
+        );
+        assert!(retrieved.is_controlled());
+    }
+
     #[test]
     fn test_deactivate() {
         let db = setup_db();
@@ -329,6 +651,57 @@ mod tests {
         assert!(!item.active);
     }
 
+    #[test]
+    fn test_list_page() {
+        let db = setup_db();
+
+        for (sku, name) in [
+            ("C", "Cefazolin"),
+            ("A", "Acepromazine"),
+            ("B", "Buprenorphine"),
+        ] {
+            db.upsert_catalog_item(&CatalogItem::new(sku.into(), name.into()))
+                .unwrap();
+        }
+        db.deactivate_catalog_item("B").unwrap();
+
+        let (page, total) = db.list_catalog_items_page(false, 2, 0).unwrap();
+        assert_eq!(total, 3);
+        let skus: Vec<_> = page.iter().map(|i| i.sku.as_str()).collect();
+        assert_eq!(skus, vec!["A", "B"]);
+
+        let (page, total) = db.list_catalog_items_page(true, 2, 0).unwrap();
+        assert_eq!(total, 2);
+        let skus: Vec<_> = page.iter().map(|i| i.sku.as_str()).collect();
+        assert_eq!(skus, vec!["A", "C"]);
+        assert!(db.list_catalog_items_page(true, 2, 2).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn test_delete_refused_when_committed() {
+        let db = setup_db();
+
+        db.upsert_catalog_item(&CatalogItem::new("LRS-1L".into(), "LRS 1L".into()))
+            .unwrap();
+        db.upsert_catalog_item(&CatalogItem::new("UNUSED".into(), "Unused".into()))
+            .unwrap();
+        db.insert_merkle_leaf("not-json", "payload").unwrap();
+        db.insert_merkle_leaf(
+            "enc1",
+            r#"{"draft_id":"d1","line_items":[{"sku":"LRS-1L"},{"sku":"LRS-1L"}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(db.count_committed_encounters_for_sku("LRS-1L").unwrap(), 1);
+        assert!(matches!(
+            db.delete_catalog_item("LRS-1L"),
+            Err(DbError::InUse { .. })
+        ));
+        assert!(db.get_catalog_item("LRS-1L").unwrap().is_some());
+        assert!(db.delete_catalog_item("UNUSED").unwrap());
+        assert!(db.get_catalog_item("UNUSED").unwrap().is_none());
+    }
+
     #[test]
     fn test_dose_range_persistence() {
         let db = setup_db();
@@ -345,6 +718,156 @@ mod tests {
         let range = retrieved.dose_range.unwrap();
         assert_eq!(range.min_dose_per_kg, 1.0);
         assert_eq!(range.max_dose_per_kg, 5.0);
-        assert_eq!(range.unit, "mg");
+        assert_eq!(range.unit.amount, DoseUnit::Milligrams);
+    }
+
+    #[test]
+    fn test_catalog_pricing() {
+        let db = setup_db();
+
+        let mut priced = CatalogItem::new("CARP-100".into(), "Carprofen 100mg tablets".into());
+        priced.unit_price = Some(1.25);
+        priced.markup = Some(0.2);
+        priced.tax_code = Some("RETAIL".into());
+        db.upsert_catalog_item(&priced).unwrap();
+        let mut minimum = CatalogItem::new("RX-FEE".into(), "Dispensing fee".into());
+        minimum.minimum_charge = Some(12.0);
+        db.upsert_catalog_item(&minimum).unwrap();
+        db.upsert_catalog_item(&CatalogItem::new("GAUZE".into(), "Gauze".into()))
+            .unwrap();
+
+        let prices = db
+            .catalog_pricing(&["CARP-100", "RX-FEE", "GAUZE", "MISSING"])
+            .unwrap();
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices["CARP-100"], priced.pricing());
+        assert_eq!(prices["RX-FEE"].minimum_charge, Some(12.0));
+        assert_eq!(
+            db.get_catalog_item("CARP-100").unwrap().unwrap().markup,
+            Some(0.2)
+        );
+    }
+
+    #[test]
+    fn test_suggest_catalog() {
+        let db = setup_db();
+
+        let mut carp = CatalogItem::new("CARP-100".into(), "Carprofen 100mg tablets".into());
+        carp.aliases = vec!["rimadyl".into()];
+        carp.concentration = Some("100mg".into());
+        db.upsert_catalog_item(&carp).unwrap();
+        let mut carb = CatalogItem::new("CARB-1".into(), "Carbimazole 5mg".into());
+        carb.concentration = Some("5mg".into());
+        db.upsert_catalog_item(&carb).unwrap();
+        let mut inactive = CatalogItem::new("CARD-1".into(), "Cardalis".into());
+        inactive.active = false;
+        db.upsert_catalog_item(&inactive).unwrap();
+
+        let names: Vec<String> = db
+            .suggest_catalog("car", 10)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec!["Carbimazole 5mg", "Carprofen 100mg tablets"]);
+
+        // Alias prefix
+        let suggestions = db.suggest_catalog("rima", 10).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].sku, "CARP-100");
+        assert_eq!(suggestions[0].strength.as_deref(), Some("100mg"));
+
+        assert_eq!(db.suggest_catalog("car", 1).unwrap().len(), 1);
+        assert!(db.suggest_catalog("  ", 10).unwrap().is_empty());
+        assert!(db.suggest_catalog("%", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_import_rolls_back_on_cancel() {
+        use crate::progress::Cancelled;
+        use std::cell::RefCell;
+
+        let db = setup_db();
+        let items: Vec<_> = (0..3)
+            .map(|i| CatalogItem::new(format!("SKU{}", i), format!("Item {}", i)))
+            .collect();
+
+        let steps = RefCell::new(Vec::new());
+        let cancel_after_two = |processed: usize, total: usize| {
+            steps.borrow_mut().push((processed, total));
+            if processed < 2 {
+                Ok(())
+            } else {
+                Err(Cancelled)
+            }
+        };
+        let result = db.import_catalog_items(&items, &cancel_after_two);
+        assert!(matches!(result, Err(DbError::Cancelled(_))));
+        assert_eq!(*steps.borrow(), vec![(0, 3), (1, 3), (2, 3)]);
+        assert!(db.get_catalog_item("SKU0").unwrap().is_none());
+
+        assert_eq!(db.import_catalog_items(&items, &()).unwrap(), 3);
+        assert!(db.get_catalog_item("SKU2").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_bulk_upsert_collects_failures() {
+        let db = setup_db();
+        let mut items: Vec<_> = (0..4)
+            .map(|i| CatalogItem::new(format!("BULK{}", i), format!("Bulk {}", i)))
+            .collect();
+        items[1].name = " ".into();
+        items[3].sku = String::new();
+
+        let report = db.upsert_catalog_items(&items, &()).unwrap();
+        assert_eq!(report.upserted, 2);
+        let failed: Vec<&str> = report.failures.iter().map(|f| f.sku.as_str()).collect();
+        assert_eq!(failed, ["BULK1", ""]);
+        assert!(db.get_catalog_item("BULK2").unwrap().is_some());
+        assert!(db.get_catalog_item("BULK1").unwrap().is_none());
+
+        // All-or-nothing imports name every failure
+        items[0].sku = "BULK9".into();
+        let err = db
+            .import_catalog_items(&items, &())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("2 catalog item(s) failed"), "{}", err);
+        assert!(db.get_catalog_item("BULK9").unwrap().is_none());
+
+        // Inside a caller's transaction, the caller decides
+        let tx = db.conn().unchecked_transaction().unwrap();
+        let report = db.upsert_catalog_items(&items[..1], &()).unwrap();
+        assert_eq!(report.upserted, 1);
+        drop(tx);
+        assert!(db.get_catalog_item("BULK9").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_dirty_tracking() {
+        let db = setup_db();
+        let mut item = CatalogItem::new("HOUSE-1".into(), "Compounded Gabapentin".into());
+        item.origin = CatalogOrigin::Local;
+        item.dirty = true;
+        db.upsert_catalog_item(&item).unwrap();
+        db.upsert_catalog_item(&CatalogItem::new("SKU001".into(), "Carprofen".into()))
+            .unwrap();
+
+        let dirty = db.list_dirty_catalog_items().unwrap();
+        assert_eq!(dirty.len(), 1);
+        assert_eq!(dirty[0].origin, CatalogOrigin::Local);
+
+        assert!(db.mark_catalog_item_dirty("SKU001").unwrap());
+        assert!(!db.mark_catalog_item_dirty("MISSING").unwrap());
+        assert_eq!(db.list_dirty_catalog_items().unwrap().len(), 2);
+
+        assert!(db
+            .mark_catalog_item_pushed("HOUSE-1", Some("srv-9"), "2024-01-15T12:00:00Z")
+            .unwrap());
+        let pushed = db.get_catalog_item("HOUSE-1").unwrap().unwrap();
+        assert!(!pushed.dirty);
+        assert_eq!(pushed.server_id.as_deref(), Some("srv-9"));
+        assert_eq!(pushed.origin, CatalogOrigin::Local);
+        assert_eq!(db.list_dirty_catalog_items().unwrap().len(), 1);
     }
 }
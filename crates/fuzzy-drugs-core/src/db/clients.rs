@@ -0,0 +1,155 @@
+//! Clients (owners) and the patients linked to them.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbResult};
+use crate::models::Client;
+
+impl Database {
+    /// Insert or update a client.
+    pub fn upsert_client(&self, client: &Client) -> DbResult<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO clients (
+                client_id, server_id, name, phone, email, address, notes,
+                created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, datetime('now'))
+            ON CONFLICT(client_id) DO UPDATE SET
+                server_id = excluded.server_id,
+                name = excluded.name,
+                phone = excluded.phone,
+                email = excluded.email,
+                address = excluded.address,
+                notes = excluded.notes,
+                updated_at = datetime('now')
+            "#,
+            params![
+                client.client_id,
+                client.server_id,
+                client.name,
+                client.phone,
+                client.email,
+                client.address,
+                client.notes,
+                client.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get a client by ID.
+    pub fn get_client(&self, client_id: &str) -> DbResult<Option<Client>> {
+        let sql = format!("SELECT {} FROM clients WHERE client_id = ?", CLIENT_COLUMNS);
+        Ok(self
+            .conn
+            .query_row(&sql, [client_id], client_row)
+            .optional()?)
+    }
+
+    /// Clients whose name starts with `query` (ignoring case), by name. An
+    /// empty query lists every client.
+    pub fn search_clients(&self, query: &str, limit: usize) -> DbResult<Vec<Client>> {
+        let sql = format!(
+            "SELECT {} FROM clients WHERE name LIKE ? ORDER BY name COLLATE NOCASE LIMIT ?",
+            CLIENT_COLUMNS
+        );
+        let pattern = format!("{}%", query.trim());
+        let mut stmt = self.conn.prepare(&sql)?;
+        let clients = stmt
+            .query_map(params![pattern, limit as i64], client_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(clients)
+    }
+
+    /// Delete a client. Their patients are kept, unlinked.
+    pub fn delete_client(&self, client_id: &str) -> DbResult<bool> {
+        let rows_affected = self
+            .conn
+            .execute("DELETE FROM clients WHERE client_id = ?", [client_id])?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Link a patient to a client, or unlink it with `None`. Returns false
+    /// if the patient doesn't exist.
+    pub fn set_patient_client(&self, local_id: &str, client_id: Option<&str>) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            "UPDATE patients SET client_id = ?2, updated_at = datetime('now') WHERE local_id = ?1",
+            params![local_id, client_id],
+        )?;
+        Ok(rows_affected > 0)
+    }
+}
+
+/// Columns selected for a client, in [`client_row`] order.
+const CLIENT_COLUMNS: &str =
+    "client_id, server_id, name, phone, email, address, notes, created_at, updated_at";
+
+/// Map a row selected with [`CLIENT_COLUMNS`].
+fn client_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Client> {
+    Ok(Client {
+        client_id: row.get(0)?,
+        server_id: row.get(1)?,
+        name: row.get(2)?,
+        phone: row.get(3)?,
+        email: row.get(4)?,
+        address: row.get(5)?,
+        notes: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Patient;
+
+    #[test]
+    fn test_clients_and_patient_links() {
+        let db = Database::open_in_memory().unwrap();
+        let mut client = Client::new("Jane Doe".into());
+        client.phone = Some("555-0100".into());
+        db.upsert_client(&client).unwrap();
+        db.upsert_client(&Client::new("John Roe".into())).unwrap();
+
+        client.email = Some("jane@example.com".into());
+        db.upsert_client(&client).unwrap();
+        assert_eq!(
+            db.get_client(&client.client_id).unwrap().unwrap().email,
+            client.email
+        );
+        let found = db.search_clients("ja", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(db.search_clients("", 10).unwrap().len(), 2);
+
+        let max = Patient::new("Max".into(), "canine".into());
+        let bella = Patient::new("Bella".into(), "feline".into());
+        db.insert_patient(&max).unwrap();
+        db.insert_patient(&bella).unwrap();
+        assert!(db
+            .set_patient_client(&max.local_id, Some(&client.client_id))
+            .unwrap());
+        assert!(db
+            .set_patient_client(&bella.local_id, Some(&client.client_id))
+            .unwrap());
+        assert!(!db
+            .set_patient_client("missing", Some(&client.client_id))
+            .unwrap());
+        assert!(db
+            .set_patient_client(&max.local_id, Some("no-such-client"))
+            .is_err());
+
+        let names: Vec<String> = db
+            .list_client_patients(&client.client_id)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, ["Bella", "Max"]);
+
+        // Deleting the client keeps its patients, unlinked
+        assert!(db.delete_client(&client.client_id).unwrap());
+        let max = db.get_patient(&max.local_id).unwrap().unwrap();
+        assert_eq!(max.client_id, None);
+    }
+}
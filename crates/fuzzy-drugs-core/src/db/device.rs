@@ -0,0 +1,174 @@
+//! Device identity and provisioning.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbError, DbResult};
+use crate::models::{DeviceIdentity, KeyFingerprint, DEFAULT_DEVICE_NAME};
+
+const DEVICE_COLUMNS: &str = "device_id, device_name, provisioned_at, key_fingerprints";
+
+impl Database {
+    /// Provision this installation's identity if it has none yet.
+    ///
+    /// Called when the database is opened; the identity is created once and
+    /// never replaced.
+    pub(super) fn ensure_device_identity(&self) -> DbResult<()> {
+        let identity = DeviceIdentity::new(DEFAULT_DEVICE_NAME.to_string());
+        self.conn.execute(
+            r#"
+            INSERT OR IGNORE INTO device_identity (id, device_id, device_name, provisioned_at)
+            VALUES (1, ?1, ?2, ?3)
+            "#,
+            params![
+                identity.device_id,
+                identity.device_name,
+                identity.provisioned_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get this installation's identity.
+    pub fn device_identity(&self) -> DbResult<DeviceIdentity> {
+        let sql = format!(
+            "SELECT {} FROM device_identity WHERE id = 1",
+            DEVICE_COLUMNS
+        );
+        self.conn
+            .query_row(&sql, [], |row| {
+                Ok(DeviceRow {
+                    device_id: row.get(0)?,
+                    device_name: row.get(1)?,
+                    provisioned_at: row.get(2)?,
+                    key_fingerprints: row.get(3)?,
+                })
+            })
+            .optional()?
+            .ok_or_else(|| DbError::NotFound("Device identity".into()))?
+            .try_into()
+    }
+
+    /// This installation's device ID (stamped into leaves and exports).
+    pub fn device_id(&self) -> DbResult<String> {
+        Ok(self.conn.query_row(
+            "SELECT device_id FROM device_identity WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Rename this device.
+    pub fn set_device_name(&self, name: &str) -> DbResult<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(DbError::Constraint("Device name cannot be empty".into()));
+        }
+        self.conn.execute(
+            "UPDATE device_identity SET device_name = ?, updated_at = datetime('now') WHERE id = 1",
+            [name],
+        )?;
+        Ok(())
+    }
+
+    /// Register a key fingerprint, replacing any earlier one for the purpose.
+    pub fn set_key_fingerprint(
+        &self,
+        purpose: &str,
+        fingerprint: &str,
+    ) -> DbResult<DeviceIdentity> {
+        let mut identity = self.device_identity()?;
+        identity
+            .key_fingerprints
+            .retain(|k| !k.purpose.eq_ignore_ascii_case(purpose));
+        identity.key_fingerprints.push(KeyFingerprint {
+            purpose: purpose.to_string(),
+            fingerprint: fingerprint.to_string(),
+            added_at: chrono::Utc::now().to_rfc3339(),
+        });
+        self.write_key_fingerprints(&identity.key_fingerprints)?;
+        Ok(identity)
+    }
+
+    /// Remove the key fingerprint for a purpose. Returns `false` if none was set.
+    pub fn remove_key_fingerprint(&self, purpose: &str) -> DbResult<bool> {
+        let mut identity = self.device_identity()?;
+        let before = identity.key_fingerprints.len();
+        identity
+            .key_fingerprints
+            .retain(|k| !k.purpose.eq_ignore_ascii_case(purpose));
+        if identity.key_fingerprints.len() == before {
+            return Ok(false);
+        }
+        self.write_key_fingerprints(&identity.key_fingerprints)?;
+        Ok(true)
+    }
+
+    fn write_key_fingerprints(&self, fingerprints: &[KeyFingerprint]) -> DbResult<()> {
+        let json = serde_json::to_string(fingerprints)?;
+        self.conn.execute(
+            "UPDATE device_identity SET key_fingerprints = ?, updated_at = datetime('now') WHERE id = 1",
+            [json],
+        )?;
+        Ok(())
+    }
+}
+
+/// Raw row from the device_identity table.
+struct DeviceRow {
+    device_id: String,
+    device_name: String,
+    provisioned_at: String,
+    key_fingerprints: String,
+}
+
+impl TryFrom<DeviceRow> for DeviceIdentity {
+    type Error = DbError;
+
+    fn try_from(row: DeviceRow) -> Result<Self, Self::Error> {
+        Ok(DeviceIdentity {
+            device_id: row.device_id,
+            device_name: row.device_name,
+            provisioned_at: row.provisioned_at,
+            key_fingerprints: serde_json::from_str(&row.key_fingerprints)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provisioned_on_open() {
+        let db = Database::open_in_memory().unwrap();
+        let identity = db.device_identity().unwrap();
+        assert_eq!(identity.device_name, DEFAULT_DEVICE_NAME);
+        assert_eq!(db.device_id().unwrap(), identity.device_id);
+    }
+
+    #[test]
+    fn test_identity_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fuzzy-drugs.sqlite");
+        let first = Database::open(&path).unwrap().device_identity().unwrap();
+        let second = Database::open(&path).unwrap().device_identity().unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_name_and_fingerprints() {
+        let db = Database::open_in_memory().unwrap();
+        db.set_device_name("Exam Room 2 iPad").unwrap();
+        assert!(db.set_device_name("  ").is_err());
+
+        db.set_key_fingerprint("signing", "ab12").unwrap();
+        let identity = db.set_key_fingerprint("signing", "cd34").unwrap();
+        assert_eq!(identity.key_fingerprints.len(), 1);
+        assert_eq!(identity.fingerprint("signing").unwrap().fingerprint, "cd34");
+
+        let identity = db.device_identity().unwrap();
+        assert_eq!(identity.device_name, "Exam Room 2 iPad");
+        assert!(db.remove_key_fingerprint("signing").unwrap());
+        assert!(!db.remove_key_fingerprint("signing").unwrap());
+    }
+}
@@ -2,122 +2,180 @@
 
 use rusqlite::{params, OptionalExtension};
 
+use super::legal_holds::hold_error;
 use super::{Database, DbError, DbResult};
-use crate::models::{DraftStatus, EncounterDraft, ResolvedItem};
+use crate::models::{
+    DraftStatus, EncounterDraft, EncounterLineItem, PendingCommit, ResolvedItem, ReviewQueueOrder,
+};
 
 impl Database {
     /// Insert a new encounter draft.
     pub fn insert_draft(&self, draft: &EncounterDraft) -> DbResult<()> {
+        self.check_transcript_limits(&draft.transcript)?;
         let resolved_items_json = serde_json::to_string(&draft.resolved_items)?;
+        let manual_items_json = serde_json::to_string(&draft.manual_items)?;
+        let interaction_warnings_json = serde_json::to_string(&draft.interaction_warnings)?;
+        let reported_medications_json = serde_json::to_string(&draft.reported_medications)?;
+        let service_items_json = serde_json::to_string(&draft.service_items)?;
         let status_str = status_to_string(&draft.status);
-
-        self.conn.execute(
+        let inline_transcript = if self.limits.should_chunk_transcript(&draft.transcript) {
+            ""
+        } else {
+            draft.transcript.as_str()
+        };
+
+        // The row and its transcript chunks are written together; inside
+        // an outer transaction (e.g. a commit) that one covers both.
+        let tx = if self.conn.is_autocommit() {
+            Some(self.conn.unchecked_transaction()?)
+        } else {
+            None
+        };
+        let mut stmt = self.conn.prepare_cached(
             r#"
             INSERT INTO encounter_drafts (
                 draft_id, patient_id, transcript, resolved_items,
-                status, created_at, updated_at
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                status, created_at, updated_at, manual_items, interaction_warnings,
+                reported_medications, service_items, visit_id
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
             "#,
-            params![
-                draft.draft_id,
-                draft.patient_id,
-                draft.transcript,
-                resolved_items_json,
-                status_str,
-                draft.created_at,
-                draft.updated_at,
-            ],
         )?;
+        stmt.execute(params![
+            draft.draft_id,
+            draft.patient_id,
+            inline_transcript,
+            resolved_items_json,
+            status_str,
+            draft.created_at,
+            draft.updated_at,
+            manual_items_json,
+            interaction_warnings_json,
+            reported_medications_json,
+            service_items_json,
+            draft.visit_id,
+        ])?;
+        self.store_transcript_chunks(&draft.draft_id, &draft.transcript)?;
+        if let Some(tx) = tx {
+            tx.commit()?;
+        }
         Ok(())
     }
 
     /// Update an existing draft.
+    ///
+    /// The transcript of a draft under legal hold cannot be changed.
     pub fn update_draft(&self, draft: &EncounterDraft) -> DbResult<bool> {
+        self.check_transcript_limits(&draft.transcript)?;
+        if let Some(hold) = self.encounter_legal_hold(&draft.draft_id, &draft.patient_id)? {
+            if let Some(stored) = self.get_draft(&draft.draft_id)? {
+                if stored.transcript != draft.transcript {
+                    return Err(hold_error(&hold));
+                }
+            }
+        }
         let resolved_items_json = serde_json::to_string(&draft.resolved_items)?;
+        let manual_items_json = serde_json::to_string(&draft.manual_items)?;
+        let interaction_warnings_json = serde_json::to_string(&draft.interaction_warnings)?;
+        let reported_medications_json = serde_json::to_string(&draft.reported_medications)?;
+        let service_items_json = serde_json::to_string(&draft.service_items)?;
         let status_str = status_to_string(&draft.status);
-
-        let rows_affected = self.conn.execute(
+        let inline_transcript = if self.limits.should_chunk_transcript(&draft.transcript) {
+            ""
+        } else {
+            draft.transcript.as_str()
+        };
+
+        let tx = if self.conn.is_autocommit() {
+            Some(self.conn.unchecked_transaction()?)
+        } else {
+            None
+        };
+        let mut stmt = self.conn.prepare_cached(
             r#"
             UPDATE encounter_drafts SET
                 transcript = ?2,
                 resolved_items = ?3,
                 status = ?4,
+                manual_items = ?5,
+                interaction_warnings = ?6,
+                reported_medications = ?7,
+                service_items = ?8,
+                visit_id = ?9,
                 updated_at = datetime('now')
             WHERE draft_id = ?1
             "#,
-            params![
-                draft.draft_id,
-                draft.transcript,
-                resolved_items_json,
-                status_str,
-            ],
         )?;
+        let rows_affected = stmt.execute(params![
+            draft.draft_id,
+            inline_transcript,
+            resolved_items_json,
+            status_str,
+            manual_items_json,
+            interaction_warnings_json,
+            reported_medications_json,
+            service_items_json,
+            draft.visit_id,
+        ])?;
+        if rows_affected > 0 {
+            self.store_transcript_chunks(&draft.draft_id, &draft.transcript)?;
+        }
+        if let Some(tx) = tx {
+            tx.commit()?;
+        }
         Ok(rows_affected > 0)
     }
 
     /// Get a draft by ID.
     pub fn get_draft(&self, draft_id: &str) -> DbResult<Option<EncounterDraft>> {
+        let sql = format!(
+            "SELECT {} FROM encounter_drafts WHERE draft_id = ?",
+            DRAFT_COLUMNS
+        );
         self.conn
-            .query_row(
-                r#"
-                SELECT draft_id, patient_id, transcript, resolved_items,
-                       status, created_at, updated_at
-                FROM encounter_drafts
-                WHERE draft_id = ?
-                "#,
-                [draft_id],
-                |row| {
-                    Ok(DraftRow {
-                        draft_id: row.get(0)?,
-                        patient_id: row.get(1)?,
-                        transcript: row.get(2)?,
-                        resolved_items: row.get(3)?,
-                        status: row.get(4)?,
-                        created_at: row.get(5)?,
-                        updated_at: row.get(6)?,
-                    })
-                },
-            )
+            .prepare_cached(&sql)?
+            .query_row([draft_id], draft_row)
             .optional()?
-            .map(|row| row.try_into())
+            .map(|row| self.draft_from_row(row))
             .transpose()
     }
 
-    /// List drafts pending review, ordered by lowest confidence first.
+    /// List drafts pending review in the clinic's review queue order
+    /// (by default, items needing most attention first).
     pub fn list_pending_review_drafts(&self) -> DbResult<Vec<EncounterDraft>> {
-        let mut stmt = self.conn.prepare(
-            r#"
-            SELECT draft_id, patient_id, transcript, resolved_items,
-                   status, created_at, updated_at
-            FROM encounter_drafts
-            WHERE status = 'pending_review'
-            ORDER BY updated_at DESC
-            "#,
-        )?;
-
-        let rows = stmt.query_map([], |row| {
-            Ok(DraftRow {
-                draft_id: row.get(0)?,
-                patient_id: row.get(1)?,
-                transcript: row.get(2)?,
-                resolved_items: row.get(3)?,
-                status: row.get(4)?,
-                created_at: row.get(5)?,
-                updated_at: row.get(6)?,
-            })
-        })?;
+        let order = self.core_settings()?.review_queue_order;
+        let direction = match order {
+            ReviewQueueOrder::OldestFirst => "ASC",
+            ReviewQueueOrder::Attention | ReviewQueueOrder::NewestFirst => "DESC",
+        };
+        let sql = format!(
+            "SELECT {} FROM encounter_drafts WHERE status = 'pending_review' ORDER BY updated_at {}, rowid {}",
+            DRAFT_COLUMNS, direction, direction
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let rows = stmt.query_map([], draft_row)?;
 
         let mut drafts = Vec::new();
         for row in rows {
-            drafts.push(row?.try_into()?);
+            drafts.push(self.draft_from_row(row?)?);
         }
 
-        // Sort by lowest confidence (items needing most attention first)
-        drafts.sort_by(|a: &EncounterDraft, b: &EncounterDraft| {
-            let conf_a = a.lowest_confidence().unwrap_or(1.0);
-            let conf_b = b.lowest_confidence().unwrap_or(1.0);
-            conf_a.partial_cmp(&conf_b).unwrap_or(std::cmp::Ordering::Equal)
-        });
+        if order == ReviewQueueOrder::Attention {
+            // Safety warnings first, then ambiguous matches, then lowest
+            // confidence. A flagged draft never sorts below an unflagged one.
+            drafts.sort_by(|a: &EncounterDraft, b: &EncounterDraft| {
+                let conf_a = a.lowest_confidence().unwrap_or(1.0);
+                let conf_b = b.lowest_confidence().unwrap_or(1.0);
+                b.has_safety_warnings()
+                    .cmp(&a.has_safety_warnings())
+                    .then_with(|| b.has_ambiguous_items().cmp(&a.has_ambiguous_items()))
+                    .then_with(|| {
+                        conf_a
+                            .partial_cmp(&conf_b)
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+            });
+        }
 
         Ok(drafts)
     }
@@ -125,74 +183,92 @@ impl Database {
     /// List drafts by status.
     pub fn list_drafts_by_status(&self, status: &DraftStatus) -> DbResult<Vec<EncounterDraft>> {
         let status_str = status_to_string(status);
-        let mut stmt = self.conn.prepare(
-            r#"
-            SELECT draft_id, patient_id, transcript, resolved_items,
-                   status, created_at, updated_at
-            FROM encounter_drafts
-            WHERE status = ?
-            ORDER BY updated_at DESC
-            "#,
-        )?;
+        let sql = format!(
+            "SELECT {} FROM encounter_drafts WHERE status = ? ORDER BY updated_at DESC",
+            DRAFT_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
 
-        let rows = stmt.query_map([status_str], |row| {
-            Ok(DraftRow {
-                draft_id: row.get(0)?,
-                patient_id: row.get(1)?,
-                transcript: row.get(2)?,
-                resolved_items: row.get(3)?,
-                status: row.get(4)?,
-                created_at: row.get(5)?,
-                updated_at: row.get(6)?,
-            })
-        })?;
+        let rows = stmt.query_map([status_str], draft_row)?;
 
         let mut drafts = Vec::new();
         for row in rows {
-            drafts.push(row?.try_into()?);
+            drafts.push(self.draft_from_row(row?)?);
         }
         Ok(drafts)
     }
 
     /// List all drafts for a patient.
     pub fn list_drafts_for_patient(&self, patient_id: &str) -> DbResult<Vec<EncounterDraft>> {
-        let mut stmt = self.conn.prepare(
-            r#"
-            SELECT draft_id, patient_id, transcript, resolved_items,
-                   status, created_at, updated_at
-            FROM encounter_drafts
-            WHERE patient_id = ?
-            ORDER BY created_at DESC
-            "#,
-        )?;
+        let sql = format!(
+            "SELECT {} FROM encounter_drafts WHERE patient_id = ? ORDER BY created_at DESC",
+            DRAFT_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
 
-        let rows = stmt.query_map([patient_id], |row| {
-            Ok(DraftRow {
-                draft_id: row.get(0)?,
-                patient_id: row.get(1)?,
-                transcript: row.get(2)?,
-                resolved_items: row.get(3)?,
-                status: row.get(4)?,
-                created_at: row.get(5)?,
-                updated_at: row.get(6)?,
-            })
-        })?;
+        let rows = stmt.query_map([patient_id], draft_row)?;
 
         let mut drafts = Vec::new();
         for row in rows {
-            drafts.push(row?.try_into()?);
+            drafts.push(self.draft_from_row(row?)?);
         }
         Ok(drafts)
     }
 
-    /// Delete a draft.
+    /// Delete a draft. Fails if the draft or its patient is under legal hold.
     pub fn delete_draft(&self, draft_id: &str) -> DbResult<bool> {
-        let rows_affected = self
-            .conn
-            .execute("DELETE FROM encounter_drafts WHERE draft_id = ?", [draft_id])?;
+        if let Some(draft) = self.get_draft(draft_id)? {
+            self.ensure_encounter_not_held(draft_id, &draft.patient_id)?;
+        }
+        let rows_affected = self.conn.execute(
+            "DELETE FROM encounter_drafts WHERE draft_id = ?",
+            [draft_id],
+        )?;
         Ok(rows_affected > 0)
     }
 
+    /// Reviewed drafts not committed within `older_than_minutes`, oldest first.
+    ///
+    /// Each is linked to its encounter leaf when the commit reached the tree
+    /// but the draft was never marked committed.
+    pub fn pending_commits(&self, older_than_minutes: u32) -> DbResult<Vec<PendingCommit>> {
+        let sql = format!(
+            "SELECT {} FROM encounter_drafts
+             WHERE status = 'reviewed' AND julianday(updated_at) <= julianday('now', ?)
+             ORDER BY julianday(updated_at)",
+            DRAFT_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let cutoff = format!("-{} minutes", older_than_minutes);
+        let rows = stmt.query_map([cutoff], draft_row)?;
+
+        let mut pending = Vec::new();
+        for row in rows {
+            let draft = self.draft_from_row(row?)?;
+            pending.push(PendingCommit {
+                leaf_hash: self.find_encounter_leaf(&draft.draft_id)?,
+                line_item_count: draft.resolved_items.len()
+                    + draft.service_items.len()
+                    + draft.manual_items.len(),
+                reviewed_at: draft.updated_at,
+                draft_id: draft.draft_id,
+                patient_id: draft.patient_id,
+            });
+        }
+        Ok(pending)
+    }
+
+    /// Convert a row to a draft, reassembling chunked transcripts.
+    fn draft_from_row(&self, row: DraftRow) -> DbResult<EncounterDraft> {
+        let mut draft: EncounterDraft = row.try_into()?;
+        if draft.transcript.is_empty() {
+            if let Some(transcript) = self.load_transcript_chunks(&draft.draft_id)? {
+                draft.transcript = transcript;
+            }
+        }
+        Ok(draft)
+    }
+
     /// Mark draft as committed (after Merkle tree commit).
     pub fn mark_draft_committed(&self, draft_id: &str) -> DbResult<bool> {
         let rows_affected = self.conn.execute(
@@ -203,6 +279,29 @@ impl Database {
     }
 }
 
+/// Columns selected for a draft, in [`draft_row`] order.
+const DRAFT_COLUMNS: &str = "draft_id, patient_id, transcript, resolved_items, status, \
+    created_at, updated_at, manual_items, interaction_warnings, reported_medications, \
+    service_items, visit_id";
+
+/// Map a row selected with [`DRAFT_COLUMNS`].
+fn draft_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<DraftRow> {
+    Ok(DraftRow {
+        draft_id: row.get(0)?,
+        patient_id: row.get(1)?,
+        transcript: row.get(2)?,
+        resolved_items: row.get(3)?,
+        status: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        manual_items: row.get(7)?,
+        interaction_warnings: row.get(8)?,
+        reported_medications: row.get(9)?,
+        service_items: row.get(10)?,
+        visit_id: row.get(11)?,
+    })
+}
+
 /// Intermediate row struct for database mapping.
 struct DraftRow {
     draft_id: String,
@@ -212,6 +311,11 @@ struct DraftRow {
     status: String,
     created_at: String,
     updated_at: String,
+    manual_items: String,
+    interaction_warnings: String,
+    reported_medications: String,
+    service_items: String,
+    visit_id: Option<String>,
 }
 
 impl TryFrom<DraftRow> for EncounterDraft {
@@ -219,6 +323,7 @@ impl TryFrom<DraftRow> for EncounterDraft {
 
     fn try_from(row: DraftRow) -> Result<Self, Self::Error> {
         let resolved_items: Vec<ResolvedItem> = serde_json::from_str(&row.resolved_items)?;
+        let manual_items: Vec<EncounterLineItem> = serde_json::from_str(&row.manual_items)?;
         let status = string_to_status(&row.status)?;
 
         Ok(EncounterDraft {
@@ -226,6 +331,11 @@ impl TryFrom<DraftRow> for EncounterDraft {
             patient_id: row.patient_id,
             transcript: row.transcript,
             resolved_items,
+            manual_items,
+            interaction_warnings: serde_json::from_str(&row.interaction_warnings)?,
+            reported_medications: serde_json::from_str(&row.reported_medications)?,
+            service_items: serde_json::from_str(&row.service_items)?,
+            visit_id: row.visit_id,
             status,
             created_at: row.created_at,
             updated_at: row.updated_at,
@@ -257,11 +367,11 @@ fn string_to_status(s: &str) -> Result<DraftStatus, DbError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::Patient;
     use crate::models::{
         DrugMention, NormalizedMention, ResolutionStatus, ResolvedItem, ScoreBreakdown,
-        ScoredCandidate,
+        ScoredCandidate, SpeakerRole,
     };
-    use crate::models::Patient;
 
     fn setup_db() -> Database {
         let db = Database::open_in_memory().unwrap();
@@ -283,11 +393,16 @@ mod tests {
                     species: None,
                     start_offset: 0,
                     end_offset: 4,
+                    field_spans: Default::default(),
+                    extraction_confidence: None,
+                    speaker: None,
                 },
                 normalized_name: "test".into(),
                 normalized_dose: Some(10.0),
                 normalized_unit: Some("mg".into()),
                 normalized_route: Some("PO".into()),
+                infusion: None,
+                taper: None,
             },
             top_candidate: ScoredCandidate {
                 sku: "SKU001".into(),
@@ -299,9 +414,22 @@ mod tests {
                     route_score: 1.0,
                     dose_score: 1.0,
                 },
+                suggested_quantity: None,
+                controlled_schedule: None,
             },
             alternatives: vec![],
             status: ResolutionStatus::PendingReview,
+            controlled_confirmed_by: None,
+            safety_warnings: vec![],
+            allergy_overridden_by: None,
+            duplicate_mentions: vec![],
+            tied_skus: vec![],
+            escalation: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            override_schedule: None,
         }
     }
 
@@ -324,6 +452,34 @@ mod tests {
         assert!(matches!(retrieved.status, DraftStatus::PendingReview));
     }
 
+    #[test]
+    fn test_manual_items_roundtrip() {
+        let db = setup_db();
+        let patient_id = db.list_patients().unwrap()[0].local_id.clone();
+
+        let mut draft = EncounterDraft::new(patient_id);
+        db.insert_draft(&draft).unwrap();
+
+        draft.add_manual_item(
+            "LRS-1L".into(),
+            "LRS 1L".into(),
+            1.0,
+            "bag".into(),
+            Some("IV".into()),
+        );
+        db.update_draft(&draft).unwrap();
+
+        let mut reported = make_resolved_item(0.9).mention.original;
+        reported.speaker = Some(SpeakerRole::Owner);
+        draft.reported_medications.push(reported.clone());
+        db.update_draft(&draft).unwrap();
+
+        let retrieved = db.get_draft(&draft.draft_id).unwrap().unwrap();
+        assert_eq!(retrieved.manual_items.len(), 1);
+        assert_eq!(retrieved.manual_items[0].sku, "LRS-1L");
+        assert_eq!(retrieved.reported_medications, vec![reported]);
+    }
+
     #[test]
     fn test_update_draft() {
         let db = setup_db();
@@ -373,6 +529,164 @@ mod tests {
         assert_eq!(pending[2].draft_id, draft1.draft_id); // 0.95
     }
 
+    #[test]
+    fn test_list_pending_review_follows_queue_order_setting() {
+        let db = setup_db();
+        let patient_id = db.list_patients().unwrap()[0].local_id.clone();
+
+        let mut ids = Vec::new();
+        for confidence in [0.95, 0.50, 0.75] {
+            let mut draft = EncounterDraft::new(patient_id.clone());
+            draft.resolved_items.push(make_resolved_item(confidence));
+            draft.status = DraftStatus::PendingReview;
+            db.insert_draft(&draft).unwrap();
+            ids.push(draft.draft_id);
+        }
+        let listed = |db: &Database| -> Vec<String> {
+            db.list_pending_review_drafts()
+                .unwrap()
+                .into_iter()
+                .map(|d| d.draft_id)
+                .collect()
+        };
+
+        let mut settings = db.core_settings().unwrap();
+        settings.review_queue_order = ReviewQueueOrder::OldestFirst;
+        db.set_core_settings(&settings).unwrap();
+        assert_eq!(listed(&db), ids);
+
+        settings.review_queue_order = ReviewQueueOrder::NewestFirst;
+        db.set_core_settings(&settings).unwrap();
+        let mut newest = ids.clone();
+        newest.reverse();
+        assert_eq!(listed(&db), newest);
+    }
+
+    #[test]
+    fn test_oversized_transcript_chunked() {
+        let mut db = setup_db();
+        db.set_limits(crate::limits::Limits {
+            transcript_inline_bytes: 128,
+            transcript_chunk_bytes: 16,
+            ..Default::default()
+        });
+        let patients = db.list_patients().unwrap();
+        let patient_id = patients[0].local_id.clone();
+
+        let mut draft = EncounterDraft::new(patient_id);
+        draft.transcript = "Give 100mg carprofen PO twice daily. ".repeat(50);
+        db.insert_draft(&draft).unwrap();
+
+        // Stored out of line
+        let inline: String = db
+            .conn()
+            .query_row(
+                "SELECT transcript FROM encounter_drafts WHERE draft_id = ?",
+                [&draft.draft_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(inline.is_empty());
+
+        let retrieved = db.get_draft(&draft.draft_id).unwrap().unwrap();
+        assert_eq!(retrieved.transcript, draft.transcript);
+
+        // Shrinking the transcript drops the chunks
+        draft.transcript = "short".into();
+        db.update_draft(&draft).unwrap();
+        assert!(db
+            .load_transcript_chunks(&draft.draft_id)
+            .unwrap()
+            .is_none());
+        let retrieved = db.get_draft(&draft.draft_id).unwrap().unwrap();
+        assert_eq!(retrieved.transcript, "short");
+    }
+
+    #[test]
+    fn test_failed_chunk_write_rolls_back_draft() {
+        let mut db = setup_db();
+        db.set_limits(crate::limits::Limits {
+            transcript_inline_bytes: 128,
+            transcript_chunk_bytes: 16,
+            ..Default::default()
+        });
+        let patients = db.list_patients().unwrap();
+        let patient_id = patients[0].local_id.clone();
+
+        let mut draft = EncounterDraft::new(patient_id);
+        draft.transcript = "short".into();
+        db.insert_draft(&draft).unwrap();
+        db.conn()
+            .execute_batch(
+                "CREATE TRIGGER fail_chunks BEFORE INSERT ON draft_transcript_chunks
+                 BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+            )
+            .unwrap();
+
+        // The row update is rolled back along with the failed chunks
+        draft.transcript = "Give 100mg carprofen PO twice daily. ".repeat(50);
+        assert!(db.update_draft(&draft).is_err());
+        let retrieved = db.get_draft(&draft.draft_id).unwrap().unwrap();
+        assert_eq!(retrieved.transcript, "short");
+
+        // And a new draft is not left behind with an empty transcript
+        let mut other = EncounterDraft::new(draft.patient_id.clone());
+        other.transcript = draft.transcript.clone();
+        assert!(db.insert_draft(&other).is_err());
+        assert!(db.get_draft(&other.draft_id).unwrap().is_none());
+        assert!(db.conn().is_autocommit());
+    }
+
+    #[test]
+    fn test_transcript_over_limit_rejected() {
+        let mut db = setup_db();
+        db.set_limits(crate::limits::Limits {
+            max_transcript_bytes: 100,
+            ..Default::default()
+        });
+        let patients = db.list_patients().unwrap();
+        let patient_id = patients[0].local_id.clone();
+
+        let mut draft = EncounterDraft::new(patient_id);
+        draft.transcript = "x".repeat(101);
+        let result = db.insert_draft(&draft);
+        assert!(matches!(result, Err(DbError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn test_pending_commits() {
+        let db = setup_db();
+        let patient_id = db.list_patients().unwrap()[0].local_id.clone();
+
+        let mut stale = EncounterDraft::new(patient_id.clone());
+        stale.resolved_items.push(make_resolved_item(0.9));
+        stale.status = DraftStatus::Reviewed;
+        stale.updated_at = "2024-01-01T00:00:00Z".into();
+        db.insert_draft(&stale).unwrap();
+
+        let mut fresh = EncounterDraft::new(patient_id.clone());
+        fresh.status = DraftStatus::Reviewed;
+        db.insert_draft(&fresh).unwrap();
+
+        let mut committed = EncounterDraft::new(patient_id);
+        committed.status = DraftStatus::Committed;
+        committed.updated_at = "2024-01-01T00:00:00Z".into();
+        db.insert_draft(&committed).unwrap();
+
+        let pending = db.pending_commits(30).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].draft_id, stale.draft_id);
+        assert_eq!(pending[0].line_item_count, 1);
+        assert!(pending[0].leaf_hash.is_none());
+
+        // A leaf that reached the tree is linked
+        let payload = format!(r#"{{"draft_id":"{}","line_items":[]}}"#, stale.draft_id);
+        db.insert_merkle_leaf("leaf-1", &payload).unwrap();
+        let pending = db.pending_commits(30).unwrap();
+        assert_eq!(pending[0].leaf_hash.as_deref(), Some("leaf-1"));
+        assert_eq!(db.pending_commits(0).unwrap().len(), 2);
+    }
+
     #[test]
     fn test_mark_committed() {
         let db = setup_db();
@@ -0,0 +1,137 @@
+//! Review escalation rule operations.
+
+use std::collections::BTreeSet;
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbResult};
+use crate::models::{split_components, EscalationRule, ResolvedItem};
+
+impl Database {
+    /// Insert or update the escalation rule for an ingredient.
+    pub fn upsert_escalation_rule(&self, rule: &EscalationRule) -> DbResult<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO escalation_rules (ingredient, required_role, note, updated_at)
+            VALUES (?1, ?2, ?3, datetime('now'))
+            ON CONFLICT(ingredient) DO UPDATE SET
+                required_role = excluded.required_role,
+                note = excluded.note,
+                updated_at = datetime('now')
+            "#,
+            params![rule.ingredient, rule.required_role, rule.note],
+        )?;
+        Ok(())
+    }
+
+    /// List all escalation rules.
+    pub fn list_escalation_rules(&self) -> DbResult<Vec<EscalationRule>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT ingredient, required_role, note FROM escalation_rules ORDER BY ingredient",
+        )?;
+        let rules = stmt
+            .query_map([], |row| {
+                Ok(EscalationRule {
+                    ingredient: row.get(0)?,
+                    required_role: row.get(1)?,
+                    note: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rules)
+    }
+
+    /// Get the escalation rule for an ingredient.
+    pub fn get_escalation_rule(&self, ingredient: &str) -> DbResult<Option<EscalationRule>> {
+        Ok(self
+            .conn
+            .prepare_cached(
+                "SELECT ingredient, required_role, note FROM escalation_rules WHERE ingredient = ?",
+            )?
+            .query_row([ingredient.trim().to_lowercase()], |row| {
+                Ok(EscalationRule {
+                    ingredient: row.get(0)?,
+                    required_role: row.get(1)?,
+                    note: row.get(2)?,
+                })
+            })
+            .optional()?)
+    }
+
+    /// Delete the escalation rule for an ingredient.
+    pub fn delete_escalation_rule(&self, ingredient: &str) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            "DELETE FROM escalation_rules WHERE ingredient = ?",
+            [ingredient.trim().to_lowercase()],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Escalation rule governing a catalog SKU, if any of its components is
+    /// restricted.
+    pub fn sku_escalation_rule(&self, sku: &str) -> DbResult<Option<EscalationRule>> {
+        match self.get_catalog_item(sku)? {
+            Some(catalog_item) => self.first_escalation_rule(catalog_item.component_names()),
+            None => Ok(None),
+        }
+    }
+
+    /// Escalation rule governing a resolved item, if any.
+    ///
+    /// Checks the spoken drug's components and the catalog components of the
+    /// chosen SKU (the top candidate while the item is pending).
+    pub fn item_escalation_rule(&self, item: &ResolvedItem) -> DbResult<Option<EscalationRule>> {
+        let sku = item.final_sku().unwrap_or(&item.top_candidate.sku);
+        let mut ingredients: BTreeSet<String> = split_components(&item.mention.normalized_name)
+            .into_iter()
+            .collect();
+        if let Some(catalog_item) = self.get_catalog_item(sku)? {
+            ingredients.extend(catalog_item.component_names());
+        }
+        self.first_escalation_rule(ingredients)
+    }
+
+    fn first_escalation_rule(
+        &self,
+        ingredients: impl IntoIterator<Item = String>,
+    ) -> DbResult<Option<EscalationRule>> {
+        for ingredient in ingredients {
+            if let Some(rule) = self.get_escalation_rule(&ingredient)? {
+                return Ok(Some(rule));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CatalogItem;
+
+    #[test]
+    fn test_escalation_rule_crud() {
+        let db = Database::open_in_memory().unwrap();
+
+        db.upsert_escalation_rule(&EscalationRule::new("Fentanyl", "pharmacist", "Opioid"))
+            .unwrap();
+        db.upsert_escalation_rule(&EscalationRule::new("fentanyl", "dvm-lead", "Opioid"))
+            .unwrap();
+
+        let rules = db.list_escalation_rules().unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].ingredient, "fentanyl");
+        assert_eq!(rules[0].required_role, "dvm-lead");
+        assert!(db.get_escalation_rule("FENTANYL").unwrap().is_some());
+
+        let mut item = CatalogItem::new("FENT-50".into(), "Fentanyl 50mcg/mL injection".into());
+        item.routes = vec!["IV".into()];
+        db.upsert_catalog_item(&item).unwrap();
+        let rule = db.sku_escalation_rule("FENT-50").unwrap().unwrap();
+        assert_eq!(rule.required_role, "dvm-lead");
+        assert!(db.sku_escalation_rule("MISSING").unwrap().is_none());
+
+        assert!(db.delete_escalation_rule("fentanyl").unwrap());
+        assert!(!db.delete_escalation_rule("fentanyl").unwrap());
+    }
+}
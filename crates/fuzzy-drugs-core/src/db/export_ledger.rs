@@ -0,0 +1,183 @@
+//! Export ledger operations: which encounter leaves went out in which
+//! billing export batch.
+
+use std::collections::HashSet;
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbError, DbResult};
+use crate::models::{ExportBatch, ExportBatchStatus};
+
+impl Database {
+    /// Record a pending batch containing `leaf_hashes`.
+    pub fn record_export_batch(&self, leaf_hashes: &[String]) -> DbResult<ExportBatch> {
+        let batch = ExportBatch {
+            batch_id: uuid::Uuid::new_v4().to_string(),
+            status: ExportBatchStatus::Pending,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            status_changed_at: None,
+            leaf_hashes: leaf_hashes.to_vec(),
+        };
+
+        let tx = self.conn.unchecked_transaction()?;
+        self.conn.execute(
+            "INSERT INTO export_batches (batch_id, status, created_at) VALUES (?1, ?2, ?3)",
+            params![batch.batch_id, batch.status.as_str(), batch.created_at],
+        )?;
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR IGNORE INTO export_batch_leaves (batch_id, leaf_hash, position) \
+             VALUES (?1, ?2, ?3)",
+        )?;
+        for (position, leaf_hash) in leaf_hashes.iter().enumerate() {
+            stmt.execute(params![batch.batch_id, leaf_hash, position as i64])?;
+        }
+        drop(stmt);
+        tx.commit()?;
+        Ok(batch)
+    }
+
+    /// Get a batch with its leaf hashes.
+    pub fn get_export_batch(&self, batch_id: &str) -> DbResult<Option<ExportBatch>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT batch_id, status, created_at, status_changed_at \
+                 FROM export_batches WHERE batch_id = ?",
+                [batch_id],
+                batch_row,
+            )
+            .optional()?;
+        row.map(|row| self.export_batch(row)).transpose()
+    }
+
+    /// All batches, newest first.
+    pub fn list_export_batches(&self) -> DbResult<Vec<ExportBatch>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT batch_id, status, created_at, status_changed_at \
+             FROM export_batches ORDER BY created_at DESC, rowid DESC",
+        )?;
+        let rows = stmt
+            .query_map([], batch_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter().map(|row| self.export_batch(row)).collect()
+    }
+
+    /// Mark a pending batch imported or void. Batches that are already
+    /// imported or void can't change.
+    pub fn set_export_batch_status(
+        &self,
+        batch_id: &str,
+        status: ExportBatchStatus,
+    ) -> DbResult<ExportBatch> {
+        let batch = self
+            .get_export_batch(batch_id)?
+            .ok_or_else(|| DbError::NotFound(format!("Export batch {}", batch_id)))?;
+        if batch.status != ExportBatchStatus::Pending || status == ExportBatchStatus::Pending {
+            return Err(DbError::Constraint(format!(
+                "Export batch {} is {} and can't be marked {}",
+                batch_id,
+                batch.status.as_str(),
+                status.as_str()
+            )));
+        }
+
+        let changed_at = chrono::Utc::now().to_rfc3339();
+        self.conn.execute(
+            "UPDATE export_batches SET status = ?1, status_changed_at = ?2 WHERE batch_id = ?3",
+            params![status.as_str(), changed_at, batch_id],
+        )?;
+        Ok(ExportBatch {
+            status,
+            status_changed_at: Some(changed_at),
+            ..batch
+        })
+    }
+
+    /// Leaf hashes in pending or imported batches.
+    pub fn billed_leaf_hashes(&self) -> DbResult<HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT l.leaf_hash FROM export_batch_leaves l \
+             JOIN export_batches b ON b.batch_id = l.batch_id \
+             WHERE b.status != 'void'",
+        )?;
+        let hashes = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(hashes)
+    }
+
+    /// Attach leaf hashes to a batch row.
+    fn export_batch(&self, row: BatchRow) -> DbResult<ExportBatch> {
+        let status = ExportBatchStatus::parse(&row.status).ok_or_else(|| {
+            DbError::Constraint(format!("Unknown export batch status: {}", row.status))
+        })?;
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT leaf_hash FROM export_batch_leaves WHERE batch_id = ? ORDER BY position",
+        )?;
+        let leaf_hashes = stmt
+            .query_map([&row.batch_id], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(ExportBatch {
+            batch_id: row.batch_id,
+            status,
+            created_at: row.created_at,
+            status_changed_at: row.status_changed_at,
+            leaf_hashes,
+        })
+    }
+}
+
+fn batch_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<BatchRow> {
+    Ok(BatchRow {
+        batch_id: row.get(0)?,
+        status: row.get(1)?,
+        created_at: row.get(2)?,
+        status_changed_at: row.get(3)?,
+    })
+}
+
+/// Intermediate row struct for database mapping.
+struct BatchRow {
+    batch_id: String,
+    status: String,
+    created_at: String,
+    status_changed_at: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_batch_lifecycle() {
+        let db = Database::open_in_memory().unwrap();
+        let leaves = vec!["leaf-b".to_string(), "leaf-a".to_string()];
+        let first = db.record_export_batch(&leaves).unwrap();
+        let second = db.record_export_batch(&["leaf-c".to_string()]).unwrap();
+
+        let stored = db.get_export_batch(&first.batch_id).unwrap().unwrap();
+        assert_eq!(stored, first);
+        assert_eq!(stored.leaf_hashes, leaves);
+        assert_eq!(db.list_export_batches().unwrap().len(), 2);
+        assert_eq!(db.billed_leaf_hashes().unwrap().len(), 3);
+
+        let void = db
+            .set_export_batch_status(&first.batch_id, ExportBatchStatus::Void)
+            .unwrap();
+        assert_eq!(void.status, ExportBatchStatus::Void);
+        assert!(void.status_changed_at.is_some());
+        let billed = db.billed_leaf_hashes().unwrap();
+        assert_eq!(billed, HashSet::from(["leaf-c".to_string()]));
+
+        db.set_export_batch_status(&second.batch_id, ExportBatchStatus::Imported)
+            .unwrap();
+        assert!(matches!(
+            db.set_export_batch_status(&second.batch_id, ExportBatchStatus::Void),
+            Err(DbError::Constraint(_))
+        ));
+        assert!(matches!(
+            db.set_export_batch_status("missing", ExportBatchStatus::Void),
+            Err(DbError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,165 @@
+//! Cached extraction results.
+//!
+//! Re-opening a draft re-runs extraction on the same transcript. Results
+//! are kept by the transcript's SHA-256 together with the extractor version
+//! (model and prompt) that produced them; a lookup under any other version
+//! misses. The cache holds at most [`EXTRACTION_CACHE_CAPACITY`] entries,
+//! dropping the oldest.
+
+use rusqlite::{params, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use super::{Database, DbResult};
+use crate::resolver::ExtractedMentions;
+
+/// Most transcripts kept in the extraction cache.
+pub const EXTRACTION_CACHE_CAPACITY: usize = 500;
+
+/// Cache key for a transcript.
+fn transcript_hash(transcript: &str) -> String {
+    hex::encode(Sha256::digest(transcript.as_bytes()))
+}
+
+impl Database {
+    /// The cached extraction of `transcript` by `extractor_version`, if any.
+    pub fn get_cached_extraction(
+        &self,
+        transcript: &str,
+        extractor_version: &str,
+    ) -> DbResult<Option<ExtractedMentions>> {
+        let output: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT output FROM extraction_cache
+                 WHERE transcript_hash = ?1 AND extractor_version = ?2",
+                params![transcript_hash(transcript), extractor_version],
+                |row| row.get(0),
+            )
+            .optional()?;
+        // An entry that no longer parses is a miss; the next store replaces it
+        Ok(output.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    /// Cache the extraction of `transcript` by `extractor_version`,
+    /// replacing any earlier entry for the transcript.
+    pub fn cache_extraction(
+        &self,
+        transcript: &str,
+        extractor_version: &str,
+        mentions: &ExtractedMentions,
+    ) -> DbResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO extraction_cache
+                 (transcript_hash, extractor_version, output, created_at)
+             VALUES (?1, ?2, ?3, datetime('now'))",
+            params![
+                transcript_hash(transcript),
+                extractor_version,
+                serde_json::to_string(mentions)?,
+            ],
+        )?;
+        self.conn.execute(
+            "DELETE FROM extraction_cache WHERE transcript_hash NOT IN (
+                 SELECT transcript_hash FROM extraction_cache
+                 ORDER BY created_at DESC, rowid DESC LIMIT ?1
+             )",
+            [EXTRACTION_CACHE_CAPACITY as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Drop cached extractions by any version other than `keep_version`
+    /// (all of them if `None`), e.g. after a model or prompt update.
+    ///
+    /// Returns the number of entries deleted.
+    pub fn invalidate_extraction_cache(&self, keep_version: Option<&str>) -> DbResult<usize> {
+        let deleted = self.conn.execute(
+            "DELETE FROM extraction_cache
+             WHERE ?1 IS NULL OR extractor_version != ?1",
+            [keep_version],
+        )?;
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DrugMention;
+
+    fn mentions(name: &str) -> ExtractedMentions {
+        ExtractedMentions {
+            drugs: vec![DrugMention {
+                raw_text: name.into(),
+                drug_name: name.into(),
+                dose: None,
+                unit: None,
+                route: None,
+                species: None,
+                start_offset: 0,
+                end_offset: name.len(),
+                field_spans: Default::default(),
+                extraction_confidence: Some(0.9),
+                speaker: None,
+            }],
+            services: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_cached_extraction_by_version() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.get_cached_extraction("rimadyl", "v1").unwrap(), None);
+
+        db.cache_extraction("rimadyl", "v1", &mentions("rimadyl"))
+            .unwrap();
+        assert_eq!(
+            db.get_cached_extraction("rimadyl", "v1").unwrap(),
+            Some(mentions("rimadyl"))
+        );
+        // Another version or transcript misses
+        assert_eq!(db.get_cached_extraction("rimadyl", "v2").unwrap(), None);
+        assert_eq!(db.get_cached_extraction("rimadyl ", "v1").unwrap(), None);
+
+        // A new version's result replaces the old one
+        db.cache_extraction("rimadyl", "v2", &mentions("carprofen"))
+            .unwrap();
+        assert_eq!(db.get_cached_extraction("rimadyl", "v1").unwrap(), None);
+        assert_eq!(
+            db.get_cached_extraction("rimadyl", "v2").unwrap(),
+            Some(mentions("carprofen"))
+        );
+
+        db.cache_extraction("metacam", "v1", &mentions("metacam"))
+            .unwrap();
+        assert_eq!(db.invalidate_extraction_cache(Some("v2")).unwrap(), 1);
+        assert!(db.get_cached_extraction("rimadyl", "v2").unwrap().is_some());
+        assert_eq!(db.invalidate_extraction_cache(None).unwrap(), 1);
+        assert_eq!(db.get_cached_extraction("rimadyl", "v2").unwrap(), None);
+    }
+
+    #[test]
+    fn test_extraction_cache_capacity() {
+        let db = Database::open_in_memory().unwrap();
+        for i in 0..=EXTRACTION_CACHE_CAPACITY {
+            db.cache_extraction(&format!("transcript {}", i), "v1", &mentions("ace"))
+                .unwrap();
+        }
+        let count: i64 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM extraction_cache", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, EXTRACTION_CACHE_CAPACITY as i64);
+        // The oldest went first
+        assert_eq!(
+            db.get_cached_extraction("transcript 0", "v1").unwrap(),
+            None
+        );
+        assert!(db
+            .get_cached_extraction("transcript 1", "v1")
+            .unwrap()
+            .is_some());
+    }
+}
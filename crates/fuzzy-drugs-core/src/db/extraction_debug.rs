@@ -0,0 +1,203 @@
+//! Retained raw LLM responses (opt-in debug mode).
+//!
+//! Rows expire after the configured TTL and are purged on every write and
+//! read. Drafts under legal hold are never purged.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbResult};
+use crate::models::{ExtractionDebug, ExtractionDebugConfig};
+
+const DEBUG_COLUMNS: &str =
+    "draft_id, model, raw_response, original_bytes, truncated, recorded_at, expires_at";
+
+impl Database {
+    /// Get the raw-response retention settings.
+    pub fn extraction_debug_config(&self) -> &ExtractionDebugConfig {
+        &self.extraction_debug
+    }
+
+    /// Replace the raw-response retention settings.
+    pub fn set_extraction_debug_config(&mut self, config: ExtractionDebugConfig) {
+        self.extraction_debug = config;
+    }
+
+    /// Store the raw model response behind a draft's extraction, replacing
+    /// any earlier one.
+    ///
+    /// Returns `false` (and stores nothing) when debug retention is disabled.
+    pub fn record_extraction_debug(
+        &self,
+        draft_id: &str,
+        model: Option<&str>,
+        raw_response: &str,
+    ) -> DbResult<bool> {
+        if !self.extraction_debug.enabled {
+            return Ok(false);
+        }
+        self.purge_expired_extraction_debug()?;
+
+        let (retained, truncated) = self.extraction_debug.truncate(raw_response);
+        let ttl = format!("+{} hours", self.extraction_debug.retention_hours);
+        self.conn.execute(
+            r#"
+            INSERT OR REPLACE INTO extraction_debug
+                (draft_id, model, raw_response, original_bytes, truncated, recorded_at, expires_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'), datetime('now', ?6))
+            "#,
+            params![
+                draft_id,
+                model,
+                retained,
+                raw_response.len() as i64,
+                truncated,
+                ttl,
+            ],
+        )?;
+        Ok(true)
+    }
+
+    /// Get the retained raw response for a draft, if one exists and has not
+    /// been purged.
+    pub fn get_extraction_debug(&self, draft_id: &str) -> DbResult<Option<ExtractionDebug>> {
+        self.purge_expired_extraction_debug()?;
+
+        let sql = format!(
+            "SELECT {} FROM extraction_debug WHERE draft_id = ?",
+            DEBUG_COLUMNS
+        );
+        let row = self
+            .conn
+            .query_row(&sql, [draft_id], |row| {
+                Ok(DebugRow {
+                    draft_id: row.get(0)?,
+                    model: row.get(1)?,
+                    raw_response: row.get(2)?,
+                    original_bytes: row.get(3)?,
+                    truncated: row.get(4)?,
+                    recorded_at: row.get(5)?,
+                    expires_at: row.get(6)?,
+                })
+            })
+            .optional()?;
+        Ok(row.map(ExtractionDebug::from))
+    }
+
+    /// Delete expired raw responses, skipping drafts under legal hold.
+    ///
+    /// Returns the number of rows deleted.
+    pub fn purge_expired_extraction_debug(&self) -> DbResult<usize> {
+        let deleted = self.conn.execute(
+            r#"
+            DELETE FROM extraction_debug
+            WHERE expires_at <= datetime('now')
+              AND NOT EXISTS (
+                SELECT 1 FROM legal_holds h
+                LEFT JOIN encounter_drafts d ON d.draft_id = extraction_debug.draft_id
+                WHERE (h.subject_type = 'encounter' AND h.subject_id = extraction_debug.draft_id)
+                   OR (h.subject_type = 'patient' AND h.subject_id = d.patient_id)
+              )
+            "#,
+            [],
+        )?;
+        Ok(deleted)
+    }
+}
+
+/// Raw row from the extraction_debug table.
+struct DebugRow {
+    draft_id: String,
+    model: Option<String>,
+    raw_response: String,
+    original_bytes: i64,
+    truncated: bool,
+    recorded_at: String,
+    expires_at: String,
+}
+
+impl From<DebugRow> for ExtractionDebug {
+    fn from(row: DebugRow) -> Self {
+        ExtractionDebug {
+            draft_id: row.draft_id,
+            model: row.model,
+            raw_response: row.raw_response,
+            original_bytes: row.original_bytes.max(0) as usize,
+            truncated: row.truncated,
+            recorded_at: row.recorded_at,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EncounterDraft, HoldSubject, LegalHold, Patient};
+
+    fn setup() -> (Database, String) {
+        let db = Database::open_in_memory().unwrap();
+        let patient = Patient::new("Max".into(), "canine".into());
+        db.insert_patient(&patient).unwrap();
+        let draft = EncounterDraft::new(patient.local_id.clone());
+        db.insert_draft(&draft).unwrap();
+        (db, draft.draft_id)
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let (db, draft_id) = setup();
+        assert!(!db.record_extraction_debug(&draft_id, None, "{}").unwrap());
+        assert!(db.get_extraction_debug(&draft_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_record_and_truncate() {
+        let (mut db, draft_id) = setup();
+        db.set_extraction_debug_config(ExtractionDebugConfig {
+            enabled: true,
+            max_response_bytes: 10,
+            retention_hours: 24,
+        });
+
+        let response = r#"{"mentions":[]} trailing text"#;
+        assert!(db
+            .record_extraction_debug(&draft_id, Some("llama-3.2-1b"), response)
+            .unwrap());
+
+        let debug = db.get_extraction_debug(&draft_id).unwrap().unwrap();
+        assert_eq!(debug.raw_response, r#"{"mentions"#);
+        assert!(debug.truncated);
+        assert_eq!(debug.original_bytes, response.len());
+        assert_eq!(debug.model.as_deref(), Some("llama-3.2-1b"));
+    }
+
+    #[test]
+    fn test_expired_rows_purged_unless_held() {
+        let (mut db, draft_id) = setup();
+        db.set_extraction_debug_config(ExtractionDebugConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        db.record_extraction_debug(&draft_id, None, "{}").unwrap();
+        db.conn()
+            .execute(
+                "UPDATE extraction_debug SET expires_at = datetime('now', '-1 hours')",
+                [],
+            )
+            .unwrap();
+
+        let hold = LegalHold::new(
+            HoldSubject::Encounter,
+            draft_id.clone(),
+            "Claim 42".into(),
+            "Dr. Smith".into(),
+        );
+        db.place_legal_hold(&hold).unwrap();
+        assert_eq!(db.purge_expired_extraction_debug().unwrap(), 0);
+
+        db.release_legal_hold(HoldSubject::Encounter, &draft_id)
+            .unwrap();
+        assert_eq!(db.purge_expired_extraction_debug().unwrap(), 1);
+        assert!(db.get_extraction_debug(&draft_id).unwrap().is_none());
+    }
+}
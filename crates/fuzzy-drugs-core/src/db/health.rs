@@ -0,0 +1,75 @@
+//! Connection recovery and integrity checks.
+
+use rusqlite::Connection;
+
+use super::{Database, DbResult};
+
+impl Database {
+    /// Run SQLite's quick integrity check.
+    ///
+    /// Returns the problems found; empty means the database is intact.
+    pub fn integrity_check(&self) -> DbResult<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA quick_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut problems = Vec::new();
+        for row in rows {
+            let line = row?;
+            if line != "ok" {
+                problems.push(line);
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Reset the connection after a panic left it in an unknown state.
+    ///
+    /// File databases get a fresh connection; in-memory databases (which would
+    /// lose their data) only have any open transaction rolled back.
+    pub fn recover_connection(&mut self) -> DbResult<()> {
+        match &self.path {
+            Some(path) => {
+                self.conn = Connection::open(path)?;
+                self.initialize()?;
+            }
+            None => {
+                if !self.conn.is_autocommit() {
+                    self.conn.execute_batch("ROLLBACK")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integrity_check_clean() {
+        let db = Database::open_in_memory().unwrap();
+        assert!(db.integrity_check().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recover_rolls_back_open_transaction() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.conn().execute_batch("BEGIN").unwrap();
+        assert!(!db.conn().is_autocommit());
+
+        db.recover_connection().unwrap();
+        assert!(db.conn().is_autocommit());
+    }
+
+    #[test]
+    fn test_recover_reopens_file_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::open(dir.path().join("fuzzy-drugs.sqlite")).unwrap();
+        db.conn().execute_batch("BEGIN").unwrap();
+
+        db.recover_connection().unwrap();
+        assert!(db.conn().is_autocommit());
+        assert!(db.integrity_check().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! Local drug interaction table operations.
+
+use rusqlite::params;
+
+use super::{Database, DbError, DbResult};
+use crate::models::{DrugInteraction, InteractionSeverity};
+
+impl Database {
+    /// Insert or update a local interaction entry.
+    pub fn upsert_interaction(&self, interaction: &DrugInteraction) -> DbResult<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO drug_interactions (drug_a, drug_b, severity, note, updated_at)
+            VALUES (?1, ?2, ?3, ?4, datetime('now'))
+            ON CONFLICT(drug_a, drug_b) DO UPDATE SET
+                severity = excluded.severity,
+                note = excluded.note,
+                updated_at = datetime('now')
+            "#,
+            params![
+                interaction.drug_a,
+                interaction.drug_b,
+                interaction.severity.as_str(),
+                interaction.note,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// List all local interaction entries.
+    pub fn list_interactions(&self) -> DbResult<Vec<DrugInteraction>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT drug_a, drug_b, severity, note FROM drug_interactions ORDER BY drug_a, drug_b",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+
+        let mut interactions = Vec::new();
+        for row in rows {
+            let (drug_a, drug_b, severity, note) = row?;
+            let severity = InteractionSeverity::parse(&severity).ok_or_else(|| {
+                DbError::Constraint(format!("Unknown interaction severity: {}", severity))
+            })?;
+            interactions.push(DrugInteraction {
+                drug_a,
+                drug_b,
+                severity,
+                note,
+            });
+        }
+        Ok(interactions)
+    }
+
+    /// Delete a local interaction entry (either side order).
+    pub fn delete_interaction(&self, drug_a: &str, drug_b: &str) -> DbResult<bool> {
+        let key = DrugInteraction::new(drug_a, drug_b, InteractionSeverity::Minor, "");
+        let rows_affected = self.conn.execute(
+            "DELETE FROM drug_interactions WHERE drug_a = ? AND drug_b = ?",
+            [&key.drug_a, &key.drug_b],
+        )?;
+        Ok(rows_affected > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interaction_crud() {
+        let db = Database::open_in_memory().unwrap();
+
+        let entry = DrugInteraction::new(
+            "cyclosporine",
+            "ketoconazole",
+            InteractionSeverity::Moderate,
+            "raises cyclosporine levels",
+        );
+        db.upsert_interaction(&entry).unwrap();
+        assert_eq!(db.list_interactions().unwrap(), vec![entry]);
+
+        let updated = DrugInteraction::new(
+            "ketoconazole",
+            "cyclosporine",
+            InteractionSeverity::Major,
+            "monitor levels",
+        );
+        db.upsert_interaction(&updated).unwrap();
+        let listed = db.list_interactions().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].severity, InteractionSeverity::Major);
+
+        assert!(db
+            .delete_interaction("ketoconazole", "cyclosporine")
+            .unwrap());
+        assert!(db.list_interactions().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+//! Legal hold operations.
+//!
+//! Every delete, retention, or redaction path must check for a hold first
+//! (`ensure_patient_not_held` / `ensure_encounter_not_held`). Triggers on
+//! `patients` and `encounter_drafts` refuse deletes of held rows as a backstop.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbError, DbResult};
+use crate::models::{HoldSubject, LegalHold};
+
+const HOLD_COLUMNS: &str = "subject_type, subject_id, reason, placed_by, placed_at";
+
+impl Database {
+    /// Place a legal hold. Returns `false` if the subject is already held.
+    pub fn place_legal_hold(&self, hold: &LegalHold) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            r#"
+            INSERT OR IGNORE INTO legal_holds (subject_type, subject_id, reason, placed_by, placed_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+            params![
+                hold.subject_type.as_str(),
+                hold.subject_id,
+                hold.reason,
+                hold.placed_by,
+                hold.placed_at,
+            ],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Release a legal hold. Returns `false` if there was no such hold.
+    pub fn release_legal_hold(
+        &self,
+        subject_type: HoldSubject,
+        subject_id: &str,
+    ) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            "DELETE FROM legal_holds WHERE subject_type = ? AND subject_id = ?",
+            [subject_type.as_str(), subject_id],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Get the hold on a specific subject, if any.
+    pub fn get_legal_hold(
+        &self,
+        subject_type: HoldSubject,
+        subject_id: &str,
+    ) -> DbResult<Option<LegalHold>> {
+        let sql = format!(
+            "SELECT {} FROM legal_holds WHERE subject_type = ? AND subject_id = ?",
+            HOLD_COLUMNS
+        );
+        self.conn
+            .query_row(&sql, [subject_type.as_str(), subject_id], hold_row)
+            .optional()?
+            .map(LegalHold::try_from)
+            .transpose()
+    }
+
+    /// List all active legal holds, oldest first.
+    pub fn list_legal_holds(&self) -> DbResult<Vec<LegalHold>> {
+        let sql = format!(
+            "SELECT {} FROM legal_holds ORDER BY placed_at",
+            HOLD_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map([], hold_row)?;
+
+        let mut holds = Vec::new();
+        for row in rows {
+            holds.push(row?.try_into()?);
+        }
+        Ok(holds)
+    }
+
+    /// The hold covering an encounter: on the encounter itself or its patient.
+    pub fn encounter_legal_hold(
+        &self,
+        draft_id: &str,
+        patient_id: &str,
+    ) -> DbResult<Option<LegalHold>> {
+        match self.get_legal_hold(HoldSubject::Encounter, draft_id)? {
+            Some(hold) => Ok(Some(hold)),
+            None => self.get_legal_hold(HoldSubject::Patient, patient_id),
+        }
+    }
+
+    /// Fail with [`DbError::LegalHold`] if the patient is held.
+    pub fn ensure_patient_not_held(&self, patient_id: &str) -> DbResult<()> {
+        match self.get_legal_hold(HoldSubject::Patient, patient_id)? {
+            Some(hold) => Err(hold_error(&hold)),
+            None => Ok(()),
+        }
+    }
+
+    /// Fail with [`DbError::LegalHold`] if the encounter or its patient is held.
+    pub fn ensure_encounter_not_held(&self, draft_id: &str, patient_id: &str) -> DbResult<()> {
+        match self.encounter_legal_hold(draft_id, patient_id)? {
+            Some(hold) => Err(hold_error(&hold)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn hold_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<HoldRow> {
+    Ok(HoldRow {
+        subject_type: row.get(0)?,
+        subject_id: row.get(1)?,
+        reason: row.get(2)?,
+        placed_by: row.get(3)?,
+        placed_at: row.get(4)?,
+    })
+}
+
+/// Intermediate row struct for database mapping.
+struct HoldRow {
+    subject_type: String,
+    subject_id: String,
+    reason: String,
+    placed_by: String,
+    placed_at: String,
+}
+
+impl TryFrom<HoldRow> for LegalHold {
+    type Error = DbError;
+
+    fn try_from(row: HoldRow) -> Result<Self, Self::Error> {
+        let subject_type = HoldSubject::parse(&row.subject_type).ok_or_else(|| {
+            DbError::Constraint(format!("Unknown legal hold subject: {}", row.subject_type))
+        })?;
+        Ok(LegalHold {
+            subject_type,
+            subject_id: row.subject_id,
+            reason: row.reason,
+            placed_by: row.placed_by,
+            placed_at: row.placed_at,
+        })
+    }
+}
+
+/// Error refusing an operation on held data.
+pub(super) fn hold_error(hold: &LegalHold) -> DbError {
+    DbError::LegalHold {
+        subject_type: hold.subject_type.as_str().to_string(),
+        subject_id: hold.subject_id.clone(),
+        reason: hold.reason.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EncounterDraft, Patient};
+
+    #[test]
+    fn test_patient_hold_blocks_deletes() {
+        let db = Database::open_in_memory().unwrap();
+        let patient = Patient::new("Max".into(), "canine".into());
+        db.insert_patient(&patient).unwrap();
+        let draft = EncounterDraft::new(patient.local_id.clone());
+        db.insert_draft(&draft).unwrap();
+
+        let hold = LegalHold::new(
+            HoldSubject::Patient,
+            patient.local_id.clone(),
+            "Claim #1234".into(),
+            "Dr. Smith".into(),
+        );
+        assert!(db.place_legal_hold(&hold).unwrap());
+        assert!(!db.place_legal_hold(&hold).unwrap());
+        assert_eq!(db.list_legal_holds().unwrap(), vec![hold]);
+
+        assert!(matches!(
+            db.delete_patient(&patient.local_id),
+            Err(DbError::LegalHold { .. })
+        ));
+        assert!(matches!(
+            db.delete_draft(&draft.draft_id),
+            Err(DbError::LegalHold { .. })
+        ));
+
+        // The trigger catches paths that bypass the check
+        assert!(db
+            .conn()
+            .execute(
+                "DELETE FROM encounter_drafts WHERE draft_id = ?",
+                [&draft.draft_id]
+            )
+            .is_err());
+
+        assert!(db
+            .release_legal_hold(HoldSubject::Patient, &patient.local_id)
+            .unwrap());
+        assert!(db.delete_draft(&draft.draft_id).unwrap());
+        assert!(db.delete_patient(&patient.local_id).unwrap());
+    }
+
+    #[test]
+    fn test_encounter_hold_blocks_transcript_changes() {
+        let db = Database::open_in_memory().unwrap();
+        let patient = Patient::new("Max".into(), "canine".into());
+        db.insert_patient(&patient).unwrap();
+        let mut draft = EncounterDraft::new(patient.local_id.clone());
+        draft.transcript = "Gave carprofen 100mg and cerenia".into();
+        db.insert_draft(&draft).unwrap();
+
+        db.place_legal_hold(&LegalHold::new(
+            HoldSubject::Encounter,
+            draft.draft_id.clone(),
+            "Dispute".into(),
+            "Dr. Smith".into(),
+        ))
+        .unwrap();
+
+        // Other edits are still allowed, so review can finish
+        draft.touch();
+        assert!(db.update_draft(&draft).unwrap());
+
+        draft.transcript = "Gave carprofen".into();
+        assert!(matches!(
+            db.update_draft(&draft),
+            Err(DbError::LegalHold { .. })
+        ));
+
+        // Only the encounter is held, not the patient's other drafts
+        let other = EncounterDraft::new(patient.local_id.clone());
+        db.insert_draft(&other).unwrap();
+        assert!(db.delete_draft(&other.draft_id).unwrap());
+    }
+}
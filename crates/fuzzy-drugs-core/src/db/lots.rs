@@ -0,0 +1,101 @@
+//! Manufacturer lots on hand, for lot and expiry recording.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbResult};
+use crate::models::InventoryLot;
+
+impl Database {
+    /// Insert a lot, or update its expiration date.
+    pub fn upsert_lot(&self, lot: &InventoryLot) -> DbResult<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO inventory_lots (sku, lot_number, expiration_date, updated_at)
+            VALUES (?1, ?2, ?3, datetime('now'))
+            ON CONFLICT(sku, lot_number) DO UPDATE SET
+                expiration_date = excluded.expiration_date,
+                updated_at = datetime('now')
+            "#,
+            params![lot.sku, lot.lot_number, lot.expiration_date],
+        )?;
+        Ok(())
+    }
+
+    /// Get a lot of an item.
+    pub fn get_lot(&self, sku: &str, lot_number: &str) -> DbResult<Option<InventoryLot>> {
+        let sql = format!(
+            "SELECT {} FROM inventory_lots WHERE sku = ? AND lot_number = ?",
+            LOT_COLUMNS
+        );
+        Ok(self
+            .conn
+            .prepare_cached(&sql)?
+            .query_row(params![sku, lot_number], lot_row)
+            .optional()?)
+    }
+
+    /// List an item's lots, soonest to expire first.
+    pub fn list_lots(&self, sku: &str) -> DbResult<Vec<InventoryLot>> {
+        let sql = format!(
+            "SELECT {} FROM inventory_lots WHERE sku = ?
+             ORDER BY expiration_date, lot_number",
+            LOT_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let lots = stmt.query_map([sku], lot_row)?.collect::<Result<_, _>>()?;
+        Ok(lots)
+    }
+
+    /// Delete a lot. Encounters that recorded it keep the lot number.
+    pub fn delete_lot(&self, sku: &str, lot_number: &str) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            "DELETE FROM inventory_lots WHERE sku = ? AND lot_number = ?",
+            params![sku, lot_number],
+        )?;
+        Ok(rows_affected > 0)
+    }
+}
+
+/// Columns selected for a lot, in [`lot_row`] order.
+const LOT_COLUMNS: &str = "sku, lot_number, expiration_date";
+
+/// Map a row selected with [`LOT_COLUMNS`].
+fn lot_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<InventoryLot> {
+    Ok(InventoryLot {
+        sku: row.get(0)?,
+        lot_number: row.get(1)?,
+        expiration_date: row.get(2)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lot_crud() {
+        let db = Database::open_in_memory().unwrap();
+        let lot = |number: &str, expires: &str| {
+            InventoryLot::new("VAC-RABIES".into(), number.into(), expires.into())
+        };
+        db.upsert_lot(&lot("R2", "2027-01-31")).unwrap();
+        db.upsert_lot(&lot("R1", "2026-12-31")).unwrap();
+        db.upsert_lot(&lot("R2", "2026-11-30")).unwrap();
+
+        assert_eq!(
+            db.get_lot("VAC-RABIES", "R2").unwrap(),
+            Some(lot("R2", "2026-11-30"))
+        );
+        assert_eq!(db.get_lot("VAC-DHPP", "R2").unwrap(), None);
+        let numbers: Vec<String> = db
+            .list_lots("VAC-RABIES")
+            .unwrap()
+            .into_iter()
+            .map(|l| l.lot_number)
+            .collect();
+        assert_eq!(numbers, ["R2", "R1"]);
+
+        assert!(db.delete_lot("VAC-RABIES", "R1").unwrap());
+        assert!(!db.delete_lot("VAC-RABIES", "R1").unwrap());
+    }
+}
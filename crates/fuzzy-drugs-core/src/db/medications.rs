@@ -0,0 +1,232 @@
+//! Patient medication lists.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbResult};
+use crate::models::{MedicationSource, PatientMedication, ReviewedEncounter};
+
+impl Database {
+    /// Add a medication to a patient's list. Returns its row ID.
+    pub fn add_patient_medication(&self, medication: &PatientMedication) -> DbResult<i64> {
+        self.conn.execute(
+            r#"
+            INSERT INTO patient_medications
+                (patient_id, sku, name, start_date, end_date, source, draft_id, notes)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                medication.patient_id,
+                medication.sku,
+                medication.name,
+                medication.start_date,
+                medication.end_date,
+                medication.source.as_str(),
+                medication.draft_id,
+                medication.notes,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Get a medication by row ID.
+    pub fn get_patient_medication(&self, id: i64) -> DbResult<Option<PatientMedication>> {
+        let sql = format!(
+            "SELECT {} FROM patient_medications WHERE id = ?",
+            MEDICATION_COLUMNS
+        );
+        Ok(self.conn.query_row(&sql, [id], medication_row).optional()?)
+    }
+
+    /// Add the drugs on a committed encounter, starting on the day it was
+    /// reviewed. Line items that are services (vaccines, procedures) are
+    /// skipped.
+    ///
+    /// Opens no transaction of its own; the commit runs it in the one that
+    /// adds the encounter leaf.
+    pub fn record_encounter_medications(&self, encounter: &ReviewedEncounter) -> DbResult<()> {
+        let start = chrono::DateTime::parse_from_rfc3339(&encounter.reviewed_at)
+            .map(|t| t.date_naive())
+            .unwrap_or_else(|_| chrono::Utc::now().date_naive());
+        for item in &encounter.line_items {
+            if self.get_service_item(&item.sku)?.is_some() {
+                continue;
+            }
+            let medication = PatientMedication::from_line_item(
+                &encounter.patient_id,
+                &encounter.draft_id,
+                item,
+                start,
+            );
+            self.add_patient_medication(&medication)?;
+        }
+        Ok(())
+    }
+
+    /// A patient's medications active on `date` (YYYY-MM-DD), most recently
+    /// started first.
+    pub fn list_active_medications(
+        &self,
+        patient_id: &str,
+        date: &str,
+    ) -> DbResult<Vec<PatientMedication>> {
+        let sql = format!(
+            "SELECT {} FROM patient_medications
+             WHERE patient_id = ?1 AND start_date <= ?2 AND (end_date IS NULL OR end_date >= ?2)
+             ORDER BY start_date DESC, id DESC",
+            MEDICATION_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let medications = stmt
+            .query_map(params![patient_id, date], medication_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(medications)
+    }
+
+    /// A patient's whole medication history, most recently started first.
+    pub fn list_patient_medications(&self, patient_id: &str) -> DbResult<Vec<PatientMedication>> {
+        let sql = format!(
+            "SELECT {} FROM patient_medications WHERE patient_id = ?
+             ORDER BY start_date DESC, id DESC",
+            MEDICATION_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let medications = stmt
+            .query_map([patient_id], medication_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(medications)
+    }
+
+    /// Set the last day of a medication (stopping it, or reopening it with
+    /// `None`). Returns false if the ID is unknown.
+    pub fn set_medication_end_date(&self, id: i64, end_date: Option<&str>) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            "UPDATE patient_medications SET end_date = ?2 WHERE id = ?1",
+            params![id, end_date],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Delete a medication (e.g., entered by mistake).
+    pub fn delete_patient_medication(&self, id: i64) -> DbResult<bool> {
+        let rows_affected = self
+            .conn
+            .execute("DELETE FROM patient_medications WHERE id = ?", [id])?;
+        Ok(rows_affected > 0)
+    }
+}
+
+/// Columns selected for a medication, in [`medication_row`] order.
+const MEDICATION_COLUMNS: &str =
+    "id, patient_id, sku, name, start_date, end_date, source, draft_id, notes";
+
+/// Map a row selected with [`MEDICATION_COLUMNS`].
+fn medication_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<PatientMedication> {
+    let source: String = row.get(6)?;
+    Ok(PatientMedication {
+        id: row.get(0)?,
+        patient_id: row.get(1)?,
+        sku: row.get(2)?,
+        name: row.get(3)?,
+        start_date: row.get(4)?,
+        end_date: row.get(5)?,
+        source: MedicationSource::parse(&source).unwrap_or(MedicationSource::Manual),
+        draft_id: row.get(7)?,
+        notes: row.get(8)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EncounterLineItem, Patient, ResolutionMethod, ServiceItem, ServiceKind};
+
+    fn line_item(sku: &str, name: &str) -> EncounterLineItem {
+        EncounterLineItem {
+            sku: sku.into(),
+            name: name.into(),
+            quantity: 1.0,
+            unit: "each".into(),
+            route: None,
+            original_mention: name.into(),
+            resolution_method: ResolutionMethod::SystemApproved { confidence: 0.9 },
+            controlled_schedule: None,
+            source_spans: vec![],
+            schedule: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        }
+    }
+
+    #[test]
+    fn test_encounter_and_manual_medications() {
+        let db = Database::open_in_memory().unwrap();
+        let patient = Patient::new("Max".into(), "canine".into());
+        db.insert_patient(&patient).unwrap();
+        let vaccine = ServiceItem::new("VAC-RABIES".into(), "Rabies".into(), ServiceKind::Vaccine);
+        db.upsert_service_item(&vaccine).unwrap();
+
+        let encounter = ReviewedEncounter {
+            draft_id: "draft-1".into(),
+            patient_id: patient.local_id.clone(),
+            patient_server_id: None,
+            transcript: String::new(),
+            line_items: vec![
+                line_item("CARP-100", "Carprofen 100mg"),
+                line_item("VAC-RABIES", "Rabies vaccine"),
+            ],
+            reviewed_by: "Dr. Smith".into(),
+            reviewed_at: "2026-03-01T10:00:00Z".into(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        };
+        db.record_encounter_medications(&encounter).unwrap();
+
+        let mut manual = PatientMedication::new(
+            patient.local_id.clone(),
+            "Levothyroxine".into(),
+            "2025-06-01".into(),
+        );
+        manual.id = db.add_patient_medication(&manual).unwrap();
+        assert_eq!(
+            db.get_patient_medication(manual.id).unwrap(),
+            Some(manual.clone())
+        );
+
+        let active = db
+            .list_active_medications(&patient.local_id, "2026-03-14")
+            .unwrap();
+        let names: Vec<&str> = active.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["Carprofen 100mg", "Levothyroxine"]);
+        assert_eq!(active[0].source, MedicationSource::Encounter);
+        assert_eq!(active[0].draft_id.as_deref(), Some("draft-1"));
+
+        let active = db
+            .list_active_medications(&patient.local_id, "2026-03-15")
+            .unwrap();
+        assert_eq!(active, vec![manual.clone()]);
+
+        assert!(db
+            .set_medication_end_date(manual.id, Some("2026-01-31"))
+            .unwrap());
+        assert!(db
+            .list_active_medications(&patient.local_id, "2026-03-15")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.list_patient_medications(&patient.local_id)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        assert!(db.delete_patient_medication(manual.id).unwrap());
+        assert!(!db.delete_patient_medication(manual.id).unwrap());
+    }
+}
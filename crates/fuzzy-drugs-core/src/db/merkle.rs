@@ -12,7 +12,6 @@ pub enum MerkleNodeType {
 }
 
 impl MerkleNodeType {
-    #[allow(dead_code)]
     fn as_str(&self) -> &'static str {
         match self {
             MerkleNodeType::Leaf => "leaf",
@@ -76,26 +75,25 @@ impl Database {
     /// Get a Merkle node by hash.
     pub fn get_merkle_node(&self, hash: &str) -> DbResult<Option<MerkleNode>> {
         self.conn
-            .query_row(
+            .prepare_cached(
                 r#"
                 SELECT hash, node_type, left_child, right_child, payload, created_at
                 FROM merkle_nodes
                 WHERE hash = ?
                 "#,
-                [hash],
-                |row| {
-                    let node_type_str: String = row.get(1)?;
-                    Ok(MerkleNode {
-                        hash: row.get(0)?,
-                        node_type: MerkleNodeType::from_str(&node_type_str)
-                            .unwrap_or(MerkleNodeType::Leaf),
-                        left_child: row.get(2)?,
-                        right_child: row.get(3)?,
-                        payload: row.get(4)?,
-                        created_at: row.get(5)?,
-                    })
-                },
-            )
+            )?
+            .query_row([hash], |row| {
+                let node_type_str: String = row.get(1)?;
+                Ok(MerkleNode {
+                    hash: row.get(0)?,
+                    node_type: MerkleNodeType::from_str(&node_type_str)
+                        .unwrap_or(MerkleNodeType::Leaf),
+                    left_child: row.get(2)?,
+                    right_child: row.get(3)?,
+                    payload: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            })
             .optional()
             .map_err(Into::into)
     }
@@ -155,6 +153,53 @@ impl Database {
         rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
+    /// Hash of the encounter leaf committed for a draft, if any.
+    pub fn find_encounter_leaf(&self, draft_id: &str) -> DbResult<Option<String>> {
+        self.conn
+            .query_row(
+                r#"
+                SELECT hash FROM merkle_nodes
+                WHERE node_type = 'leaf'
+                  AND json_valid(payload)
+                  AND json_extract(payload, '$.draft_id') = ?
+                ORDER BY created_at
+                LIMIT 1
+                "#,
+                [draft_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
+    /// Encounter leaves committed for a patient, newest first.
+    pub fn get_encounter_leaves_for_patient(&self, patient_id: &str) -> DbResult<Vec<MerkleNode>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT hash, node_type, left_child, right_child, payload, created_at
+            FROM merkle_nodes
+            WHERE node_type = 'leaf'
+              AND json_valid(payload)
+              AND json_extract(payload, '$.patient_id') = ?
+            ORDER BY created_at DESC, rowid DESC
+            "#,
+        )?;
+
+        let rows = stmt.query_map([patient_id], |row| {
+            let node_type_str: String = row.get(1)?;
+            Ok(MerkleNode {
+                hash: row.get(0)?,
+                node_type: MerkleNodeType::from_str(&node_type_str).unwrap_or(MerkleNodeType::Leaf),
+                left_child: row.get(2)?,
+                right_child: row.get(3)?,
+                payload: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
     /// Get nodes created after a given timestamp.
     pub fn get_nodes_since(&self, since: &str) -> DbResult<Vec<MerkleNode>> {
         let mut stmt = self.conn.prepare(
@@ -162,7 +207,7 @@ impl Database {
             SELECT hash, node_type, left_child, right_child, payload, created_at
             FROM merkle_nodes
             WHERE created_at > ?
-            ORDER BY created_at
+            ORDER BY created_at, rowid
             "#,
         )?;
 
@@ -170,8 +215,7 @@ impl Database {
             let node_type_str: String = row.get(1)?;
             Ok(MerkleNode {
                 hash: row.get(0)?,
-                node_type: MerkleNodeType::from_str(&node_type_str)
-                    .unwrap_or(MerkleNodeType::Leaf),
+                node_type: MerkleNodeType::from_str(&node_type_str).unwrap_or(MerkleNodeType::Leaf),
                 left_child: row.get(2)?,
                 right_child: row.get(3)?,
                 payload: row.get(4)?,
@@ -182,6 +226,50 @@ impl Database {
         rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
+    /// Nodes inserted after `hash`, in insertion order; `None` if `hash` is
+    /// unknown.
+    ///
+    /// Ordered by insertion rather than `created_at`, so nodes written in the
+    /// same second as `hash` are not missed. Every leaf under a root was
+    /// inserted before it, so passing an old root yields the leaves committed
+    /// since.
+    pub fn get_nodes_after(&self, hash: &str) -> DbResult<Option<Vec<MerkleNode>>> {
+        let position: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT rowid FROM merkle_nodes WHERE hash = ?",
+                [hash],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(position) = position else {
+            return Ok(None);
+        };
+
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT hash, node_type, left_child, right_child, payload, created_at
+            FROM merkle_nodes
+            WHERE rowid > ?
+            ORDER BY rowid
+            "#,
+        )?;
+
+        let rows = stmt.query_map([position], |row| {
+            let node_type_str: String = row.get(1)?;
+            Ok(MerkleNode {
+                hash: row.get(0)?,
+                node_type: MerkleNodeType::from_str(&node_type_str).unwrap_or(MerkleNodeType::Leaf),
+                left_child: row.get(2)?,
+                right_child: row.get(3)?,
+                payload: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })?;
+
+        Ok(Some(rows.collect::<Result<Vec<_>, _>>()?))
+    }
+
     /// Get nodes by list of hashes (for sync).
     pub fn get_nodes_by_hashes(&self, hashes: &[String]) -> DbResult<Vec<MerkleNode>> {
         if hashes.is_empty() {
@@ -199,37 +287,101 @@ impl Database {
         );
 
         let mut stmt = self.conn.prepare(&sql)?;
-        let rows = stmt.query_map(
-            rusqlite::params_from_iter(hashes.iter()),
-            |row| {
-                let node_type_str: String = row.get(1)?;
-                Ok(MerkleNode {
-                    hash: row.get(0)?,
-                    node_type: MerkleNodeType::from_str(&node_type_str)
-                        .unwrap_or(MerkleNodeType::Leaf),
-                    left_child: row.get(2)?,
-                    right_child: row.get(3)?,
-                    payload: row.get(4)?,
-                    created_at: row.get(5)?,
-                })
-            },
-        )?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(hashes.iter()), |row| {
+            let node_type_str: String = row.get(1)?;
+            Ok(MerkleNode {
+                hash: row.get(0)?,
+                node_type: MerkleNodeType::from_str(&node_type_str).unwrap_or(MerkleNodeType::Leaf),
+                left_child: row.get(2)?,
+                right_child: row.get(3)?,
+                payload: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })?;
 
         rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
-    /// Get sync state value.
-    pub fn get_sync_state(&self, key: &str) -> DbResult<Option<String>> {
+    /// Hold a node received for the tree under `expected_root` until that
+    /// tree is complete (PIMS side, see `merkle::server`).
+    pub fn stage_merkle_node(&self, expected_root: &str, node: &MerkleNode) -> DbResult<()> {
+        self.conn.execute(
+            r#"
+            INSERT OR REPLACE INTO merkle_staging
+                (hash, expected_root, node_type, left_child, right_child, payload)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            params![
+                node.hash,
+                expected_root,
+                node.node_type.as_str(),
+                node.left_child,
+                node.right_child,
+                node.payload
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get a staged node by hash.
+    pub fn get_staged_merkle_node(&self, hash: &str) -> DbResult<Option<MerkleNode>> {
         self.conn
             .query_row(
-                "SELECT value FROM sync_state WHERE key = ?",
-                [key],
-                |row| row.get(0),
+                r#"
+                SELECT hash, node_type, left_child, right_child, payload, received_at
+                FROM merkle_staging
+                WHERE hash = ?
+                "#,
+                [hash],
+                |row| {
+                    let node_type_str: String = row.get(1)?;
+                    Ok(MerkleNode {
+                        hash: row.get(0)?,
+                        node_type: MerkleNodeType::from_str(&node_type_str)
+                            .unwrap_or(MerkleNodeType::Leaf),
+                        left_child: row.get(2)?,
+                        right_child: row.get(3)?,
+                        payload: row.get(4)?,
+                        created_at: row.get(5)?,
+                    })
+                },
             )
             .optional()
             .map_err(Into::into)
     }
 
+    /// Drop staged nodes, except those for the tree under `keep_root`.
+    pub fn clear_staged_merkle_nodes(&self, keep_root: Option<&str>) -> DbResult<usize> {
+        Ok(self.conn.execute(
+            "DELETE FROM merkle_staging WHERE expected_root IS NOT ?",
+            [keep_root],
+        )?)
+    }
+
+    /// Number of leaves inserted after the node `hash` (every leaf if
+    /// `None` or unknown).
+    pub fn count_leaves_after(&self, hash: Option<&str>) -> DbResult<u32> {
+        Ok(self.conn.query_row(
+            r#"
+            SELECT COUNT(*) FROM merkle_nodes
+            WHERE node_type = 'leaf'
+              AND rowid > COALESCE((SELECT rowid FROM merkle_nodes WHERE hash = ?), 0)
+            "#,
+            [hash],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Get sync state value.
+    pub fn get_sync_state(&self, key: &str) -> DbResult<Option<String>> {
+        self.conn
+            .query_row("SELECT value FROM sync_state WHERE key = ?", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(Into::into)
+    }
+
     /// Set sync state value.
     pub fn set_sync_state(&self, key: &str, value: &str) -> DbResult<()> {
         self.conn.execute(
@@ -327,6 +479,47 @@ mod tests {
         assert_eq!(hashes[2], "leaf3");
     }
 
+    #[test]
+    fn test_encounter_leaves_for_patient() {
+        let db = setup_db();
+
+        db.insert_merkle_leaf("enc1", r#"{"draft_id":"d1","patient_id":"p1"}"#)
+            .unwrap();
+        db.insert_merkle_leaf("enc2", r#"{"draft_id":"d2","patient_id":"p2"}"#)
+            .unwrap();
+        db.insert_merkle_leaf("enc3", r#"{"draft_id":"d3","patient_id":"p1"}"#)
+            .unwrap();
+        db.insert_merkle_leaf("audit", r#"{"subject_id":"p1"}"#)
+            .unwrap();
+
+        let leaves = db.get_encounter_leaves_for_patient("p1").unwrap();
+        let hashes: Vec<_> = leaves.iter().map(|node| node.hash.as_str()).collect();
+        assert_eq!(hashes, vec!["enc3", "enc1"]);
+        assert!(db
+            .get_encounter_leaves_for_patient("p3")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_nodes_after() {
+        let db = setup_db();
+
+        db.insert_merkle_leaf("leaf1", "p1").unwrap();
+        db.insert_merkle_leaf("leaf2", "p2").unwrap();
+        db.insert_merkle_internal("root", "leaf1", Some("leaf2"))
+            .unwrap();
+        db.insert_merkle_leaf("leaf3", "p3").unwrap();
+
+        // Same second as the root, still returned
+        let nodes = db.get_nodes_after("root").unwrap().unwrap();
+        let hashes: Vec<_> = nodes.iter().map(|node| node.hash.as_str()).collect();
+        assert_eq!(hashes, vec!["leaf3"]);
+        assert!(db.get_nodes_after("unknown").unwrap().is_none());
+        assert_eq!(db.count_leaves_after(Some("root")).unwrap(), 1);
+        assert_eq!(db.count_leaves_after(None).unwrap(), 3);
+    }
+
     #[test]
     fn test_sync_state() {
         let db = setup_db();
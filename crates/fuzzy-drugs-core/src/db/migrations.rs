@@ -0,0 +1,248 @@
+//! Schema upgrades for databases created by earlier versions.
+//!
+//! [`SCHEMA`] only uses `CREATE ... IF NOT EXISTS`, which adds new tables
+//! and indexes but never changes a table that already exists. Columns added
+//! to an existing table therefore also need a migration here. The version a
+//! database is at lives in `PRAGMA user_version`.
+
+use rusqlite::Connection;
+
+use super::{Database, DbError, DbResult, SCHEMA};
+
+/// Schema version written by this build.
+pub const SCHEMA_VERSION: i32 = 1;
+
+/// Columns added since the unversioned schema, as `(table, column, definition)`.
+///
+/// Version 0 covers every database opened before versioning, so some of
+/// these may already exist; only the missing ones are added. Tables that
+/// don't exist yet are left to [`SCHEMA`].
+const V1_COLUMNS: &[(&str, &str, &str)] = &[
+    (
+        "inventory_catalog",
+        "withdrawal_times",
+        "TEXT NOT NULL DEFAULT '[]'",
+    ),
+    (
+        "inventory_catalog",
+        "components",
+        "TEXT NOT NULL DEFAULT '[]'",
+    ),
+    ("inventory_catalog", "controlled_schedule", "TEXT"),
+    ("inventory_catalog", "unit_price", "REAL"),
+    ("inventory_catalog", "markup", "REAL"),
+    ("inventory_catalog", "minimum_charge", "REAL"),
+    ("inventory_catalog", "tax_code", "TEXT"),
+    (
+        "inventory_catalog",
+        "origin",
+        "TEXT NOT NULL DEFAULT 'pims' CHECK (origin IN ('pims', 'local'))",
+    ),
+    ("inventory_catalog", "dirty", "INTEGER NOT NULL DEFAULT 0"),
+    ("inventory_catalog", "quantity_on_hand", "REAL"),
+    ("inventory_catalog", "reorder_point", "REAL"),
+    (
+        "patients",
+        "client_id",
+        "TEXT REFERENCES clients(client_id) ON DELETE SET NULL",
+    ),
+    ("patients", "allergies", "TEXT NOT NULL DEFAULT '[]'"),
+    (
+        "encounter_drafts",
+        "manual_items",
+        "TEXT NOT NULL DEFAULT '[]'",
+    ),
+    (
+        "encounter_drafts",
+        "interaction_warnings",
+        "TEXT NOT NULL DEFAULT '[]'",
+    ),
+    (
+        "encounter_drafts",
+        "reported_medications",
+        "TEXT NOT NULL DEFAULT '[]'",
+    ),
+    (
+        "encounter_drafts",
+        "service_items",
+        "TEXT NOT NULL DEFAULT '[]'",
+    ),
+    (
+        "encounter_drafts",
+        "visit_id",
+        "TEXT REFERENCES visits(visit_id) ON DELETE SET NULL",
+    ),
+    ("users", "roles", "TEXT NOT NULL DEFAULT '[]'"),
+];
+
+/// The catalog's full-text index gained `components` in version 1. An FTS5
+/// table can't be altered, so it and its triggers are dropped here, created
+/// again by [`SCHEMA`], and then rebuilt from the catalog.
+const V1_DROP_CATALOG_FTS: &str = r#"
+DROP TRIGGER IF EXISTS inventory_catalog_ai;
+DROP TRIGGER IF EXISTS inventory_catalog_ad;
+DROP TRIGGER IF EXISTS inventory_catalog_au;
+DROP TABLE IF EXISTS inventory_catalog_fts;
+"#;
+
+const REBUILD_CATALOG_FTS: &str =
+    "INSERT INTO inventory_catalog_fts(inventory_catalog_fts) VALUES ('rebuild')";
+
+impl Database {
+    /// Bring the schema up to [`SCHEMA_VERSION`], in one transaction.
+    pub(super) fn migrate(&self) -> DbResult<()> {
+        let version: i32 = self
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(DbError::Constraint(format!(
+                "Database schema version {} is newer than this build supports ({})",
+                version, SCHEMA_VERSION
+            )));
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        // A database without the catalog table is new: SCHEMA creates it
+        // at the current version.
+        let upgrading = version < SCHEMA_VERSION && table_exists(&tx, "inventory_catalog")?;
+        if upgrading && version < 1 {
+            for (table, column, definition) in V1_COLUMNS {
+                if table_exists(&tx, table)? && !column_exists(&tx, table, column)? {
+                    tx.execute_batch(&format!(
+                        "ALTER TABLE {} ADD COLUMN {} {}",
+                        table, column, definition
+                    ))?;
+                }
+            }
+            tx.execute_batch(V1_DROP_CATALOG_FTS)?;
+        }
+        tx.execute_batch(SCHEMA)?;
+        if upgrading && version < 1 {
+            tx.execute_batch(REBUILD_CATALOG_FTS)?;
+        }
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn table_exists(conn: &Connection, table: &str) -> DbResult<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> DbResult<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SCHEMA as it was before versioning.
+    const SCHEMA_V0: &str = include_str!("testdata/schema_v0.sql");
+
+    fn baseline_database(path: &std::path::Path) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(SCHEMA_V0).unwrap();
+        conn.execute(
+            "INSERT INTO inventory_catalog (sku, name, aliases) VALUES (?1, ?2, ?3)",
+            ["CARP-100", "Carprofen 100mg", r#"["rimadyl"]"#],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO patients (local_id, name, species) VALUES ('p1', 'Max', 'canine')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO encounter_drafts (draft_id, patient_id, transcript, status)
+             VALUES ('d1', 'p1', 'Gave carprofen', 'pending_review')",
+            [],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_upgrades_baseline_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("baseline.db");
+        baseline_database(&path);
+
+        let db = Database::open(&path).unwrap();
+        let version: i32 = db
+            .conn()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+
+        // Existing rows read back through the current columns
+        let item = db.get_catalog_item("CARP-100").unwrap().unwrap();
+        assert_eq!(item.aliases, vec!["rimadyl".to_string()]);
+        assert!(item.controlled_schedule.is_none());
+        let patient = db.get_patient("p1").unwrap().unwrap();
+        assert!(patient.allergies.is_empty());
+        let draft = db.get_draft("d1").unwrap().unwrap();
+        assert!(draft.manual_items.is_empty());
+        assert!(draft.visit_id.is_none());
+
+        // The rebuilt full-text index covers old rows and new columns
+        let hits: i64 = db
+            .conn()
+            .query_row(
+                "SELECT COUNT(*) FROM inventory_catalog_fts WHERE inventory_catalog_fts MATCH 'rimadyl'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hits, 1);
+        db.conn()
+            .execute(
+                "UPDATE inventory_catalog SET components = '[\"meloxicam\"]' WHERE sku = 'CARP-100'",
+                [],
+            )
+            .unwrap();
+        let hits: i64 = db
+            .conn()
+            .query_row(
+                "SELECT COUNT(*) FROM inventory_catalog_fts WHERE inventory_catalog_fts MATCH 'meloxicam'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hits, 1);
+
+        // The reporting views query the added columns
+        let rows: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM v_inventory", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 1);
+
+        // Opening again is a no-op
+        drop(db);
+        Database::open(&path).unwrap();
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("newer.db");
+        Database::open(&path).unwrap();
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+
+        assert!(matches!(Database::open(&path), Err(DbError::Constraint(_))));
+    }
+}
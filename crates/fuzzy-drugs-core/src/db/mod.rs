@@ -1,24 +1,52 @@
 //! Database layer for fuzzy-drugs.
 
-mod schema;
 mod catalog;
-mod patients;
+mod clients;
+mod device;
 mod drafts;
+mod escalation;
+mod export_ledger;
+mod extraction_cache;
+mod extraction_debug;
+mod health;
+mod interactions;
+mod legal_holds;
+mod lots;
+mod medications;
 mod merkle;
+mod migrations;
+mod outbox;
+mod patients;
+mod reporting;
+mod schema;
+mod scoring;
+mod services;
+mod settings;
+mod stock;
+mod transcripts;
+mod users;
+mod vaccinations;
+mod visits;
 
-pub use schema::*;
 #[allow(unused_imports)]
 pub use catalog::*;
 #[allow(unused_imports)]
-pub use patients::*;
-#[allow(unused_imports)]
 pub use drafts::*;
+pub use extraction_cache::EXTRACTION_CACHE_CAPACITY;
 pub use merkle::*;
+pub use migrations::SCHEMA_VERSION;
+#[allow(unused_imports)]
+pub use patients::*;
+pub use reporting::REPORTING_VIEWS_VERSION;
+pub use schema::*;
 
 use rusqlite::Connection;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+use crate::limits::Limits;
+use crate::models::ExtractionDebugConfig;
+
 /// Database errors.
 #[derive(Error, Debug)]
 pub enum DbError {
@@ -33,20 +61,58 @@ pub enum DbError {
 
     #[error("Constraint violation: {0}")]
     Constraint(String),
+
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Legal hold: {subject_type} {subject_id} is under legal hold ({reason})")]
+    LegalHold {
+        subject_type: String,
+        subject_id: String,
+        reason: String,
+    },
+
+    #[error("In use: {message}")]
+    InUse {
+        subject_type: String,
+        subject_id: String,
+        message: String,
+    },
+
+    #[error(transparent)]
+    Cancelled(#[from] crate::progress::Cancelled),
+
+    #[error("Compression error: {0}")]
+    Compression(#[from] std::io::Error),
 }
 
 pub type DbResult<T> = Result<T, DbError>;
 
+/// Prepared statements kept per connection (rusqlite's default is 16).
+///
+/// Lookups on the resolve and review paths use `prepare_cached`; statements
+/// built with `format!` share the cache as long as the SQL text is the same.
+pub const STATEMENT_CACHE_CAPACITY: usize = 64;
+
 /// Database connection wrapper.
 pub struct Database {
     conn: Connection,
+    path: Option<PathBuf>,
+    limits: Limits,
+    extraction_debug: ExtractionDebugConfig,
 }
 
 impl Database {
     /// Open database at path, creating if needed.
     pub fn open<P: AsRef<Path>>(path: P) -> DbResult<Self> {
-        let conn = Connection::open(path)?;
-        let db = Self { conn };
+        let path = path.as_ref().to_path_buf();
+        let conn = Connection::open(&path)?;
+        let db = Self {
+            conn,
+            path: Some(path),
+            limits: Limits::default(),
+            extraction_debug: ExtractionDebugConfig::default(),
+        };
         db.initialize()?;
         Ok(db)
     }
@@ -54,14 +120,25 @@ impl Database {
     /// Create in-memory database (for testing).
     pub fn open_in_memory() -> DbResult<Self> {
         let conn = Connection::open_in_memory()?;
-        let db = Self { conn };
+        let db = Self {
+            conn,
+            path: None,
+            limits: Limits::default(),
+            extraction_debug: ExtractionDebugConfig::default(),
+        };
         db.initialize()?;
         Ok(db)
     }
 
     /// Initialize schema.
     fn initialize(&self) -> DbResult<()> {
-        self.conn.execute_batch(SCHEMA)?;
+        self.conn
+            .set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        // Outside the migration transaction, where this pragma is a no-op
+        self.conn.pragma_update(None, "foreign_keys", true)?;
+        self.migrate()?;
+        self.create_reporting_views()?;
+        self.ensure_device_identity()?;
         Ok(())
     }
 
@@ -70,6 +147,16 @@ impl Database {
         &self.conn
     }
 
+    /// Get the active size limits.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Replace the active size limits.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
     /// Begin a transaction.
     pub fn transaction(&mut self) -> DbResult<rusqlite::Transaction<'_>> {
         Ok(self.conn.transaction()?)
@@ -105,5 +192,6 @@ mod tests {
         assert!(tables.contains(&"encounter_drafts".to_string()));
         assert!(tables.contains(&"merkle_nodes".to_string()));
         assert!(tables.contains(&"merkle_root".to_string()));
+        assert!(tables.contains(&"draft_transcript_chunks".to_string()));
     }
 }
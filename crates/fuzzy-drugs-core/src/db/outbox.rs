@@ -0,0 +1,297 @@
+//! Sync outbox operations.
+//!
+//! Times are passed in rather than read from the clock so retries can be
+//! scheduled and tested deterministically. They are stored as RFC 3339 UTC
+//! with whole seconds, so string order is time order.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbError, DbResult};
+use crate::models::{OutboxItem, OutboxKind, OutboxStatus, RetryPolicy};
+
+const OUTBOX_COLUMNS: &str = "id, kind, subject_id, status, attempts, last_error, \
+                              next_attempt_at, created_at, updated_at";
+
+impl Database {
+    /// Queue an operation, due immediately. Queuing a subject that is already
+    /// queued resets its attempts, error, and status.
+    pub fn enqueue_outbox(
+        &self,
+        kind: OutboxKind,
+        subject_id: &str,
+        now: DateTime<Utc>,
+    ) -> DbResult<()> {
+        let now = outbox_time(now);
+        self.conn.execute(
+            r#"
+            INSERT INTO sync_outbox (kind, subject_id, next_attempt_at, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?3, ?3)
+            ON CONFLICT (kind, subject_id) DO UPDATE SET
+                status = 'pending',
+                attempts = 0,
+                last_error = NULL,
+                next_attempt_at = excluded.next_attempt_at,
+                updated_at = excluded.updated_at
+            "#,
+            params![kind.as_str(), subject_id, now],
+        )?;
+        Ok(())
+    }
+
+    /// Get a queued operation.
+    pub fn get_outbox_item(&self, id: i64) -> DbResult<Option<OutboxItem>> {
+        let row = self
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM sync_outbox WHERE id = ?", OUTBOX_COLUMNS),
+                [id],
+                outbox_row,
+            )
+            .optional()?;
+        row.map(OutboxItem::try_from).transpose()
+    }
+
+    /// Every queued operation, oldest first.
+    pub fn list_outbox(&self) -> DbResult<Vec<OutboxItem>> {
+        self.query_outbox("1 = 1", params![])
+    }
+
+    /// Pending operations whose next attempt is at or before `now`, oldest
+    /// first.
+    pub fn due_outbox_items(&self, now: DateTime<Utc>) -> DbResult<Vec<OutboxItem>> {
+        self.query_outbox(
+            "status = 'pending' AND next_attempt_at <= ?1",
+            params![outbox_time(now)],
+        )
+    }
+
+    /// Earliest next attempt among pending operations.
+    pub fn next_outbox_attempt(&self) -> DbResult<Option<String>> {
+        Ok(self.conn.query_row(
+            "SELECT MIN(next_attempt_at) FROM sync_outbox WHERE status = 'pending'",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Number of queued operations with `status`.
+    pub fn count_outbox(&self, status: OutboxStatus) -> DbResult<u32> {
+        Ok(self.conn.query_row(
+            "SELECT COUNT(*) FROM sync_outbox WHERE status = ?",
+            [status.as_str()],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Remove an operation that succeeded.
+    pub fn complete_outbox_item(&self, id: i64) -> DbResult<()> {
+        self.conn
+            .execute("DELETE FROM sync_outbox WHERE id = ?", [id])?;
+        Ok(())
+    }
+
+    /// Remove every queued operation of `kind` (e.g. encounter pushes after
+    /// a manual sync delivered the whole tree). Returns how many were removed.
+    pub fn complete_outbox_kind(&self, kind: OutboxKind) -> DbResult<usize> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM sync_outbox WHERE kind = ?", [kind.as_str()])?)
+    }
+
+    /// Record a failed attempt: schedule the next one per `policy`, or mark
+    /// the operation failed once the policy is exhausted.
+    pub fn fail_outbox_item(
+        &self,
+        id: i64,
+        error: &str,
+        now: DateTime<Utc>,
+        policy: &RetryPolicy,
+    ) -> DbResult<OutboxItem> {
+        let item = self
+            .get_outbox_item(id)?
+            .ok_or_else(|| DbError::NotFound(format!("Outbox item {}", id)))?;
+        let attempts = item.attempts + 1;
+        let status = if policy.is_exhausted(attempts) {
+            OutboxStatus::Failed
+        } else {
+            OutboxStatus::Pending
+        };
+        let next_attempt_at = outbox_time(now + policy.delay_after(attempts));
+        let updated_at = outbox_time(now);
+        self.conn.execute(
+            r#"
+            UPDATE sync_outbox
+            SET status = ?1, attempts = ?2, last_error = ?3, next_attempt_at = ?4, updated_at = ?5
+            WHERE id = ?6
+            "#,
+            params![
+                status.as_str(),
+                attempts,
+                error,
+                next_attempt_at,
+                updated_at,
+                id
+            ],
+        )?;
+        Ok(OutboxItem {
+            status,
+            attempts,
+            last_error: Some(error.to_string()),
+            next_attempt_at,
+            updated_at,
+            ..item
+        })
+    }
+
+    /// Make an operation due immediately with a fresh set of attempts
+    /// (e.g. a failed one, once the cause is fixed).
+    pub fn retry_outbox_item(&self, id: i64, now: DateTime<Utc>) -> DbResult<OutboxItem> {
+        let now = outbox_time(now);
+        let rows_affected = self.conn.execute(
+            r#"
+            UPDATE sync_outbox
+            SET status = 'pending', attempts = 0, next_attempt_at = ?1, updated_at = ?1
+            WHERE id = ?2
+            "#,
+            params![now, id],
+        )?;
+        if rows_affected == 0 {
+            return Err(DbError::NotFound(format!("Outbox item {}", id)));
+        }
+        self.get_outbox_item(id)?
+            .ok_or_else(|| DbError::NotFound(format!("Outbox item {}", id)))
+    }
+
+    fn query_outbox(
+        &self,
+        condition: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> DbResult<Vec<OutboxItem>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_outbox WHERE {} ORDER BY created_at, id",
+            OUTBOX_COLUMNS, condition
+        ))?;
+        let rows = stmt
+            .query_map(params, outbox_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter().map(OutboxItem::try_from).collect()
+    }
+}
+
+/// Stored form of an outbox timestamp.
+fn outbox_time(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn outbox_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<OutboxRow> {
+    Ok(OutboxRow {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        subject_id: row.get(2)?,
+        status: row.get(3)?,
+        attempts: row.get(4)?,
+        last_error: row.get(5)?,
+        next_attempt_at: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+/// Intermediate row struct for database mapping.
+struct OutboxRow {
+    id: i64,
+    kind: String,
+    subject_id: String,
+    status: String,
+    attempts: u32,
+    last_error: Option<String>,
+    next_attempt_at: String,
+    created_at: String,
+    updated_at: String,
+}
+
+impl TryFrom<OutboxRow> for OutboxItem {
+    type Error = DbError;
+
+    fn try_from(row: OutboxRow) -> DbResult<Self> {
+        let kind = OutboxKind::parse(&row.kind)
+            .ok_or_else(|| DbError::Constraint(format!("Unknown outbox kind: {}", row.kind)))?;
+        let status = OutboxStatus::parse(&row.status)
+            .ok_or_else(|| DbError::Constraint(format!("Unknown outbox status: {}", row.status)))?;
+        Ok(OutboxItem {
+            id: row.id,
+            kind,
+            subject_id: row.subject_id,
+            status,
+            attempts: row.attempts,
+            last_error: row.last_error,
+            next_attempt_at: row.next_attempt_at,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbox_backoff_and_failure() {
+        let db = Database::open_in_memory().unwrap();
+        let start = DateTime::parse_from_rfc3339("2024-01-15T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let policy = RetryPolicy {
+            base_delay_secs: 60,
+            max_delay_secs: 600,
+            max_attempts: 2,
+        };
+        db.enqueue_outbox(OutboxKind::PatientUpsert, "patient-1", start)
+            .unwrap();
+        db.enqueue_outbox(OutboxKind::EncounterPush, "leaf-1", start)
+            .unwrap();
+        // Re-queuing the same subject doesn't add a row
+        db.enqueue_outbox(OutboxKind::PatientUpsert, "patient-1", start)
+            .unwrap();
+        let due = db.due_outbox_items(start).unwrap();
+        assert_eq!(due.len(), 2);
+
+        let id = due[0].id;
+        let failed = db
+            .fail_outbox_item(id, "connection reset", start, &policy)
+            .unwrap();
+        assert_eq!(failed.attempts, 1);
+        assert_eq!(failed.status, OutboxStatus::Pending);
+        assert_eq!(failed.next_attempt_at, "2024-01-15T10:01:00Z");
+        assert_eq!(db.get_outbox_item(id).unwrap().unwrap(), failed);
+        assert_eq!(db.due_outbox_items(start).unwrap().len(), 1);
+        let later = start + chrono::Duration::seconds(60);
+        assert_eq!(db.due_outbox_items(later).unwrap().len(), 2);
+
+        let failed = db.fail_outbox_item(id, "HTTP 500", later, &policy).unwrap();
+        assert_eq!(failed.status, OutboxStatus::Failed);
+        assert_eq!(failed.last_error.as_deref(), Some("HTTP 500"));
+        let far_future = start + chrono::Duration::days(1);
+        assert_eq!(db.due_outbox_items(far_future).unwrap().len(), 1);
+        assert_eq!(db.count_outbox(OutboxStatus::Pending).unwrap(), 1);
+        assert_eq!(db.count_outbox(OutboxStatus::Failed).unwrap(), 1);
+
+        let retried = db.retry_outbox_item(id, later).unwrap();
+        assert_eq!(retried.status, OutboxStatus::Pending);
+        assert_eq!(retried.attempts, 0);
+        assert_eq!(retried.last_error.as_deref(), Some("HTTP 500"));
+
+        db.complete_outbox_item(id).unwrap();
+        assert_eq!(db.list_outbox().unwrap().len(), 1);
+        assert_eq!(
+            db.next_outbox_attempt().unwrap().as_deref(),
+            Some("2024-01-15T10:00:00Z")
+        );
+        assert!(matches!(
+            db.retry_outbox_item(id, later),
+            Err(DbError::NotFound(_))
+        ));
+    }
+}
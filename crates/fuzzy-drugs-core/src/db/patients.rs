@@ -8,12 +8,14 @@ use crate::models::Patient;
 impl Database {
     /// Insert a new patient.
     pub fn insert_patient(&self, patient: &Patient) -> DbResult<()> {
+        let allergies_json = serde_json::to_string(&patient.allergies)?;
         self.conn.execute(
             r#"
             INSERT INTO patients (
                 local_id, server_id, name, species, breed, weight_kg,
-                date_of_birth, owner_name, notes, created_at, updated_at
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                date_of_birth, owner_name, notes, created_at, updated_at, client_id,
+                allergies
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
             "#,
             params![
                 patient.local_id,
@@ -27,6 +29,8 @@ impl Database {
                 patient.notes,
                 patient.created_at,
                 patient.updated_at,
+                patient.client_id,
+                allergies_json,
             ],
         )?;
         Ok(())
@@ -34,6 +38,7 @@ impl Database {
 
     /// Update an existing patient.
     pub fn update_patient(&self, patient: &Patient) -> DbResult<bool> {
+        let allergies_json = serde_json::to_string(&patient.allergies)?;
         let rows_affected = self.conn.execute(
             r#"
             UPDATE patients SET
@@ -45,6 +50,8 @@ impl Database {
                 date_of_birth = ?7,
                 owner_name = ?8,
                 notes = ?9,
+                client_id = ?10,
+                allergies = ?11,
                 updated_at = datetime('now')
             WHERE local_id = ?1
             "#,
@@ -58,6 +65,8 @@ impl Database {
                 patient.date_of_birth,
                 patient.owner_name,
                 patient.notes,
+                patient.client_id,
+                allergies_json,
             ],
         )?;
         Ok(rows_affected > 0)
@@ -65,137 +74,103 @@ impl Database {
 
     /// Get a patient by local ID.
     pub fn get_patient(&self, local_id: &str) -> DbResult<Option<Patient>> {
-        self.conn
-            .query_row(
-                r#"
-                SELECT local_id, server_id, name, species, breed, weight_kg,
-                       date_of_birth, owner_name, notes, created_at, updated_at
-                FROM patients
-                WHERE local_id = ?
-                "#,
-                [local_id],
-                |row| {
-                    Ok(Patient {
-                        local_id: row.get(0)?,
-                        server_id: row.get(1)?,
-                        name: row.get(2)?,
-                        species: row.get(3)?,
-                        breed: row.get(4)?,
-                        weight_kg: row.get(5)?,
-                        date_of_birth: row.get(6)?,
-                        owner_name: row.get(7)?,
-                        notes: row.get(8)?,
-                        created_at: row.get(9)?,
-                        updated_at: row.get(10)?,
-                    })
-                },
-            )
-            .optional()
-            .map_err(Into::into)
+        let sql = format!(
+            "SELECT {} FROM patients WHERE local_id = ?",
+            PATIENT_COLUMNS
+        );
+        Ok(self
+            .conn
+            .prepare_cached(&sql)?
+            .query_row([local_id], patient_row)
+            .optional()?)
     }
 
     /// Get a patient by server ID.
     pub fn get_patient_by_server_id(&self, server_id: &str) -> DbResult<Option<Patient>> {
-        self.conn
-            .query_row(
-                r#"
-                SELECT local_id, server_id, name, species, breed, weight_kg,
-                       date_of_birth, owner_name, notes, created_at, updated_at
-                FROM patients
-                WHERE server_id = ?
-                "#,
-                [server_id],
-                |row| {
-                    Ok(Patient {
-                        local_id: row.get(0)?,
-                        server_id: row.get(1)?,
-                        name: row.get(2)?,
-                        species: row.get(3)?,
-                        breed: row.get(4)?,
-                        weight_kg: row.get(5)?,
-                        date_of_birth: row.get(6)?,
-                        owner_name: row.get(7)?,
-                        notes: row.get(8)?,
-                        created_at: row.get(9)?,
-                        updated_at: row.get(10)?,
-                    })
-                },
-            )
-            .optional()
-            .map_err(Into::into)
+        let sql = format!(
+            "SELECT {} FROM patients WHERE server_id = ?",
+            PATIENT_COLUMNS
+        );
+        Ok(self
+            .conn
+            .query_row(&sql, [server_id], patient_row)
+            .optional()?)
     }
 
     /// Search patients by name (prefix match).
     pub fn search_patients(&self, query: &str, limit: usize) -> DbResult<Vec<Patient>> {
         let pattern = format!("{}%", query);
-        let mut stmt = self.conn.prepare(
-            r#"
-            SELECT local_id, server_id, name, species, breed, weight_kg,
-                   date_of_birth, owner_name, notes, created_at, updated_at
-            FROM patients
-            WHERE name LIKE ?
-            ORDER BY name
-            LIMIT ?
-            "#,
-        )?;
-
-        let rows = stmt.query_map(params![pattern, limit as i64], |row| {
-            Ok(Patient {
-                local_id: row.get(0)?,
-                server_id: row.get(1)?,
-                name: row.get(2)?,
-                species: row.get(3)?,
-                breed: row.get(4)?,
-                weight_kg: row.get(5)?,
-                date_of_birth: row.get(6)?,
-                owner_name: row.get(7)?,
-                notes: row.get(8)?,
-                created_at: row.get(9)?,
-                updated_at: row.get(10)?,
-            })
-        })?;
-
+        let sql = format!(
+            "SELECT {} FROM patients WHERE name LIKE ? ORDER BY name LIMIT ?",
+            PATIENT_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![pattern, limit as i64], patient_row)?;
         rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
     /// List all patients.
     pub fn list_patients(&self) -> DbResult<Vec<Patient>> {
-        let mut stmt = self.conn.prepare(
-            r#"
-            SELECT local_id, server_id, name, species, breed, weight_kg,
-                   date_of_birth, owner_name, notes, created_at, updated_at
-            FROM patients
-            ORDER BY name
-            "#,
-        )?;
-
-        let rows = stmt.query_map([], |row| {
-            Ok(Patient {
-                local_id: row.get(0)?,
-                server_id: row.get(1)?,
-                name: row.get(2)?,
-                species: row.get(3)?,
-                breed: row.get(4)?,
-                weight_kg: row.get(5)?,
-                date_of_birth: row.get(6)?,
-                owner_name: row.get(7)?,
-                notes: row.get(8)?,
-                created_at: row.get(9)?,
-                updated_at: row.get(10)?,
-            })
-        })?;
+        let sql = format!("SELECT {} FROM patients ORDER BY name", PATIENT_COLUMNS);
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map([], patient_row)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
 
+    /// A client's patients, by name.
+    pub fn list_client_patients(&self, client_id: &str) -> DbResult<Vec<Patient>> {
+        let sql = format!(
+            "SELECT {} FROM patients WHERE client_id = ? ORDER BY name",
+            PATIENT_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map([client_id], patient_row)?;
         rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
-    /// Delete a patient.
+    /// Delete a patient. Fails if the patient is under legal hold.
     pub fn delete_patient(&self, local_id: &str) -> DbResult<bool> {
+        self.ensure_patient_not_held(local_id)?;
         let rows_affected = self
             .conn
             .execute("DELETE FROM patients WHERE local_id = ?", [local_id])?;
         Ok(rows_affected > 0)
     }
 
+    /// Find an unlinked local patient that duplicates a PIMS patient: same
+    /// name, owner, and date of birth (names compared case-insensitively,
+    /// a missing owner or date of birth only matching another missing one).
+    ///
+    /// A name alone is not enough: at least one of owner and date of birth
+    /// must be present and equal, so two "Max"es with nothing else recorded
+    /// are never merged.
+    pub fn find_unlinked_patient_match(
+        &self,
+        name: &str,
+        owner_name: Option<&str>,
+        date_of_birth: Option<&str>,
+    ) -> DbResult<Option<Patient>> {
+        let sql = format!(
+            r#"
+            SELECT {}
+            FROM patients
+            WHERE server_id IS NULL
+              AND lower(trim(name)) = lower(trim(?1))
+              AND lower(trim(owner_name)) IS lower(trim(?2))
+              AND date_of_birth IS ?3
+              AND (nullif(lower(trim(owner_name)), '') = lower(trim(?2))
+                   OR nullif(trim(date_of_birth), '') = trim(?3))
+            ORDER BY created_at, local_id
+            LIMIT 1
+            "#,
+            PATIENT_COLUMNS
+        );
+        Ok(self
+            .conn
+            .query_row(&sql, params![name, owner_name, date_of_birth], patient_row)
+            .optional()?)
+    }
+
     /// Link local patient to server ID after first sync.
     pub fn link_patient_server_id(&self, local_id: &str, server_id: &str) -> DbResult<bool> {
         let rows_affected = self.conn.execute(
@@ -206,9 +181,37 @@ impl Database {
     }
 }
 
+/// Columns selected for a patient, in [`patient_row`] order.
+const PATIENT_COLUMNS: &str = "local_id, server_id, name, species, breed, weight_kg, \
+     date_of_birth, owner_name, notes, created_at, updated_at, client_id, allergies";
+
+/// Map a row selected with [`PATIENT_COLUMNS`].
+fn patient_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Patient> {
+    let allergies: String = row.get(12)?;
+    let allergies = serde_json::from_str(&allergies).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(12, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    Ok(Patient {
+        local_id: row.get(0)?,
+        server_id: row.get(1)?,
+        name: row.get(2)?,
+        species: row.get(3)?,
+        breed: row.get(4)?,
+        weight_kg: row.get(5)?,
+        date_of_birth: row.get(6)?,
+        owner_name: row.get(7)?,
+        notes: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+        client_id: row.get(11)?,
+        allergies,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::PatientAllergy;
 
     fn setup_db() -> Database {
         Database::open_in_memory().unwrap()
@@ -240,11 +243,13 @@ mod tests {
 
         patient.weight_kg = Some(32.0);
         patient.notes = Some("Good boy".into());
+        patient.allergies = vec![PatientAllergy::new("amoxicillin".into())];
         db.update_patient(&patient).unwrap();
 
         let retrieved = db.get_patient(&patient.local_id).unwrap().unwrap();
         assert_eq!(retrieved.weight_kg, Some(32.0));
         assert_eq!(retrieved.notes, Some("Good boy".into()));
+        assert_eq!(retrieved.allergies, patient.allergies);
     }
 
     #[test]
@@ -281,10 +286,50 @@ mod tests {
         assert_eq!(retrieved.server_id, Some("server-123".into()));
 
         // Should also be findable by server ID
-        let by_server = db
-            .get_patient_by_server_id("server-123")
+        let by_server = db.get_patient_by_server_id("server-123").unwrap().unwrap();
+        assert_eq!(by_server.local_id, patient.local_id);
+    }
+
+    #[test]
+    fn test_find_unlinked_patient_match() {
+        let db = setup_db();
+
+        let mut patient = Patient::new("Max".into(), "canine".into());
+        patient.owner_name = Some("Jane Doe".into());
+        patient.date_of_birth = Some("2019-04-01".into());
+        db.insert_patient(&patient).unwrap();
+        let no_owner = Patient::new("Bella".into(), "feline".into());
+        db.insert_patient(&no_owner).unwrap();
+
+        let found = db
+            .find_unlinked_patient_match(" max ", Some("jane doe"), Some("2019-04-01"))
             .unwrap()
             .unwrap();
-        assert_eq!(by_server.local_id, patient.local_id);
+        assert_eq!(found.local_id, patient.local_id);
+        assert!(db
+            .find_unlinked_patient_match("Max", Some("Jane Doe"), None)
+            .unwrap()
+            .is_none());
+        // Matching on name alone would merge unrelated patients
+        assert!(db
+            .find_unlinked_patient_match("Bella", None, None)
+            .unwrap()
+            .is_none());
+        let mut dob_only = Patient::new("Bella".into(), "feline".into());
+        dob_only.date_of_birth = Some("2020-06-15".into());
+        db.insert_patient(&dob_only).unwrap();
+        let found = db
+            .find_unlinked_patient_match("bella", None, Some("2020-06-15"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.local_id, dob_only.local_id);
+
+        // Linked patients are no longer candidates
+        db.link_patient_server_id(&patient.local_id, "server-1")
+            .unwrap();
+        assert!(db
+            .find_unlinked_patient_match("Max", Some("Jane Doe"), Some("2019-04-01"))
+            .unwrap()
+            .is_none());
     }
 }
@@ -0,0 +1,244 @@
+//! Read-only SQL views for external reporting (BI) tools.
+//!
+//! These views are a stable contract: clinics can point a BI tool at the
+//! database file and query them without depending on internal table layouts,
+//! which may change at any time.
+//!
+//! Contract rules:
+//! - Columns are only ever added to a view, never renamed, removed, or retyped,
+//!   within a reporting version.
+//! - Any breaking change bumps [`REPORTING_VIEWS_VERSION`], exposed to SQL as
+//!   `SELECT version FROM v_reporting_version`.
+//! - Views are dropped and recreated at every open, so they always match the
+//!   running code.
+//!
+//! Views:
+//! - `v_committed_line_items`: one row per line item of every committed encounter
+//! - `v_inventory`: catalog items
+//! - `v_controlled_log`: committed controlled-substance line items with patient details
+
+use super::{Database, DbResult};
+
+/// Version of the reporting view contract.
+pub const REPORTING_VIEWS_VERSION: u32 = 1;
+
+/// Reporting view definitions (after `v_reporting_version`).
+const REPORTING_VIEWS: &str = r#"
+DROP VIEW IF EXISTS v_controlled_log;
+DROP VIEW IF EXISTS v_committed_line_items;
+DROP VIEW IF EXISTS v_inventory;
+
+-- One row per committed line item. Sourced from encounter leaves of the
+-- Merkle tree; audit leaves (no draft_id) are excluded.
+--   resolution_method: SystemApproved, AlternativeSelected, ManualOverride,
+--     ManualEntry
+--   confidence: system confidence for SystemApproved/AlternativeSelected,
+--     else NULL
+--   escalation_approved_by / escalation_reason: set for items with an
+--     escalation-restricted ingredient
+--   disposition: AdministeredInClinic, Dispensed, Prescribed, or NULL
+CREATE VIEW v_committed_line_items AS
+SELECT
+    n.hash AS leaf_hash,
+    n.created_at AS committed_at,
+    json_extract(n.payload, '$.draft_id') AS encounter_id,
+    json_extract(n.payload, '$.patient_id') AS patient_id,
+    json_extract(n.payload, '$.patient_server_id') AS patient_server_id,
+    json_extract(n.payload, '$.reviewed_by') AS reviewed_by,
+    json_extract(n.payload, '$.reviewed_at') AS reviewed_at,
+    CAST(li.key AS INTEGER) AS line_number,
+    json_extract(li.value, '$.sku') AS sku,
+    json_extract(li.value, '$.name') AS name,
+    json_extract(li.value, '$.quantity') AS quantity,
+    json_extract(li.value, '$.unit') AS unit,
+    json_extract(li.value, '$.route') AS route,
+    json_extract(li.value, '$.original_mention') AS original_mention,
+    CASE json_type(li.value, '$.resolution_method')
+        WHEN 'text' THEN json_extract(li.value, '$.resolution_method')
+        ELSE (SELECT m.key FROM json_each(li.value, '$.resolution_method') AS m LIMIT 1)
+    END AS resolution_method,
+    COALESCE(
+        json_extract(li.value, '$.resolution_method.SystemApproved.confidence'),
+        json_extract(li.value, '$.resolution_method.AlternativeSelected.original_confidence')
+    ) AS confidence,
+    json_extract(li.value, '$.escalation_approval.approved_by') AS escalation_approved_by,
+    json_extract(li.value, '$.escalation_approval.reason') AS escalation_reason,
+    json_extract(li.value, '$.controlled_schedule') AS controlled_schedule,
+    json_extract(li.value, '$.disposition') AS disposition
+FROM merkle_nodes AS n, json_each(n.payload, '$.line_items') AS li
+WHERE n.node_type = 'leaf'
+  AND json_valid(n.payload)
+  AND json_extract(n.payload, '$.draft_id') IS NOT NULL;
+
+-- Catalog items. species/routes/components are JSON arrays of strings.
+CREATE VIEW v_inventory AS
+SELECT
+    sku,
+    name,
+    concentration,
+    package_size,
+    species,
+    routes,
+    components,
+    controlled_schedule,
+    active,
+    server_id,
+    last_synced,
+    created_at,
+    updated_at
+FROM inventory_catalog;
+
+-- Controlled-substance dispensing log. Patient columns are NULL if the
+-- patient record is not on this device.
+CREATE VIEW v_controlled_log AS
+SELECT
+    li.committed_at,
+    li.encounter_id,
+    li.patient_id,
+    li.patient_server_id,
+    p.name AS patient_name,
+    p.species AS patient_species,
+    p.owner_name,
+    li.sku,
+    li.name,
+    li.controlled_schedule,
+    li.quantity,
+    li.unit,
+    li.route,
+    li.reviewed_by,
+    li.reviewed_at,
+    li.leaf_hash
+FROM v_committed_line_items AS li
+LEFT JOIN patients AS p ON p.local_id = li.patient_id
+WHERE li.controlled_schedule IS NOT NULL;
+"#;
+
+impl Database {
+    /// Drop and recreate the reporting views.
+    pub(super) fn create_reporting_views(&self) -> DbResult<()> {
+        self.conn.execute_batch(&format!(
+            "DROP VIEW IF EXISTS v_reporting_version;\n\
+             CREATE VIEW v_reporting_version AS SELECT {} AS version;\n{}",
+            REPORTING_VIEWS_VERSION, REPORTING_VIEWS
+        ))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::MerkleTree;
+    use crate::models::{
+        AuditEvent, CatalogItem, ControlledSchedule, EncounterLineItem, HoldSubject, LegalHold,
+        Patient, ResolutionMethod, ReviewedEncounter,
+    };
+
+    fn line_item(sku: &str, schedule: Option<ControlledSchedule>) -> EncounterLineItem {
+        EncounterLineItem {
+            sku: sku.into(),
+            name: format!("{} name", sku),
+            quantity: 2.0,
+            unit: "mL".into(),
+            route: Some("IV".into()),
+            original_mention: "mention".into(),
+            resolution_method: ResolutionMethod::SystemApproved { confidence: 0.9 },
+            controlled_schedule: schedule,
+            source_spans: vec![],
+            schedule: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        }
+    }
+
+    #[test]
+    fn test_reporting_views() {
+        let db = Database::open_in_memory().unwrap();
+        let version: u32 = db
+            .conn()
+            .query_row("SELECT version FROM v_reporting_version", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(version, REPORTING_VIEWS_VERSION);
+
+        let mut patient = Patient::new("Max".into(), "canine".into());
+        patient.owner_name = Some("Jane Doe".into());
+        db.insert_patient(&patient).unwrap();
+        db.upsert_catalog_item(&CatalogItem::new(
+            "CARP-100".into(),
+            "Carprofen 100mg".into(),
+        ))
+        .unwrap();
+
+        let tree = MerkleTree::new(&db);
+        let mut manual = line_item("BUTORPH", Some(ControlledSchedule::CIV));
+        manual.resolution_method = ResolutionMethod::ManualEntry;
+        tree.commit_encounter(&ReviewedEncounter {
+            draft_id: "draft-1".into(),
+            patient_id: patient.local_id.clone(),
+            patient_server_id: None,
+            transcript: "".into(),
+            line_items: vec![line_item("CARP-100", None), manual],
+            reviewed_by: "Dr. Smith".into(),
+            reviewed_at: "2024-01-15T10:00:00Z".into(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        })
+        .unwrap();
+        let hold = LegalHold::new(
+            HoldSubject::Patient,
+            patient.local_id.clone(),
+            "Claim".into(),
+            "Dr. Smith".into(),
+        );
+        tree.commit_audit_event(&AuditEvent::legal_hold_placed(&hold))
+            .unwrap();
+
+        let rows: Vec<(i64, String, String, Option<f64>)> = db
+            .conn()
+            .prepare(
+                "SELECT line_number, sku, resolution_method, confidence
+                 FROM v_committed_line_items ORDER BY line_number",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (0, "CARP-100".into(), "SystemApproved".into(), Some(0.9)),
+                (1, "BUTORPH".into(), "ManualEntry".into(), None),
+            ]
+        );
+
+        let (sku, schedule, owner): (String, String, String) = db
+            .conn()
+            .query_row(
+                "SELECT sku, controlled_schedule, owner_name FROM v_controlled_log",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(sku, "BUTORPH");
+        assert_eq!(schedule, "C-IV");
+        assert_eq!(owner, "Jane Doe");
+
+        let count: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM v_inventory", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
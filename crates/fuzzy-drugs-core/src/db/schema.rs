@@ -18,9 +18,20 @@ CREATE TABLE IF NOT EXISTS inventory_catalog (
     species TEXT NOT NULL DEFAULT '[]',           -- JSON array of strings
     routes TEXT NOT NULL DEFAULT '[]',            -- JSON array of strings
     dose_range TEXT,                              -- JSON object {min, max, unit}
+    withdrawal_times TEXT NOT NULL DEFAULT '[]',  -- JSON array {species, meat_days, milk_hours}
     active INTEGER NOT NULL DEFAULT 1,
     server_id TEXT,
     last_synced TEXT,
+    components TEXT NOT NULL DEFAULT '[]',        -- JSON array of active ingredients (combination products)
+    controlled_schedule TEXT,                     -- DEA schedule: C-II, C-III, C-IV, C-V (NULL if not controlled)
+    unit_price REAL,                              -- client price per dispensing unit
+    markup REAL,                                  -- markup on unit_price as a fraction (0.25 = 25%)
+    minimum_charge REAL,                          -- smallest charge for a line of this item
+    tax_code TEXT,                                -- sales tax code (rates live in settings)
+    origin TEXT NOT NULL DEFAULT 'pims' CHECK (origin IN ('pims', 'local')),
+    dirty INTEGER NOT NULL DEFAULT 0,             -- changed locally since the last catalog push
+    quantity_on_hand REAL,                        -- stock in dispensing units (NULL if not tracked)
+    reorder_point REAL,                           -- reorder at or below this quantity
     created_at TEXT NOT NULL DEFAULT (datetime('now')),
     updated_at TEXT NOT NULL DEFAULT (datetime('now'))
 );
@@ -30,31 +41,159 @@ CREATE VIRTUAL TABLE IF NOT EXISTS inventory_catalog_fts USING fts5(
     sku,
     name,
     aliases,
+    components,
     content='inventory_catalog',
     content_rowid='rowid'
 );
 
 -- Triggers to keep FTS5 in sync with main table
 CREATE TRIGGER IF NOT EXISTS inventory_catalog_ai AFTER INSERT ON inventory_catalog BEGIN
-    INSERT INTO inventory_catalog_fts(rowid, sku, name, aliases)
-    VALUES (new.rowid, new.sku, new.name, new.aliases);
+    INSERT INTO inventory_catalog_fts(rowid, sku, name, aliases, components)
+    VALUES (new.rowid, new.sku, new.name, new.aliases, new.components);
 END;
 
 CREATE TRIGGER IF NOT EXISTS inventory_catalog_ad AFTER DELETE ON inventory_catalog BEGIN
-    INSERT INTO inventory_catalog_fts(inventory_catalog_fts, rowid, sku, name, aliases)
-    VALUES ('delete', old.rowid, old.sku, old.name, old.aliases);
+    INSERT INTO inventory_catalog_fts(inventory_catalog_fts, rowid, sku, name, aliases, components)
+    VALUES ('delete', old.rowid, old.sku, old.name, old.aliases, old.components);
 END;
 
 CREATE TRIGGER IF NOT EXISTS inventory_catalog_au AFTER UPDATE ON inventory_catalog BEGIN
-    INSERT INTO inventory_catalog_fts(inventory_catalog_fts, rowid, sku, name, aliases)
-    VALUES ('delete', old.rowid, old.sku, old.name, old.aliases);
-    INSERT INTO inventory_catalog_fts(rowid, sku, name, aliases)
-    VALUES (new.rowid, new.sku, new.name, new.aliases);
+    INSERT INTO inventory_catalog_fts(inventory_catalog_fts, rowid, sku, name, aliases, components)
+    VALUES ('delete', old.rowid, old.sku, old.name, old.aliases, old.components);
+    INSERT INTO inventory_catalog_fts(rowid, sku, name, aliases, components)
+    VALUES (new.rowid, new.sku, new.name, new.aliases, new.components);
 END;
 
+-- Case-insensitive name index for type-ahead prefix lookups (LIKE 'abc%')
+CREATE INDEX IF NOT EXISTS idx_catalog_name_nocase ON inventory_catalog(name COLLATE NOCASE);
+
 -- Index for server sync
 CREATE INDEX IF NOT EXISTS idx_catalog_server_id ON inventory_catalog(server_id);
 CREATE INDEX IF NOT EXISTS idx_catalog_last_synced ON inventory_catalog(last_synced);
+CREATE INDEX IF NOT EXISTS idx_catalog_dirty ON inventory_catalog(dirty) WHERE dirty = 1;
+
+-- ============================================================================
+-- Services Catalog (procedures, vaccines, diagnostics)
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS service_catalog (
+    code TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    aliases TEXT NOT NULL DEFAULT '[]',           -- JSON array of strings
+    kind TEXT NOT NULL CHECK (kind IN ('procedure', 'vaccine', 'diagnostic')),
+    unit_price REAL,
+    tax_code TEXT,
+    active INTEGER NOT NULL DEFAULT 1,
+    antigen TEXT,                                 -- vaccines: antigen, if not the name
+    booster_interval_days INTEGER,                -- vaccines: days until the next dose
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- ============================================================================
+-- Stock (quantity-on-hand ledger)
+-- ============================================================================
+
+-- Every change to an item's quantity on hand (catalog upserts never touch it)
+CREATE TABLE IF NOT EXISTS stock_adjustments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    sku TEXT NOT NULL,
+    delta REAL NOT NULL,
+    quantity_after REAL NOT NULL,
+    reason TEXT NOT NULL CHECK (reason IN ('received', 'count', 'waste', 'expired', 'returned', 'encounter')),
+    note TEXT NOT NULL DEFAULT '',
+    reference TEXT,                               -- encounter leaf hash, for encounter usage
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_adjustments_sku ON stock_adjustments(sku, id);
+
+-- ============================================================================
+-- Inventory Lots (lot and expiry recording for vaccines and controlled drugs)
+-- ============================================================================
+
+-- sku is a catalog SKU or, for vaccines, a service code
+CREATE TABLE IF NOT EXISTS inventory_lots (
+    sku TEXT NOT NULL,
+    lot_number TEXT NOT NULL,
+    expiration_date TEXT NOT NULL,                -- YYYY-MM-DD
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (sku, lot_number)
+);
+
+-- ============================================================================
+-- Drug Interactions
+-- ============================================================================
+
+-- Local interaction entries, layered over the compiled-in table.
+-- drug_a/drug_b are lowercase generic names or "class:<name>", stored sorted.
+CREATE TABLE IF NOT EXISTS drug_interactions (
+    drug_a TEXT NOT NULL,
+    drug_b TEXT NOT NULL,
+    severity TEXT NOT NULL CHECK (severity IN ('minor', 'moderate', 'major', 'contraindicated')),
+    note TEXT NOT NULL DEFAULT '',
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (drug_a, drug_b)
+);
+
+-- Ingredients whose items need approval by a specific role, with a reason
+CREATE TABLE IF NOT EXISTS escalation_rules (
+    ingredient TEXT PRIMARY KEY,  -- lowercase generic name
+    required_role TEXT NOT NULL,
+    note TEXT NOT NULL DEFAULT '',
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- This clinic's disambiguator weights and limits (at most one row; defaults
+-- apply when absent)
+CREATE TABLE IF NOT EXISTS scoring_config (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    config TEXT NOT NULL,  -- JSON ScoringConfig
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Settings chosen when the database is opened (review_queue_order, locale,
+-- system_id); absent keys use defaults
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- ============================================================================
+-- Users (reviewers and their prescribing credentials)
+-- ============================================================================
+
+-- user_id is what the host passes as reviewed_by
+CREATE TABLE IF NOT EXISTS users (
+    user_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    license_number TEXT,
+    license_state TEXT,
+    dea_number TEXT,
+    roles TEXT NOT NULL DEFAULT '[]',             -- JSON array of roles (escalation approvals)
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- ============================================================================
+-- Clients (owners; one client can have several patients)
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS clients (
+    client_id TEXT PRIMARY KEY,
+    server_id TEXT,                              -- PIMS client ID, if known
+    name TEXT NOT NULL,
+    phone TEXT,
+    email TEXT,
+    address TEXT,
+    notes TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_clients_name ON clients(name);
 
 -- ============================================================================
 -- Patients
@@ -69,6 +208,8 @@ CREATE TABLE IF NOT EXISTS patients (
     weight_kg REAL,
     date_of_birth TEXT,
     owner_name TEXT,
+    client_id TEXT REFERENCES clients(client_id) ON DELETE SET NULL,
+    allergies TEXT NOT NULL DEFAULT '[]',        -- JSON array of {substance, reaction}
     notes TEXT,
     created_at TEXT NOT NULL DEFAULT (datetime('now')),
     updated_at TEXT NOT NULL DEFAULT (datetime('now'))
@@ -76,6 +217,62 @@ CREATE TABLE IF NOT EXISTS patients (
 
 CREATE INDEX IF NOT EXISTS idx_patients_server_id ON patients(server_id);
 CREATE INDEX IF NOT EXISTS idx_patients_name ON patients(name);
+CREATE INDEX IF NOT EXISTS idx_patients_client_id ON patients(client_id);
+
+-- Medications a patient is or was on: one row per drug on a committed
+-- encounter (source 'encounter'), plus staff entries (source 'manual')
+CREATE TABLE IF NOT EXISTS patient_medications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    patient_id TEXT NOT NULL REFERENCES patients(local_id) ON DELETE CASCADE,
+    sku TEXT,
+    name TEXT NOT NULL,
+    start_date TEXT NOT NULL,                     -- YYYY-MM-DD
+    end_date TEXT,                                -- YYYY-MM-DD, last day; NULL while ongoing
+    source TEXT NOT NULL CHECK (source IN ('encounter', 'manual')),
+    draft_id TEXT,                                -- encounter's draft, for source 'encounter'
+    notes TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_patient_medications_patient
+    ON patient_medications(patient_id, start_date);
+
+-- Vaccine doses: one row per vaccine line item on a committed encounter
+-- (draft_id set), plus doses given elsewhere entered by hand
+CREATE TABLE IF NOT EXISTS vaccinations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    patient_id TEXT NOT NULL REFERENCES patients(local_id) ON DELETE CASCADE,
+    antigen TEXT NOT NULL,
+    code TEXT NOT NULL,                           -- service code of the vaccine
+    name TEXT NOT NULL,
+    administered_on TEXT NOT NULL,                -- YYYY-MM-DD
+    lot_number TEXT,
+    expiration_date TEXT,
+    next_due_date TEXT,                           -- YYYY-MM-DD; NULL if no booster
+    administered_by TEXT,
+    draft_id TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_vaccinations_patient
+    ON vaccinations(patient_id, antigen, administered_on);
+CREATE INDEX IF NOT EXISTS idx_vaccinations_next_due ON vaccinations(next_due_date);
+
+-- ============================================================================
+-- Visits (one trip to the clinic; groups its drafts and encounters)
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS visits (
+    visit_id TEXT PRIMARY KEY,
+    patient_id TEXT NOT NULL REFERENCES patients(local_id) ON DELETE CASCADE,
+    visit_date TEXT NOT NULL,                     -- YYYY-MM-DD
+    reason TEXT,
+    attending_vet TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_visits_patient ON visits(patient_id, visit_date);
 
 -- ============================================================================
 -- Encounter Drafts (Staging Area - Mutable)
@@ -87,12 +284,88 @@ CREATE TABLE IF NOT EXISTS encounter_drafts (
     transcript TEXT NOT NULL DEFAULT '',
     resolved_items TEXT NOT NULL DEFAULT '[]',   -- JSON array of ResolvedItem
     status TEXT NOT NULL DEFAULT 'recording',    -- recording, transcribed, pending_review, reviewed, committed
+    manual_items TEXT NOT NULL DEFAULT '[]',     -- JSON array of EncounterLineItem added by the vet
+    interaction_warnings TEXT NOT NULL DEFAULT '[]', -- JSON array of InteractionWarning
+    reported_medications TEXT NOT NULL DEFAULT '[]', -- JSON array of DrugMention said by the owner
+    service_items TEXT NOT NULL DEFAULT '[]',    -- JSON array of ServiceLineItem
+    visit_id TEXT REFERENCES visits(visit_id) ON DELETE SET NULL,
     created_at TEXT NOT NULL DEFAULT (datetime('now')),
     updated_at TEXT NOT NULL DEFAULT (datetime('now'))
 );
 
 CREATE INDEX IF NOT EXISTS idx_drafts_patient ON encounter_drafts(patient_id);
 CREATE INDEX IF NOT EXISTS idx_drafts_status ON encounter_drafts(status);
+CREATE INDEX IF NOT EXISTS idx_drafts_visit ON encounter_drafts(visit_id);
+
+-- Oversized transcripts are deflate-compressed and split into chunks.
+-- When chunks exist for a draft, encounter_drafts.transcript is left empty.
+CREATE TABLE IF NOT EXISTS draft_transcript_chunks (
+    draft_id TEXT NOT NULL REFERENCES encounter_drafts(draft_id) ON DELETE CASCADE,
+    chunk_index INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    PRIMARY KEY (draft_id, chunk_index)
+);
+
+-- Extraction results by transcript, so re-processing an unchanged transcript
+-- skips inference. Keyed by the transcript's SHA-256; a row only counts for
+-- the extractor version (model and prompt) that produced it.
+CREATE TABLE IF NOT EXISTS extraction_cache (
+    transcript_hash TEXT PRIMARY KEY,
+    extractor_version TEXT NOT NULL,
+    output TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_extraction_cache_created ON extraction_cache(created_at);
+
+-- Raw LLM responses kept for diagnosing extractions (opt-in debug mode).
+-- Truncated to a size cap and purged after expires_at unless under legal hold.
+CREATE TABLE IF NOT EXISTS extraction_debug (
+    draft_id TEXT PRIMARY KEY REFERENCES encounter_drafts(draft_id) ON DELETE CASCADE,
+    model TEXT,
+    raw_response TEXT NOT NULL,
+    original_bytes INTEGER NOT NULL,
+    truncated INTEGER NOT NULL DEFAULT 0,
+    recorded_at TEXT NOT NULL DEFAULT (datetime('now')),
+    expires_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_extraction_debug_expires ON extraction_debug(expires_at);
+
+-- ============================================================================
+-- Legal Holds
+-- ============================================================================
+
+-- Patients/encounters under dispute. Held data must not be deleted, archived,
+-- truncated, or redacted. subject_id is a patient local_id or an encounter's
+-- draft_id. Placement and removal are also recorded as Merkle audit leaves.
+CREATE TABLE IF NOT EXISTS legal_holds (
+    subject_type TEXT NOT NULL CHECK (subject_type IN ('patient', 'encounter')),
+    subject_id TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    placed_by TEXT NOT NULL,
+    placed_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (subject_type, subject_id)
+);
+
+-- Backstop: refuse deletes of held rows regardless of code path
+CREATE TRIGGER IF NOT EXISTS patients_legal_hold BEFORE DELETE ON patients
+WHEN EXISTS (
+    SELECT 1 FROM legal_holds WHERE subject_type = 'patient' AND subject_id = old.local_id
+)
+BEGIN
+    SELECT RAISE(ABORT, 'Patient is under legal hold');
+END;
+
+CREATE TRIGGER IF NOT EXISTS encounter_drafts_legal_hold BEFORE DELETE ON encounter_drafts
+WHEN EXISTS (
+    SELECT 1 FROM legal_holds
+    WHERE (subject_type = 'encounter' AND subject_id = old.draft_id)
+       OR (subject_type = 'patient' AND subject_id = old.patient_id)
+)
+BEGIN
+    SELECT RAISE(ABORT, 'Encounter is under legal hold');
+END;
 
 -- ============================================================================
 -- Merkle Tree (Append-Only - Immutable after creation)
@@ -108,6 +381,19 @@ CREATE TABLE IF NOT EXISTS merkle_nodes (
     created_at TEXT NOT NULL DEFAULT (datetime('now'))
 );
 
+-- PIMS side (merkle::server): nodes received for a device tree that is
+-- still missing descendants, held until the tree under expected_root is
+-- complete and verified
+CREATE TABLE IF NOT EXISTS merkle_staging (
+    hash TEXT PRIMARY KEY,
+    expected_root TEXT NOT NULL,
+    node_type TEXT NOT NULL CHECK (node_type IN ('leaf', 'internal')),
+    left_child TEXT,
+    right_child TEXT,
+    payload TEXT,
+    received_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
 -- Ensure leaves have payload, internals have children
 CREATE TRIGGER IF NOT EXISTS merkle_nodes_check_leaf BEFORE INSERT ON merkle_nodes
 WHEN new.node_type = 'leaf'
@@ -148,10 +434,43 @@ CREATE TABLE IF NOT EXISTS merkle_root (
 INSERT OR IGNORE INTO merkle_root (id, root_hash, tree_height, leaf_count)
 VALUES (1, NULL, 0, 0);
 
+-- ============================================================================
+-- Export Ledger
+-- ============================================================================
+
+-- Unbilled billing exports and the encounter leaves each included, so the
+-- next unbilled export skips them. Void batches release their leaves;
+-- imported batches are final.
+CREATE TABLE IF NOT EXISTS export_batches (
+    batch_id TEXT PRIMARY KEY,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'imported', 'void')),
+    created_at TEXT NOT NULL,
+    status_changed_at TEXT
+);
+
+CREATE TABLE IF NOT EXISTS export_batch_leaves (
+    batch_id TEXT NOT NULL REFERENCES export_batches(batch_id),
+    leaf_hash TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (batch_id, leaf_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_export_batch_leaves_leaf ON export_batch_leaves(leaf_hash);
+
 -- ============================================================================
 -- Sync State
 -- ============================================================================
 
+-- Identity of this installation (exactly one row)
+CREATE TABLE IF NOT EXISTS device_identity (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    device_id TEXT NOT NULL,
+    device_name TEXT NOT NULL,
+    provisioned_at TEXT NOT NULL,
+    key_fingerprints TEXT NOT NULL DEFAULT '[]',  -- JSON array of {purpose, fingerprint, added_at}
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
 CREATE TABLE IF NOT EXISTS sync_state (
     key TEXT PRIMARY KEY,
     value TEXT NOT NULL,
@@ -161,7 +480,32 @@ CREATE TABLE IF NOT EXISTS sync_state (
 -- Track last successful sync timestamps
 INSERT OR IGNORE INTO sync_state (key, value) VALUES ('catalog_last_sync', '');
 INSERT OR IGNORE INTO sync_state (key, value) VALUES ('encounters_last_sync', '');
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('patients_last_sync', '');
 INSERT OR IGNORE INTO sync_state (key, value) VALUES ('last_synced_root', '');
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('last_server_root', '');
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('sync_conflict', '');
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('server_protocol', '');
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('chunk_progress', '');
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('last_sync_error', '');
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('last_sync_error_at', '');
+
+-- Sync operations waiting to reach the PIMS. Succeeded operations are
+-- deleted; the rest are retried with backoff until they run out of attempts
+-- ('failed'). Re-queuing a subject resets its row.
+CREATE TABLE IF NOT EXISTS sync_outbox (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL CHECK (kind IN ('encounter_push', 'patient_upsert')),
+    subject_id TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (kind, subject_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_outbox_due ON sync_outbox(status, next_attempt_at);
 "#;
 
 #[cfg(test)]
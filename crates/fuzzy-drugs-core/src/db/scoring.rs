@@ -0,0 +1,82 @@
+//! Per-clinic disambiguator scoring configuration.
+
+use rusqlite::OptionalExtension;
+
+use super::{Database, DbError, DbResult};
+use crate::models::ScoringConfig;
+
+impl Database {
+    /// This clinic's scoring weights and limits (defaults if never set).
+    pub fn scoring_config(&self) -> DbResult<ScoringConfig> {
+        let json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT config FROM scoring_config WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match json {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(ScoringConfig::default()),
+        }
+    }
+
+    /// Store this clinic's scoring weights and limits.
+    pub fn set_scoring_config(&self, config: &ScoringConfig) -> DbResult<()> {
+        config.validate().map_err(DbError::Constraint)?;
+        self.conn.execute(
+            r#"
+            INSERT INTO scoring_config (id, config, updated_at)
+            VALUES (1, ?1, datetime('now'))
+            ON CONFLICT(id) DO UPDATE SET
+                config = excluded.config,
+                updated_at = datetime('now')
+            "#,
+            [serde_json::to_string(config)?],
+        )?;
+        Ok(())
+    }
+
+    /// Go back to the default scoring weights and limits.
+    pub fn reset_scoring_config(&self) -> DbResult<()> {
+        self.conn
+            .execute("DELETE FROM scoring_config WHERE id = 1", [])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoring_config_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.scoring_config().unwrap(), ScoringConfig::default());
+
+        let config = ScoringConfig {
+            name_weight: 0.5,
+            species_weight: 0.2,
+            route_weight: 0.2,
+            dose_weight: 0.1,
+            max_alternatives: 2,
+            ..Default::default()
+        };
+        db.set_scoring_config(&config).unwrap();
+        assert_eq!(db.scoring_config().unwrap(), config);
+
+        let invalid = ScoringConfig {
+            name_weight: 0.9,
+            ..Default::default()
+        };
+        assert!(matches!(
+            db.set_scoring_config(&invalid),
+            Err(DbError::Constraint(_))
+        ));
+        assert_eq!(db.scoring_config().unwrap(), config);
+
+        db.reset_scoring_config().unwrap();
+        assert_eq!(db.scoring_config().unwrap(), ScoringConfig::default());
+    }
+}
@@ -0,0 +1,176 @@
+//! Services catalog operations.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbError, DbResult};
+use crate::models::{ServiceItem, ServiceKind};
+
+impl Database {
+    /// Insert or update a services catalog entry.
+    pub fn upsert_service_item(&self, item: &ServiceItem) -> DbResult<()> {
+        let aliases_json = serde_json::to_string(&item.aliases)?;
+        self.conn.execute(
+            r#"
+            INSERT INTO service_catalog (
+                code, name, aliases, kind, unit_price, tax_code, active, antigen,
+                booster_interval_days, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, datetime('now'))
+            ON CONFLICT(code) DO UPDATE SET
+                name = excluded.name,
+                aliases = excluded.aliases,
+                kind = excluded.kind,
+                unit_price = excluded.unit_price,
+                tax_code = excluded.tax_code,
+                active = excluded.active,
+                antigen = excluded.antigen,
+                booster_interval_days = excluded.booster_interval_days,
+                updated_at = datetime('now')
+            "#,
+            params![
+                item.code,
+                item.name,
+                aliases_json,
+                item.kind.as_str(),
+                item.unit_price,
+                item.tax_code,
+                item.active,
+                item.antigen,
+                item.booster_interval_days,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get a services catalog entry by code.
+    pub fn get_service_item(&self, code: &str) -> DbResult<Option<ServiceItem>> {
+        let sql = format!(
+            "SELECT {} FROM service_catalog WHERE code = ?",
+            SERVICE_COLUMNS
+        );
+        let row = self
+            .conn
+            .prepare_cached(&sql)?
+            .query_row([code], service_item_row)
+            .optional()?;
+        row.map(ServiceItem::try_from).transpose()
+    }
+
+    /// List services catalog entries by name.
+    pub fn list_service_items(&self, active_only: bool) -> DbResult<Vec<ServiceItem>> {
+        let sql = format!(
+            "SELECT {} FROM service_catalog {} ORDER BY name COLLATE NOCASE",
+            SERVICE_COLUMNS,
+            if active_only { "WHERE active = 1" } else { "" }
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map([], service_item_row)?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            items.push(row?.try_into()?);
+        }
+        Ok(items)
+    }
+
+    /// Delete a services catalog entry.
+    pub fn delete_service_item(&self, code: &str) -> DbResult<bool> {
+        let rows_affected = self
+            .conn
+            .execute("DELETE FROM service_catalog WHERE code = ?", [code])?;
+        Ok(rows_affected > 0)
+    }
+}
+
+/// Columns selected for a service, in [`service_item_row`] order.
+const SERVICE_COLUMNS: &str =
+    "code, name, aliases, kind, unit_price, tax_code, active, antigen, booster_interval_days";
+
+/// Map a row selected with [`SERVICE_COLUMNS`].
+fn service_item_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ServiceRow> {
+    Ok(ServiceRow {
+        code: row.get(0)?,
+        name: row.get(1)?,
+        aliases: row.get(2)?,
+        kind: row.get(3)?,
+        unit_price: row.get(4)?,
+        tax_code: row.get(5)?,
+        active: row.get(6)?,
+        antigen: row.get(7)?,
+        booster_interval_days: row.get(8)?,
+    })
+}
+
+/// Intermediate row struct for database mapping.
+struct ServiceRow {
+    code: String,
+    name: String,
+    aliases: String,
+    kind: String,
+    unit_price: Option<f64>,
+    tax_code: Option<String>,
+    active: bool,
+    antigen: Option<String>,
+    booster_interval_days: Option<u32>,
+}
+
+impl TryFrom<ServiceRow> for ServiceItem {
+    type Error = DbError;
+
+    fn try_from(row: ServiceRow) -> Result<Self, Self::Error> {
+        let kind = ServiceKind::parse(&row.kind)
+            .ok_or_else(|| DbError::Constraint(format!("Unknown service kind: {}", row.kind)))?;
+        Ok(ServiceItem {
+            code: row.code,
+            name: row.name,
+            aliases: serde_json::from_str(&row.aliases)?,
+            kind,
+            unit_price: row.unit_price,
+            tax_code: row.tax_code,
+            active: row.active,
+            antigen: row.antigen,
+            booster_interval_days: row.booster_interval_days,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_item_crud() {
+        let db = Database::open_in_memory().unwrap();
+
+        let mut rads = ServiceItem::new(
+            "DX-RAD2".into(),
+            "Radiographs (2 views)".into(),
+            ServiceKind::Diagnostic,
+        );
+        rads.aliases = vec!["x-rays".into(), "rads".into()];
+        rads.unit_price = Some(180.0);
+        db.upsert_service_item(&rads).unwrap();
+        let mut nails = ServiceItem::new(
+            "PROC-NAIL".into(),
+            "Nail Trim".into(),
+            ServiceKind::Procedure,
+        );
+        db.upsert_service_item(&nails).unwrap();
+
+        assert_eq!(db.get_service_item("DX-RAD2").unwrap(), Some(rads));
+        assert_eq!(db.get_service_item("missing").unwrap(), None);
+
+        nails.active = false;
+        db.upsert_service_item(&nails).unwrap();
+        let names: Vec<String> = db
+            .list_service_items(false)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["Nail Trim", "Radiographs (2 views)"]);
+        assert_eq!(db.list_service_items(true).unwrap().len(), 1);
+
+        assert!(db.delete_service_item("PROC-NAIL").unwrap());
+        assert!(!db.delete_service_item("PROC-NAIL").unwrap());
+    }
+}
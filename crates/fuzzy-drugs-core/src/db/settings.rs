@@ -0,0 +1,265 @@
+//! Persisted clinic settings (review queue order, locale, export system ID,
+//! sync conflict strategy, QuickBooks mapping, billing CSV layout, tax
+//! rates, pseudonym salt, extraction sampling).
+
+use rusqlite::OptionalExtension;
+
+use super::{Database, DbError, DbResult};
+use crate::models::{
+    CoreSettings, CsvLayout, QuickBooksMapping, ReviewQueueOrder, SamplingSettings,
+    SyncConflictStrategy, TaxRates,
+};
+
+const REVIEW_QUEUE_ORDER: &str = "review_queue_order";
+const LOCALE: &str = "locale";
+const SYSTEM_ID: &str = "system_id";
+const SYNC_CONFLICT_STRATEGY: &str = "sync_conflict_strategy";
+const QUICKBOOKS_MAPPING: &str = "quickbooks_mapping";
+const BILLING_CSV_LAYOUT: &str = "billing_csv_layout";
+const TAX_RATES: &str = "tax_rates";
+const PSEUDONYM_SALT: &str = "pseudonym_salt";
+const SAMPLING_SETTINGS: &str = "sampling_settings";
+
+impl Database {
+    /// Stored settings (defaults for anything never set).
+    pub fn core_settings(&self) -> DbResult<CoreSettings> {
+        let review_queue_order = match self.get_setting(REVIEW_QUEUE_ORDER)? {
+            Some(value) => ReviewQueueOrder::parse(&value).ok_or_else(|| {
+                DbError::Constraint(format!("Unknown review queue order: {}", value))
+            })?,
+            None => ReviewQueueOrder::default(),
+        };
+        let sync_conflict_strategy = match self.get_setting(SYNC_CONFLICT_STRATEGY)? {
+            Some(value) => SyncConflictStrategy::parse(&value).ok_or_else(|| {
+                DbError::Constraint(format!("Unknown sync conflict strategy: {}", value))
+            })?,
+            None => SyncConflictStrategy::default(),
+        };
+        Ok(CoreSettings {
+            review_queue_order,
+            locale: self.get_setting(LOCALE)?,
+            system_id: self.get_setting(SYSTEM_ID)?,
+            sync_conflict_strategy,
+        })
+    }
+
+    /// Store all settings; `None` values clear the stored setting.
+    pub fn set_core_settings(&self, settings: &CoreSettings) -> DbResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        self.set_setting(
+            REVIEW_QUEUE_ORDER,
+            Some(settings.review_queue_order.as_str()),
+        )?;
+        self.set_setting(LOCALE, settings.locale.as_deref())?;
+        self.set_setting(SYSTEM_ID, settings.system_id.as_deref())?;
+        self.set_setting(
+            SYNC_CONFLICT_STRATEGY,
+            Some(settings.sync_conflict_strategy.as_str()),
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Store the dictation language alone.
+    pub fn set_locale_setting(&self, locale: &str) -> DbResult<()> {
+        self.set_setting(LOCALE, Some(locale))
+    }
+
+    /// QuickBooks account/item mapping (defaults if never set).
+    pub fn quickbooks_mapping(&self) -> DbResult<QuickBooksMapping> {
+        match self.get_setting(QUICKBOOKS_MAPPING)? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(QuickBooksMapping::default()),
+        }
+    }
+
+    /// Store the QuickBooks mapping.
+    pub fn set_quickbooks_mapping(&self, mapping: &QuickBooksMapping) -> DbResult<()> {
+        self.set_setting(QUICKBOOKS_MAPPING, Some(&serde_json::to_string(mapping)?))
+    }
+
+    /// Billing CSV layout (the original columns if never set).
+    pub fn billing_csv_layout(&self) -> DbResult<CsvLayout> {
+        match self.get_setting(BILLING_CSV_LAYOUT)? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(CsvLayout::default()),
+        }
+    }
+
+    /// Store the billing CSV layout.
+    pub fn set_billing_csv_layout(&self, layout: &CsvLayout) -> DbResult<()> {
+        self.set_setting(BILLING_CSV_LAYOUT, Some(&serde_json::to_string(layout)?))
+    }
+
+    /// Sales tax rates (none if never set).
+    pub fn tax_rates(&self) -> DbResult<TaxRates> {
+        match self.get_setting(TAX_RATES)? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(TaxRates::default()),
+        }
+    }
+
+    /// Store the sales tax rates.
+    pub fn set_tax_rates(&self, rates: &TaxRates) -> DbResult<()> {
+        self.set_setting(TAX_RATES, Some(&serde_json::to_string(rates)?))
+    }
+
+    /// Extraction model sampling parameters (deterministic defaults if
+    /// never set).
+    pub fn sampling_settings(&self) -> DbResult<SamplingSettings> {
+        match self.get_setting(SAMPLING_SETTINGS)? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(SamplingSettings::default()),
+        }
+    }
+
+    /// Store the extraction model sampling parameters.
+    pub fn set_sampling_settings(&self, settings: &SamplingSettings) -> DbResult<()> {
+        self.set_setting(SAMPLING_SETTINGS, Some(&serde_json::to_string(settings)?))
+    }
+
+    /// Salt for de-identified export pseudonyms, generated on first use so
+    /// pseudonyms stay stable across this clinic's exports.
+    pub fn pseudonym_salt(&self) -> DbResult<String> {
+        if let Some(salt) = self.get_setting(PSEUDONYM_SALT)? {
+            return Ok(salt);
+        }
+        let salt = uuid::Uuid::new_v4().to_string();
+        self.set_setting(PSEUDONYM_SALT, Some(&salt))?;
+        Ok(salt)
+    }
+
+    fn get_setting(&self, key: &str) -> DbResult<Option<String>> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM settings WHERE key = ?", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    fn set_setting(&self, key: &str, value: Option<&str>) -> DbResult<()> {
+        match value {
+            Some(value) => self.conn.execute(
+                r#"
+                INSERT INTO settings (key, value, updated_at)
+                VALUES (?1, ?2, datetime('now'))
+                ON CONFLICT(key) DO UPDATE SET
+                    value = excluded.value,
+                    updated_at = datetime('now')
+                "#,
+                [key, value],
+            )?,
+            None => self
+                .conn
+                .execute("DELETE FROM settings WHERE key = ?", [key])?,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_settings_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.core_settings().unwrap(), CoreSettings::default());
+
+        let settings = CoreSettings {
+            review_queue_order: ReviewQueueOrder::OldestFirst,
+            locale: Some("es".into()),
+            system_id: Some("clinic-7".into()),
+            sync_conflict_strategy: SyncConflictStrategy::Manual,
+        };
+        db.set_core_settings(&settings).unwrap();
+        assert_eq!(db.core_settings().unwrap(), settings);
+
+        db.set_core_settings(&CoreSettings {
+            locale: None,
+            ..settings.clone()
+        })
+        .unwrap();
+        assert_eq!(db.core_settings().unwrap().locale, None);
+        assert_eq!(db.core_settings().unwrap().system_id, settings.system_id);
+
+        db.set_locale_setting("en").unwrap();
+        assert_eq!(db.core_settings().unwrap().locale, Some("en".into()));
+    }
+
+    #[test]
+    fn test_quickbooks_mapping_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(
+            db.quickbooks_mapping().unwrap(),
+            QuickBooksMapping::default()
+        );
+
+        let mapping = QuickBooksMapping {
+            income_account: "Medical Income".into(),
+            ..Default::default()
+        };
+        db.set_quickbooks_mapping(&mapping).unwrap();
+        assert_eq!(db.quickbooks_mapping().unwrap(), mapping);
+        // Other settings are untouched
+        assert_eq!(db.core_settings().unwrap(), CoreSettings::default());
+    }
+
+    #[test]
+    fn test_billing_csv_layout_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.billing_csv_layout().unwrap(), CsvLayout::default());
+
+        let layout = CsvLayout {
+            delimiter: ';',
+            date_format: Some("%d.%m.%Y".into()),
+            ..Default::default()
+        };
+        db.set_billing_csv_layout(&layout).unwrap();
+        assert_eq!(db.billing_csv_layout().unwrap(), layout);
+        assert_eq!(
+            db.quickbooks_mapping().unwrap(),
+            QuickBooksMapping::default()
+        );
+    }
+
+    #[test]
+    fn test_tax_rates_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.tax_rates().unwrap(), TaxRates::default());
+
+        let mut rates = TaxRates::default();
+        rates.rates.insert("RETAIL".into(), 0.0725);
+        db.set_tax_rates(&rates).unwrap();
+        assert_eq!(db.tax_rates().unwrap(), rates);
+        assert_eq!(db.billing_csv_layout().unwrap(), CsvLayout::default());
+    }
+
+    #[test]
+    fn test_sampling_settings_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.sampling_settings().unwrap(), SamplingSettings::default());
+
+        let settings = SamplingSettings {
+            temperature: 0.8,
+            top_p: 0.9,
+            repeat_penalty: 1.1,
+            max_tokens: 512,
+            seed: 42,
+        };
+        db.set_sampling_settings(&settings).unwrap();
+        assert_eq!(db.sampling_settings().unwrap(), settings);
+        assert_eq!(db.tax_rates().unwrap(), TaxRates::default());
+    }
+
+    #[test]
+    fn test_pseudonym_salt_is_stable() {
+        let db = Database::open_in_memory().unwrap();
+        let salt = db.pseudonym_salt().unwrap();
+        assert!(!salt.is_empty());
+        assert_eq!(db.pseudonym_salt().unwrap(), salt);
+        let other = Database::open_in_memory().unwrap();
+        assert_ne!(other.pseudonym_salt().unwrap(), salt);
+    }
+}
@@ -0,0 +1,235 @@
+//! Stock levels on the catalog and the adjustment ledger.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbResult};
+use crate::models::{StockAdjustment, StockAdjustmentReason, StockLevel};
+
+impl Database {
+    /// An item's stock level (None if the SKU is unknown or its stock isn't
+    /// tracked).
+    pub fn get_stock_level(&self, sku: &str) -> DbResult<Option<StockLevel>> {
+        let sql = format!(
+            "SELECT {} FROM inventory_catalog WHERE sku = ? AND quantity_on_hand IS NOT NULL",
+            STOCK_COLUMNS
+        );
+        Ok(self.conn.query_row(&sql, [sku], stock_row).optional()?)
+    }
+
+    /// Set or clear an item's reorder point. Returns false if the SKU is
+    /// unknown.
+    pub fn set_reorder_point(&self, sku: &str, reorder_point: Option<f64>) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            "UPDATE inventory_catalog SET reorder_point = ?2 WHERE sku = ?1",
+            params![sku, reorder_point],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Change an item's quantity on hand by `delta` and record why.
+    ///
+    /// An untracked item starts from zero. Returns the new quantity (None if
+    /// the SKU is unknown).
+    pub fn adjust_stock(
+        &self,
+        sku: &str,
+        delta: f64,
+        reason: StockAdjustmentReason,
+        note: &str,
+    ) -> DbResult<Option<f64>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let quantity = self.apply_stock_delta(sku, delta, reason, note, None)?;
+        tx.commit()?;
+        Ok(quantity)
+    }
+
+    /// Set an item's quantity on hand to a physical count, recorded as a
+    /// [`Count`](StockAdjustmentReason::Count) adjustment by the difference.
+    pub fn record_stock_count(&self, sku: &str, counted: f64, note: &str) -> DbResult<Option<f64>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let previous: Option<f64> = self
+            .conn
+            .query_row(
+                "SELECT COALESCE(quantity_on_hand, 0) FROM inventory_catalog WHERE sku = ?",
+                [sku],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(previous) = previous else {
+            return Ok(None);
+        };
+        let delta = counted - previous;
+        let quantity =
+            self.apply_stock_delta(sku, delta, StockAdjustmentReason::Count, note, None)?;
+        tx.commit()?;
+        Ok(quantity)
+    }
+
+    /// Draw down the items used on a committed encounter: `usage` is each
+    /// line's SKU and quantity in dispensing units. Items whose stock isn't
+    /// tracked (and SKUs not in the catalog, like services) are skipped.
+    ///
+    /// Opens no transaction of its own; the commit runs it in the one that
+    /// adds the encounter leaf, so the two land together.
+    pub fn record_stock_usage(&self, usage: &[(String, f64)], leaf_hash: &str) -> DbResult<()> {
+        for (sku, quantity) in usage {
+            let tracked: bool = self
+                .conn
+                .query_row(
+                    "SELECT quantity_on_hand IS NOT NULL FROM inventory_catalog WHERE sku = ?",
+                    [sku],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .unwrap_or(false);
+            if tracked {
+                self.apply_stock_delta(
+                    sku,
+                    -quantity,
+                    StockAdjustmentReason::Encounter,
+                    "",
+                    Some(leaf_hash),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Active items at or below their reorder point, furthest below first.
+    pub fn list_low_stock(&self) -> DbResult<Vec<StockLevel>> {
+        let sql = format!(
+            "SELECT {} FROM inventory_catalog
+             WHERE active = 1 AND quantity_on_hand <= reorder_point
+             ORDER BY quantity_on_hand - reorder_point, sku",
+            STOCK_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let levels = stmt.query_map([], stock_row)?.collect::<Result<_, _>>()?;
+        Ok(levels)
+    }
+
+    /// An item's most recent adjustments, newest first.
+    pub fn list_stock_adjustments(
+        &self,
+        sku: &str,
+        limit: usize,
+    ) -> DbResult<Vec<StockAdjustment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, sku, delta, quantity_after, reason, note, reference, created_at
+             FROM stock_adjustments WHERE sku = ? ORDER BY id DESC LIMIT ?",
+        )?;
+        let adjustments = stmt
+            .query_map(params![sku, limit as i64], |row| {
+                let reason: String = row.get(4)?;
+                Ok(StockAdjustment {
+                    id: row.get(0)?,
+                    sku: row.get(1)?,
+                    delta: row.get(2)?,
+                    quantity_after: row.get(3)?,
+                    reason: StockAdjustmentReason::parse(&reason)
+                        .unwrap_or(StockAdjustmentReason::Count),
+                    note: row.get(5)?,
+                    reference: row.get(6)?,
+                    created_at: row.get(7)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(adjustments)
+    }
+
+    /// Apply `delta` to a catalog item's quantity on hand and log it.
+    /// Returns the new quantity (None if the SKU is unknown).
+    fn apply_stock_delta(
+        &self,
+        sku: &str,
+        delta: f64,
+        reason: StockAdjustmentReason,
+        note: &str,
+        reference: Option<&str>,
+    ) -> DbResult<Option<f64>> {
+        let quantity: Option<f64> = self
+            .conn
+            .query_row(
+                "UPDATE inventory_catalog SET quantity_on_hand = COALESCE(quantity_on_hand, 0) + ?2
+                 WHERE sku = ?1 RETURNING quantity_on_hand",
+                params![sku, delta],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(quantity) = quantity else {
+            return Ok(None);
+        };
+        self.conn.execute(
+            "INSERT INTO stock_adjustments (sku, delta, quantity_after, reason, note, reference)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![sku, delta, quantity, reason.as_str(), note, reference],
+        )?;
+        Ok(Some(quantity))
+    }
+}
+
+/// Columns selected for a stock level, in [`stock_row`] order.
+const STOCK_COLUMNS: &str = "sku, name, quantity_on_hand, reorder_point";
+
+/// Map a row selected with [`STOCK_COLUMNS`].
+fn stock_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<StockLevel> {
+    Ok(StockLevel {
+        sku: row.get(0)?,
+        name: row.get(1)?,
+        quantity_on_hand: row.get(2)?,
+        reorder_point: row.get(3)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CatalogItem;
+
+    #[test]
+    fn test_stock_adjustments_and_usage() {
+        let db = Database::open_in_memory().unwrap();
+        for sku in ["CARP-100", "GABA-100"] {
+            db.upsert_catalog_item(&CatalogItem::new(sku.into(), sku.into()))
+                .unwrap();
+        }
+        assert_eq!(db.get_stock_level("CARP-100").unwrap(), None);
+
+        let received = StockAdjustmentReason::Received;
+        assert_eq!(
+            db.adjust_stock("CARP-100", 100.0, received, "PO 42")
+                .unwrap(),
+            Some(100.0)
+        );
+        assert_eq!(db.adjust_stock("MISSING", 1.0, received, "").unwrap(), None);
+        assert!(db.set_reorder_point("CARP-100", Some(40.0)).unwrap());
+
+        // Only tracked items are drawn down
+        let usage = vec![
+            ("CARP-100".to_string(), 60.0),
+            ("GABA-100".to_string(), 10.0),
+        ];
+        db.record_stock_usage(&usage, "leaf-1").unwrap();
+        assert_eq!(db.get_stock_level("GABA-100").unwrap(), None);
+        let level = db.get_stock_level("CARP-100").unwrap().unwrap();
+        assert_eq!(level.quantity_on_hand, 40.0);
+        assert!(level.is_low());
+        assert_eq!(db.list_low_stock().unwrap(), vec![level]);
+
+        // Catalog edits leave stock alone
+        let mut item = db.get_catalog_item("CARP-100").unwrap().unwrap();
+        item.unit_price = Some(0.9);
+        db.upsert_catalog_item(&item).unwrap();
+        assert_eq!(
+            db.record_stock_count("CARP-100", 38.0, "").unwrap(),
+            Some(38.0)
+        );
+
+        let adjustments = db.list_stock_adjustments("CARP-100", 10).unwrap();
+        let deltas: Vec<f64> = adjustments.iter().map(|a| a.delta).collect();
+        assert_eq!(deltas, [-2.0, -60.0, 100.0]);
+        assert_eq!(adjustments[1].reason, StockAdjustmentReason::Encounter);
+        assert_eq!(adjustments[1].reference.as_deref(), Some("leaf-1"));
+        assert_eq!(adjustments[2].note, "PO 42");
+    }
+}
@@ -0,0 +1,161 @@
+-- Schema as shipped before versioning (user_version 0). Used to test upgrades.
+-- Enable foreign keys
+PRAGMA foreign_keys = ON;
+
+-- ============================================================================
+-- Inventory Catalog
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS inventory_catalog (
+    sku TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    aliases TEXT NOT NULL DEFAULT '[]',           -- JSON array of strings
+    concentration TEXT,
+    package_size TEXT,
+    species TEXT NOT NULL DEFAULT '[]',           -- JSON array of strings
+    routes TEXT NOT NULL DEFAULT '[]',            -- JSON array of strings
+    dose_range TEXT,                              -- JSON object {min, max, unit}
+    active INTEGER NOT NULL DEFAULT 1,
+    server_id TEXT,
+    last_synced TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- FTS5 virtual table for full-text search
+CREATE VIRTUAL TABLE IF NOT EXISTS inventory_catalog_fts USING fts5(
+    sku,
+    name,
+    aliases,
+    content='inventory_catalog',
+    content_rowid='rowid'
+);
+
+-- Triggers to keep FTS5 in sync with main table
+CREATE TRIGGER IF NOT EXISTS inventory_catalog_ai AFTER INSERT ON inventory_catalog BEGIN
+    INSERT INTO inventory_catalog_fts(rowid, sku, name, aliases)
+    VALUES (new.rowid, new.sku, new.name, new.aliases);
+END;
+
+CREATE TRIGGER IF NOT EXISTS inventory_catalog_ad AFTER DELETE ON inventory_catalog BEGIN
+    INSERT INTO inventory_catalog_fts(inventory_catalog_fts, rowid, sku, name, aliases)
+    VALUES ('delete', old.rowid, old.sku, old.name, old.aliases);
+END;
+
+CREATE TRIGGER IF NOT EXISTS inventory_catalog_au AFTER UPDATE ON inventory_catalog BEGIN
+    INSERT INTO inventory_catalog_fts(inventory_catalog_fts, rowid, sku, name, aliases)
+    VALUES ('delete', old.rowid, old.sku, old.name, old.aliases);
+    INSERT INTO inventory_catalog_fts(rowid, sku, name, aliases)
+    VALUES (new.rowid, new.sku, new.name, new.aliases);
+END;
+
+-- Index for server sync
+CREATE INDEX IF NOT EXISTS idx_catalog_server_id ON inventory_catalog(server_id);
+CREATE INDEX IF NOT EXISTS idx_catalog_last_synced ON inventory_catalog(last_synced);
+
+-- ============================================================================
+-- Patients
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS patients (
+    local_id TEXT PRIMARY KEY,
+    server_id TEXT,                              -- NULL until first sync
+    name TEXT NOT NULL,
+    species TEXT NOT NULL,
+    breed TEXT,
+    weight_kg REAL,
+    date_of_birth TEXT,
+    owner_name TEXT,
+    notes TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_patients_server_id ON patients(server_id);
+CREATE INDEX IF NOT EXISTS idx_patients_name ON patients(name);
+
+-- ============================================================================
+-- Encounter Drafts (Staging Area - Mutable)
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS encounter_drafts (
+    draft_id TEXT PRIMARY KEY,
+    patient_id TEXT NOT NULL REFERENCES patients(local_id),
+    transcript TEXT NOT NULL DEFAULT '',
+    resolved_items TEXT NOT NULL DEFAULT '[]',   -- JSON array of ResolvedItem
+    status TEXT NOT NULL DEFAULT 'recording',    -- recording, transcribed, pending_review, reviewed, committed
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_drafts_patient ON encounter_drafts(patient_id);
+CREATE INDEX IF NOT EXISTS idx_drafts_status ON encounter_drafts(status);
+
+-- ============================================================================
+-- Merkle Tree (Append-Only - Immutable after creation)
+-- ============================================================================
+
+-- Merkle tree nodes
+CREATE TABLE IF NOT EXISTS merkle_nodes (
+    hash TEXT PRIMARY KEY,                       -- SHA-256 of content or children
+    node_type TEXT NOT NULL CHECK (node_type IN ('leaf', 'internal')),
+    left_child TEXT REFERENCES merkle_nodes(hash),
+    right_child TEXT REFERENCES merkle_nodes(hash),
+    payload TEXT,                                -- JSON content (leaf only)
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Ensure leaves have payload, internals have children
+CREATE TRIGGER IF NOT EXISTS merkle_nodes_check_leaf BEFORE INSERT ON merkle_nodes
+WHEN new.node_type = 'leaf'
+BEGIN
+    SELECT CASE
+        WHEN new.payload IS NULL THEN
+            RAISE(ABORT, 'Leaf nodes must have payload')
+        WHEN new.left_child IS NOT NULL OR new.right_child IS NOT NULL THEN
+            RAISE(ABORT, 'Leaf nodes cannot have children')
+    END;
+END;
+
+CREATE TRIGGER IF NOT EXISTS merkle_nodes_check_internal BEFORE INSERT ON merkle_nodes
+WHEN new.node_type = 'internal'
+BEGIN
+    SELECT CASE
+        WHEN new.left_child IS NULL THEN
+            RAISE(ABORT, 'Internal nodes must have left child')
+        WHEN new.payload IS NOT NULL THEN
+            RAISE(ABORT, 'Internal nodes cannot have payload')
+    END;
+END;
+
+-- Index for efficient tree traversal
+CREATE INDEX IF NOT EXISTS idx_merkle_children ON merkle_nodes(left_child, right_child);
+CREATE INDEX IF NOT EXISTS idx_merkle_type ON merkle_nodes(node_type);
+
+-- Current root (single row, updated atomically)
+CREATE TABLE IF NOT EXISTS merkle_root (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    root_hash TEXT REFERENCES merkle_nodes(hash),
+    tree_height INTEGER NOT NULL DEFAULT 0,
+    leaf_count INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Initialize with empty tree state
+INSERT OR IGNORE INTO merkle_root (id, root_hash, tree_height, leaf_count)
+VALUES (1, NULL, 0, 0);
+
+-- ============================================================================
+-- Sync State
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS sync_state (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Track last successful sync timestamps
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('catalog_last_sync', '');
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('encounters_last_sync', '');
+INSERT OR IGNORE INTO sync_state (key, value) VALUES ('last_synced_root', '');
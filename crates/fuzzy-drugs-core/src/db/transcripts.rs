@@ -0,0 +1,92 @@
+//! Chunked, compressed storage for oversized draft transcripts.
+
+use std::io::{Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use rusqlite::params;
+
+use super::{Database, DbError, DbResult};
+
+impl Database {
+    /// Validate a transcript against the configured limits.
+    pub fn check_transcript_limits(&self, transcript: &str) -> DbResult<()> {
+        self.limits
+            .check_transcript(transcript)
+            .map_err(DbError::LimitExceeded)
+    }
+
+    /// Replace any stored chunks for a draft with the given transcript.
+    ///
+    /// A transcript that fits inline leaves no chunks behind. Callers write
+    /// the draft row in the same transaction.
+    pub fn store_transcript_chunks(&self, draft_id: &str, transcript: &str) -> DbResult<()> {
+        self.conn.execute(
+            "DELETE FROM draft_transcript_chunks WHERE draft_id = ?",
+            [draft_id],
+        )?;
+
+        if !self.limits.should_chunk_transcript(transcript) {
+            return Ok(());
+        }
+
+        let compressed = compress(transcript)?;
+        let chunk_size = self.limits.transcript_chunk_bytes.max(1);
+        for (index, chunk) in compressed.chunks(chunk_size).enumerate() {
+            self.conn.execute(
+                "INSERT INTO draft_transcript_chunks (draft_id, chunk_index, data) VALUES (?, ?, ?)",
+                params![draft_id, index as i64, chunk],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reassemble a chunked transcript, if the draft has one.
+    pub fn load_transcript_chunks(&self, draft_id: &str) -> DbResult<Option<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT data FROM draft_transcript_chunks WHERE draft_id = ? ORDER BY chunk_index",
+        )?;
+        let rows = stmt.query_map([draft_id], |row| row.get::<_, Vec<u8>>(0))?;
+
+        let mut compressed = Vec::new();
+        let mut any = false;
+        for row in rows {
+            compressed.extend_from_slice(&row?);
+            any = true;
+        }
+
+        if !any {
+            return Ok(None);
+        }
+        Ok(Some(decompress(&compressed)?))
+    }
+}
+
+/// Deflate-compress transcript text.
+fn compress(text: &str) -> std::io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(text.as_bytes())?;
+    encoder.finish()
+}
+
+/// Inflate compressed transcript text.
+fn decompress(data: &[u8]) -> std::io::Result<String> {
+    let mut decoder = DeflateDecoder::new(data);
+    let mut text = String::new();
+    decoder.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_roundtrip() {
+        let text = "Give 100mg carprofen PO. ".repeat(1000);
+        let compressed = compress(&text).unwrap();
+        assert!(compressed.len() < text.len());
+        assert_eq!(decompress(&compressed).unwrap(), text);
+    }
+}
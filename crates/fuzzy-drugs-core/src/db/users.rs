@@ -0,0 +1,129 @@
+//! Users and their prescribing credentials.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbResult};
+use crate::models::User;
+
+impl Database {
+    /// Insert or update a user.
+    pub fn upsert_user(&self, user: &User) -> DbResult<()> {
+        let roles_json = serde_json::to_string(&user.roles)?;
+        self.conn.execute(
+            r#"
+            INSERT INTO users (
+                user_id, name, license_number, license_state, dea_number, roles, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))
+            ON CONFLICT(user_id) DO UPDATE SET
+                name = excluded.name,
+                license_number = excluded.license_number,
+                license_state = excluded.license_state,
+                dea_number = excluded.dea_number,
+                roles = excluded.roles,
+                updated_at = datetime('now')
+            "#,
+            params![
+                user.user_id,
+                user.name,
+                user.license_number,
+                user.license_state,
+                user.dea_number,
+                roles_json,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get a user by ID.
+    pub fn get_user(&self, user_id: &str) -> DbResult<Option<User>> {
+        let sql = format!("SELECT {} FROM users WHERE user_id = ?", USER_COLUMNS);
+        Ok(self.conn.query_row(&sql, [user_id], user_row).optional()?)
+    }
+
+    /// The user an encounter's `reviewed_by` names: by user ID, else by
+    /// display name (ignoring case).
+    pub fn find_reviewer(&self, reviewed_by: &str) -> DbResult<Option<User>> {
+        let sql = format!(
+            "SELECT {} FROM users WHERE user_id = ?1 OR name = ?2 COLLATE NOCASE
+             ORDER BY user_id = ?1 DESC LIMIT 1",
+            USER_COLUMNS
+        );
+        let reviewed_by = reviewed_by.trim();
+        Ok(self
+            .conn
+            .query_row(&sql, params![reviewed_by, reviewed_by], user_row)
+            .optional()?)
+    }
+
+    /// List users by name.
+    pub fn list_users(&self) -> DbResult<Vec<User>> {
+        let sql = format!(
+            "SELECT {} FROM users ORDER BY name COLLATE NOCASE",
+            USER_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let users = stmt.query_map([], user_row)?.collect::<Result<_, _>>()?;
+        Ok(users)
+    }
+
+    /// Delete a user. Encounters they committed keep their credentials.
+    pub fn delete_user(&self, user_id: &str) -> DbResult<bool> {
+        let rows_affected = self
+            .conn
+            .execute("DELETE FROM users WHERE user_id = ?", [user_id])?;
+        Ok(rows_affected > 0)
+    }
+}
+
+/// Columns selected for a user, in [`user_row`] order.
+const USER_COLUMNS: &str = "user_id, name, license_number, license_state, dea_number, roles";
+
+/// Map a row selected with [`USER_COLUMNS`].
+fn user_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<User> {
+    let roles: String = row.get(5)?;
+    let roles = serde_json::from_str(&roles).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    Ok(User {
+        user_id: row.get(0)?,
+        name: row.get(1)?,
+        license_number: row.get(2)?,
+        license_state: row.get(3)?,
+        dea_number: row.get(4)?,
+        roles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_crud_and_reviewer_lookup() {
+        let db = Database::open_in_memory().unwrap();
+        let mut smith = User::new("jsmith".into(), "Dr. Smith".into());
+        smith.license_number = Some("VET-12345".into());
+        smith.dea_number = Some("AB1234563".into());
+        smith.roles = vec!["dvm-lead".into()];
+        db.upsert_user(&smith).unwrap();
+        db.upsert_user(&User::new("alee".into(), "Dr. Lee".into()))
+            .unwrap();
+
+        assert_eq!(db.get_user("jsmith").unwrap(), Some(smith.clone()));
+        assert_eq!(db.find_reviewer("jsmith").unwrap(), Some(smith.clone()));
+        assert_eq!(db.find_reviewer(" dr. smith").unwrap(), Some(smith.clone()));
+        assert!(smith.has_role("DVM-Lead"));
+        assert!(!db.get_user("alee").unwrap().unwrap().has_role("dvm-lead"));
+        assert_eq!(db.find_reviewer("Dr. Jones").unwrap(), None);
+        let names: Vec<String> = db
+            .list_users()
+            .unwrap()
+            .into_iter()
+            .map(|u| u.name)
+            .collect();
+        assert_eq!(names, ["Dr. Lee", "Dr. Smith"]);
+
+        assert!(db.delete_user("alee").unwrap());
+        assert!(!db.delete_user("alee").unwrap());
+    }
+}
@@ -0,0 +1,253 @@
+//! Vaccination records and due dates.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbResult};
+use crate::models::{ReviewedEncounter, ServiceKind, Vaccination};
+
+impl Database {
+    /// Record a vaccine dose. Returns its row ID.
+    pub fn add_vaccination(&self, vaccination: &Vaccination) -> DbResult<i64> {
+        self.conn.execute(
+            r#"
+            INSERT INTO vaccinations (
+                patient_id, antigen, code, name, administered_on, lot_number,
+                expiration_date, next_due_date, administered_by, draft_id
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            "#,
+            params![
+                vaccination.patient_id,
+                vaccination.antigen,
+                vaccination.code,
+                vaccination.name,
+                vaccination.administered_on,
+                vaccination.lot_number,
+                vaccination.expiration_date,
+                vaccination.next_due_date,
+                vaccination.administered_by,
+                vaccination.draft_id,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Get a vaccination by row ID.
+    pub fn get_vaccination(&self, id: i64) -> DbResult<Option<Vaccination>> {
+        let sql = format!(
+            "SELECT {} FROM vaccinations WHERE id = ?",
+            VACCINATION_COLUMNS
+        );
+        Ok(self
+            .conn
+            .query_row(&sql, [id], vaccination_row)
+            .optional()?)
+    }
+
+    /// Record a dose for each line item on a committed encounter whose
+    /// service is a vaccine, given on the day it was reviewed by its
+    /// prescriber (or reviewer).
+    ///
+    /// Opens no transaction of its own; the commit runs it in the one that
+    /// adds the encounter leaf.
+    pub fn record_encounter_vaccinations(&self, encounter: &ReviewedEncounter) -> DbResult<()> {
+        let date = chrono::DateTime::parse_from_rfc3339(&encounter.reviewed_at)
+            .map(|t| t.date_naive())
+            .unwrap_or_else(|_| chrono::Utc::now().date_naive());
+        let administered_by = match &encounter.prescriber {
+            Some(prescriber) => prescriber.name.clone(),
+            None => encounter.reviewed_by.clone(),
+        };
+        for item in &encounter.line_items {
+            let Some(service) = self.get_service_item(&item.sku)? else {
+                continue;
+            };
+            if service.kind != ServiceKind::Vaccine {
+                continue;
+            }
+            let mut vaccination = Vaccination::from_line_item(
+                &encounter.patient_id,
+                &encounter.draft_id,
+                &service,
+                item,
+                date,
+            );
+            vaccination.administered_by = Some(administered_by.clone());
+            self.add_vaccination(&vaccination)?;
+        }
+        Ok(())
+    }
+
+    /// A patient's vaccinations, most recent first.
+    pub fn list_patient_vaccinations(&self, patient_id: &str) -> DbResult<Vec<Vaccination>> {
+        let sql = format!(
+            "SELECT {} FROM vaccinations WHERE patient_id = ?
+             ORDER BY administered_on DESC, id DESC",
+            VACCINATION_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let vaccinations = stmt
+            .query_map([patient_id], vaccination_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(vaccinations)
+    }
+
+    /// Each patient's latest dose of each antigen whose next dose is due on
+    /// or before `due_by` (YYYY-MM-DD), soonest due first. A later dose of
+    /// the same antigen supersedes an earlier one's due date.
+    pub fn list_due_vaccinations(&self, due_by: &str) -> DbResult<Vec<Vaccination>> {
+        let sql = format!(
+            "SELECT {} FROM vaccinations v
+             WHERE next_due_date IS NOT NULL AND next_due_date <= ?1
+               AND NOT EXISTS (
+                   SELECT 1 FROM vaccinations later
+                   WHERE later.patient_id = v.patient_id
+                     AND later.antigen = v.antigen COLLATE NOCASE
+                     AND (later.administered_on, later.id) > (v.administered_on, v.id))
+             ORDER BY next_due_date, patient_id",
+            VACCINATION_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let vaccinations = stmt
+            .query_map([due_by], vaccination_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(vaccinations)
+    }
+
+    /// Delete a vaccination recorded by mistake.
+    pub fn delete_vaccination(&self, id: i64) -> DbResult<bool> {
+        let rows_affected = self
+            .conn
+            .execute("DELETE FROM vaccinations WHERE id = ?", [id])?;
+        Ok(rows_affected > 0)
+    }
+}
+
+/// Columns selected for a vaccination, in [`vaccination_row`] order.
+const VACCINATION_COLUMNS: &str = "id, patient_id, antigen, code, name, administered_on, \
+     lot_number, expiration_date, next_due_date, administered_by, draft_id";
+
+/// Map a row selected with [`VACCINATION_COLUMNS`].
+fn vaccination_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Vaccination> {
+    Ok(Vaccination {
+        id: row.get(0)?,
+        patient_id: row.get(1)?,
+        antigen: row.get(2)?,
+        code: row.get(3)?,
+        name: row.get(4)?,
+        administered_on: row.get(5)?,
+        lot_number: row.get(6)?,
+        expiration_date: row.get(7)?,
+        next_due_date: row.get(8)?,
+        administered_by: row.get(9)?,
+        draft_id: row.get(10)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Patient, ServiceItem, ServiceLineItem, ServiceMention};
+
+    fn vaccine(code: &str, antigen: &str, interval: u32) -> ServiceItem {
+        let mut item = ServiceItem::new(code.into(), code.into(), ServiceKind::Vaccine);
+        item.antigen = Some(antigen.into());
+        item.booster_interval_days = Some(interval);
+        item
+    }
+
+    #[test]
+    fn test_committed_vaccines_and_due_list() {
+        let db = Database::open_in_memory().unwrap();
+        let patient = Patient::new("Max".into(), "canine".into());
+        db.insert_patient(&patient).unwrap();
+        let rabies_1yr = vaccine("VAC-RAB1", "rabies", 365);
+        let rabies_3yr = vaccine("VAC-RAB3", "Rabies", 1095);
+        let dhpp = vaccine("VAC-DHPP", "dhpp", 21);
+        for item in [&rabies_1yr, &rabies_3yr, &dhpp] {
+            db.upsert_service_item(item).unwrap();
+        }
+        let nails = ServiceItem::new(
+            "PROC-NAIL".into(),
+            "Nail Trim".into(),
+            ServiceKind::Procedure,
+        );
+        db.upsert_service_item(&nails).unwrap();
+
+        let line_item = |service: &ServiceItem, lot: Option<&str>| {
+            let item = ServiceLineItem {
+                mention: ServiceMention {
+                    raw_text: service.name.clone(),
+                    service_name: service.name.clone(),
+                    kind: Some(service.kind),
+                    quantity: None,
+                    start_offset: 0,
+                    end_offset: 0,
+                },
+                code: service.code.clone(),
+                name: service.name.clone(),
+                kind: service.kind,
+                quantity: 1.0,
+                confidence: 1.0,
+                lot_number: lot.map(Into::into),
+                expiration_date: lot.map(|_| "2027-01-31".into()),
+            };
+            item.to_line_item()
+        };
+        let encounter = |draft_id: &str, reviewed_at: &str, items| ReviewedEncounter {
+            draft_id: draft_id.into(),
+            patient_id: patient.local_id.clone(),
+            patient_server_id: None,
+            transcript: String::new(),
+            line_items: items,
+            reviewed_by: "Dr. Smith".into(),
+            reviewed_at: reviewed_at.into(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        };
+        let first = encounter(
+            "draft-1",
+            "2025-03-01T10:00:00Z",
+            vec![
+                line_item(&rabies_1yr, Some("R1")),
+                line_item(&dhpp, None),
+                line_item(&nails, None),
+            ],
+        );
+        db.record_encounter_vaccinations(&first).unwrap();
+
+        let history = db.list_patient_vaccinations(&patient.local_id).unwrap();
+        assert_eq!(history.len(), 2);
+        let rabies = history.iter().find(|v| v.code == "VAC-RAB1").unwrap();
+        assert_eq!(rabies.lot_number.as_deref(), Some("R1"));
+        assert_eq!(rabies.next_due_date.as_deref(), Some("2026-03-01"));
+        assert_eq!(rabies.administered_by.as_deref(), Some("Dr. Smith"));
+        assert_eq!(
+            db.get_vaccination(rabies.id).unwrap().as_ref(),
+            Some(rabies)
+        );
+
+        let due: Vec<String> = db
+            .list_due_vaccinations("2026-03-01")
+            .unwrap()
+            .into_iter()
+            .map(|v| v.code)
+            .collect();
+        assert_eq!(due, ["VAC-DHPP", "VAC-RAB1"]);
+
+        // A 3-year rabies booster supersedes the 1-year dose's due date
+        let booster = encounter(
+            "draft-2",
+            "2026-02-20T10:00:00Z",
+            vec![line_item(&rabies_3yr, Some("R2"))],
+        );
+        db.record_encounter_vaccinations(&booster).unwrap();
+        let due = db.list_due_vaccinations("2026-03-01").unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].antigen, "dhpp");
+
+        assert!(db.delete_vaccination(due[0].id).unwrap());
+        assert!(db.list_due_vaccinations("2026-03-01").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Visits and the drafts linked to them.
+
+use rusqlite::{params, OptionalExtension};
+
+use super::{Database, DbResult};
+use crate::models::Visit;
+
+impl Database {
+    /// Insert or update a visit.
+    pub fn upsert_visit(&self, visit: &Visit) -> DbResult<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO visits (
+                visit_id, patient_id, visit_date, reason, attending_vet, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))
+            ON CONFLICT(visit_id) DO UPDATE SET
+                visit_date = excluded.visit_date,
+                reason = excluded.reason,
+                attending_vet = excluded.attending_vet,
+                updated_at = datetime('now')
+            "#,
+            params![
+                visit.visit_id,
+                visit.patient_id,
+                visit.visit_date,
+                visit.reason,
+                visit.attending_vet,
+                visit.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get a visit by ID.
+    pub fn get_visit(&self, visit_id: &str) -> DbResult<Option<Visit>> {
+        let sql = format!("SELECT {} FROM visits WHERE visit_id = ?", VISIT_COLUMNS);
+        Ok(self
+            .conn
+            .query_row(&sql, [visit_id], visit_row)
+            .optional()?)
+    }
+
+    /// A patient's visits, most recent first.
+    pub fn list_patient_visits(&self, patient_id: &str) -> DbResult<Vec<Visit>> {
+        let sql = format!(
+            "SELECT {} FROM visits WHERE patient_id = ? ORDER BY visit_date DESC, created_at DESC",
+            VISIT_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let visits = stmt
+            .query_map([patient_id], visit_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(visits)
+    }
+
+    /// IDs of the drafts linked to a visit, oldest first.
+    pub fn list_visit_draft_ids(&self, visit_id: &str) -> DbResult<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT draft_id FROM encounter_drafts WHERE visit_id = ? ORDER BY created_at, rowid",
+        )?;
+        let ids = stmt
+            .query_map([visit_id], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(ids)
+    }
+
+    /// Link a draft to a visit, or unlink it with `None`. Returns false if
+    /// the draft is unknown.
+    pub fn set_draft_visit(&self, draft_id: &str, visit_id: Option<&str>) -> DbResult<bool> {
+        let rows_affected = self.conn.execute(
+            "UPDATE encounter_drafts SET visit_id = ?2, updated_at = datetime('now')
+             WHERE draft_id = ?1",
+            params![draft_id, visit_id],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Delete a visit. Its drafts are kept, unlinked; committed encounters
+    /// keep the visit ID they were committed with.
+    pub fn delete_visit(&self, visit_id: &str) -> DbResult<bool> {
+        let rows_affected = self
+            .conn
+            .execute("DELETE FROM visits WHERE visit_id = ?", [visit_id])?;
+        Ok(rows_affected > 0)
+    }
+}
+
+/// Columns selected for a visit, in [`visit_row`] order.
+const VISIT_COLUMNS: &str =
+    "visit_id, patient_id, visit_date, reason, attending_vet, created_at, updated_at";
+
+/// Map a row selected with [`VISIT_COLUMNS`].
+fn visit_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Visit> {
+    Ok(Visit {
+        visit_id: row.get(0)?,
+        patient_id: row.get(1)?,
+        visit_date: row.get(2)?,
+        reason: row.get(3)?,
+        attending_vet: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EncounterDraft, Patient};
+
+    #[test]
+    fn test_visit_links_drafts() {
+        let db = Database::open_in_memory().unwrap();
+        let patient = Patient::new("Max".into(), "canine".into());
+        db.insert_patient(&patient).unwrap();
+
+        let mut visit = Visit::new(patient.local_id.clone(), "2026-03-01".into());
+        visit.reason = Some("Limping".into());
+        db.upsert_visit(&visit).unwrap();
+        visit.attending_vet = Some("Dr. Smith".into());
+        db.upsert_visit(&visit).unwrap();
+        let stored = db.get_visit(&visit.visit_id).unwrap().unwrap();
+        assert_eq!(stored.attending_vet.as_deref(), Some("Dr. Smith"));
+        assert_eq!(db.list_patient_visits(&patient.local_id).unwrap().len(), 1);
+
+        let mut exam = EncounterDraft::new(patient.local_id.clone());
+        exam.visit_id = Some(visit.visit_id.clone());
+        db.insert_draft(&exam).unwrap();
+        let discharge = EncounterDraft::new(patient.local_id.clone());
+        db.insert_draft(&discharge).unwrap();
+        assert!(db
+            .set_draft_visit(&discharge.draft_id, Some(&visit.visit_id))
+            .unwrap());
+        assert!(!db
+            .set_draft_visit("missing", Some(&visit.visit_id))
+            .unwrap());
+        assert_eq!(
+            db.list_visit_draft_ids(&visit.visit_id).unwrap(),
+            [exam.draft_id.clone(), discharge.draft_id]
+        );
+        let stored = db.get_draft(&exam.draft_id).unwrap().unwrap();
+        assert_eq!(stored.visit_id.as_ref(), Some(&visit.visit_id));
+
+        // Deleting the visit unlinks its drafts
+        assert!(db.delete_visit(&visit.visit_id).unwrap());
+        assert_eq!(
+            db.get_draft(&exam.draft_id).unwrap().unwrap().visit_id,
+            None
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Audit bundles: everything an auditor needs to verify a period offline.
+//!
+//! An [`AuditBundle`] holds the compliance export for a date range, the full
+//! Merkle tree, plain-language verification steps, and a manifest listing
+//! the SHA-256 of every other file. The manifest carries an
+//! [`ExportIntegrity`] section for the tree's root hash and leaf count,
+//! signed when the clinic has an export signing key, so
+//! [`verify_export`](super::verify_export) on the manifest checks the signed
+//! root and, through the checksums, every file in the bundle. Zip archives
+//! need the `zip` feature.
+
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::merkle::TreeExport;
+
+use super::{BatchComplianceExport, ExportIntegrity, ExportResult};
+
+/// Name of the manifest inside a bundle.
+pub const AUDIT_MANIFEST_FILE: &str = "manifest.json";
+
+/// How to check a bundle by hand or with any SHA-256 and Ed25519 tool.
+const VERIFY_INSTRUCTIONS: &str = r#"# Verifying this audit bundle
+
+Every check below needs only SHA-256 (and Ed25519 if the bundle is signed).
+Hashes are lowercase hex.
+
+1. manifest.json: remove its "integrity" key, sort every object's keys,
+   serialize without whitespace, and take the SHA-256. It must equal
+   integrity.body_hash. If integrity.signature is present, it is an Ed25519
+   signature by integrity.public_key over the integrity section itself
+   (minus "signature"), canonicalized the same way. Compare public_key with
+   the key the clinic published.
+2. Each entry in manifest.json "files" gives the SHA-256 and size of a file
+   in this bundle. Recompute them.
+3. tree.json lists every node. A leaf's hash is the SHA-256 of its payload
+   string. An internal node's hash is the SHA-256 of the concatenated hex
+   hashes of its left and right children; a node without a right child
+   uses its left child twice. The node with hash root_hash must be the
+   root, and root_hash and leaf_count must match the manifest.
+4. compliance.json has each encounter in the period with an inclusion
+   proof. Starting from proof.leaf_hash, for each audit_path entry hash the
+   concatenation (current + entry.hash if position is "right", entry.hash +
+   current if "left"). The result must be proof.root_hash, which must match
+   the manifest. The leaf_hash must be a leaf in tree.json whose payload is
+   the encounter.
+"#;
+
+/// One file in an audit bundle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditBundleFile {
+    pub name: String,
+    pub contents: Vec<u8>,
+}
+
+/// Checksum of one bundle file, as listed in the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditFileEntry {
+    pub name: String,
+    pub bytes: u64,
+    /// Hex SHA-256 of the file contents
+    pub sha256: String,
+}
+
+/// Machine-readable description of an audit bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditManifest {
+    pub format_version: String,
+    pub created_at: String,
+    /// Start of the audited period (exclusive)
+    pub period_start: String,
+    /// End of the audited period (inclusive)
+    pub period_end: String,
+    /// Merkle root hash of the bundled tree
+    pub root_hash: String,
+    pub tree_height: u32,
+    pub leaf_count: u32,
+    /// Encounters in the compliance export
+    pub encounter_count: usize,
+    /// Every other file in the bundle
+    pub files: Vec<AuditFileEntry>,
+    /// Signed root hash and leaf count, and the hash of this manifest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<ExportIntegrity>,
+}
+
+/// Compliance export, tree, verification steps, and sealed manifest for one
+/// audited period.
+#[derive(Debug, Clone)]
+pub struct AuditBundle {
+    pub manifest: AuditManifest,
+    /// Bundle files other than the manifest
+    pub files: Vec<AuditBundleFile>,
+}
+
+impl AuditBundle {
+    /// Assemble a bundle for encounters committed after `start` and up to
+    /// `end`, sealing the manifest with `signing_key` if given.
+    pub fn new(
+        compliance: &BatchComplianceExport,
+        tree: &TreeExport,
+        start: &str,
+        end: &str,
+        signing_key: Option<&SigningKey>,
+    ) -> ExportResult<Self> {
+        let files = vec![
+            AuditBundleFile {
+                name: "compliance.json".to_string(),
+                contents: compliance.to_json()?.into_bytes(),
+            },
+            AuditBundleFile {
+                name: "tree.json".to_string(),
+                contents: serde_json::to_vec_pretty(tree)?,
+            },
+            AuditBundleFile {
+                name: "VERIFY.md".to_string(),
+                contents: VERIFY_INSTRUCTIONS.as_bytes().to_vec(),
+            },
+        ];
+
+        let mut manifest = AuditManifest {
+            format_version: "1.0".to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            period_start: start.to_string(),
+            period_end: end.to_string(),
+            root_hash: tree.root_hash.clone(),
+            tree_height: tree.tree_height,
+            leaf_count: tree.leaf_count,
+            encounter_count: compliance.encounters.len(),
+            files: files
+                .iter()
+                .map(|file| AuditFileEntry {
+                    name: file.name.clone(),
+                    bytes: file.contents.len() as u64,
+                    sha256: hex::encode(Sha256::digest(&file.contents)),
+                })
+                .collect(),
+            integrity: None,
+        };
+        manifest.integrity = Some(ExportIntegrity::seal(
+            &manifest,
+            tree.root_hash.clone(),
+            tree.leaf_count,
+            signing_key,
+        )?);
+
+        Ok(Self { manifest, files })
+    }
+
+    /// The manifest as pretty JSON.
+    pub fn manifest_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(&self.manifest)
+    }
+
+    /// Bundle files whose contents no longer match the manifest checksums.
+    pub fn modified_files(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|file| {
+                let expected = self.manifest.files.iter().find(|e| e.name == file.name);
+                expected.map(|e| e.sha256.as_str())
+                    != Some(hex::encode(Sha256::digest(&file.contents)).as_str())
+            })
+            .map(|file| file.name.clone())
+            .collect()
+    }
+}
+
+#[cfg(feature = "zip")]
+mod archive {
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use std::path::Path;
+
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    use super::{AuditBundle, AUDIT_MANIFEST_FILE};
+    use crate::export::file::partial_path;
+    use crate::export::{ExportError, ExportResult};
+
+    impl From<zip::result::ZipError> for ExportError {
+        fn from(e: zip::result::ZipError) -> Self {
+            ExportError::Zip(e.to_string())
+        }
+    }
+
+    impl AuditBundle {
+        /// The bundle as a zip archive, manifest first.
+        pub fn to_zip(&self) -> ExportResult<Vec<u8>> {
+            let options =
+                SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+            let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+            zip.start_file(AUDIT_MANIFEST_FILE, options)?;
+            zip.write_all(self.manifest_json()?.as_bytes())?;
+            for file in &self.files {
+                zip.start_file(file.name.as_str(), options)?;
+                zip.write_all(&file.contents)?;
+            }
+            Ok(zip.finish()?.into_inner())
+        }
+
+        /// Write the zip archive to `path`, returning its size. An existing
+        /// file at `path` is replaced only once the archive is complete.
+        pub fn write_zip(&self, path: &Path) -> ExportResult<u64> {
+            let bytes = self.to_zip()?;
+            let partial = partial_path(path);
+            if let Err(e) = fs::write(&partial, &bytes) {
+                let _ = fs::remove_file(&partial);
+                return Err(e.into());
+            }
+            fs::rename(&partial, path)?;
+            Ok(bytes.len() as u64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::export::{verify_export, ComplianceExporter};
+    use crate::merkle::{MerkleTree, SyncManager};
+    use crate::models::{EncounterLineItem, ResolutionMethod, ReviewedEncounter};
+
+    fn make_encounter(id: &str) -> ReviewedEncounter {
+        ReviewedEncounter {
+            draft_id: id.to_string(),
+            patient_id: "patient-1".to_string(),
+            patient_server_id: None,
+            transcript: "Test transcript".to_string(),
+            line_items: vec![EncounterLineItem {
+                sku: "SKU001".to_string(),
+                name: "Test Drug".to_string(),
+                quantity: 1.0,
+                unit: "tablet".into(),
+                route: Some("PO".to_string()),
+                original_mention: "test drug".to_string(),
+                resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                controlled_schedule: None,
+                source_spans: vec![],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
+            }],
+            reviewed_by: "Dr. Smith".to_string(),
+            reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+
+    fn make_bundle(signing_key: Option<&SigningKey>) -> AuditBundle {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+        let compliance = ComplianceExporter::new(&db)
+            .export_date_range("2000-01-01 00:00:00", "9999-12-31 23:59:59")
+            .unwrap();
+        let tree = SyncManager::new(&db).export_full_tree().unwrap();
+        AuditBundle::new(
+            &compliance,
+            &tree,
+            "2000-01-01 00:00:00",
+            "9999-12-31 23:59:59",
+            signing_key,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_manifest_is_sealed_and_lists_files() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut bundle = make_bundle(Some(&key));
+        let manifest = &bundle.manifest;
+        assert_eq!(manifest.encounter_count, 2);
+        assert_eq!(manifest.leaf_count, 2);
+        let names: Vec<&str> = manifest.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["compliance.json", "tree.json", "VERIFY.md"]);
+
+        let trusted = hex::encode(key.verifying_key().as_bytes());
+        let verification = verify_export(&bundle.manifest_json().unwrap(), Some(&trusted)).unwrap();
+        assert!(verification.is_valid());
+        assert_eq!(verification.signature_valid, Some(true));
+        assert_eq!(verification.root_hash, manifest.root_hash);
+
+        let compliance: BatchComplianceExport =
+            serde_json::from_slice(&bundle.files[0].contents).unwrap();
+        assert_eq!(compliance.metadata.root_hash, manifest.root_hash);
+        assert!(compliance.verify_all_proofs().iter().all(|p| p.is_valid));
+
+        assert!(bundle.modified_files().is_empty());
+        bundle.files[1].contents.push(b'\n');
+        assert_eq!(bundle.modified_files(), ["tree.json"]);
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_zip_round_trip() {
+        use std::io::Read;
+
+        let bundle = make_bundle(None);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.zip");
+        let bytes = bundle.write_zip(&path).unwrap();
+        assert_eq!(bytes, std::fs::metadata(&path).unwrap().len());
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(archive.len(), 4);
+        let mut manifest = String::new();
+        archive
+            .by_name(AUDIT_MANIFEST_FILE)
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .unwrap();
+        assert!(verify_export(&manifest, None).unwrap().body_hash_valid);
+        let mut tree = Vec::new();
+        archive
+            .by_name("tree.json")
+            .unwrap()
+            .read_to_end(&mut tree)
+            .unwrap();
+        assert_eq!(tree, bundle.files[1].contents);
+    }
+}
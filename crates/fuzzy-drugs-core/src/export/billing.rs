@@ -1,10 +1,25 @@
 //! Billing export for PIMS integration.
 
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::SigningKey;
 use serde::{Deserialize, Serialize};
 
-use crate::db::Database;
-use crate::merkle::{MerkleResult, MerkleTree};
-use crate::models::ReviewedEncounter;
+use crate::db::{Database, DbError};
+use crate::merkle::{is_encounter_payload, MerkleResult, MerkleTree};
+use crate::models::{
+    round_cents, Client, CsvColumn, CsvLayout, Patient, Pricing, ReviewedEncounter, TaxRates,
+};
+use crate::progress::Progress;
+use crate::resolver::DispensingCalculator;
+
+use super::{
+    write_export_file, Deidentifier, ExportFormat, ExportIntegrity, ExportManifest, ExportResult,
+    Invoice, InvoiceDetails, Phrasebook, IIF_HEADER,
+};
 
 /// Billing export for a single encounter.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +28,9 @@ pub struct BillingExport {
     pub metadata: BillingMetadata,
     /// Line items for billing
     pub line_items: Vec<BillingLineItem>,
+    /// Charges for the line items
+    #[serde(default)]
+    pub totals: BillingTotals,
 }
 
 /// Billing export metadata.
@@ -24,6 +42,13 @@ pub struct BillingMetadata {
     pub patient_id: String,
     /// Patient server ID (if synced)
     pub patient_server_id: Option<String>,
+    /// Client (owner) the patient is linked to, from the local patient
+    /// record at export time
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Visit the encounter was committed under
+    #[serde(default)]
+    pub visit_id: Option<String>,
     /// Vet who reviewed
     pub reviewed_by: String,
     /// Review timestamp
@@ -32,6 +57,18 @@ pub struct BillingMetadata {
     pub exported_at: String,
     /// Merkle leaf hash for audit trail
     pub merkle_leaf_hash: String,
+    /// Device that committed the encounter
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// Reviewer's state license number, as committed
+    #[serde(default)]
+    pub prescriber_license: Option<String>,
+    /// Reviewer's DEA registration number, as committed
+    #[serde(default)]
+    pub prescriber_dea: Option<String>,
+    /// Patient identifiers are pseudonyms
+    #[serde(default)]
+    pub redacted: bool,
 }
 
 /// Single line item for billing.
@@ -47,20 +84,125 @@ pub struct BillingLineItem {
     pub unit: String,
     /// Route of administration (optional)
     pub route: Option<String>,
+    /// DEA schedule ("C-II" ... "C-V") for controlled substances
+    pub controlled_schedule: Option<String>,
+    /// Route expanded for the export's phrasebook (e.g., "Oral")
+    #[serde(default)]
+    pub route_description: Option<String>,
+    /// "administered", "dispensed", or "prescribed"
+    #[serde(default)]
+    pub disposition: Option<String>,
+    /// `quantity` in the catalog item's dispensing units (tablets, mL),
+    /// which `unit_price` is per; None if the dose can't be converted
+    #[serde(default)]
+    pub dispensing_quantity: Option<f64>,
+    /// Unit of `dispensing_quantity` ("tablets", "mL")
+    #[serde(default)]
+    pub dispensing_unit: Option<String>,
+    /// Catalog price per dispensing unit, after markup, at export time
+    #[serde(default)]
+    pub unit_price: Option<f64>,
+    /// Charge for this line: the priced quantity × `unit_price`, raised to
+    /// the catalog's minimum charge
+    #[serde(default)]
+    pub extended_price: Option<f64>,
+    /// Catalog sales tax code
+    #[serde(default)]
+    pub tax_code: Option<String>,
+    /// Sales tax on `extended_price` at the clinic's rate for `tax_code`
+    #[serde(default)]
+    pub tax: Option<f64>,
+}
+
+impl BillingLineItem {
+    /// Quantity and unit the line is priced in: the dispensing quantity, or
+    /// the dictated quantity when the dose couldn't be converted.
+    pub fn priced_quantity(&self) -> (f64, &str) {
+        match (self.dispensing_quantity, &self.dispensing_unit) {
+            (Some(quantity), Some(unit)) => (quantity, unit),
+            _ => (self.quantity, &self.unit),
+        }
+    }
+}
+
+/// Invoice totals for one encounter.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BillingTotals {
+    /// Sum of extended prices, before tax
+    pub total: f64,
+    /// Sum of line taxes
+    #[serde(default)]
+    pub tax: f64,
+    /// `total + tax`
+    #[serde(default)]
+    pub total_with_tax: f64,
+    /// Items with a charge
+    pub priced_items: usize,
+    /// Items without a price (excluded from the total)
+    pub unpriced_items: usize,
+}
+
+impl BillingTotals {
+    /// Totals for `items`.
+    pub fn of(items: &[BillingLineItem]) -> Self {
+        let priced: Vec<f64> = items.iter().filter_map(|i| i.extended_price).collect();
+        // Lines are in cents; rounding the sums drops float noise
+        let total = round_cents(priced.iter().sum());
+        let tax = round_cents(items.iter().filter_map(|i| i.tax).sum());
+        Self {
+            total,
+            tax,
+            total_with_tax: round_cents(total + tax),
+            priced_items: priced.len(),
+            unpriced_items: items.len() - priced.len(),
+        }
+    }
+
+    /// Add `other` to these totals.
+    pub fn add(&mut self, other: &BillingTotals) {
+        self.total = round_cents(self.total + other.total);
+        self.tax = round_cents(self.tax + other.tax);
+        self.total_with_tax = round_cents(self.total_with_tax + other.total_with_tax);
+        self.priced_items += other.priced_items;
+        self.unpriced_items += other.unpriced_items;
+    }
 }
 
 impl BillingExport {
     /// Create billing export from a reviewed encounter and its Merkle hash.
     pub fn from_encounter(encounter: &ReviewedEncounter, merkle_hash: &str) -> Self {
-        let line_items = encounter
+        Self::from_encounter_with_phrasebook(encounter, merkle_hash, &Phrasebook::default())
+    }
+
+    /// Create billing export, expanding route codes with the given phrasebook.
+    pub fn from_encounter_with_phrasebook(
+        encounter: &ReviewedEncounter,
+        merkle_hash: &str,
+        phrasebook: &Phrasebook,
+    ) -> Self {
+        let prescriber = encounter.prescriber.as_ref();
+        let line_items: Vec<BillingLineItem> = encounter
             .line_items
             .iter()
             .map(|item| BillingLineItem {
                 sku: item.sku.clone(),
                 description: item.name.clone(),
                 quantity: item.quantity,
-                unit: item.unit.clone(),
+                unit: item.unit.to_string(),
                 route: item.route.clone(),
+                controlled_schedule: item.controlled_schedule.map(|s| s.to_string()),
+                route_description: item
+                    .route
+                    .as_deref()
+                    .and_then(|r| phrasebook.route(r))
+                    .map(str::to_string),
+                disposition: item.disposition.map(|d| d.as_str().to_string()),
+                dispensing_quantity: None,
+                dispensing_unit: None,
+                unit_price: None,
+                extended_price: None,
+                tax_code: None,
+                tax: None,
             })
             .collect();
 
@@ -69,46 +211,179 @@ impl BillingExport {
                 draft_id: encounter.draft_id.clone(),
                 patient_id: encounter.patient_id.clone(),
                 patient_server_id: encounter.patient_server_id.clone(),
+                client_id: None,
+                visit_id: encounter.visit_id.clone(),
                 reviewed_by: encounter.reviewed_by.clone(),
                 reviewed_at: encounter.reviewed_at.clone(),
                 exported_at: chrono::Utc::now().to_rfc3339(),
                 merkle_leaf_hash: merkle_hash.to_string(),
+                device_id: encounter.device_id.clone(),
+                prescriber_license: prescriber.and_then(|p| p.license_number.clone()),
+                prescriber_dea: prescriber.and_then(|p| p.dea_number.clone()),
+                redacted: false,
             },
+            totals: BillingTotals::of(&line_items),
             line_items,
         }
     }
 
+    /// Price and tax each line item from the catalog `pricing` (by SKU)
+    /// and the clinic's `tax_rates`, then recompute the totals. Items are
+    /// priced by their dispensing quantity when set, and each line's price
+    /// and tax is rounded to cents before the totals add them. Items whose
+    /// SKU isn't in `pricing` are unpriced; items whose tax code has no
+    /// rate are untaxed.
+    pub fn apply_pricing(&mut self, pricing: &HashMap<String, Pricing>, tax_rates: &TaxRates) {
+        for item in &mut self.line_items {
+            let pricing = pricing.get(&item.sku);
+            item.unit_price = pricing.and_then(Pricing::marked_up_unit_price);
+            let (quantity, _) = item.priced_quantity();
+            item.extended_price = pricing.and_then(|p| p.charge(quantity));
+            item.tax_code = pricing.and_then(|p| p.tax_code.clone());
+            let rate = item
+                .tax_code
+                .as_deref()
+                .and_then(|code| tax_rates.rate(code));
+            item.tax = item
+                .extended_price
+                .zip(rate)
+                .map(|(price, rate)| round_cents(price * rate));
+        }
+        self.totals = BillingTotals::of(&self.line_items);
+    }
+
     /// Export to JSON.
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string_pretty(self)
     }
 
-    /// Export to CSV format.
+    /// Export to CSV format with the original columns.
     pub fn to_csv(&self) -> String {
-        let mut csv = String::new();
+        self.to_csv_with_layout(&CsvLayout::default())
+    }
 
-        // Header
-        csv.push_str("draft_id,patient_id,sku,description,quantity,unit,route,reviewed_by,reviewed_at,merkle_hash\n");
+    /// Export to CSV with the given columns, headers, and formatting.
+    pub fn to_csv_with_layout(&self, layout: &CsvLayout) -> String {
+        let mut csv = csv_header(layout);
+        csv.push_str(&self.csv_rows(layout));
+        csv
+    }
 
-        // Lines
+    /// CSV rows for this encounter's line items, without the header.
+    fn csv_rows(&self, layout: &CsvLayout) -> String {
+        let mut csv = String::new();
         for item in &self.line_items {
-            csv.push_str(&format!(
-                "{},{},{},{},{},{},{},{},{},{}\n",
-                escape_csv(&self.metadata.draft_id),
-                escape_csv(&self.metadata.patient_id),
-                escape_csv(&item.sku),
-                escape_csv(&item.description),
-                item.quantity,
-                escape_csv(&item.unit),
-                item.route.as_deref().unwrap_or(""),
-                escape_csv(&self.metadata.reviewed_by),
-                escape_csv(&self.metadata.reviewed_at),
-                escape_csv(&self.metadata.merkle_leaf_hash),
-            ));
+            let fields: Vec<String> = layout
+                .columns
+                .iter()
+                .map(|c| escape_field(&self.csv_field(item, c.column, layout), layout.delimiter))
+                .collect();
+            csv.push_str(&fields.join(&layout.delimiter.to_string()));
+            csv.push('\n');
         }
-
         csv
     }
+
+    /// Unescaped value of `column` for `item`.
+    fn csv_field(&self, item: &BillingLineItem, column: CsvColumn, layout: &CsvLayout) -> String {
+        let metadata = &self.metadata;
+        let text = |s: &Option<String>| s.clone().unwrap_or_default();
+        let date = |s: &str| format_timestamp(s, layout.date_format.as_deref());
+        let money = |n: Option<f64>| n.map(|n| format!("{:.2}", n)).unwrap_or_default();
+        match column {
+            CsvColumn::DraftId => metadata.draft_id.clone(),
+            CsvColumn::PatientId => metadata.patient_id.clone(),
+            CsvColumn::PatientServerId => text(&metadata.patient_server_id),
+            CsvColumn::ClientId => text(&metadata.client_id),
+            CsvColumn::Sku => item.sku.clone(),
+            CsvColumn::Description => item.description.clone(),
+            CsvColumn::Quantity => item.quantity.to_string(),
+            CsvColumn::Unit => item.unit.clone(),
+            CsvColumn::Route => text(&item.route),
+            CsvColumn::RouteDescription => text(&item.route_description),
+            CsvColumn::ControlledSchedule => text(&item.controlled_schedule),
+            CsvColumn::Disposition => text(&item.disposition),
+            CsvColumn::UnitPrice => money(item.unit_price),
+            CsvColumn::Amount => money(item.extended_price),
+            CsvColumn::TaxCode => text(&item.tax_code),
+            CsvColumn::Tax => money(item.tax),
+            CsvColumn::ReviewedBy => metadata.reviewed_by.clone(),
+            CsvColumn::ReviewedAt => date(&metadata.reviewed_at),
+            CsvColumn::ExportedAt => date(&metadata.exported_at),
+            CsvColumn::MerkleHash => metadata.merkle_leaf_hash.clone(),
+            CsvColumn::DeviceId => text(&metadata.device_id),
+            CsvColumn::PrescriberLicense => text(&metadata.prescriber_license),
+            CsvColumn::PrescriberDea => text(&metadata.prescriber_dea),
+        }
+    }
+}
+
+/// Which committed encounters and items a filtered billing export includes.
+/// Unset fields match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BillingFilter {
+    /// Patient local ID
+    pub patient_id: Option<String>,
+    /// Client the patient is linked to
+    pub client_id: Option<String>,
+    /// Visit the encounter was committed under
+    pub visit_id: Option<String>,
+    /// Reviewing vet (case-insensitive)
+    pub reviewed_by: Option<String>,
+    /// Reviewed at or after
+    pub start: Option<DateTime<Utc>>,
+    /// Reviewed at or before
+    pub end: Option<DateTime<Utc>>,
+    /// Only these SKUs; encounters with none of them are left out
+    pub skus: Vec<String>,
+}
+
+impl BillingFilter {
+    /// Whether the encounter described by `metadata` is included.
+    pub fn matches(&self, metadata: &BillingMetadata) -> bool {
+        if self
+            .patient_id
+            .as_ref()
+            .is_some_and(|id| *id != metadata.patient_id)
+        {
+            return false;
+        }
+        if self
+            .client_id
+            .as_ref()
+            .is_some_and(|id| metadata.client_id.as_ref() != Some(id))
+        {
+            return false;
+        }
+        if self
+            .visit_id
+            .as_ref()
+            .is_some_and(|id| metadata.visit_id.as_ref() != Some(id))
+        {
+            return false;
+        }
+        if self
+            .reviewed_by
+            .as_ref()
+            .is_some_and(|vet| !vet.trim().eq_ignore_ascii_case(metadata.reviewed_by.trim()))
+        {
+            return false;
+        }
+        if self.start.is_none() && self.end.is_none() {
+            return true;
+        }
+        let Ok(reviewed_at) = DateTime::parse_from_rfc3339(&metadata.reviewed_at) else {
+            return false;
+        };
+        let reviewed_at = reviewed_at.with_timezone(&Utc);
+        self.start.is_none_or(|start| reviewed_at >= start)
+            && self.end.is_none_or(|end| reviewed_at <= end)
+    }
+
+    /// Whether a line item with `sku` is included.
+    pub fn includes_sku(&self, sku: &str) -> bool {
+        self.skus.is_empty() || self.skus.iter().any(|s| s == sku)
+    }
 }
 
 /// Batch billing export.
@@ -116,10 +391,29 @@ impl BillingExport {
 pub struct BatchBillingExport {
     /// Export timestamp
     pub exported_at: String,
+    /// Device that produced the export
+    #[serde(default)]
+    pub exported_by_device: Option<String>,
     /// Individual encounter exports
     pub encounters: Vec<BillingExport>,
+    /// Encounters grouped by who is billed: one group per client, and one
+    /// per patient not linked to a client
+    #[serde(default)]
+    pub clients: Vec<ClientBilling>,
+    /// Encounters committed under a visit, grouped by visit
+    #[serde(default)]
+    pub visits: Vec<VisitBilling>,
     /// Total line item count
     pub total_items: usize,
+    /// Patient identifiers are pseudonyms
+    #[serde(default)]
+    pub redacted: bool,
+    /// Export ledger batch, for unbilled exports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    /// Hash and optional signature of this export
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<ExportIntegrity>,
 }
 
 impl BatchBillingExport {
@@ -128,33 +422,114 @@ impl BatchBillingExport {
         serde_json::to_string_pretty(self)
     }
 
-    /// Export to CSV format.
+    /// Export to CSV format with the original columns.
     pub fn to_csv(&self) -> String {
-        let mut csv = String::new();
-
-        // Header
-        csv.push_str("draft_id,patient_id,sku,description,quantity,unit,route,reviewed_by,reviewed_at,merkle_hash\n");
+        self.to_csv_with_layout(&CsvLayout::default())
+    }
 
-        // Lines from all encounters
+    /// Export to CSV with the given columns, headers, and formatting.
+    pub fn to_csv_with_layout(&self, layout: &CsvLayout) -> String {
+        let mut csv = csv_header(layout);
         for export in &self.encounters {
-            for item in &export.line_items {
-                csv.push_str(&format!(
-                    "{},{},{},{},{},{},{},{},{},{}\n",
-                    escape_csv(&export.metadata.draft_id),
-                    escape_csv(&export.metadata.patient_id),
-                    escape_csv(&item.sku),
-                    escape_csv(&item.description),
-                    item.quantity,
-                    escape_csv(&item.unit),
-                    item.route.as_deref().unwrap_or(""),
-                    escape_csv(&export.metadata.reviewed_by),
-                    escape_csv(&export.metadata.reviewed_at),
-                    escape_csv(&export.metadata.merkle_leaf_hash),
-                ));
+            csv.push_str(&export.csv_rows(layout));
+        }
+        csv
+    }
+}
+
+/// The encounters billed to one client.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientBilling {
+    /// Client ID (None for a patient not linked to a client)
+    pub client_id: Option<String>,
+    /// Patient local IDs, in export order
+    pub patient_ids: Vec<String>,
+    /// Merkle leaf hashes of the client's encounters
+    pub leaf_hashes: Vec<String>,
+    /// Combined totals of the client's encounters
+    pub totals: BillingTotals,
+}
+
+impl ClientBilling {
+    /// Group `encounters` by client, in order of each group's first
+    /// encounter.
+    pub fn group(encounters: &[BillingExport]) -> Vec<Self> {
+        let mut groups = Vec::new();
+        for export in encounters {
+            Self::add(&mut groups, export);
+        }
+        groups
+    }
+
+    /// Add `export` to its group in `groups`, starting a new group if it
+    /// has none.
+    fn add(groups: &mut Vec<Self>, export: &BillingExport) {
+        let metadata = &export.metadata;
+        let position = groups.iter().position(|group| match &metadata.client_id {
+            Some(client_id) => group.client_id.as_ref() == Some(client_id),
+            None => {
+                group.client_id.is_none() && group.patient_ids.first() == Some(&metadata.patient_id)
+            }
+        });
+        let group = match position {
+            Some(i) => &mut groups[i],
+            None => {
+                groups.push(Self {
+                    client_id: metadata.client_id.clone(),
+                    ..Self::default()
+                });
+                groups.last_mut().expect("just pushed")
             }
+        };
+        if !group.patient_ids.contains(&metadata.patient_id) {
+            group.patient_ids.push(metadata.patient_id.clone());
         }
+        group.leaf_hashes.push(metadata.merkle_leaf_hash.clone());
+        group.totals.add(&export.totals);
+    }
+}
 
-        csv
+/// The encounters committed under one visit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VisitBilling {
+    pub visit_id: String,
+    pub patient_id: String,
+    /// Merkle leaf hashes of the visit's encounters
+    pub leaf_hashes: Vec<String>,
+    /// Combined totals of the visit's encounters
+    pub totals: BillingTotals,
+}
+
+impl VisitBilling {
+    /// Group the `encounters` that have a visit by visit, in order of each
+    /// visit's first encounter.
+    pub fn group(encounters: &[BillingExport]) -> Vec<Self> {
+        let mut groups = Vec::new();
+        for export in encounters {
+            Self::add(&mut groups, export);
+        }
+        groups
+    }
+
+    /// Add `export` to its visit's group in `groups`, if it has a visit.
+    fn add(groups: &mut Vec<Self>, export: &BillingExport) {
+        let metadata = &export.metadata;
+        let Some(visit_id) = &metadata.visit_id else {
+            return;
+        };
+        let group = match groups.iter().position(|group| group.visit_id == *visit_id) {
+            Some(i) => &mut groups[i],
+            None => {
+                groups.push(Self {
+                    visit_id: visit_id.clone(),
+                    patient_id: metadata.patient_id.clone(),
+                    ..Self::default()
+                });
+                groups.last_mut().expect("just pushed")
+            }
+        };
+        group.leaf_hashes.push(metadata.merkle_leaf_hash.clone());
+        group.totals.add(&export.totals);
     }
 }
 
@@ -162,6 +537,9 @@ impl BatchBillingExport {
 pub struct BillingExporter<'a> {
     db: &'a Database,
     tree: MerkleTree<'a>,
+    phrasebook: Phrasebook,
+    signing_key: Option<SigningKey>,
+    deidentifier: Option<Deidentifier>,
 }
 
 impl<'a> BillingExporter<'a> {
@@ -170,75 +548,377 @@ impl<'a> BillingExporter<'a> {
         Self {
             db,
             tree: MerkleTree::new(db),
+            phrasebook: Phrasebook::default(),
+            signing_key: None,
+            deidentifier: None,
         }
     }
 
+    /// Use a different phrasebook for route descriptions.
+    pub fn with_phrasebook(mut self, phrasebook: Phrasebook) -> Self {
+        self.phrasebook = phrasebook;
+        self
+    }
+
+    /// Sign batch exports with `key`.
+    pub fn with_signing_key(mut self, key: SigningKey) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    /// Pseudonymize patients and owners in every export (see
+    /// [`Deidentifier`]). Invoices are for the clinic and aren't affected.
+    pub fn with_deidentifier(mut self, deidentifier: Deidentifier) -> Self {
+        self.deidentifier = Some(deidentifier);
+        self
+    }
+
     /// Export billing for a specific leaf hash.
     pub fn export_by_hash(&self, leaf_hash: &str) -> MerkleResult<BillingExport> {
+        let mut export = self.priced_export(leaf_hash)?;
+        self.deidentify(&mut export);
+        Ok(export)
+    }
+
+    /// Billing for a leaf hash with real patient identifiers.
+    fn priced_export(&self, leaf_hash: &str) -> MerkleResult<BillingExport> {
         let payload = self
             .tree
             .get_leaf_payload(leaf_hash)?
             .ok_or_else(|| crate::merkle::MerkleError::NodeNotFound(leaf_hash.to_string()))?;
 
         let encounter: ReviewedEncounter = serde_json::from_str(&payload)?;
-        Ok(BillingExport::from_encounter(&encounter, leaf_hash))
+        let mut export =
+            BillingExport::from_encounter_with_phrasebook(&encounter, leaf_hash, &self.phrasebook);
+        // Catalog prices are per tablet or mL, not per dictated mg, so
+        // convert each dose the way commit draws down stock
+        let dispensing = DispensingCalculator::new();
+        for (line, item) in export.line_items.iter_mut().zip(&encounter.line_items) {
+            let Some(catalog_item) = self.db.get_catalog_item(&item.sku)? else {
+                continue;
+            };
+            let schedule = item.schedule.as_ref();
+            if let Some(suggested) =
+                dispensing.dispense_line(&catalog_item, item.quantity, &item.unit, schedule)
+            {
+                line.dispensing_quantity = Some(suggested.per_dose);
+                line.dispensing_unit = Some(suggested.unit);
+            }
+        }
+        let skus: Vec<&str> = encounter
+            .line_items
+            .iter()
+            .map(|i| i.sku.as_str())
+            .collect();
+        export.apply_pricing(&self.db.catalog_pricing(&skus)?, &self.db.tax_rates()?);
+        export.metadata.client_id = self
+            .db
+            .get_patient(&encounter.patient_id)?
+            .and_then(|patient| patient.client_id);
+        Ok(export)
+    }
+
+    /// Printable invoice for the encounter at `leaf_hash`, with patient and
+    /// owner from the local patient record (the owner being the linked
+    /// client, if any).
+    pub fn invoice_by_hash(
+        &self,
+        leaf_hash: &str,
+        clinic_name: Option<String>,
+    ) -> MerkleResult<Invoice> {
+        let export = self.priced_export(leaf_hash)?;
+        let patient = self
+            .db
+            .get_patient(&export.metadata.patient_id)?
+            .ok_or_else(|| DbError::NotFound(format!("Patient {}", export.metadata.patient_id)))?;
+        let mut details = InvoiceDetails::for_patient(&patient, clinic_name);
+        if let Some(client) = self.client_of(&patient)? {
+            details.owner_name = Some(client.name);
+        }
+        Ok(Invoice::new(&export, &details))
+    }
+
+    /// QuickBooks customer for `export` (before de-identification): the
+    /// patient's client, else its owner name, else the patient's name (or
+    /// ID if the patient record is missing). Pseudonymized when
+    /// de-identifying.
+    fn customer_name(&self, export: &BillingExport) -> MerkleResult<String> {
+        let name = match self.db.get_patient(&export.metadata.patient_id)? {
+            Some(patient) => match self.client_of(&patient)? {
+                Some(client) => client.name,
+                None => patient.owner_name.unwrap_or(patient.name),
+            },
+            None => export.metadata.patient_id.clone(),
+        };
+        Ok(match &self.deidentifier {
+            Some(deidentifier) => deidentifier.pseudonym("owner", &name),
+            None => name,
+        })
+    }
+
+    /// The client `patient` is linked to, if any.
+    fn client_of(&self, patient: &Patient) -> MerkleResult<Option<Client>> {
+        match &patient.client_id {
+            Some(client_id) => Ok(self.db.get_client(client_id)?),
+            None => Ok(None),
+        }
+    }
+
+    /// Apply the deidentifier, if any.
+    fn deidentify(&self, export: &mut BillingExport) {
+        if let Some(deidentifier) = &self.deidentifier {
+            deidentifier.billing(export);
+        }
     }
 
     /// Export billing for all leaves.
     pub fn export_all(&self) -> MerkleResult<BatchBillingExport> {
-        let leaf_hashes = self.db.get_all_leaf_hashes()?;
+        let leaf_hashes = self.tree.encounter_leaf_hashes()?;
         let mut encounters = Vec::new();
-        let mut total_items = 0;
 
         for hash in leaf_hashes {
-            let export = self.export_by_hash(&hash)?;
-            total_items += export.line_items.len();
+            encounters.push(self.export_by_hash(&hash)?);
+        }
+
+        self.batch(encounters, None)
+    }
+
+    /// Stream billing for all leaves to `path`, one encounter at a time.
+    ///
+    /// Avoids building the whole export in memory. JSON output has the same
+    /// shape as [`BatchBillingExport`] (compact rather than pretty-printed).
+    /// The manifest's record count is the number of line items.
+    pub fn export_all_to_file(
+        &self,
+        path: &Path,
+        format: ExportFormat,
+    ) -> ExportResult<ExportManifest> {
+        self.export_all_to_file_with_progress(path, format, &())
+    }
+
+    /// [`export_all_to_file`](Self::export_all_to_file), reporting progress
+    /// after each encounter. If `progress` cancels, the partial file is
+    /// removed and any existing file at `path` is left untouched.
+    pub fn export_all_to_file_with_progress(
+        &self,
+        path: &Path,
+        format: ExportFormat,
+        progress: &dyn Progress,
+    ) -> ExportResult<ExportManifest> {
+        write_export_file(path, format, |out| self.write_all(out, format, progress))
+    }
+
+    /// Stream billing for all leaves to `out`, reporting progress after each
+    /// encounter. Returns the line item count.
+    pub fn write_all(
+        &self,
+        out: &mut dyn Write,
+        format: ExportFormat,
+        progress: &dyn Progress,
+    ) -> ExportResult<usize> {
+        let leaf_hashes = self.tree.encounter_leaf_hashes()?;
+        let total = leaf_hashes.len();
+        let mut total_items = 0;
+        let mut clients = Vec::new();
+        let mut visits = Vec::new();
+        progress.step(0, total)?;
+
+        match format {
+            ExportFormat::Csv => {
+                let layout = self.db.billing_csv_layout()?;
+                out.write_all(csv_header(&layout).as_bytes())?;
+                for (i, hash) in leaf_hashes.iter().enumerate() {
+                    let export = self.export_by_hash(hash)?;
+                    out.write_all(export.csv_rows(&layout).as_bytes())?;
+                    total_items += export.line_items.len();
+                    progress.step(i + 1, total)?;
+                }
+            }
+            ExportFormat::Json => {
+                write!(
+                    out,
+                    "{{\"exported_at\":{},\"exported_by_device\":{},\"encounters\":[",
+                    serde_json::to_string(&chrono::Utc::now().to_rfc3339())?,
+                    serde_json::to_string(&self.db.device_id()?)?,
+                )?;
+                for (i, hash) in leaf_hashes.iter().enumerate() {
+                    let export = self.export_by_hash(hash)?;
+                    if i > 0 {
+                        out.write_all(b",")?;
+                    }
+                    serde_json::to_writer(&mut *out, &export)?;
+                    total_items += export.line_items.len();
+                    ClientBilling::add(&mut clients, &export);
+                    VisitBilling::add(&mut visits, &export);
+                    progress.step(i + 1, total)?;
+                }
+                write!(
+                    out,
+                    "],\"clients\":{},\"visits\":{},\"total_items\":{},\"redacted\":{}}}",
+                    serde_json::to_string(&clients)?,
+                    serde_json::to_string(&visits)?,
+                    total_items,
+                    self.deidentifier.is_some()
+                )?;
+            }
+            ExportFormat::Iif => {
+                let mapping = self.db.quickbooks_mapping()?;
+                out.write_all(IIF_HEADER.as_bytes())?;
+                for (i, hash) in leaf_hashes.iter().enumerate() {
+                    let export = self.priced_export(hash)?;
+                    let customer = self.customer_name(&export)?;
+                    out.write_all(export.iif_transaction(&mapping, &customer).as_bytes())?;
+                    total_items += export.line_items.len();
+                    progress.step(i + 1, total)?;
+                }
+            }
+        }
+
+        Ok(total_items)
+    }
+
+    /// Export billing for the encounters and items matching `filter`.
+    pub fn export_filtered(&self, filter: &BillingFilter) -> MerkleResult<BatchBillingExport> {
+        let mut encounters = Vec::new();
+
+        for hash in self.tree.encounter_leaf_hashes()? {
+            let mut export = self.priced_export(&hash)?;
+            if !filter.matches(&export.metadata) {
+                continue;
+            }
+            self.deidentify(&mut export);
+            if !filter.skus.is_empty() {
+                export
+                    .line_items
+                    .retain(|item| filter.includes_sku(&item.sku));
+                if export.line_items.is_empty() {
+                    continue;
+                }
+                export.totals = BillingTotals::of(&export.line_items);
+            }
             encounters.push(export);
         }
 
-        Ok(BatchBillingExport {
-            exported_at: chrono::Utc::now().to_rfc3339(),
-            encounters,
-            total_items,
-        })
+        self.batch(encounters, None)
     }
 
     /// Export billing for leaves since a given timestamp.
     pub fn export_since(&self, since: &str) -> MerkleResult<BatchBillingExport> {
         let nodes = self.db.get_nodes_since(since)?;
         let mut encounters = Vec::new();
-        let mut total_items = 0;
 
         for node in nodes {
-            if node.payload.is_some() {
-                // It's a leaf
-                let export = self.export_by_hash(&node.hash)?;
-                total_items += export.line_items.len();
-                encounters.push(export);
+            if node.payload.as_deref().is_some_and(is_encounter_payload) {
+                encounters.push(self.export_by_hash(&node.hash)?);
             }
         }
 
-        Ok(BatchBillingExport {
+        self.batch(encounters, None)
+    }
+
+    /// Export billing for encounters not in a pending or imported ledger
+    /// batch, and record them as a new pending batch so later calls skip
+    /// them until the batch is voided. Nothing is recorded (and `batch_id`
+    /// is `None`) when there is nothing unbilled.
+    pub fn export_unbilled(&self) -> MerkleResult<BatchBillingExport> {
+        let billed = self.db.billed_leaf_hashes()?;
+        let leaf_hashes: Vec<String> = self
+            .tree
+            .encounter_leaf_hashes()?
+            .into_iter()
+            .filter(|hash| !billed.contains(hash))
+            .collect();
+
+        let mut encounters = Vec::new();
+        for hash in &leaf_hashes {
+            encounters.push(self.export_by_hash(hash)?);
+        }
+        let batch_id = if leaf_hashes.is_empty() {
+            None
+        } else {
+            Some(self.db.record_export_batch(&leaf_hashes)?.batch_id)
+        };
+
+        self.batch(encounters, batch_id)
+    }
+
+    /// Wrap encounter exports in a batch, then seal it against the current
+    /// tree.
+    fn batch(
+        &self,
+        encounters: Vec<BillingExport>,
+        batch_id: Option<String>,
+    ) -> MerkleResult<BatchBillingExport> {
+        let root_state = self.db.get_merkle_root()?;
+        let mut batch = BatchBillingExport {
             exported_at: chrono::Utc::now().to_rfc3339(),
+            exported_by_device: Some(self.db.device_id()?),
+            total_items: encounters.iter().map(|e| e.line_items.len()).sum(),
+            redacted: self.deidentifier.is_some(),
+            batch_id,
+            clients: ClientBilling::group(&encounters),
+            visits: VisitBilling::group(&encounters),
             encounters,
-            total_items,
-        })
+            integrity: None,
+        };
+        batch.integrity = Some(ExportIntegrity::seal(
+            &batch,
+            root_state.root_hash.unwrap_or_default(),
+            root_state.leaf_count,
+            self.signing_key.as_ref(),
+        )?);
+        Ok(batch)
     }
 }
 
 /// Escape a string for CSV output.
-fn escape_csv(s: &str) -> String {
-    if s.contains(',') || s.contains('"') || s.contains('\n') {
+pub(super) fn escape_csv(s: &str) -> String {
+    escape_field(s, ',')
+}
+
+/// Escape a field separated by `delimiter`.
+fn escape_field(s: &str, delimiter: char) -> String {
+    if s.contains(delimiter) || s.contains('"') || s.contains('\n') {
         format!("\"{}\"", s.replace('"', "\"\""))
     } else {
         s.to_string()
     }
 }
 
+/// Header row for `layout`.
+fn csv_header(layout: &CsvLayout) -> String {
+    let headers: Vec<String> = layout
+        .columns
+        .iter()
+        .map(|c| escape_field(c.header(), layout.delimiter))
+        .collect();
+    let mut header = headers.join(&layout.delimiter.to_string());
+    header.push('\n');
+    header
+}
+
+/// `timestamp` in `format` (strftime), or unchanged if there is no format or
+/// it doesn't parse.
+fn format_timestamp(timestamp: &str, format: Option<&str>) -> String {
+    use std::fmt::Write as _;
+
+    let (Some(format), Ok(parsed)) = (format, DateTime::parse_from_rfc3339(timestamp)) else {
+        return timestamp.to_string();
+    };
+    let mut formatted = String::new();
+    match write!(formatted, "{}", parsed.format(format)) {
+        Ok(()) => formatted,
+        Err(_) => timestamp.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{EncounterLineItem, ResolutionMethod};
+    use crate::export::{ExportError, PhraseLanguage, PhraseTarget};
+    use crate::models::{
+        ControlledSchedule, CsvLayoutColumn, DispositionType, EncounterLineItem, ResolutionMethod,
+    };
 
     fn make_encounter() -> ReviewedEncounter {
         ReviewedEncounter {
@@ -251,24 +931,47 @@ mod tests {
                     sku: "SKU001".to_string(),
                     name: "Carprofen 100mg".to_string(),
                     quantity: 2.0,
-                    unit: "tablets".to_string(),
+                    unit: "tablets".into(),
                     route: Some("PO".to_string()),
                     original_mention: "2 carprofen tablets".to_string(),
                     resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                    controlled_schedule: None,
+                    source_spans: vec![],
+                    schedule: None,
+                    disposition: None,
+                    lot_number: None,
+                    expiration_date: None,
+                    withdrawal: None,
+                    escalation_approval: None,
+                    controlled_confirmed_by: None,
+                    allergy_overridden_by: None,
                 },
                 EncounterLineItem {
                     sku: "SKU002".to_string(),
                     name: "Meloxicam 1.5mg/mL".to_string(),
                     quantity: 0.5,
-                    unit: "mL".to_string(),
+                    unit: "mL".into(),
                     route: Some("PO".to_string()),
                     original_mention: "half mL meloxicam".to_string(),
                     resolution_method: ResolutionMethod::SystemApproved { confidence: 0.88 },
+                    controlled_schedule: None,
+                    source_spans: vec![],
+                    schedule: None,
+                    disposition: None,
+                    lot_number: None,
+                    expiration_date: None,
+                    withdrawal: None,
+                    escalation_approval: None,
+                    controlled_confirmed_by: None,
+                    allergy_overridden_by: None,
                 },
             ],
             reviewed_by: "Dr. Smith".to_string(),
             reviewed_at: "2024-01-15T10:00:00Z".to_string(),
             notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
         }
     }
 
@@ -308,6 +1011,116 @@ mod tests {
         assert!(lines[2].contains("SKU002"));
     }
 
+    #[test]
+    fn test_billing_csv_layout() {
+        let export = BillingExport::from_encounter(&make_encounter(), "hash123");
+        let column = |column, header: Option<&str>| CsvLayoutColumn {
+            column,
+            header: header.map(str::to_string),
+        };
+        let layout = CsvLayout {
+            columns: vec![
+                column(CsvColumn::ReviewedAt, Some("Date")),
+                column(CsvColumn::PatientServerId, Some("Client; Patient")),
+                column(CsvColumn::Description, None),
+                column(CsvColumn::Quantity, Some("Qty")),
+            ],
+            delimiter: ';',
+            date_format: Some("%m/%d/%Y".into()),
+        };
+
+        let csv = export.to_csv_with_layout(&layout);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "Date;\"Client; Patient\";description;Qty");
+        assert_eq!(lines[1], "01/15/2024;server-patient-1;Carprofen 100mg;2");
+        assert_eq!(
+            lines[2],
+            "01/15/2024;server-patient-1;Meloxicam 1.5mg/mL;0.5"
+        );
+    }
+
+    #[test]
+    fn test_prescriber_columns() {
+        let mut encounter = make_encounter();
+        let mut smith = crate::models::User::new("jsmith".into(), "Dr. Smith".into());
+        smith.license_number = Some("VET-12345".into());
+        encounter.prescriber = Some(smith.prescriber());
+        let export = BillingExport::from_encounter(&encounter, "hash123");
+        assert_eq!(
+            export.metadata.prescriber_license.as_deref(),
+            Some("VET-12345")
+        );
+
+        let layout = CsvLayout {
+            columns: [
+                CsvColumn::Sku,
+                CsvColumn::PrescriberLicense,
+                CsvColumn::PrescriberDea,
+            ]
+            .into_iter()
+            .map(|column| CsvLayoutColumn {
+                column,
+                header: None,
+            })
+            .collect(),
+            ..Default::default()
+        };
+        let csv = export.to_csv_with_layout(&layout);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "sku,prescriber_license,prescriber_dea");
+        assert_eq!(lines[1], "SKU001,VET-12345,");
+    }
+
+    #[test]
+    fn test_controlled_schedule_tagged() {
+        let mut encounter = make_encounter();
+        encounter.line_items[0].controlled_schedule = Some(ControlledSchedule::CIV);
+        let export = BillingExport::from_encounter(&encounter, "hash123");
+
+        assert_eq!(
+            export.line_items[0].controlled_schedule,
+            Some("C-IV".into())
+        );
+        assert_eq!(export.line_items[1].controlled_schedule, None);
+
+        let csv = export.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[0].ends_with(",controlled_schedule,route_description,disposition"));
+        assert!(lines[1].ends_with(",C-IV,Oral,"));
+        assert!(lines[2].ends_with(",,Oral,"));
+    }
+
+    #[test]
+    fn test_disposition_column() {
+        let mut encounter = make_encounter();
+        encounter.line_items[0].disposition = Some(DispositionType::Dispensed);
+        let export = BillingExport::from_encounter(&encounter, "hash123");
+
+        assert_eq!(export.line_items[0].disposition, Some("dispensed".into()));
+        assert_eq!(export.line_items[1].disposition, None);
+
+        let csv = export.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[1].ends_with(",Oral,dispensed"));
+        assert!(lines[2].ends_with(",Oral,"));
+    }
+
+    #[test]
+    fn test_route_description() {
+        let encounter = make_encounter();
+        let export = BillingExport::from_encounter(&encounter, "hash123");
+        assert_eq!(export.line_items[0].route, Some("PO".into()));
+        assert_eq!(export.line_items[0].route_description, Some("Oral".into()));
+
+        let phrasebook = Phrasebook::new(PhraseTarget::Client, PhraseLanguage::Spanish);
+        let export =
+            BillingExport::from_encounter_with_phrasebook(&encounter, "hash123", &phrasebook);
+        assert_eq!(
+            export.line_items[0].route_description,
+            Some("por la boca".into())
+        );
+    }
+
     #[test]
     fn test_csv_escaping() {
         assert_eq!(escape_csv("simple"), "simple");
@@ -333,5 +1146,417 @@ mod tests {
 
         assert_eq!(batch.encounters.len(), 2);
         assert_eq!(batch.total_items, 4); // 2 items per encounter
+
+        let device_id = db.device_id().unwrap();
+        assert_eq!(batch.exported_by_device.as_ref(), Some(&device_id));
+        assert_eq!(batch.encounters[0].metadata.device_id, Some(device_id));
+    }
+
+    #[test]
+    fn test_invoice_uses_catalog_prices_and_patient() {
+        use crate::models::{CatalogItem, DrugMention, Patient, ResolutionStatus};
+        use crate::resolver::Resolver;
+
+        let db = Database::open_in_memory().unwrap();
+        let mut patient = Patient::new("Max".to_string(), "canine".to_string());
+        patient.owner_name = Some("Jane Doe".to_string());
+        db.insert_patient(&patient).unwrap();
+        let mut carprofen = CatalogItem::new(
+            "CARP-100".to_string(),
+            "Carprofen 100mg tablets".to_string(),
+        );
+        carprofen.unit_price = Some(0.85);
+        db.upsert_catalog_item(&carprofen).unwrap();
+
+        // "100 mg carprofen" commits a 100 mg line, billed as one tablet
+        let mention = DrugMention {
+            raw_text: "100 mg carprofen PO".to_string(),
+            drug_name: "carprofen".to_string(),
+            dose: Some(100.0),
+            unit: Some("mg".to_string()),
+            route: Some("PO".to_string()),
+            species: None,
+            start_offset: 0,
+            end_offset: 19,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+        let mut resolved = Resolver::new(&db)
+            .resolve(&mention, Some("canine"), Some(25.0), None)
+            .unwrap();
+        assert_eq!(resolved.top_candidate.sku, "CARP-100");
+        resolved.status = ResolutionStatus::Approved;
+        let mut encounter = make_encounter();
+        encounter.patient_id = patient.local_id.clone();
+        encounter.line_items[0] = resolved.to_line_item().unwrap();
+        assert_eq!(encounter.line_items[0].quantity, 100.0);
+        let commit = MerkleTree::new(&db).commit_encounter(&encounter).unwrap();
+
+        let exporter = BillingExporter::new(&db);
+        let export = exporter.export_by_hash(&commit.leaf_hash).unwrap();
+        assert_eq!(export.line_items[0].quantity, 100.0);
+        assert_eq!(export.line_items[0].dispensing_quantity, Some(1.0));
+        assert_eq!(export.line_items[0].unit_price, Some(0.85));
+        assert_eq!(export.line_items[0].extended_price, Some(0.85));
+        assert_eq!(export.totals.total, 0.85);
+        assert_eq!(export.totals.unpriced_items, 1);
+        assert_eq!(export.line_items[1].unit_price, None);
+
+        let invoice = exporter
+            .invoice_by_hash(&commit.leaf_hash, Some("Valley Vet".to_string()))
+            .unwrap();
+        assert_eq!(invoice.details.owner_name.as_deref(), Some("Jane Doe"));
+        assert_eq!(invoice.rows[0].quantity, 1.0);
+        assert_eq!(invoice.rows[0].unit, "tablets");
+        assert_eq!(invoice.rows[0].amount, Some(0.85));
+        assert_eq!(invoice.total, 0.85);
+        assert_eq!(invoice.unpriced_items, 1);
+        assert_eq!(invoice.verification_hash, commit.leaf_hash);
+    }
+
+    #[test]
+    fn test_encounters_grouped_by_client() {
+        use crate::export::FreeTextRedaction;
+        use crate::models::{CatalogItem, Client, Patient};
+
+        let db = Database::open_in_memory().unwrap();
+        let client = Client::new("Jane Doe".to_string());
+        db.upsert_client(&client).unwrap();
+        let mut carprofen = CatalogItem::new("SKU001".to_string(), "Carprofen 100mg".to_string());
+        carprofen.unit_price = Some(1.0);
+        db.upsert_catalog_item(&carprofen).unwrap();
+        let tree = MerkleTree::new(&db);
+        for (i, name) in ["Max", "Bella", "Rex"].into_iter().enumerate() {
+            let mut patient = Patient::new(name.to_string(), "canine".to_string());
+            if name != "Rex" {
+                patient.client_id = Some(client.client_id.clone());
+            }
+            db.insert_patient(&patient).unwrap();
+            let mut encounter = make_encounter();
+            encounter.draft_id = format!("draft-{}", i);
+            encounter.patient_id = patient.local_id;
+            tree.commit_encounter(&encounter).unwrap();
+        }
+
+        let exporter = BillingExporter::new(&db);
+        let batch = exporter.export_all().unwrap();
+        assert_eq!(batch.clients.len(), 2);
+        let family = &batch.clients[0];
+        assert_eq!(family.client_id.as_ref(), Some(&client.client_id));
+        assert_eq!(family.patient_ids.len(), 2);
+        assert_eq!(family.leaf_hashes.len(), 2);
+        assert_eq!(family.totals.total, 4.0);
+        assert_eq!(family.totals.unpriced_items, 2);
+        assert_eq!(batch.clients[1].client_id, None);
+
+        let filter = BillingFilter {
+            client_id: Some(client.client_id.clone()),
+            ..Default::default()
+        };
+        assert_eq!(
+            exporter.export_filtered(&filter).unwrap().encounters.len(),
+            2
+        );
+        let invoice = exporter
+            .invoice_by_hash(&family.leaf_hashes[0], None)
+            .unwrap();
+        assert_eq!(invoice.details.owner_name.as_deref(), Some("Jane Doe"));
+
+        // Pseudonymized client IDs still group
+        let deidentified = BillingExporter::new(&db)
+            .with_deidentifier(Deidentifier::new("salt", FreeTextRedaction::Strip))
+            .export_all()
+            .unwrap();
+        let client_ids: Vec<_> = deidentified.clients.iter().map(|c| &c.client_id).collect();
+        assert_eq!(client_ids.len(), 2);
+        assert!(client_ids[0]
+            .as_ref()
+            .is_some_and(|id| *id != client.client_id));
+    }
+
+    #[test]
+    fn test_export_unbilled() {
+        use crate::models::ExportBatchStatus;
+
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter()).unwrap();
+        let exporter = BillingExporter::new(&db);
+
+        let first = exporter.export_unbilled().unwrap();
+        assert_eq!(first.encounters.len(), 1);
+        let batch_id = first.batch_id.clone().unwrap();
+        let empty = exporter.export_unbilled().unwrap();
+        assert!(empty.encounters.is_empty());
+        assert_eq!(empty.batch_id, None);
+
+        let mut enc2 = make_encounter();
+        enc2.draft_id = "draft-2".to_string();
+        tree.commit_encounter(&enc2).unwrap();
+        let second = exporter.export_unbilled().unwrap();
+        assert_eq!(second.encounters.len(), 1);
+        assert_eq!(second.encounters[0].metadata.draft_id, "draft-2");
+
+        // Voiding the first batch makes its encounter unbilled again
+        db.set_export_batch_status(&batch_id, ExportBatchStatus::Void)
+            .unwrap();
+        let retry = exporter.export_unbilled().unwrap();
+        assert_eq!(retry.encounters.len(), 1);
+        assert_eq!(retry.encounters[0].metadata.draft_id, "draft-1");
+        assert_ne!(retry.batch_id.as_deref(), Some(batch_id.as_str()));
+        // Plain exports ignore the ledger
+        assert_eq!(exporter.export_all().unwrap().encounters.len(), 2);
+    }
+
+    #[test]
+    fn test_deidentified_billing() {
+        use crate::export::FreeTextRedaction;
+        use crate::models::Patient;
+
+        let db = Database::open_in_memory().unwrap();
+        let mut patient = Patient::new("Max".to_string(), "canine".to_string());
+        patient.owner_name = Some("Jane Doe".to_string());
+        db.insert_patient(&patient).unwrap();
+        let mut encounter = make_encounter();
+        encounter.patient_id = patient.local_id.clone();
+        MerkleTree::new(&db).commit_encounter(&encounter).unwrap();
+
+        let exporter = BillingExporter::new(&db)
+            .with_deidentifier(Deidentifier::new("salt", FreeTextRedaction::Strip));
+        // Filters match real patient IDs
+        let filter = BillingFilter {
+            patient_id: Some(patient.local_id.clone()),
+            ..Default::default()
+        };
+        let batch = exporter.export_filtered(&filter).unwrap();
+        assert!(batch.redacted);
+        assert_eq!(batch.encounters.len(), 1);
+        let metadata = &batch.encounters[0].metadata;
+        assert!(metadata.redacted);
+        assert!(metadata.patient_id.starts_with("patient-"));
+        assert_ne!(metadata.patient_id, patient.local_id);
+
+        let mut iif = Vec::new();
+        exporter
+            .write_all(&mut iif, ExportFormat::Iif, &())
+            .unwrap();
+        let iif = String::from_utf8(iif).unwrap();
+        assert!(!iif.contains("Jane Doe"));
+        assert!(iif.contains("owner-"));
+    }
+
+    #[test]
+    fn test_tax_from_catalog_code_and_clinic_rate() {
+        use crate::models::CatalogItem;
+
+        let db = Database::open_in_memory().unwrap();
+        let mut carprofen = CatalogItem::new("SKU001".to_string(), "Carprofen 100mg".to_string());
+        carprofen.unit_price = Some(10.0);
+        carprofen.tax_code = Some("RETAIL".to_string());
+        db.upsert_catalog_item(&carprofen).unwrap();
+        let mut rates = TaxRates::default();
+        rates.rates.insert("retail".to_string(), 0.08);
+        db.set_tax_rates(&rates).unwrap();
+        let commit = MerkleTree::new(&db)
+            .commit_encounter(&make_encounter())
+            .unwrap();
+
+        let export = BillingExporter::new(&db)
+            .export_by_hash(&commit.leaf_hash)
+            .unwrap();
+        let item = &export.line_items[0];
+        assert_eq!(item.tax_code.as_deref(), Some("RETAIL"));
+        assert!((item.tax.unwrap() - 1.6).abs() < 1e-9);
+        assert_eq!(export.line_items[1].tax, None);
+        assert!((export.totals.tax - 1.6).abs() < 1e-9);
+        assert!((export.totals.total_with_tax - 21.6).abs() < 1e-9);
+
+        let layout = CsvLayout {
+            columns: [CsvColumn::Sku, CsvColumn::TaxCode, CsvColumn::Tax]
+                .into_iter()
+                .map(|column| crate::models::CsvLayoutColumn {
+                    column,
+                    header: None,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let csv = export.to_csv_with_layout(&layout);
+        assert!(csv.contains("sku,tax_code,tax\nSKU001,RETAIL,1.60\n"));
+    }
+
+    #[test]
+    fn test_export_all_to_file() {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter()).unwrap();
+        let mut enc2 = make_encounter();
+        enc2.draft_id = "draft-2".to_string();
+        tree.commit_encounter(&enc2).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let exporter = BillingExporter::new(&db);
+
+        let path = dir.path().join("billing.json");
+        let manifest = exporter
+            .export_all_to_file(&path, ExportFormat::Json)
+            .unwrap();
+        let contents = std::fs::read(&path).unwrap();
+        assert_eq!(manifest.record_count, 4);
+        assert_eq!(manifest.bytes, contents.len() as u64);
+        assert_eq!(manifest.checksum, crate::merkle::hash_data(&contents));
+        let batch: BatchBillingExport = serde_json::from_slice(&contents).unwrap();
+        assert_eq!(batch.encounters.len(), 2);
+        assert_eq!(batch.total_items, 4);
+        assert_eq!(batch.exported_by_device, Some(db.device_id().unwrap()));
+
+        // CSV matches the in-memory export
+        let path = dir.path().join("billing.csv");
+        let manifest = exporter
+            .export_all_to_file(&path, ExportFormat::Csv)
+            .unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(csv, exporter.export_all().unwrap().to_csv());
+        assert_eq!(manifest.record_count, 4);
+    }
+
+    #[test]
+    fn test_billing_filter_matches() {
+        let metadata = BillingExport::from_encounter(&make_encounter(), "hash123").metadata;
+        let at = |s: &str| Some(s.parse::<DateTime<Utc>>().unwrap());
+
+        assert!(BillingFilter::default().matches(&metadata));
+        let vet = BillingFilter {
+            patient_id: Some("patient-1".to_string()),
+            reviewed_by: Some(" dr. smith".to_string()),
+            ..Default::default()
+        };
+        assert!(vet.matches(&metadata));
+        let other_patient = BillingFilter {
+            patient_id: Some("patient-2".to_string()),
+            ..Default::default()
+        };
+        assert!(!other_patient.matches(&metadata));
+
+        // Reviewed 2024-01-15T10:00:00Z; bounds are inclusive
+        let day = BillingFilter {
+            start: at("2024-01-15T00:00:00Z"),
+            end: at("2024-01-15T10:00:00Z"),
+            ..Default::default()
+        };
+        assert!(day.matches(&metadata));
+        let later = BillingFilter {
+            start: at("2024-01-15T10:00:01Z"),
+            ..Default::default()
+        };
+        assert!(!later.matches(&metadata));
+    }
+
+    #[test]
+    fn test_export_filtered() {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter()).unwrap();
+        let mut other = make_encounter();
+        other.draft_id = "draft-2".to_string();
+        other.patient_id = "patient-2".to_string();
+        tree.commit_encounter(&other).unwrap();
+
+        let exporter = BillingExporter::new(&db);
+        let batch = exporter
+            .export_filtered(&BillingFilter {
+                patient_id: Some("patient-1".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(batch.encounters.len(), 1);
+        assert_eq!(batch.encounters[0].metadata.draft_id, "draft-1");
+        assert_eq!(batch.total_items, 2);
+
+        let batch = exporter
+            .export_filtered(&BillingFilter {
+                skus: vec!["SKU002".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(batch.encounters.len(), 2);
+        assert_eq!(batch.total_items, 2);
+        assert!(batch
+            .encounters
+            .iter()
+            .all(|e| e.line_items.len() == 1 && e.line_items[0].sku == "SKU002"));
+
+        let none = exporter
+            .export_filtered(&BillingFilter {
+                skus: vec!["MISSING".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(none.encounters.is_empty());
+    }
+
+    #[test]
+    fn test_export_all_to_iif_uses_stored_mapping() {
+        use crate::models::{Patient, QuickBooksMapping};
+
+        let db = Database::open_in_memory().unwrap();
+        let mut patient = Patient::new("Max".to_string(), "canine".to_string());
+        patient.owner_name = Some("Jane Doe".to_string());
+        db.insert_patient(&patient).unwrap();
+        db.set_quickbooks_mapping(&QuickBooksMapping {
+            income_account: "Medical Income".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let tree = MerkleTree::new(&db);
+        let mut encounter = make_encounter();
+        encounter.patient_id = patient.local_id.clone();
+        tree.commit_encounter(&encounter).unwrap();
+        let mut unknown_patient = make_encounter();
+        unknown_patient.draft_id = "draft-2".to_string();
+        tree.commit_encounter(&unknown_patient).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("billing.iif");
+        let manifest = BillingExporter::new(&db)
+            .export_all_to_file(&path, ExportFormat::Iif)
+            .unwrap();
+        let iif = std::fs::read_to_string(&path).unwrap();
+
+        assert_eq!(manifest.record_count, 4);
+        assert!(iif.starts_with(IIF_HEADER));
+        assert_eq!(iif.matches("ENDTRNS\n").count(), 3); // header + 2 invoices
+        assert!(iif.contains("\tAccounts Receivable\tJane Doe\t"));
+        assert!(iif.contains("\tAccounts Receivable\tpatient-1\t"));
+        assert!(iif.contains("\tMedical Income\t\t0.00\tdraft-1\tCarprofen 100mg\t-2\t\tSKU001\n"));
+    }
+
+    #[test]
+    fn test_cancelled_file_export_keeps_existing_file() {
+        use crate::merkle::MerkleError;
+
+        let db = Database::open_in_memory().unwrap();
+        MerkleTree::new(&db)
+            .commit_encounter(&make_encounter())
+            .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("billing.csv");
+        std::fs::write(&path, "previous export").unwrap();
+
+        let flag = crate::progress::CancellationFlag::new();
+        flag.cancel();
+        let result = BillingExporter::new(&db).export_all_to_file_with_progress(
+            &path,
+            ExportFormat::Csv,
+            &flag,
+        );
+
+        assert!(matches!(
+            result,
+            Err(ExportError::Merkle(MerkleError::Cancelled(_)))
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "previous export");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
     }
 }
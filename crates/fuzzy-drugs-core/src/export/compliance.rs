@@ -1,10 +1,20 @@
 //! Compliance export with full audit trail.
 
+use std::io::Write;
+use std::path::Path;
+
+use ed25519_dalek::SigningKey;
 use serde::{Deserialize, Serialize};
 
 use crate::db::Database;
-use crate::merkle::{ComplianceProof, MerkleResult, MerkleTree};
-use crate::models::ReviewedEncounter;
+use crate::merkle::{is_encounter_payload, ComplianceProof, MerkleResult, MerkleTree};
+use crate::models::{DeviceIdentity, ReviewedEncounter};
+use crate::progress::Progress;
+use crate::resolver::NormalizerDataInfo;
+
+use super::{
+    write_export_file, Deidentifier, ExportFormat, ExportIntegrity, ExportManifest, ExportResult,
+};
 
 /// Full compliance export for a single encounter.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +38,22 @@ pub struct ComplianceMetadata {
     pub hash_algorithm: String,
     /// Exporting system identifier
     pub system_id: Option<String>,
+    /// Version/checksum of the normalizer data used for resolution
+    pub normalizer_data: Option<NormalizerDataInfo>,
+    /// Number of controlled substance line items (for the DEA log)
+    #[serde(default)]
+    pub controlled_item_count: usize,
+    /// Last day any food-animal withdrawal on the encounter runs to
+    /// (YYYY-MM-DD)
+    #[serde(default)]
+    pub withdrawal_end_date: Option<String>,
+    /// Device that produced the export
+    #[serde(default)]
+    pub exported_by_device: Option<DeviceIdentity>,
+    /// Patient identifiers are pseudonyms and free text is stripped or
+    /// hashed
+    #[serde(default)]
+    pub redacted: bool,
 }
 
 impl EncounterComplianceExport {
@@ -44,6 +70,9 @@ pub struct BatchComplianceExport {
     pub metadata: BatchComplianceMetadata,
     /// Individual encounter exports
     pub encounters: Vec<EncounterComplianceExport>,
+    /// Hash and optional signature of this export
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<ExportIntegrity>,
 }
 
 /// Batch compliance export metadata.
@@ -63,6 +92,15 @@ pub struct BatchComplianceMetadata {
     pub leaf_count: u32,
     /// Exporting system identifier
     pub system_id: Option<String>,
+    /// Version/checksum of the normalizer data used for resolution
+    pub normalizer_data: Option<NormalizerDataInfo>,
+    /// Device that produced the export
+    #[serde(default)]
+    pub exported_by_device: Option<DeviceIdentity>,
+    /// Patient identifiers are pseudonyms and free text is stripped or
+    /// hashed
+    #[serde(default)]
+    pub redacted: bool,
 }
 
 impl BatchComplianceExport {
@@ -120,6 +158,9 @@ pub struct ComplianceExporter<'a> {
     db: &'a Database,
     tree: MerkleTree<'a>,
     system_id: Option<String>,
+    normalizer_data: Option<NormalizerDataInfo>,
+    signing_key: Option<SigningKey>,
+    deidentifier: Option<Deidentifier>,
 }
 
 impl<'a> ComplianceExporter<'a> {
@@ -129,6 +170,9 @@ impl<'a> ComplianceExporter<'a> {
             db,
             tree: MerkleTree::new(db),
             system_id: None,
+            normalizer_data: None,
+            signing_key: None,
+            deidentifier: None,
         }
     }
 
@@ -138,6 +182,25 @@ impl<'a> ComplianceExporter<'a> {
         self
     }
 
+    /// Record the normalizer data version/checksum in exports.
+    pub fn with_normalizer_data(mut self, info: NormalizerDataInfo) -> Self {
+        self.normalizer_data = Some(info);
+        self
+    }
+
+    /// Sign batch exports with `key`.
+    pub fn with_signing_key(mut self, key: SigningKey) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    /// De-identify every encounter (see [`Deidentifier`]). Proofs still
+    /// refer to the original leaves.
+    pub fn with_deidentifier(mut self, deidentifier: Deidentifier) -> Self {
+        self.deidentifier = Some(deidentifier);
+        self
+    }
+
     /// Export compliance data for a specific leaf hash.
     pub fn export_by_hash(&self, leaf_hash: &str) -> MerkleResult<EncounterComplianceExport> {
         let payload = self
@@ -145,8 +208,22 @@ impl<'a> ComplianceExporter<'a> {
             .get_leaf_payload(leaf_hash)?
             .ok_or_else(|| crate::merkle::MerkleError::NodeNotFound(leaf_hash.to_string()))?;
 
-        let encounter: ReviewedEncounter = serde_json::from_str(&payload)?;
+        let mut encounter: ReviewedEncounter = serde_json::from_str(&payload)?;
+        if let Some(deidentifier) = &self.deidentifier {
+            encounter = deidentifier.encounter(&encounter);
+        }
         let proof = self.tree.generate_proof(leaf_hash)?;
+        let controlled_item_count = encounter
+            .line_items
+            .iter()
+            .filter(|item| item.controlled_schedule.is_some())
+            .count();
+        let withdrawal_end_date = encounter
+            .line_items
+            .iter()
+            .filter_map(|item| item.withdrawal.as_ref()?.end_date())
+            .max()
+            .map(String::from);
 
         Ok(EncounterComplianceExport {
             metadata: ComplianceMetadata {
@@ -154,6 +231,11 @@ impl<'a> ComplianceExporter<'a> {
                 exported_at: chrono::Utc::now().to_rfc3339(),
                 hash_algorithm: "SHA-256".to_string(),
                 system_id: self.system_id.clone(),
+                normalizer_data: self.normalizer_data.clone(),
+                controlled_item_count,
+                withdrawal_end_date,
+                exported_by_device: Some(self.db.device_identity()?),
+                redacted: self.deidentifier.is_some(),
             },
             encounter,
             proof: proof.to_compliance_format(),
@@ -162,63 +244,189 @@ impl<'a> ComplianceExporter<'a> {
 
     /// Export full compliance data for all encounters.
     pub fn export_all(&self) -> MerkleResult<BatchComplianceExport> {
-        let root_state = self.db.get_merkle_root()?;
-        let leaf_hashes = self.db.get_all_leaf_hashes()?;
+        self.export_all_with_progress(&())
+    }
+
+    /// Export all encounters, reporting progress after each one. Stops with
+    /// [`MerkleError::Cancelled`](crate::merkle::MerkleError::Cancelled) if
+    /// `progress` cancels.
+    pub fn export_all_with_progress(
+        &self,
+        progress: &dyn Progress,
+    ) -> MerkleResult<BatchComplianceExport> {
+        let stream = self.export_stream()?;
+        let total = stream.len();
+        progress.step(0, total)?;
 
         let mut encounters = Vec::new();
-        for hash in leaf_hashes {
-            encounters.push(self.export_by_hash(&hash)?);
+        for (i, export) in stream.enumerate() {
+            encounters.push(export?);
+            progress.step(i + 1, total)?;
         }
 
-        Ok(BatchComplianceExport {
-            metadata: BatchComplianceMetadata {
-                format_version: "1.0".to_string(),
-                exported_at: chrono::Utc::now().to_rfc3339(),
-                hash_algorithm: "SHA-256".to_string(),
-                root_hash: root_state.root_hash.unwrap_or_default(),
-                tree_height: root_state.tree_height,
-                leaf_count: root_state.leaf_count,
-                system_id: self.system_id.clone(),
-            },
-            encounters,
+        self.batch(encounters)
+    }
+
+    /// All encounters, exported one at a time as the iterator advances, so
+    /// callers can process an export without holding all of it in memory.
+    pub fn export_stream(&self) -> MerkleResult<ComplianceStream<'_, 'a>> {
+        Ok(ComplianceStream {
+            exporter: self,
+            leaf_hashes: self.tree.encounter_leaf_hashes()?.into_iter(),
         })
     }
 
-    /// Export compliance data for a date range.
-    pub fn export_date_range(
+    /// Stream all encounters to `path` as JSON, one encounter at a time.
+    ///
+    /// The file has the same shape as [`BatchComplianceExport`] (compact
+    /// rather than pretty-printed). The manifest's record count is the
+    /// number of encounters.
+    pub fn export_all_to_file(&self, path: &Path) -> ExportResult<ExportManifest> {
+        self.export_all_to_file_with_progress(path, &())
+    }
+
+    /// [`export_all_to_file`](Self::export_all_to_file), reporting progress
+    /// after each encounter. If `progress` cancels, the partial file is
+    /// removed and any existing file at `path` is left untouched.
+    pub fn export_all_to_file_with_progress(
+        &self,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> ExportResult<ExportManifest> {
+        write_export_file(path, ExportFormat::Json, |out| {
+            self.write_all(out, progress)
+        })
+    }
+
+    /// Stream all encounters to `out` as JSON, reporting progress after each
+    /// one. Returns the encounter count.
+    pub fn write_all(&self, out: &mut dyn Write, progress: &dyn Progress) -> ExportResult<usize> {
+        let metadata = self.batch_metadata()?;
+        let stream = self.export_stream()?;
+        let total = stream.len();
+        progress.step(0, total)?;
+
+        write!(
+            out,
+            "{{\"metadata\":{},\"encounters\":[",
+            serde_json::to_string(&metadata)?
+        )?;
+        for (i, export) in stream.enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut *out, &export?)?;
+            progress.step(i + 1, total)?;
+        }
+        out.write_all(b"]}")?;
+
+        Ok(total)
+    }
+
+    /// Export compliance data for encounters committed after `since_root`.
+    ///
+    /// Exports everything if `since_root` is `None` or not a node in this
+    /// tree. Proofs are against the current root.
+    pub fn export_since_root(
         &self,
-        start: &str,
-        end: &str,
+        since_root: Option<&str>,
     ) -> MerkleResult<BatchComplianceExport> {
-        let root_state = self.db.get_merkle_root()?;
+        let nodes = match since_root {
+            Some(root) => self.db.get_nodes_after(root)?,
+            None => None,
+        };
+        let Some(nodes) = nodes else {
+            return self.export_all();
+        };
+
+        let mut encounters = Vec::new();
+        for node in nodes {
+            if node.payload.as_deref().is_some_and(is_encounter_payload) {
+                encounters.push(self.export_by_hash(&node.hash)?);
+            }
+        }
+
+        self.batch(encounters)
+    }
+
+    /// Export compliance data for a date range.
+    pub fn export_date_range(&self, start: &str, end: &str) -> MerkleResult<BatchComplianceExport> {
         let nodes = self.db.get_nodes_since(start)?;
 
         let mut encounters = Vec::new();
         for node in nodes {
-            if node.payload.is_some() && node.created_at <= end.to_string() {
+            let is_encounter = node.payload.as_deref().is_some_and(is_encounter_payload);
+            if is_encounter && node.created_at.as_str() <= end {
                 encounters.push(self.export_by_hash(&node.hash)?);
             }
         }
 
-        Ok(BatchComplianceExport {
-            metadata: BatchComplianceMetadata {
-                format_version: "1.0".to_string(),
-                exported_at: chrono::Utc::now().to_rfc3339(),
-                hash_algorithm: "SHA-256".to_string(),
-                root_hash: root_state.root_hash.unwrap_or_default(),
-                tree_height: root_state.tree_height,
-                leaf_count: root_state.leaf_count,
-                system_id: self.system_id.clone(),
-            },
+        self.batch(encounters)
+    }
+
+    /// Wrap encounter exports with metadata for the current tree, then
+    /// seal the batch.
+    fn batch(
+        &self,
+        encounters: Vec<EncounterComplianceExport>,
+    ) -> MerkleResult<BatchComplianceExport> {
+        let mut batch = BatchComplianceExport {
+            metadata: self.batch_metadata()?,
             encounters,
+            integrity: None,
+        };
+        batch.integrity = Some(ExportIntegrity::seal(
+            &batch,
+            batch.metadata.root_hash.clone(),
+            batch.metadata.leaf_count,
+            self.signing_key.as_ref(),
+        )?);
+        Ok(batch)
+    }
+
+    /// Batch metadata for the current tree.
+    fn batch_metadata(&self) -> MerkleResult<BatchComplianceMetadata> {
+        let root_state = self.db.get_merkle_root()?;
+        Ok(BatchComplianceMetadata {
+            format_version: "1.0".to_string(),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            hash_algorithm: "SHA-256".to_string(),
+            root_hash: root_state.root_hash.unwrap_or_default(),
+            tree_height: root_state.tree_height,
+            leaf_count: root_state.leaf_count,
+            system_id: self.system_id.clone(),
+            normalizer_data: self.normalizer_data.clone(),
+            exported_by_device: Some(self.db.device_identity()?),
+            redacted: self.deidentifier.is_some(),
         })
     }
 }
 
+/// Encounters exported lazily by [`ComplianceExporter::export_stream`].
+pub struct ComplianceStream<'e, 'a> {
+    exporter: &'e ComplianceExporter<'a>,
+    leaf_hashes: std::vec::IntoIter<String>,
+}
+
+impl Iterator for ComplianceStream<'_, '_> {
+    type Item = MerkleResult<EncounterComplianceExport>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hash = self.leaf_hashes.next()?;
+        Some(self.exporter.export_by_hash(&hash))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.leaf_hashes.size_hint()
+    }
+}
+
+impl ExactSizeIterator for ComplianceStream<'_, '_> {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{EncounterLineItem, ResolutionMethod};
+    use crate::models::{AuditEvent, ControlledSchedule, EncounterLineItem, ResolutionMethod};
 
     fn make_encounter(id: &str) -> ReviewedEncounter {
         ReviewedEncounter {
@@ -230,14 +438,27 @@ mod tests {
                 sku: "SKU001".to_string(),
                 name: "Test Drug".to_string(),
                 quantity: 10.0,
-                unit: "mg".to_string(),
+                unit: "mg".into(),
                 route: Some("PO".to_string()),
                 original_mention: "10mg test drug".to_string(),
                 resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                controlled_schedule: None,
+                source_spans: vec![],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
             }],
             reviewed_by: "Dr. Smith".to_string(),
             reviewed_at: "2024-01-15T10:00:00Z".to_string(),
             notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
         }
     }
 
@@ -258,8 +479,43 @@ mod tests {
         assert_eq!(export.metadata.system_id, Some("test-system".into()));
     }
 
+    #[test]
+    fn test_controlled_items_counted() {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+
+        let mut enc = make_encounter("draft-1");
+        enc.line_items[0].controlled_schedule = Some(ControlledSchedule::CII);
+        let commit = tree.commit_encounter(&enc).unwrap();
+
+        let export = ComplianceExporter::new(&db)
+            .export_by_hash(&commit.leaf_hash)
+            .unwrap();
+        assert_eq!(export.metadata.controlled_item_count, 1);
+        assert_eq!(
+            export.encounter.line_items[0].controlled_schedule,
+            Some(ControlledSchedule::CII)
+        );
+    }
+
+    #[test]
+    fn test_normalizer_data_in_export() {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+
+        let info = NormalizerDataInfo::builtin();
+        let exporter = ComplianceExporter::new(&db).with_normalizer_data(info.clone());
+        let batch = exporter.export_all().unwrap();
+
+        assert_eq!(batch.metadata.normalizer_data, Some(info.clone()));
+        assert_eq!(batch.encounters[0].metadata.normalizer_data, Some(info));
+    }
+
     #[test]
     fn test_batch_compliance_export() {
+        use crate::progress::Cancelled;
+
         let db = Database::open_in_memory().unwrap();
         let tree = MerkleTree::new(&db);
 
@@ -273,6 +529,83 @@ mod tests {
 
         assert_eq!(batch.encounters.len(), 3);
         assert_eq!(batch.metadata.leaf_count, 3);
+
+        let steps = std::cell::RefCell::new(Vec::new());
+        let record = |processed: usize, total: usize| -> Result<(), Cancelled> {
+            steps.borrow_mut().push((processed, total));
+            Ok(())
+        };
+        exporter.export_all_with_progress(&record).unwrap();
+        assert_eq!(*steps.borrow(), vec![(0, 3), (1, 3), (2, 3), (3, 3)]);
+
+        let device = db.device_identity().unwrap();
+        assert_eq!(batch.metadata.exported_by_device.as_ref(), Some(&device));
+        assert_eq!(
+            batch.encounters[0].encounter.device_id,
+            Some(device.device_id)
+        );
+    }
+
+    #[test]
+    fn test_compliance_stream_and_file() {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        tree.commit_audit_event(&AuditEvent::draft_discarded(
+            "draft-x".into(),
+            "Dr. Smith".into(),
+            "Duplicate".into(),
+        ))
+        .unwrap();
+        tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+
+        let exporter = ComplianceExporter::new(&db);
+        let stream = exporter.export_stream().unwrap();
+        assert_eq!(stream.len(), 2);
+        let ids: Vec<String> = stream
+            .map(|export| export.unwrap().encounter.draft_id)
+            .collect();
+        assert_eq!(ids, vec!["draft-1", "draft-2"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("compliance.json");
+        let manifest = exporter.export_all_to_file(&path).unwrap();
+        let contents = std::fs::read(&path).unwrap();
+        assert_eq!(manifest.record_count, 2);
+        assert_eq!(manifest.format, ExportFormat::Json);
+        assert_eq!(manifest.checksum, crate::merkle::hash_data(&contents));
+        let batch: BatchComplianceExport = serde_json::from_slice(&contents).unwrap();
+        assert_eq!(batch.encounters.len(), 2);
+        assert_eq!(batch.metadata.leaf_count, 3);
+        assert!(batch.verify_all_proofs().iter().all(|v| v.is_valid));
+    }
+
+    #[test]
+    fn test_export_since_root() {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+
+        let first = tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        tree.commit_audit_event(&AuditEvent::draft_discarded(
+            "draft-x".into(),
+            "Dr. Smith".into(),
+            "Duplicate".into(),
+        ))
+        .unwrap();
+        tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+
+        let exporter = ComplianceExporter::new(&db);
+        let batch = exporter.export_since_root(Some(&first.root_hash)).unwrap();
+        assert_eq!(batch.encounters.len(), 1);
+        assert_eq!(batch.encounters[0].encounter.draft_id, "draft-2");
+        assert_eq!(batch.metadata.leaf_count, 3);
+        assert!(batch.verify_all_proofs().iter().all(|v| v.is_valid));
+
+        // Unknown roots fall back to a full export
+        let unknown = exporter.export_since_root(Some("unknown")).unwrap();
+        assert_eq!(unknown.encounters.len(), 2);
+        let all = exporter.export_since_root(None).unwrap();
+        assert_eq!(all.encounters.len(), 2);
     }
 
     #[test]
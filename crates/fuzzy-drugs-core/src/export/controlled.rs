@@ -0,0 +1,463 @@
+//! DEA controlled substance dispensing log.
+//!
+//! One row per scheduled line item in encounters committed within a date
+//! range, with the columns inspectors expect: date, patient, owner, drug,
+//! strength, quantity, running balance, prescriber (with license and DEA
+//! numbers, as committed), and witness. Items entered by hand carry no
+//! schedule, so the catalog's schedule is used for them.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::Database;
+use crate::merkle::{is_encounter_payload, MerkleResult};
+use crate::models::{CatalogItem, DispositionType, ReviewedEncounter};
+use crate::resolver::DispensingCalculator;
+
+use super::billing::escape_csv;
+#[cfg(feature = "pdf")]
+use super::ExportResult;
+
+const CSV_HEADER: &str = "date,patient,owner,drug,sku,strength,schedule,quantity,unit,disposition,balance,prescriber,prescriber_license,prescriber_dea,witness,merkle_hash\n";
+
+/// One controlled drug administered, dispensed, or prescribed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ControlledSubstanceLogEntry {
+    /// Review timestamp of the encounter
+    pub date: String,
+    pub patient_id: String,
+    /// Patient name (None if the patient record is missing)
+    pub patient_name: Option<String>,
+    pub owner_name: Option<String>,
+    pub drug: String,
+    pub sku: String,
+    /// Catalog concentration (e.g., "10mg/mL")
+    pub strength: Option<String>,
+    /// DEA schedule ("C-II" ... "C-V")
+    pub schedule: String,
+    pub quantity: f64,
+    pub unit: String,
+    /// "administered", "dispensed", or "prescribed"
+    pub disposition: Option<String>,
+    /// Stock on hand after this entry; None without an opening balance for
+    /// the SKU
+    pub balance: Option<f64>,
+    /// Veterinarian who reviewed the encounter
+    pub prescriber: String,
+    /// Prescriber's state license number, as committed
+    #[serde(default)]
+    pub prescriber_license: Option<String>,
+    /// Prescriber's DEA registration number, as committed
+    #[serde(default)]
+    pub prescriber_dea: Option<String>,
+    /// Second signer (the approver of an escalated item), if any
+    pub witness: Option<String>,
+    /// Merkle leaf hash of the encounter
+    pub merkle_leaf_hash: String,
+}
+
+/// Controlled substance log for a date range.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ControlledSubstanceLog {
+    /// Range start (exclusive), as given
+    pub start: String,
+    /// Range end (inclusive), as given
+    pub end: String,
+    /// Export timestamp
+    pub exported_at: String,
+    /// Entries in commit order
+    pub entries: Vec<ControlledSubstanceLogEntry>,
+}
+
+impl ControlledSubstanceLog {
+    /// Export to JSON.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Export to CSV format.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        for entry in &self.entries {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                escape_csv(&entry.date),
+                escape_csv(entry.patient_name.as_deref().unwrap_or(&entry.patient_id)),
+                escape_csv(entry.owner_name.as_deref().unwrap_or("")),
+                escape_csv(&entry.drug),
+                escape_csv(&entry.sku),
+                escape_csv(entry.strength.as_deref().unwrap_or("")),
+                entry.schedule,
+                entry.quantity,
+                escape_csv(&entry.unit),
+                entry.disposition.as_deref().unwrap_or(""),
+                entry.balance.map(|b| b.to_string()).unwrap_or_default(),
+                escape_csv(&entry.prescriber),
+                escape_csv(entry.prescriber_license.as_deref().unwrap_or("")),
+                escape_csv(entry.prescriber_dea.as_deref().unwrap_or("")),
+                escape_csv(entry.witness.as_deref().unwrap_or("")),
+                escape_csv(&entry.merkle_leaf_hash),
+            ));
+        }
+        csv
+    }
+
+    /// Render as a PDF (US Letter landscape).
+    #[cfg(feature = "pdf")]
+    pub fn to_pdf(&self) -> ExportResult<Vec<u8>> {
+        use super::pdf::{PdfWriter, FONT_SIZE, LETTER};
+
+        const SIZE: f32 = 8.0;
+        let mut pdf = PdfWriter::new("Controlled Substance Log", (LETTER.1, LETTER.0))?;
+        let margin = pdf.margin();
+        // date, patient, owner, drug, strength, quantity, balance,
+        // prescriber, witness
+        let columns = [margin, 42.0, 70.0, 98.0, 146.0, 168.0, 190.0, 208.0, 236.0];
+
+        pdf.line("Controlled Substance Log", 14.0, true);
+        pdf.line(&format!("{} to {}", self.start, self.end), FONT_SIZE, false);
+        pdf.skip();
+
+        let headings = [
+            "Date",
+            "Patient",
+            "Owner",
+            "Drug",
+            "Strength",
+            "Quantity",
+            "Balance",
+            "Prescriber",
+            "Witness",
+        ];
+        let cells: Vec<(&str, f32)> = headings.into_iter().zip(columns).collect();
+        pdf.row(&cells, SIZE, true);
+        for entry in &self.entries {
+            let texts = [
+                entry.date.chars().take(10).collect(),
+                entry
+                    .patient_name
+                    .clone()
+                    .unwrap_or_else(|| entry.patient_id.clone()),
+                entry.owner_name.clone().unwrap_or_default(),
+                format!("{} ({})", entry.drug, entry.schedule),
+                entry.strength.clone().unwrap_or_default(),
+                format!("{} {}", entry.quantity, entry.unit),
+                entry.balance.map(|b| b.to_string()).unwrap_or_default(),
+                entry.prescriber.clone(),
+                entry.witness.clone().unwrap_or_default(),
+            ];
+            let cells: Vec<(&str, f32)> = texts.iter().map(String::as_str).zip(columns).collect();
+            pdf.row(&cells, SIZE, false);
+        }
+
+        pdf.finish()
+    }
+}
+
+/// Builds the controlled substance log from committed encounters.
+pub struct ControlledSubstanceLogExporter<'a> {
+    db: &'a Database,
+    opening_balances: HashMap<String, f64>,
+}
+
+impl<'a> ControlledSubstanceLogExporter<'a> {
+    /// Create a new exporter.
+    pub fn new(db: &'a Database) -> Self {
+        Self {
+            db,
+            opening_balances: HashMap::new(),
+        }
+    }
+
+    /// Stock on hand per SKU at the start of the range, from the last
+    /// physical count, in dispensing units (tablets, mL). Administered and
+    /// dispensed quantities are converted to those units and deducted from
+    /// it; prescriptions filled elsewhere are not.
+    pub fn with_opening_balances(mut self, balances: HashMap<String, f64>) -> Self {
+        self.opening_balances = balances;
+        self
+    }
+
+    /// Log entries for encounters reviewed after `start` and up to `end`
+    /// (both in the tree's "YYYY-MM-DD HH:MM:SS" UTC form), in review order.
+    pub fn export_date_range(
+        &self,
+        start: &str,
+        end: &str,
+    ) -> MerkleResult<ControlledSubstanceLog> {
+        let mut balances = self.opening_balances.clone();
+        let mut catalog: HashMap<String, Option<CatalogItem>> = HashMap::new();
+        let dispensing = DispensingCalculator::new();
+        let mut entries = Vec::new();
+
+        // Encounters are committed after they are reviewed, so everything
+        // reviewed in the range is in a leaf created since `start`
+        let mut encounters = Vec::new();
+        for node in self.db.get_nodes_since(start)? {
+            let Some(payload) = node.payload.as_deref() else {
+                continue;
+            };
+            if !is_encounter_payload(payload) {
+                continue;
+            }
+            let encounter: ReviewedEncounter = serde_json::from_str(payload)?;
+            let reviewed_at = chrono::DateTime::parse_from_rfc3339(&encounter.reviewed_at)
+                .map(|t| t.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|_| node.created_at.clone());
+            if reviewed_at.as_str() <= start || reviewed_at.as_str() > end {
+                continue;
+            }
+            encounters.push((reviewed_at, node.hash, encounter));
+        }
+        encounters.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (_, leaf_hash, encounter) in encounters {
+            let patient = self.db.get_patient(&encounter.patient_id)?;
+            let prescriber = encounter.prescriber.as_ref();
+
+            for item in &encounter.line_items {
+                if let Entry::Vacant(entry) = catalog.entry(item.sku.clone()) {
+                    entry.insert(self.db.get_catalog_item(&item.sku)?);
+                }
+                let catalog_item = catalog[&item.sku].as_ref();
+                let Some(schedule) = item
+                    .controlled_schedule
+                    .or_else(|| catalog_item.and_then(|c| c.controlled_schedule))
+                else {
+                    continue;
+                };
+
+                // Balances are in dispensing units, as the commit draws
+                // down stock
+                let balance = balances.get_mut(&item.sku).map(|balance| {
+                    if item.disposition != Some(DispositionType::Prescribed) {
+                        *balance -= catalog_item
+                            .and_then(|c| {
+                                let schedule = item.schedule.as_ref();
+                                dispensing.dispense_line(c, item.quantity, &item.unit, schedule)
+                            })
+                            .map_or(item.quantity, |q| q.per_dose);
+                    }
+                    *balance
+                });
+                let witness = item
+                    .escalation_approval
+                    .as_ref()
+                    .map(|a| a.approved_by.clone());
+                entries.push(ControlledSubstanceLogEntry {
+                    date: encounter.reviewed_at.clone(),
+                    patient_id: encounter.patient_id.clone(),
+                    patient_name: patient.as_ref().map(|p| p.name.clone()),
+                    owner_name: patient.as_ref().and_then(|p| p.owner_name.clone()),
+                    drug: item.name.clone(),
+                    sku: item.sku.clone(),
+                    strength: catalog_item.and_then(|c| c.concentration.clone()),
+                    schedule: schedule.to_string(),
+                    quantity: item.quantity,
+                    unit: item.unit.to_string(),
+                    disposition: item.disposition.map(|d| d.as_str().to_string()),
+                    balance,
+                    prescriber: encounter.reviewed_by.clone(),
+                    prescriber_license: prescriber.and_then(|p| p.license_number.clone()),
+                    prescriber_dea: prescriber.and_then(|p| p.dea_number.clone()),
+                    witness,
+                    merkle_leaf_hash: leaf_hash.clone(),
+                });
+            }
+        }
+
+        Ok(ControlledSubstanceLog {
+            start: start.to_string(),
+            end: end.to_string(),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::MerkleTree;
+    use crate::models::{
+        ControlledSchedule, EncounterLineItem, EscalationApproval, Patient, ResolutionMethod, User,
+    };
+
+    fn line_item(sku: &str, name: &str, quantity: f64) -> EncounterLineItem {
+        EncounterLineItem {
+            sku: sku.to_string(),
+            name: name.to_string(),
+            quantity,
+            unit: "mL".into(),
+            route: Some("IV".to_string()),
+            original_mention: name.to_string(),
+            resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+            controlled_schedule: None,
+            source_spans: vec![],
+            schedule: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        }
+    }
+
+    fn setup() -> (Database, String) {
+        let db = Database::open_in_memory().unwrap();
+        let mut patient = Patient::new("Max".to_string(), "canine".to_string());
+        patient.owner_name = Some("Jane Doe".to_string());
+        db.insert_patient(&patient).unwrap();
+        let mut ketamine = CatalogItem::new("KET-100".to_string(), "Ketamine".to_string());
+        ketamine.concentration = Some("100mg/mL".to_string());
+        ketamine.controlled_schedule = Some(ControlledSchedule::CIII);
+        db.upsert_catalog_item(&ketamine).unwrap();
+        let mut smith = User::new("jsmith".to_string(), "Dr. Smith".to_string());
+        smith.license_number = Some("VET-12345".to_string());
+        smith.dea_number = Some("AB1234563".to_string());
+        db.upsert_user(&smith).unwrap();
+        (db, patient.local_id)
+    }
+
+    fn encounter(patient_id: &str, line_items: Vec<EncounterLineItem>) -> ReviewedEncounter {
+        ReviewedEncounter {
+            draft_id: uuid::Uuid::new_v4().to_string(),
+            patient_id: patient_id.to_string(),
+            patient_server_id: None,
+            transcript: String::new(),
+            line_items,
+            reviewed_by: "Dr. Smith".to_string(),
+            reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+
+    #[test]
+    fn test_log_filters_scheduled_items_and_tracks_balance() {
+        let (db, patient_id) = setup();
+        let tree = MerkleTree::new(&db);
+
+        let mut hydromorphone = line_item("HYD-2", "Hydromorphone", 0.5);
+        hydromorphone.controlled_schedule = Some(ControlledSchedule::CII);
+        hydromorphone.escalation_approval = Some(EscalationApproval {
+            approved_by: "Dr. Jones".to_string(),
+            role: "medical_director".to_string(),
+            reason: "Post-op pain".to_string(),
+            approved_at: "2024-01-15T10:00:00Z".to_string(),
+        });
+        let first = tree
+            .commit_encounter(&encounter(
+                &patient_id,
+                vec![
+                    line_item("CARP-100", "Carprofen", 2.0),
+                    line_item("KET-100", "Ketamine", 0.3),
+                    hydromorphone,
+                ],
+            ))
+            .unwrap();
+        let mut prescribed = line_item("KET-100", "Ketamine", 5.0);
+        prescribed.disposition = Some(DispositionType::Prescribed);
+        tree.commit_encounter(&encounter(
+            &patient_id,
+            vec![line_item("KET-100", "Ketamine", 0.2), prescribed],
+        ))
+        .unwrap();
+
+        let log = ControlledSubstanceLogExporter::new(&db)
+            .with_opening_balances(HashMap::from([("KET-100".to_string(), 10.0)]))
+            .export_date_range("2000-01-01 00:00:00", "2999-01-01 00:00:00")
+            .unwrap();
+
+        assert_eq!(log.entries.len(), 4);
+        let ketamine = &log.entries[0];
+        assert_eq!(ketamine.schedule, "C-III");
+        assert_eq!(ketamine.strength.as_deref(), Some("100mg/mL"));
+        assert_eq!(ketamine.owner_name.as_deref(), Some("Jane Doe"));
+        assert_eq!(ketamine.merkle_leaf_hash, first.leaf_hash);
+        assert!((ketamine.balance.unwrap() - 9.7).abs() < 1e-9);
+        assert_eq!(ketamine.prescriber_license.as_deref(), Some("VET-12345"));
+        assert_eq!(ketamine.prescriber_dea.as_deref(), Some("AB1234563"));
+
+        let hydromorphone = &log.entries[1];
+        assert_eq!(hydromorphone.schedule, "C-II");
+        assert_eq!(hydromorphone.balance, None);
+        assert_eq!(hydromorphone.witness.as_deref(), Some("Dr. Jones"));
+
+        // Prescriptions don't leave clinic stock
+        assert!((log.entries[2].balance.unwrap() - 9.5).abs() < 1e-9);
+        assert!((log.entries[3].balance.unwrap() - 9.5).abs() < 1e-9);
+
+        // Doses in mg come off the balance in mL
+        let mut dose = line_item("KET-100", "Ketamine", 50.0);
+        dose.unit = "mg".into();
+        tree.commit_encounter(&encounter(&patient_id, vec![dose]))
+            .unwrap();
+        let log = ControlledSubstanceLogExporter::new(&db)
+            .with_opening_balances(HashMap::from([("KET-100".to_string(), 10.0)]))
+            .export_date_range("2000-01-01 00:00:00", "2999-01-01 00:00:00")
+            .unwrap();
+        assert_eq!(log.entries.len(), 5);
+        assert!((log.entries[4].balance.unwrap() - 9.0).abs() < 1e-9);
+        assert_eq!(log.entries[4].unit, "mg");
+
+        let csv = log.to_csv();
+        assert_eq!(csv.lines().count(), 6);
+        assert!(csv
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("2024-01-15T10:00:00Z,Max,Jane Doe,Ketamine"));
+        assert!(csv.contains(",Dr. Smith,VET-12345,AB1234563,"));
+    }
+
+    #[test]
+    fn test_log_excludes_encounters_outside_range() {
+        let (db, patient_id) = setup();
+        MerkleTree::new(&db)
+            .commit_encounter(&encounter(
+                &patient_id,
+                vec![line_item("KET-100", "Ketamine", 0.3)],
+            ))
+            .unwrap();
+
+        let exporter = ControlledSubstanceLogExporter::new(&db);
+        let log = exporter
+            .export_date_range("2000-01-01 00:00:00", "2000-12-31 23:59:59")
+            .unwrap();
+        assert!(log.entries.is_empty());
+
+        // The range applies to the review date shown on the entry, not
+        // when the leaf was committed
+        let log = exporter
+            .export_date_range("2024-01-15 00:00:00", "2024-01-15 23:59:59")
+            .unwrap();
+        assert_eq!(log.entries.len(), 1);
+        let log = exporter
+            .export_date_range("2024-01-15 10:00:00", "2024-01-16 00:00:00")
+            .unwrap();
+        assert!(log.entries.is_empty());
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_log_pdf() {
+        let (db, patient_id) = setup();
+        MerkleTree::new(&db)
+            .commit_encounter(&encounter(
+                &patient_id,
+                vec![line_item("KET-100", "Ketamine", 0.3)],
+            ))
+            .unwrap();
+        let log = ControlledSubstanceLogExporter::new(&db)
+            .export_date_range("2000-01-01 00:00:00", "2999-01-01 00:00:00")
+            .unwrap();
+        assert!(log.to_pdf().unwrap().starts_with(b"%PDF"));
+    }
+}
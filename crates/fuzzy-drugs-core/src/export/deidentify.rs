@@ -0,0 +1,217 @@
+//! De-identified exports for research and vendor support.
+//!
+//! A [`Deidentifier`] replaces patient and owner identifiers with stable
+//! pseudonyms and strips or hashes free text (transcripts, notes, and the
+//! original mentions quoted from them). Pseudonyms are keyed with a
+//! per-clinic salt, so the same patient gets the same pseudonym in every
+//! export from that clinic but can't be looked up by anyone without the
+//! salt. SKUs, quantities, resolution methods, and Merkle proofs are kept,
+//! which is what resolution-quality debugging needs.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::models::ReviewedEncounter;
+
+use super::BillingExport;
+
+/// What a de-identified export does with free text.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FreeTextRedaction {
+    /// Replace with an empty string
+    #[default]
+    Strip,
+    /// Replace with a salted SHA-256, so identical text can still be matched
+    Hash,
+}
+
+impl FreeTextRedaction {
+    /// FFI name ("strip", "hash").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FreeTextRedaction::Strip => "strip",
+            FreeTextRedaction::Hash => "hash",
+        }
+    }
+
+    /// Parse an FFI name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "strip" => Some(FreeTextRedaction::Strip),
+            "hash" => Some(FreeTextRedaction::Hash),
+            _ => None,
+        }
+    }
+}
+
+/// Replaces PHI in exports with pseudonyms and redacted text.
+#[derive(Debug, Clone)]
+pub struct Deidentifier {
+    salt: String,
+    free_text: FreeTextRedaction,
+}
+
+impl Deidentifier {
+    /// Pseudonyms keyed with `salt`; see [`crate::db::Database::pseudonym_salt`].
+    pub fn new(salt: impl Into<String>, free_text: FreeTextRedaction) -> Self {
+        Self {
+            salt: salt.into(),
+            free_text,
+        }
+    }
+
+    /// Stable pseudonym for `value`, prefixed with its `kind` (e.g.
+    /// "patient-3f9a1c0d2b7e4a65").
+    pub fn pseudonym(&self, kind: &str, value: &str) -> String {
+        format!("{}-{}", kind, &self.digest(kind, value)[..16])
+    }
+
+    /// `text` redacted per the free-text mode. Empty text stays empty.
+    pub fn free_text(&self, text: &str) -> String {
+        if text.is_empty() {
+            return String::new();
+        }
+        match self.free_text {
+            FreeTextRedaction::Strip => String::new(),
+            FreeTextRedaction::Hash => format!("sha256:{}", self.digest("text", text)),
+        }
+    }
+
+    /// `encounter` with its patient IDs pseudonymized and free text
+    /// redacted. Source spans are dropped since they point into the
+    /// transcript.
+    pub fn encounter(&self, encounter: &ReviewedEncounter) -> ReviewedEncounter {
+        let mut encounter = encounter.clone();
+        encounter.patient_id = self.pseudonym("patient", &encounter.patient_id);
+        encounter.patient_server_id = encounter
+            .patient_server_id
+            .map(|id| self.pseudonym("patient", &id));
+        encounter.visit_id = encounter.visit_id.map(|id| self.pseudonym("visit", &id));
+        encounter.transcript = self.free_text(&encounter.transcript);
+        encounter.notes = encounter
+            .notes
+            .map(|notes| self.free_text(&notes))
+            .filter(|notes| !notes.is_empty());
+        for item in &mut encounter.line_items {
+            item.original_mention = self.free_text(&item.original_mention);
+            item.source_spans.clear();
+        }
+        encounter
+    }
+
+    /// Pseudonymize the patient, client, and visit IDs of a billing export
+    /// and mark it redacted.
+    pub fn billing(&self, export: &mut BillingExport) {
+        let metadata = &mut export.metadata;
+        metadata.patient_id = self.pseudonym("patient", &metadata.patient_id);
+        metadata.patient_server_id = metadata
+            .patient_server_id
+            .take()
+            .map(|id| self.pseudonym("patient", &id));
+        metadata.client_id = metadata
+            .client_id
+            .take()
+            .map(|id| self.pseudonym("client", &id));
+        metadata.visit_id = metadata
+            .visit_id
+            .take()
+            .map(|id| self.pseudonym("visit", &id));
+        metadata.redacted = true;
+    }
+
+    /// Hex SHA-256 of the salt, `kind`, and `value`.
+    fn digest(&self, kind: &str, value: &str) -> String {
+        let mut hasher = Sha256::new();
+        for part in [self.salt.as_str(), kind, value] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EncounterLineItem, ResolutionMethod, SourceSpan};
+
+    fn make_encounter() -> ReviewedEncounter {
+        ReviewedEncounter {
+            draft_id: "draft-1".to_string(),
+            patient_id: "patient-local-1".to_string(),
+            patient_server_id: Some("srv-9".to_string()),
+            transcript: "Max, owned by Jane Doe, got carprofen".to_string(),
+            line_items: vec![EncounterLineItem {
+                sku: "CARP-100".to_string(),
+                name: "Carprofen 100mg".to_string(),
+                quantity: 1.0,
+                unit: "tablet".into(),
+                route: Some("PO".to_string()),
+                original_mention: "carprofen".to_string(),
+                resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                controlled_schedule: None,
+                source_spans: vec![SourceSpan {
+                    start_offset: 28,
+                    end_offset: 37,
+                }],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
+            }],
+            reviewed_by: "Dr. Smith".to_string(),
+            reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+            notes: Some("Owner phone 555-0100".to_string()),
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+
+    #[test]
+    fn test_pseudonyms_are_stable_and_salted() {
+        let a = Deidentifier::new("salt-a", FreeTextRedaction::Strip);
+        let b = Deidentifier::new("salt-b", FreeTextRedaction::Strip);
+        let pseudonym = a.pseudonym("patient", "patient-local-1");
+        assert!(pseudonym.starts_with("patient-"));
+        assert_eq!(pseudonym.len(), "patient-".len() + 16);
+        assert_eq!(pseudonym, a.pseudonym("patient", "patient-local-1"));
+        assert_ne!(pseudonym, a.pseudonym("patient", "patient-local-2"));
+        assert_ne!(pseudonym, a.pseudonym("owner", "patient-local-1"));
+        assert_ne!(pseudonym, b.pseudonym("patient", "patient-local-1"));
+    }
+
+    #[test]
+    fn test_encounter_strip_and_hash() {
+        let encounter = make_encounter();
+
+        let stripped = Deidentifier::new("salt", FreeTextRedaction::Strip).encounter(&encounter);
+        assert_ne!(stripped.patient_id, encounter.patient_id);
+        assert!(stripped.patient_server_id.unwrap().starts_with("patient-"));
+        assert_eq!(stripped.transcript, "");
+        assert_eq!(stripped.notes, None);
+        assert_eq!(stripped.line_items[0].original_mention, "");
+        assert!(stripped.line_items[0].source_spans.is_empty());
+        assert_eq!(stripped.line_items[0].sku, "CARP-100");
+
+        let hashing = Deidentifier::new("salt", FreeTextRedaction::Hash);
+        let hashed = hashing.encounter(&encounter);
+        assert!(hashed.transcript.starts_with("sha256:"));
+        assert!(!hashed.notes.unwrap().contains("555"));
+        assert_eq!(
+            hashed.line_items[0].original_mention,
+            hashing.free_text("carprofen")
+        );
+        assert_eq!(hashing.free_text(""), "");
+
+        for mode in [FreeTextRedaction::Strip, FreeTextRedaction::Hash] {
+            assert_eq!(FreeTextRedaction::parse(mode.as_str()), Some(mode));
+        }
+        assert_eq!(FreeTextRedaction::parse("blur"), None);
+    }
+}
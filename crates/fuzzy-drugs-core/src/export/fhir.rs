@@ -0,0 +1,544 @@
+//! HL7 FHIR R4 export for corporate groups.
+//!
+//! Each committed line item becomes one resource, chosen by its disposition:
+//! given in clinic (or never determined) → `MedicationAdministration`,
+//! dispensed → `MedicationDispense`, prescribed → `MedicationRequest`.
+//! Resources are collected in a `Bundle` of type "collection". Patients are
+//! referenced as `Patient/{server_id}` once synced, and by local identifier
+//! otherwise; the encounter and Merkle leaf are carried as identifiers so
+//! the receiver can trace each resource back to the audit log.
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::Database;
+use crate::merkle::{is_encounter_payload, MerkleError, MerkleResult, MerkleTree};
+use crate::models::{DispositionType, EncounterLineItem, ReviewedEncounter};
+
+use super::Phrasebook;
+
+/// Identifier system for catalog SKUs.
+pub const FHIR_SKU_SYSTEM: &str = "urn:fuzzy-drugs:sku";
+/// Identifier system for patient local IDs.
+pub const FHIR_PATIENT_SYSTEM: &str = "urn:fuzzy-drugs:patient";
+/// Identifier system for encounters (draft IDs).
+pub const FHIR_ENCOUNTER_SYSTEM: &str = "urn:fuzzy-drugs:encounter";
+/// Identifier system for line items ("{draft_id}-{index}").
+pub const FHIR_LINE_ITEM_SYSTEM: &str = "urn:fuzzy-drugs:line-item";
+/// Identifier system for Merkle leaf hashes.
+pub const FHIR_MERKLE_LEAF_SYSTEM: &str = "urn:fuzzy-drugs:merkle-leaf";
+
+/// A FHIR `Bundle` of medication resources.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FhirBundle {
+    /// Always "Bundle"
+    pub resource_type: String,
+    /// Random bundle ID
+    pub id: String,
+    /// Always "collection"
+    #[serde(rename = "type")]
+    pub bundle_type: String,
+    /// Export timestamp
+    pub timestamp: String,
+    /// One entry per line item
+    #[serde(default)]
+    pub entry: Vec<FhirBundleEntry>,
+}
+
+/// A bundle entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FhirBundleEntry {
+    pub resource: FhirResource,
+}
+
+/// A medication resource, tagged with its FHIR `resourceType`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "resourceType")]
+pub enum FhirResource {
+    MedicationAdministration(FhirMedicationAdministration),
+    MedicationDispense(FhirMedicationDispense),
+    MedicationRequest(FhirMedicationRequest),
+}
+
+/// Drug given in clinic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FhirMedicationAdministration {
+    pub id: String,
+    pub identifier: Vec<FhirIdentifier>,
+    /// Always "completed"
+    pub status: String,
+    pub medication_codeable_concept: FhirCodeableConcept,
+    pub subject: FhirReference,
+    /// The encounter
+    pub context: FhirReference,
+    /// Review timestamp
+    pub effective_date_time: String,
+    pub performer: Vec<FhirPerformer>,
+    pub dosage: FhirAdministrationDosage,
+}
+
+/// Dose and route of an administration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FhirAdministrationDosage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<FhirCodeableConcept>,
+    pub dose: FhirQuantity,
+}
+
+/// Drug sent home from clinic stock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FhirMedicationDispense {
+    pub id: String,
+    pub identifier: Vec<FhirIdentifier>,
+    /// Always "completed"
+    pub status: String,
+    pub medication_codeable_concept: FhirCodeableConcept,
+    pub subject: FhirReference,
+    /// The encounter
+    pub context: FhirReference,
+    pub performer: Vec<FhirPerformer>,
+    pub quantity: FhirQuantity,
+    /// Review timestamp
+    pub when_handed_over: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dosage_instruction: Vec<FhirDosage>,
+}
+
+/// Prescription to be filled elsewhere.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FhirMedicationRequest {
+    pub id: String,
+    pub identifier: Vec<FhirIdentifier>,
+    /// Always "active"
+    pub status: String,
+    /// Always "order"
+    pub intent: String,
+    pub medication_codeable_concept: FhirCodeableConcept,
+    pub subject: FhirReference,
+    pub encounter: FhirReference,
+    /// Review timestamp
+    pub authored_on: String,
+    pub requester: FhirReference,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dosage_instruction: Vec<FhirDosage>,
+    pub dispense_request: FhirDispenseRequest,
+}
+
+/// Quantity to dispense for a prescription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FhirDispenseRequest {
+    pub quantity: FhirQuantity,
+}
+
+/// Route instruction for dispensed or prescribed drugs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FhirDosage {
+    pub route: FhirCodeableConcept,
+}
+
+/// FHIR `Identifier`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FhirIdentifier {
+    pub system: String,
+    pub value: String,
+}
+
+/// FHIR `Reference`: a literal reference, a logical (identifier) reference,
+/// or a display name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FhirReference {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<FhirIdentifier>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+/// FHIR `CodeableConcept`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FhirCodeableConcept {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coding: Vec<FhirCoding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// FHIR `Coding`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FhirCoding {
+    pub system: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+/// FHIR `Quantity` (free-text unit).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FhirQuantity {
+    pub value: f64,
+    pub unit: String,
+}
+
+/// Who performed an administration or dispense.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FhirPerformer {
+    pub actor: FhirReference,
+}
+
+impl FhirBundle {
+    /// Build a bundle from encounters and their Merkle leaf hashes.
+    pub fn from_encounters<'e>(
+        encounters: impl IntoIterator<Item = (&'e ReviewedEncounter, &'e str)>,
+        phrasebook: &Phrasebook,
+    ) -> Self {
+        let entry = encounters
+            .into_iter()
+            .flat_map(|(encounter, leaf_hash)| {
+                encounter
+                    .line_items
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, item)| FhirBundleEntry {
+                        resource: FhirResource::from_line_item(
+                            encounter, index, item, leaf_hash, phrasebook,
+                        ),
+                    })
+            })
+            .collect();
+
+        Self {
+            resource_type: "Bundle".to_string(),
+            id: uuid::Uuid::new_v4().to_string(),
+            bundle_type: "collection".to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            entry,
+        }
+    }
+
+    /// Export to JSON.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl FhirResource {
+    /// Map one line item to the resource for its disposition.
+    fn from_line_item(
+        encounter: &ReviewedEncounter,
+        index: usize,
+        item: &EncounterLineItem,
+        leaf_hash: &str,
+        phrasebook: &Phrasebook,
+    ) -> Self {
+        let id = format!("{}-{}", encounter.draft_id, index);
+        let identifier = vec![
+            FhirIdentifier {
+                system: FHIR_LINE_ITEM_SYSTEM.to_string(),
+                value: id.clone(),
+            },
+            FhirIdentifier {
+                system: FHIR_MERKLE_LEAF_SYSTEM.to_string(),
+                value: leaf_hash.to_string(),
+            },
+        ];
+        let medication = FhirCodeableConcept {
+            coding: vec![FhirCoding {
+                system: FHIR_SKU_SYSTEM.to_string(),
+                code: item.sku.clone(),
+                display: Some(item.name.clone()),
+            }],
+            text: Some(item.name.clone()),
+        };
+        let subject = patient_reference(encounter);
+        let encounter_ref = FhirReference {
+            identifier: Some(FhirIdentifier {
+                system: FHIR_ENCOUNTER_SYSTEM.to_string(),
+                value: encounter.draft_id.clone(),
+            }),
+            ..Default::default()
+        };
+        let reviewer = FhirReference {
+            display: Some(encounter.reviewed_by.clone()),
+            ..Default::default()
+        };
+        let quantity = FhirQuantity {
+            value: item.quantity,
+            unit: item.unit.to_string(),
+        };
+        let route = item.route.as_deref().map(|route| FhirCodeableConcept {
+            coding: Vec::new(),
+            text: Some(phrasebook.route(route).unwrap_or(route).to_string()),
+        });
+        let dosage_instruction = route
+            .clone()
+            .map(|route| FhirDosage { route })
+            .into_iter()
+            .collect();
+
+        match item.disposition {
+            Some(DispositionType::Dispensed) => {
+                FhirResource::MedicationDispense(FhirMedicationDispense {
+                    id,
+                    identifier,
+                    status: "completed".to_string(),
+                    medication_codeable_concept: medication,
+                    subject,
+                    context: encounter_ref,
+                    performer: vec![FhirPerformer { actor: reviewer }],
+                    quantity,
+                    when_handed_over: encounter.reviewed_at.clone(),
+                    dosage_instruction,
+                })
+            }
+            Some(DispositionType::Prescribed) => {
+                FhirResource::MedicationRequest(FhirMedicationRequest {
+                    id,
+                    identifier,
+                    status: "active".to_string(),
+                    intent: "order".to_string(),
+                    medication_codeable_concept: medication,
+                    subject,
+                    encounter: encounter_ref,
+                    authored_on: encounter.reviewed_at.clone(),
+                    requester: reviewer,
+                    dosage_instruction,
+                    dispense_request: FhirDispenseRequest { quantity },
+                })
+            }
+            Some(DispositionType::AdministeredInClinic) | None => {
+                FhirResource::MedicationAdministration(FhirMedicationAdministration {
+                    id,
+                    identifier,
+                    status: "completed".to_string(),
+                    medication_codeable_concept: medication,
+                    subject,
+                    context: encounter_ref,
+                    effective_date_time: encounter.reviewed_at.clone(),
+                    performer: vec![FhirPerformer { actor: reviewer }],
+                    dosage: FhirAdministrationDosage {
+                        route,
+                        dose: quantity,
+                    },
+                })
+            }
+        }
+    }
+}
+
+/// `Patient/{server_id}` once synced, always with the local identifier.
+fn patient_reference(encounter: &ReviewedEncounter) -> FhirReference {
+    FhirReference {
+        reference: encounter
+            .patient_server_id
+            .as_ref()
+            .map(|id| format!("Patient/{}", id)),
+        identifier: Some(FhirIdentifier {
+            system: FHIR_PATIENT_SYSTEM.to_string(),
+            value: encounter.patient_id.clone(),
+        }),
+        display: None,
+    }
+}
+
+/// FHIR exporter.
+pub struct FhirExporter<'a> {
+    db: &'a Database,
+    tree: MerkleTree<'a>,
+    phrasebook: Phrasebook,
+}
+
+impl<'a> FhirExporter<'a> {
+    /// Create a new FHIR exporter.
+    pub fn new(db: &'a Database) -> Self {
+        Self {
+            db,
+            tree: MerkleTree::new(db),
+            phrasebook: Phrasebook::default(),
+        }
+    }
+
+    /// Use a different phrasebook for route text.
+    pub fn with_phrasebook(mut self, phrasebook: Phrasebook) -> Self {
+        self.phrasebook = phrasebook;
+        self
+    }
+
+    /// Export one committed encounter as a bundle.
+    pub fn export_by_hash(&self, leaf_hash: &str) -> MerkleResult<FhirBundle> {
+        self.export_hashes(&[leaf_hash.to_string()])
+    }
+
+    /// Export the given committed encounters as one bundle.
+    pub fn export_hashes(&self, leaf_hashes: &[String]) -> MerkleResult<FhirBundle> {
+        let encounters = leaf_hashes
+            .iter()
+            .map(|hash| Ok((self.encounter(hash)?, hash.as_str())))
+            .collect::<MerkleResult<Vec<_>>>()?;
+        Ok(FhirBundle::from_encounters(
+            encounters
+                .iter()
+                .map(|(encounter, hash)| (encounter, *hash)),
+            &self.phrasebook,
+        ))
+    }
+
+    /// Export every committed encounter as one bundle.
+    pub fn export_all(&self) -> MerkleResult<FhirBundle> {
+        self.export_hashes(&self.tree.encounter_leaf_hashes()?)
+    }
+
+    /// Export encounters committed since a given timestamp as one bundle.
+    pub fn export_since(&self, since: &str) -> MerkleResult<FhirBundle> {
+        let hashes: Vec<String> = self
+            .db
+            .get_nodes_since(since)?
+            .into_iter()
+            .filter(|node| node.payload.as_deref().is_some_and(is_encounter_payload))
+            .map(|node| node.hash)
+            .collect();
+        self.export_hashes(&hashes)
+    }
+
+    /// The encounter stored at `leaf_hash`; audit leaves are not encounters.
+    fn encounter(&self, leaf_hash: &str) -> MerkleResult<ReviewedEncounter> {
+        let payload = self
+            .tree
+            .get_leaf_payload(leaf_hash)?
+            .filter(|payload| is_encounter_payload(payload))
+            .ok_or_else(|| MerkleError::NodeNotFound(leaf_hash.to_string()))?;
+        Ok(serde_json::from_str(&payload)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ResolutionMethod;
+
+    fn line_item(sku: &str, disposition: Option<DispositionType>) -> EncounterLineItem {
+        EncounterLineItem {
+            sku: sku.to_string(),
+            name: format!("{} name", sku),
+            quantity: 2.0,
+            unit: "tablets".into(),
+            route: Some("PO".to_string()),
+            original_mention: sku.to_lowercase(),
+            resolution_method: ResolutionMethod::ManualEntry,
+            controlled_schedule: None,
+            source_spans: vec![],
+            schedule: None,
+            disposition,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        }
+    }
+
+    fn make_encounter() -> ReviewedEncounter {
+        ReviewedEncounter {
+            draft_id: "draft-1".to_string(),
+            patient_id: "patient-1".to_string(),
+            patient_server_id: Some("server-patient-1".to_string()),
+            transcript: "Test transcript".to_string(),
+            line_items: vec![
+                line_item("CARP-100", None),
+                line_item("MELOX-15", Some(DispositionType::Dispensed)),
+                line_item("GABA-100", Some(DispositionType::Prescribed)),
+            ],
+            reviewed_by: "Dr. Smith".to_string(),
+            reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+
+    #[test]
+    fn test_resource_per_disposition() {
+        let encounter = make_encounter();
+        let bundle = FhirBundle::from_encounters([(&encounter, "hash123")], &Phrasebook::default());
+        let json: serde_json::Value = serde_json::from_str(&bundle.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["resourceType"], "Bundle");
+        assert_eq!(json["type"], "collection");
+        let entries = json["entry"].as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+
+        let administration = &entries[0]["resource"];
+        assert_eq!(administration["resourceType"], "MedicationAdministration");
+        assert_eq!(administration["id"], "draft-1-0");
+        assert_eq!(
+            administration["subject"]["reference"],
+            "Patient/server-patient-1"
+        );
+        assert_eq!(
+            administration["subject"]["identifier"]["value"],
+            "patient-1"
+        );
+        assert_eq!(administration["context"]["identifier"]["value"], "draft-1");
+        assert_eq!(
+            administration["medicationCodeableConcept"]["coding"][0]["code"],
+            "CARP-100"
+        );
+        assert_eq!(administration["dosage"]["dose"]["value"], 2.0);
+        assert_eq!(administration["dosage"]["route"]["text"], "Oral");
+        assert_eq!(administration["identifier"][1]["value"], "hash123");
+
+        let dispense = &entries[1]["resource"];
+        assert_eq!(dispense["resourceType"], "MedicationDispense");
+        assert_eq!(dispense["quantity"]["unit"], "tablets");
+        assert_eq!(dispense["whenHandedOver"], "2024-01-15T10:00:00Z");
+
+        let request = &entries[2]["resource"];
+        assert_eq!(request["resourceType"], "MedicationRequest");
+        assert_eq!(request["intent"], "order");
+        assert_eq!(request["requester"]["display"], "Dr. Smith");
+        assert_eq!(request["dispenseRequest"]["quantity"]["value"], 2.0);
+    }
+
+    #[test]
+    fn test_unsynced_patient_referenced_by_identifier() {
+        let mut encounter = make_encounter();
+        encounter.patient_server_id = None;
+        let bundle = FhirBundle::from_encounters([(&encounter, "hash123")], &Phrasebook::default());
+        let json = serde_json::to_value(&bundle).unwrap();
+        let subject = &json["entry"][0]["resource"]["subject"];
+        assert!(subject.get("reference").is_none());
+        assert_eq!(subject["identifier"]["system"], FHIR_PATIENT_SYSTEM);
+    }
+
+    #[test]
+    fn test_exporter_single_and_batch() {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+        let first = tree.commit_encounter(&make_encounter()).unwrap();
+        let mut second = make_encounter();
+        second.draft_id = "draft-2".to_string();
+        let second = tree.commit_encounter(&second).unwrap();
+
+        let exporter = FhirExporter::new(&db);
+        let single = exporter.export_by_hash(&first.leaf_hash).unwrap();
+        assert_eq!(single.entry.len(), 3);
+
+        let batch = exporter.export_all().unwrap();
+        assert_eq!(batch.entry.len(), 6);
+        let selected = exporter.export_hashes(&[second.leaf_hash]).unwrap();
+        let FhirResource::MedicationAdministration(first_item) = &selected.entry[0].resource else {
+            panic!("expected an administration");
+        };
+        assert_eq!(first_item.id, "draft-2-0");
+
+        assert!(matches!(
+            exporter.export_by_hash("missing"),
+            Err(MerkleError::NodeNotFound(_))
+        ));
+    }
+}
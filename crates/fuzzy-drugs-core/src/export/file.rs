@@ -0,0 +1,241 @@
+//! File-based exports.
+//!
+//! Large clinics produce exports too big to pass across FFI as one String.
+//! These helpers stream an export to disk and return a manifest (path, size,
+//! checksum, record count) instead. Files are written to a temporary sibling
+//! and renamed into place, so a failed export never leaves a truncated file at
+//! the requested path.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::merkle::MerkleError;
+
+/// Errors writing or verifying an export.
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Export error: {0}")]
+    Merkle(#[from] MerkleError),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("PDF error: {0}")]
+    Pdf(String),
+
+    #[error("XLSX error: {0}")]
+    Xlsx(String),
+
+    #[error("Zip error: {0}")]
+    Zip(String),
+
+    #[error("Export has no integrity section")]
+    MissingIntegrity,
+}
+
+impl From<crate::db::DbError> for ExportError {
+    fn from(e: crate::db::DbError) -> Self {
+        ExportError::Merkle(e.into())
+    }
+}
+
+impl From<crate::progress::Cancelled> for ExportError {
+    fn from(e: crate::progress::Cancelled) -> Self {
+        ExportError::Merkle(e.into())
+    }
+}
+
+pub type ExportResult<T> = Result<T, ExportError>;
+
+/// Output format of a file export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    /// QuickBooks Desktop import file (billing only)
+    Iif,
+}
+
+impl ExportFormat {
+    /// Parse "json", "csv", or "iif" (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Some(ExportFormat::Json),
+            "csv" => Some(ExportFormat::Csv),
+            "iif" => Some(ExportFormat::Iif),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Iif => "iif",
+        }
+    }
+}
+
+/// Description of an export written to disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportManifest {
+    /// Path the export was written to
+    pub path: String,
+    /// Output format
+    pub format: ExportFormat,
+    /// File size in bytes
+    pub bytes: u64,
+    /// SHA-256 of the file contents (hex)
+    pub checksum: String,
+    /// Number of records written (line items for billing)
+    pub record_count: usize,
+    /// Export timestamp
+    pub exported_at: String,
+}
+
+/// Writer that hashes and counts everything written through it.
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Bytes written so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Finish, returning the inner writer, byte count, and hex checksum.
+    pub fn finish(self) -> (W, u64, String) {
+        (self.inner, self.bytes, hex::encode(self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Stream an export to `path` and describe the result.
+///
+/// `write` receives the output and returns the number of records written.
+pub fn write_export_file<F>(
+    path: &Path,
+    format: ExportFormat,
+    write: F,
+) -> ExportResult<ExportManifest>
+where
+    F: FnOnce(&mut dyn Write) -> ExportResult<usize>,
+{
+    let partial = partial_path(path);
+    let result = write_partial(&partial, write);
+    let (bytes, checksum, record_count) = match result {
+        Ok(written) => written,
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+    };
+    fs::rename(&partial, path)?;
+
+    Ok(ExportManifest {
+        path: path.to_string_lossy().into_owned(),
+        format,
+        bytes,
+        checksum,
+        record_count,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+fn write_partial<F>(partial: &Path, write: F) -> ExportResult<(u64, String, usize)>
+where
+    F: FnOnce(&mut dyn Write) -> ExportResult<usize>,
+{
+    let mut writer = HashingWriter::new(BufWriter::new(File::create(partial)?));
+    let record_count = write(&mut writer)?;
+    writer.flush()?;
+    let (buffered, bytes, checksum) = writer.finish();
+    let file = buffered.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    Ok((bytes, checksum, record_count))
+}
+
+/// Temporary sibling written before the final rename.
+pub(super) fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::hash_data;
+
+    #[test]
+    fn test_format_parse() {
+        assert_eq!(ExportFormat::parse("JSON"), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::parse("csv"), Some(ExportFormat::Csv));
+        assert_eq!(ExportFormat::parse("xml"), None);
+    }
+
+    #[test]
+    fn test_write_export_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.csv");
+
+        let manifest = write_export_file(&path, ExportFormat::Csv, |w| {
+            w.write_all(b"a,b\n1,2\n3,4\n")?;
+            Ok(2)
+        })
+        .unwrap();
+
+        let contents = fs::read(&path).unwrap();
+        assert_eq!(manifest.bytes, contents.len() as u64);
+        assert_eq!(manifest.checksum, hash_data(&contents));
+        assert_eq!(manifest.record_count, 2);
+        assert!(!partial_path(&path).exists());
+    }
+
+    #[test]
+    fn test_failed_export_leaves_no_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.json");
+
+        let result = write_export_file(&path, ExportFormat::Json, |w| {
+            w.write_all(b"{")?;
+            Err(MerkleError::InvalidState("boom".into()).into())
+        });
+
+        assert!(result.is_err());
+        assert!(!path.exists());
+        assert!(!partial_path(&path).exists());
+    }
+}
@@ -0,0 +1,327 @@
+//! Integrity section of batch exports, for proving an export file wasn't
+//! modified after it was generated.
+//!
+//! The body hash is the SHA-256 of the export's canonical JSON: the whole
+//! document minus its `integrity` key, with object keys sorted and no
+//! whitespace. Re-indenting the file doesn't change it; editing any value
+//! does. The optional Ed25519 signature covers the canonical JSON of the
+//! integrity section itself (minus `signature`), so it binds the body hash
+//! to the Merkle root and leaf count at export time.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::{ExportError, ExportResult};
+
+/// Key of the integrity section in a batch export's JSON.
+const INTEGRITY_KEY: &str = "integrity";
+
+/// Hash, Merkle root, and optional signature of a batch export.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportIntegrity {
+    /// Hash algorithm used for `body_hash`
+    pub hash_algorithm: String,
+    /// Hex SHA-256 of the canonical export body
+    pub body_hash: String,
+    /// Merkle root hash at export time
+    pub root_hash: String,
+    /// Merkle leaf count at export time
+    pub leaf_count: u32,
+    /// Hex Ed25519 public key of the signer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// Hex Ed25519 signature over the rest of this section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl ExportIntegrity {
+    /// Hash `export` and, given a `signing_key`, sign the result.
+    pub fn seal<T: Serialize>(
+        export: &T,
+        root_hash: String,
+        leaf_count: u32,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<Self, serde_json::Error> {
+        let mut integrity = ExportIntegrity {
+            hash_algorithm: "SHA-256".to_string(),
+            body_hash: body_hash(serde_json::to_value(export)?),
+            root_hash,
+            leaf_count,
+            public_key: signing_key.map(|key| hex::encode(key.verifying_key().as_bytes())),
+            signature: None,
+        };
+        if let Some(key) = signing_key {
+            let signature = key.sign(integrity.signed_message()?.as_bytes());
+            integrity.signature = Some(hex::encode(signature.to_bytes()));
+        }
+        Ok(integrity)
+    }
+
+    /// Canonical JSON of this section without its signature.
+    fn signed_message(&self) -> Result<String, serde_json::Error> {
+        let unsigned = ExportIntegrity {
+            signature: None,
+            ..self.clone()
+        };
+        Ok(canonical(serde_json::to_value(unsigned)?).to_string())
+    }
+
+    /// Whether the signature is valid for the hex `public_key`. Malformed
+    /// keys and signatures are invalid.
+    fn signed_by(&self, public_key: &str) -> bool {
+        let Some(signature) = &self.signature else {
+            return false;
+        };
+        let key = hex::decode(public_key)
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok());
+        let signature = hex::decode(signature)
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+            .map(|bytes| Signature::from_bytes(&bytes));
+        let (Some(key), Some(signature), Ok(message)) = (key, signature, self.signed_message())
+        else {
+            return false;
+        };
+        key.verify(message.as_bytes(), &signature).is_ok()
+    }
+}
+
+/// Result of [`verify_export`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportVerification {
+    /// Whether the body still hashes to the recorded `body_hash`
+    pub body_hash_valid: bool,
+    /// Whether the export is signed by the trusted key; `None` when no
+    /// trusted key was given, since a key recorded in the export proves
+    /// nothing about who produced it
+    pub signature_valid: Option<bool>,
+    /// Public key recorded in the export
+    pub public_key: Option<String>,
+    /// Merkle root hash recorded in the export
+    pub root_hash: String,
+    /// Merkle leaf count recorded in the export
+    pub leaf_count: u32,
+}
+
+impl ExportVerification {
+    /// The body is unmodified and signed by the trusted key. Never true for
+    /// an unsigned export or one checked without a trusted key.
+    pub fn is_valid(&self) -> bool {
+        self.body_hash_valid && self.signature_valid == Some(true)
+    }
+}
+
+/// Check a batch export's JSON against its integrity section.
+///
+/// The signature is only checked against a `trusted_public_key` (hex): anyone
+/// can re-sign an edited export with their own key and record that, so
+/// without one only `body_hash_valid` is meaningful, and it proves no more
+/// than that the file wasn't edited by hand.
+pub fn verify_export(
+    json: &str,
+    trusted_public_key: Option<&str>,
+) -> ExportResult<ExportVerification> {
+    let mut value: Value = serde_json::from_str(json)?;
+    let integrity = value
+        .as_object_mut()
+        .and_then(|object| object.remove(INTEGRITY_KEY))
+        .ok_or(ExportError::MissingIntegrity)?;
+    let integrity: ExportIntegrity = serde_json::from_value(integrity)?;
+
+    let signature_valid =
+        trusted_public_key.map(|trusted| integrity.signed_by(&trusted.to_lowercase()));
+    Ok(ExportVerification {
+        body_hash_valid: body_hash(value) == integrity.body_hash,
+        signature_valid,
+        public_key: integrity.public_key,
+        root_hash: integrity.root_hash,
+        leaf_count: integrity.leaf_count,
+    })
+}
+
+/// Hex SHA-256 of an export's canonical JSON, ignoring its integrity section.
+fn body_hash(mut export: Value) -> String {
+    if let Some(object) = export.as_object_mut() {
+        object.remove(INTEGRITY_KEY);
+    }
+    hex::encode(Sha256::digest(canonical(export).to_string().as_bytes()))
+}
+
+/// `value` with every object's keys in sorted order.
+fn canonical(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<_> = object.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, canonical(v)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(canonical).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::export::{BillingExporter, ComplianceExporter};
+    use crate::merkle::MerkleTree;
+    use crate::models::{EncounterLineItem, ResolutionMethod, ReviewedEncounter};
+
+    fn make_encounter(id: &str) -> ReviewedEncounter {
+        ReviewedEncounter {
+            draft_id: id.to_string(),
+            patient_id: "patient-1".to_string(),
+            patient_server_id: None,
+            transcript: "Test transcript".to_string(),
+            line_items: vec![EncounterLineItem {
+                sku: "SKU001".to_string(),
+                name: "Test Drug".to_string(),
+                quantity: 1.5,
+                unit: "tablet".into(),
+                route: Some("PO".to_string()),
+                original_mention: "test drug".to_string(),
+                resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                controlled_schedule: None,
+                source_spans: vec![],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
+            }],
+            reviewed_by: "Dr. Smith".to_string(),
+            reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+
+    fn setup() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+        db
+    }
+
+    #[test]
+    fn test_unsigned_export_verifies() {
+        let db = setup();
+        let batch = ComplianceExporter::new(&db).export_all().unwrap();
+        let integrity = batch.integrity.clone().unwrap();
+        assert_eq!(integrity.root_hash, batch.metadata.root_hash);
+        assert_eq!(integrity.leaf_count, 2);
+        assert!(integrity.signature.is_none());
+
+        let verification = verify_export(&batch.to_json().unwrap(), None).unwrap();
+        assert!(verification.body_hash_valid);
+        assert_eq!(verification.signature_valid, None);
+        // Intact, but nothing vouches for it
+        assert!(!verification.is_valid());
+
+        // Formatting doesn't matter, only content
+        let compact = serde_json::to_string(&batch).unwrap();
+        assert!(verify_export(&compact, None).unwrap().body_hash_valid);
+    }
+
+    #[test]
+    fn test_signed_export_verifies() {
+        let db = setup();
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = hex::encode(key.verifying_key().as_bytes());
+        let batch = BillingExporter::new(&db)
+            .with_signing_key(key)
+            .export_all()
+            .unwrap();
+        let json = batch.to_json().unwrap();
+
+        // The recorded key isn't trusted on its own word
+        let verification = verify_export(&json, None).unwrap();
+        assert_eq!(verification.signature_valid, None);
+        assert!(!verification.is_valid());
+        assert_eq!(
+            verification.public_key.as_deref(),
+            Some(public_key.as_str())
+        );
+
+        let trusted = verify_export(&json, Some(&public_key.to_uppercase())).unwrap();
+        assert!(trusted.is_valid());
+
+        let other = hex::encode(SigningKey::from_bytes(&[8; 32]).verifying_key().as_bytes());
+        let untrusted = verify_export(&json, Some(&other)).unwrap();
+        assert_eq!(untrusted.signature_valid, Some(false));
+        assert!(!untrusted.is_valid());
+    }
+
+    #[test]
+    fn test_tampered_export_fails() {
+        let db = setup();
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let trusted = hex::encode(key.verifying_key().as_bytes());
+        let batch = BillingExporter::new(&db)
+            .with_signing_key(key)
+            .export_all()
+            .unwrap();
+
+        let mut edited = batch.clone();
+        edited.encounters[0].line_items[0].quantity = 15.0;
+        let verification = verify_export(&edited.to_json().unwrap(), Some(&trusted)).unwrap();
+        assert!(!verification.body_hash_valid);
+        assert_eq!(verification.signature_valid, Some(true));
+
+        // Re-hashing the body doesn't help without the key
+        let mut resealed = edited.clone();
+        let integrity = resealed.integrity.as_mut().unwrap();
+        integrity.body_hash = body_hash(serde_json::to_value(&edited).unwrap());
+        let verification = verify_export(&resealed.to_json().unwrap(), Some(&trusted)).unwrap();
+        assert!(verification.body_hash_valid);
+        assert_eq!(verification.signature_valid, Some(false));
+
+        // Nor does re-signing it with another key and recording that one
+        let forger = SigningKey::from_bytes(&[9; 32]);
+        let mut forged = edited.clone();
+        forged.integrity = None;
+        let integrity = batch.integrity.as_ref().unwrap();
+        forged.integrity = Some(
+            ExportIntegrity::seal(
+                &forged,
+                integrity.root_hash.clone(),
+                integrity.leaf_count,
+                Some(&forger),
+            )
+            .unwrap(),
+        );
+        let json = forged.to_json().unwrap();
+        assert!(verify_export(&json, None).unwrap().body_hash_valid);
+        assert!(!verify_export(&json, None).unwrap().is_valid());
+        assert!(!verify_export(&json, Some(&trusted)).unwrap().is_valid());
+    }
+
+    #[test]
+    fn test_missing_integrity_rejected() {
+        assert!(matches!(
+            verify_export(r#"{"encounters": []}"#, None),
+            Err(ExportError::MissingIntegrity)
+        ));
+        assert!(matches!(
+            verify_export("not json", None),
+            Err(ExportError::Json(_))
+        ));
+    }
+}
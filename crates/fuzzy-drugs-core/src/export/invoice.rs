@@ -0,0 +1,285 @@
+//! Printable invoices for clinics without a PIMS.
+//!
+//! [`Invoice`] lays out one billing export (patient and owner, priced line
+//! items, totals, and the Merkle leaf hash as a verification footer).
+//! Rendering it as a PDF needs the `pdf` feature.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::Patient;
+
+use super::BillingExport;
+#[cfg(feature = "pdf")]
+use super::ExportResult;
+
+/// Who the invoice is for; not part of the billing export itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct InvoiceDetails {
+    /// Printed as the invoice heading
+    pub clinic_name: Option<String>,
+    pub patient_name: String,
+    pub species: String,
+    pub owner_name: Option<String>,
+}
+
+impl InvoiceDetails {
+    /// Details for `patient`.
+    pub fn for_patient(patient: &Patient, clinic_name: Option<String>) -> Self {
+        Self {
+            clinic_name,
+            patient_name: patient.name.clone(),
+            species: patient.species.clone(),
+            owner_name: patient.owner_name.clone(),
+        }
+    }
+}
+
+/// One invoice line.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvoiceRow {
+    pub description: String,
+    /// Quantity in dispensing units (tablets, mL) when known, else as
+    /// dictated
+    pub quantity: f64,
+    pub unit: String,
+    /// Catalog price per unit after markup (None if the SKU has no price)
+    pub unit_price: Option<f64>,
+    /// Extended price, including any minimum charge
+    pub amount: Option<f64>,
+}
+
+/// An itemized invoice for one committed encounter.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Invoice {
+    pub details: InvoiceDetails,
+    /// Encounter (draft) ID, used as the invoice number
+    pub invoice_number: String,
+    /// Review timestamp
+    pub date: String,
+    pub reviewed_by: String,
+    pub rows: Vec<InvoiceRow>,
+    /// Sum of priced amounts, before tax
+    pub total: f64,
+    /// Sales tax on the priced amounts
+    #[serde(default)]
+    pub tax: f64,
+    /// Items without a price (excluded from the total)
+    pub unpriced_items: usize,
+    /// Merkle leaf hash, printed so the invoice can be checked against the
+    /// audit log
+    pub verification_hash: String,
+}
+
+impl Invoice {
+    /// Lay out `export` for `details`.
+    pub fn new(export: &BillingExport, details: &InvoiceDetails) -> Self {
+        let rows: Vec<InvoiceRow> = export
+            .line_items
+            .iter()
+            .map(|item| {
+                let (quantity, unit) = item.priced_quantity();
+                InvoiceRow {
+                    description: item.description.clone(),
+                    quantity,
+                    unit: unit.to_string(),
+                    unit_price: item.unit_price,
+                    amount: item.extended_price,
+                }
+            })
+            .collect();
+        Self {
+            details: details.clone(),
+            invoice_number: export.metadata.draft_id.clone(),
+            date: export.metadata.reviewed_at.clone(),
+            reviewed_by: export.metadata.reviewed_by.clone(),
+            total: export.totals.total,
+            tax: export.totals.tax,
+            unpriced_items: export.totals.unpriced_items,
+            rows,
+            verification_hash: export.metadata.merkle_leaf_hash.clone(),
+        }
+    }
+
+    /// Heading and patient/owner lines, top to bottom.
+    pub fn header_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            self.details
+                .clinic_name
+                .clone()
+                .unwrap_or_else(|| "Invoice".to_string()),
+            format!("Invoice {}", self.invoice_number),
+            format!("Date: {}", self.date),
+            format!(
+                "Patient: {} ({})",
+                self.details.patient_name, self.details.species
+            ),
+        ];
+        if let Some(owner) = &self.details.owner_name {
+            lines.push(format!("Owner: {}", owner));
+        }
+        lines.push(format!("Veterinarian: {}", self.reviewed_by));
+        lines
+    }
+
+    /// Total (with subtotal and tax when taxed) and verification lines,
+    /// top to bottom.
+    pub fn footer_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.tax > 0.0 {
+            lines.push(format!("Subtotal: {:.2}", self.total));
+            lines.push(format!("Tax: {:.2}", self.tax));
+        }
+        lines.push(format!("Total: {:.2}", self.total + self.tax));
+        if self.unpriced_items > 0 {
+            lines.push(format!(
+                "{} item(s) without a price are not included in the total",
+                self.unpriced_items
+            ));
+        }
+        lines.push(format!("Verification: {}", self.verification_hash));
+        lines
+    }
+
+    /// Render as a PDF (US Letter, Helvetica), continuing onto new pages as
+    /// needed.
+    #[cfg(feature = "pdf")]
+    pub fn to_pdf(&self) -> ExportResult<Vec<u8>> {
+        use super::pdf::{PdfWriter, FONT_SIZE, LETTER};
+
+        let mut pdf = PdfWriter::new(&format!("Invoice {}", self.invoice_number), LETTER)?;
+        // Column x positions: description, quantity, unit price, amount
+        let columns = [pdf.margin(), 110.0, 145.0, 175.0];
+
+        for (i, line) in self.header_lines().iter().enumerate() {
+            if i == 0 {
+                pdf.line(line, 16.0, true);
+            } else {
+                pdf.line(line, FONT_SIZE, false);
+            }
+        }
+        pdf.skip();
+
+        let headings = ["Item", "Quantity", "Unit price", "Amount"];
+        let cells: Vec<(&str, f32)> = headings.into_iter().zip(columns).collect();
+        pdf.row(&cells, FONT_SIZE, true);
+        for row in &self.rows {
+            let texts = [
+                row.description.clone(),
+                format!("{} {}", row.quantity, row.unit),
+                row.unit_price
+                    .map(|p| format!("{:.2}", p))
+                    .unwrap_or_default(),
+                row.amount
+                    .map(|a| format!("{:.2}", a))
+                    .unwrap_or_else(|| "-".into()),
+            ];
+            let cells: Vec<(&str, f32)> = texts.iter().map(String::as_str).zip(columns).collect();
+            pdf.row(&cells, FONT_SIZE, false);
+        }
+        pdf.skip();
+
+        for line in self.footer_lines() {
+            pdf.line(&line, FONT_SIZE, false);
+        }
+
+        pdf.finish()
+    }
+}
+
+#[cfg(feature = "pdf")]
+impl BillingExport {
+    /// Render this export as a printable invoice PDF.
+    pub fn to_pdf(&self, details: &InvoiceDetails) -> ExportResult<Vec<u8>> {
+        Invoice::new(self, details).to_pdf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{BillingLineItem, BillingMetadata, BillingTotals};
+
+    fn make_export() -> BillingExport {
+        let line = |sku: &str, quantity: f64, unit_price: Option<f64>| BillingLineItem {
+            sku: sku.to_string(),
+            description: format!("{} description", sku),
+            quantity,
+            unit: "tablets".to_string(),
+            route: None,
+            controlled_schedule: None,
+            route_description: None,
+            disposition: None,
+            dispensing_quantity: None,
+            dispensing_unit: None,
+            unit_price,
+            extended_price: unit_price.map(|price| price * quantity),
+            tax_code: None,
+            tax: None,
+        };
+        let line_items = vec![
+            line("CARP-100", 14.0, Some(0.85)),
+            line("MELOX-15", 1.0, Some(12.5)),
+            line("LRS-1L", 1.0, None),
+        ];
+        BillingExport {
+            metadata: BillingMetadata {
+                draft_id: "draft-1".to_string(),
+                patient_id: "patient-1".to_string(),
+                patient_server_id: None,
+                reviewed_by: "Dr. Smith".to_string(),
+                reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+                exported_at: "2024-01-15T11:00:00Z".to_string(),
+                merkle_leaf_hash: "hash123".to_string(),
+                device_id: None,
+                prescriber_license: None,
+                prescriber_dea: None,
+                redacted: false,
+                client_id: None,
+                visit_id: None,
+            },
+            totals: BillingTotals::of(&line_items),
+            line_items,
+        }
+    }
+
+    fn details() -> InvoiceDetails {
+        InvoiceDetails {
+            clinic_name: Some("Valley Vet".to_string()),
+            patient_name: "Max".to_string(),
+            species: "canine".to_string(),
+            owner_name: Some("Jane Doe".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_invoice_totals_and_footer() {
+        let invoice = Invoice::new(&make_export(), &details());
+
+        assert_eq!(invoice.rows.len(), 3);
+        assert!((invoice.rows[0].amount.unwrap() - 11.9).abs() < 1e-9);
+        assert!((invoice.total - 24.4).abs() < 1e-9);
+        assert_eq!(invoice.unpriced_items, 1);
+        assert!(invoice
+            .header_lines()
+            .contains(&"Owner: Jane Doe".to_string()));
+        let footer = invoice.footer_lines();
+        assert_eq!(footer[0], "Total: 24.40");
+        assert_eq!(footer.last().unwrap(), "Verification: hash123");
+
+        let mut taxed = make_export();
+        taxed.line_items[1].tax = Some(1.0);
+        taxed.totals = BillingTotals::of(&taxed.line_items);
+        let footer = Invoice::new(&taxed, &details()).footer_lines();
+        assert_eq!(
+            footer[..3],
+            ["Subtotal: 24.40", "Tax: 1.00", "Total: 25.40"]
+        );
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_to_pdf() {
+        let pdf = make_export().to_pdf(&details()).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+}
@@ -1,7 +1,34 @@
-//! Export functionality for billing and compliance.
+//! Export functionality for billing (including QuickBooks IIF), compliance,
+//! FHIR, invoices, encounter summaries, the controlled substance log, Excel
+//! workbooks, and audit bundles, plus integrity sections for verifying batch
+//! exports and de-identification for sharing them.
 
+mod audit;
 mod billing;
 mod compliance;
+mod controlled;
+mod deidentify;
+mod fhir;
+mod file;
+mod integrity;
+mod invoice;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod phrases;
+mod quickbooks;
+mod summary;
+#[cfg(feature = "xlsx")]
+mod xlsx;
 
+pub use audit::*;
 pub use billing::*;
 pub use compliance::*;
+pub use controlled::*;
+pub use deidentify::*;
+pub use fhir::*;
+pub use file::*;
+pub use integrity::*;
+pub use invoice::*;
+pub use phrases::*;
+pub use quickbooks::*;
+pub use summary::*;
@@ -0,0 +1,85 @@
+//! Plain-text PDF layout shared by printable exports (`pdf` feature).
+
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+
+use super::{ExportError, ExportResult};
+
+/// US Letter, in millimetres.
+pub(crate) const LETTER: (f32, f32) = (215.9, 279.4);
+
+const MARGIN: f32 = 20.0;
+const LINE: f32 = 6.0;
+pub(crate) const FONT_SIZE: f32 = 10.0;
+
+fn pdf_error(e: printpdf::Error) -> ExportError {
+    ExportError::Pdf(e.to_string())
+}
+
+/// Writes lines of Helvetica top to bottom, continuing onto new pages.
+pub(crate) struct PdfWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    size: (f32, f32),
+    y: f32,
+    font: IndirectFontRef,
+    bold: IndirectFontRef,
+}
+
+impl PdfWriter {
+    /// A document titled `title` with pages of `size` (width, height) mm.
+    pub(crate) fn new(title: &str, size: (f32, f32)) -> ExportResult<Self> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(size.0), Mm(size.1), "Content");
+        let font = doc
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(pdf_error)?;
+        let bold = doc
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(pdf_error)?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(Self {
+            doc,
+            layer,
+            size,
+            y: size.1 - MARGIN,
+            font,
+            bold,
+        })
+    }
+
+    /// Left margin, for column positions.
+    pub(crate) fn margin(&self) -> f32 {
+        MARGIN
+    }
+
+    /// One line of text at the left margin.
+    pub(crate) fn line(&mut self, text: &str, size: f32, bold: bool) {
+        self.row(&[(text, MARGIN)], size, bold);
+    }
+
+    /// One line with each cell at its x position (mm).
+    pub(crate) fn row(&mut self, cells: &[(&str, f32)], size: f32, bold: bool) {
+        let font = if bold { &self.bold } else { &self.font };
+        for (text, x) in cells {
+            self.layer.use_text(*text, size, Mm(*x), Mm(self.y), font);
+        }
+        self.skip();
+    }
+
+    /// Leave a blank line.
+    pub(crate) fn skip(&mut self) {
+        self.y -= LINE;
+        if self.y < MARGIN {
+            let (page, layer) = self
+                .doc
+                .add_page(Mm(self.size.0), Mm(self.size.1), "Content");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = self.size.1 - MARGIN;
+        }
+    }
+
+    pub(crate) fn finish(self) -> ExportResult<Vec<u8>> {
+        self.doc.save_to_bytes().map_err(pdf_error)
+    }
+}
@@ -0,0 +1,340 @@
+//! Route and frequency abbreviation expansion for exports and labels.
+//!
+//! Line items carry canonical codes (PO, SQ, BID). Client-facing output —
+//! labels, after-visit summaries, printable reports — needs plain phrases
+//! ("by mouth", "twice daily"), and billing output needs formal terms ("Oral").
+//! The table below maps each code per target and language.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::Route;
+
+/// Who the expanded text is for.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum PhraseTarget {
+    /// Billing/PIMS exports: formal terms ("Oral", "Subcutaneous")
+    #[default]
+    Billing,
+    /// Labels, after-visit summaries, printable reports ("by mouth")
+    Client,
+}
+
+impl PhraseTarget {
+    /// Parse a target name ("billing", "client"/"label"/"summary"/"report").
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "billing" => Some(PhraseTarget::Billing),
+            "client" | "label" | "summary" | "report" => Some(PhraseTarget::Client),
+            _ => None,
+        }
+    }
+}
+
+/// Output language.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum PhraseLanguage {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl PhraseLanguage {
+    /// Parse a language tag ("en", "en-US", "es", "spanish", ...).
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_lowercase();
+        let primary = s.split(['-', '_']).next().unwrap_or("");
+        match primary {
+            "en" | "english" => Some(PhraseLanguage::English),
+            "es" | "spanish" | "español" => Some(PhraseLanguage::Spanish),
+            _ => None,
+        }
+    }
+}
+
+/// One code's phrases: (billing English, client English, billing Spanish, client Spanish).
+type PhraseRow = (&'static str, &'static str, &'static str, &'static str);
+
+/// Route codes, keyed by the normalizer's canonical codes.
+const ROUTE_PHRASES: &[(&str, PhraseRow)] = &[
+    ("PO", ("Oral", "by mouth", "Oral", "por la boca")),
+    (
+        "SQ",
+        (
+            "Subcutaneous",
+            "under the skin",
+            "Subcutánea",
+            "debajo de la piel",
+        ),
+    ),
+    (
+        "IM",
+        (
+            "Intramuscular",
+            "into the muscle",
+            "Intramuscular",
+            "en el músculo",
+        ),
+    ),
+    (
+        "IV",
+        ("Intravenous", "into the vein", "Intravenosa", "en la vena"),
+    ),
+    (
+        "IN",
+        ("Intranasal", "in the nose", "Intranasal", "en la nariz"),
+    ),
+    (
+        "TD",
+        (
+            "Transdermal",
+            "on the skin",
+            "Transdérmica",
+            "sobre la piel",
+        ),
+    ),
+    (
+        "TOP",
+        (
+            "Topical",
+            "on the affected area",
+            "Tópica",
+            "en el área afectada",
+        ),
+    ),
+    (
+        "OPH",
+        ("Ophthalmic", "in the eye", "Oftálmica", "en el ojo"),
+    ),
+    ("OT", ("Otic", "in the ear", "Ótica", "en el oído")),
+    ("PR", ("Rectal", "rectally", "Rectal", "por vía rectal")),
+];
+
+/// Frequency codes (sig abbreviations).
+const FREQUENCY_PHRASES: &[(&str, PhraseRow)] = &[
+    (
+        "SID",
+        (
+            "Once daily",
+            "once daily",
+            "Una vez al día",
+            "una vez al día",
+        ),
+    ),
+    (
+        "BID",
+        (
+            "Twice daily",
+            "twice daily",
+            "Dos veces al día",
+            "dos veces al día",
+        ),
+    ),
+    (
+        "TID",
+        (
+            "Three times daily",
+            "three times daily",
+            "Tres veces al día",
+            "tres veces al día",
+        ),
+    ),
+    (
+        "QID",
+        (
+            "Four times daily",
+            "four times daily",
+            "Cuatro veces al día",
+            "cuatro veces al día",
+        ),
+    ),
+    (
+        "Q24H",
+        (
+            "Every 24 hours",
+            "every 24 hours",
+            "Cada 24 horas",
+            "cada 24 horas",
+        ),
+    ),
+    (
+        "Q12H",
+        (
+            "Every 12 hours",
+            "every 12 hours",
+            "Cada 12 horas",
+            "cada 12 horas",
+        ),
+    ),
+    (
+        "Q8H",
+        (
+            "Every 8 hours",
+            "every 8 hours",
+            "Cada 8 horas",
+            "cada 8 horas",
+        ),
+    ),
+    (
+        "Q6H",
+        (
+            "Every 6 hours",
+            "every 6 hours",
+            "Cada 6 horas",
+            "cada 6 horas",
+        ),
+    ),
+    (
+        "Q4H",
+        (
+            "Every 4 hours",
+            "every 4 hours",
+            "Cada 4 horas",
+            "cada 4 horas",
+        ),
+    ),
+    (
+        "EOD",
+        (
+            "Every other day",
+            "every other day",
+            "Cada dos días",
+            "cada dos días",
+        ),
+    ),
+    (
+        "QOD",
+        (
+            "Every other day",
+            "every other day",
+            "Cada dos días",
+            "cada dos días",
+        ),
+    ),
+    (
+        "PRN",
+        (
+            "As needed",
+            "as needed",
+            "Según sea necesario",
+            "según sea necesario",
+        ),
+    ),
+    (
+        "STAT",
+        (
+            "Immediately",
+            "right away",
+            "Inmediatamente",
+            "de inmediato",
+        ),
+    ),
+];
+
+/// Expands route and frequency codes for one target and language.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Phrasebook {
+    target: PhraseTarget,
+    language: PhraseLanguage,
+}
+
+impl Phrasebook {
+    /// Create a phrasebook for a target and language.
+    pub fn new(target: PhraseTarget, language: PhraseLanguage) -> Self {
+        Self { target, language }
+    }
+
+    /// Phrase for a route code (case-insensitive, synonyms such as "SC"
+    /// accepted), if known.
+    pub fn route(&self, code: &str) -> Option<&'static str> {
+        self.lookup(ROUTE_PHRASES, Route::parse(code).as_str())
+    }
+
+    /// Phrase for a frequency code (case-insensitive), if known.
+    pub fn frequency(&self, code: &str) -> Option<&'static str> {
+        self.lookup(FREQUENCY_PHRASES, code)
+    }
+
+    /// Replace every route/frequency code in free text (e.g., a sig) with its
+    /// phrase. Only whole uppercase tokens are replaced, so ordinary words
+    /// ("in", "po" in "tempo") are left alone.
+    pub fn expand(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut token = String::new();
+        for c in text.chars() {
+            if c.is_ascii_alphanumeric() {
+                token.push(c);
+            } else {
+                self.push_token(&mut out, &token);
+                token.clear();
+                out.push(c);
+            }
+        }
+        self.push_token(&mut out, &token);
+        out
+    }
+
+    fn push_token(&self, out: &mut String, token: &str) {
+        let is_code = !token.is_empty()
+            && token
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+        let phrase = if is_code {
+            self.route(token).or_else(|| self.frequency(token))
+        } else {
+            None
+        };
+        out.push_str(phrase.unwrap_or(token));
+    }
+
+    fn lookup(&self, table: &[(&str, PhraseRow)], code: &str) -> Option<&'static str> {
+        let code = code.trim();
+        let (_, row) = table.iter().find(|(c, _)| c.eq_ignore_ascii_case(code))?;
+        Some(match (self.language, self.target) {
+            (PhraseLanguage::English, PhraseTarget::Billing) => row.0,
+            (PhraseLanguage::English, PhraseTarget::Client) => row.1,
+            (PhraseLanguage::Spanish, PhraseTarget::Billing) => row.2,
+            (PhraseLanguage::Spanish, PhraseTarget::Client) => row.3,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_and_frequency_lookup() {
+        let client = Phrasebook::new(PhraseTarget::Client, PhraseLanguage::English);
+        assert_eq!(client.route("PO"), Some("by mouth"));
+        assert_eq!(client.route("sq"), Some("under the skin"));
+        assert_eq!(client.frequency("BID"), Some("twice daily"));
+        assert_eq!(client.route("XYZ"), None);
+
+        let billing = Phrasebook::default();
+        assert_eq!(billing.route("PO"), Some("Oral"));
+
+        let spanish = Phrasebook::new(PhraseTarget::Client, PhraseLanguage::Spanish);
+        assert_eq!(spanish.frequency("BID"), Some("dos veces al día"));
+    }
+
+    #[test]
+    fn test_expand_sig() {
+        let client = Phrasebook::new(PhraseTarget::Client, PhraseLanguage::English);
+        assert_eq!(
+            client.expand("Give 1 tablet PO BID x 7 days, PRN for pain"),
+            "Give 1 tablet by mouth twice daily x 7 days, as needed for pain"
+        );
+        // Lowercase words are not codes
+        assert_eq!(client.expand("put it in the bowl"), "put it in the bowl");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            PhraseLanguage::parse("es-MX"),
+            Some(PhraseLanguage::Spanish)
+        );
+        assert_eq!(PhraseTarget::parse("label"), Some(PhraseTarget::Client));
+        assert_eq!(PhraseTarget::parse("fax"), None);
+    }
+}
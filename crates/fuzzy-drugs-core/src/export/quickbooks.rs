@@ -0,0 +1,202 @@
+//! QuickBooks Desktop IIF export of billing.
+//!
+//! Each encounter becomes an `INVOICE` transaction: a `TRNS` line posting
+//! the total to the receivable account, then one `SPL` line per item
+//! crediting its income account, and one crediting the tax account with the
+//! sales tax. Items and accounts come from the [`QuickBooksMapping`] stored
+//! in settings; the customer is the patient's owner.
+//!
+//! QuickBooks rejects a transaction whose splits don't balance to the cent,
+//! so every split is rounded to cents and the `TRNS` amount is their sum.
+
+use crate::models::{round_cents, QuickBooksMapping};
+
+use super::BillingExport;
+
+/// Column definitions that start every IIF file.
+pub const IIF_HEADER: &str = "!TRNS\tTRNSID\tTRNSTYPE\tDATE\tACCNT\tNAME\tAMOUNT\tDOCNUM\tMEMO\n\
+!SPL\tSPLID\tTRNSTYPE\tDATE\tACCNT\tNAME\tAMOUNT\tDOCNUM\tMEMO\tQNTY\tPRICE\tINVITEM\n\
+!ENDTRNS\n";
+
+impl BillingExport {
+    /// Export as an IIF file with a single invoice billed to `customer`.
+    pub fn to_iif(&self, mapping: &QuickBooksMapping, customer: &str) -> String {
+        let mut iif = String::from(IIF_HEADER);
+        iif.push_str(&self.iif_transaction(mapping, customer));
+        iif
+    }
+
+    /// This encounter's invoice transaction, without the header.
+    ///
+    /// Unpriced items are listed at zero so the invoice still shows what
+    /// was given.
+    pub(super) fn iif_transaction(&self, mapping: &QuickBooksMapping, customer: &str) -> String {
+        let date = iif_date(&self.metadata.reviewed_at);
+        let doc = escape_iif(&self.metadata.draft_id);
+        let customer = escape_iif(customer);
+
+        let mut splits = String::new();
+        let mut total = 0.0;
+        for item in &self.line_items {
+            let credit = item.extended_price.map_or(0.0, |price| -round_cents(price));
+            total -= credit;
+            splits.push_str(&format!(
+                "SPL\t\tINVOICE\t{}\t{}\t\t{:.2}\t{}\t{}\t{}\t{}\t{}\n",
+                date,
+                escape_iif(mapping.income_account_for(&item.sku)),
+                credit,
+                doc,
+                escape_iif(&item.description),
+                -item.priced_quantity().0,
+                item.unit_price
+                    .map(|p| format!("{:.2}", p))
+                    .unwrap_or_default(),
+                escape_iif(mapping.item_name(&item.sku)),
+            ));
+        }
+        let tax = round_cents(self.totals.tax);
+        if tax != 0.0 {
+            total += tax;
+            splits.push_str(&format!(
+                "SPL\t\tINVOICE\t{}\t{}\t\t{:.2}\t{}\tSales tax\t\t\t\n",
+                date,
+                escape_iif(&mapping.tax_account),
+                -tax,
+                doc,
+            ));
+        }
+
+        let mut iif = format!(
+            "TRNS\t\tINVOICE\t{}\t{}\t{}\t{:.2}\t{}\t{}\n",
+            date,
+            escape_iif(&mapping.receivable_account),
+            customer,
+            round_cents(total),
+            doc,
+            escape_iif(&self.metadata.merkle_leaf_hash),
+        );
+        iif.push_str(&splits);
+        iif.push_str("ENDTRNS\n");
+        iif
+    }
+}
+
+/// IIF dates are MM/DD/YYYY; unparseable timestamps pass through.
+fn iif_date(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.format("%m/%d/%Y").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// IIF is tab- and line-delimited with no quoting, so those become spaces.
+fn escape_iif(s: &str) -> String {
+    s.replace(['\t', '\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{BillingLineItem, BillingMetadata, BillingTotals};
+    use crate::models::QuickBooksItem;
+
+    fn make_export() -> BillingExport {
+        let line = |sku: &str, quantity: f64, unit_price: Option<f64>| BillingLineItem {
+            sku: sku.to_string(),
+            description: format!("{}\tdescription", sku),
+            quantity,
+            unit: "tablets".to_string(),
+            route: None,
+            controlled_schedule: None,
+            route_description: None,
+            disposition: None,
+            dispensing_quantity: None,
+            dispensing_unit: None,
+            unit_price,
+            extended_price: unit_price.map(|price| price * quantity),
+            tax_code: None,
+            tax: None,
+        };
+        let line_items = vec![
+            line("CARP-100", 14.0, Some(0.85)),
+            line("LRS-1L", 1.0, None),
+        ];
+        BillingExport {
+            metadata: BillingMetadata {
+                draft_id: "draft-1".to_string(),
+                patient_id: "patient-1".to_string(),
+                patient_server_id: None,
+                reviewed_by: "Dr. Smith".to_string(),
+                reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+                exported_at: "2024-01-15T11:00:00Z".to_string(),
+                merkle_leaf_hash: "hash123".to_string(),
+                device_id: None,
+                prescriber_license: None,
+                prescriber_dea: None,
+                redacted: false,
+                client_id: None,
+                visit_id: None,
+            },
+            totals: BillingTotals::of(&line_items),
+            line_items,
+        }
+    }
+
+    #[test]
+    fn test_to_iif() {
+        let mut mapping = QuickBooksMapping::default();
+        mapping.items.insert(
+            "CARP-100".to_string(),
+            QuickBooksItem {
+                item: "Carprofen".to_string(),
+                account: Some("Pharmacy Income".to_string()),
+            },
+        );
+        let iif = make_export().to_iif(&mapping, "Jane Doe");
+        let lines: Vec<&str> = iif.lines().collect();
+
+        assert_eq!(lines.len(), 7);
+        assert_eq!(
+            lines[3],
+            "TRNS\t\tINVOICE\t01/15/2024\tAccounts Receivable\tJane Doe\t11.90\tdraft-1\thash123"
+        );
+        assert_eq!(
+            lines[4],
+            "SPL\t\tINVOICE\t01/15/2024\tPharmacy Income\t\t-11.90\tdraft-1\tCARP-100 description\t-14\t0.85\tCarprofen"
+        );
+        assert_eq!(
+            lines[5],
+            "SPL\t\tINVOICE\t01/15/2024\tSales\t\t0.00\tdraft-1\tLRS-1L description\t-1\t\tLRS-1L"
+        );
+        assert_eq!(lines[6], "ENDTRNS");
+    }
+
+    #[test]
+    fn test_iif_splits_balance_to_cents() {
+        let mut export = make_export();
+        // Two lines of 3 × $0.335 = $1.005: $2.01 unrounded, but the splits
+        // are $1.01 each
+        for sku in ["GAUZE", "TAPE"] {
+            let mut line = export.line_items[0].clone();
+            line.sku = sku.to_string();
+            line.quantity = 3.0;
+            line.unit_price = Some(0.335);
+            line.extended_price = Some(0.335 * 3.0);
+            line.tax = Some(0.335 * 3.0 * 0.0725);
+            export.line_items.push(line);
+        }
+        export.totals = BillingTotals::of(&export.line_items);
+
+        let iif = export.to_iif(&QuickBooksMapping::default(), "Jane Doe");
+        let amount = |line: &str| line.split('\t').nth(6).unwrap().parse::<f64>().unwrap();
+        let lines: Vec<&str> = iif.lines().filter(|l| !l.starts_with('!')).collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(amount(lines[3]), -1.01);
+        assert_eq!(amount(lines[4]), -1.01);
+        // Tax goes to its own split: 2.01 × 7.25% = 0.1457...
+        assert!(lines[5].contains("\tSales Tax Payable\t\t-0.15\t"));
+        assert!(lines[5].ends_with("\tSales tax\t\t\t"));
+        assert_eq!(amount(lines[0]), 14.07);
+        let splits: f64 = lines[1..6].iter().map(|l| amount(l)).sum();
+        assert!((amount(lines[0]) + splits).abs() < 1e-9);
+    }
+}
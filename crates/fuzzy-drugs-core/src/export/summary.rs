@@ -0,0 +1,459 @@
+//! Human-readable encounter summaries for pasting into a PIMS record.
+//!
+//! [`EncounterSummary`] lays out one committed encounter as a medical-record
+//! note: patient, date, reviewing vet, medications with dose, route, and any
+//! taper directions, and the vet's notes. It renders as Markdown or plain
+//! text, for clinics whose PIMS can't ingest JSON.
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::Database;
+use crate::merkle::{MerkleError, MerkleResult, MerkleTree};
+use crate::models::{EncounterLineItem, Patient, ReviewedEncounter};
+
+use super::Phrasebook;
+
+/// Output format of an encounter summary.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryFormat {
+    #[default]
+    Markdown,
+    PlainText,
+}
+
+impl SummaryFormat {
+    /// Parse a format name ("markdown"/"md", "text"/"plain"/"txt").
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "markdown" | "md" => Some(SummaryFormat::Markdown),
+            "text" | "plain" | "plain_text" | "txt" => Some(SummaryFormat::PlainText),
+            _ => None,
+        }
+    }
+}
+
+/// One medication in a summary.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SummaryMedication {
+    pub name: String,
+    /// Quantity and unit ("2 tablets")
+    pub dose: String,
+    /// Route phrase ("Oral"), or the code if the phrasebook doesn't know it
+    pub route: Option<String>,
+    /// Taper phases ("20 mg Twice daily for 5 days, then ...")
+    pub directions: Option<String>,
+    /// "administered", "dispensed", or "prescribed"
+    pub disposition: Option<String>,
+    /// DEA schedule ("C-II" ... "C-V")
+    pub controlled_schedule: Option<String>,
+    /// Food-animal withdrawal ("meat until 2026-03-29, milk until 2026-03-05")
+    #[serde(default)]
+    pub withdrawal: Option<String>,
+}
+
+impl SummaryMedication {
+    /// Lay out `item`, expanding codes with `phrasebook`.
+    pub fn new(item: &EncounterLineItem, phrasebook: &Phrasebook) -> Self {
+        let directions = item.schedule.as_ref().map(|schedule| {
+            schedule
+                .phases
+                .iter()
+                .map(|phase| {
+                    let frequency = phrasebook
+                        .frequency(&phase.frequency)
+                        .unwrap_or(&phase.frequency);
+                    format!(
+                        "{} {} {} for {} days",
+                        phase.dose, phase.unit, frequency, phase.days
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", then ")
+        });
+        let withdrawal = item.withdrawal.as_ref().map(|withdrawal| {
+            let periods = [
+                ("meat", &withdrawal.meat_end_date),
+                ("milk", &withdrawal.milk_end_date),
+            ];
+            periods
+                .into_iter()
+                .filter_map(|(product, end)| Some(format!("{} until {}", product, end.as_ref()?)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        });
+        Self {
+            name: item.name.clone(),
+            dose: format!("{} {}", item.quantity, item.unit),
+            route: item
+                .route
+                .as_deref()
+                .map(|route| phrasebook.route(route).unwrap_or(route).to_string()),
+            directions,
+            disposition: item.disposition.map(|d| d.as_str().to_string()),
+            controlled_schedule: item.controlled_schedule.map(|s| s.to_string()),
+            withdrawal,
+        }
+    }
+
+    /// "2 tablets, Oral (administered) [C-IV]", passing the recorded values
+    /// (not the schedule tag) through `escape`.
+    fn detail(&self, escape: fn(&str) -> String) -> String {
+        let mut detail = escape(&self.dose);
+        if let Some(route) = &self.route {
+            detail.push_str(&format!(", {}", escape(route)));
+        }
+        if let Some(disposition) = &self.disposition {
+            detail.push_str(&format!(" ({})", escape(disposition)));
+        }
+        if let Some(schedule) = &self.controlled_schedule {
+            detail.push_str(&format!(" [{}]", schedule));
+        }
+        detail
+    }
+}
+
+/// A medical-record note for one committed encounter.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EncounterSummary {
+    /// Patient name, or the patient ID if the record is missing
+    pub patient: String,
+    pub species: Option<String>,
+    pub breed: Option<String>,
+    pub owner_name: Option<String>,
+    pub weight_kg: Option<f64>,
+    /// Review date ("2024-01-15 10:00 UTC")
+    pub date: String,
+    pub reviewed_by: String,
+    pub medications: Vec<SummaryMedication>,
+    pub notes: Option<String>,
+    /// Merkle leaf hash, so the note can be checked against the audit log
+    pub verification_hash: String,
+}
+
+impl EncounterSummary {
+    /// Lay out `encounter` for `patient` (if the record still exists).
+    pub fn new(
+        encounter: &ReviewedEncounter,
+        patient: Option<&Patient>,
+        leaf_hash: &str,
+        phrasebook: &Phrasebook,
+    ) -> Self {
+        let date = chrono::DateTime::parse_from_rfc3339(&encounter.reviewed_at)
+            .map(|at| {
+                at.with_timezone(&chrono::Utc)
+                    .format("%Y-%m-%d %H:%M UTC")
+                    .to_string()
+            })
+            .unwrap_or_else(|_| encounter.reviewed_at.clone());
+        Self {
+            patient: patient.map_or_else(|| encounter.patient_id.clone(), |p| p.name.clone()),
+            species: patient.map(|p| p.species.clone()),
+            breed: patient.and_then(|p| p.breed.clone()),
+            owner_name: patient.and_then(|p| p.owner_name.clone()),
+            weight_kg: patient.and_then(|p| p.weight_kg),
+            date,
+            reviewed_by: encounter.reviewed_by.clone(),
+            medications: encounter
+                .line_items
+                .iter()
+                .map(|item| SummaryMedication::new(item, phrasebook))
+                .collect(),
+            notes: encounter
+                .notes
+                .clone()
+                .filter(|notes| !notes.trim().is_empty()),
+            verification_hash: leaf_hash.to_string(),
+        }
+    }
+
+    /// Render in `format`.
+    pub fn render(&self, format: SummaryFormat) -> String {
+        match format {
+            SummaryFormat::Markdown => self.to_markdown(),
+            SummaryFormat::PlainText => self.to_text(),
+        }
+    }
+
+    /// Render as Markdown.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Medical Record Note\n\n");
+        for (label, value) in self.fields() {
+            out.push_str(&format!("**{}:** {}  \n", label, escape_markdown(&value)));
+        }
+        out.push_str("\n## Medications\n\n");
+        if self.medications.is_empty() {
+            out.push_str("None recorded.\n");
+        }
+        for medication in &self.medications {
+            out.push_str(&format!(
+                "- **{}**: {}\n",
+                escape_markdown(&medication.name),
+                medication.detail(escape_markdown)
+            ));
+            if let Some(directions) = &medication.directions {
+                out.push_str(&format!("  - {}\n", escape_markdown(directions)));
+            }
+            if let Some(withdrawal) = &medication.withdrawal {
+                out.push_str(&format!("  - Withdrawal: {}\n", withdrawal));
+            }
+        }
+        if let Some(notes) = &self.notes {
+            out.push_str(&format!("\n## Notes\n\n{}\n", escape_markdown(notes)));
+        }
+        out.push_str(&format!(
+            "\n---\n\nVerification: `{}`\n",
+            self.verification_hash
+        ));
+        out
+    }
+
+    /// Render as plain text.
+    pub fn to_text(&self) -> String {
+        let mut out = String::from("MEDICAL RECORD NOTE\n\n");
+        for (label, value) in self.fields() {
+            out.push_str(&format!("{}: {}\n", label, value));
+        }
+        out.push_str("\nMedications:\n");
+        if self.medications.is_empty() {
+            out.push_str("  None recorded.\n");
+        }
+        for medication in &self.medications {
+            out.push_str(&format!(
+                "- {}: {}\n",
+                medication.name,
+                medication.detail(str::to_string)
+            ));
+            if let Some(directions) = &medication.directions {
+                out.push_str(&format!("    {}\n", directions));
+            }
+            if let Some(withdrawal) = &medication.withdrawal {
+                out.push_str(&format!("    Withdrawal: {}\n", withdrawal));
+            }
+        }
+        if let Some(notes) = &self.notes {
+            out.push_str(&format!("\nNotes:\n{}\n", notes));
+        }
+        out.push_str(&format!("\nVerification: {}\n", self.verification_hash));
+        out
+    }
+
+    /// Labelled header fields, top to bottom.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let description: Vec<&str> = [self.species.as_deref(), self.breed.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        let patient = if description.is_empty() {
+            self.patient.clone()
+        } else {
+            format!("{} ({})", self.patient, description.join(", "))
+        };
+
+        let mut fields = vec![("Patient", patient)];
+        if let Some(owner) = &self.owner_name {
+            fields.push(("Owner", owner.clone()));
+        }
+        if let Some(weight) = self.weight_kg {
+            fields.push(("Weight", format!("{} kg", weight)));
+        }
+        fields.push(("Date", self.date.clone()));
+        fields.push(("Veterinarian", self.reviewed_by.clone()));
+        fields
+    }
+}
+
+/// Backslash-escape characters Markdown would treat as formatting.
+fn escape_markdown(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '#' | '<' | '>') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Builds encounter summaries from committed leaves.
+pub struct SummaryExporter<'a> {
+    db: &'a Database,
+    tree: MerkleTree<'a>,
+    phrasebook: Phrasebook,
+}
+
+impl<'a> SummaryExporter<'a> {
+    /// Create a summary exporter with formal English phrases ("Oral").
+    pub fn new(db: &'a Database) -> Self {
+        Self {
+            db,
+            tree: MerkleTree::new(db),
+            phrasebook: Phrasebook::default(),
+        }
+    }
+
+    /// Use a different phrasebook for routes and frequencies.
+    pub fn with_phrasebook(mut self, phrasebook: Phrasebook) -> Self {
+        self.phrasebook = phrasebook;
+        self
+    }
+
+    /// Summary of the encounter at `leaf_hash`, with patient details from
+    /// the local patient record.
+    pub fn summary_by_hash(&self, leaf_hash: &str) -> MerkleResult<EncounterSummary> {
+        let payload = self
+            .tree
+            .get_leaf_payload(leaf_hash)?
+            .ok_or_else(|| MerkleError::NodeNotFound(leaf_hash.to_string()))?;
+        let encounter: ReviewedEncounter = serde_json::from_str(&payload)?;
+        let patient = self.db.get_patient(&encounter.patient_id)?;
+        Ok(EncounterSummary::new(
+            &encounter,
+            patient.as_ref(),
+            leaf_hash,
+            &self.phrasebook,
+        ))
+    }
+
+    /// The encounter at `leaf_hash` rendered as a note in `format`.
+    pub fn render_summary(&self, leaf_hash: &str, format: SummaryFormat) -> MerkleResult<String> {
+        Ok(self.summary_by_hash(leaf_hash)?.render(format))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        ControlledSchedule, DispositionType, DosePhase, ResolutionMethod, TaperSchedule,
+        Withdrawal, WithdrawalTime,
+    };
+
+    fn item(name: &str, quantity: f64, unit: &str) -> EncounterLineItem {
+        EncounterLineItem {
+            sku: name.to_uppercase(),
+            name: name.to_string(),
+            quantity,
+            unit: unit.into(),
+            route: Some("PO".to_string()),
+            original_mention: name.to_lowercase(),
+            resolution_method: ResolutionMethod::ManualEntry,
+            controlled_schedule: None,
+            source_spans: vec![],
+            schedule: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        }
+    }
+
+    fn make_encounter(patient_id: &str) -> ReviewedEncounter {
+        let mut carprofen = item("Carprofen 100mg", 2.0, "tablet");
+        carprofen.disposition = Some(DispositionType::AdministeredInClinic);
+        let mut prednisone = item("Prednisone 5mg", 150.0, "mg");
+        prednisone.schedule = Some(TaperSchedule {
+            phases: vec![
+                DosePhase {
+                    dose: 10.0,
+                    unit: "mg".to_string(),
+                    frequency: "BID".to_string(),
+                    doses_per_day: 2.0,
+                    days: 5.0,
+                },
+                DosePhase {
+                    dose: 10.0,
+                    unit: "mg".to_string(),
+                    frequency: "SID".to_string(),
+                    doses_per_day: 1.0,
+                    days: 5.0,
+                },
+            ],
+        });
+        let mut butorphanol = item("Butorphanol_10", 0.2, "mL");
+        butorphanol.route = Some("IV".to_string());
+        butorphanol.controlled_schedule = Some(ControlledSchedule::CIV);
+        let time = WithdrawalTime {
+            species: "bovine".to_string(),
+            meat_days: Some(28),
+            milk_hours: Some(96),
+        };
+        let last_dose = chrono::NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        butorphanol.withdrawal = Some(Withdrawal::new(&time, last_dose));
+        ReviewedEncounter {
+            draft_id: "draft-1".to_string(),
+            patient_id: patient_id.to_string(),
+            patient_server_id: None,
+            transcript: "Test transcript".to_string(),
+            line_items: vec![carprofen, prednisone, butorphanol],
+            reviewed_by: "Dr. Smith".to_string(),
+            reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+            notes: Some("Recheck in 2 weeks".to_string()),
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+
+    #[test]
+    fn test_render_markdown_and_text() {
+        let db = Database::open_in_memory().unwrap();
+        let mut patient = Patient::new("Max".to_string(), "canine".to_string());
+        patient.owner_name = Some("Jane Doe".to_string());
+        patient.weight_kg = Some(30.5);
+        db.insert_patient(&patient).unwrap();
+        let commit = MerkleTree::new(&db)
+            .commit_encounter(&make_encounter(&patient.local_id))
+            .unwrap();
+        let exporter = SummaryExporter::new(&db);
+
+        let markdown = exporter
+            .render_summary(&commit.leaf_hash, SummaryFormat::Markdown)
+            .unwrap();
+        assert!(markdown.starts_with("# Medical Record Note\n"));
+        assert!(markdown.contains("**Patient:** Max (canine)  \n"));
+        assert!(markdown.contains("**Owner:** Jane Doe  \n"));
+        assert!(markdown.contains("**Date:** 2024-01-15 10:00 UTC  \n"));
+        assert!(markdown.contains("- **Carprofen 100mg**: 2 tablets, Oral (administered)\n"));
+        assert!(markdown.contains("  - 10 mg Twice daily for 5 days, then 10 mg"));
+        assert!(markdown.contains("**Butorphanol\\_10**: 0.2 mL, Intravenous [C-IV]"));
+        assert!(markdown.contains("  - Withdrawal: meat until 2024-02-12, milk until 2024-01-19"));
+        assert!(markdown.contains("## Notes\n\nRecheck in 2 weeks\n"));
+        assert!(markdown.contains(&format!("Verification: `{}`", commit.leaf_hash)));
+
+        let text = exporter
+            .render_summary(&commit.leaf_hash, SummaryFormat::PlainText)
+            .unwrap();
+        assert!(text.contains("Patient: Max (canine)\nOwner: Jane Doe\nWeight: 30.5 kg\n"));
+        assert!(text.contains("- Butorphanol_10: 0.2 mL, Intravenous [C-IV]\n"));
+        assert!(!text.contains("**"));
+    }
+
+    #[test]
+    fn test_missing_patient_and_unknown_leaf() {
+        let db = Database::open_in_memory().unwrap();
+        let mut encounter = make_encounter("patient-gone");
+        encounter.line_items.clear();
+        encounter.notes = None;
+        let commit = MerkleTree::new(&db).commit_encounter(&encounter).unwrap();
+        let exporter = SummaryExporter::new(&db);
+
+        let text = exporter
+            .render_summary(&commit.leaf_hash, SummaryFormat::PlainText)
+            .unwrap();
+        assert!(text.contains("Patient: patient-gone\nDate:"));
+        assert!(text.contains("Medications:\n  None recorded.\n"));
+        assert!(!text.contains("Notes:"));
+
+        assert!(matches!(
+            exporter.render_summary("missing", SummaryFormat::Markdown),
+            Err(MerkleError::NodeNotFound(_))
+        ));
+        assert_eq!(SummaryFormat::parse("MD"), Some(SummaryFormat::Markdown));
+        assert_eq!(SummaryFormat::parse("txt"), Some(SummaryFormat::PlainText));
+        assert_eq!(SummaryFormat::parse("html"), None);
+    }
+}
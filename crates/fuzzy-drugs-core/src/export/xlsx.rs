@@ -0,0 +1,368 @@
+//! Excel workbooks for billing and compliance batches (`xlsx` feature).
+//!
+//! Each workbook has a summary sheet with one row per encounter, then one
+//! sheet per encounter ("Encounter 1", "Encounter 2", ...) in batch order.
+//! Quantities, prices, and counts are written as numbers so they sort and
+//! sum in Excel.
+
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+
+use crate::models::ResolutionMethod;
+
+use super::{BatchBillingExport, BatchComplianceExport, ExportError, ExportResult};
+
+impl From<XlsxError> for ExportError {
+    fn from(e: XlsxError) -> Self {
+        ExportError::Xlsx(e.to_string())
+    }
+}
+
+/// One spreadsheet cell.
+enum Cell<'a> {
+    Text(&'a str),
+    Number(f64),
+    Empty,
+}
+
+impl<'a> From<&'a str> for Cell<'a> {
+    fn from(s: &'a str) -> Self {
+        Cell::Text(s)
+    }
+}
+
+impl<'a> From<&'a String> for Cell<'a> {
+    fn from(s: &'a String) -> Self {
+        Cell::Text(s)
+    }
+}
+
+impl<'a> From<Option<&'a str>> for Cell<'a> {
+    fn from(s: Option<&'a str>) -> Self {
+        s.map_or(Cell::Empty, Cell::Text)
+    }
+}
+
+impl From<f64> for Cell<'_> {
+    fn from(n: f64) -> Self {
+        Cell::Number(n)
+    }
+}
+
+impl From<Option<f64>> for Cell<'_> {
+    fn from(n: Option<f64>) -> Self {
+        n.map_or(Cell::Empty, Cell::Number)
+    }
+}
+
+impl From<usize> for Cell<'_> {
+    fn from(n: usize) -> Self {
+        Cell::Number(n as f64)
+    }
+}
+
+/// Writes rows top to bottom on one sheet.
+struct SheetWriter<'a> {
+    sheet: &'a mut Worksheet,
+    row: u32,
+    bold: Format,
+}
+
+impl<'a> SheetWriter<'a> {
+    fn new(workbook: &'a mut Workbook, name: &str) -> ExportResult<Self> {
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(name)?;
+        Ok(Self {
+            sheet,
+            row: 0,
+            bold: Format::new().set_bold(),
+        })
+    }
+
+    fn row(&mut self, cells: Vec<Cell<'_>>) -> ExportResult<()> {
+        for (col, cell) in cells.into_iter().enumerate() {
+            let col = col as u16;
+            match cell {
+                Cell::Text(s) => self.sheet.write_string(self.row, col, s)?,
+                Cell::Number(n) => self.sheet.write_number(self.row, col, n)?,
+                Cell::Empty => continue,
+            };
+        }
+        self.row += 1;
+        Ok(())
+    }
+
+    /// A bold header row.
+    fn header(&mut self, names: &[&str]) -> ExportResult<()> {
+        for (col, name) in names.iter().enumerate() {
+            self.sheet
+                .write_string_with_format(self.row, col as u16, *name, &self.bold)?;
+        }
+        self.row += 1;
+        Ok(())
+    }
+
+    /// A bold label with its value beside it.
+    fn field(&mut self, label: &str, value: Cell<'_>) -> ExportResult<()> {
+        self.sheet
+            .write_string_with_format(self.row, 0, label, &self.bold)?;
+        self.row(vec![Cell::Empty, value])
+    }
+
+    fn skip(&mut self) {
+        self.row += 1;
+    }
+}
+
+/// Sheet name for the encounter at `index` in the batch.
+fn encounter_sheet(index: usize) -> String {
+    format!("Encounter {}", index + 1)
+}
+
+fn method_name(method: &ResolutionMethod) -> &'static str {
+    match method {
+        ResolutionMethod::SystemApproved { .. } => "System approved",
+        ResolutionMethod::AlternativeSelected { .. } => "Alternative selected",
+        ResolutionMethod::ManualOverride => "Manual override",
+        ResolutionMethod::ManualEntry => "Manual entry",
+    }
+}
+
+impl BatchBillingExport {
+    /// Export as an Excel workbook.
+    pub fn to_xlsx(&self) -> ExportResult<Vec<u8>> {
+        let mut workbook = Workbook::new();
+
+        let mut summary = SheetWriter::new(&mut workbook, "Summary")?;
+        summary.field("Exported at", Cell::from(&self.exported_at))?;
+        summary.field("Device", Cell::from(self.exported_by_device.as_deref()))?;
+        summary.field("Line items", Cell::from(self.total_items))?;
+        summary.skip();
+        summary.header(&[
+            "Sheet",
+            "Draft ID",
+            "Patient ID",
+            "Reviewed by",
+            "Reviewed at",
+            "Items",
+            "Total",
+            "Tax",
+            "Merkle leaf hash",
+        ])?;
+        for (i, export) in self.encounters.iter().enumerate() {
+            let metadata = &export.metadata;
+            let sheet = encounter_sheet(i);
+            summary.row(vec![
+                Cell::from(&sheet),
+                Cell::from(&metadata.draft_id),
+                Cell::from(&metadata.patient_id),
+                Cell::from(&metadata.reviewed_by),
+                Cell::from(&metadata.reviewed_at),
+                Cell::from(export.line_items.len()),
+                Cell::from(export.totals.total),
+                Cell::from(export.totals.tax),
+                Cell::from(&metadata.merkle_leaf_hash),
+            ])?;
+        }
+
+        for (i, export) in self.encounters.iter().enumerate() {
+            let metadata = &export.metadata;
+            let mut sheet = SheetWriter::new(&mut workbook, &encounter_sheet(i))?;
+            sheet.field("Draft ID", Cell::from(&metadata.draft_id))?;
+            sheet.field("Patient ID", Cell::from(&metadata.patient_id))?;
+            sheet.field("Reviewed by", Cell::from(&metadata.reviewed_by))?;
+            sheet.field("Reviewed at", Cell::from(&metadata.reviewed_at))?;
+            sheet.field("Merkle leaf hash", Cell::from(&metadata.merkle_leaf_hash))?;
+            sheet.skip();
+            sheet.header(&[
+                "SKU",
+                "Description",
+                "Quantity",
+                "Unit",
+                "Route",
+                "Schedule",
+                "Disposition",
+                "Unit price",
+                "Amount",
+                "Tax code",
+                "Tax",
+            ])?;
+            for item in &export.line_items {
+                sheet.row(vec![
+                    Cell::from(&item.sku),
+                    Cell::from(&item.description),
+                    Cell::from(item.quantity),
+                    Cell::from(&item.unit),
+                    Cell::from(item.route.as_deref()),
+                    Cell::from(item.controlled_schedule.as_deref()),
+                    Cell::from(item.disposition.as_deref()),
+                    Cell::from(item.unit_price),
+                    Cell::from(item.extended_price),
+                    Cell::from(item.tax_code.as_deref()),
+                    Cell::from(item.tax),
+                ])?;
+            }
+        }
+
+        Ok(workbook.save_to_buffer()?)
+    }
+}
+
+impl BatchComplianceExport {
+    /// Export as an Excel workbook, with each encounter's Merkle audit path.
+    pub fn to_xlsx(&self) -> ExportResult<Vec<u8>> {
+        let mut workbook = Workbook::new();
+        let metadata = &self.metadata;
+        let verifications = self.verify_all_proofs();
+
+        let mut summary = SheetWriter::new(&mut workbook, "Summary")?;
+        summary.field("Exported at", Cell::from(&metadata.exported_at))?;
+        summary.field("System", Cell::from(metadata.system_id.as_deref()))?;
+        summary.field(
+            "Device",
+            Cell::from(
+                metadata
+                    .exported_by_device
+                    .as_ref()
+                    .map(|d| d.device_id.as_str()),
+            ),
+        )?;
+        summary.field("Hash algorithm", Cell::from(&metadata.hash_algorithm))?;
+        summary.field("Root hash", Cell::from(&metadata.root_hash))?;
+        summary.field("Tree height", Cell::from(f64::from(metadata.tree_height)))?;
+        summary.field("Leaf count", Cell::from(f64::from(metadata.leaf_count)))?;
+        summary.skip();
+        summary.header(&[
+            "Sheet",
+            "Draft ID",
+            "Patient ID",
+            "Reviewed by",
+            "Reviewed at",
+            "Items",
+            "Controlled items",
+            "Leaf hash",
+            "Proof valid",
+        ])?;
+        for (i, (export, verification)) in self.encounters.iter().zip(&verifications).enumerate() {
+            let encounter = &export.encounter;
+            let sheet = encounter_sheet(i);
+            summary.row(vec![
+                Cell::from(&sheet),
+                Cell::from(&encounter.draft_id),
+                Cell::from(&encounter.patient_id),
+                Cell::from(&encounter.reviewed_by),
+                Cell::from(&encounter.reviewed_at),
+                Cell::from(encounter.line_items.len()),
+                Cell::from(export.metadata.controlled_item_count),
+                Cell::from(&export.proof.leaf_hash),
+                Cell::from(if verification.is_valid { "yes" } else { "no" }),
+            ])?;
+        }
+
+        for (i, export) in self.encounters.iter().enumerate() {
+            let encounter = &export.encounter;
+            let mut sheet = SheetWriter::new(&mut workbook, &encounter_sheet(i))?;
+            sheet.field("Draft ID", Cell::from(&encounter.draft_id))?;
+            sheet.field("Patient ID", Cell::from(&encounter.patient_id))?;
+            sheet.field("Reviewed by", Cell::from(&encounter.reviewed_by))?;
+            sheet.field("Reviewed at", Cell::from(&encounter.reviewed_at))?;
+            sheet.field("Notes", Cell::from(encounter.notes.as_deref()))?;
+            sheet.skip();
+            sheet.header(&[
+                "SKU",
+                "Name",
+                "Quantity",
+                "Unit",
+                "Route",
+                "Schedule",
+                "Disposition",
+                "Resolution",
+                "Original mention",
+            ])?;
+            for item in &encounter.line_items {
+                let unit = item.unit.to_string();
+                sheet.row(vec![
+                    Cell::from(&item.sku),
+                    Cell::from(&item.name),
+                    Cell::from(item.quantity),
+                    Cell::from(&unit),
+                    Cell::from(item.route.as_deref()),
+                    Cell::from(item.controlled_schedule.map(|s| s.as_str())),
+                    Cell::from(item.disposition.map(|d| d.as_str())),
+                    Cell::from(method_name(&item.resolution_method)),
+                    Cell::from(&item.original_mention),
+                ])?;
+            }
+
+            sheet.skip();
+            sheet.field("Leaf hash", Cell::from(&export.proof.leaf_hash))?;
+            sheet.field("Root hash", Cell::from(&export.proof.root_hash))?;
+            sheet.field("Leaf index", Cell::from(export.proof.leaf_index))?;
+            sheet.header(&["Audit path", "Sibling position", "Sibling hash"])?;
+            for (step, entry) in export.proof.audit_path.iter().enumerate() {
+                sheet.row(vec![
+                    Cell::from(step + 1),
+                    Cell::from(&entry.position),
+                    Cell::from(&entry.hash),
+                ])?;
+            }
+        }
+
+        Ok(workbook.save_to_buffer()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::Database;
+    use crate::export::{BillingExporter, ComplianceExporter};
+    use crate::merkle::MerkleTree;
+    use crate::models::{EncounterLineItem, ResolutionMethod, ReviewedEncounter};
+
+    fn make_encounter(id: &str) -> ReviewedEncounter {
+        ReviewedEncounter {
+            draft_id: id.to_string(),
+            patient_id: "patient-1".to_string(),
+            patient_server_id: None,
+            transcript: "Test transcript".to_string(),
+            line_items: vec![EncounterLineItem {
+                sku: "SKU001".to_string(),
+                name: "Test Drug".to_string(),
+                quantity: 1.0,
+                unit: "tablet".into(),
+                route: Some("PO".to_string()),
+                original_mention: "test drug".to_string(),
+                resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                controlled_schedule: None,
+                source_spans: vec![],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
+            }],
+            reviewed_by: "Dr. Smith".to_string(),
+            reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+
+    #[test]
+    fn test_batch_xlsx() {
+        let db = Database::open_in_memory().unwrap();
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+
+        // XLSX files are zip archives
+        let billing = BillingExporter::new(&db).export_all().unwrap();
+        assert!(billing.to_xlsx().unwrap().starts_with(b"PK"));
+        let compliance = ComplianceExporter::new(&db).export_all().unwrap();
+        assert!(compliance.to_xlsx().unwrap().starts_with(b"PK"));
+    }
+}
@@ -0,0 +1,118 @@
+//! Health reporting for the shared database handle.
+//!
+//! A panic while holding the database lock poisons it. Rather than failing
+//! every later call, the FFI layer recovers (clears the poison, re-opens the
+//! connection, verifies integrity) and records what happened here so the app
+//! can surface a degraded state instead of forcing a reinstall.
+
+use serde::{Deserialize, Serialize};
+
+/// Overall health of the core.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum HealthState {
+    /// No problems seen
+    #[default]
+    Healthy,
+    /// A poisoned lock was recovered and integrity verified
+    Recovered,
+    /// Recovery failed, integrity check failed, or the lock appears stuck
+    Degraded,
+}
+
+impl HealthState {
+    /// Lowercase name ("healthy", "recovered", "degraded").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthState::Healthy => "healthy",
+            HealthState::Recovered => "recovered",
+            HealthState::Degraded => "degraded",
+        }
+    }
+}
+
+/// Snapshot of the core's health.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct HealthStatus {
+    /// Overall state
+    pub state: HealthState,
+    /// Number of poisoned-lock recoveries since open
+    pub poison_recoveries: u32,
+    /// When the last recovery happened
+    pub last_recovery_at: Option<String>,
+    /// Result of the most recent integrity check
+    pub integrity_ok: bool,
+    /// Whether the database lock could be acquired within the probe timeout
+    pub lock_available: bool,
+    /// Most recent problem, if any
+    pub last_error: Option<String>,
+}
+
+impl HealthStatus {
+    /// Status for a freshly opened database.
+    pub fn new() -> Self {
+        Self {
+            integrity_ok: true,
+            lock_available: true,
+            ..Default::default()
+        }
+    }
+
+    /// Record a successful recovery from a poisoned lock.
+    pub fn record_recovery(&mut self) {
+        self.poison_recoveries += 1;
+        self.last_recovery_at = Some(chrono::Utc::now().to_rfc3339());
+        self.integrity_ok = true;
+        if self.state != HealthState::Degraded {
+            self.state = HealthState::Recovered;
+        }
+    }
+
+    /// Record a problem that leaves the core degraded.
+    pub fn record_failure(&mut self, error: String) {
+        self.state = HealthState::Degraded;
+        self.last_error = Some(error);
+    }
+
+    /// Record the result of an integrity check.
+    ///
+    /// A passing check clears a previous degraded state.
+    pub fn record_integrity(&mut self, problems: &[String]) {
+        if problems.is_empty() {
+            self.integrity_ok = true;
+            if self.state == HealthState::Degraded && self.lock_available {
+                self.state = if self.poison_recoveries > 0 {
+                    HealthState::Recovered
+                } else {
+                    HealthState::Healthy
+                };
+                self.last_error = None;
+            }
+        } else {
+            self.integrity_ok = false;
+            self.record_failure(format!("integrity check failed: {}", problems.join("; ")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_transitions() {
+        let mut status = HealthStatus::new();
+        assert_eq!(status.state, HealthState::Healthy);
+
+        status.record_recovery();
+        assert_eq!(status.state, HealthState::Recovered);
+        assert_eq!(status.poison_recoveries, 1);
+
+        status.record_integrity(&["page 3: btree corrupt".to_string()]);
+        assert_eq!(status.state, HealthState::Degraded);
+        assert!(!status.integrity_ok);
+
+        status.record_integrity(&[]);
+        assert_eq!(status.state, HealthState::Recovered);
+        assert!(status.last_error.is_none());
+    }
+}
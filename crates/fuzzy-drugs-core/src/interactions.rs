@@ -0,0 +1,572 @@
+//! Drug-drug interaction checking.
+//!
+//! A compiled-in interaction table covers common veterinary combinations
+//! (NSAID + corticosteroid, aminoglycoside + furosemide, serotonergic stacks);
+//! clinics can add or override entries in the local `drug_interactions` table.
+//!
+//! Entries key on generic names or drug classes (`"class:nsaid"`), so
+//! "meloxicam + prednisone" is caught by the NSAID/corticosteroid rule.
+//!
+//! A draft is checked against itself and against the patient's active
+//! medications: whatever on their medication list (`patient_medications`)
+//! runs through today. Warnings are informational for the reviewing vet; they
+//! never block commit on their own.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::db::{Database, DbResult};
+use crate::models::{
+    split_components, DrugInteraction, EncounterDraft, InteractionSeverity, InteractionSource,
+    InteractionWarning, ResolutionStatus, DRUG_CLASS_PREFIX,
+};
+
+/// Interaction lookup table with drug class membership.
+#[derive(Debug, Clone, Default)]
+pub struct InteractionTable {
+    entries: Vec<DrugInteraction>,
+    /// Generic name → classes it belongs to
+    classes: HashMap<String, Vec<String>>,
+}
+
+impl InteractionTable {
+    /// Create an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The compiled-in table.
+    pub fn builtin() -> Self {
+        use InteractionSeverity::*;
+
+        let mut table = Self::new();
+        for (class, members) in default_classes() {
+            for drug in members {
+                table.add_class_member(class, drug);
+            }
+        }
+
+        let entries = [
+            (
+                "class:nsaid",
+                "class:corticosteroid",
+                Major,
+                "GI ulceration risk",
+            ),
+            (
+                "class:nsaid",
+                "class:nsaid",
+                Major,
+                "additive GI ulceration and renal toxicity; allow a washout between NSAIDs",
+            ),
+            (
+                "class:nsaid",
+                "class:ace_inhibitor",
+                Moderate,
+                "reduced renal perfusion; monitor renal values",
+            ),
+            (
+                "class:aminoglycoside",
+                "furosemide",
+                Major,
+                "increased ototoxicity and nephrotoxicity",
+            ),
+            (
+                "class:serotonergic",
+                "class:serotonergic",
+                Moderate,
+                "serotonin syndrome risk",
+            ),
+            (
+                "selegiline",
+                "class:serotonergic",
+                Contraindicated,
+                "serotonin syndrome; do not combine MAO-B inhibitors with serotonergic drugs",
+            ),
+            (
+                "class:ace_inhibitor",
+                "spironolactone",
+                Moderate,
+                "hyperkalemia risk; monitor potassium",
+            ),
+            (
+                "digoxin",
+                "furosemide",
+                Moderate,
+                "diuretic-induced hypokalemia increases digoxin toxicity",
+            ),
+            (
+                "enrofloxacin",
+                "theophylline",
+                Moderate,
+                "enrofloxacin raises theophylline levels",
+            ),
+            (
+                "ketoconazole",
+                "cyclosporine",
+                Moderate,
+                "ketoconazole raises cyclosporine levels",
+            ),
+        ];
+        for (a, b, severity, note) in entries {
+            table.add(DrugInteraction::new(a, b, severity, note));
+        }
+        table
+    }
+
+    /// The compiled-in table with local database entries layered on top.
+    pub fn load(db: &Database) -> DbResult<Self> {
+        let mut table = Self::builtin();
+        for entry in db.list_interactions()? {
+            table.add(entry);
+        }
+        Ok(table)
+    }
+
+    /// Add an entry, replacing any existing entry for the same pair.
+    pub fn add(&mut self, interaction: DrugInteraction) {
+        self.entries
+            .retain(|e| !e.matches(&interaction.drug_a, &interaction.drug_b));
+        self.entries.push(interaction);
+    }
+
+    /// Record that a drug belongs to a class (e.g., "meloxicam" → "nsaid").
+    pub fn add_class_member(&mut self, class: &str, drug: &str) {
+        let key = format!("{}{}", DRUG_CLASS_PREFIX, class.trim().to_lowercase());
+        let classes = self.classes.entry(drug.trim().to_lowercase()).or_default();
+        if !classes.contains(&key) {
+            classes.push(key);
+        }
+    }
+
+    /// Find the interaction between two generic drug names.
+    ///
+    /// An entry for the exact drug pair wins (so local entries can override a
+    /// class rule); otherwise the most severe matching class entry is used.
+    pub fn lookup(&self, drug_a: &str, drug_b: &str) -> Option<&DrugInteraction> {
+        let a = drug_a.to_lowercase();
+        let b = drug_b.to_lowercase();
+        if a == b {
+            return None;
+        }
+        if let Some(exact) = self.entries.iter().find(|e| e.matches(&a, &b)) {
+            return Some(exact);
+        }
+
+        let keys_a = self.keys(&a);
+        let keys_b = self.keys(&b);
+
+        self.entries
+            .iter()
+            .filter(|e| {
+                keys_a
+                    .iter()
+                    .any(|ka| keys_b.iter().any(|kb| e.matches(ka, kb)))
+            })
+            .max_by_key(|e| e.severity)
+    }
+
+    /// The drug itself plus every class it belongs to.
+    fn keys(&self, drug: &str) -> Vec<String> {
+        let mut keys = vec![drug.to_string()];
+        if let Some(classes) = self.classes.get(drug) {
+            keys.extend(classes.iter().cloned());
+        }
+        keys
+    }
+}
+
+fn default_classes() -> Vec<(&'static str, Vec<&'static str>)> {
+    vec![
+        (
+            "nsaid",
+            vec![
+                "carprofen",
+                "meloxicam",
+                "deracoxib",
+                "firocoxib",
+                "robenacoxib",
+                "grapiprant",
+                "ketoprofen",
+                "aspirin",
+                "phenylbutazone",
+                "flunixin",
+            ],
+        ),
+        (
+            "corticosteroid",
+            vec![
+                "prednisone",
+                "prednisolone",
+                "dexamethasone",
+                "methylprednisolone",
+                "triamcinolone",
+                "budesonide",
+            ],
+        ),
+        (
+            "aminoglycoside",
+            vec!["gentamicin", "amikacin", "tobramycin", "neomycin"],
+        ),
+        (
+            "serotonergic",
+            vec![
+                "tramadol",
+                "trazodone",
+                "fluoxetine",
+                "clomipramine",
+                "amitriptyline",
+                "mirtazapine",
+                "selegiline",
+            ],
+        ),
+        ("ace_inhibitor", vec!["enalapril", "benazepril"]),
+    ]
+}
+
+/// Checks drafts for drug-drug interactions.
+pub struct InteractionChecker<'a> {
+    db: &'a Database,
+    table: InteractionTable,
+}
+
+impl<'a> InteractionChecker<'a> {
+    /// Create a checker using the compiled-in and local interaction tables.
+    pub fn new(db: &'a Database) -> DbResult<Self> {
+        Ok(Self::with_table(db, InteractionTable::load(db)?))
+    }
+
+    /// Create a checker with a specific table.
+    pub fn with_table(db: &'a Database, table: InteractionTable) -> Self {
+        Self { db, table }
+    }
+
+    /// Check all non-rejected items and manual additions on a draft, plus
+    /// the patient's active medications.
+    pub fn check_draft(&self, draft: &EncounterDraft) -> DbResult<Vec<InteractionWarning>> {
+        let mut drugs: Vec<(String, InteractionSource)> = Vec::new();
+
+        for (index, item) in draft.resolved_items.iter().enumerate() {
+            if matches!(item.status, ResolutionStatus::Rejected) {
+                continue;
+            }
+            let sku = item.final_sku().unwrap_or(&item.top_candidate.sku);
+            let mut names: BTreeSet<String> = split_components(&item.mention.normalized_name)
+                .into_iter()
+                .collect();
+            if let Some(catalog_item) = self.db.get_catalog_item(sku)? {
+                names.extend(catalog_item.component_names());
+            }
+            for name in names {
+                drugs.push((name, InteractionSource::DraftItem { item_index: index }));
+            }
+        }
+
+        for (index, line_item) in draft.manual_items.iter().enumerate() {
+            for name in self.line_item_drugs(&line_item.sku, &line_item.name)? {
+                drugs.push((
+                    name,
+                    InteractionSource::ManualItem {
+                        manual_index: index,
+                    },
+                ));
+            }
+        }
+
+        for name in self.active_medications(&draft.patient_id, Some(&draft.draft_id))? {
+            drugs.push((name, InteractionSource::ActiveMedication));
+        }
+
+        Ok(self.check_drugs(&drugs))
+    }
+
+    /// Check a list of drugs pairwise.
+    ///
+    /// Pairs where both drugs are active medications are skipped; those were
+    /// already reviewed when prescribed.
+    pub fn check_drugs(&self, drugs: &[(String, InteractionSource)]) -> Vec<InteractionWarning> {
+        let mut warnings: Vec<InteractionWarning> = Vec::new();
+        let mut seen: HashSet<(String, String)> = HashSet::new();
+
+        for (i, (drug_a, source_a)) in drugs.iter().enumerate() {
+            for (drug_b, source_b) in drugs.iter().skip(i + 1) {
+                if source_a == source_b {
+                    continue;
+                }
+                let Some(entry) = self.table.lookup(drug_a, drug_b) else {
+                    continue;
+                };
+                let pair = if drug_a <= drug_b {
+                    (drug_a.clone(), drug_b.clone())
+                } else {
+                    (drug_b.clone(), drug_a.clone())
+                };
+                if !seen.insert(pair) {
+                    continue;
+                }
+                warnings.push(InteractionWarning {
+                    drug_a: drug_a.clone(),
+                    source_a: source_a.clone(),
+                    drug_b: drug_b.clone(),
+                    source_b: source_b.clone(),
+                    severity: entry.severity,
+                    note: entry.note.clone(),
+                });
+            }
+        }
+
+        warnings.sort_by_key(|w| std::cmp::Reverse(w.severity));
+        warnings
+    }
+
+    /// Generic names of the patient's medications active today, leaving out
+    /// those committed from `exclude_draft_id` (the draft being checked).
+    pub fn active_medications(
+        &self,
+        patient_id: &str,
+        exclude_draft_id: Option<&str>,
+    ) -> DbResult<Vec<String>> {
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        let mut names: Vec<String> = Vec::new();
+        for medication in self.db.list_active_medications(patient_id, &today)? {
+            if exclude_draft_id.is_some() && medication.draft_id.as_deref() == exclude_draft_id {
+                continue;
+            }
+            let sku = medication.sku.as_deref().unwrap_or_default();
+            for name in self.line_item_drugs(sku, &medication.name)? {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    /// Generic names for a line item or medication (catalog components, else
+    /// its name).
+    fn line_item_drugs(&self, sku: &str, name: &str) -> DbResult<Vec<String>> {
+        Ok(match self.db.get_catalog_item(sku)? {
+            Some(item) => item.component_names(),
+            None => split_components(name),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        CatalogItem, DrugMention, EncounterLineItem, NormalizedMention, Patient, ResolutionMethod,
+        ResolvedItem, ReviewedEncounter, ScoreBreakdown, ScoredCandidate,
+    };
+
+    fn resolved(drug: &str, sku: &str, status: ResolutionStatus) -> ResolvedItem {
+        ResolvedItem {
+            mention: NormalizedMention {
+                original: DrugMention {
+                    raw_text: drug.into(),
+                    drug_name: drug.into(),
+                    dose: None,
+                    unit: None,
+                    route: None,
+                    species: None,
+                    start_offset: 0,
+                    end_offset: drug.len(),
+                    field_spans: Default::default(),
+                    extraction_confidence: None,
+                    speaker: None,
+                },
+                normalized_name: drug.into(),
+                normalized_dose: None,
+                normalized_unit: None,
+                normalized_route: None,
+                infusion: None,
+                taper: None,
+            },
+            top_candidate: ScoredCandidate {
+                sku: sku.into(),
+                name: drug.into(),
+                confidence: 0.9,
+                score_breakdown: ScoreBreakdown {
+                    name_score: 1.0,
+                    species_score: 1.0,
+                    route_score: 1.0,
+                    dose_score: 1.0,
+                },
+                suggested_quantity: None,
+                controlled_schedule: None,
+            },
+            alternatives: vec![],
+            status,
+            controlled_confirmed_by: None,
+            safety_warnings: vec![],
+            allergy_overridden_by: None,
+            duplicate_mentions: vec![],
+            tied_skus: vec![],
+            escalation: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            override_schedule: None,
+        }
+    }
+
+    #[test]
+    fn test_lookup_by_class() {
+        let table = InteractionTable::builtin();
+
+        let entry = table.lookup("meloxicam", "prednisone").unwrap();
+        assert_eq!(entry.severity, InteractionSeverity::Major);
+        assert_eq!(entry.note, "GI ulceration risk");
+
+        // Two different NSAIDs interact; the same NSAID twice does not
+        assert!(table.lookup("carprofen", "meloxicam").is_some());
+        assert!(table.lookup("carprofen", "carprofen").is_none());
+
+        // Most severe class rule wins
+        assert_eq!(
+            table.lookup("selegiline", "tramadol").unwrap().severity,
+            InteractionSeverity::Contraindicated
+        );
+
+        assert!(table.lookup("carprofen", "cerenia").is_none());
+    }
+
+    #[test]
+    fn test_local_entry_overrides_class_rule() {
+        let db = Database::open_in_memory().unwrap();
+        db.upsert_interaction(&DrugInteraction::new(
+            "meloxicam",
+            "budesonide",
+            InteractionSeverity::Minor,
+            "low systemic exposure",
+        ))
+        .unwrap();
+
+        let table = InteractionTable::load(&db).unwrap();
+        assert_eq!(
+            table.lookup("budesonide", "meloxicam").unwrap().severity,
+            InteractionSeverity::Minor
+        );
+        assert_eq!(
+            table.lookup("prednisone", "meloxicam").unwrap().severity,
+            InteractionSeverity::Major
+        );
+    }
+
+    #[test]
+    fn test_check_draft_with_active_medications() {
+        let db = Database::open_in_memory().unwrap();
+        let patient = Patient::new("Max".into(), "canine".into());
+        db.insert_patient(&patient).unwrap();
+        db.upsert_catalog_item(&CatalogItem::new(
+            "MELOX-15".into(),
+            "Meloxicam 1.5mg/mL".into(),
+        ))
+        .unwrap();
+        db.upsert_catalog_item(&CatalogItem::new("PRED-5".into(), "Prednisone 5mg".into()))
+            .unwrap();
+
+        // Prednisone committed at a previous visit
+        let previous = ReviewedEncounter {
+            draft_id: "previous".into(),
+            patient_id: patient.local_id.clone(),
+            patient_server_id: None,
+            transcript: "prednisone 5mg".into(),
+            line_items: vec![EncounterLineItem {
+                sku: "PRED-5".into(),
+                name: "Prednisone 5mg".into(),
+                quantity: 1.0,
+                unit: "tablets".into(),
+                route: Some("PO".into()),
+                original_mention: "prednisone 5mg".into(),
+                resolution_method: ResolutionMethod::SystemApproved { confidence: 0.9 },
+                controlled_schedule: None,
+                source_spans: vec![],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
+            }],
+            reviewed_by: "Dr. Smith".into(),
+            reviewed_at: chrono::Utc::now().to_rfc3339(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        };
+        db.record_encounter_medications(&previous).unwrap();
+
+        let mut draft = EncounterDraft::new(patient.local_id.clone());
+        draft.resolved_items.push(resolved(
+            "meloxicam",
+            "MELOX-15",
+            ResolutionStatus::Approved,
+        ));
+
+        let checker = InteractionChecker::new(&db).unwrap();
+        assert_eq!(
+            checker.active_medications(&patient.local_id, None).unwrap(),
+            vec!["prednisone"]
+        );
+        // Re-checking the committed draft itself doesn't count its own drugs
+        assert!(checker
+            .active_medications(&patient.local_id, Some("previous"))
+            .unwrap()
+            .is_empty());
+
+        let warnings = checker.check_draft(&draft).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message(),
+            "meloxicam + prednisone: GI ulceration risk"
+        );
+        assert_eq!(
+            warnings[0].source_a,
+            InteractionSource::DraftItem { item_index: 0 }
+        );
+        assert_eq!(warnings[0].source_b, InteractionSource::ActiveMedication);
+
+        // Rejected items are not checked
+        draft.resolved_items[0].status = ResolutionStatus::Rejected;
+        assert!(checker.check_draft(&draft).unwrap().is_empty());
+
+        // Other patients' medications don't count
+        let other = Patient::new("Bella".into(), "canine".into());
+        assert!(checker
+            .active_medications(&other.local_id, None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_check_draft_items_pairwise() {
+        let db = Database::open_in_memory().unwrap();
+        let mut draft = EncounterDraft::new("patient-1".into());
+        draft.resolved_items.push(resolved(
+            "gentamicin",
+            "GENT",
+            ResolutionStatus::PendingReview,
+        ));
+        draft.add_manual_item(
+            "FUROS".into(),
+            "Furosemide 50mg/mL".into(),
+            1.0,
+            "mL".into(),
+            None,
+        );
+
+        let checker = InteractionChecker::new(&db).unwrap();
+        let warnings = checker.check_draft(&draft).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, InteractionSeverity::Major);
+        assert_eq!(
+            warnings[0].source_b,
+            InteractionSource::ManualItem { manual_index: 0 }
+        );
+    }
+}
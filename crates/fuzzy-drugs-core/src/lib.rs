@@ -37,26 +37,50 @@
 //! - [`merkle`]: Merkle tree for tamper-evident audit log
 //! - [`resolver`]: Semantic resolver (normalizer + disambiguator)
 //! - [`export`]: Billing and compliance export
+//! - [`interactions`]: Drug-drug interaction checking
+//! - [`limits`]: Size limits for transcripts and payloads
+//! - [`logging`]: Diagnostics log sink for the host app
+//! - [`health`]: Lock poisoning recovery and health reporting
+//! - [`compat`]: FFI API version and deprecated method shims
+//! - [`progress`]: Progress reporting and cancellation for long operations
 
+pub mod compat;
 pub mod db;
 pub mod export;
+pub mod health;
+pub mod interactions;
+pub mod limits;
+pub mod logging;
 pub mod merkle;
 pub mod models;
+pub mod progress;
 pub mod resolver;
 
 // Re-export commonly used types
 pub use db::Database;
-pub use merkle::{LeafCommit, MerkleTree, TreeStats};
+pub use health::{HealthState, HealthStatus};
+pub use interactions::{InteractionChecker, InteractionTable};
+pub use limits::Limits;
+pub use merkle::{LeafCommit, MerkleProof, MerkleTree, TreeStats};
 pub use models::{
-    CatalogItem, DoseRange, DraftStatus, EncounterDraft, EncounterLineItem, Patient,
-    ResolutionMethod, ResolutionStatus, ReviewedEncounter,
+    CatalogItem, CommitPreview, ControlledSchedule, DoseRange, DraftStatus, EncounterDraft,
+    EncounterLineItem, Patient, PreviewChange, PriceEstimate, ResolutionMethod, ResolutionStatus,
+    ReviewedEncounter, WeightUnit,
+};
+pub use resolver::{
+    ExtractedMentions, MentionExtractor, Normalizer, NormalizerDataInfo, NormalizerLocale,
+    Resolver, RuleBasedExtractor,
 };
-pub use resolver::{Normalizer, Resolver};
 
 // UniFFI setup - using proc macros
 uniffi::setup_scaffolding!();
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::time::{Duration, Instant};
+
+use ed25519_dalek::SigningKey;
+
+use progress::Progress;
 
 // =========================================================================
 // FFI Error Type
@@ -78,12 +102,113 @@ pub enum FuzzyDrugsError {
 
     #[error("Sync error: {0}")]
     SyncError(String),
+
+    #[error("I/O error: {0}")]
+    IoError(String),
+
+    #[error("Extraction error: {0}")]
+    ExtractionError(String),
+
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
+    /// No catalog item matched the drug name.
+    #[error("No candidates found for: {drug_name}")]
+    NoCandidates { drug_name: String },
+
+    /// The record's current state doesn't allow the operation. `subject_type`
+    /// is "draft", "patient", "encounter", "catalog_item", or "sync" (subject:
+    /// PIMS root); `reason` is one of "already_committed", "already_reviewed",
+    /// "not_awaiting_commit", "legal_hold", "already_held", "in_use", or
+    /// "sync_diverged".
+    #[error("Conflict: {message}")]
+    Conflict {
+        subject_type: String,
+        subject_id: String,
+        reason: String,
+        message: String,
+    },
+
+    /// The caller lacks the role the operation requires.
+    #[error("Permission denied: {message}")]
+    PermissionDenied {
+        required_role: String,
+        message: String,
+    },
+
+    /// Stored data failed verification. `component` is "database" (SQLite
+    /// integrity check or corruption) or "merkle_tree" (inconsistent tree).
+    #[error("Integrity failure: {message}")]
+    IntegrityFailure {
+        component: String,
+        problems: Vec<String>,
+        message: String,
+    },
+}
+
+impl FuzzyDrugsError {
+    fn conflict(subject_type: &str, subject_id: &str, reason: &str, message: String) -> Self {
+        FuzzyDrugsError::Conflict {
+            subject_type: subject_type.to_string(),
+            subject_id: subject_id.to_string(),
+            reason: reason.to_string(),
+            message,
+        }
+    }
+
+    fn integrity_failure(component: &str, problems: Vec<String>) -> Self {
+        FuzzyDrugsError::IntegrityFailure {
+            component: component.to_string(),
+            message: problems.join("; "),
+            problems,
+        }
+    }
 }
 
 impl From<db::DbError> for FuzzyDrugsError {
     fn from(e: db::DbError) -> Self {
-        FuzzyDrugsError::DatabaseError(e.to_string())
+        match e {
+            db::DbError::LimitExceeded(msg) => FuzzyDrugsError::InvalidInput(msg),
+            db::DbError::NotFound(msg) => FuzzyDrugsError::NotFound(msg),
+            db::DbError::LegalHold {
+                subject_type,
+                subject_id,
+                reason,
+            } => {
+                let message = format!(
+                    "{} {} is under legal hold ({})",
+                    subject_type, subject_id, reason
+                );
+                FuzzyDrugsError::conflict(&subject_type, &subject_id, "legal_hold", message)
+            }
+            db::DbError::InUse {
+                subject_type,
+                subject_id,
+                message,
+            } => FuzzyDrugsError::conflict(&subject_type, &subject_id, "in_use", message),
+            db::DbError::Cancelled(e) => e.into(),
+            db::DbError::Sqlite(e) => sqlite_error(e),
+            e => FuzzyDrugsError::DatabaseError(e.to_string()),
+        }
+    }
+}
+
+/// Corruption and malformed search queries get their own variants; other
+/// SQLite errors are reported as database errors.
+fn sqlite_error(e: rusqlite::Error) -> FuzzyDrugsError {
+    if let rusqlite::Error::SqliteFailure(failure, _) = &e {
+        if matches!(
+            failure.code,
+            rusqlite::ErrorCode::DatabaseCorrupt | rusqlite::ErrorCode::NotADatabase
+        ) {
+            return FuzzyDrugsError::integrity_failure("database", vec![e.to_string()]);
+        }
     }
+    let message = e.to_string();
+    if message.contains("fts5: syntax error") {
+        return FuzzyDrugsError::InvalidInput(format!("Search query: {}", message));
+    }
+    FuzzyDrugsError::DatabaseError(db::DbError::Sqlite(e).to_string())
 }
 
 impl From<serde_json::Error> for FuzzyDrugsError {
@@ -94,13 +219,69 @@ impl From<serde_json::Error> for FuzzyDrugsError {
 
 impl From<merkle::MerkleError> for FuzzyDrugsError {
     fn from(e: merkle::MerkleError) -> Self {
-        FuzzyDrugsError::DatabaseError(e.to_string())
+        match e {
+            merkle::MerkleError::PayloadTooLarge(msg) => FuzzyDrugsError::InvalidInput(msg),
+            merkle::MerkleError::InvalidChunk(msg) => FuzzyDrugsError::InvalidInput(msg),
+            merkle::MerkleError::Database(e) => e.into(),
+            merkle::MerkleError::NodeNotFound(hash) => {
+                FuzzyDrugsError::NotFound(format!("Node {}", hash))
+            }
+            merkle::MerkleError::InvalidState(msg) => {
+                FuzzyDrugsError::integrity_failure("merkle_tree", vec![msg])
+            }
+            merkle::MerkleError::Cancelled(e) => e.into(),
+            merkle::MerkleError::Transport(e) => e.into(),
+            merkle::MerkleError::SyncConflict { ref server_root } => {
+                FuzzyDrugsError::conflict("sync", server_root, "sync_diverged", e.to_string())
+            }
+            e => FuzzyDrugsError::DatabaseError(e.to_string()),
+        }
+    }
+}
+
+impl From<merkle::TransportError> for FuzzyDrugsError {
+    fn from(e: merkle::TransportError) -> Self {
+        FuzzyDrugsError::SyncError(e.to_string())
+    }
+}
+
+impl From<progress::Cancelled> for FuzzyDrugsError {
+    fn from(e: progress::Cancelled) -> Self {
+        FuzzyDrugsError::Cancelled(e.to_string())
+    }
+}
+
+impl From<export::ExportError> for FuzzyDrugsError {
+    fn from(e: export::ExportError) -> Self {
+        match e {
+            export::ExportError::Merkle(e) => e.into(),
+            export::ExportError::Json(e) => e.into(),
+            export::ExportError::Io(e) => FuzzyDrugsError::IoError(e.to_string()),
+            export::ExportError::Pdf(e) => FuzzyDrugsError::SerializationError(e),
+            export::ExportError::Xlsx(e) => FuzzyDrugsError::SerializationError(e),
+            export::ExportError::Zip(e) => FuzzyDrugsError::SerializationError(e),
+            e @ export::ExportError::MissingIntegrity => {
+                FuzzyDrugsError::InvalidInput(e.to_string())
+            }
+        }
     }
 }
 
 impl From<resolver::ResolverError> for FuzzyDrugsError {
     fn from(e: resolver::ResolverError) -> Self {
-        FuzzyDrugsError::DatabaseError(e.to_string())
+        match e {
+            resolver::ResolverError::Extraction(msg) => FuzzyDrugsError::ExtractionError(msg),
+            resolver::ResolverError::NoCandidates(drug_name) => {
+                FuzzyDrugsError::NoCandidates { drug_name }
+            }
+            resolver::ResolverError::Database(e) => e.into(),
+        }
+    }
+}
+
+impl From<uniffi::UnexpectedUniFFICallbackError> for FuzzyDrugsError {
+    fn from(e: uniffi::UnexpectedUniFFICallbackError) -> Self {
+        FuzzyDrugsError::ExtractionError(e.reason)
     }
 }
 
@@ -117,21 +298,121 @@ impl<T> From<std::sync::PoisonError<T>> for FuzzyDrugsError {
 /// Open or create a database at the given path.
 #[uniffi::export]
 pub fn open_database(path: String) -> Result<Arc<FuzzyDrugsCore>, FuzzyDrugsError> {
+    FuzzyDrugsCore::from_database(Database::open(&path)?)
+}
+
+/// Open or create a database at the given path and store `options` as this
+/// clinic's settings. Options left `None` keep their stored values; later
+/// `open_database` calls use the stored settings.
+#[uniffi::export]
+pub fn open_database_with_options(
+    path: String,
+    options: FfiCoreOptions,
+) -> Result<Arc<FuzzyDrugsCore>, FuzzyDrugsError> {
     let db = Database::open(&path)?;
-    Ok(Arc::new(FuzzyDrugsCore {
-        db: Arc::new(Mutex::new(db)),
-    }))
+    FuzzyDrugsCore::store_options(&db, options)?;
+    FuzzyDrugsCore::from_database(db)
 }
 
 /// Create an in-memory database (for testing).
 #[uniffi::export]
 pub fn open_database_in_memory() -> Result<Arc<FuzzyDrugsCore>, FuzzyDrugsError> {
-    let db = Database::open_in_memory()?;
-    Ok(Arc::new(FuzzyDrugsCore {
-        db: Arc::new(Mutex::new(db)),
-    }))
+    FuzzyDrugsCore::from_database(Database::open_in_memory()?)
+}
+
+/// Expand route/frequency codes in free text (e.g., a sig) for labels,
+/// after-visit summaries, and printable reports.
+///
+/// `target` is "billing" or "client" (aliases: "label", "summary", "report");
+/// `language` is a language tag such as "en" or "es".
+#[uniffi::export]
+pub fn expand_abbreviations(
+    text: String,
+    target: String,
+    language: String,
+) -> Result<String, FuzzyDrugsError> {
+    let phrasebook = parse_phrasebook(&target, &language)?;
+    Ok(phrasebook.expand(&text))
+}
+
+/// Check a batch billing or compliance JSON export against its integrity
+/// section: whether the body was modified and whether it is signed by
+/// `trusted_public_key` (hex, the clinic's published key).
+///
+/// Without a trusted key the signature isn't checked (`signature_valid` is
+/// None) and `is_valid` is false: a key recorded in the export proves
+/// nothing. Exports without an integrity section fail with `InvalidInput`.
+#[uniffi::export]
+pub fn verify_export(
+    json: String,
+    trusted_public_key: Option<String>,
+) -> Result<FfiExportVerification, FuzzyDrugsError> {
+    Ok(export::verify_export(&json, trusted_public_key.as_deref())?.into())
+}
+
+/// Report the core's version, FFI API version, and deprecated methods.
+///
+/// Host apps call this before opening a database to check that their
+/// generated bindings are still supported.
+#[uniffi::export]
+pub fn get_core_version() -> FfiCoreVersion {
+    FfiCoreVersion {
+        core_version: env!("CARGO_PKG_VERSION").to_string(),
+        api_version: compat::API_VERSION,
+        min_supported_api_version: compat::MIN_SUPPORTED_API_VERSION,
+        deprecated_methods: compat::DEPRECATIONS.iter().map(|d| (*d).into()).collect(),
+        compatibility_report: COMPATIBILITY_REPORT.to_string(),
+    }
+}
+
+/// Whether bindings generated for `api_version` can call this core.
+#[uniffi::export]
+pub fn is_api_version_supported(api_version: u32) -> bool {
+    compat::is_supported(api_version)
+}
+
+/// Verify an inclusion proof by hashing the leaf up its audit path.
+///
+/// This only shows the leaf is in the tree with `proof.root_hash`; compare
+/// that root with `get_tree_stats().root_hash` (or a root synced from the
+/// server) to check it against a trusted tree. Malformed proofs are invalid.
+#[uniffi::export]
+pub fn verify_inclusion_proof(proof: FfiInclusionProof) -> bool {
+    MerkleProof::try_from(proof).is_ok_and(|proof| merkle::verify_proof(&proof))
+}
+
+/// Send core diagnostics to `sink`, replacing any previous sink.
+///
+/// Events at `level` and above are logged ("error", "warn", "info", "debug",
+/// "trace", or "off"); `modules` overrides the level per module, e.g.
+/// `resolver` at "trace" for candidate scores. Applies process-wide.
+#[uniffi::export]
+pub fn set_log_sink(
+    sink: Arc<dyn FfiLogSink>,
+    level: String,
+    modules: Vec<FfiModuleLevel>,
+) -> Result<(), FuzzyDrugsError> {
+    let default = parse_log_level(&level)?;
+    let modules = modules
+        .into_iter()
+        .map(|m| Ok((m.module, parse_log_level(&m.level)?)))
+        .collect::<Result<Vec<_>, FuzzyDrugsError>>()?;
+    logging::set_sink(
+        Arc::new(ForeignLogSink(sink)),
+        logging::filter(default, &modules),
+    );
+    Ok(())
 }
 
+/// Stop sending core diagnostics to the host app.
+#[uniffi::export]
+pub fn clear_log_sink() {
+    logging::clear_sink();
+}
+
+/// Compatibility report written by the build script.
+const COMPATIBILITY_REPORT: &str = include_str!(concat!(env!("OUT_DIR"), "/ffi_compat_report.txt"));
+
 // =========================================================================
 // Main API Object
 // =========================================================================
@@ -140,118 +421,608 @@ pub fn open_database_in_memory() -> Result<Arc<FuzzyDrugsCore>, FuzzyDrugsError>
 #[derive(uniffi::Object)]
 pub struct FuzzyDrugsCore {
     db: Arc<Mutex<Database>>,
+    normalizer: Arc<Mutex<Normalizer>>,
+    health: Arc<Mutex<HealthStatus>>,
+    /// Extractor used by `process_transcript`, set by the host app
+    extractor: Arc<Mutex<Option<Arc<dyn MentionExtractor>>>>,
+    /// Model and prompt version of the host's extractor, for the extraction
+    /// cache; cleared when the extractor is replaced
+    extractor_version: Arc<Mutex<Option<String>>>,
+    /// Change listener, set by the host app
+    listener: Arc<Mutex<Option<Arc<dyn FuzzyDrugsListener>>>>,
+    /// Key for signing batch exports, set by the host app (never stored)
+    signing_key: Arc<Mutex<Option<SigningKey>>>,
+    /// Held for a whole sync exchange, so only one runs at a time while the
+    /// database lock is released for the network calls
+    sync: Arc<Mutex<()>>,
+}
+
+/// Lends a sync engine the core's database one step at a time, locking it
+/// only for that step.
+struct LockedDatabase<'a>(&'a FuzzyDrugsCore);
+
+impl merkle::SyncStore for LockedDatabase<'_> {
+    fn with_db<R>(
+        &self,
+        f: impl FnOnce(&Database) -> merkle::MerkleResult<R>,
+    ) -> merkle::MerkleResult<R> {
+        let db = self
+            .0
+            .lock_db()
+            .map_err(|e| merkle::MerkleError::InvalidState(e.to_string()))?;
+        f(&db)
+    }
 }
 
-#[uniffi::export]
 impl FuzzyDrugsCore {
-    // =========================================================================
-    // Catalog Operations
-    // =========================================================================
+    /// Wrap an opened database, applying its stored locale.
+    fn from_database(db: Database) -> Result<Arc<Self>, FuzzyDrugsError> {
+        let mut normalizer = Normalizer::new();
+        if let Some(locale) = db
+            .core_settings()?
+            .locale
+            .as_deref()
+            .and_then(NormalizerLocale::parse)
+        {
+            normalizer.set_locale(locale);
+        }
+        Ok(Arc::new(FuzzyDrugsCore {
+            db: Arc::new(Mutex::new(db)),
+            normalizer: Arc::new(Mutex::new(normalizer)),
+            health: Arc::new(Mutex::new(HealthStatus::new())),
+            extractor: Arc::new(Mutex::new(None)),
+            extractor_version: Arc::new(Mutex::new(None)),
+            listener: Arc::new(Mutex::new(None)),
+            signing_key: Arc::new(Mutex::new(None)),
+            sync: Arc::new(Mutex::new(())),
+        }))
+    }
 
-    /// Add or update a catalog item.
-    pub fn upsert_catalog_item(&self, item: FfiCatalogItem) -> Result<(), FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let catalog_item = item.into();
-        db.upsert_catalog_item(&catalog_item)?;
-        Ok(())
+    /// Validate `options`, then store the ones that are set. Nothing is
+    /// stored if any option is invalid.
+    fn store_options(db: &Database, options: FfiCoreOptions) -> Result<(), FuzzyDrugsError> {
+        let scoring = options.scoring.map(models::ScoringConfig::from);
+        if let Some(config) = &scoring {
+            config.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        }
+        let mut settings = db.core_settings()?;
+        if let Some(order) = options.review_queue_order {
+            settings.review_queue_order =
+                models::ReviewQueueOrder::parse(&order).ok_or_else(|| {
+                    FuzzyDrugsError::InvalidInput(format!("Unknown review queue order: {}", order))
+                })?;
+        }
+        if let Some(language) = options.locale {
+            let locale = NormalizerLocale::parse(&language).ok_or_else(|| {
+                FuzzyDrugsError::InvalidInput(format!("Unsupported language: {}", language))
+            })?;
+            settings.locale = Some(locale.as_str().to_string());
+        }
+        if let Some(system_id) = options.system_id {
+            let system_id = system_id.trim();
+            if system_id.is_empty() {
+                return Err(FuzzyDrugsError::InvalidInput("System ID is empty".into()));
+            }
+            settings.system_id = Some(system_id.to_string());
+        }
+        if let Some(strategy) = options.sync_conflict_strategy {
+            settings.sync_conflict_strategy = models::SyncConflictStrategy::parse(&strategy)
+                .ok_or_else(|| {
+                    FuzzyDrugsError::InvalidInput(format!(
+                        "Unknown sync conflict strategy: {}",
+                        strategy
+                    ))
+                })?;
+        }
+
+        if let Some(config) = &scoring {
+            db.set_scoring_config(config)?;
+        }
+        Ok(db.set_core_settings(&settings)?)
     }
 
-    /// Get a catalog item by SKU.
-    pub fn get_catalog_item(&self, sku: String) -> Result<Option<FfiCatalogItem>, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let item = db.get_catalog_item(&sku)?;
-        Ok(item.map(|i| i.into()))
+    /// Lock the database, recovering if a previous holder panicked.
+    ///
+    /// Recovery clears the poison, resets the connection, and verifies
+    /// integrity; the outcome is recorded in the health status.
+    fn lock_db(&self) -> Result<MutexGuard<'_, Database>, FuzzyDrugsError> {
+        let mut db = match self.db.lock() {
+            Ok(db) => return Ok(db),
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        self.db.clear_poison();
+
+        let checked = db.recover_connection().and_then(|_| db.integrity_check());
+        let mut health = self.lock_health();
+        match checked {
+            Ok(problems) => {
+                health.record_recovery();
+                health.record_integrity(&problems);
+                if !problems.is_empty() {
+                    tracing::error!(?problems, "Database failed integrity check after recovery");
+                    return Err(FuzzyDrugsError::integrity_failure("database", problems));
+                }
+                tracing::warn!("Recovered database connection after a panic");
+                Ok(db)
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Database recovery failed");
+                health.record_failure(format!("Recovery failed: {}", e));
+                Err(e.into())
+            }
+        }
     }
 
-    /// Search catalog by name/alias.
-    pub fn search_catalog(
+    /// Lock the normalizer, clearing poison (its data is replaced wholesale,
+    /// so a panic cannot leave it half-updated).
+    fn lock_normalizer(&self) -> Result<MutexGuard<'_, Normalizer>, FuzzyDrugsError> {
+        Ok(self.normalizer.lock().unwrap_or_else(|poisoned| {
+            self.normalizer.clear_poison();
+            poisoned.into_inner()
+        }))
+    }
+
+    /// Lock the health status (plain data; poison is ignored).
+    fn lock_health(&self) -> MutexGuard<'_, HealthStatus> {
+        self.health.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The configured mention extractor (poison is ignored).
+    fn mention_extractor(&self) -> Result<Arc<dyn MentionExtractor>, FuzzyDrugsError> {
+        self.extractor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .ok_or_else(|| FuzzyDrugsError::InvalidInput("No mention extractor set".into()))
+    }
+
+    /// Replace the host extractor's version (poison is ignored).
+    fn set_version_slot(&self, version: Option<String>) {
+        *self
+            .extractor_version
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = version;
+    }
+
+    /// Run the configured extractor over `transcript`, reusing a cached
+    /// result when the extractor has a version (its own, or the one set by
+    /// `set_extractor_version`).
+    fn extract_cached(&self, transcript: &str) -> Result<ExtractedMentions, FuzzyDrugsError> {
+        let extractor = self.mention_extractor()?;
+        let version = extractor.version().or_else(|| {
+            self.extractor_version
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        });
+        let Some(version) = version else {
+            return Ok(extractor.extract_all(transcript)?);
+        };
+        if let Some(cached) = self
+            .lock_db()?
+            .get_cached_extraction(transcript, &version)?
+        {
+            tracing::debug!(version, "Extraction cache hit");
+            return Ok(cached);
+        }
+        let extracted = extractor.extract_all(transcript)?;
+        self.lock_db()?
+            .cache_extraction(transcript, &version, &extracted)?;
+        Ok(extracted)
+    }
+
+    /// Report changes to the listener, if one is set. Call only after the
+    /// database lock is released.
+    fn notify(&self, events: Vec<CoreEvent>) {
+        let listener = self
+            .listener
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Some(listener) = listener {
+            for event in events {
+                event.deliver(listener.as_ref());
+            }
+        }
+    }
+
+    /// Whether the tree has changes not yet synced.
+    fn has_unsynced(db: &Database) -> Result<bool, FuzzyDrugsError> {
+        Ok(merkle::SyncManager::new(db).has_unsynced_changes()?)
+    }
+
+    /// Serialize sync exchanges, ignoring a poisoned lock (it guards no data).
+    fn lock_sync(&self) -> MutexGuard<'_, ()> {
+        self.sync
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sync the tree over `transport`, then report any sync-state change.
+    ///
+    /// The database is locked only between network calls, so other calls
+    /// (and commits) proceed while the PIMS is being waited on.
+    fn sync_over(
         &self,
-        query: String,
-        limit: u32,
-    ) -> Result<Vec<FfiCatalogItem>, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let items = db.search_catalog(&query, limit as usize)?;
-        Ok(items.into_iter().map(|i| i.into()).collect())
+        transport: &dyn merkle::SyncTransport,
+    ) -> Result<FfiSyncReport, FuzzyDrugsError> {
+        let _sync = self.lock_sync();
+        let (was_unsynced, queued) = {
+            let db = self.lock_db()?;
+            let queued: Vec<i64> = db
+                .list_outbox()?
+                .into_iter()
+                .filter(|item| item.kind == models::OutboxKind::EncounterPush)
+                .map(|item| item.id)
+                .collect();
+            (Self::has_unsynced(&db)?, queued)
+        };
+        let report = merkle::SyncEngine::new(&LockedDatabase(self), transport).run_sync()?;
+        let db = self.lock_db()?;
+        // The tree as of the sync reached the PIMS, with the encounters
+        // queued before it; ones committed meanwhile stay queued
+        for id in queued {
+            db.complete_outbox_item(id)?;
+        }
+        let events = Self::sync_state_event(&db, was_unsynced)?
+            .into_iter()
+            .collect();
+        drop(db);
+        self.notify(events);
+        Ok(report.into())
     }
 
-    // =========================================================================
-    // Patient Operations
-    // =========================================================================
+    /// Work through the sync outbox over `transport`, then report any
+    /// sync-state change.
+    fn process_outbox_over(
+        &self,
+        transport: &dyn merkle::SyncTransport,
+    ) -> Result<FfiOutboxReport, FuzzyDrugsError> {
+        let _sync = self.lock_sync();
+        let was_unsynced = Self::has_unsynced(&*self.lock_db()?)?;
+        let report = merkle::SyncEngine::new(&LockedDatabase(self), transport)
+            .process_outbox(chrono::Utc::now(), &models::RetryPolicy::default())?;
+        let db = self.lock_db()?;
+        let events = Self::sync_state_event(&db, was_unsynced)?
+            .into_iter()
+            .collect();
+        drop(db);
+        self.notify(events);
+        Ok(report.into())
+    }
 
-    /// Create a new patient.
-    pub fn create_patient(
+    /// Event for a sync-state flip after the tree changed, if it flipped.
+    fn sync_state_event(
+        db: &Database,
+        was_unsynced: bool,
+    ) -> Result<Option<CoreEvent>, FuzzyDrugsError> {
+        let has_unsynced_changes = Self::has_unsynced(db)?;
+        Ok(
+            (has_unsynced_changes != was_unsynced).then_some(CoreEvent::SyncStateChanged {
+                has_unsynced_changes,
+            }),
+        )
+    }
+
+    /// Events for committing a draft's encounter: the draft's move to
+    /// `Committed` (if it wasn't already), the new leaf, and sync state.
+    fn commit_events(
+        db: &Database,
+        draft_id: &str,
+        previous_status: Option<&DraftStatus>,
+        leaf_hash: &str,
+        was_unsynced: bool,
+    ) -> Result<Vec<CoreEvent>, FuzzyDrugsError> {
+        let mut events = Vec::new();
+        if previous_status.is_some_and(|status| *status != DraftStatus::Committed) {
+            events.push(CoreEvent::draft_status(draft_id, &DraftStatus::Committed));
+        }
+        events.push(CoreEvent::EncounterCommitted {
+            draft_id: draft_id.to_string(),
+            leaf_hash: leaf_hash.to_string(),
+        });
+        events.extend(Self::sync_state_event(db, was_unsynced)?);
+        Ok(events)
+    }
+
+    /// Apply a review decision to one resolved item of a stored draft.
+    ///
+    /// The read, decision, and write happen under one database lock, so
+    /// concurrent review calls cannot overwrite each other. If the chosen SKU
+    /// changes, its escalation rule is looked up again (any earlier approval
+    /// was for the old SKU), and so is its catalog schedule when it isn't
+    /// one of the candidates. The new SKU is checked against the patient's
+    /// allergies.
+    fn review_item(
         &self,
-        name: String,
-        species: String,
-    ) -> Result<FfiPatient, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let patient = Patient::new(name, species);
-        db.insert_patient(&patient)?;
-        Ok(patient.into())
+        draft_id: &str,
+        item_index: u32,
+        decide: impl FnOnce(
+            &Database,
+            &models::ResolvedItem,
+        ) -> Result<ResolutionStatus, FuzzyDrugsError>,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        if draft.status == DraftStatus::Committed {
+            return Err(FuzzyDrugsError::conflict(
+                "draft",
+                draft_id,
+                "already_committed",
+                format!("Draft {} is already committed", draft_id),
+            ));
+        }
+        let item = draft
+            .resolved_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Item {}", item_index)))?;
+        let status = decide(&db, item)?;
+        if item.review(status) {
+            item.escalation = db
+                .item_escalation_rule(item)?
+                .map(|rule| models::Escalation::required(&rule));
+            if let (Some(sku), None) = (item.final_sku(), item.final_candidate()) {
+                item.override_schedule = db
+                    .get_catalog_item(sku)?
+                    .and_then(|c| c.controlled_schedule);
+            }
+            Self::recheck_allergies(&db, &draft.patient_id, item)?;
+        }
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
     }
 
-    /// Get a patient by local ID.
-    pub fn get_patient(&self, local_id: String) -> Result<Option<FfiPatient>, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let patient = db.get_patient(&local_id)?;
-        Ok(patient.map(|p| p.into()))
+    /// Check an item's chosen SKU against the patient's allergies as they
+    /// are now, replacing the warnings from when it was staged.
+    fn recheck_allergies(
+        db: &Database,
+        patient_id: &str,
+        item: &mut models::ResolvedItem,
+    ) -> Result<(), FuzzyDrugsError> {
+        let allergies = db
+            .get_patient(patient_id)?
+            .map(|p| p.allergies)
+            .unwrap_or_default();
+        let sku = item.final_sku().unwrap_or(&item.top_candidate.sku);
+        let warnings = match db.get_catalog_item(sku)? {
+            Some(catalog_item) => resolver::allergy_warnings(&catalog_item, &allergies),
+            None => Vec::new(),
+        };
+        item.replace_blocking_warnings(warnings);
+        Ok(())
     }
 
-    /// Search patients by name.
-    pub fn search_patients(
+    /// Drafts can be (re)processed until committed, but not in a way that
+    /// discards review decisions once they are reviewed.
+    fn check_processable(
+        draft: &EncounterDraft,
+        keep_reviews: bool,
+    ) -> Result<(), FuzzyDrugsError> {
+        match draft.status {
+            DraftStatus::Committed => Err(FuzzyDrugsError::conflict(
+                "draft",
+                &draft.draft_id,
+                "already_committed",
+                format!("Draft {} is already committed", draft.draft_id),
+            )),
+            DraftStatus::Reviewed if !keep_reviews => Err(FuzzyDrugsError::conflict(
+                "draft",
+                &draft.draft_id,
+                "already_reviewed",
+                format!("Draft {} is already reviewed", draft.draft_id),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Extract, resolve, and stage a transcript on a stored draft.
+    ///
+    /// Extraction runs without holding the database lock. With
+    /// `keep_reviews`, review decisions carry over to items whose mention
+    /// text is unchanged.
+    fn run_pipeline(
         &self,
-        query: String,
-        limit: u32,
-    ) -> Result<Vec<FfiPatient>, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let patients = db.search_patients(&query, limit as usize)?;
-        Ok(patients.into_iter().map(|p| p.into()).collect())
+        draft_id: &str,
+        transcript: String,
+        keep_reviews: bool,
+        turns: &[models::SpeakerTurn],
+    ) -> Result<FfiProcessedTranscript, FuzzyDrugsError> {
+        {
+            let db = self.lock_db()?;
+            db.limits()
+                .check_transcript(&transcript)
+                .map_err(FuzzyDrugsError::InvalidInput)?;
+            let draft = db
+                .get_draft(draft_id)?
+                .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+            Self::check_processable(&draft, keep_reviews)?;
+        }
+        let extracted = self.extract_cached(&transcript)?;
+        let mut mentions = extracted.drugs;
+        models::assign_speakers(&mut mentions, turns);
+
+        let (draft, unmatched_drugs, unmatched_services, previous_status) = {
+            let db = self.lock_db()?;
+            for mention in &mentions {
+                db.limits()
+                    .check_drug_name(&mention.drug_name)
+                    .map_err(FuzzyDrugsError::InvalidInput)?;
+            }
+            for service in &extracted.services {
+                db.limits()
+                    .check_drug_name(&service.service_name)
+                    .map_err(FuzzyDrugsError::InvalidInput)?;
+            }
+            // Re-read: the draft may have been reviewed during extraction
+            let mut draft = db
+                .get_draft(draft_id)?
+                .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+            Self::check_processable(&draft, keep_reviews)?;
+            let previous_status = draft.status.clone();
+            let patient = db.get_patient(&draft.patient_id)?;
+            let normalizer = self.lock_normalizer()?.clone();
+            let resolver = Resolver::with_normalizer(&db, normalizer)
+                .with_config(db.scoring_config()?)
+                .with_treatment_date(draft.treatment_date());
+            let previous = keep_reviews.then(|| draft.resolved_items.clone());
+            let unmatched_drugs =
+                resolver.stage_transcript(&mut draft, transcript, &mentions, patient.as_ref())?;
+            let unmatched_services = resolver.stage_services(&mut draft, &extracted.services)?;
+            if let Some(previous) = previous {
+                draft.keep_reviews(previous);
+            }
+            draft.interaction_warnings = InteractionChecker::new(&db)?.check_draft(&draft)?;
+            db.update_draft(&draft)?;
+            tracing::info!(
+                draft_id,
+                mentions = mentions.len(),
+                items = draft.resolved_items.len(),
+                unmatched = unmatched_drugs.len(),
+                services = draft.service_items.len(),
+                status = ?draft.status,
+                "Processed transcript"
+            );
+            (draft, unmatched_drugs, unmatched_services, previous_status)
+        };
+        if draft.status != previous_status {
+            self.notify(vec![CoreEvent::draft_status(
+                &draft.draft_id,
+                &draft.status,
+            )]);
+        }
+        Ok(FfiProcessedTranscript {
+            draft: draft.into(),
+            unmatched_drugs,
+            unmatched_services,
+        })
     }
 
-    // =========================================================================
-    // Draft Operations
-    // =========================================================================
+    /// Normalize an FFI patient weight to kg (missing unit = kg).
+    fn patient_weight_kg(
+        normalizer: &Normalizer,
+        weight: Option<f64>,
+        unit: Option<&str>,
+    ) -> Result<Option<f64>, FuzzyDrugsError> {
+        match weight {
+            Some(w) => normalizer
+                .normalize_weight_kg(w, unit)
+                .map(Some)
+                .ok_or_else(|| {
+                    FuzzyDrugsError::InvalidInput(format!(
+                        "Unknown weight unit: {}",
+                        unit.unwrap_or_default()
+                    ))
+                }),
+            None => Ok(None),
+        }
+    }
 
-    /// Create a new encounter draft.
-    pub fn create_draft(&self, patient_id: String) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let draft = EncounterDraft::new(patient_id);
-        db.insert_draft(&draft)?;
-        Ok(draft.into())
+    /// Compliance exporter stamped with the normalizer data and this
+    /// clinic's system ID, signing with the export signing key if set.
+    fn compliance_exporter<'d>(
+        &self,
+        db: &'d Database,
+        normalizer_data: NormalizerDataInfo,
+    ) -> Result<export::ComplianceExporter<'d>, FuzzyDrugsError> {
+        let mut exporter =
+            export::ComplianceExporter::new(db).with_normalizer_data(normalizer_data);
+        if let Some(system_id) = db.core_settings()?.system_id {
+            exporter = exporter.with_system_id(system_id);
+        }
+        Ok(match self.export_signing_key() {
+            Some(key) => exporter.with_signing_key(key),
+            None => exporter,
+        })
     }
 
-    /// Get a draft by ID.
-    pub fn get_draft(&self, draft_id: String) -> Result<Option<FfiEncounterDraft>, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let draft = db.get_draft(&draft_id)?;
-        Ok(draft.map(|d| d.into()))
+    /// Billing exporter, signing with the export signing key if set.
+    fn billing_exporter<'d>(&self, db: &'d Database) -> export::BillingExporter<'d> {
+        let exporter = export::BillingExporter::new(db);
+        match self.export_signing_key() {
+            Some(key) => exporter.with_signing_key(key),
+            None => exporter,
+        }
     }
 
-    /// Get drafts pending review (sorted by lowest confidence first).
-    pub fn get_pending_review_drafts(&self) -> Result<Vec<FfiEncounterDraft>, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let drafts = db.list_pending_review_drafts()?;
-        Ok(drafts.into_iter().map(|d| d.into()).collect())
+    /// Deidentifier keyed with this clinic's pseudonym salt. `free_text`
+    /// is "strip" or "hash".
+    fn deidentifier(
+        &self,
+        db: &Database,
+        free_text: &str,
+    ) -> Result<export::Deidentifier, FuzzyDrugsError> {
+        let free_text = export::FreeTextRedaction::parse(free_text).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unknown free text redaction: {}", free_text))
+        })?;
+        Ok(export::Deidentifier::new(db.pseudonym_salt()?, free_text))
     }
 
-    // =========================================================================
-    // Resolver Operations
-    // =========================================================================
+    /// Move a pending export batch to `status`. Batches already imported or
+    /// void are a `Conflict`.
+    fn set_export_batch_status(
+        &self,
+        batch_id: &str,
+        status: models::ExportBatchStatus,
+    ) -> Result<FfiExportBatch, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let batch = db
+            .get_export_batch(batch_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Export batch {}", batch_id)))?;
+        if batch.status != models::ExportBatchStatus::Pending {
+            return Err(FuzzyDrugsError::conflict(
+                "export_batch",
+                batch_id,
+                batch.status.as_str(),
+                format!(
+                    "Export batch {} is already {}",
+                    batch_id,
+                    batch.status.as_str()
+                ),
+            ));
+        }
+        Ok(db.set_export_batch_status(batch_id, status)?.into())
+    }
 
-    /// Resolve a drug mention to SKU candidates.
-    pub fn resolve_mention(
+    /// The key set by `set_export_signing_key` (poison is ignored).
+    fn export_signing_key(&self) -> Option<SigningKey> {
+        self.signing_key
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Controlled substance log for encounters committed after `start` and
+    /// up to `end`.
+    fn controlled_substance_log(
         &self,
+        start: &str,
+        end: &str,
+        opening_balances: Vec<FfiStockBalance>,
+    ) -> Result<export::ControlledSubstanceLog, FuzzyDrugsError> {
+        let start = parse_since_timestamp(start)?;
+        let end = parse_since_timestamp(end)?;
+        let balances = opening_balances
+            .into_iter()
+            .map(|balance| (balance.sku, balance.quantity))
+            .collect();
+        let db = self.lock_db()?;
+        let exporter =
+            export::ControlledSubstanceLogExporter::new(&db).with_opening_balances(balances);
+        Ok(exporter.export_date_range(&start, &end)?)
+    }
+
+    /// Build a mention from FFI arguments (no transcript offsets).
+    fn ffi_mention(
         drug_name: String,
         dose: Option<f64>,
         unit: Option<String>,
         route: Option<String>,
-        patient_species: Option<String>,
-        patient_weight_kg: Option<f64>,
-    ) -> Result<FfiResolvedItem, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let resolver = Resolver::new(&db);
-
-        let mention = models::DrugMention {
+        species: Option<String>,
+    ) -> models::DrugMention {
+        models::DrugMention {
             raw_text: format!(
                 "{} {} {}",
                 dose.map(|d| d.to_string()).unwrap_or_default(),
@@ -262,306 +1033,9434 @@ impl FuzzyDrugsCore {
             dose,
             unit,
             route,
-            species: patient_species.clone(),
+            species,
             start_offset: 0,
             end_offset: 0,
-        };
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        }
+    }
 
-        let resolved = resolver.resolve(&mention, patient_species.as_deref(), patient_weight_kg)?;
+    /// Validate and commit a reviewed encounter, then mark its draft committed.
+    fn commit_reviewed(
+        db: &Database,
+        draft: Option<&EncounterDraft>,
+        mut reviewed: ReviewedEncounter,
+    ) -> Result<LeafCommit, FuzzyDrugsError> {
+        // Restricted items need an approval valid under the current rule, by
+        // a user who still holds its role. It is committed on the line item,
+        // taken from the draft's item when the caller didn't send it.
+        let mut unescalated = 0;
+        for line_item in reviewed.line_items.iter_mut() {
+            let resolved = draft.and_then(|d| {
+                d.resolved_items
+                    .iter()
+                    .find(|i| i.mention.original.raw_text == line_item.original_mention)
+            });
+            let rule = match db.sku_escalation_rule(&line_item.sku)? {
+                Some(rule) => Some(rule),
+                None => match resolved {
+                    Some(item) => db.item_escalation_rule(item)?,
+                    None => None,
+                },
+            };
+            let Some(rule) = rule else { continue };
+            if line_item.escalation_approval.is_none() {
+                let manual = draft.and_then(|d| {
+                    d.manual_items.iter().find(|m| {
+                        m.sku == line_item.sku && m.original_mention == line_item.original_mention
+                    })
+                });
+                line_item.escalation_approval = match (resolved, manual) {
+                    (Some(item), _) => item.escalation.as_ref().and_then(|e| e.approval.clone()),
+                    (None, Some(manual)) => manual.escalation_approval.clone(),
+                    (None, None) => None,
+                };
+            }
+            let approved = match &line_item.escalation_approval {
+                Some(approval) => Self::holds_escalation_role(db, approval, &rule)?,
+                None => false,
+            };
+            if !approved {
+                unescalated += 1;
+            }
+        }
+        if unescalated > 0 {
+            return Err(FuzzyDrugsError::InvalidInput(format!(
+                "{} restricted item(s) need escalated approval",
+                unescalated
+            )));
+        }
+        // Carry the draft's visit, and the reviewer's disposition and lot
+        // onto items the client didn't set
+        if let Some(draft) = draft {
+            if reviewed.visit_id.is_none() {
+                reviewed.visit_id = draft.visit_id.clone();
+            }
+            for line_item in reviewed.line_items.iter_mut() {
+                let resolved = draft
+                    .resolved_items
+                    .iter()
+                    .find(|i| i.mention.original.raw_text == line_item.original_mention);
+                if line_item.disposition.is_none() {
+                    line_item.disposition = resolved.and_then(|i| i.disposition);
+                }
+                if line_item.lot_number.is_none() {
+                    let lot = match resolved {
+                        Some(item) => Some((&item.lot_number, &item.expiration_date)),
+                        None => draft
+                            .service_items
+                            .iter()
+                            .find(|s| {
+                                s.code == line_item.sku
+                                    && s.mention.raw_text == line_item.original_mention
+                            })
+                            .map(|s| (&s.lot_number, &s.expiration_date)),
+                    };
+                    if let Some((lot_number, expiration_date)) = lot {
+                        line_item.lot_number = lot_number.clone();
+                        line_item.expiration_date = expiration_date.clone();
+                    }
+                }
+            }
+        }
+        // Fill in expiration dates of lots recorded without one
+        for item in reviewed.line_items.iter_mut() {
+            if let (Some(lot_number), None) = (&item.lot_number, &item.expiration_date) {
+                item.expiration_date = db
+                    .get_lot(&item.sku, lot_number)?
+                    .map(|l| l.expiration_date);
+            }
+        }
+        // Tag controlled substances from the catalog for the DEA log, and
+        // work out the stock each item draws down in dispensing units
+        // (prescriptions are filled elsewhere)
+        let dispensing = resolver::DispensingCalculator::new();
+        let mut usage = Vec::new();
+        // Withdrawal periods count from the day the encounter was reviewed
+        let patient = db.get_patient(&reviewed.patient_id)?;
+        let species = patient.as_ref().map(|p| p.species.as_str());
+        let reviewed_on = chrono::DateTime::parse_from_rfc3339(&reviewed.reviewed_at)
+            .map(|t| t.date_naive())
+            .unwrap_or_else(|_| chrono::Utc::now().date_naive());
+        for item in reviewed.line_items.iter_mut() {
+            let Some(catalog_item) = db.get_catalog_item(&item.sku)? else {
+                continue;
+            };
+            if catalog_item.controlled_schedule.is_some() {
+                item.controlled_schedule = catalog_item.controlled_schedule;
+            }
+            item.withdrawal = species
+                .and_then(|species| catalog_item.withdrawal_time(species))
+                .map(|time| {
+                    let last_dose = models::last_dose_date(
+                        reviewed_on,
+                        item.schedule.as_ref(),
+                        item.disposition,
+                    );
+                    models::Withdrawal::new(time, last_dose)
+                });
+            if item.disposition != Some(models::DispositionType::Prescribed) {
+                let quantity = dispensing
+                    .dispense_line(
+                        &catalog_item,
+                        item.quantity,
+                        &item.unit,
+                        item.schedule.as_ref(),
+                    )
+                    .map_or(item.quantity, |q| q.per_dose);
+                usage.push((item.sku.clone(), quantity));
+            }
+        }
+        // Controlled items from a draft need explicit confirmation for the
+        // SKU being committed, whose schedule was just taken from the catalog
+        if let Some(draft) = draft {
+            let mut unconfirmed = 0;
+            for line_item in reviewed.line_items.iter_mut() {
+                if line_item.controlled_schedule.is_none() {
+                    continue;
+                }
+                if line_item.controlled_confirmed_by.is_none() {
+                    let resolved = draft.resolved_items.iter().find(|i| {
+                        i.mention.original.raw_text == line_item.original_mention
+                            && i.final_sku() == Some(line_item.sku.as_str())
+                    });
+                    let manual = draft.manual_items.iter().find(|m| {
+                        m.sku == line_item.sku && m.original_mention == line_item.original_mention
+                    });
+                    line_item.controlled_confirmed_by = match (resolved, manual) {
+                        (Some(item), _) => item.controlled_confirmed_by.clone(),
+                        (None, Some(manual)) => manual.controlled_confirmed_by.clone(),
+                        (None, None) => None,
+                    };
+                }
+                if line_item.controlled_confirmed_by.is_none() {
+                    unconfirmed += 1;
+                }
+            }
+            if unconfirmed > 0 {
+                return Err(FuzzyDrugsError::InvalidInput(format!(
+                    "{} controlled substance item(s) need explicit confirmation",
+                    unconfirmed
+                )));
+            }
+        }
+        // Allergies are checked again against the SKU being committed and
+        // the patient's allergies as they are now: an override or manual
+        // item may not be one of the SKUs checked at staging, and allergies
+        // may have been recorded since. Only an override recorded on the
+        // draft counts.
+        if let Some(draft) = draft {
+            let allergies = patient.as_ref().map_or(&[][..], |p| &p.allergies[..]);
+            let mut allergic = 0;
+            for line_item in reviewed.line_items.iter_mut() {
+                let Some(catalog_item) = db.get_catalog_item(&line_item.sku)? else {
+                    continue;
+                };
+                if resolver::allergy_warnings(&catalog_item, allergies).is_empty() {
+                    continue;
+                }
+                let resolved = draft.resolved_items.iter().find(|i| {
+                    i.mention.original.raw_text == line_item.original_mention
+                        && i.final_sku() == Some(line_item.sku.as_str())
+                });
+                let manual = draft.manual_items.iter().find(|m| {
+                    m.sku == line_item.sku && m.original_mention == line_item.original_mention
+                });
+                line_item.allergy_overridden_by = match (resolved, manual) {
+                    (Some(item), _) => item.allergy_overridden_by.clone(),
+                    (None, Some(manual)) => manual.allergy_overridden_by.clone(),
+                    (None, None) => None,
+                };
+                if line_item.allergy_overridden_by.is_none() {
+                    allergic += 1;
+                }
+            }
+            if allergic > 0 {
+                return Err(FuzzyDrugsError::InvalidInput(format!(
+                    "{} item(s) match a recorded patient allergy and need an override",
+                    allergic
+                )));
+            }
+        }
 
-        Ok(resolved.into())
+        // The leaf, draft status, outbox entry, stock draw-down, medication
+        // list entries, and vaccination records land together or not at all
+        let tx = db
+            .conn()
+            .unchecked_transaction()
+            .map_err(db::DbError::from)?;
+        let tree = MerkleTree::new(db);
+        let commit = tree.commit_encounter(&reviewed)?;
+        if draft.is_some() {
+            db.mark_draft_committed(&reviewed.draft_id)?;
+        }
+        db.enqueue_outbox(
+            models::OutboxKind::EncounterPush,
+            &commit.leaf_hash,
+            chrono::Utc::now(),
+        )?;
+        db.record_stock_usage(&usage, &commit.leaf_hash)?;
+        db.record_encounter_medications(&reviewed)?;
+        db.record_encounter_vaccinations(&reviewed)?;
+        tx.commit().map_err(db::DbError::from)?;
+        Ok(commit)
     }
 
-    // =========================================================================
-    // Merkle Tree Operations
-    // =========================================================================
+    /// Whether `approval` is valid under `rule` and its approver holds the
+    /// role in the users table.
+    fn holds_escalation_role(
+        db: &Database,
+        approval: &models::EscalationApproval,
+        rule: &models::EscalationRule,
+    ) -> Result<bool, FuzzyDrugsError> {
+        if !approval.is_valid_under(rule) {
+            return Ok(false);
+        }
+        Ok(db
+            .find_reviewer(&approval.approved_by)?
+            .is_some_and(|user| user.has_role(&approval.role)))
+    }
 
-    /// Commit a reviewed encounter to the Merkle tree.
-    pub fn commit_encounter(
-        &self,
-        encounter: FfiReviewedEncounter,
-    ) -> Result<FfiLeafCommit, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let tree = MerkleTree::new(&db);
-        let reviewed: ReviewedEncounter = encounter.into();
-        let commit = tree.commit_encounter(&reviewed)?;
-        Ok(commit.into())
+    /// An approval under `rule` by `approver`, who must be a user holding
+    /// `role`, with a reason note.
+    fn escalation_approval(
+        db: &Database,
+        rule: models::EscalationRule,
+        approver: &str,
+        role: &str,
+        reason: &str,
+    ) -> Result<models::EscalationApproval, FuzzyDrugsError> {
+        let user = db
+            .find_reviewer(approver)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("User {}", approver)))?;
+        if !rule.permits(role) || !user.has_role(role) {
+            return Err(FuzzyDrugsError::PermissionDenied {
+                message: format!(
+                    "Approving {} requires the {} role",
+                    rule.ingredient, rule.required_role
+                ),
+                required_role: rule.required_role,
+            });
+        }
+        if reason.trim().is_empty() {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "A reason note is required for escalated items".into(),
+            ));
+        }
+        Ok(models::EscalationApproval {
+            approved_by: user.user_id,
+            role: role.trim().to_string(),
+            reason: reason.trim().to_string(),
+            approved_at: chrono::Utc::now().to_rfc3339(),
+        })
     }
 
-    /// Get current tree statistics.
-    pub fn get_tree_stats(&self) -> Result<FfiTreeStats, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let tree = MerkleTree::new(&db);
-        let stats = tree.get_stats()?;
-        Ok(stats.into())
+    /// An item's stock level after an adjustment.
+    fn stock_level(db: &Database, sku: &str) -> Result<FfiStockLevel, FuzzyDrugsError> {
+        let level = db
+            .get_stock_level(sku)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Catalog item {}", sku)))?;
+        Ok(level.into())
     }
 
-    /// Check if there are unsynced changes.
-    pub fn has_unsynced_changes(&self) -> Result<bool, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let sync_manager = merkle::SyncManager::new(&db);
-        Ok(sync_manager.has_unsynced_changes()?)
+    /// The expiration date to record with `lot_number` of `sku`: the one
+    /// given, else the inventory lot's (None if neither). A malformed or
+    /// past date is `InvalidInput`.
+    fn lot_expiration(
+        db: &Database,
+        sku: &str,
+        lot_number: &str,
+        expiration_date: Option<String>,
+    ) -> Result<Option<String>, FuzzyDrugsError> {
+        let expiration_date = match expiration_date {
+            Some(date) => Some(date.trim().to_string()),
+            None => db.get_lot(sku, lot_number)?.map(|l| l.expiration_date),
+        };
+        let Some(expiration_date) = expiration_date else {
+            return Ok(None);
+        };
+        let lot = models::InventoryLot::new(sku.into(), lot_number.into(), expiration_date);
+        lot.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        if lot.is_expired(chrono::Utc::now().date_naive()) {
+            return Err(FuzzyDrugsError::InvalidInput(format!(
+                "Lot {} expired on {}",
+                lot_number, lot.expiration_date
+            )));
+        }
+        Ok(Some(lot.expiration_date))
     }
+}
 
+#[uniffi::export]
+impl FuzzyDrugsCore {
     // =========================================================================
-    // Export Operations
+    // Catalog Operations
     // =========================================================================
 
-    /// Export billing data as JSON.
-    pub fn export_billing_json(&self) -> Result<String, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let exporter = export::BillingExporter::new(&db);
-        let batch = exporter.export_all()?;
-        Ok(batch.to_json()?)
+    /// Add or update a catalog item.
+    ///
+    /// This is a local change: new items are local-only, and the item goes
+    /// out with the next `create_catalog_push_delta`.
+    pub fn upsert_catalog_item(&self, item: FfiCatalogItem) -> Result<(), FuzzyDrugsError> {
+        if let Some(schedule) = &item.controlled_schedule {
+            if ControlledSchedule::parse(schedule).is_none() {
+                return Err(FuzzyDrugsError::InvalidInput(format!(
+                    "Unknown controlled schedule: {}",
+                    schedule
+                )));
+            }
+        }
+        let mut catalog_item: CatalogItem = item.into();
+        catalog_item
+            .validate()
+            .map_err(FuzzyDrugsError::InvalidInput)?;
+        {
+            let db = self.lock_db()?;
+            // Edits keep the PIMS link
+            match db.get_catalog_item(&catalog_item.sku)? {
+                Some(existing) => {
+                    catalog_item.server_id = existing.server_id;
+                    catalog_item.last_synced = existing.last_synced;
+                    catalog_item.origin = existing.origin;
+                }
+                None => catalog_item.origin = models::CatalogOrigin::Local,
+            }
+            catalog_item.dirty = true;
+            db.upsert_catalog_item(&catalog_item)?;
+        }
+        self.notify(vec![CoreEvent::CatalogUpdated {
+            skus: vec![catalog_item.sku],
+        }]);
+        Ok(())
     }
 
-    /// Export billing data as CSV.
-    pub fn export_billing_csv(&self) -> Result<String, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let exporter = export::BillingExporter::new(&db);
-        let batch = exporter.export_all()?;
-        Ok(batch.to_csv())
+    /// Get a catalog item by SKU.
+    pub fn get_catalog_item(&self, sku: String) -> Result<Option<FfiCatalogItem>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let item = db.get_catalog_item(&sku)?;
+        Ok(item.map(|i| i.into()))
     }
 
-    /// Export compliance data as JSON.
-    pub fn export_compliance_json(&self) -> Result<String, FuzzyDrugsError> {
-        let db = self.db.lock()?;
-        let exporter = export::ComplianceExporter::new(&db);
-        let batch = exporter.export_all()?;
-        Ok(batch.to_json()?)
+    /// Search catalog by name/alias.
+    pub fn search_catalog(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<FfiCatalogItem>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let items = db.search_catalog(&query, limit as usize)?;
+        Ok(items.into_iter().map(|i| i.into()).collect())
     }
-}
-
-// =========================================================================
-// FFI Types
-// =========================================================================
 
-/// FFI-safe catalog item.
-#[derive(Debug, Clone, uniffi::Record)]
-pub struct FfiCatalogItem {
-    pub sku: String,
-    pub name: String,
-    pub aliases: Vec<String>,
-    pub concentration: Option<String>,
-    pub package_size: Option<String>,
-    pub species: Vec<String>,
-    pub routes: Vec<String>,
-    pub active: bool,
-}
+    /// Type-ahead suggestions for the manual-add picker (sku/name/strength only).
+    ///
+    /// Cheaper than `search_catalog`; meant to be called on every keystroke.
+    pub fn suggest_catalog(
+        &self,
+        prefix: String,
+        limit: u32,
+    ) -> Result<Vec<FfiCatalogSuggestion>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let suggestions = db.suggest_catalog(&prefix, limit as usize)?;
+        Ok(suggestions.into_iter().map(|s| s.into()).collect())
+    }
 
-impl From<CatalogItem> for FfiCatalogItem {
-    fn from(item: CatalogItem) -> Self {
-        Self {
-            sku: item.sku,
-            name: item.name,
-            aliases: item.aliases,
-            concentration: item.concentration,
-            package_size: item.package_size,
-            species: item.species,
-            routes: item.routes,
-            active: item.active,
+    /// List catalog items by name, one page at a time (`page` is 0-based).
+    pub fn list_catalog_items(
+        &self,
+        active_only: bool,
+        page: u32,
+        page_size: u32,
+    ) -> Result<FfiCatalogPage, FuzzyDrugsError> {
+        let limit = page_size as usize;
+        if !(1..=db::MAX_CATALOG_PAGE_SIZE).contains(&limit) {
+            return Err(FuzzyDrugsError::InvalidInput(format!(
+                "Page size must be between 1 and {}",
+                db::MAX_CATALOG_PAGE_SIZE
+            )));
         }
+        let db = self.lock_db()?;
+        let (items, total) =
+            db.list_catalog_items_page(active_only, limit, page as usize * limit)?;
+        Ok(FfiCatalogPage {
+            items: items.into_iter().map(|i| i.into()).collect(),
+            page,
+            page_size,
+            total_count: total as u32,
+        })
     }
-}
 
-impl From<FfiCatalogItem> for CatalogItem {
-    fn from(item: FfiCatalogItem) -> Self {
-        CatalogItem {
-            sku: item.sku,
-            name: item.name,
-            aliases: item.aliases,
-            concentration: item.concentration,
-            package_size: item.package_size,
-            species: item.species,
-            routes: item.routes,
-            dose_range: None,
-            active: item.active,
-            server_id: None,
-            last_synced: None,
+    /// Deactivate a catalog item so it no longer matches mentions or
+    /// suggestions. Committed encounters referencing it are unaffected.
+    /// Pushed to the PIMS like any local change.
+    pub fn deactivate_catalog_item(&self, sku: String) -> Result<(), FuzzyDrugsError> {
+        {
+            let db = self.lock_db()?;
+            if !db.deactivate_catalog_item(&sku)? {
+                return Err(FuzzyDrugsError::NotFound(format!("Catalog item {}", sku)));
+            }
+            db.mark_catalog_item_dirty(&sku)?;
         }
+        self.notify(vec![CoreEvent::CatalogUpdated { skus: vec![sku] }]);
+        Ok(())
     }
-}
 
-/// FFI-safe patient.
-#[derive(Debug, Clone, uniffi::Record)]
-pub struct FfiPatient {
-    pub local_id: String,
-    pub server_id: Option<String>,
-    pub name: String,
-    pub species: String,
-    pub breed: Option<String>,
-    pub weight_kg: Option<f64>,
-}
+    /// Delete a catalog item. Items billed on committed encounters cannot be
+    /// deleted; deactivate them instead.
+    pub fn delete_catalog_item(&self, sku: String) -> Result<(), FuzzyDrugsError> {
+        {
+            let db = self.lock_db()?;
+            if db.get_catalog_item(&sku)?.is_none() {
+                return Err(FuzzyDrugsError::NotFound(format!("Catalog item {}", sku)));
+            }
+            db.delete_catalog_item(&sku)?;
+        }
+        self.notify(vec![CoreEvent::CatalogUpdated { skus: vec![sku] }]);
+        Ok(())
+    }
 
-impl From<Patient> for FfiPatient {
-    fn from(patient: Patient) -> Self {
-        Self {
-            local_id: patient.local_id,
-            server_id: patient.server_id,
-            name: patient.name,
-            species: patient.species,
-            breed: patient.breed,
-            weight_kg: patient.weight_kg,
+    /// Add or update many catalog items in one transaction, reporting
+    /// progress after each. If any item fails or `cancel` fires, nothing is
+    /// imported. Returns the number of items imported.
+    ///
+    /// Imports are treated as PIMS data and are not pushed back.
+    pub fn import_catalog_items(
+        &self,
+        items: Vec<FfiCatalogItem>,
+        progress: Option<Arc<dyn FfiProgressListener>>,
+        cancel: Option<Arc<FfiCancellationToken>>,
+    ) -> Result<u32, FuzzyDrugsError> {
+        for item in &items {
+            if let Some(schedule) = &item.controlled_schedule {
+                if ControlledSchedule::parse(schedule).is_none() {
+                    return Err(FuzzyDrugsError::InvalidInput(format!(
+                        "Unknown controlled schedule for {}: {}",
+                        item.sku, schedule
+                    )));
+                }
+            }
+        }
+        let items: Vec<CatalogItem> = items.into_iter().map(Into::into).collect();
+        for item in &items {
+            item.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        }
+        let progress = ForeignProgress {
+            listener: progress,
+            cancel,
+        };
+        let imported = self.lock_db()?.import_catalog_items(&items, &progress)?;
+        if imported > 0 {
+            self.notify(vec![CoreEvent::CatalogUpdated {
+                skus: items.into_iter().map(|item| item.sku).collect(),
+            }]);
         }
+        Ok(imported as u32)
     }
-}
 
-/// FFI-safe encounter draft.
-#[derive(Debug, Clone, uniffi::Record)]
-pub struct FfiEncounterDraft {
-    pub draft_id: String,
-    pub patient_id: String,
-    pub transcript: String,
-    pub status: String,
-    pub pending_review_count: u32,
-    pub lowest_confidence: Option<f64>,
-}
+    /// Add or update a services catalog entry (procedure, vaccine, or
+    /// diagnostic) that transcripts' service mentions are matched against.
+    pub fn upsert_service_item(&self, item: FfiServiceItem) -> Result<(), FuzzyDrugsError> {
+        let item = models::ServiceItem::try_from(item)?;
+        self.lock_db()?.upsert_service_item(&item)?;
+        Ok(())
+    }
 
-impl From<EncounterDraft> for FfiEncounterDraft {
-    fn from(draft: EncounterDraft) -> Self {
-        Self {
-            draft_id: draft.draft_id.clone(),
-            patient_id: draft.patient_id.clone(),
-            transcript: draft.transcript.clone(),
-            status: format!("{:?}", draft.status),
-            pending_review_count: draft.pending_review_count() as u32,
-            lowest_confidence: draft.lowest_confidence(),
+    /// List services catalog entries by name.
+    pub fn list_service_items(
+        &self,
+        active_only: bool,
+    ) -> Result<Vec<FfiServiceItem>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let items = db.list_service_items(active_only)?;
+        Ok(items.into_iter().map(|i| i.into()).collect())
+    }
+
+    /// Delete a services catalog entry. Drafts already matched to it keep
+    /// their service items.
+    pub fn delete_service_item(&self, code: String) -> Result<(), FuzzyDrugsError> {
+        if !self.lock_db()?.delete_service_item(&code)? {
+            return Err(FuzzyDrugsError::NotFound(format!("Service {}", code)));
         }
+        Ok(())
     }
-}
 
-/// FFI-safe resolved item.
-#[derive(Debug, Clone, uniffi::Record)]
-pub struct FfiResolvedItem {
-    pub normalized_name: String,
-    pub normalized_dose: Option<f64>,
-    pub normalized_unit: Option<String>,
-    pub normalized_route: Option<String>,
-    pub top_sku: String,
-    pub top_name: String,
-    pub top_confidence: f64,
-    pub alternatives: Vec<FfiScoredCandidate>,
-}
+    // =========================================================================
+    // Inventory Lots
+    // =========================================================================
 
-impl From<models::ResolvedItem> for FfiResolvedItem {
-    fn from(item: models::ResolvedItem) -> Self {
-        Self {
-            normalized_name: item.mention.normalized_name,
-            normalized_dose: item.mention.normalized_dose,
-            normalized_unit: item.mention.normalized_unit,
-            normalized_route: item.mention.normalized_route,
-            top_sku: item.top_candidate.sku,
-            top_name: item.top_candidate.name,
-            top_confidence: item.top_candidate.confidence,
-            alternatives: item.alternatives.into_iter().map(|c| c.into()).collect(),
-        }
+    /// Add a lot of a catalog item (or vaccine service code), or update its
+    /// expiration date (YYYY-MM-DD).
+    pub fn upsert_inventory_lot(&self, lot: FfiInventoryLot) -> Result<(), FuzzyDrugsError> {
+        let lot = models::InventoryLot::from(lot);
+        lot.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        self.lock_db()?.upsert_lot(&lot)?;
+        Ok(())
     }
-}
 
-/// FFI-safe scored candidate.
-#[derive(Debug, Clone, uniffi::Record)]
-pub struct FfiScoredCandidate {
-    pub sku: String,
-    pub name: String,
-    pub confidence: f64,
-}
+    /// List an item's lots, soonest to expire first. Expired lots are left
+    /// out unless `include_expired`.
+    pub fn list_inventory_lots(
+        &self,
+        sku: String,
+        include_expired: bool,
+    ) -> Result<Vec<FfiInventoryLot>, FuzzyDrugsError> {
+        let today = chrono::Utc::now().date_naive();
+        let lots = self.lock_db()?.list_lots(&sku)?;
+        Ok(lots
+            .into_iter()
+            .filter(|lot| include_expired || !lot.is_expired(today))
+            .map(|lot| lot.into())
+            .collect())
+    }
 
-impl From<models::ScoredCandidate> for FfiScoredCandidate {
-    fn from(candidate: models::ScoredCandidate) -> Self {
-        Self {
-            sku: candidate.sku,
-            name: candidate.name,
-            confidence: candidate.confidence,
+    /// Delete a lot. Encounters that recorded it keep the lot number.
+    pub fn delete_inventory_lot(
+        &self,
+        sku: String,
+        lot_number: String,
+    ) -> Result<(), FuzzyDrugsError> {
+        if !self.lock_db()?.delete_lot(&sku, &lot_number)? {
+            return Err(FuzzyDrugsError::NotFound(format!(
+                "Lot {} of {}",
+                lot_number, sku
+            )));
         }
+        Ok(())
     }
-}
 
-/// FFI-safe reviewed encounter.
-#[derive(Debug, Clone, uniffi::Record)]
-pub struct FfiReviewedEncounter {
-    pub draft_id: String,
-    pub patient_id: String,
-    pub patient_server_id: Option<String>,
-    pub transcript: String,
-    pub line_items: Vec<FfiLineItem>,
-    pub reviewed_by: String,
-    pub notes: Option<String>,
-}
+    // =========================================================================
+    // Inventory Stock
+    // =========================================================================
 
-impl From<FfiReviewedEncounter> for ReviewedEncounter {
-    fn from(enc: FfiReviewedEncounter) -> Self {
-        ReviewedEncounter {
-            draft_id: enc.draft_id,
-            patient_id: enc.patient_id,
-            patient_server_id: enc.patient_server_id,
-            transcript: enc.transcript,
-            line_items: enc.line_items.into_iter().map(|i| i.into()).collect(),
-            reviewed_by: enc.reviewed_by,
-            reviewed_at: chrono::Utc::now().to_rfc3339(),
-            notes: enc.notes,
+    /// An item's quantity on hand (None until stock is received or counted).
+    pub fn get_stock_level(&self, sku: String) -> Result<Option<FfiStockLevel>, FuzzyDrugsError> {
+        let level = self.lock_db()?.get_stock_level(&sku)?;
+        Ok(level.map(|l| l.into()))
+    }
+
+    /// Set or clear the quantity at or below which an item is low on stock.
+    pub fn set_reorder_point(
+        &self,
+        sku: String,
+        reorder_point: Option<f64>,
+    ) -> Result<(), FuzzyDrugsError> {
+        if reorder_point.is_some_and(|p| !p.is_finite() || p < 0.0) {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "Reorder point must be zero or more".into(),
+            ));
+        }
+        if !self.lock_db()?.set_reorder_point(&sku, reorder_point)? {
+            return Err(FuzzyDrugsError::NotFound(format!("Catalog item {}", sku)));
         }
+        Ok(())
     }
-}
 
-/// FFI-safe line item.
-#[derive(Debug, Clone, uniffi::Record)]
-pub struct FfiLineItem {
-    pub sku: String,
-    pub name: String,
-    pub quantity: f64,
-    pub unit: String,
-    pub route: Option<String>,
-    pub original_mention: String,
-}
+    /// Change an item's quantity on hand by `delta` (in dispensing units)
+    /// for a `reason`: "received", "waste", "expired", "returned", or
+    /// "count". Usage on encounters is drawn down by the commit itself.
+    pub fn adjust_stock(
+        &self,
+        sku: String,
+        delta: f64,
+        reason: String,
+        note: String,
+    ) -> Result<FfiStockLevel, FuzzyDrugsError> {
+        let reason = models::StockAdjustmentReason::parse(&reason)
+            .filter(|r| *r != models::StockAdjustmentReason::Encounter)
+            .ok_or_else(|| {
+                FuzzyDrugsError::InvalidInput(format!("Unknown adjustment reason: {}", reason))
+            })?;
+        if !delta.is_finite() {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "Stock delta must be finite".into(),
+            ));
+        }
+        let db = self.lock_db()?;
+        db.adjust_stock(&sku, delta, reason, note.trim())?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Catalog item {}", sku)))?;
+        Self::stock_level(&db, &sku)
+    }
 
-impl From<FfiLineItem> for EncounterLineItem {
-    fn from(item: FfiLineItem) -> Self {
-        EncounterLineItem {
-            sku: item.sku,
-            name: item.name,
-            quantity: item.quantity,
-            unit: item.unit,
-            route: item.route,
-            original_mention: item.original_mention,
-            resolution_method: ResolutionMethod::SystemApproved { confidence: 1.0 },
+    /// Set an item's quantity on hand to a physical count. The difference
+    /// is recorded as a "count" adjustment.
+    pub fn record_stock_count(
+        &self,
+        sku: String,
+        counted: f64,
+        note: String,
+    ) -> Result<FfiStockLevel, FuzzyDrugsError> {
+        if !counted.is_finite() || counted < 0.0 {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "Counted stock must be zero or more".into(),
+            ));
         }
+        let db = self.lock_db()?;
+        db.record_stock_count(&sku, counted, note.trim())?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Catalog item {}", sku)))?;
+        Self::stock_level(&db, &sku)
     }
-}
 
-/// FFI-safe leaf commit result.
-#[derive(Debug, Clone, uniffi::Record)]
-pub struct FfiLeafCommit {
-    pub leaf_hash: String,
-    pub root_hash: String,
-    pub tree_height: u32,
-    pub leaf_count: u32,
-}
+    /// Active items at or below their reorder point, furthest below first.
+    pub fn list_low_stock_items(&self) -> Result<Vec<FfiStockLevel>, FuzzyDrugsError> {
+        let levels = self.lock_db()?.list_low_stock()?;
+        Ok(levels.into_iter().map(|l| l.into()).collect())
+    }
 
-impl From<LeafCommit> for FfiLeafCommit {
-    fn from(commit: LeafCommit) -> Self {
-        Self {
-            leaf_hash: commit.leaf_hash,
-            root_hash: commit.root_hash,
-            tree_height: commit.tree_height,
-            leaf_count: commit.leaf_count,
-        }
+    /// An item's most recent stock adjustments, newest first.
+    pub fn list_stock_adjustments(
+        &self,
+        sku: String,
+        limit: u32,
+    ) -> Result<Vec<FfiStockAdjustment>, FuzzyDrugsError> {
+        let adjustments = self
+            .lock_db()?
+            .list_stock_adjustments(&sku, limit as usize)?;
+        Ok(adjustments.into_iter().map(|a| a.into()).collect())
     }
-}
 
-/// FFI-safe tree statistics.
-#[derive(Debug, Clone, uniffi::Record)]
-pub struct FfiTreeStats {
-    pub root_hash: Option<String>,
-    pub height: u32,
-    pub leaf_count: u32,
-}
+    // =========================================================================
+    // Users
+    // =========================================================================
 
-impl From<TreeStats> for FfiTreeStats {
-    fn from(stats: TreeStats) -> Self {
-        Self {
-            root_hash: stats.root_hash,
-            height: stats.height,
-            leaf_count: stats.leaf_count,
+    /// Add or update a user. Encounters whose `reviewed_by` is the user's
+    /// ID (or name) are stamped with their license and DEA numbers when
+    /// committed. A malformed DEA number is `InvalidInput`.
+    pub fn upsert_user(&self, user: FfiUser) -> Result<(), FuzzyDrugsError> {
+        let user = models::User::from(user);
+        user.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        self.lock_db()?.upsert_user(&user)?;
+        Ok(())
+    }
+
+    /// List users by name.
+    pub fn list_users(&self) -> Result<Vec<FfiUser>, FuzzyDrugsError> {
+        let users = self.lock_db()?.list_users()?;
+        Ok(users.into_iter().map(|u| u.into()).collect())
+    }
+
+    /// Delete a user. Encounters already committed keep the credentials
+    /// they were stamped with.
+    pub fn delete_user(&self, user_id: String) -> Result<(), FuzzyDrugsError> {
+        if !self.lock_db()?.delete_user(&user_id)? {
+            return Err(FuzzyDrugsError::NotFound(format!("User {}", user_id)));
         }
+        Ok(())
+    }
+
+    // =========================================================================
+    // Patient Operations
+    // =========================================================================
+
+    /// Create a new patient.
+    pub fn create_patient(
+        &self,
+        name: String,
+        species: String,
+    ) -> Result<FfiPatient, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let patient = Patient::new(name, species);
+        db.insert_patient(&patient)?;
+        db.enqueue_outbox(
+            models::OutboxKind::PatientUpsert,
+            &patient.local_id,
+            chrono::Utc::now(),
+        )?;
+        Ok(patient.into())
+    }
+
+    /// Get a patient by local ID.
+    pub fn get_patient(&self, local_id: String) -> Result<Option<FfiPatient>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let patient = db.get_patient(&local_id)?;
+        Ok(patient.map(|p| p.into()))
+    }
+
+    /// Set a patient's weight, converting from the given unit (kg if omitted).
+    pub fn set_patient_weight(
+        &self,
+        local_id: String,
+        weight: f64,
+        weight_unit: Option<String>,
+    ) -> Result<FfiPatient, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut patient = db
+            .get_patient(&local_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Patient {}", local_id)))?;
+        let unit = match weight_unit.as_deref() {
+            Some(u) => WeightUnit::parse(u).ok_or_else(|| {
+                FuzzyDrugsError::InvalidInput(format!("Unknown weight unit: {}", u))
+            })?,
+            None => WeightUnit::Kilograms,
+        };
+        patient.set_weight(weight, unit);
+        db.update_patient(&patient)?;
+        db.enqueue_outbox(
+            models::OutboxKind::PatientUpsert,
+            &patient.local_id,
+            chrono::Utc::now(),
+        )?;
+        Ok(patient.into())
+    }
+
+    /// Search patients by name.
+    pub fn search_patients(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<FfiPatient>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let patients = db.search_patients(&query, limit as usize)?;
+        Ok(patients.into_iter().map(|p| p.into()).collect())
+    }
+
+    /// Replace a patient's recorded allergies. Drafts staged afterwards get
+    /// a blocking warning on any item with a matching ingredient.
+    pub fn set_patient_allergies(
+        &self,
+        local_id: String,
+        allergies: Vec<FfiPatientAllergy>,
+    ) -> Result<FfiPatient, FuzzyDrugsError> {
+        let allergies: Vec<models::PatientAllergy> =
+            allergies.into_iter().map(|a| a.into()).collect();
+        for allergy in &allergies {
+            allergy.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        }
+        let db = self.lock_db()?;
+        let mut patient = db
+            .get_patient(&local_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Patient {}", local_id)))?;
+        patient.allergies = allergies;
+        db.update_patient(&patient)?;
+        Ok(patient.into())
+    }
+
+    // =========================================================================
+    // Patient Medications
+    // =========================================================================
+
+    /// Add a medication to a patient's list by hand (one they're on at home
+    /// or from another clinic). Dates are YYYY-MM-DD; leave `end_date` out
+    /// for an ongoing medication. Drugs on committed encounters are added
+    /// automatically.
+    pub fn add_patient_medication(
+        &self,
+        patient_id: String,
+        name: String,
+        sku: Option<String>,
+        start_date: String,
+        end_date: Option<String>,
+        notes: Option<String>,
+    ) -> Result<FfiPatientMedication, FuzzyDrugsError> {
+        let mut medication = models::PatientMedication::new(
+            patient_id,
+            name.trim().to_string(),
+            start_date.trim().to_string(),
+        );
+        medication.sku = sku.filter(|s| !s.trim().is_empty());
+        medication.end_date = end_date.map(|d| d.trim().to_string());
+        medication.notes = notes.filter(|n| !n.trim().is_empty());
+        medication
+            .validate()
+            .map_err(FuzzyDrugsError::InvalidInput)?;
+        let db = self.lock_db()?;
+        if db.get_patient(&medication.patient_id)?.is_none() {
+            return Err(FuzzyDrugsError::NotFound(format!(
+                "Patient {}",
+                medication.patient_id
+            )));
+        }
+        medication.id = db.add_patient_medication(&medication)?;
+        Ok(medication.into())
+    }
+
+    /// A patient's medications active on `date` (YYYY-MM-DD; today if
+    /// `None`), most recently started first.
+    pub fn list_active_medications(
+        &self,
+        patient_id: String,
+        date: Option<String>,
+    ) -> Result<Vec<FfiPatientMedication>, FuzzyDrugsError> {
+        let date = match date {
+            Some(date) => models::parse_expiration_date(&date)
+                .map_err(|_| FuzzyDrugsError::InvalidInput(format!("Invalid date: {}", date)))?,
+            None => chrono::Utc::now().date_naive(),
+        };
+        let date = date.format("%Y-%m-%d").to_string();
+        let medications = self
+            .lock_db()?
+            .list_active_medications(&patient_id, &date)?;
+        Ok(medications.into_iter().map(|m| m.into()).collect())
+    }
+
+    /// A patient's whole medication history, most recently started first.
+    pub fn list_patient_medications(
+        &self,
+        patient_id: String,
+    ) -> Result<Vec<FfiPatientMedication>, FuzzyDrugsError> {
+        let medications = self.lock_db()?.list_patient_medications(&patient_id)?;
+        Ok(medications.into_iter().map(|m| m.into()).collect())
+    }
+
+    /// Set the last day a patient is on a medication (YYYY-MM-DD), e.g. when
+    /// it's stopped early; `None` makes it ongoing.
+    pub fn set_medication_end_date(
+        &self,
+        id: i64,
+        end_date: Option<String>,
+    ) -> Result<FfiPatientMedication, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut medication = db
+            .get_patient_medication(id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Medication {}", id)))?;
+        medication.end_date = end_date.map(|d| d.trim().to_string());
+        medication
+            .validate()
+            .map_err(FuzzyDrugsError::InvalidInput)?;
+        db.set_medication_end_date(id, medication.end_date.as_deref())?;
+        Ok(medication.into())
+    }
+
+    /// Delete a medication entered by mistake.
+    pub fn delete_patient_medication(&self, id: i64) -> Result<(), FuzzyDrugsError> {
+        if !self.lock_db()?.delete_patient_medication(id)? {
+            return Err(FuzzyDrugsError::NotFound(format!("Medication {}", id)));
+        }
+        Ok(())
+    }
+
+    // =========================================================================
+    // Vaccinations
+    // =========================================================================
+
+    /// Record a vaccine dose given elsewhere (or before the app). `code` is
+    /// a vaccine in the services catalog, which supplies the antigen and the
+    /// next-due date; doses on committed encounters are recorded
+    /// automatically.
+    pub fn record_vaccination(
+        &self,
+        patient_id: String,
+        code: String,
+        administered_on: String,
+        lot_number: Option<String>,
+        administered_by: Option<String>,
+    ) -> Result<FfiVaccination, FuzzyDrugsError> {
+        let date = models::parse_expiration_date(&administered_on).map_err(|_| {
+            FuzzyDrugsError::InvalidInput(format!("Invalid date: {}", administered_on))
+        })?;
+        let db = self.lock_db()?;
+        if db.get_patient(&patient_id)?.is_none() {
+            return Err(FuzzyDrugsError::NotFound(format!("Patient {}", patient_id)));
+        }
+        let service = db
+            .get_service_item(&code)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Service {}", code)))?;
+        if service.kind != models::ServiceKind::Vaccine {
+            return Err(FuzzyDrugsError::InvalidInput(format!(
+                "{} is not a vaccine",
+                code
+            )));
+        }
+        let mut vaccination = models::Vaccination::new(patient_id, &service, date);
+        vaccination.lot_number = lot_number.filter(|l| !l.trim().is_empty());
+        vaccination.administered_by = administered_by.filter(|a| !a.trim().is_empty());
+        vaccination
+            .validate()
+            .map_err(FuzzyDrugsError::InvalidInput)?;
+        vaccination.id = db.add_vaccination(&vaccination)?;
+        Ok(vaccination.into())
+    }
+
+    /// A patient's vaccinations, most recent first.
+    pub fn list_patient_vaccinations(
+        &self,
+        patient_id: String,
+    ) -> Result<Vec<FfiVaccination>, FuzzyDrugsError> {
+        let vaccinations = self.lock_db()?.list_patient_vaccinations(&patient_id)?;
+        Ok(vaccinations.into_iter().map(|v| v.into()).collect())
+    }
+
+    /// Vaccines overdue or coming due within `within_days` of `as_of`
+    /// (YYYY-MM-DD; today if `None`), soonest due first: each patient's
+    /// latest dose of each antigen.
+    pub fn list_due_vaccinations(
+        &self,
+        as_of: Option<String>,
+        within_days: u32,
+    ) -> Result<Vec<FfiDueVaccination>, FuzzyDrugsError> {
+        let as_of = match as_of {
+            Some(date) => models::parse_expiration_date(&date)
+                .map_err(|_| FuzzyDrugsError::InvalidInput(format!("Invalid date: {}", date)))?,
+            None => chrono::Utc::now().date_naive(),
+        };
+        let due_by = as_of + chrono::Duration::days(i64::from(within_days));
+        let due = self
+            .lock_db()?
+            .list_due_vaccinations(&due_by.format("%Y-%m-%d").to_string())?;
+        Ok(due
+            .into_iter()
+            .map(|vaccination| FfiDueVaccination {
+                days_until_due: vaccination.days_until_due(as_of).unwrap_or_default(),
+                overdue: vaccination.is_overdue(as_of),
+                vaccination: vaccination.into(),
+            })
+            .collect())
+    }
+
+    /// Delete a vaccination recorded by mistake.
+    pub fn delete_vaccination(&self, id: i64) -> Result<(), FuzzyDrugsError> {
+        if !self.lock_db()?.delete_vaccination(id)? {
+            return Err(FuzzyDrugsError::NotFound(format!("Vaccination {}", id)));
+        }
+        Ok(())
+    }
+
+    // =========================================================================
+    // Visits
+    // =========================================================================
+
+    /// Create a visit for a patient on `visit_date` (YYYY-MM-DD). Link the
+    /// visit's drafts with `set_draft_visit`.
+    pub fn create_visit(
+        &self,
+        patient_id: String,
+        visit_date: String,
+        reason: Option<String>,
+        attending_vet: Option<String>,
+    ) -> Result<FfiVisit, FuzzyDrugsError> {
+        let mut visit = models::Visit::new(patient_id, visit_date);
+        visit.reason = reason.filter(|r| !r.trim().is_empty());
+        visit.attending_vet = attending_vet.filter(|a| !a.trim().is_empty());
+        visit.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        let db = self.lock_db()?;
+        if db.get_patient(&visit.patient_id)?.is_none() {
+            return Err(FuzzyDrugsError::NotFound(format!(
+                "Patient {}",
+                visit.patient_id
+            )));
+        }
+        db.upsert_visit(&visit)?;
+        Ok(visit.into())
+    }
+
+    /// Update a visit's date, reason, and attending vet.
+    pub fn update_visit(&self, visit: FfiVisit) -> Result<FfiVisit, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut existing = db
+            .get_visit(&visit.visit_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Visit {}", visit.visit_id)))?;
+        existing.visit_date = visit.visit_date;
+        existing.reason = visit.reason;
+        existing.attending_vet = visit.attending_vet;
+        existing.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        db.upsert_visit(&existing)?;
+        Ok(existing.into())
+    }
+
+    /// Get a visit by ID.
+    pub fn get_visit(&self, visit_id: String) -> Result<Option<FfiVisit>, FuzzyDrugsError> {
+        let visit = self.lock_db()?.get_visit(&visit_id)?;
+        Ok(visit.map(|v| v.into()))
+    }
+
+    /// A patient's visits, most recent first.
+    pub fn list_patient_visits(
+        &self,
+        patient_id: String,
+    ) -> Result<Vec<FfiVisit>, FuzzyDrugsError> {
+        let visits = self.lock_db()?.list_patient_visits(&patient_id)?;
+        Ok(visits.into_iter().map(|v| v.into()).collect())
+    }
+
+    /// Delete a visit. Its drafts are kept, unlinked.
+    pub fn delete_visit(&self, visit_id: String) -> Result<(), FuzzyDrugsError> {
+        if !self.lock_db()?.delete_visit(&visit_id)? {
+            return Err(FuzzyDrugsError::NotFound(format!("Visit {}", visit_id)));
+        }
+        Ok(())
+    }
+
+    /// Link a draft to one of its patient's visits (or unlink it with
+    /// `None`). The link is copied onto the encounter when it's committed.
+    pub fn set_draft_visit(
+        &self,
+        draft_id: String,
+        visit_id: Option<String>,
+    ) -> Result<(), FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        if let Some(visit_id) = &visit_id {
+            let visit = db
+                .get_visit(visit_id)?
+                .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Visit {}", visit_id)))?;
+            if visit.patient_id != draft.patient_id {
+                return Err(FuzzyDrugsError::InvalidInput(format!(
+                    "Visit {} is for another patient",
+                    visit_id
+                )));
+            }
+        }
+        db.set_draft_visit(&draft_id, visit_id.as_deref())?;
+        Ok(())
+    }
+
+    /// A visit's drafts and the combined billing of its committed
+    /// encounters.
+    pub fn get_visit_billing(&self, visit_id: String) -> Result<FfiVisitBilling, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let visit = db
+            .get_visit(&visit_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Visit {}", visit_id)))?;
+        let filter = export::BillingFilter {
+            visit_id: Some(visit_id.clone()),
+            ..Default::default()
+        };
+        let batch = self.billing_exporter(&db).export_filtered(&filter)?;
+        let billing = batch.visits.into_iter().next().unwrap_or_default();
+        Ok(FfiVisitBilling {
+            visit_id,
+            patient_id: visit.patient_id,
+            draft_ids: db.list_visit_draft_ids(&visit.visit_id)?,
+            leaf_hashes: billing.leaf_hashes,
+            total: billing.totals.total,
+            tax: billing.totals.tax,
+            total_with_tax: billing.totals.total_with_tax,
+            priced_items: billing.totals.priced_items as u32,
+            unpriced_items: billing.totals.unpriced_items as u32,
+        })
+    }
+
+    // =========================================================================
+    // Clients
+    // =========================================================================
+
+    /// Create a client (owner). Link their patients with
+    /// `set_patient_client`.
+    pub fn create_client(&self, name: String) -> Result<FfiClient, FuzzyDrugsError> {
+        let client = models::Client::new(name.trim().to_string());
+        client.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        self.lock_db()?.upsert_client(&client)?;
+        Ok(client.into())
+    }
+
+    /// Update a client's name, PIMS ID, and contact details.
+    pub fn update_client(&self, client: FfiClient) -> Result<FfiClient, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut existing = db
+            .get_client(&client.client_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Client {}", client.client_id)))?;
+        existing.server_id = client.server_id;
+        existing.name = client.name.trim().to_string();
+        existing.phone = client.phone;
+        existing.email = client.email;
+        existing.address = client.address;
+        existing.notes = client.notes;
+        existing.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        db.upsert_client(&existing)?;
+        Ok(existing.into())
+    }
+
+    /// Get a client by ID.
+    pub fn get_client(&self, client_id: String) -> Result<Option<FfiClient>, FuzzyDrugsError> {
+        let client = self.lock_db()?.get_client(&client_id)?;
+        Ok(client.map(|c| c.into()))
+    }
+
+    /// Search clients by name (prefix match, ignoring case).
+    pub fn search_clients(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<FfiClient>, FuzzyDrugsError> {
+        let clients = self.lock_db()?.search_clients(&query, limit as usize)?;
+        Ok(clients.into_iter().map(|c| c.into()).collect())
+    }
+
+    /// Delete a client. Their patients are kept, unlinked.
+    pub fn delete_client(&self, client_id: String) -> Result<(), FuzzyDrugsError> {
+        if !self.lock_db()?.delete_client(&client_id)? {
+            return Err(FuzzyDrugsError::NotFound(format!("Client {}", client_id)));
+        }
+        Ok(())
+    }
+
+    /// Link a patient to a client (or unlink it with `None`). Linking also
+    /// sets the patient's owner name to the client's, so it syncs to the
+    /// PIMS.
+    pub fn set_patient_client(
+        &self,
+        local_id: String,
+        client_id: Option<String>,
+    ) -> Result<FfiPatient, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut patient = db
+            .get_patient(&local_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Patient {}", local_id)))?;
+        if let Some(client_id) = &client_id {
+            let client = db
+                .get_client(client_id)?
+                .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Client {}", client_id)))?;
+            patient.owner_name = Some(client.name);
+        }
+        patient.client_id = client_id;
+        db.update_patient(&patient)?;
+        db.enqueue_outbox(
+            models::OutboxKind::PatientUpsert,
+            &patient.local_id,
+            chrono::Utc::now(),
+        )?;
+        Ok(patient.into())
+    }
+
+    /// A client's patients, by name.
+    pub fn list_client_patients(
+        &self,
+        client_id: String,
+    ) -> Result<Vec<FfiPatient>, FuzzyDrugsError> {
+        let patients = self.lock_db()?.list_client_patients(&client_id)?;
+        Ok(patients.into_iter().map(|p| p.into()).collect())
+    }
+
+    // =========================================================================
+    // Draft Operations
+    // =========================================================================
+
+    /// Create a new encounter draft.
+    pub fn create_draft(&self, patient_id: String) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let draft = EncounterDraft::new(patient_id);
+        self.lock_db()?.insert_draft(&draft)?;
+        self.notify(vec![CoreEvent::draft_status(
+            &draft.draft_id,
+            &draft.status,
+        )]);
+        Ok(draft.into())
+    }
+
+    /// Get a draft by ID.
+    pub fn get_draft(
+        &self,
+        draft_id: String,
+    ) -> Result<Option<FfiEncounterDraft>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let draft = db.get_draft(&draft_id)?;
+        Ok(draft.map(|d| d.into()))
+    }
+
+    /// List drafts pending review (sorted by lowest confidence first).
+    pub fn list_pending_review_drafts(&self) -> Result<Vec<FfiEncounterDraft>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let drafts = db.list_pending_review_drafts()?;
+        Ok(drafts.into_iter().map(|d| d.into()).collect())
+    }
+
+    /// List a patient's uncommitted drafts, newest first.
+    ///
+    /// Committed drafts are listed by `list_committed_encounters_for_patient`.
+    pub fn list_drafts_for_patient(
+        &self,
+        patient_id: String,
+    ) -> Result<Vec<FfiDraftSummary>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        if db.get_patient(&patient_id)?.is_none() {
+            return Err(FuzzyDrugsError::NotFound(format!("Patient {}", patient_id)));
+        }
+        let drafts = db.list_drafts_for_patient(&patient_id)?;
+        Ok(drafts
+            .into_iter()
+            .filter(|d| d.status != DraftStatus::Committed)
+            .map(|d| d.into())
+            .collect())
+    }
+
+    /// List a patient's committed encounters, newest first, with the leaf
+    /// hash to request proofs for.
+    pub fn list_committed_encounters_for_patient(
+        &self,
+        patient_id: String,
+    ) -> Result<Vec<FfiCommittedEncounter>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        if db.get_patient(&patient_id)?.is_none() {
+            return Err(FuzzyDrugsError::NotFound(format!("Patient {}", patient_id)));
+        }
+        let leaves = db.get_encounter_leaves_for_patient(&patient_id)?;
+        Ok(leaves
+            .into_iter()
+            .filter_map(|leaf| {
+                let encounter: ReviewedEncounter =
+                    serde_json::from_str(leaf.payload.as_deref()?).ok()?;
+                Some(FfiCommittedEncounter {
+                    draft_id: encounter.draft_id,
+                    patient_id: encounter.patient_id,
+                    reviewed_by: encounter.reviewed_by,
+                    reviewed_at: encounter.reviewed_at,
+                    committed_at: leaf.created_at,
+                    line_item_count: encounter.line_items.len() as u32,
+                    notes: encounter.notes,
+                    leaf_hash: leaf.hash,
+                })
+            })
+            .collect())
+    }
+
+    /// Get a draft's resolved items grouped by clinical category.
+    ///
+    /// Groups are in review order (anesthesia, analgesia, antibiotics, fluids,
+    /// other medications, supplies); empty groups are omitted. Each item
+    /// carries its index in the draft for review calls, and the transcript
+    /// offsets of its drug, dose, unit, route, and frequency tokens checked
+    /// against the draft's current transcript.
+    pub fn get_draft_items(&self, draft_id: String) -> Result<Vec<FfiItemGroup>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let normalizer = self.lock_normalizer()?;
+        Ok(draft
+            .grouped_items()
+            .into_iter()
+            .map(|(category, indices)| FfiItemGroup {
+                category: category.as_str().to_string(),
+                label: category.label().to_string(),
+                items: indices
+                    .into_iter()
+                    .map(|index| {
+                        let resolved = &draft.resolved_items[index];
+                        let mut item: FfiResolvedItem = resolved.clone().into();
+                        item.field_spans = normalizer
+                            .field_spans(&draft.transcript, &resolved.mention.original)
+                            .into();
+                        FfiDraftItem {
+                            index: index as u32,
+                            item,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Add a line item the vet entered by hand (not from the transcript).
+    pub fn add_manual_item(
+        &self,
+        draft_id: String,
+        item: FfiLineItem,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let schedule = db
+            .get_catalog_item(&item.sku)?
+            .and_then(|c| c.controlled_schedule);
+        draft
+            .add_manual_item(item.sku, item.name, item.quantity, item.unit, item.route)
+            .controlled_schedule = schedule;
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Remove a service item the extractor wrongly matched. Committed drafts
+    /// cannot be changed.
+    pub fn remove_service_item(
+        &self,
+        draft_id: String,
+        item_index: u32,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        if draft.status == DraftStatus::Committed {
+            return Err(FuzzyDrugsError::conflict(
+                "draft",
+                &draft_id,
+                "already_committed",
+                format!("Draft {} is already committed", draft_id),
+            ));
+        }
+        if item_index as usize >= draft.service_items.len() {
+            return Err(FuzzyDrugsError::NotFound(format!(
+                "Service item {}",
+                item_index
+            )));
+        }
+        draft.service_items.remove(item_index as usize);
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Record the lot a vaccine (or other service item) was given from.
+    ///
+    /// As for [`set_item_lot`](Self::set_item_lot), the expiration date is
+    /// looked up from the service code's inventory lots when not given.
+    pub fn set_service_lot(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        lot_number: String,
+        expiration_date: Option<String>,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let lot_number = parse_lot_number(&lot_number)?;
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let item = draft
+            .service_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Service item {}", item_index)))?;
+        item.expiration_date = Self::lot_expiration(&db, &item.code, &lot_number, expiration_date)?;
+        item.lot_number = Some(lot_number);
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Discard an uncommitted draft, committing an audit leaf for it in the
+    /// same transaction.
+    ///
+    /// Only drafts still recording, transcribed, or pending review can be
+    /// discarded; reviewed drafts are awaiting commit. Drafts under legal
+    /// hold cannot be discarded.
+    pub fn discard_draft(
+        &self,
+        draft_id: String,
+        discarded_by: String,
+        reason: String,
+    ) -> Result<FfiLeafCommit, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let reviewed = match draft.status {
+            DraftStatus::Reviewed => Some("reviewed"),
+            DraftStatus::Committed => Some("committed"),
+            _ => None,
+        };
+        if let Some(status) = reviewed {
+            return Err(FuzzyDrugsError::conflict(
+                "draft",
+                &draft_id,
+                &format!("already_{}", status),
+                format!(
+                    "Draft {} is already {} and cannot be discarded",
+                    draft_id, status
+                ),
+            ));
+        }
+        let was_unsynced = Self::has_unsynced(&db)?;
+
+        // The draft is only gone once its audit leaf is in the tree
+        let tx = db
+            .conn()
+            .unchecked_transaction()
+            .map_err(db::DbError::from)?;
+        db.delete_draft(&draft_id)?;
+        let event = models::AuditEvent::draft_discarded(draft_id.clone(), discarded_by, reason);
+        let commit = MerkleTree::new(&db).commit_audit_event(&event)?;
+        tx.commit().map_err(db::DbError::from)?;
+
+        let mut events = vec![CoreEvent::DraftStatusChanged {
+            draft_id,
+            status: "Discarded".into(),
+        }];
+        events.extend(Self::sync_state_event(&db, was_unsynced)?);
+        drop(db);
+        self.notify(events);
+        Ok(commit.into())
+    }
+
+    /// Approve an item's top candidate.
+    pub fn approve_item(
+        &self,
+        draft_id: String,
+        item_index: u32,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        self.review_item(&draft_id, item_index, |_, _| Ok(ResolutionStatus::Approved))
+    }
+
+    /// Choose one of an item's alternative candidates instead of the top one.
+    pub fn select_alternative(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        sku: String,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        self.review_item(&draft_id, item_index, |_, item| {
+            if item.top_candidate.sku == sku {
+                return Ok(ResolutionStatus::Approved);
+            }
+            if !item.alternatives.iter().any(|c| c.sku == sku) {
+                return Err(FuzzyDrugsError::InvalidInput(format!(
+                    "{} is not an alternative for item {}",
+                    sku, item_index
+                )));
+            }
+            Ok(ResolutionStatus::AlternativeSelected { selected_sku: sku })
+        })
+    }
+
+    /// Replace an item's match with a catalog SKU the vet picked by hand.
+    pub fn manual_override(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        sku: String,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        self.review_item(&draft_id, item_index, |db, _| {
+            if db.get_catalog_item(&sku)?.is_none() {
+                return Err(FuzzyDrugsError::NotFound(format!("Catalog item {}", sku)));
+            }
+            Ok(ResolutionStatus::ManualOverride { override_sku: sku })
+        })
+    }
+
+    /// Reject an item (no catalog match is appropriate); it is not committed.
+    pub fn reject_item(
+        &self,
+        draft_id: String,
+        item_index: u32,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        self.review_item(&draft_id, item_index, |_, _| Ok(ResolutionStatus::Rejected))
+    }
+
+    /// Explicitly confirm a controlled substance item on a draft.
+    ///
+    /// Controlled items stay pending review until confirmed, so an encounter
+    /// containing them cannot be committed without this step.
+    pub fn confirm_controlled_item(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        reviewer: String,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let item = draft
+            .resolved_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Item {}", item_index)))?;
+        if item.controlled_schedule().is_none() {
+            return Err(FuzzyDrugsError::InvalidInput(format!(
+                "Item {} is not a controlled substance",
+                item_index
+            )));
+        }
+        item.confirm_controlled(reviewer);
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Explicitly confirm a controlled substance the vet added by hand, as
+    /// `confirm_controlled_item` does for transcript items.
+    pub fn confirm_controlled_manual_item(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        reviewer: String,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let item = draft
+            .manual_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Manual item {}", item_index)))?;
+        if item.controlled_schedule.is_none() {
+            return Err(FuzzyDrugsError::InvalidInput(format!(
+                "Manual item {} is not a controlled substance",
+                item_index
+            )));
+        }
+        item.controlled_confirmed_by = Some(reviewer);
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Give an item despite a recorded allergy to one of its ingredients.
+    ///
+    /// Items matching an allergy stay pending review until overridden, so
+    /// the encounter can't be committed without this step. Changing the
+    /// item's SKU clears the override. The item is checked against the
+    /// patient's allergies as they are now, so this also covers allergies
+    /// recorded after the draft was staged.
+    pub fn override_allergy_warning(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        reviewer: String,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let item = draft
+            .resolved_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Item {}", item_index)))?;
+        Self::recheck_allergies(&db, &draft.patient_id, item)?;
+        if item.blocking_warnings().next().is_none() {
+            return Err(FuzzyDrugsError::InvalidInput(format!(
+                "Item {} has no allergy warning",
+                item_index
+            )));
+        }
+        item.override_allergy(reviewer);
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Give an item the vet added by hand despite a recorded allergy, as
+    /// `override_allergy_warning` does for transcript items.
+    ///
+    /// Manual items are checked against the patient's allergies on commit.
+    pub fn override_manual_allergy_warning(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        reviewer: String,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let allergies = db
+            .get_patient(&draft.patient_id)?
+            .map(|p| p.allergies)
+            .unwrap_or_default();
+        let item = draft
+            .manual_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Manual item {}", item_index)))?;
+        let allergic = db
+            .get_catalog_item(&item.sku)?
+            .is_some_and(|c| !resolver::allergy_warnings(&c, &allergies).is_empty());
+        if !allergic {
+            return Err(FuzzyDrugsError::InvalidInput(format!(
+                "Manual item {} has no allergy warning",
+                item_index
+            )));
+        }
+        item.allergy_overridden_by = Some(reviewer);
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Set whether an item was administered in clinic, dispensed, or
+    /// prescribed ("administered", "dispensed", "prescribed").
+    ///
+    /// Overrides the disposition inferred from the transcript.
+    pub fn set_item_disposition(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        disposition: String,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let disposition = parse_disposition(&disposition)?;
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let item = draft
+            .resolved_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Item {}", item_index)))?;
+        item.disposition = Some(disposition);
+        draft.recount_withdrawals();
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Record the manufacturer lot an item was given from.
+    ///
+    /// Controlled drugs given or dispensed (and vaccines, see
+    /// [`set_service_lot`](Self::set_service_lot)) are flagged in the
+    /// draft's `missing_lot_count` until their lot is recorded. The
+    /// expiration date (YYYY-MM-DD) is looked up from the item's inventory
+    /// lots when not given; an expired lot is `InvalidInput`. Changing the
+    /// item's SKU clears the lot.
+    pub fn set_item_lot(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        lot_number: String,
+        expiration_date: Option<String>,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let lot_number = parse_lot_number(&lot_number)?;
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let item = draft
+            .resolved_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Item {}", item_index)))?;
+        let sku = item
+            .final_sku()
+            .unwrap_or(&item.top_candidate.sku)
+            .to_string();
+        item.expiration_date = Self::lot_expiration(&db, &sku, &lot_number, expiration_date)?;
+        item.lot_number = Some(lot_number);
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Approve an item containing an escalation-restricted ingredient.
+    ///
+    /// The approver must be a user holding the rule's role (see
+    /// `upsert_user`) and give a reason note. The approval is committed on
+    /// the line item alongside how it was resolved.
+    pub fn approve_escalated_item(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        approver: String,
+        role: String,
+        reason: String,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let item = draft
+            .resolved_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Item {}", item_index)))?;
+        let rule = db.item_escalation_rule(item)?.ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!(
+                "Item {} does not require escalation",
+                item_index
+            ))
+        })?;
+        let escalation = models::Escalation::required(&rule);
+        let approval = Self::escalation_approval(&db, rule, &approver, &role, &reason)?;
+        item.escalation = Some(models::Escalation {
+            approval: Some(approval),
+            ..escalation
+        });
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Approve a manually added item whose SKU contains an
+    /// escalation-restricted ingredient, as `approve_escalated_item` does
+    /// for transcript items.
+    pub fn approve_escalated_manual_item(
+        &self,
+        draft_id: String,
+        item_index: u32,
+        approver: String,
+        role: String,
+        reason: String,
+    ) -> Result<FfiEncounterDraft, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        let item = draft
+            .manual_items
+            .get_mut(item_index as usize)
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Manual item {}", item_index)))?;
+        let rule = db.sku_escalation_rule(&item.sku)?.ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!(
+                "Manual item {} does not require escalation",
+                item_index
+            ))
+        })?;
+        item.escalation_approval = Some(Self::escalation_approval(
+            &db, rule, &approver, &role, &reason,
+        )?);
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft.into())
+    }
+
+    /// Preview what committing a draft will write, diffed against the transcript.
+    ///
+    /// Highlights manually added items, dropped (rejected) mentions, and doses
+    /// that differ from what was spoken, for the vet sign-off screen.
+    pub fn get_commit_preview(
+        &self,
+        draft_id: String,
+    ) -> Result<FfiCommitPreview, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        Ok(CommitPreview::from_draft(&draft).into())
+    }
+
+    /// Provisional client price estimate for a draft, even mid-review.
+    ///
+    /// Pending items are priced at their top candidate with a range across
+    /// plausible alternatives. Nothing is stored or committed.
+    pub fn get_estimate(&self, draft_id: String) -> Result<FfiPriceEstimate, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+
+        let mut skus: Vec<&str> = draft
+            .resolved_items
+            .iter()
+            .flat_map(|item| {
+                std::iter::once(item.top_candidate.sku.as_str())
+                    .chain(item.alternatives.iter().map(|c| c.sku.as_str()))
+                    .chain(item.final_sku())
+            })
+            .chain(draft.manual_items.iter().map(|item| item.sku.as_str()))
+            .collect();
+        skus.sort_unstable();
+        skus.dedup();
+        let mut pricing = db.catalog_pricing(&skus)?;
+        for service in db.list_service_items(false)? {
+            pricing.entry(service.code).or_insert(models::Pricing {
+                unit_price: service.unit_price,
+                tax_code: service.tax_code,
+                ..Default::default()
+            });
+        }
+
+        let mut catalog = std::collections::HashMap::new();
+        for sku in skus {
+            if let Some(item) = db.get_catalog_item(sku)? {
+                catalog.insert(sku, item);
+            }
+        }
+        let dispensing = resolver::DispensingCalculator::new();
+
+        let estimate = PriceEstimate::from_draft(
+            &draft,
+            |sku, quantity, unit, schedule| {
+                let item = catalog.get(sku)?;
+                Some(
+                    dispensing
+                        .dispense_line(item, quantity, unit, schedule)?
+                        .per_dose,
+                )
+            },
+            |sku, quantity| pricing.get(sku)?.charge(quantity),
+        );
+        Ok(estimate.into())
+    }
+
+    /// Check a draft for drug-drug interactions.
+    ///
+    /// Checks every non-rejected item and manual addition against each other
+    /// and against the patient's active medications. The warnings are stored
+    /// on the draft so the review screen shows them before approval.
+    pub fn check_interactions(
+        &self,
+        draft_id: String,
+    ) -> Result<Vec<FfiInteractionWarning>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        draft.interaction_warnings = InteractionChecker::new(&db)?.check_draft(&draft)?;
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(draft
+            .interaction_warnings
+            .into_iter()
+            .map(|w| w.into())
+            .collect())
+    }
+
+    /// Add or override an entry in the local interaction table.
+    ///
+    /// Each side is a generic drug name or a class key such as "class:nsaid".
+    /// `severity` is one of "minor", "moderate", "major", "contraindicated".
+    pub fn upsert_interaction(
+        &self,
+        drug_a: String,
+        drug_b: String,
+        severity: String,
+        note: String,
+    ) -> Result<(), FuzzyDrugsError> {
+        let severity = models::InteractionSeverity::parse(&severity).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unknown interaction severity: {}", severity))
+        })?;
+        let db = self.lock_db()?;
+        db.upsert_interaction(&models::DrugInteraction::new(
+            &drug_a, &drug_b, severity, &note,
+        ))?;
+        Ok(())
+    }
+
+    // =========================================================================
+    // Escalation Rules
+    // =========================================================================
+
+    /// Require escalated approval for items containing an ingredient.
+    ///
+    /// Only reviewers with `required_role` can approve such items, and each
+    /// approval needs a reason note. Replaces any existing rule.
+    pub fn upsert_escalation_rule(
+        &self,
+        ingredient: String,
+        required_role: String,
+        note: String,
+    ) -> Result<(), FuzzyDrugsError> {
+        if ingredient.trim().is_empty() || required_role.trim().is_empty() {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "Ingredient and required role are required".into(),
+            ));
+        }
+        let db = self.lock_db()?;
+        db.upsert_escalation_rule(&models::EscalationRule::new(
+            &ingredient,
+            &required_role,
+            &note,
+        ))?;
+        Ok(())
+    }
+
+    /// List all escalation rules.
+    pub fn list_escalation_rules(&self) -> Result<Vec<FfiEscalationRule>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        Ok(db
+            .list_escalation_rules()?
+            .into_iter()
+            .map(|r| r.into())
+            .collect())
+    }
+
+    /// Remove the escalation rule for an ingredient. Returns false if none existed.
+    pub fn delete_escalation_rule(&self, ingredient: String) -> Result<bool, FuzzyDrugsError> {
+        Ok(self.lock_db()?.delete_escalation_rule(&ingredient)?)
+    }
+
+    // =========================================================================
+    // Scoring
+    // =========================================================================
+
+    /// Get this clinic's disambiguator weights and limits.
+    pub fn get_scoring_config(&self) -> Result<FfiScoringConfig, FuzzyDrugsError> {
+        Ok(self.lock_db()?.scoring_config()?.into())
+    }
+
+    /// Set this clinic's disambiguator weights and limits.
+    ///
+    /// Weights must be non-negative and sum to 1. Takes effect on the next
+    /// resolution.
+    pub fn set_scoring_config(&self, config: FfiScoringConfig) -> Result<(), FuzzyDrugsError> {
+        let config: models::ScoringConfig = config.into();
+        config.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        Ok(self.lock_db()?.set_scoring_config(&config)?)
+    }
+
+    /// Restore the default disambiguator weights and limits.
+    pub fn reset_scoring_config(&self) -> Result<(), FuzzyDrugsError> {
+        Ok(self.lock_db()?.reset_scoring_config()?)
+    }
+
+    /// The stored settings, in the shape `open_database_with_options` takes.
+    /// `locale` and `system_id` are `None` if never set.
+    pub fn get_core_options(&self) -> Result<FfiCoreOptions, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let settings = db.core_settings()?;
+        Ok(FfiCoreOptions {
+            scoring: Some(db.scoring_config()?.into()),
+            review_queue_order: Some(settings.review_queue_order.as_str().to_string()),
+            locale: settings.locale,
+            system_id: settings.system_id,
+            sync_conflict_strategy: Some(settings.sync_conflict_strategy.as_str().to_string()),
+        })
+    }
+
+    /// QuickBooks accounts and per-SKU items used by IIF billing exports.
+    pub fn get_quickbooks_mapping(&self) -> Result<FfiQuickBooksMapping, FuzzyDrugsError> {
+        Ok(self.lock_db()?.quickbooks_mapping()?.into())
+    }
+
+    /// Replace the QuickBooks mapping. Account and item names must not be
+    /// empty.
+    pub fn set_quickbooks_mapping(
+        &self,
+        mapping: FfiQuickBooksMapping,
+    ) -> Result<(), FuzzyDrugsError> {
+        let mapping = models::QuickBooksMapping::from(mapping);
+        let names = [&mapping.receivable_account, &mapping.income_account]
+            .into_iter()
+            .chain(mapping.items.values().map(|item| &item.item))
+            .chain(
+                mapping
+                    .items
+                    .values()
+                    .filter_map(|item| item.account.as_ref()),
+            );
+        for name in names {
+            if name.trim().is_empty() {
+                return Err(FuzzyDrugsError::InvalidInput(
+                    "QuickBooks account and item names must not be empty".into(),
+                ));
+            }
+        }
+        Ok(self.lock_db()?.set_quickbooks_mapping(&mapping)?)
+    }
+
+    /// Columns, headers, delimiter, and date format of billing CSV exports.
+    pub fn get_billing_csv_layout(&self) -> Result<FfiCsvLayout, FuzzyDrugsError> {
+        Ok(self.lock_db()?.billing_csv_layout()?.into())
+    }
+
+    /// Replace the billing CSV layout used by every billing CSV export
+    /// (`export_billing_csv`, filtered, and file exports).
+    pub fn set_billing_csv_layout(&self, layout: FfiCsvLayout) -> Result<(), FuzzyDrugsError> {
+        let mut chars = layout.delimiter.chars();
+        let delimiter = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => {
+                return Err(FuzzyDrugsError::InvalidInput(format!(
+                    "CSV delimiter must be one character: {:?}",
+                    layout.delimiter
+                )))
+            }
+        };
+        let columns = layout
+            .columns
+            .into_iter()
+            .map(|c| {
+                let column = models::CsvColumn::parse(&c.column).ok_or_else(|| {
+                    FuzzyDrugsError::InvalidInput(format!("Unknown CSV column: {}", c.column))
+                })?;
+                Ok(models::CsvLayoutColumn {
+                    column,
+                    header: c.header,
+                })
+            })
+            .collect::<Result<_, FuzzyDrugsError>>()?;
+        let layout = models::CsvLayout {
+            columns,
+            delimiter,
+            date_format: layout.date_format,
+        };
+        layout.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        Ok(self.lock_db()?.set_billing_csv_layout(&layout)?)
+    }
+
+    /// Sales tax rates by catalog tax code, sorted by code.
+    pub fn get_tax_rates(&self) -> Result<Vec<FfiTaxRate>, FuzzyDrugsError> {
+        let rates = self.lock_db()?.tax_rates()?;
+        Ok(rates
+            .rates
+            .into_iter()
+            .map(|(code, rate)| FfiTaxRate { code, rate })
+            .collect())
+    }
+
+    /// Replace the sales tax rates applied to billing exports. Codes must
+    /// be unique (ignoring case) and rates between 0 and 1.
+    pub fn set_tax_rates(&self, rates: Vec<FfiTaxRate>) -> Result<(), FuzzyDrugsError> {
+        let mut tax_rates = models::TaxRates::default();
+        for FfiTaxRate { code, rate } in rates {
+            if tax_rates.rates.insert(code.clone(), rate).is_some() {
+                return Err(FuzzyDrugsError::InvalidInput(format!(
+                    "Duplicate tax code: {}",
+                    code
+                )));
+            }
+        }
+        tax_rates
+            .validate()
+            .map_err(FuzzyDrugsError::InvalidInput)?;
+        Ok(self.lock_db()?.set_tax_rates(&tax_rates)?)
+    }
+
+    // =========================================================================
+    // Extraction Debug
+    // =========================================================================
+
+    /// Get the raw LLM response retention settings.
+    pub fn get_extraction_debug_config(&self) -> Result<FfiExtractionDebugConfig, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        Ok(db.extraction_debug_config().clone().into())
+    }
+
+    /// Enable/disable raw LLM response retention and set its size cap and TTL.
+    pub fn set_extraction_debug_config(
+        &self,
+        config: FfiExtractionDebugConfig,
+    ) -> Result<(), FuzzyDrugsError> {
+        let mut db = self.lock_db()?;
+        db.set_extraction_debug_config(config.into());
+        Ok(())
+    }
+
+    /// Store the raw model output behind a draft's extraction.
+    ///
+    /// Returns `false` when debug retention is disabled (nothing is stored).
+    pub fn record_extraction_debug(
+        &self,
+        draft_id: String,
+        model: Option<String>,
+        raw_response: String,
+    ) -> Result<bool, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        if db.get_draft(&draft_id)?.is_none() {
+            return Err(FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)));
+        }
+        Ok(db.record_extraction_debug(&draft_id, model.as_deref(), &raw_response)?)
+    }
+
+    /// Get the retained raw model output for a draft, if any.
+    pub fn get_extraction_debug(
+        &self,
+        draft_id: String,
+    ) -> Result<Option<FfiExtractionDebug>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let debug = db.get_extraction_debug(&draft_id)?;
+        Ok(debug.map(|d| d.into()))
+    }
+
+    /// Sampling parameters for the extraction model (temperature 0 and
+    /// seed 0 unless changed).
+    pub fn get_sampling_settings(&self) -> Result<FfiSamplingSettings, FuzzyDrugsError> {
+        Ok(self.lock_db()?.sampling_settings()?.into())
+    }
+
+    /// Store the extraction model's sampling parameters. The host (or the
+    /// llm crate's `GenerationParams::from`) applies them when it loads the
+    /// model; a change should also bump the extractor version.
+    pub fn set_sampling_settings(
+        &self,
+        settings: FfiSamplingSettings,
+    ) -> Result<(), FuzzyDrugsError> {
+        let settings = models::SamplingSettings::from(settings);
+        settings.validate().map_err(FuzzyDrugsError::InvalidInput)?;
+        Ok(self.lock_db()?.set_sampling_settings(&settings)?)
+    }
+
+    // =========================================================================
+    // Transcript Processing
+    // =========================================================================
+
+    /// Set the extractor `process_transcript` uses to find drug mentions.
+    pub fn set_mention_extractor(
+        &self,
+        extractor: Arc<dyn FfiMentionExtractor>,
+    ) -> Result<(), FuzzyDrugsError> {
+        let extractor: Arc<dyn MentionExtractor> = Arc::new(ForeignExtractor(extractor));
+        let mut slot = self
+            .extractor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *slot = Some(extractor);
+        self.set_version_slot(None);
+        Ok(())
+    }
+
+    /// Set an extractor that finds service mentions (procedures, vaccines,
+    /// diagnostics) as well as drugs, in one pass. Replaces the extractor
+    /// set by `set_mention_extractor`.
+    pub fn set_entity_extractor(
+        &self,
+        extractor: Arc<dyn FfiEntityExtractor>,
+    ) -> Result<(), FuzzyDrugsError> {
+        let extractor: Arc<dyn MentionExtractor> = Arc::new(ForeignEntityExtractor(extractor));
+        let mut slot = self
+            .extractor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *slot = Some(extractor);
+        self.set_version_slot(None);
+        Ok(())
+    }
+
+    /// Use the built-in rule-based extractor, which finds drugs from the
+    /// normalizer's aliases and the active catalog and services, with no
+    /// model. For installs whose model isn't downloaded (yet): mentions it
+    /// can't read are left for review rather than failing the transcript.
+    /// It takes a snapshot, so call again after the catalog changes.
+    /// Replaces the extractor set by `set_mention_extractor`.
+    pub fn use_rule_based_extractor(&self) -> Result<(), FuzzyDrugsError> {
+        let (items, services) = {
+            let db = self.lock_db()?;
+            (db.list_catalog_items(true)?, db.list_service_items(true)?)
+        };
+        let extractor = RuleBasedExtractor::new(&*self.lock_normalizer()?)
+            .with_catalog(&items)
+            .with_services(&services);
+        let mut slot = self
+            .extractor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *slot = Some(Arc::new(extractor));
+        self.set_version_slot(None);
+        Ok(())
+    }
+
+    /// Name the model and prompt behind the host's extractor (e.g.
+    /// "llama-3.2-1b-q4/3+prompt-7"), which turns on the extraction cache:
+    /// a transcript already extracted under this version is not extracted
+    /// again. Change it whenever the model or prompt changes; results cached
+    /// under other versions are dropped. `None` turns caching off. Setting
+    /// an extractor clears the version, so set it afterwards.
+    pub fn set_extractor_version(&self, version: Option<String>) -> Result<(), FuzzyDrugsError> {
+        if let Some(version) = &version {
+            if version.trim().is_empty() {
+                return Err(FuzzyDrugsError::InvalidInput(
+                    "Extractor version must not be empty".into(),
+                ));
+            }
+            let dropped = self.lock_db()?.invalidate_extraction_cache(Some(version))?;
+            if dropped > 0 {
+                tracing::info!(version, dropped, "Dropped stale cached extractions");
+            }
+        }
+        self.set_version_slot(version);
+        Ok(())
+    }
+
+    /// Drop every cached extraction. Returns how many were dropped.
+    pub fn clear_extraction_cache(&self) -> Result<u32, FuzzyDrugsError> {
+        Ok(self.lock_db()?.invalidate_extraction_cache(None)? as u32)
+    }
+
+    /// Register the listener notified of draft, commit, catalog, and sync
+    /// changes. Replaces any previously registered listener.
+    pub fn set_listener(
+        &self,
+        listener: Arc<dyn FuzzyDrugsListener>,
+    ) -> Result<(), FuzzyDrugsError> {
+        let mut slot = self.listener.lock().unwrap_or_else(PoisonError::into_inner);
+        *slot = Some(listener);
+        Ok(())
+    }
+
+    /// Stop notifying the registered listener, if any.
+    pub fn clear_listener(&self) -> Result<(), FuzzyDrugsError> {
+        let mut slot = self.listener.lock().unwrap_or_else(PoisonError::into_inner);
+        *slot = None;
+        Ok(())
+    }
+
+    /// Run a transcript through the whole pipeline and stage it for review.
+    ///
+    /// Extracts mentions with the configured extractor, then normalizes and
+    /// resolves them for the draft's patient, stores the transcript and items
+    /// on the draft, checks interactions, and moves it to `PendingReview`.
+    /// Processing again replaces the resolved items; manual items are kept.
+    /// Extraction runs without holding the database lock.
+    pub fn process_transcript(
+        &self,
+        draft_id: String,
+        transcript: String,
+    ) -> Result<FfiProcessedTranscript, FuzzyDrugsError> {
+        self.run_pipeline(&draft_id, transcript, false, &[])
+    }
+
+    /// Like `process_transcript`, for a diarized transcript: each mention is
+    /// tagged with the role of the speaker turn it falls in, and mentions
+    /// said by the owner are listed as the draft's `reported_medications`
+    /// (home medications) instead of being resolved as orders.
+    pub fn process_transcript_with_speakers(
+        &self,
+        draft_id: String,
+        transcript: String,
+        turns: Vec<FfiSpeakerTurn>,
+    ) -> Result<FfiProcessedTranscript, FuzzyDrugsError> {
+        let turns = turns
+            .into_iter()
+            .map(models::SpeakerTurn::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.run_pipeline(&draft_id, transcript, false, &turns)
+    }
+
+    /// Save a corrected transcript, optionally re-running extraction and
+    /// resolution.
+    ///
+    /// Without `re_resolve` only the text changes; item offsets are checked
+    /// against it when items are read. With it, the draft goes back to
+    /// `PendingReview`, and items whose mention text is unchanged keep their
+    /// review decisions. Committed drafts cannot be edited.
+    pub fn update_draft_transcript(
+        &self,
+        draft_id: String,
+        new_transcript: String,
+        re_resolve: bool,
+    ) -> Result<FfiProcessedTranscript, FuzzyDrugsError> {
+        if re_resolve {
+            return self.run_pipeline(&draft_id, new_transcript, true, &[]);
+        }
+        let db = self.lock_db()?;
+        let mut draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        Self::check_processable(&draft, true)?;
+        draft.transcript = new_transcript;
+        draft.touch();
+        db.update_draft(&draft)?;
+        Ok(FfiProcessedTranscript {
+            draft: draft.into(),
+            unmatched_drugs: Vec::new(),
+            unmatched_services: Vec::new(),
+        })
+    }
+
+    // =========================================================================
+    // Resolver Operations
+    // =========================================================================
+
+    /// Resolve a drug mention to SKU candidates.
+    ///
+    /// `patient_weight` is interpreted in `patient_weight_unit` ("kg", "lbs", ...),
+    /// defaulting to kg, and normalized to kg before dose plausibility scoring.
+    /// `patient_breed` enables breed-specific safety checks (e.g., MDR1 breeds),
+    /// and implies the species when `patient_species` is nil.
+    #[allow(clippy::too_many_arguments)]
+    pub fn resolve_mention(
+        &self,
+        drug_name: String,
+        dose: Option<f64>,
+        unit: Option<String>,
+        route: Option<String>,
+        patient_species: Option<String>,
+        patient_weight: Option<f64>,
+        patient_weight_unit: Option<String>,
+        patient_breed: Option<String>,
+    ) -> Result<FfiResolvedItem, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        db.limits()
+            .check_drug_name(&drug_name)
+            .map_err(FuzzyDrugsError::InvalidInput)?;
+        let normalizer = self.lock_normalizer()?.clone();
+        let resolver = Resolver::with_normalizer(&db, normalizer).with_config(db.scoring_config()?);
+
+        let patient_weight_kg = Self::patient_weight_kg(
+            resolver.normalizer(),
+            patient_weight,
+            patient_weight_unit.as_deref(),
+        )?;
+        let mention = Self::ffi_mention(drug_name, dose, unit, route, patient_species.clone());
+
+        let resolved = resolver.resolve(
+            &mention,
+            patient_species.as_deref(),
+            patient_weight_kg,
+            patient_breed.as_deref(),
+        )?;
+
+        Ok(resolved.into())
+    }
+
+    /// Explain how a drug mention resolves, for a "why this match" screen.
+    ///
+    /// Takes the same arguments as `resolve_mention`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn explain_mention(
+        &self,
+        drug_name: String,
+        dose: Option<f64>,
+        unit: Option<String>,
+        route: Option<String>,
+        patient_species: Option<String>,
+        patient_weight: Option<f64>,
+        patient_weight_unit: Option<String>,
+        patient_breed: Option<String>,
+    ) -> Result<FfiResolutionTrace, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        db.limits()
+            .check_drug_name(&drug_name)
+            .map_err(FuzzyDrugsError::InvalidInput)?;
+        let normalizer = self.lock_normalizer()?.clone();
+        let resolver = Resolver::with_normalizer(&db, normalizer).with_config(db.scoring_config()?);
+
+        let patient_weight_kg = Self::patient_weight_kg(
+            resolver.normalizer(),
+            patient_weight,
+            patient_weight_unit.as_deref(),
+        )?;
+        let mention = Self::ffi_mention(drug_name, dose, unit, route, patient_species.clone());
+
+        let (_, trace) = resolver.resolve_with_trace(
+            &mention,
+            patient_species.as_deref(),
+            patient_weight_kg,
+            patient_breed.as_deref(),
+        )?;
+
+        Ok(trace.into())
+    }
+
+    /// Resolve many mentions for a patient, reporting progress after each.
+    ///
+    /// Uses the patient's species, weight, and breed. Mentions with no
+    /// catalog match are returned by name. If `cancel` fires, no results are
+    /// returned and the call fails with `Cancelled`.
+    pub fn resolve_mentions_for_patient(
+        &self,
+        patient_id: String,
+        mentions: Vec<FfiDrugMention>,
+        progress: Option<Arc<dyn FfiProgressListener>>,
+        cancel: Option<Arc<FfiCancellationToken>>,
+    ) -> Result<FfiBatchResolution, FuzzyDrugsError> {
+        let progress = ForeignProgress {
+            listener: progress,
+            cancel,
+        };
+        let db = self.lock_db()?;
+        for mention in &mentions {
+            db.limits()
+                .check_drug_name(&mention.drug_name)
+                .map_err(FuzzyDrugsError::InvalidInput)?;
+        }
+        let patient = db
+            .get_patient(&patient_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Patient {}", patient_id)))?;
+        let species = patient.canonical_species();
+        let normalizer = self.lock_normalizer()?.clone();
+        let resolver = Resolver::with_normalizer(&db, normalizer)
+            .with_config(db.scoring_config()?)
+            .with_allergies(patient.allergies.clone());
+
+        let total = mentions.len();
+        let mut items = Vec::new();
+        let mut unmatched_drugs = Vec::new();
+        progress.step(0, total)?;
+        for (i, mention) in mentions.into_iter().enumerate() {
+            let mention: models::DrugMention = mention.into();
+            match resolver.resolve(
+                &mention,
+                Some(species.as_str()),
+                patient.weight_kg,
+                patient.breed.as_deref(),
+            ) {
+                Ok(item) => items.push(item.into()),
+                Err(resolver::ResolverError::NoCandidates(name)) => unmatched_drugs.push(name),
+                Err(e) => return Err(e.into()),
+            }
+            progress.step(i + 1, total)?;
+        }
+        Ok(FfiBatchResolution {
+            items,
+            unmatched_drugs,
+        })
+    }
+
+    /// Load alias/unit/route data from a JSON resource shipped with the app.
+    ///
+    /// On failure the current data (compiled-in by default) stays active.
+    pub fn load_normalizer_data(
+        &self,
+        path: String,
+    ) -> Result<FfiNormalizerDataInfo, FuzzyDrugsError> {
+        let normalizer = Normalizer::from_file(&path)
+            .map_err(|e| FuzzyDrugsError::InvalidInput(format!("Normalizer data: {}", e)))?;
+        let info = normalizer.data_info().clone();
+        *self.lock_normalizer()? = normalizer;
+        Ok(info.into())
+    }
+
+    /// Set the clinic's dictation language ("en", "es").
+    ///
+    /// Spanish adds Spanish drug names, unit words, route phrases, and spoken
+    /// numbers on top of the English tables. A `locale` in loaded normalizer
+    /// data sets this too. The language is stored and applied on the next
+    /// open.
+    pub fn set_normalizer_locale(&self, language: String) -> Result<(), FuzzyDrugsError> {
+        let locale = NormalizerLocale::parse(&language).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unsupported language: {}", language))
+        })?;
+        self.lock_db()?.set_locale_setting(locale.as_str())?;
+        self.lock_normalizer()?.set_locale(locale);
+        Ok(())
+    }
+
+    /// Report library and data versions.
+    pub fn get_capabilities(&self) -> Result<FfiCapabilities, FuzzyDrugsError> {
+        let normalizer = self.lock_normalizer()?;
+        Ok(FfiCapabilities {
+            core_version: env!("CARGO_PKG_VERSION").to_string(),
+            api_version: compat::API_VERSION,
+            normalizer_data: normalizer.data_info().clone().into(),
+            normalizer_locale: normalizer.locale().as_str().to_string(),
+            sync_protocol_version: merkle::SYNC_PROTOCOL_VERSION,
+        })
+    }
+
+    // =========================================================================
+    // Device Identity
+    // =========================================================================
+
+    /// Get this installation's identity.
+    ///
+    /// The device ID is provisioned when the database is first opened and is
+    /// stamped into every committed leaf and export.
+    pub fn get_device_identity(&self) -> Result<FfiDeviceIdentity, FuzzyDrugsError> {
+        Ok(self.lock_db()?.device_identity()?.into())
+    }
+
+    /// Rename this device (e.g., "Exam Room 2 iPad").
+    pub fn rename_device(&self, name: String) -> Result<FfiDeviceIdentity, FuzzyDrugsError> {
+        if name.trim().is_empty() {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "Device name cannot be empty".into(),
+            ));
+        }
+        let db = self.lock_db()?;
+        db.set_device_name(&name)?;
+        Ok(db.device_identity()?.into())
+    }
+
+    /// Register a key fingerprint, replacing any earlier one for the purpose.
+    pub fn set_key_fingerprint(
+        &self,
+        purpose: String,
+        fingerprint: String,
+    ) -> Result<FfiDeviceIdentity, FuzzyDrugsError> {
+        if purpose.trim().is_empty() || fingerprint.trim().is_empty() {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "Key purpose and fingerprint are required".into(),
+            ));
+        }
+        let db = self.lock_db()?;
+        Ok(db
+            .set_key_fingerprint(purpose.trim(), fingerprint.trim())?
+            .into())
+    }
+
+    /// Remove the key fingerprint for a purpose. Returns false if none was set.
+    pub fn remove_key_fingerprint(&self, purpose: String) -> Result<bool, FuzzyDrugsError> {
+        Ok(self.lock_db()?.remove_key_fingerprint(&purpose)?)
+    }
+
+    // =========================================================================
+    // Merkle Tree Operations
+    // =========================================================================
+
+    /// Commit a reviewed encounter to the Merkle tree.
+    pub fn commit_encounter(
+        &self,
+        encounter: FfiReviewedEncounter,
+    ) -> Result<FfiLeafCommit, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let was_unsynced = Self::has_unsynced(&db)?;
+        let draft = db.get_draft(&encounter.draft_id)?;
+        let draft_id = encounter.draft_id.clone();
+        let commit = Self::commit_reviewed(&db, draft.as_ref(), encounter.into())?;
+        let events = Self::commit_events(
+            &db,
+            &draft_id,
+            draft.as_ref().map(|d| &d.status),
+            &commit.leaf_hash,
+            was_unsynced,
+        )?;
+        drop(db);
+        self.notify(events);
+        Ok(commit.into())
+    }
+
+    /// Reviewed drafts not committed within `older_than_minutes`, oldest first.
+    pub fn list_pending_commits(
+        &self,
+        older_than_minutes: u32,
+    ) -> Result<Vec<FfiPendingCommit>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let pending = db.pending_commits(older_than_minutes)?;
+        Ok(pending.into_iter().map(|p| p.into()).collect())
+    }
+
+    /// Finish committing a reviewed draft whose commit was interrupted.
+    ///
+    /// The encounter is rebuilt from the draft's stored review decisions. If
+    /// its leaf already reached the tree, the draft is only marked committed
+    /// and the existing leaf is returned.
+    pub fn resume_pending_commit(
+        &self,
+        draft_id: String,
+        reviewed_by: String,
+    ) -> Result<FfiLeafCommit, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let draft = db
+            .get_draft(&draft_id)?
+            .ok_or_else(|| FuzzyDrugsError::NotFound(format!("Draft {}", draft_id)))?;
+        if draft.status != DraftStatus::Reviewed {
+            return Err(FuzzyDrugsError::conflict(
+                "draft",
+                &draft_id,
+                "not_awaiting_commit",
+                format!("Draft {} is not awaiting commit", draft_id),
+            ));
+        }
+        let was_unsynced = Self::has_unsynced(&db)?;
+        let commit = match db.find_encounter_leaf(&draft_id)? {
+            Some(leaf_hash) => {
+                db.mark_draft_committed(&draft_id)?;
+                MerkleTree::new(&db).leaf_commit(&leaf_hash)?
+            }
+            None => {
+                let reviewed =
+                    ReviewedEncounter::from_draft(&draft, reviewed_by).ok_or_else(|| {
+                        FuzzyDrugsError::InvalidInput(format!(
+                            "Draft {} has items awaiting review",
+                            draft_id
+                        ))
+                    })?;
+                Self::commit_reviewed(&db, Some(&draft), reviewed)?
+            }
+        };
+        let events = Self::commit_events(
+            &db,
+            &draft_id,
+            Some(&draft.status),
+            &commit.leaf_hash,
+            was_unsynced,
+        )?;
+        drop(db);
+        self.notify(events);
+        Ok(commit.into())
+    }
+
+    /// Counts for the home screen: drafts awaiting review, reviewed drafts
+    /// stuck before commit for over `stale_commit_minutes`, and unsynced work.
+    pub fn get_dashboard_summary(
+        &self,
+        stale_commit_minutes: u32,
+    ) -> Result<FfiDashboardSummary, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let pending_review_count = db.list_drafts_by_status(&DraftStatus::PendingReview)?.len();
+        let pending_commits = db.pending_commits(stale_commit_minutes)?;
+        let has_unsynced_changes = merkle::SyncManager::new(&db).has_unsynced_changes()?;
+        Ok(FfiDashboardSummary {
+            pending_review_count: pending_review_count as u32,
+            pending_commits: pending_commits.into_iter().map(|p| p.into()).collect(),
+            has_unsynced_changes,
+        })
+    }
+
+    /// Get current tree statistics.
+    pub fn get_tree_stats(&self) -> Result<FfiTreeStats, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let tree = MerkleTree::new(&db);
+        let stats = tree.get_stats()?;
+        Ok(stats.into())
+    }
+
+    /// Inclusion proof for a leaf against the current root.
+    pub fn get_inclusion_proof(
+        &self,
+        leaf_hash: String,
+    ) -> Result<FfiInclusionProof, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let tree = MerkleTree::new(&db);
+        if tree.get_leaf_payload(&leaf_hash)?.is_none() {
+            return Err(FuzzyDrugsError::NotFound(format!("Leaf {}", leaf_hash)));
+        }
+        let proof = tree.generate_proof(&leaf_hash)?;
+        Ok(proof.into())
+    }
+
+    /// Check if there are unsynced changes.
+    pub fn has_unsynced_changes(&self) -> Result<bool, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let sync_manager = merkle::SyncManager::new(&db);
+        Ok(sync_manager.has_unsynced_changes()?)
+    }
+
+    /// Get the sync status for a status screen: last catalog, encounter,
+    /// and patient sync times, unsynced leaves, outbox depth, the latest
+    /// sync error, and whether the PIMS root matches ours.
+    pub fn get_sync_status(&self) -> Result<FfiSyncStatus, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        Ok(merkle::SyncManager::new(&db).get_sync_status()?.into())
+    }
+
+    /// Sync committed encounters to the PIMS through a host-app HTTP
+    /// client: send the root, send the nodes the PIMS is missing, and record
+    /// the acknowledged root as synced. The client POSTs JSON to paths
+    /// relative to the PIMS base URL ("sync/request", then "sync/payload").
+    ///
+    /// The database lock is held only to build each request and record each
+    /// reply, never while the client is waiting on the PIMS; one sync runs at
+    /// a time. Transport failures and rejected syncs fail with `SyncError`.
+    pub fn run_sync(
+        &self,
+        transport: Arc<dyn FfiSyncTransport>,
+    ) -> Result<FfiSyncReport, FuzzyDrugsError> {
+        self.sync_over(&ForeignSyncTransport(transport))
+    }
+
+    /// `run_sync` with the built-in HTTP client against the PIMS at
+    /// `base_url`, sending `auth_token` as a bearer token. Builds without the
+    /// `http` feature fail with `InvalidInput`.
+    pub fn run_http_sync(
+        &self,
+        base_url: String,
+        auth_token: Option<String>,
+    ) -> Result<FfiSyncReport, FuzzyDrugsError> {
+        #[cfg(feature = "http")]
+        {
+            let mut transport = merkle::HttpSyncTransport::new(base_url)?;
+            if let Some(token) = auth_token {
+                transport = transport.with_auth_token(token);
+            }
+            self.sync_over(&transport)
+        }
+        #[cfg(not(feature = "http"))]
+        {
+            let _ = (base_url, auth_token);
+            Err(FuzzyDrugsError::InvalidInput(
+                "HTTP sync is not enabled in this build".into(),
+            ))
+        }
+    }
+
+    /// The latest sync conflict (PIMS tree diverged from the one last
+    /// synced), resolved or not.
+    pub fn get_sync_conflict(&self) -> Result<Option<FfiSyncConflict>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let conflict = merkle::SyncManager::new(&db).get_sync_conflict()?;
+        Ok(conflict.map(|c| c.into()))
+    }
+
+    /// Unblock sync after a conflict held under the "manual" strategy:
+    /// "local_wins" pushes our tree as usual, "server_rebase" has the PIMS
+    /// re-append our leaves onto its tree. Applied by the next sync.
+    pub fn resolve_sync_conflict(
+        &self,
+        strategy: String,
+    ) -> Result<FfiSyncConflict, FuzzyDrugsError> {
+        let strategy = match models::SyncConflictStrategy::parse(&strategy) {
+            Some(models::SyncConflictStrategy::Manual) | None => {
+                return Err(FuzzyDrugsError::InvalidInput(format!(
+                    "Unknown conflict resolution: {}",
+                    strategy
+                )))
+            }
+            Some(strategy) => strategy,
+        };
+        let db = self.lock_db()?;
+        let manager = merkle::SyncManager::new(&db);
+        if !manager.get_sync_conflict()?.is_some_and(|c| c.is_open()) {
+            return Err(FuzzyDrugsError::NotFound("Open sync conflict".into()));
+        }
+        Ok(manager.resolve_conflict(strategy)?.into())
+    }
+
+    /// Retry every sync outbox operation that is due: queued encounters go
+    /// out in one tree sync, queued patients are upserted ("sync/patients")
+    /// and linked to the PIMS ID they get back. Operations that fail are
+    /// retried with exponential backoff on later calls, and marked failed
+    /// once out of attempts.
+    ///
+    /// Call on a timer and when connectivity returns; `next_attempt_at` says
+    /// when the next operation is due. Transport failures don't fail the
+    /// call; they're recorded on the operations.
+    pub fn process_sync_outbox(
+        &self,
+        transport: Arc<dyn FfiSyncTransport>,
+    ) -> Result<FfiOutboxReport, FuzzyDrugsError> {
+        self.process_outbox_over(&ForeignSyncTransport(transport))
+    }
+
+    /// `process_sync_outbox` with the built-in HTTP client (see
+    /// `run_http_sync`).
+    pub fn process_http_sync_outbox(
+        &self,
+        base_url: String,
+        auth_token: Option<String>,
+    ) -> Result<FfiOutboxReport, FuzzyDrugsError> {
+        #[cfg(feature = "http")]
+        {
+            let mut transport = merkle::HttpSyncTransport::new(base_url)?;
+            if let Some(token) = auth_token {
+                transport = transport.with_auth_token(token);
+            }
+            self.process_outbox_over(&transport)
+        }
+        #[cfg(not(feature = "http"))]
+        {
+            let _ = (base_url, auth_token);
+            Err(FuzzyDrugsError::InvalidInput(
+                "HTTP sync is not enabled in this build".into(),
+            ))
+        }
+    }
+
+    /// Queued sync operations, oldest first, with attempts and last error.
+    pub fn list_sync_outbox(&self) -> Result<Vec<FfiOutboxItem>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        Ok(db.list_outbox()?.into_iter().map(|i| i.into()).collect())
+    }
+
+    /// Make a queued operation due now with a fresh set of attempts (e.g. a
+    /// failed one, once its cause is fixed).
+    pub fn retry_sync_outbox_item(&self, id: i64) -> Result<FfiOutboxItem, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        Ok(db.retry_outbox_item(id, chrono::Utc::now())?.into())
+    }
+
+    /// Catalog sync request to send to the PIMS (last applied delta time).
+    pub fn create_catalog_sync_request(&self) -> Result<FfiCatalogSyncRequest, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let request = merkle::SyncManager::new(&db).create_catalog_sync_request()?;
+        Ok(FfiCatalogSyncRequest {
+            since: request.since,
+            retry_skus: request.retry_skus,
+        })
+    }
+
+    /// Apply a catalog delta from the PIMS: upsert its items, deactivate
+    /// removed SKUs, and record its timestamp for the next request.
+    ///
+    /// Items that fail validation are skipped, returned in the report, and
+    /// named in `get_sync_status().last_error`; the rest apply. The next
+    /// sync request lists the skipped SKUs in `retry_skus` until a delta
+    /// applies them. Locally managed dose ranges are kept.
+    pub fn apply_catalog_delta(
+        &self,
+        delta: FfiCatalogDelta,
+    ) -> Result<FfiCatalogUpsertReport, FuzzyDrugsError> {
+        if delta.timestamp.trim().is_empty() {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "Catalog delta timestamp is required".into(),
+            ));
+        }
+        for item in &delta.items {
+            if let Some(schedule) = &item.controlled_schedule {
+                if ControlledSchedule::parse(schedule).is_none() {
+                    return Err(FuzzyDrugsError::InvalidInput(format!(
+                        "Unknown controlled schedule for {}: {}",
+                        item.sku, schedule
+                    )));
+                }
+            }
+        }
+        let skus = delta
+            .items
+            .iter()
+            .map(|item| item.sku.clone())
+            .chain(delta.deactivated_skus.iter().cloned())
+            .collect::<Vec<_>>();
+        let report = {
+            let db = self.lock_db()?;
+            merkle::SyncManager::new(&db).apply_catalog_delta(&delta.into())?
+        };
+        if !skus.is_empty() {
+            self.notify(vec![CoreEvent::CatalogUpdated { skus }]);
+        }
+        Ok(report.into())
+    }
+
+    /// Catalog items added or changed on this device since the last push,
+    /// to send to the PIMS. Until the PIMS accepts them, catalog deltas
+    /// leave them as they are.
+    pub fn create_catalog_push_delta(&self) -> Result<FfiCatalogPushDelta, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let push = merkle::SyncManager::new(&db).create_catalog_push_delta()?;
+        Ok(push.into())
+    }
+
+    /// Apply the PIMS reply to a catalog push: accepted items are linked to
+    /// their server IDs and stop being pushed. Returns how many were accepted.
+    pub fn apply_catalog_push_ack(&self, ack: FfiCatalogPushAck) -> Result<u32, FuzzyDrugsError> {
+        if ack.timestamp.trim().is_empty() {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "Catalog push timestamp is required".into(),
+            ));
+        }
+        let db = self.lock_db()?;
+        let accepted = merkle::SyncManager::new(&db).apply_catalog_push_ack(&ack.into())?;
+        Ok(accepted as u32)
+    }
+
+    /// Patient sync request to send to the PIMS (last applied delta time).
+    pub fn create_patient_sync_request(&self) -> Result<FfiPatientSyncRequest, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let request = merkle::SyncManager::new(&db).create_patient_sync_request()?;
+        Ok(FfiPatientSyncRequest {
+            since: request.since,
+        })
+    }
+
+    /// Apply a patient delta from the PIMS and record its timestamp for the
+    /// next request.
+    ///
+    /// Patients already linked to a server ID are updated; a local patient
+    /// with the same name, owner, and date of birth is linked rather than
+    /// duplicated; the rest are created. Applied atomically. Encounters
+    /// committed for a linked patient carry its server ID.
+    pub fn apply_patient_delta(
+        &self,
+        delta: FfiPatientDelta,
+    ) -> Result<FfiPatientSyncReport, FuzzyDrugsError> {
+        if delta.timestamp.trim().is_empty() {
+            return Err(FuzzyDrugsError::InvalidInput(
+                "Patient delta timestamp is required".into(),
+            ));
+        }
+        let db = self.lock_db()?;
+        let report = merkle::SyncManager::new(&db).apply_patient_delta(&delta.into())?;
+        Ok(report.into())
+    }
+
+    /// Pull PIMS patients changed since the last pull through a host-app
+    /// HTTP client ("sync/patients/changes") and apply them as
+    /// `apply_patient_delta` does. Transport failures fail with `SyncError`.
+    pub fn pull_patients(
+        &self,
+        transport: Arc<dyn FfiSyncTransport>,
+    ) -> Result<FfiPatientSyncReport, FuzzyDrugsError> {
+        let transport = ForeignSyncTransport(transport);
+        let _sync = self.lock_sync();
+        let report = merkle::SyncEngine::new(&LockedDatabase(self), &transport).pull_patients()?;
+        Ok(report.into())
+    }
+
+    // =========================================================================
+    // Legal Holds
+    // =========================================================================
+
+    /// Place a legal hold on a patient or encounter.
+    ///
+    /// `subject_type` is "patient" (subject_id = patient local ID) or
+    /// "encounter" (subject_id = draft ID). Held data cannot be deleted or
+    /// have its transcript changed. The placement is committed as an audit leaf.
+    pub fn place_legal_hold(
+        &self,
+        subject_type: String,
+        subject_id: String,
+        reason: String,
+        placed_by: String,
+    ) -> Result<FfiLeafCommit, FuzzyDrugsError> {
+        let subject_type = parse_hold_subject(&subject_type)?;
+        let db = self.lock_db()?;
+        let exists = match subject_type {
+            models::HoldSubject::Patient => db.get_patient(&subject_id)?.is_some(),
+            models::HoldSubject::Encounter => db.get_draft(&subject_id)?.is_some(),
+        };
+        if !exists {
+            return Err(FuzzyDrugsError::NotFound(format!(
+                "{} {}",
+                subject_type.as_str(),
+                subject_id
+            )));
+        }
+
+        let hold = models::LegalHold::new(subject_type, subject_id, reason, placed_by);
+        if !db.place_legal_hold(&hold)? {
+            return Err(FuzzyDrugsError::conflict(
+                hold.subject_type.as_str(),
+                &hold.subject_id,
+                "already_held",
+                format!(
+                    "{} {} is already under legal hold",
+                    hold.subject_type.as_str(),
+                    hold.subject_id
+                ),
+            ));
+        }
+
+        let was_unsynced = Self::has_unsynced(&db)?;
+        let tree = MerkleTree::new(&db);
+        let commit = tree.commit_audit_event(&models::AuditEvent::legal_hold_placed(&hold))?;
+        let events = Self::sync_state_event(&db, was_unsynced)?;
+        drop(db);
+        self.notify(events.into_iter().collect());
+        Ok(commit.into())
+    }
+
+    /// Release a legal hold. The removal is committed as an audit leaf.
+    pub fn release_legal_hold(
+        &self,
+        subject_type: String,
+        subject_id: String,
+        released_by: String,
+        reason: String,
+    ) -> Result<FfiLeafCommit, FuzzyDrugsError> {
+        let subject_type = parse_hold_subject(&subject_type)?;
+        let db = self.lock_db()?;
+        if !db.release_legal_hold(subject_type, &subject_id)? {
+            return Err(FuzzyDrugsError::NotFound(format!(
+                "Legal hold on {} {}",
+                subject_type.as_str(),
+                subject_id
+            )));
+        }
+
+        let was_unsynced = Self::has_unsynced(&db)?;
+        let event =
+            models::AuditEvent::legal_hold_released(subject_type, subject_id, released_by, reason);
+        let tree = MerkleTree::new(&db);
+        let commit = tree.commit_audit_event(&event)?;
+        let events = Self::sync_state_event(&db, was_unsynced)?;
+        drop(db);
+        self.notify(events.into_iter().collect());
+        Ok(commit.into())
+    }
+
+    /// List active legal holds.
+    pub fn list_legal_holds(&self) -> Result<Vec<FfiLegalHold>, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let holds = db.list_legal_holds()?;
+        Ok(holds.into_iter().map(|h| h.into()).collect())
+    }
+
+    // =========================================================================
+    // Export Operations
+    // =========================================================================
+
+    /// Sign batch JSON exports with an Ed25519 key from its 32-byte secret,
+    /// or stop signing with `None`. Returns the public key (hex) to give
+    /// auditors for `verify_export`.
+    ///
+    /// The key is held in memory only; the host app keeps the secret (e.g.
+    /// in the Keychain) and sets it again after opening the database.
+    pub fn set_export_signing_key(
+        &self,
+        secret_key: Option<Vec<u8>>,
+    ) -> Result<Option<String>, FuzzyDrugsError> {
+        let key = secret_key
+            .map(|secret| {
+                <[u8; 32]>::try_from(secret)
+                    .map(|bytes| SigningKey::from_bytes(&bytes))
+                    .map_err(|_| {
+                        FuzzyDrugsError::InvalidInput("Signing key must be 32 bytes".into())
+                    })
+            })
+            .transpose()?;
+        let public_key = key
+            .as_ref()
+            .map(|key| hex::encode(key.verifying_key().as_bytes()));
+        *self
+            .signing_key
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = key;
+        Ok(public_key)
+    }
+
+    /// Export billing data as JSON.
+    pub fn export_billing_json(&self) -> Result<String, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let exporter = self.billing_exporter(&db);
+        let batch = exporter.export_all()?;
+        Ok(batch.to_json()?)
+    }
+
+    /// Export billing as JSON with patient IDs replaced by stable
+    /// pseudonyms, for sharing outside the clinic. Marked `redacted`.
+    pub fn export_billing_json_deidentified(&self) -> Result<String, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let deidentifier = self.deidentifier(&db, "strip")?;
+        let exporter = self.billing_exporter(&db).with_deidentifier(deidentifier);
+        Ok(exporter.export_all()?.to_json()?)
+    }
+
+    /// Export billing data as CSV in the stored layout.
+    pub fn export_billing_csv(&self) -> Result<String, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let exporter = self.billing_exporter(&db);
+        let batch = exporter.export_all()?;
+        Ok(batch.to_csv_with_layout(&db.billing_csv_layout()?))
+    }
+
+    /// Export billing for the encounters and items matching `filter`, as
+    /// "json" or "csv" (e.g. one client's charges for today).
+    pub fn export_billing_filtered(
+        &self,
+        filter: FfiBillingFilter,
+        format: String,
+    ) -> Result<String, FuzzyDrugsError> {
+        let format = export::ExportFormat::parse(&format).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unknown export format: {}", format))
+        })?;
+        let filter = export::BillingFilter {
+            patient_id: filter.patient_id,
+            client_id: filter.client_id,
+            visit_id: filter.visit_id,
+            reviewed_by: filter.reviewed_by,
+            start: filter.start.as_deref().map(parse_timestamp).transpose()?,
+            end: filter.end.as_deref().map(parse_timestamp).transpose()?,
+            skus: filter.skus,
+        };
+        let db = self.lock_db()?;
+        let batch = self.billing_exporter(&db).export_filtered(&filter)?;
+        match format {
+            export::ExportFormat::Json => Ok(batch.to_json()?),
+            export::ExportFormat::Csv => Ok(batch.to_csv_with_layout(&db.billing_csv_layout()?)),
+            export::ExportFormat::Iif => Err(FuzzyDrugsError::InvalidInput(
+                "Filtered billing exports are JSON or CSV".into(),
+            )),
+        }
+    }
+
+    /// Export billing for encounters not yet in a pending or imported batch,
+    /// as "json" or "csv", and record them as a new pending batch. Confirm
+    /// with `mark_export_batch_imported` once the PIMS has the charges, or
+    /// `void_export_batch` to export them again. `batch_id` is `None` when
+    /// nothing was unbilled.
+    pub fn export_unbilled(&self, format: String) -> Result<FfiUnbilledExport, FuzzyDrugsError> {
+        let format = export::ExportFormat::parse(&format).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unknown export format: {}", format))
+        })?;
+        let db = self.lock_db()?;
+        let layout = match format {
+            export::ExportFormat::Json => None,
+            export::ExportFormat::Csv => Some(db.billing_csv_layout()?),
+            export::ExportFormat::Iif => {
+                return Err(FuzzyDrugsError::InvalidInput(
+                    "Unbilled exports are JSON or CSV".into(),
+                ))
+            }
+        };
+        let batch = self.billing_exporter(&db).export_unbilled()?;
+        let data = match layout {
+            Some(layout) => batch.to_csv_with_layout(&layout),
+            None => batch.to_json()?,
+        };
+        Ok(FfiUnbilledExport {
+            batch_id: batch.batch_id,
+            encounter_count: batch.encounters.len() as u32,
+            data,
+        })
+    }
+
+    /// Confirm the PIMS imported an unbilled export batch. Its encounters
+    /// stay billed for good.
+    pub fn mark_export_batch_imported(
+        &self,
+        batch_id: String,
+    ) -> Result<FfiExportBatch, FuzzyDrugsError> {
+        self.set_export_batch_status(&batch_id, models::ExportBatchStatus::Imported)
+    }
+
+    /// Discard a pending export batch (e.g. the import failed) so its
+    /// encounters are exported again by the next `export_unbilled`.
+    pub fn void_export_batch(&self, batch_id: String) -> Result<FfiExportBatch, FuzzyDrugsError> {
+        self.set_export_batch_status(&batch_id, models::ExportBatchStatus::Void)
+    }
+
+    /// Unbilled export batches, newest first.
+    pub fn list_export_batches(&self) -> Result<Vec<FfiExportBatch>, FuzzyDrugsError> {
+        let batches = self.lock_db()?.list_export_batches()?;
+        Ok(batches.into_iter().map(Into::into).collect())
+    }
+
+    /// Export billing as an Excel workbook: a summary sheet plus one sheet
+    /// per encounter. Builds without the `xlsx` feature fail with
+    /// `InvalidInput`.
+    pub fn export_billing_xlsx(&self) -> Result<Vec<u8>, FuzzyDrugsError> {
+        #[cfg(feature = "xlsx")]
+        {
+            let batch = self.billing_exporter(&*self.lock_db()?).export_all()?;
+            Ok(batch.to_xlsx()?)
+        }
+        #[cfg(not(feature = "xlsx"))]
+        {
+            Err(FuzzyDrugsError::InvalidInput(
+                "XLSX exports are not enabled in this build".into(),
+            ))
+        }
+    }
+
+    /// Export billing as a QuickBooks Desktop IIF file, one invoice per
+    /// encounter billed to the patient's owner, using the stored
+    /// QuickBooks mapping.
+    pub fn export_billing_iif(&self) -> Result<String, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let mut out = Vec::new();
+        self.billing_exporter(&db)
+            .write_all(&mut out, export::ExportFormat::Iif, &())?;
+        String::from_utf8(out).map_err(|e| FuzzyDrugsError::SerializationError(e.to_string()))
+    }
+
+    /// Printable PDF invoice for a committed encounter: patient and owner,
+    /// line items at current catalog prices, total, and the Merkle leaf hash
+    /// as a verification footer. Builds without the `pdf` feature fail with
+    /// `InvalidInput`.
+    pub fn export_invoice_pdf(
+        &self,
+        leaf_hash: String,
+        clinic_name: Option<String>,
+    ) -> Result<Vec<u8>, FuzzyDrugsError> {
+        #[cfg(feature = "pdf")]
+        {
+            let invoice = {
+                let db = self.lock_db()?;
+                self.billing_exporter(&db)
+                    .invoice_by_hash(&leaf_hash, clinic_name)?
+            };
+            Ok(invoice.to_pdf()?)
+        }
+        #[cfg(not(feature = "pdf"))]
+        {
+            let _ = (leaf_hash, clinic_name);
+            Err(FuzzyDrugsError::InvalidInput(
+                "PDF invoices are not enabled in this build".into(),
+            ))
+        }
+    }
+
+    /// Medical-record note for a committed encounter, as "markdown" or
+    /// "text": patient, date, reviewing vet, medications with dose, route,
+    /// and taper directions, and notes. For pasting into a PIMS that can't
+    /// ingest JSON.
+    pub fn render_summary(
+        &self,
+        leaf_hash: String,
+        format: String,
+    ) -> Result<String, FuzzyDrugsError> {
+        let format = export::SummaryFormat::parse(&format).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unknown summary format: {}", format))
+        })?;
+        let db = self.lock_db()?;
+        Ok(export::SummaryExporter::new(&db).render_summary(&leaf_hash, format)?)
+    }
+
+    /// DEA controlled substance log for encounters reviewed after `start`
+    /// and up to `end` (RFC 3339, or "YYYY-MM-DD HH:MM:SS" in UTC), as
+    /// "csv" or "json".
+    ///
+    /// `opening_balances` is stock on hand at `start` in dispensing units
+    /// (tablets, mL); each entry's balance is what remains after it. SKUs
+    /// without an opening balance have no balance column.
+    pub fn export_controlled_substance_log(
+        &self,
+        start: String,
+        end: String,
+        format: String,
+        opening_balances: Vec<FfiStockBalance>,
+    ) -> Result<String, FuzzyDrugsError> {
+        let format = export::ExportFormat::parse(&format).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unknown export format: {}", format))
+        })?;
+        let log = self.controlled_substance_log(&start, &end, opening_balances)?;
+        Ok(match format {
+            export::ExportFormat::Csv => log.to_csv(),
+            export::ExportFormat::Json => log.to_json()?,
+            export::ExportFormat::Iif => {
+                return Err(FuzzyDrugsError::InvalidInput(
+                    "IIF is only available for billing exports".into(),
+                ))
+            }
+        })
+    }
+
+    /// `export_controlled_substance_log` as a printable PDF. Builds without
+    /// the `pdf` feature fail with `InvalidInput`.
+    pub fn export_controlled_substance_log_pdf(
+        &self,
+        start: String,
+        end: String,
+        opening_balances: Vec<FfiStockBalance>,
+    ) -> Result<Vec<u8>, FuzzyDrugsError> {
+        #[cfg(feature = "pdf")]
+        {
+            let log = self.controlled_substance_log(&start, &end, opening_balances)?;
+            Ok(log.to_pdf()?)
+        }
+        #[cfg(not(feature = "pdf"))]
+        {
+            let _ = (start, end, opening_balances);
+            Err(FuzzyDrugsError::InvalidInput(
+                "PDF exports are not enabled in this build".into(),
+            ))
+        }
+    }
+
+    /// Write an audit bundle for encounters committed after `start` and up
+    /// to `end` (RFC 3339, or "YYYY-MM-DD HH:MM:SS" in UTC) as a zip at
+    /// `path`: the period's compliance JSON, the full Merkle tree,
+    /// verification steps, and a manifest with every file's SHA-256 and the
+    /// root hash, signed with the export signing key if set. Auditors check
+    /// the manifest with `verify_export`. Builds without the `zip` feature
+    /// fail with `InvalidInput`.
+    pub fn export_audit_bundle(
+        &self,
+        path: String,
+        start: String,
+        end: String,
+    ) -> Result<FfiAuditBundle, FuzzyDrugsError> {
+        #[cfg(feature = "zip")]
+        {
+            let start = parse_since_timestamp(&start)?;
+            let end = parse_since_timestamp(&end)?;
+            let normalizer_data = self.lock_normalizer()?.data_info().clone();
+            let db = self.lock_db()?;
+            if db.get_merkle_root()?.root_hash.is_none() {
+                return Err(FuzzyDrugsError::InvalidInput("Tree is empty".into()));
+            }
+            let compliance = self
+                .compliance_exporter(&db, normalizer_data)?
+                .export_date_range(&start, &end)?;
+            let tree = merkle::SyncManager::new(&db).export_full_tree()?;
+            drop(db);
+
+            let signing_key = self.export_signing_key();
+            let bundle =
+                export::AuditBundle::new(&compliance, &tree, &start, &end, signing_key.as_ref())?;
+            let bytes = bundle.write_zip(std::path::Path::new(&path))?;
+            Ok(FfiAuditBundle {
+                path,
+                bytes,
+                root_hash: bundle.manifest.root_hash,
+                leaf_count: bundle.manifest.leaf_count,
+                encounter_count: bundle.manifest.encounter_count as u64,
+                signed: signing_key.is_some(),
+                created_at: bundle.manifest.created_at,
+            })
+        }
+        #[cfg(not(feature = "zip"))]
+        {
+            let _ = (path, start, end);
+            Err(FuzzyDrugsError::InvalidInput(
+                "Audit bundles are not enabled in this build".into(),
+            ))
+        }
+    }
+
+    /// Export one committed encounter as a FHIR R4 `Bundle` (JSON).
+    ///
+    /// Line items become `MedicationAdministration` (given in clinic),
+    /// `MedicationDispense` (sent home), or `MedicationRequest` (prescribed).
+    pub fn export_fhir_encounter(&self, leaf_hash: String) -> Result<String, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let bundle = export::FhirExporter::new(&db).export_by_hash(&leaf_hash)?;
+        Ok(bundle.to_json()?)
+    }
+
+    /// Export committed encounters as one FHIR R4 `Bundle` (JSON): those
+    /// with the given leaf hashes, or all of them if `leaf_hashes` is `None`.
+    pub fn export_fhir_bundle(
+        &self,
+        leaf_hashes: Option<Vec<String>>,
+    ) -> Result<String, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        let exporter = export::FhirExporter::new(&db);
+        let bundle = match leaf_hashes {
+            Some(hashes) => exporter.export_hashes(&hashes)?,
+            None => exporter.export_all()?,
+        };
+        Ok(bundle.to_json()?)
+    }
+
+    /// Stream billing data to a file instead of returning it as a String.
+    ///
+    /// `format` is "json" or "csv". Returns a manifest with the file's size,
+    /// SHA-256 checksum, and line item count. An existing file at `path` is
+    /// replaced only once the export has been fully written.
+    pub fn export_billing_to_file(
+        &self,
+        path: String,
+        format: String,
+    ) -> Result<FfiExportManifest, FuzzyDrugsError> {
+        let format = export::ExportFormat::parse(&format).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unknown export format: {}", format))
+        })?;
+        let db = self.lock_db()?;
+        let exporter = self.billing_exporter(&db);
+        let manifest = exporter.export_all_to_file(std::path::Path::new(&path), format)?;
+        Ok(manifest.into())
+    }
+
+    /// `export_billing_to_file`, reporting progress per encounter. If
+    /// `cancel` fires, the partial file is removed and any existing file at
+    /// `path` is left as it was.
+    pub fn export_billing_to_file_with_progress(
+        &self,
+        path: String,
+        format: String,
+        progress: Option<Arc<dyn FfiProgressListener>>,
+        cancel: Option<Arc<FfiCancellationToken>>,
+    ) -> Result<FfiExportManifest, FuzzyDrugsError> {
+        let format = export::ExportFormat::parse(&format).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unknown export format: {}", format))
+        })?;
+        let progress = ForeignProgress {
+            listener: progress,
+            cancel,
+        };
+        let db = self.lock_db()?;
+        let exporter = self.billing_exporter(&db);
+        let manifest = exporter.export_all_to_file_with_progress(
+            std::path::Path::new(&path),
+            format,
+            &progress,
+        )?;
+        Ok(manifest.into())
+    }
+
+    /// Export compliance data as JSON.
+    pub fn export_compliance_json(&self) -> Result<String, FuzzyDrugsError> {
+        let normalizer_data = self.lock_normalizer()?.data_info().clone();
+        let db = self.lock_db()?;
+        let exporter = self.compliance_exporter(&db, normalizer_data)?;
+        let batch = exporter.export_all()?;
+        Ok(batch.to_json()?)
+    }
+
+    /// Export compliance data as JSON with patient IDs replaced by stable
+    /// pseudonyms and transcripts, notes, and original mentions redacted:
+    /// `free_text` is "strip" (emptied) or "hash" (salted SHA-256, so
+    /// repeated text can still be matched). Marked `redacted`.
+    pub fn export_compliance_json_deidentified(
+        &self,
+        free_text: String,
+    ) -> Result<String, FuzzyDrugsError> {
+        let normalizer_data = self.lock_normalizer()?.data_info().clone();
+        let db = self.lock_db()?;
+        let deidentifier = self.deidentifier(&db, &free_text)?;
+        let exporter = self
+            .compliance_exporter(&db, normalizer_data)?
+            .with_deidentifier(deidentifier);
+        Ok(exporter.export_all()?.to_json()?)
+    }
+
+    /// Export compliance data as an Excel workbook: a summary sheet with
+    /// proof status plus one sheet per encounter with its audit path.
+    /// Builds without the `xlsx` feature fail with `InvalidInput`.
+    pub fn export_compliance_xlsx(&self) -> Result<Vec<u8>, FuzzyDrugsError> {
+        #[cfg(feature = "xlsx")]
+        {
+            let normalizer_data = self.lock_normalizer()?.data_info().clone();
+            let db = self.lock_db()?;
+            let batch = self
+                .compliance_exporter(&db, normalizer_data)?
+                .export_all()?;
+            drop(db);
+            Ok(batch.to_xlsx()?)
+        }
+        #[cfg(not(feature = "xlsx"))]
+        {
+            Err(FuzzyDrugsError::InvalidInput(
+                "XLSX exports are not enabled in this build".into(),
+            ))
+        }
+    }
+
+    /// `export_compliance_json`, reporting progress per encounter. If
+    /// `cancel` fires, nothing is returned and the call fails with
+    /// `Cancelled`.
+    pub fn export_compliance_json_with_progress(
+        &self,
+        progress: Option<Arc<dyn FfiProgressListener>>,
+        cancel: Option<Arc<FfiCancellationToken>>,
+    ) -> Result<String, FuzzyDrugsError> {
+        let progress = ForeignProgress {
+            listener: progress,
+            cancel,
+        };
+        let normalizer_data = self.lock_normalizer()?.data_info().clone();
+        let db = self.lock_db()?;
+        let exporter = self.compliance_exporter(&db, normalizer_data)?;
+        let batch = exporter.export_all_with_progress(&progress)?;
+        Ok(batch.to_json()?)
+    }
+
+    /// Stream compliance data for all encounters to a JSON file at `path`,
+    /// one encounter at a time, instead of building it in memory.
+    ///
+    /// Returns a manifest with the file's size, SHA-256 checksum, and
+    /// encounter count. An existing file at `path` is replaced only once the
+    /// export has been fully written.
+    pub fn export_compliance_to_file(
+        &self,
+        path: String,
+    ) -> Result<FfiExportManifest, FuzzyDrugsError> {
+        self.export_compliance_to_file_with_progress(path, None, None)
+    }
+
+    /// `export_compliance_to_file`, reporting progress per encounter. If
+    /// `cancel` fires, the partial file is removed and any existing file at
+    /// `path` is left as it was.
+    pub fn export_compliance_to_file_with_progress(
+        &self,
+        path: String,
+        progress: Option<Arc<dyn FfiProgressListener>>,
+        cancel: Option<Arc<FfiCancellationToken>>,
+    ) -> Result<FfiExportManifest, FuzzyDrugsError> {
+        let progress = ForeignProgress {
+            listener: progress,
+            cancel,
+        };
+        let normalizer_data = self.lock_normalizer()?.data_info().clone();
+        let db = self.lock_db()?;
+        let exporter = self.compliance_exporter(&db, normalizer_data)?;
+        let manifest =
+            exporter.export_all_to_file_with_progress(std::path::Path::new(&path), &progress)?;
+        Ok(manifest.into())
+    }
+
+    /// Export billing JSON for encounters committed after `timestamp`
+    /// (RFC 3339, or SQLite's "YYYY-MM-DD HH:MM:SS" in UTC).
+    pub fn export_billing_since(&self, timestamp: String) -> Result<String, FuzzyDrugsError> {
+        let since = parse_since_timestamp(&timestamp)?;
+        let db = self.lock_db()?;
+        let exporter = self.billing_exporter(&db);
+        let batch = exporter.export_since(&since)?;
+        Ok(batch.to_json()?)
+    }
+
+    /// Export compliance JSON for encounters committed after the tree had
+    /// `root_hash`, with proofs against the current root. Exports everything
+    /// if `root_hash` is `None` or unknown on this device.
+    pub fn export_compliance_since_root(
+        &self,
+        root_hash: Option<String>,
+    ) -> Result<String, FuzzyDrugsError> {
+        let normalizer_data = self.lock_normalizer()?.data_info().clone();
+        let db = self.lock_db()?;
+        let exporter = self.compliance_exporter(&db, normalizer_data)?;
+        let batch = exporter.export_since_root(root_hash.as_deref())?;
+        Ok(batch.to_json()?)
+    }
+
+    /// Export tree nodes written after the tree had `root_hash`, as JSON.
+    /// Exports the whole tree if `root_hash` is `None` or unknown on this
+    /// device.
+    pub fn export_tree_since(&self, root_hash: Option<String>) -> Result<String, FuzzyDrugsError> {
+        let db = self.lock_db()?;
+        if db.get_merkle_root()?.root_hash.is_none() {
+            return Err(FuzzyDrugsError::InvalidInput("Tree is empty".into()));
+        }
+        let export = merkle::SyncManager::new(&db).export_since(root_hash.as_deref())?;
+        Ok(serde_json::to_string_pretty(&export)?)
+    }
+
+    // =========================================================================
+    // Health
+    // =========================================================================
+
+    /// Report the core's health.
+    ///
+    /// Waits up to `timeout_ms` for the database lock; if it stays held the
+    /// lock is reported stuck. Otherwise an integrity check is run (recovering
+    /// a poisoned lock first, if needed).
+    pub fn get_health_status(&self, timeout_ms: u32) -> FfiHealthStatus {
+        let deadline = Instant::now() + Duration::from_millis(u64::from(timeout_ms));
+        let lock_available = loop {
+            match self.db.try_lock() {
+                Ok(_) | Err(TryLockError::Poisoned(_)) => break true,
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                Err(TryLockError::WouldBlock) => break false,
+            }
+        };
+        self.lock_health().lock_available = lock_available;
+
+        if lock_available {
+            // Recovery failures are recorded by lock_db itself
+            if let Ok(db) = self.lock_db() {
+                let checked = db.integrity_check();
+                drop(db);
+                match checked {
+                    Ok(problems) => self.lock_health().record_integrity(&problems),
+                    Err(e) => self
+                        .lock_health()
+                        .record_failure(format!("Integrity check failed: {}", e)),
+                }
+            }
+        } else {
+            self.lock_health().record_failure(format!(
+                "Database lock not acquired within {} ms",
+                timeout_ms
+            ));
+        }
+
+        self.lock_health().clone().into()
+    }
+
+    /// Reset the database connection and verify integrity, e.g. after the
+    /// app sees a degraded health status.
+    pub fn recover_database(&self) -> Result<FfiHealthStatus, FuzzyDrugsError> {
+        let mut db = self.lock_db()?;
+        let checked = db.recover_connection().and_then(|_| db.integrity_check());
+        drop(db);
+
+        let mut health = self.lock_health();
+        health.lock_available = true;
+        match checked {
+            Ok(problems) => health.record_integrity(&problems),
+            Err(e) => health.record_failure(format!("Recovery failed: {}", e)),
+        }
+        Ok(health.clone().into())
+    }
+}
+
+/// Trim a lot number, rejecting an empty one.
+fn parse_lot_number(lot_number: &str) -> Result<String, FuzzyDrugsError> {
+    let lot_number = lot_number.trim();
+    if lot_number.is_empty() {
+        return Err(FuzzyDrugsError::InvalidInput(
+            "Lot number can't be empty".into(),
+        ));
+    }
+    Ok(lot_number.to_string())
+}
+
+/// Parse a line item disposition ("administered", "dispensed", "prescribed").
+fn parse_disposition(disposition: &str) -> Result<models::DispositionType, FuzzyDrugsError> {
+    models::DispositionType::parse(disposition).ok_or_else(|| {
+        FuzzyDrugsError::InvalidInput(format!("Unknown disposition: {}", disposition))
+    })
+}
+
+/// SQLite's `datetime('now')` format, used for Merkle node timestamps.
+const SQLITE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Parse an RFC 3339 timestamp, or SQLite's "YYYY-MM-DD HH:MM:SS" in UTC.
+fn parse_timestamp(timestamp: &str) -> Result<chrono::DateTime<chrono::Utc>, FuzzyDrugsError> {
+    let timestamp = timestamp.trim();
+    if let Ok(parsed) = chrono::DateTime::parse_from_rfc3339(timestamp) {
+        return Ok(parsed.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDateTime::parse_from_str(timestamp, SQLITE_FORMAT)
+        .map(|parsed| parsed.and_utc())
+        .map_err(|_| FuzzyDrugsError::InvalidInput(format!("Invalid timestamp: {}", timestamp)))
+}
+
+/// Parse an export cut-off into the UTC "YYYY-MM-DD HH:MM:SS" form that
+/// Merkle node timestamps are stored in, so they compare as strings.
+fn parse_since_timestamp(timestamp: &str) -> Result<String, FuzzyDrugsError> {
+    Ok(parse_timestamp(timestamp)?
+        .format(SQLITE_FORMAT)
+        .to_string())
+}
+
+/// Parse a legal hold subject type ("patient" or "encounter").
+fn parse_hold_subject(subject_type: &str) -> Result<models::HoldSubject, FuzzyDrugsError> {
+    models::HoldSubject::parse(subject_type).ok_or_else(|| {
+        FuzzyDrugsError::InvalidInput(format!("Unknown legal hold subject: {}", subject_type))
+    })
+}
+
+/// Parse a log level name.
+fn parse_log_level(
+    level: &str,
+) -> Result<tracing_subscriber::filter::LevelFilter, FuzzyDrugsError> {
+    logging::parse_level(level)
+        .ok_or_else(|| FuzzyDrugsError::InvalidInput(format!("Unknown log level: {}", level)))
+}
+
+/// Build a phrasebook from FFI target/language strings.
+fn parse_phrasebook(target: &str, language: &str) -> Result<export::Phrasebook, FuzzyDrugsError> {
+    let target = export::PhraseTarget::parse(target).ok_or_else(|| {
+        FuzzyDrugsError::InvalidInput(format!("Unknown phrase target: {}", target))
+    })?;
+    let language = export::PhraseLanguage::parse(language).ok_or_else(|| {
+        FuzzyDrugsError::InvalidInput(format!("Unsupported language: {}", language))
+    })?;
+    Ok(export::Phrasebook::new(target, language))
+}
+
+// =========================================================================
+// FFI Types
+// =========================================================================
+
+/// Drug mention extractor implemented by the host app (the NER step).
+#[uniffi::export(with_foreign)]
+pub trait FfiMentionExtractor: Send + Sync {
+    /// Find the drug mentions in a transcript, with byte offsets into it.
+    fn extract(&self, transcript: String) -> Result<Vec<FfiDrugMention>, FuzzyDrugsError>;
+}
+
+/// Adapts a host app extractor to the resolver's extractor trait.
+struct ForeignExtractor(Arc<dyn FfiMentionExtractor>);
+
+impl MentionExtractor for ForeignExtractor {
+    fn extract(&self, transcript: &str) -> resolver::ResolverResult<Vec<models::DrugMention>> {
+        self.0
+            .extract(transcript.to_string())
+            .map(|mentions| mentions.into_iter().map(Into::into).collect())
+            .map_err(extraction_error)
+    }
+}
+
+/// Drug and service extractor implemented by the host app.
+#[uniffi::export(with_foreign)]
+pub trait FfiEntityExtractor: Send + Sync {
+    /// Find the drug and service mentions in a transcript, with byte
+    /// offsets into it.
+    fn extract_entities(&self, transcript: String)
+        -> Result<FfiExtractedMentions, FuzzyDrugsError>;
+}
+
+/// Adapts a host app entity extractor to the resolver's extractor trait.
+struct ForeignEntityExtractor(Arc<dyn FfiEntityExtractor>);
+
+impl MentionExtractor for ForeignEntityExtractor {
+    fn extract(&self, transcript: &str) -> resolver::ResolverResult<Vec<models::DrugMention>> {
+        Ok(self.extract_all(transcript)?.drugs)
+    }
+
+    fn extract_all(&self, transcript: &str) -> resolver::ResolverResult<ExtractedMentions> {
+        let extracted = self
+            .0
+            .extract_entities(transcript.to_string())
+            .map_err(extraction_error)?;
+        Ok(ExtractedMentions {
+            drugs: extracted.drugs.into_iter().map(Into::into).collect(),
+            services: extracted.services.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+/// A host extractor failure as a resolver error.
+fn extraction_error(e: FuzzyDrugsError) -> resolver::ResolverError {
+    resolver::ResolverError::Extraction(match e {
+        FuzzyDrugsError::ExtractionError(msg) => msg,
+        e => e.to_string(),
+    })
+}
+
+/// HTTP client implemented by the host app for `run_sync`.
+///
+/// Called on the calling thread while the sync holds the database lock, so
+/// don't call back into the core from it.
+#[uniffi::export(with_foreign)]
+pub trait FfiSyncTransport: Send + Sync {
+    /// POST the JSON `body` to `path` (relative to the PIMS base URL) and
+    /// return the JSON reply. Fail with `SyncError` on network errors or a
+    /// non-2xx status, except a 404 from `sync/capabilities`: return
+    /// `{"protocol_version":1}` for it, since older servers lack the endpoint.
+    fn post(&self, path: String, body: String) -> Result<String, FuzzyDrugsError>;
+}
+
+/// Adapts a host app HTTP client to the sync transport trait.
+struct ForeignSyncTransport(Arc<dyn FfiSyncTransport>);
+
+impl ForeignSyncTransport {
+    fn post<B: serde::Serialize, R: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<R, merkle::TransportError> {
+        let body = serde_json::to_string(body)?;
+        let reply = self.0.post(path.to_string(), body).map_err(|e| {
+            merkle::TransportError::Connection(match e {
+                FuzzyDrugsError::SyncError(msg) => msg,
+                e => e.to_string(),
+            })
+        })?;
+        Ok(serde_json::from_str(&reply)?)
+    }
+}
+
+impl merkle::SyncTransport for ForeignSyncTransport {
+    fn capabilities(
+        &self,
+        local: &merkle::SyncCapabilities,
+    ) -> Result<merkle::SyncCapabilities, merkle::TransportError> {
+        self.post(merkle::CAPABILITIES_PATH, local)
+    }
+
+    fn send_request(
+        &self,
+        request: &merkle::SyncRequest,
+    ) -> Result<merkle::SyncResponse, merkle::TransportError> {
+        self.post(merkle::SYNC_REQUEST_PATH, request)
+    }
+
+    fn send_payload(
+        &self,
+        payload: &merkle::SyncPayload,
+    ) -> Result<merkle::SyncAck, merkle::TransportError> {
+        self.post(merkle::SYNC_PAYLOAD_PATH, payload)
+    }
+
+    fn send_chunk(
+        &self,
+        chunk: &merkle::SyncChunk,
+    ) -> Result<merkle::SyncChunkAck, merkle::TransportError> {
+        self.post(merkle::SYNC_CHUNK_PATH, chunk)
+    }
+
+    fn upsert_patient(
+        &self,
+        patient: &Patient,
+    ) -> Result<merkle::PatientUpsertAck, merkle::TransportError> {
+        self.post(merkle::PATIENT_UPSERT_PATH, patient)
+    }
+
+    fn pull_patients(
+        &self,
+        request: &merkle::PatientSyncRequest,
+    ) -> Result<merkle::PatientDelta, merkle::TransportError> {
+        self.post(merkle::PATIENT_PULL_PATH, request)
+    }
+}
+
+/// Progress of a long-running operation (exports, catalog imports, batch
+/// resolution), reported as items processed out of `total`.
+///
+/// Called on the calling thread while the operation holds the database lock,
+/// so don't call back into the core from it.
+#[uniffi::export(with_foreign)]
+pub trait FfiProgressListener: Send + Sync {
+    fn on_progress(&self, processed: u64, total: u64);
+}
+
+/// Cancels a long-running operation from another thread. The operation
+/// stops at the next item, discards or rolls back its partial work, and
+/// fails with `FuzzyDrugsError::Cancelled`.
+#[derive(Debug, Default, uniffi::Object)]
+pub struct FfiCancellationToken {
+    flag: progress::CancellationFlag,
+}
+
+#[uniffi::export]
+impl FfiCancellationToken {
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Ask the running operation to stop.
+    pub fn cancel(&self) {
+        self.flag.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.is_cancelled()
+    }
+}
+
+/// Adapts a host app progress listener and cancellation token to the core's
+/// progress trait.
+struct ForeignProgress {
+    listener: Option<Arc<dyn FfiProgressListener>>,
+    cancel: Option<Arc<FfiCancellationToken>>,
+}
+
+impl progress::Progress for ForeignProgress {
+    fn step(&self, processed: usize, total: usize) -> Result<(), progress::Cancelled> {
+        if let Some(cancel) = &self.cancel {
+            cancel.flag.check()?;
+        }
+        if let Some(listener) = &self.listener {
+            listener.on_progress(processed as u64, total as u64);
+        }
+        Ok(())
+    }
+}
+
+/// Receives core diagnostics (see `set_log_sink`).
+///
+/// May be called on any thread, including while the core holds its locks,
+/// so don't call back into the core from it.
+#[uniffi::export(with_foreign)]
+pub trait FfiLogSink: Send + Sync {
+    /// `level` is "error", "warn", "info", "debug", or "trace"; `target` is
+    /// the emitting module (e.g., "fuzzy_drugs_core::resolver::disambiguator").
+    fn log(&self, level: String, target: String, message: String);
+}
+
+/// Adapts a host app log sink to the core's sink trait.
+struct ForeignLogSink(Arc<dyn FfiLogSink>);
+
+impl logging::LogSink for ForeignLogSink {
+    fn log(&self, level: tracing::Level, target: &str, message: &str) {
+        self.0.log(
+            logging::level_name(level).to_string(),
+            target.to_string(),
+            message.to_string(),
+        );
+    }
+}
+
+/// FFI-safe per-module log level for `set_log_sink`.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiModuleLevel {
+    /// Module path ("resolver", "merkle::sync", or a full
+    /// "fuzzy_drugs_core::..." target); includes submodules
+    pub module: String,
+    /// "error", "warn", "info", "debug", "trace", or "off"
+    pub level: String,
+}
+
+/// Change notifications for the host app, so screens can refresh without
+/// polling.
+///
+/// Called synchronously on the thread that made the change, after the
+/// database lock is released, so listeners may call back into the core.
+/// Dispatch to the UI thread before touching views, and don't throw.
+#[uniffi::export(with_foreign)]
+pub trait FuzzyDrugsListener: Send + Sync {
+    /// A draft moved to a new status (`FfiEncounterDraft.status` values, or
+    /// "Discarded" once deleted).
+    fn on_draft_status_changed(&self, draft_id: String, status: String);
+    /// An encounter was committed to the Merkle tree.
+    fn on_encounter_committed(&self, draft_id: String, leaf_hash: String);
+    /// Catalog items were added, updated, deactivated, or deleted.
+    fn on_catalog_updated(&self, skus: Vec<String>);
+    /// Whether the tree has changes not yet synced to the PIMS changed.
+    fn on_sync_state_changed(&self, has_unsynced_changes: bool);
+}
+
+/// A change to report to the listener once the database lock is released.
+enum CoreEvent {
+    DraftStatusChanged { draft_id: String, status: String },
+    EncounterCommitted { draft_id: String, leaf_hash: String },
+    CatalogUpdated { skus: Vec<String> },
+    SyncStateChanged { has_unsynced_changes: bool },
+}
+
+impl CoreEvent {
+    fn draft_status(draft_id: &str, status: &DraftStatus) -> Self {
+        CoreEvent::DraftStatusChanged {
+            draft_id: draft_id.to_string(),
+            status: format!("{:?}", status),
+        }
+    }
+
+    fn deliver(self, listener: &dyn FuzzyDrugsListener) {
+        match self {
+            CoreEvent::DraftStatusChanged { draft_id, status } => {
+                listener.on_draft_status_changed(draft_id, status)
+            }
+            CoreEvent::EncounterCommitted {
+                draft_id,
+                leaf_hash,
+            } => listener.on_encounter_committed(draft_id, leaf_hash),
+            CoreEvent::CatalogUpdated { skus } => listener.on_catalog_updated(skus),
+            CoreEvent::SyncStateChanged {
+                has_unsynced_changes,
+            } => listener.on_sync_state_changed(has_unsynced_changes),
+        }
+    }
+}
+
+/// FFI-safe extracted drug mention.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiDrugMention {
+    /// Mention text as transcribed
+    pub raw_text: String,
+    pub drug_name: String,
+    pub dose: Option<f64>,
+    pub unit: Option<String>,
+    pub route: Option<String>,
+    pub species: Option<String>,
+    pub start_offset: u32,
+    pub end_offset: u32,
+}
+
+impl From<FfiDrugMention> for models::DrugMention {
+    fn from(mention: FfiDrugMention) -> Self {
+        Self {
+            raw_text: mention.raw_text,
+            drug_name: mention.drug_name,
+            dose: mention.dose,
+            unit: mention.unit,
+            route: mention.route,
+            species: mention.species,
+            start_offset: mention.start_offset as usize,
+            end_offset: mention.end_offset as usize,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        }
+    }
+}
+
+impl From<models::DrugMention> for FfiDrugMention {
+    fn from(mention: models::DrugMention) -> Self {
+        Self {
+            raw_text: mention.raw_text,
+            drug_name: mention.drug_name,
+            dose: mention.dose,
+            unit: mention.unit,
+            route: mention.route,
+            species: mention.species,
+            start_offset: mention.start_offset as u32,
+            end_offset: mention.end_offset as u32,
+        }
+    }
+}
+
+/// FFI-safe service mention (procedure, vaccine, or diagnostic).
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiServiceMention {
+    /// Mention text as transcribed
+    pub raw_text: String,
+    pub service_name: String,
+    /// "procedure", "vaccine", or "diagnostic"; nil if unknown (unrecognized
+    /// kinds are treated as unknown)
+    pub kind: Option<String>,
+    pub quantity: Option<f64>,
+    pub start_offset: u32,
+    pub end_offset: u32,
+}
+
+impl From<FfiServiceMention> for models::ServiceMention {
+    fn from(mention: FfiServiceMention) -> Self {
+        Self {
+            raw_text: mention.raw_text,
+            service_name: mention.service_name,
+            kind: mention.kind.as_deref().and_then(models::ServiceKind::parse),
+            quantity: mention.quantity,
+            start_offset: mention.start_offset as usize,
+            end_offset: mention.end_offset as usize,
+        }
+    }
+}
+
+/// What an `FfiEntityExtractor` found in a transcript.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiExtractedMentions {
+    pub drugs: Vec<FfiDrugMention>,
+    pub services: Vec<FfiServiceMention>,
+}
+
+/// FFI-safe manufacturer lot of a catalog item or vaccine service.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiInventoryLot {
+    /// Catalog SKU, or service code for vaccines
+    pub sku: String,
+    pub lot_number: String,
+    /// Expiration date (YYYY-MM-DD)
+    pub expiration_date: String,
+}
+
+impl From<models::InventoryLot> for FfiInventoryLot {
+    fn from(lot: models::InventoryLot) -> Self {
+        Self {
+            sku: lot.sku,
+            lot_number: lot.lot_number,
+            expiration_date: lot.expiration_date,
+        }
+    }
+}
+
+impl From<FfiInventoryLot> for models::InventoryLot {
+    fn from(lot: FfiInventoryLot) -> Self {
+        Self::new(lot.sku, lot.lot_number, lot.expiration_date)
+    }
+}
+
+/// FFI-safe stock level of a catalog item.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiStockLevel {
+    pub sku: String,
+    pub name: String,
+    /// Quantity on hand, in dispensing units
+    pub quantity_on_hand: f64,
+    pub reorder_point: Option<f64>,
+    /// At or below the reorder point
+    pub is_low: bool,
+}
+
+impl From<models::StockLevel> for FfiStockLevel {
+    fn from(level: models::StockLevel) -> Self {
+        Self {
+            is_low: level.is_low(),
+            sku: level.sku,
+            name: level.name,
+            quantity_on_hand: level.quantity_on_hand,
+            reorder_point: level.reorder_point,
+        }
+    }
+}
+
+/// FFI-safe change to an item's quantity on hand.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiStockAdjustment {
+    pub id: i64,
+    pub sku: String,
+    pub delta: f64,
+    pub quantity_after: f64,
+    /// "received", "count", "waste", "expired", "returned", or "encounter"
+    pub reason: String,
+    pub note: String,
+    /// Encounter leaf hash, for encounter usage
+    pub reference: Option<String>,
+    pub created_at: String,
+}
+
+impl From<models::StockAdjustment> for FfiStockAdjustment {
+    fn from(a: models::StockAdjustment) -> Self {
+        Self {
+            id: a.id,
+            sku: a.sku,
+            delta: a.delta,
+            quantity_after: a.quantity_after,
+            reason: a.reason.as_str().into(),
+            note: a.note,
+            reference: a.reference,
+            created_at: a.created_at,
+        }
+    }
+}
+
+/// FFI-safe user with prescribing credentials.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiUser {
+    /// Identifier passed as `reviewed_by` when committing
+    pub user_id: String,
+    pub name: String,
+    /// State veterinary license number
+    pub license_number: Option<String>,
+    /// Licensing state ("CA")
+    pub license_state: Option<String>,
+    /// DEA registration number (two letters, seven digits)
+    pub dea_number: Option<String>,
+    /// Roles held, checked when the user approves escalated items
+    pub roles: Vec<String>,
+}
+
+impl From<models::User> for FfiUser {
+    fn from(u: models::User) -> Self {
+        Self {
+            user_id: u.user_id,
+            name: u.name,
+            license_number: u.license_number,
+            license_state: u.license_state,
+            dea_number: u.dea_number,
+            roles: u.roles,
+        }
+    }
+}
+
+impl From<FfiUser> for models::User {
+    fn from(u: FfiUser) -> Self {
+        Self {
+            user_id: u.user_id,
+            name: u.name,
+            license_number: u.license_number,
+            license_state: u.license_state,
+            dea_number: u.dea_number,
+            roles: u.roles.iter().map(|r| r.trim().to_string()).collect(),
+        }
+    }
+}
+
+/// FFI-safe services catalog entry.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiServiceItem {
+    pub code: String,
+    pub name: String,
+    pub aliases: Vec<String>,
+    /// "procedure", "vaccine", or "diagnostic"
+    pub kind: String,
+    pub unit_price: Option<f64>,
+    pub tax_code: Option<String>,
+    pub active: bool,
+    /// Vaccines: antigen shared by products ("rabies"); the name if unset
+    pub antigen: Option<String>,
+    /// Vaccines: days from a dose until the next is due
+    pub booster_interval_days: Option<u32>,
+}
+
+impl TryFrom<FfiServiceItem> for models::ServiceItem {
+    type Error = FuzzyDrugsError;
+
+    fn try_from(item: FfiServiceItem) -> Result<Self, Self::Error> {
+        let kind = models::ServiceKind::parse(&item.kind).ok_or_else(|| {
+            FuzzyDrugsError::InvalidInput(format!("Unknown service kind: {}", item.kind))
+        })?;
+        Ok(Self {
+            code: item.code,
+            name: item.name,
+            aliases: item.aliases,
+            kind,
+            unit_price: item.unit_price,
+            tax_code: item.tax_code,
+            active: item.active,
+            antigen: item.antigen.filter(|a| !a.trim().is_empty()),
+            booster_interval_days: item.booster_interval_days,
+        })
+    }
+}
+
+impl From<models::ServiceItem> for FfiServiceItem {
+    fn from(item: models::ServiceItem) -> Self {
+        Self {
+            code: item.code,
+            name: item.name,
+            aliases: item.aliases,
+            kind: item.kind.as_str().into(),
+            unit_price: item.unit_price,
+            tax_code: item.tax_code,
+            active: item.active,
+            antigen: item.antigen,
+            booster_interval_days: item.booster_interval_days,
+        }
+    }
+}
+
+/// FFI-safe service on a draft, matched from a transcript mention.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiServiceLineItem {
+    pub code: String,
+    pub name: String,
+    pub kind: String,
+    pub quantity: f64,
+    /// Name similarity of the match (0.0 - 1.0)
+    pub confidence: f64,
+    /// Mention text as transcribed
+    pub original_mention: String,
+    pub start_offset: u32,
+    pub end_offset: u32,
+    /// Whether a lot number must be recorded (vaccines)
+    pub requires_lot: bool,
+    pub lot_number: Option<String>,
+    /// Lot expiration date (YYYY-MM-DD)
+    pub expiration_date: Option<String>,
+}
+
+impl From<models::ServiceLineItem> for FfiServiceLineItem {
+    fn from(item: models::ServiceLineItem) -> Self {
+        Self {
+            requires_lot: item.requires_lot(),
+            code: item.code,
+            name: item.name,
+            kind: item.kind.as_str().into(),
+            quantity: item.quantity,
+            confidence: item.confidence,
+            original_mention: item.mention.raw_text,
+            start_offset: item.mention.start_offset as u32,
+            end_offset: item.mention.end_offset as u32,
+            lot_number: item.lot_number,
+            expiration_date: item.expiration_date,
+        }
+    }
+}
+
+/// FFI-safe speaker turn from diarization.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiSpeakerTurn {
+    /// Diarization label ("SPEAKER_00")
+    pub speaker: String,
+    /// "clinician" or "owner"; nil if unknown
+    pub role: Option<String>,
+    pub start_offset: u32,
+    pub end_offset: u32,
+}
+
+impl TryFrom<FfiSpeakerTurn> for models::SpeakerTurn {
+    type Error = FuzzyDrugsError;
+
+    fn try_from(turn: FfiSpeakerTurn) -> Result<Self, Self::Error> {
+        if turn.end_offset < turn.start_offset {
+            return Err(FuzzyDrugsError::InvalidInput(format!(
+                "Speaker turn ends ({}) before it starts ({})",
+                turn.end_offset, turn.start_offset
+            )));
+        }
+        let role = turn
+            .role
+            .map(|role| {
+                models::SpeakerRole::parse(&role).ok_or_else(|| {
+                    FuzzyDrugsError::InvalidInput(format!("Unknown speaker role: {}", role))
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            speaker: turn.speaker,
+            role,
+            start_offset: turn.start_offset as usize,
+            end_offset: turn.end_offset as usize,
+        })
+    }
+}
+
+/// FFI-safe result of processing a transcript.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiProcessedTranscript {
+    pub draft: FfiEncounterDraft,
+    /// Extracted drug names with no catalog match (add them by hand)
+    pub unmatched_drugs: Vec<String>,
+    /// Extracted service names with no services catalog match
+    pub unmatched_services: Vec<String>,
+}
+
+/// FFI-safe result of resolving a batch of mentions.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiBatchResolution {
+    pub items: Vec<FfiResolvedItem>,
+    /// Drug names with no catalog match
+    pub unmatched_drugs: Vec<String>,
+}
+
+/// FFI-safe page of catalog items.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogPage {
+    pub items: Vec<FfiCatalogItem>,
+    pub page: u32,
+    pub page_size: u32,
+    /// Items matching the filter across all pages
+    pub total_count: u32,
+}
+
+/// FFI-safe result of `run_sync`.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiSyncReport {
+    /// Nothing needed sending (empty tree, root already synced, or the PIMS
+    /// already had every node)
+    pub up_to_date: bool,
+    pub nodes_sent: u32,
+    /// Chunks the nodes went out in (0 if sent whole)
+    pub chunks_sent: u32,
+    /// Root the PIMS acknowledged
+    pub synced_root: Option<String>,
+    /// Divergence found (and resolved) during this sync
+    pub conflict: Option<FfiSyncConflict>,
+}
+
+impl From<merkle::SyncReport> for FfiSyncReport {
+    fn from(report: merkle::SyncReport) -> Self {
+        Self {
+            up_to_date: report.up_to_date,
+            nodes_sent: report.nodes_sent as u32,
+            chunks_sent: report.chunks_sent as u32,
+            synced_root: report.synced_root,
+            conflict: report.conflict.map(|c| c.into()),
+        }
+    }
+}
+
+/// FFI-safe sync status (see `get_sync_status`).
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiSyncStatus {
+    /// Last applied catalog delta (PIMS clock)
+    pub catalog_last_sync: Option<String>,
+    /// Last acknowledged encounter sync
+    pub encounters_last_sync: Option<String>,
+    /// Last applied patient delta (PIMS clock)
+    pub patients_last_sync: Option<String>,
+    /// Leaves committed since the last synced root
+    pub unsynced_leaf_count: u32,
+    /// Outbox operations awaiting delivery
+    pub outbox_pending: u32,
+    /// Outbox operations out of attempts (see `retry_sync_outbox_item`)
+    pub outbox_failed: u32,
+    /// Latest sync error, cleared by the next successful attempt
+    pub last_error: Option<String>,
+    pub last_error_at: Option<String>,
+    pub local_root: Option<String>,
+    /// PIMS root acknowledged by the last successful sync
+    pub server_root: Option<String>,
+    /// False after a rebase: the PIMS tree also holds other devices' leaves
+    pub server_root_matches: bool,
+    /// Encounter sync is blocked until `resolve_sync_conflict`
+    pub conflict_open: bool,
+}
+
+impl From<merkle::SyncStatus> for FfiSyncStatus {
+    fn from(status: merkle::SyncStatus) -> Self {
+        Self {
+            catalog_last_sync: status.catalog_last_sync,
+            encounters_last_sync: status.encounters_last_sync,
+            patients_last_sync: status.patients_last_sync,
+            unsynced_leaf_count: status.unsynced_leaf_count,
+            outbox_pending: status.outbox_pending,
+            outbox_failed: status.outbox_failed,
+            last_error: status.last_error,
+            last_error_at: status.last_error_at,
+            local_root: status.local_root,
+            server_root: status.server_root,
+            server_root_matches: status.server_root_matches,
+            conflict_open: status.conflict_open,
+        }
+    }
+}
+
+/// FFI-safe sync conflict: the PIMS tree diverged from the one last synced.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiSyncConflict {
+    /// Local root when the conflict was last seen
+    pub local_root: String,
+    /// PIMS root acknowledged by the last successful sync
+    pub last_server_root: Option<String>,
+    /// Root the PIMS reported
+    pub server_root: String,
+    pub detected_at: String,
+    /// Clinic strategy when detected ("local_wins", "server_rebase", "manual")
+    pub strategy: String,
+    /// Strategy applied; `None` while sync waits for `resolve_sync_conflict`
+    pub resolution: Option<String>,
+}
+
+impl From<merkle::SyncConflict> for FfiSyncConflict {
+    fn from(conflict: merkle::SyncConflict) -> Self {
+        Self {
+            local_root: conflict.local_root,
+            last_server_root: conflict.last_server_root,
+            server_root: conflict.server_root,
+            detected_at: conflict.detected_at,
+            strategy: conflict.strategy.as_str().to_string(),
+            resolution: conflict.resolution.map(|r| r.as_str().to_string()),
+        }
+    }
+}
+
+/// FFI-safe result of `process_sync_outbox`.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiOutboxReport {
+    /// Operations that reached the PIMS (and left the outbox)
+    pub succeeded: u32,
+    /// Operations that failed this time
+    pub failed: u32,
+    /// Of those, operations now out of attempts ("failed" status)
+    pub exhausted: u32,
+    /// Earliest next attempt among pending operations (RFC 3339)
+    pub next_attempt_at: Option<String>,
+}
+
+impl From<merkle::OutboxReport> for FfiOutboxReport {
+    fn from(report: merkle::OutboxReport) -> Self {
+        Self {
+            succeeded: report.succeeded as u32,
+            failed: report.failed as u32,
+            exhausted: report.exhausted as u32,
+            next_attempt_at: report.next_attempt_at,
+        }
+    }
+}
+
+/// FFI-safe queued sync operation.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiOutboxItem {
+    pub id: i64,
+    /// "encounter_push" or "patient_upsert"
+    pub kind: String,
+    /// Leaf hash or patient local ID, depending on `kind`
+    pub subject_id: String,
+    /// "pending" or "failed" (out of attempts)
+    pub status: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<models::OutboxItem> for FfiOutboxItem {
+    fn from(item: models::OutboxItem) -> Self {
+        Self {
+            id: item.id,
+            kind: item.kind.as_str().to_string(),
+            subject_id: item.subject_id,
+            status: item.status.as_str().to_string(),
+            attempts: item.attempts,
+            last_error: item.last_error,
+            next_attempt_at: item.next_attempt_at,
+            created_at: item.created_at,
+            updated_at: item.updated_at,
+        }
+    }
+}
+
+/// FFI-safe patient sync request.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPatientSyncRequest {
+    /// Timestamp of the last applied delta; `None` requests every patient
+    pub since: Option<String>,
+}
+
+/// FFI-safe patient delta from the PIMS.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPatientDelta {
+    /// Patients created or edited on the PIMS
+    pub patients: Vec<FfiPatientSyncItem>,
+    /// Server timestamp of this delta, sent back as `since` next time
+    pub timestamp: String,
+}
+
+/// FFI-safe patient in a sync delta.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPatientSyncItem {
+    pub server_id: String,
+    pub name: String,
+    pub species: String,
+    pub breed: Option<String>,
+    pub weight_kg: Option<f64>,
+    pub date_of_birth: Option<String>,
+    pub owner_name: Option<String>,
+}
+
+impl From<FfiPatientDelta> for merkle::PatientDelta {
+    fn from(delta: FfiPatientDelta) -> Self {
+        merkle::PatientDelta {
+            patients: delta
+                .patients
+                .into_iter()
+                .map(|p| merkle::PatientSyncItem {
+                    server_id: p.server_id,
+                    name: p.name,
+                    species: p.species,
+                    breed: p.breed,
+                    weight_kg: p.weight_kg,
+                    date_of_birth: p.date_of_birth,
+                    owner_name: p.owner_name,
+                })
+                .collect(),
+            timestamp: delta.timestamp,
+        }
+    }
+}
+
+/// FFI-safe result of applying a patient delta.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPatientSyncReport {
+    /// PIMS patients new to this device
+    pub created: u32,
+    /// Already-linked patients updated from the PIMS
+    pub updated: u32,
+    /// Local patients matched as duplicates and linked to their PIMS ID
+    pub linked: u32,
+}
+
+impl From<merkle::PatientDeltaReport> for FfiPatientSyncReport {
+    fn from(report: merkle::PatientDeltaReport) -> Self {
+        Self {
+            created: report.created as u32,
+            updated: report.updated as u32,
+            linked: report.linked as u32,
+        }
+    }
+}
+
+/// FFI-safe catalog sync request.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogSyncRequest {
+    /// Timestamp of the last applied delta; `None` requests the full catalog
+    pub since: Option<String>,
+    /// SKUs that failed to apply from earlier deltas; send them again even
+    /// if unchanged since `since`
+    pub retry_skus: Vec<String>,
+}
+
+/// FFI-safe outcome of applying a catalog delta.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogUpsertReport {
+    /// Items written
+    pub upserted: u32,
+    /// Items skipped, in delta order
+    pub failures: Vec<FfiCatalogUpsertFailure>,
+}
+
+/// FFI-safe catalog item a delta couldn't write.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogUpsertFailure {
+    pub sku: String,
+    pub error: String,
+}
+
+impl From<models::CatalogUpsertReport> for FfiCatalogUpsertReport {
+    fn from(report: models::CatalogUpsertReport) -> Self {
+        Self {
+            upserted: report.upserted as u32,
+            failures: report
+                .failures
+                .into_iter()
+                .map(|f| FfiCatalogUpsertFailure {
+                    sku: f.sku,
+                    error: f.error,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// FFI-safe catalog delta from the PIMS.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogDelta {
+    /// Items to add or update
+    pub items: Vec<FfiCatalogSyncItem>,
+    /// SKUs to deactivate
+    pub deactivated_skus: Vec<String>,
+    /// Server timestamp of this delta, sent back as `since` next time
+    pub timestamp: String,
+}
+
+/// FFI-safe catalog item in a sync delta.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogSyncItem {
+    pub sku: String,
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub concentration: Option<String>,
+    pub package_size: Option<String>,
+    pub species: Vec<String>,
+    pub routes: Vec<String>,
+    pub active: bool,
+    pub server_id: String,
+    pub components: Vec<String>,
+    pub controlled_schedule: Option<String>,
+    pub unit_price: Option<f64>,
+    /// Markup on `unit_price` as a fraction (0.25 = 25%)
+    pub markup: Option<f64>,
+    pub minimum_charge: Option<f64>,
+    pub tax_code: Option<String>,
+}
+
+impl From<FfiCatalogDelta> for merkle::CatalogDelta {
+    fn from(delta: FfiCatalogDelta) -> Self {
+        merkle::CatalogDelta {
+            items: delta.items.into_iter().map(|i| i.into()).collect(),
+            deactivated_skus: delta.deactivated_skus,
+            timestamp: delta.timestamp,
+        }
+    }
+}
+
+impl From<FfiCatalogSyncItem> for merkle::CatalogSyncItem {
+    fn from(item: FfiCatalogSyncItem) -> Self {
+        merkle::CatalogSyncItem {
+            sku: item.sku,
+            name: item.name,
+            aliases: item.aliases,
+            concentration: item.concentration,
+            package_size: item.package_size,
+            species: item.species,
+            routes: item.routes,
+            active: item.active,
+            server_id: item.server_id,
+            components: item.components,
+            controlled_schedule: item
+                .controlled_schedule
+                .as_deref()
+                .and_then(ControlledSchedule::parse),
+            unit_price: item.unit_price,
+            markup: item.markup,
+            minimum_charge: item.minimum_charge,
+            tax_code: item.tax_code,
+        }
+    }
+}
+
+/// FFI-safe catalog push.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogPushDelta {
+    pub items: Vec<FfiCatalogPushItem>,
+}
+
+/// FFI-safe catalog item in a push.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogPushItem {
+    pub sku: String,
+    /// `None` for local items the PIMS hasn't accepted yet
+    pub server_id: Option<String>,
+    /// "pims" or "local"
+    pub origin: String,
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub concentration: Option<String>,
+    pub package_size: Option<String>,
+    pub species: Vec<String>,
+    pub routes: Vec<String>,
+    pub active: bool,
+    pub components: Vec<String>,
+    pub controlled_schedule: Option<String>,
+    pub unit_price: Option<f64>,
+    /// Markup on `unit_price` as a fraction (0.25 = 25%)
+    pub markup: Option<f64>,
+    pub minimum_charge: Option<f64>,
+    pub tax_code: Option<String>,
+}
+
+/// FFI-safe PIMS reply to a catalog push.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogPushAck {
+    /// Items the PIMS accepted; the rest are pushed again next time
+    pub accepted: Vec<FfiCatalogPushResult>,
+    /// Server timestamp of the push
+    pub timestamp: String,
+}
+
+/// FFI-safe accepted catalog push item.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogPushResult {
+    pub sku: String,
+    pub server_id: String,
+}
+
+impl From<merkle::CatalogPushDelta> for FfiCatalogPushDelta {
+    fn from(push: merkle::CatalogPushDelta) -> Self {
+        Self {
+            items: push
+                .items
+                .into_iter()
+                .map(|item| FfiCatalogPushItem {
+                    sku: item.sku,
+                    server_id: item.server_id,
+                    origin: item.origin.as_str().to_string(),
+                    name: item.name,
+                    aliases: item.aliases,
+                    concentration: item.concentration,
+                    package_size: item.package_size,
+                    species: item.species,
+                    routes: item.routes,
+                    active: item.active,
+                    components: item.components,
+                    controlled_schedule: item.controlled_schedule.map(|s| s.to_string()),
+                    unit_price: item.unit_price,
+                    markup: item.markup,
+                    minimum_charge: item.minimum_charge,
+                    tax_code: item.tax_code,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<FfiCatalogPushAck> for merkle::CatalogPushAck {
+    fn from(ack: FfiCatalogPushAck) -> Self {
+        merkle::CatalogPushAck {
+            accepted: ack
+                .accepted
+                .into_iter()
+                .map(|result| merkle::CatalogPushResult {
+                    sku: result.sku,
+                    server_id: result.server_id,
+                })
+                .collect(),
+            timestamp: ack.timestamp,
+        }
+    }
+}
+
+/// FFI-safe type-ahead suggestion.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogSuggestion {
+    pub sku: String,
+    pub name: String,
+    pub strength: Option<String>,
+}
+
+impl From<models::CatalogSuggestion> for FfiCatalogSuggestion {
+    fn from(suggestion: models::CatalogSuggestion) -> Self {
+        Self {
+            sku: suggestion.sku,
+            name: suggestion.name,
+            strength: suggestion.strength,
+        }
+    }
+}
+
+/// FFI-safe catalog item.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCatalogItem {
+    pub sku: String,
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub concentration: Option<String>,
+    pub package_size: Option<String>,
+    pub species: Vec<String>,
+    pub routes: Vec<String>,
+    pub active: bool,
+    pub components: Vec<String>,
+    pub controlled_schedule: Option<String>,
+    pub unit_price: Option<f64>,
+    /// Markup on `unit_price` as a fraction (0.25 = 25%)
+    pub markup: Option<f64>,
+    /// Smallest charge for a line of this item
+    pub minimum_charge: Option<f64>,
+    /// Sales tax code (rates are set with `set_tax_rates`)
+    pub tax_code: Option<String>,
+    /// Meat/milk withdrawal periods per food-animal species
+    pub withdrawal_times: Vec<FfiWithdrawalTime>,
+}
+
+impl From<CatalogItem> for FfiCatalogItem {
+    fn from(item: CatalogItem) -> Self {
+        Self {
+            sku: item.sku,
+            name: item.name,
+            aliases: item.aliases,
+            concentration: item.concentration,
+            package_size: item.package_size,
+            species: item.species,
+            routes: item.routes,
+            active: item.active,
+            components: item.components,
+            controlled_schedule: item.controlled_schedule.map(|s| s.to_string()),
+            unit_price: item.unit_price,
+            markup: item.markup,
+            minimum_charge: item.minimum_charge,
+            tax_code: item.tax_code,
+            withdrawal_times: item
+                .withdrawal_times
+                .into_iter()
+                .map(|t| t.into())
+                .collect(),
+        }
+    }
+}
+
+impl From<FfiCatalogItem> for CatalogItem {
+    fn from(item: FfiCatalogItem) -> Self {
+        CatalogItem {
+            sku: item.sku,
+            name: item.name,
+            aliases: item.aliases,
+            concentration: item.concentration,
+            package_size: item.package_size,
+            species: item.species,
+            routes: item.routes,
+            dose_range: None,
+            active: item.active,
+            server_id: None,
+            last_synced: None,
+            components: item.components,
+            controlled_schedule: item
+                .controlled_schedule
+                .as_deref()
+                .and_then(ControlledSchedule::parse),
+            unit_price: item.unit_price,
+            markup: item.markup,
+            minimum_charge: item.minimum_charge,
+            tax_code: item.tax_code,
+            withdrawal_times: item
+                .withdrawal_times
+                .into_iter()
+                .map(|t| t.into())
+                .collect(),
+            origin: models::CatalogOrigin::Pims,
+            dirty: false,
+        }
+    }
+}
+
+/// FFI-safe withdrawal periods for a catalog item in one species.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiWithdrawalTime {
+    /// Food-animal species ("bovine", "equine", "ovine", "caprine", "porcine")
+    pub species: String,
+    /// Days before meat may be sold
+    pub meat_days: Option<u32>,
+    /// Hours before milk may be sold
+    pub milk_hours: Option<u32>,
+}
+
+impl From<models::WithdrawalTime> for FfiWithdrawalTime {
+    fn from(time: models::WithdrawalTime) -> Self {
+        Self {
+            species: time.species,
+            meat_days: time.meat_days,
+            milk_hours: time.milk_hours,
+        }
+    }
+}
+
+impl From<FfiWithdrawalTime> for models::WithdrawalTime {
+    fn from(time: FfiWithdrawalTime) -> Self {
+        Self {
+            species: time.species,
+            meat_days: time.meat_days,
+            milk_hours: time.milk_hours,
+        }
+    }
+}
+
+/// FFI-safe patient.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPatient {
+    pub local_id: String,
+    pub server_id: Option<String>,
+    pub name: String,
+    pub species: String,
+    pub breed: Option<String>,
+    pub weight_kg: Option<f64>,
+    /// Client (owner) the patient is linked to
+    pub client_id: Option<String>,
+    /// Recorded drug and ingredient allergies
+    pub allergies: Vec<FfiPatientAllergy>,
+}
+
+impl From<Patient> for FfiPatient {
+    fn from(patient: Patient) -> Self {
+        Self {
+            local_id: patient.local_id,
+            server_id: patient.server_id,
+            name: patient.name,
+            species: patient.species,
+            breed: patient.breed,
+            weight_kg: patient.weight_kg,
+            client_id: patient.client_id,
+            allergies: patient.allergies.into_iter().map(|a| a.into()).collect(),
+        }
+    }
+}
+
+/// FFI-safe patient allergy.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPatientAllergy {
+    /// Generic drug or ingredient name ("amoxicillin")
+    pub substance: String,
+    /// Reaction seen, if known
+    pub reaction: Option<String>,
+}
+
+impl From<models::PatientAllergy> for FfiPatientAllergy {
+    fn from(a: models::PatientAllergy) -> Self {
+        Self {
+            substance: a.substance,
+            reaction: a.reaction,
+        }
+    }
+}
+
+impl From<FfiPatientAllergy> for models::PatientAllergy {
+    fn from(a: FfiPatientAllergy) -> Self {
+        Self {
+            substance: a.substance.trim().to_string(),
+            reaction: a.reaction.filter(|r| !r.trim().is_empty()),
+        }
+    }
+}
+
+/// FFI-safe entry on a patient's medication list.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPatientMedication {
+    pub id: i64,
+    pub patient_id: String,
+    /// Catalog SKU, if known
+    pub sku: Option<String>,
+    pub name: String,
+    /// First day (YYYY-MM-DD)
+    pub start_date: String,
+    /// Last day (YYYY-MM-DD); None while ongoing
+    pub end_date: Option<String>,
+    /// "encounter" or "manual"
+    pub source: String,
+    /// Draft of the encounter it was committed on
+    pub draft_id: Option<String>,
+    pub notes: Option<String>,
+}
+
+impl From<models::PatientMedication> for FfiPatientMedication {
+    fn from(m: models::PatientMedication) -> Self {
+        Self {
+            id: m.id,
+            patient_id: m.patient_id,
+            sku: m.sku,
+            name: m.name,
+            start_date: m.start_date,
+            end_date: m.end_date,
+            source: m.source.as_str().into(),
+            draft_id: m.draft_id,
+            notes: m.notes,
+        }
+    }
+}
+
+/// FFI-safe vaccine dose.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiVaccination {
+    pub id: i64,
+    pub patient_id: String,
+    /// Antigen ("rabies")
+    pub antigen: String,
+    /// Service code of the vaccine
+    pub code: String,
+    pub name: String,
+    /// Date given (YYYY-MM-DD)
+    pub administered_on: String,
+    pub lot_number: Option<String>,
+    pub expiration_date: Option<String>,
+    /// When the next dose is due (YYYY-MM-DD); None if no booster
+    pub next_due_date: Option<String>,
+    pub administered_by: Option<String>,
+    /// Draft of the encounter it was committed on; None if entered by hand
+    pub draft_id: Option<String>,
+}
+
+impl From<models::Vaccination> for FfiVaccination {
+    fn from(v: models::Vaccination) -> Self {
+        Self {
+            id: v.id,
+            patient_id: v.patient_id,
+            antigen: v.antigen,
+            code: v.code,
+            name: v.name,
+            administered_on: v.administered_on,
+            lot_number: v.lot_number,
+            expiration_date: v.expiration_date,
+            next_due_date: v.next_due_date,
+            administered_by: v.administered_by,
+            draft_id: v.draft_id,
+        }
+    }
+}
+
+/// FFI-safe vaccine coming due, for reminder lists.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiDueVaccination {
+    /// The patient's latest dose of the antigen
+    pub vaccination: FfiVaccination,
+    /// Days from the query date until due (negative if overdue)
+    pub days_until_due: i64,
+    pub overdue: bool,
+}
+
+/// FFI-safe visit grouping a patient's drafts.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiVisit {
+    pub visit_id: String,
+    pub patient_id: String,
+    /// Day of the visit (YYYY-MM-DD)
+    pub visit_date: String,
+    pub reason: Option<String>,
+    pub attending_vet: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<models::Visit> for FfiVisit {
+    fn from(v: models::Visit) -> Self {
+        Self {
+            visit_id: v.visit_id,
+            patient_id: v.patient_id,
+            visit_date: v.visit_date,
+            reason: v.reason,
+            attending_vet: v.attending_vet,
+            created_at: v.created_at,
+            updated_at: v.updated_at,
+        }
+    }
+}
+
+/// FFI-safe billing rollup for one visit.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiVisitBilling {
+    pub visit_id: String,
+    pub patient_id: String,
+    /// Drafts linked to the visit, committed or not
+    pub draft_ids: Vec<String>,
+    /// Merkle leaf hashes of the visit's committed encounters
+    pub leaf_hashes: Vec<String>,
+    /// Sum of extended prices, before tax
+    pub total: f64,
+    pub tax: f64,
+    pub total_with_tax: f64,
+    pub priced_items: u32,
+    pub unpriced_items: u32,
+}
+
+/// FFI-safe client (owner) with contact details.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiClient {
+    pub client_id: String,
+    /// PIMS client ID, if known
+    pub server_id: Option<String>,
+    pub name: String,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    pub address: Option<String>,
+    pub notes: Option<String>,
+}
+
+impl From<models::Client> for FfiClient {
+    fn from(c: models::Client) -> Self {
+        Self {
+            client_id: c.client_id,
+            server_id: c.server_id,
+            name: c.name,
+            phone: c.phone,
+            email: c.email,
+            address: c.address,
+            notes: c.notes,
+        }
+    }
+}
+
+/// FFI-safe encounter draft.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiEncounterDraft {
+    pub draft_id: String,
+    pub patient_id: String,
+    pub transcript: String,
+    pub status: String,
+    pub pending_review_count: u32,
+    pub lowest_confidence: Option<f64>,
+    pub controlled_item_count: u32,
+    pub interaction_warnings: Vec<FfiInteractionWarning>,
+    pub has_safety_warnings: bool,
+    pub has_ambiguous_items: bool,
+    pub review_order: Vec<u32>,
+    /// Medications the owner said the patient is on at home (not orders)
+    pub reported_medications: Vec<FfiDrugMention>,
+    /// Procedures, vaccines, and diagnostics matched to the services catalog
+    pub service_items: Vec<FfiServiceLineItem>,
+    /// Accepted vaccines and controlled drugs with no lot number recorded
+    pub missing_lot_count: u32,
+    /// Visit the draft is linked to
+    pub visit_id: Option<String>,
+}
+
+impl From<EncounterDraft> for FfiEncounterDraft {
+    fn from(draft: EncounterDraft) -> Self {
+        Self {
+            draft_id: draft.draft_id.clone(),
+            patient_id: draft.patient_id.clone(),
+            transcript: draft.transcript.clone(),
+            status: format!("{:?}", draft.status),
+            pending_review_count: draft.pending_review_count() as u32,
+            lowest_confidence: draft.lowest_confidence(),
+            controlled_item_count: draft.controlled_item_indices().len() as u32,
+            missing_lot_count: draft.missing_lot_count() as u32,
+            visit_id: draft.visit_id.clone(),
+            has_safety_warnings: draft.has_safety_warnings(),
+            has_ambiguous_items: draft.has_ambiguous_items(),
+            review_order: draft.review_order().into_iter().map(|i| i as u32).collect(),
+            interaction_warnings: draft
+                .interaction_warnings
+                .into_iter()
+                .map(|w| w.into())
+                .collect(),
+            reported_medications: draft
+                .reported_medications
+                .into_iter()
+                .map(|m| m.into())
+                .collect(),
+            service_items: draft.service_items.into_iter().map(|s| s.into()).collect(),
+        }
+    }
+}
+
+/// FFI-safe draft summary for a patient timeline.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiDraftSummary {
+    pub draft_id: String,
+    pub patient_id: String,
+    pub status: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub item_count: u32,
+    pub pending_review_count: u32,
+}
+
+impl From<EncounterDraft> for FfiDraftSummary {
+    fn from(draft: EncounterDraft) -> Self {
+        Self {
+            status: format!("{:?}", draft.status),
+            item_count: (draft.resolved_items.len()
+                + draft.service_items.len()
+                + draft.manual_items.len()) as u32,
+            pending_review_count: draft.pending_review_count() as u32,
+            draft_id: draft.draft_id,
+            patient_id: draft.patient_id,
+            created_at: draft.created_at,
+            updated_at: draft.updated_at,
+        }
+    }
+}
+
+/// FFI-safe committed encounter for a patient timeline.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCommittedEncounter {
+    pub draft_id: String,
+    pub patient_id: String,
+    pub reviewed_by: String,
+    pub reviewed_at: String,
+    /// When the leaf was added to the tree
+    pub committed_at: String,
+    pub line_item_count: u32,
+    pub notes: Option<String>,
+    /// Leaf identifying the encounter in Merkle proofs
+    pub leaf_hash: String,
+}
+
+/// Draft items in one clinical category.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiItemGroup {
+    /// Category identifier ("anesthesia")
+    pub category: String,
+    /// Heading for display ("Anesthesia")
+    pub label: String,
+    pub items: Vec<FfiDraftItem>,
+}
+
+/// A resolved item with its position in the draft.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiDraftItem {
+    pub index: u32,
+    pub item: FfiResolvedItem,
+}
+
+/// FFI-safe resolved item.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiResolvedItem {
+    pub normalized_name: String,
+    pub normalized_dose: Option<f64>,
+    pub normalized_unit: Option<String>,
+    pub normalized_route: Option<String>,
+    pub top_sku: String,
+    pub top_name: String,
+    pub top_confidence: f64,
+    pub top_suggested_quantity: Option<f64>,
+    pub top_suggested_unit: Option<String>,
+    pub alternatives: Vec<FfiScoredCandidate>,
+    pub controlled_schedule: Option<String>,
+    pub controlled_confirmed: bool,
+    pub safety_warnings: Vec<FfiSafetyWarning>,
+    /// Whether the accepted SKU matches a recorded allergy that still needs
+    /// an override
+    pub requires_allergy_override: bool,
+    /// Who chose to give the item despite the allergy, once overridden
+    pub allergy_overridden_by: Option<String>,
+    pub source_spans: Vec<FfiSourceSpan>,
+    /// Offsets of the drug, dose, unit, route, and frequency tokens
+    pub field_spans: FfiFieldSpans,
+    pub tied_skus: Vec<String>,
+    /// Clinical category ("anesthesia", "analgesia", "antibiotics", "fluids",
+    /// "other", "supplies")
+    pub category: String,
+    /// "absolute" ("100 mg"), "per_kg" ("2 mg/kg"), or "rate" ("3 mcg/kg/hr")
+    pub dose_kind: Option<String>,
+    /// CRI duration in hours, when the dose is an infusion rate
+    pub infusion_duration_hours: Option<f64>,
+    /// Total amount delivered over the CRI, in `infusion_total_unit`
+    pub infusion_total: Option<f64>,
+    pub infusion_total_unit: Option<String>,
+    /// Dosing phases when dictated as a taper (empty otherwise)
+    pub taper_phases: Vec<FfiDosePhase>,
+    /// "administered", "dispensed", or "prescribed", when known
+    pub disposition: Option<String>,
+    /// Role required to approve, when the item contains a restricted ingredient
+    pub escalation_required_role: Option<String>,
+    /// Who gave the escalated approval, once given
+    pub escalation_approved_by: Option<String>,
+    /// Whether a lot number must be recorded (controlled drugs given or
+    /// dispensed)
+    pub requires_lot: bool,
+    pub lot_number: Option<String>,
+    /// Lot expiration date (YYYY-MM-DD)
+    pub expiration_date: Option<String>,
+    /// Meat/milk withdrawal for the top candidate (food-animal patients)
+    pub withdrawal: Option<FfiWithdrawal>,
+}
+
+impl From<models::ResolvedItem> for FfiResolvedItem {
+    fn from(item: models::ResolvedItem) -> Self {
+        let controlled_schedule = item.controlled_schedule().map(|s| s.to_string());
+        let controlled_confirmed = item.controlled_confirmed_by.is_some();
+        let source_spans = item.source_spans().into_iter().map(|s| s.into()).collect();
+        let field_spans = item.mention.original.field_spans.clone().into();
+        let infusion = item.mention.infusion.clone();
+        let dose_kind = item.mention.dose_expression().map(|d| d.kind().to_string());
+        let category = models::ClinicalCategory::for_item(&item)
+            .as_str()
+            .to_string();
+        let requires_lot = item.requires_lot();
+        let requires_allergy_override = item.requires_allergy_override();
+        Self {
+            normalized_name: item.mention.normalized_name,
+            normalized_dose: item.mention.normalized_dose,
+            normalized_unit: item.mention.normalized_unit.map(String::from),
+            normalized_route: item.mention.normalized_route,
+            top_sku: item.top_candidate.sku,
+            top_name: item.top_candidate.name,
+            top_confidence: item.top_candidate.confidence,
+            top_suggested_quantity: item
+                .top_candidate
+                .suggested_quantity
+                .as_ref()
+                .map(|q| q.per_dose),
+            top_suggested_unit: item.top_candidate.suggested_quantity.map(|q| q.unit),
+            alternatives: item.alternatives.into_iter().map(|c| c.into()).collect(),
+            controlled_schedule,
+            controlled_confirmed,
+            safety_warnings: item.safety_warnings.into_iter().map(|w| w.into()).collect(),
+            requires_allergy_override,
+            allergy_overridden_by: item.allergy_overridden_by,
+            source_spans,
+            field_spans,
+            tied_skus: item.tied_skus,
+            category,
+            dose_kind,
+            infusion_duration_hours: infusion.as_ref().and_then(|r| r.duration_hours),
+            infusion_total: infusion.as_ref().and_then(|r| r.total_amount),
+            infusion_total_unit: infusion.map(|r| r.unit),
+            taper_phases: item
+                .mention
+                .taper
+                .map(|t| t.phases.into_iter().map(|p| p.into()).collect())
+                .unwrap_or_default(),
+            disposition: item.disposition.map(|d| d.as_str().to_string()),
+            escalation_required_role: item.escalation.as_ref().map(|e| e.required_role.clone()),
+            escalation_approved_by: item
+                .escalation
+                .and_then(|e| e.approval)
+                .map(|a| a.approved_by),
+            requires_lot,
+            lot_number: item.lot_number,
+            expiration_date: item.expiration_date,
+            withdrawal: item.withdrawal.map(|w| w.into()),
+        }
+    }
+}
+
+/// FFI-safe escalation rule.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiEscalationRule {
+    pub ingredient: String,
+    pub required_role: String,
+    pub note: String,
+}
+
+impl From<models::EscalationRule> for FfiEscalationRule {
+    fn from(rule: models::EscalationRule) -> Self {
+        Self {
+            ingredient: rule.ingredient,
+            required_role: rule.required_role,
+            note: rule.note,
+        }
+    }
+}
+
+/// FFI-safe resolution trace.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiResolutionTrace {
+    pub spoken_name: String,
+    pub normalized_name: String,
+    /// "spoken → canonical" for each alias that fired
+    pub aliases_fired: Vec<String>,
+    pub fts_query: String,
+    pub used_fallback: bool,
+    pub candidates: Vec<FfiCandidateTrace>,
+    /// "name", "species", "route", or "dose"
+    pub deciding_factor: Option<String>,
+    /// Plain-text rendering of the whole trace
+    pub rendered: String,
+}
+
+impl From<models::ResolutionTrace> for FfiResolutionTrace {
+    fn from(trace: models::ResolutionTrace) -> Self {
+        let rendered = trace.render();
+        Self {
+            spoken_name: trace.spoken_name,
+            normalized_name: trace.normalized_name,
+            aliases_fired: trace
+                .aliases_fired
+                .into_iter()
+                .map(|a| format!("{} → {}", a.spoken, a.canonical))
+                .collect(),
+            fts_query: trace.search.fts_query,
+            used_fallback: trace.search.used_fallback,
+            candidates: trace
+                .search
+                .candidates
+                .into_iter()
+                .map(|c| c.into())
+                .collect(),
+            deciding_factor: trace.deciding_factor.map(|f| f.as_str().to_string()),
+            rendered,
+        }
+    }
+}
+
+/// FFI-safe per-candidate score trace.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCandidateTrace {
+    pub sku: String,
+    pub name: String,
+    pub confidence: f64,
+    pub name_score: f64,
+    pub species_score: f64,
+    pub route_score: f64,
+    pub dose_score: f64,
+    pub dominant_factor: String,
+    pub matched_alias: Option<String>,
+    pub below_threshold: bool,
+}
+
+impl From<models::CandidateTrace> for FfiCandidateTrace {
+    fn from(c: models::CandidateTrace) -> Self {
+        Self {
+            sku: c.sku,
+            name: c.name,
+            confidence: c.confidence,
+            name_score: c.score_breakdown.name_score,
+            species_score: c.score_breakdown.species_score,
+            route_score: c.score_breakdown.route_score,
+            dose_score: c.score_breakdown.dose_score,
+            dominant_factor: c.dominant_factor.as_str().to_string(),
+            matched_alias: c.matched_alias,
+            below_threshold: c.below_threshold,
+        }
+    }
+}
+
+/// FFI-safe transcript span.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiSourceSpan {
+    pub start_offset: u32,
+    pub end_offset: u32,
+}
+
+impl From<models::SourceSpan> for FfiSourceSpan {
+    fn from(span: models::SourceSpan) -> Self {
+        Self {
+            start_offset: span.start_offset as u32,
+            end_offset: span.end_offset as u32,
+        }
+    }
+}
+
+/// FFI-safe transcript offsets of a mention's fields, for highlighting.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiFieldSpans {
+    pub drug: Option<FfiSourceSpan>,
+    pub dose: Option<FfiSourceSpan>,
+    pub unit: Option<FfiSourceSpan>,
+    pub route: Option<FfiSourceSpan>,
+    pub frequency: Option<FfiSourceSpan>,
+}
+
+impl From<models::FieldSpans> for FfiFieldSpans {
+    fn from(spans: models::FieldSpans) -> Self {
+        Self {
+            drug: spans.drug.map(|s| s.into()),
+            dose: spans.dose.map(|s| s.into()),
+            unit: spans.unit.map(|s| s.into()),
+            route: spans.route.map(|s| s.into()),
+            frequency: spans.frequency.map(|s| s.into()),
+        }
+    }
+}
+
+/// FFI-safe species/breed safety warning.
+///
+/// `severity` is "caution" or "contraindicated".
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiSafetyWarning {
+    pub sku: String,
+    pub drug: String,
+    pub severity: String,
+    pub reason: String,
+    pub message: String,
+    /// Recorded allergy: the item can't be committed on this SKU until
+    /// overridden (`override_allergy_warning`)
+    pub blocking: bool,
+}
+
+impl From<models::SafetyWarning> for FfiSafetyWarning {
+    fn from(warning: models::SafetyWarning) -> Self {
+        let message = warning.message();
+        Self {
+            sku: warning.sku,
+            drug: warning.drug,
+            severity: warning.severity.as_str().to_string(),
+            reason: warning.reason,
+            message,
+            blocking: warning.blocking,
+        }
+    }
+}
+
+/// FFI-safe scored candidate.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiScoredCandidate {
+    pub sku: String,
+    pub name: String,
+    pub confidence: f64,
+    pub suggested_quantity: Option<f64>,
+    pub suggested_unit: Option<String>,
+    pub controlled_schedule: Option<String>,
+}
+
+impl From<models::ScoredCandidate> for FfiScoredCandidate {
+    fn from(candidate: models::ScoredCandidate) -> Self {
+        Self {
+            sku: candidate.sku,
+            name: candidate.name,
+            confidence: candidate.confidence,
+            suggested_quantity: candidate.suggested_quantity.as_ref().map(|q| q.per_dose),
+            suggested_unit: candidate.suggested_quantity.map(|q| q.unit),
+            controlled_schedule: candidate.controlled_schedule.map(|s| s.to_string()),
+        }
+    }
+}
+
+/// FFI-safe reviewed encounter.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiReviewedEncounter {
+    pub draft_id: String,
+    pub patient_id: String,
+    pub patient_server_id: Option<String>,
+    pub transcript: String,
+    pub line_items: Vec<FfiLineItem>,
+    pub reviewed_by: String,
+    pub notes: Option<String>,
+}
+
+impl From<FfiReviewedEncounter> for ReviewedEncounter {
+    fn from(enc: FfiReviewedEncounter) -> Self {
+        ReviewedEncounter {
+            draft_id: enc.draft_id,
+            patient_id: enc.patient_id,
+            patient_server_id: enc.patient_server_id,
+            transcript: enc.transcript,
+            line_items: enc.line_items.into_iter().map(|i| i.into()).collect(),
+            reviewed_by: enc.reviewed_by,
+            reviewed_at: chrono::Utc::now().to_rfc3339(),
+            notes: enc.notes,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+}
+
+/// FFI-safe line item.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiLineItem {
+    pub sku: String,
+    pub name: String,
+    pub quantity: f64,
+    pub unit: String,
+    pub route: Option<String>,
+    pub original_mention: String,
+    pub controlled_schedule: Option<String>,
+    /// Taper phases; `quantity` is the total over all of them
+    pub schedule: Vec<FfiDosePhase>,
+    /// "administered", "dispensed", or "prescribed"
+    pub disposition: Option<String>,
+    /// Manufacturer lot (vaccines and controlled drugs)
+    pub lot_number: Option<String>,
+    /// Lot expiration date (YYYY-MM-DD); filled in from the inventory lot
+    /// when unset
+    pub expiration_date: Option<String>,
+    /// Meat/milk withdrawal for food-animal patients; worked out from the
+    /// catalog on commit (ignored on input)
+    pub withdrawal: Option<FfiWithdrawal>,
+    /// Sign-off for an item with an escalation-restricted ingredient
+    pub escalation_approval: Option<FfiEscalationApproval>,
+    /// Reviewer who confirmed a controlled substance
+    pub controlled_confirmed_by: Option<String>,
+    /// Reviewer who chose to give the item despite a recorded allergy;
+    /// taken from the draft on commit (ignored on input)
+    pub allergy_overridden_by: Option<String>,
+}
+
+impl From<FfiLineItem> for EncounterLineItem {
+    fn from(item: FfiLineItem) -> Self {
+        EncounterLineItem {
+            sku: item.sku,
+            name: item.name,
+            quantity: item.quantity,
+            unit: item.unit.into(),
+            route: item.route,
+            original_mention: item.original_mention,
+            resolution_method: ResolutionMethod::SystemApproved { confidence: 1.0 },
+            controlled_schedule: item
+                .controlled_schedule
+                .as_deref()
+                .and_then(ControlledSchedule::parse),
+            source_spans: vec![],
+            schedule: (!item.schedule.is_empty()).then(|| models::TaperSchedule {
+                phases: item.schedule.into_iter().map(|p| p.into()).collect(),
+            }),
+            disposition: item
+                .disposition
+                .as_deref()
+                .and_then(models::DispositionType::parse),
+            lot_number: item.lot_number,
+            expiration_date: item.expiration_date,
+            withdrawal: None,
+            escalation_approval: item.escalation_approval.map(|a| a.into()),
+            controlled_confirmed_by: item.controlled_confirmed_by,
+            allergy_overridden_by: None,
+        }
+    }
+}
+
+impl From<EncounterLineItem> for FfiLineItem {
+    fn from(item: EncounterLineItem) -> Self {
+        Self {
+            sku: item.sku,
+            name: item.name,
+            quantity: item.quantity,
+            unit: item.unit.to_string(),
+            route: item.route,
+            original_mention: item.original_mention,
+            controlled_schedule: item.controlled_schedule.map(|s| s.to_string()),
+            schedule: item
+                .schedule
+                .map(|t| t.phases.into_iter().map(|p| p.into()).collect())
+                .unwrap_or_default(),
+            disposition: item.disposition.map(|d| d.as_str().to_string()),
+            lot_number: item.lot_number,
+            expiration_date: item.expiration_date,
+            withdrawal: item.withdrawal.map(|w| w.into()),
+            escalation_approval: item.escalation_approval.map(|a| a.into()),
+            controlled_confirmed_by: item.controlled_confirmed_by,
+            allergy_overridden_by: item.allergy_overridden_by,
+        }
+    }
+}
+
+/// FFI-safe escalation approval.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiEscalationApproval {
+    /// User ID (or name) of the approver; must hold `role`
+    pub approved_by: String,
+    pub role: String,
+    /// Required reason note
+    pub reason: String,
+    /// RFC 3339; the commit time is used when empty
+    pub approved_at: String,
+}
+
+impl From<models::EscalationApproval> for FfiEscalationApproval {
+    fn from(a: models::EscalationApproval) -> Self {
+        Self {
+            approved_by: a.approved_by,
+            role: a.role,
+            reason: a.reason,
+            approved_at: a.approved_at,
+        }
+    }
+}
+
+impl From<FfiEscalationApproval> for models::EscalationApproval {
+    fn from(a: FfiEscalationApproval) -> Self {
+        let approved_at = match a.approved_at.trim() {
+            "" => chrono::Utc::now().to_rfc3339(),
+            at => at.to_string(),
+        };
+        Self {
+            approved_by: a.approved_by.trim().to_string(),
+            role: a.role.trim().to_string(),
+            reason: a.reason.trim().to_string(),
+            approved_at,
+        }
+    }
+}
+
+/// FFI-safe taper phase.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiDosePhase {
+    pub dose: f64,
+    pub unit: String,
+    pub frequency: String,
+    pub doses_per_day: f64,
+    pub days: f64,
+}
+
+impl From<models::DosePhase> for FfiDosePhase {
+    fn from(phase: models::DosePhase) -> Self {
+        Self {
+            dose: phase.dose,
+            unit: phase.unit,
+            frequency: phase.frequency,
+            doses_per_day: phase.doses_per_day,
+            days: phase.days,
+        }
+    }
+}
+
+impl From<FfiDosePhase> for models::DosePhase {
+    fn from(phase: FfiDosePhase) -> Self {
+        Self {
+            dose: phase.dose,
+            unit: phase.unit,
+            frequency: phase.frequency,
+            doses_per_day: phase.doses_per_day,
+            days: phase.days,
+        }
+    }
+}
+
+/// FFI-safe withdrawal a treated food animal is under.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiWithdrawal {
+    pub species: String,
+    pub meat_days: Option<u32>,
+    pub milk_hours: Option<u32>,
+    /// Last day of treatment the periods count from (YYYY-MM-DD)
+    pub last_dose_date: String,
+    pub meat_end_date: Option<String>,
+    pub milk_end_date: Option<String>,
+    /// The later of the two end dates
+    pub end_date: Option<String>,
+}
+
+impl From<models::Withdrawal> for FfiWithdrawal {
+    fn from(withdrawal: models::Withdrawal) -> Self {
+        Self {
+            end_date: withdrawal.end_date().map(String::from),
+            species: withdrawal.species,
+            meat_days: withdrawal.meat_days,
+            milk_hours: withdrawal.milk_hours,
+            last_dose_date: withdrawal.last_dose_date,
+            meat_end_date: withdrawal.meat_end_date,
+            milk_end_date: withdrawal.milk_end_date,
+        }
+    }
+}
+
+/// FFI-safe provisional price estimate.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPriceEstimate {
+    pub draft_id: String,
+    pub expected: f64,
+    pub low: f64,
+    pub high: f64,
+    pub lines: Vec<FfiEstimateLine>,
+    pub pending_items: u32,
+    pub unpriced_items: u32,
+    /// Always true; show the estimate as provisional
+    pub provisional: bool,
+}
+
+impl From<PriceEstimate> for FfiPriceEstimate {
+    fn from(estimate: PriceEstimate) -> Self {
+        Self {
+            draft_id: estimate.draft_id,
+            expected: estimate.expected,
+            low: estimate.low,
+            high: estimate.high,
+            lines: estimate.lines.into_iter().map(|l| l.into()).collect(),
+            pending_items: estimate.pending_items as u32,
+            unpriced_items: estimate.unpriced_items as u32,
+            provisional: estimate.provisional,
+        }
+    }
+}
+
+/// FFI-safe estimate line.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiEstimateLine {
+    pub item_index: Option<u32>,
+    pub sku: String,
+    pub name: String,
+    pub expected: Option<f64>,
+    pub low: Option<f64>,
+    pub high: Option<f64>,
+    pub settled: bool,
+}
+
+impl From<models::EstimateLine> for FfiEstimateLine {
+    fn from(line: models::EstimateLine) -> Self {
+        Self {
+            item_index: line.item_index.map(|i| i as u32),
+            sku: line.sku,
+            name: line.name,
+            expected: line.expected,
+            low: line.low,
+            high: line.high,
+            settled: line.settled,
+        }
+    }
+}
+
+/// FFI-safe commit preview.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCommitPreview {
+    pub draft_id: String,
+    pub patient_id: String,
+    pub transcript: String,
+    pub entries: Vec<FfiPreviewEntry>,
+    pub ready_to_commit: bool,
+    pub added_count: u32,
+    pub dropped_count: u32,
+    pub dose_changed_count: u32,
+}
+
+impl From<CommitPreview> for FfiCommitPreview {
+    fn from(preview: CommitPreview) -> Self {
+        let added_count = preview.count(|c| matches!(c, PreviewChange::Added)) as u32;
+        let dropped_count = preview.count(|c| matches!(c, PreviewChange::Dropped)) as u32;
+        let dose_changed_count =
+            preview.count(|c| matches!(c, PreviewChange::DoseChanged { .. })) as u32;
+        Self {
+            draft_id: preview.draft_id,
+            patient_id: preview.patient_id,
+            transcript: preview.transcript,
+            entries: preview.entries.into_iter().map(|e| e.into()).collect(),
+            ready_to_commit: preview.ready_to_commit,
+            added_count,
+            dropped_count,
+            dose_changed_count,
+        }
+    }
+}
+
+/// FFI-safe commit preview entry.
+///
+/// `change` is one of "unchanged", "added", "dropped", "dose_changed", "pending".
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPreviewEntry {
+    pub item_index: Option<u32>,
+    pub span_start: Option<u32>,
+    pub span_end: Option<u32>,
+    pub span_text: Option<String>,
+    pub line_item: Option<FfiLineItem>,
+    pub change: String,
+    pub spoken_dose: Option<f64>,
+    pub spoken_unit: Option<String>,
+}
+
+impl From<models::PreviewEntry> for FfiPreviewEntry {
+    fn from(entry: models::PreviewEntry) -> Self {
+        let (change, spoken_dose, spoken_unit) = match entry.change {
+            PreviewChange::Unchanged => ("unchanged", None, None),
+            PreviewChange::Added => ("added", None, None),
+            PreviewChange::Dropped => ("dropped", None, None),
+            PreviewChange::DoseChanged {
+                spoken_dose,
+                spoken_unit,
+            } => ("dose_changed", spoken_dose, spoken_unit),
+            PreviewChange::Pending => ("pending", None, None),
+        };
+        Self {
+            item_index: entry.item_index.map(|i| i as u32),
+            span_start: entry.span.as_ref().map(|s| s.start_offset as u32),
+            span_end: entry.span.as_ref().map(|s| s.end_offset as u32),
+            span_text: entry.span.map(|s| s.text),
+            line_item: entry.line_item.map(|i| i.into()),
+            change: change.to_string(),
+            spoken_dose,
+            spoken_unit,
+        }
+    }
+}
+
+/// FFI-safe interaction warning.
+///
+/// `source_a`/`source_b` are "item", "manual", or "active_medication";
+/// `index_a`/`index_b` index into the resolved or manual items accordingly.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiInteractionWarning {
+    pub drug_a: String,
+    pub source_a: String,
+    pub index_a: Option<u32>,
+    pub drug_b: String,
+    pub source_b: String,
+    pub index_b: Option<u32>,
+    pub severity: String,
+    pub note: String,
+    pub message: String,
+}
+
+impl From<models::InteractionWarning> for FfiInteractionWarning {
+    fn from(warning: models::InteractionWarning) -> Self {
+        fn source(source: &models::InteractionSource) -> (String, Option<u32>) {
+            match source {
+                models::InteractionSource::DraftItem { item_index } => {
+                    ("item".into(), Some(*item_index as u32))
+                }
+                models::InteractionSource::ManualItem { manual_index } => {
+                    ("manual".into(), Some(*manual_index as u32))
+                }
+                models::InteractionSource::ActiveMedication => ("active_medication".into(), None),
+            }
+        }
+        let message = warning.message();
+        let (source_a, index_a) = source(&warning.source_a);
+        let (source_b, index_b) = source(&warning.source_b);
+        Self {
+            drug_a: warning.drug_a,
+            source_a,
+            index_a,
+            drug_b: warning.drug_b,
+            source_b,
+            index_b,
+            severity: warning.severity.as_str().to_string(),
+            note: warning.note,
+            message,
+        }
+    }
+}
+
+/// FFI-safe legal hold.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiLegalHold {
+    pub subject_type: String,
+    pub subject_id: String,
+    pub reason: String,
+    pub placed_by: String,
+    pub placed_at: String,
+}
+
+impl From<models::LegalHold> for FfiLegalHold {
+    fn from(hold: models::LegalHold) -> Self {
+        Self {
+            subject_type: hold.subject_type.as_str().to_string(),
+            subject_id: hold.subject_id,
+            reason: hold.reason,
+            placed_by: hold.placed_by,
+            placed_at: hold.placed_at,
+        }
+    }
+}
+
+/// FFI-safe billing export filter. `None` fields and an empty `skus` match
+/// everything.
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct FfiBillingFilter {
+    /// Patient local ID
+    pub patient_id: Option<String>,
+    /// Client the patient is linked to
+    pub client_id: Option<String>,
+    /// Visit the encounter was committed under
+    pub visit_id: Option<String>,
+    /// Reviewing vet (case-insensitive)
+    pub reviewed_by: Option<String>,
+    /// Reviewed at or after (RFC 3339, or "YYYY-MM-DD HH:MM:SS" in UTC)
+    pub start: Option<String>,
+    /// Reviewed at or before
+    pub end: Option<String>,
+    /// Only these SKUs
+    pub skus: Vec<String>,
+}
+
+/// FFI-safe export manifest.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiExportManifest {
+    pub path: String,
+    /// "json" or "csv"
+    pub format: String,
+    pub bytes: u64,
+    /// SHA-256 of the file contents (hex)
+    pub checksum: String,
+    pub record_count: u64,
+    pub exported_at: String,
+}
+
+impl From<export::ExportManifest> for FfiExportManifest {
+    fn from(manifest: export::ExportManifest) -> Self {
+        Self {
+            path: manifest.path,
+            format: manifest.format.as_str().to_string(),
+            bytes: manifest.bytes,
+            checksum: manifest.checksum,
+            record_count: manifest.record_count as u64,
+            exported_at: manifest.exported_at,
+        }
+    }
+}
+
+/// FFI-safe summary of an audit bundle written by `export_audit_bundle`.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiAuditBundle {
+    pub path: String,
+    /// Zip size in bytes
+    pub bytes: u64,
+    /// Merkle root hash recorded in the manifest
+    pub root_hash: String,
+    pub leaf_count: u32,
+    /// Encounters in the audited period
+    pub encounter_count: u64,
+    /// Whether the manifest is signed with the export signing key
+    pub signed: bool,
+    pub created_at: String,
+}
+
+/// FFI-safe result of `verify_export`.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiExportVerification {
+    /// Whether the body still matches its recorded SHA-256
+    pub body_hash_valid: bool,
+    /// Whether the export is signed by the trusted key; `None` when checked
+    /// without one
+    pub signature_valid: Option<bool>,
+    /// Signer's public key recorded in the export (hex)
+    pub public_key: Option<String>,
+    pub root_hash: String,
+    pub leaf_count: u32,
+    /// Body unmodified and signed by the trusted key
+    pub is_valid: bool,
+}
+
+impl From<export::ExportVerification> for FfiExportVerification {
+    fn from(verification: export::ExportVerification) -> Self {
+        Self {
+            is_valid: verification.is_valid(),
+            body_hash_valid: verification.body_hash_valid,
+            signature_valid: verification.signature_valid,
+            public_key: verification.public_key,
+            root_hash: verification.root_hash,
+            leaf_count: verification.leaf_count,
+        }
+    }
+}
+
+/// Stock on hand for one SKU, e.g. from the last controlled drug count.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiStockBalance {
+    pub sku: String,
+    pub quantity: f64,
+}
+
+/// FFI-safe device identity.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiDeviceIdentity {
+    pub device_id: String,
+    pub device_name: String,
+    pub provisioned_at: String,
+    pub key_fingerprints: Vec<FfiKeyFingerprint>,
+}
+
+impl From<models::DeviceIdentity> for FfiDeviceIdentity {
+    fn from(identity: models::DeviceIdentity) -> Self {
+        Self {
+            device_id: identity.device_id,
+            device_name: identity.device_name,
+            provisioned_at: identity.provisioned_at,
+            key_fingerprints: identity
+                .key_fingerprints
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        }
+    }
+}
+
+/// FFI-safe key fingerprint.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiKeyFingerprint {
+    /// What the key is for (e.g., "signing", "sync")
+    pub purpose: String,
+    pub fingerprint: String,
+    pub added_at: String,
+}
+
+impl From<models::KeyFingerprint> for FfiKeyFingerprint {
+    fn from(key: models::KeyFingerprint) -> Self {
+        Self {
+            purpose: key.purpose,
+            fingerprint: key.fingerprint,
+            added_at: key.added_at,
+        }
+    }
+}
+
+/// FFI-safe disambiguator weights and limits.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiScoringConfig {
+    pub name_weight: f64,
+    pub species_weight: f64,
+    pub route_weight: f64,
+    pub dose_weight: f64,
+    /// Candidates below this confidence are dropped
+    pub min_confidence: f64,
+    /// Candidates retrieved from full-text search before scoring
+    pub fts_candidate_limit: u32,
+    /// Alternatives returned after the top candidate
+    pub max_alternatives: u32,
+    /// How much a low extraction confidence pulls candidates down (0 - 1;
+    /// 0 ignores it)
+    pub extraction_weight: f64,
+}
+
+impl From<models::ScoringConfig> for FfiScoringConfig {
+    fn from(config: models::ScoringConfig) -> Self {
+        Self {
+            name_weight: config.name_weight,
+            species_weight: config.species_weight,
+            route_weight: config.route_weight,
+            dose_weight: config.dose_weight,
+            min_confidence: config.min_confidence,
+            fts_candidate_limit: config.fts_candidate_limit as u32,
+            max_alternatives: config.max_alternatives as u32,
+            extraction_weight: config.extraction_weight,
+        }
+    }
+}
+
+/// FFI-safe settings for `open_database_with_options`. `None` keeps the
+/// stored value.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCoreOptions {
+    /// Disambiguator weights, minimum confidence, and FTS candidate limit
+    pub scoring: Option<FfiScoringConfig>,
+    /// "attention" (warnings, ambiguity, lowest confidence first),
+    /// "newest_first", or "oldest_first"
+    pub review_queue_order: Option<String>,
+    /// Dictation language tag ("en", "es")
+    pub locale: Option<String>,
+    /// System identifier stamped on compliance exports
+    pub system_id: Option<String>,
+    /// When the PIMS tree has diverged: "local_wins" (push as usual),
+    /// "server_rebase" (PIMS re-appends our leaves), or "manual" (stop until
+    /// `resolve_sync_conflict`)
+    pub sync_conflict_strategy: Option<String>,
+}
+
+impl From<FfiScoringConfig> for models::ScoringConfig {
+    fn from(config: FfiScoringConfig) -> Self {
+        Self {
+            name_weight: config.name_weight,
+            species_weight: config.species_weight,
+            route_weight: config.route_weight,
+            dose_weight: config.dose_weight,
+            min_confidence: config.min_confidence,
+            fts_candidate_limit: config.fts_candidate_limit as usize,
+            max_alternatives: config.max_alternatives as usize,
+            extraction_weight: config.extraction_weight,
+        }
+    }
+}
+
+/// FFI-safe QuickBooks item for one SKU.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiQuickBooksItem {
+    pub sku: String,
+    /// Item name in the QuickBooks item list
+    pub item: String,
+    /// Income account; `None` uses the mapping's default
+    pub account: Option<String>,
+}
+
+/// FFI-safe QuickBooks mapping for IIF billing exports.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiQuickBooksMapping {
+    /// Receivable account invoices are posted to
+    pub receivable_account: String,
+    /// Income account for items without their own
+    pub income_account: String,
+    /// Account sales tax is credited to; `None` keeps "Sales Tax Payable"
+    pub tax_account: Option<String>,
+    /// Unmapped SKUs use the SKU as the item name
+    pub items: Vec<FfiQuickBooksItem>,
+}
+
+impl From<models::QuickBooksMapping> for FfiQuickBooksMapping {
+    fn from(mapping: models::QuickBooksMapping) -> Self {
+        Self {
+            receivable_account: mapping.receivable_account,
+            income_account: mapping.income_account,
+            tax_account: Some(mapping.tax_account),
+            items: mapping
+                .items
+                .into_iter()
+                .map(|(sku, item)| FfiQuickBooksItem {
+                    sku,
+                    item: item.item,
+                    account: item.account,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<FfiQuickBooksMapping> for models::QuickBooksMapping {
+    fn from(mapping: FfiQuickBooksMapping) -> Self {
+        Self {
+            receivable_account: mapping.receivable_account,
+            income_account: mapping.income_account,
+            tax_account: mapping
+                .tax_account
+                .unwrap_or_else(|| models::QuickBooksMapping::default().tax_account),
+            items: mapping
+                .items
+                .into_iter()
+                .map(|item| {
+                    let entry = models::QuickBooksItem {
+                        item: item.item,
+                        account: item.account,
+                    };
+                    (item.sku, entry)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One column of a billing CSV.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCsvColumn {
+    /// "draft_id", "patient_id", "patient_server_id", "client_id", "sku", "description",
+    /// "quantity", "unit", "route", "route_description",
+    /// "controlled_schedule", "disposition", "unit_price", "amount",
+    /// "reviewed_by", "reviewed_at", "exported_at", "merkle_hash", or
+    /// "device_id"
+    pub column: String,
+    /// Header text; `None` uses the column name
+    pub header: Option<String>,
+}
+
+/// FFI-safe billing CSV layout.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCsvLayout {
+    /// Columns in output order
+    pub columns: Vec<FfiCsvColumn>,
+    /// One character, e.g. "," or ";" or "\t"
+    pub delimiter: String,
+    /// strftime format for timestamps (e.g. "%m/%d/%Y"); `None` keeps
+    /// RFC 3339
+    pub date_format: Option<String>,
+}
+
+impl From<models::CsvLayout> for FfiCsvLayout {
+    fn from(layout: models::CsvLayout) -> Self {
+        Self {
+            columns: layout
+                .columns
+                .into_iter()
+                .map(|c| FfiCsvColumn {
+                    column: c.column.as_str().to_string(),
+                    header: c.header,
+                })
+                .collect(),
+            delimiter: layout.delimiter.to_string(),
+            date_format: layout.date_format,
+        }
+    }
+}
+
+/// FFI-safe result of `export_unbilled`.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiUnbilledExport {
+    /// Ledger batch to mark imported or void; `None` if nothing was unbilled
+    pub batch_id: Option<String>,
+    pub encounter_count: u32,
+    /// JSON or CSV export
+    pub data: String,
+}
+
+/// FFI-safe export ledger batch.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiExportBatch {
+    pub batch_id: String,
+    /// "pending", "imported", or "void"
+    pub status: String,
+    pub created_at: String,
+    pub status_changed_at: Option<String>,
+    pub leaf_hashes: Vec<String>,
+}
+
+impl From<models::ExportBatch> for FfiExportBatch {
+    fn from(batch: models::ExportBatch) -> Self {
+        Self {
+            batch_id: batch.batch_id,
+            status: batch.status.as_str().to_string(),
+            created_at: batch.created_at,
+            status_changed_at: batch.status_changed_at,
+            leaf_hashes: batch.leaf_hashes,
+        }
+    }
+}
+
+/// FFI-safe extraction model sampling parameters.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiSamplingSettings {
+    /// 0.0 samples greedily (deterministic)
+    pub temperature: f64,
+    /// Nucleus sampling cutoff (1.0 is off)
+    pub top_p: f64,
+    /// Penalty on recently generated tokens (1.0 is off)
+    pub repeat_penalty: f64,
+    pub max_tokens: u32,
+    pub seed: u32,
+}
+
+impl From<models::SamplingSettings> for FfiSamplingSettings {
+    fn from(s: models::SamplingSettings) -> Self {
+        Self {
+            temperature: s.temperature,
+            top_p: s.top_p,
+            repeat_penalty: s.repeat_penalty,
+            max_tokens: s.max_tokens,
+            seed: s.seed,
+        }
+    }
+}
+
+impl From<FfiSamplingSettings> for models::SamplingSettings {
+    fn from(s: FfiSamplingSettings) -> Self {
+        Self {
+            temperature: s.temperature,
+            top_p: s.top_p,
+            repeat_penalty: s.repeat_penalty,
+            max_tokens: s.max_tokens,
+            seed: s.seed,
+        }
+    }
+}
+
+/// FFI-safe sales tax rate for one catalog tax code.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiTaxRate {
+    pub code: String,
+    /// Fraction, e.g. 0.0725 for 7.25%
+    pub rate: f64,
+}
+
+/// FFI-safe raw LLM response retention settings.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiExtractionDebugConfig {
+    pub enabled: bool,
+    pub max_response_bytes: u64,
+    pub retention_hours: u32,
+}
+
+impl From<models::ExtractionDebugConfig> for FfiExtractionDebugConfig {
+    fn from(config: models::ExtractionDebugConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            max_response_bytes: config.max_response_bytes as u64,
+            retention_hours: config.retention_hours,
+        }
+    }
+}
+
+impl From<FfiExtractionDebugConfig> for models::ExtractionDebugConfig {
+    fn from(config: FfiExtractionDebugConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            max_response_bytes: config.max_response_bytes as usize,
+            retention_hours: config.retention_hours,
+        }
+    }
+}
+
+/// FFI-safe retained raw LLM response.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiExtractionDebug {
+    pub draft_id: String,
+    pub model: Option<String>,
+    pub raw_response: String,
+    pub original_bytes: u64,
+    pub truncated: bool,
+    pub recorded_at: String,
+    pub expires_at: String,
+}
+
+impl From<models::ExtractionDebug> for FfiExtractionDebug {
+    fn from(debug: models::ExtractionDebug) -> Self {
+        Self {
+            draft_id: debug.draft_id,
+            model: debug.model,
+            raw_response: debug.raw_response,
+            original_bytes: debug.original_bytes as u64,
+            truncated: debug.truncated,
+            recorded_at: debug.recorded_at,
+            expires_at: debug.expires_at,
+        }
+    }
+}
+
+/// FFI-safe health status.
+///
+/// `state` is "healthy", "recovered", or "degraded".
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiHealthStatus {
+    pub state: String,
+    pub poison_recoveries: u32,
+    pub last_recovery_at: Option<String>,
+    pub integrity_ok: bool,
+    pub lock_available: bool,
+    pub last_error: Option<String>,
+}
+
+impl From<HealthStatus> for FfiHealthStatus {
+    fn from(status: HealthStatus) -> Self {
+        Self {
+            state: status.state.as_str().to_string(),
+            poison_recoveries: status.poison_recoveries,
+            last_recovery_at: status.last_recovery_at,
+            integrity_ok: status.integrity_ok,
+            lock_available: status.lock_available,
+            last_error: status.last_error,
+        }
+    }
+}
+
+/// FFI-safe leaf commit result.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiLeafCommit {
+    pub leaf_hash: String,
+    pub root_hash: String,
+    pub tree_height: u32,
+    pub leaf_count: u32,
+}
+
+impl From<LeafCommit> for FfiLeafCommit {
+    fn from(commit: LeafCommit) -> Self {
+        Self {
+            leaf_hash: commit.leaf_hash,
+            root_hash: commit.root_hash,
+            tree_height: commit.tree_height,
+            leaf_count: commit.leaf_count,
+        }
+    }
+}
+
+/// FFI-safe reviewed draft awaiting commit.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiPendingCommit {
+    pub draft_id: String,
+    pub patient_id: String,
+    pub reviewed_at: String,
+    pub line_item_count: u32,
+    /// Set when the encounter is already in the tree and only the draft
+    /// status needs fixing
+    pub leaf_hash: Option<String>,
+}
+
+impl From<models::PendingCommit> for FfiPendingCommit {
+    fn from(pending: models::PendingCommit) -> Self {
+        Self {
+            draft_id: pending.draft_id,
+            patient_id: pending.patient_id,
+            reviewed_at: pending.reviewed_at,
+            line_item_count: pending.line_item_count as u32,
+            leaf_hash: pending.leaf_hash,
+        }
+    }
+}
+
+/// FFI-safe dashboard summary.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiDashboardSummary {
+    pub pending_review_count: u32,
+    /// Reviewed drafts whose commit never completed; resume with
+    /// `resume_pending_commit`
+    pub pending_commits: Vec<FfiPendingCommit>,
+    pub has_unsynced_changes: bool,
+}
+
+/// FFI-safe tree statistics.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiTreeStats {
+    pub root_hash: Option<String>,
+    pub height: u32,
+    pub leaf_count: u32,
+}
+
+impl From<TreeStats> for FfiTreeStats {
+    fn from(stats: TreeStats) -> Self {
+        Self {
+            root_hash: stats.root_hash,
+            height: stats.height,
+            leaf_count: stats.leaf_count,
+        }
+    }
+}
+
+/// FFI-safe Merkle inclusion proof.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiInclusionProof {
+    pub leaf_hash: String,
+    /// Root the audit path hashes up to
+    pub root_hash: String,
+    pub leaf_index: u32,
+    /// Sibling hashes from leaf to root
+    pub audit_path: Vec<FfiAuditPathEntry>,
+}
+
+/// One sibling on an inclusion proof's audit path.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiAuditPathEntry {
+    pub hash: String,
+    /// Side the sibling is on ("left" or "right")
+    pub position: String,
+}
+
+impl From<MerkleProof> for FfiInclusionProof {
+    fn from(proof: MerkleProof) -> Self {
+        let compliance = proof.to_compliance_format();
+        Self {
+            leaf_hash: compliance.leaf_hash,
+            root_hash: compliance.root_hash,
+            leaf_index: compliance.leaf_index as u32,
+            audit_path: compliance
+                .audit_path
+                .into_iter()
+                .map(|entry| FfiAuditPathEntry {
+                    hash: entry.hash,
+                    position: entry.position,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<FfiInclusionProof> for MerkleProof {
+    type Error = FuzzyDrugsError;
+
+    fn try_from(proof: FfiInclusionProof) -> Result<Self, Self::Error> {
+        let mut proof_hashes = Vec::with_capacity(proof.audit_path.len());
+        let mut proof_directions = Vec::with_capacity(proof.audit_path.len());
+        for entry in proof.audit_path {
+            let on_right = match entry.position.as_str() {
+                "right" => true,
+                "left" => false,
+                other => {
+                    return Err(FuzzyDrugsError::InvalidInput(format!(
+                        "Unknown audit path position: {}",
+                        other
+                    )))
+                }
+            };
+            proof_hashes.push(entry.hash);
+            proof_directions.push(on_right);
+        }
+        Ok(MerkleProof {
+            leaf_hash: proof.leaf_hash,
+            root_hash: proof.root_hash,
+            proof_hashes,
+            proof_directions,
+            leaf_index: proof.leaf_index as usize,
+        })
+    }
+}
+
+/// FFI-safe normalizer data version info.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiNormalizerDataInfo {
+    pub version: String,
+    pub checksum: String,
+    pub source: String,
+}
+
+impl From<NormalizerDataInfo> for FfiNormalizerDataInfo {
+    fn from(info: NormalizerDataInfo) -> Self {
+        Self {
+            version: info.version,
+            checksum: info.checksum,
+            source: format!("{:?}", info.source),
+        }
+    }
+}
+
+/// FFI-safe library capabilities.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCapabilities {
+    pub core_version: String,
+    /// FFI API version (see `get_core_version`)
+    pub api_version: u32,
+    pub normalizer_data: FfiNormalizerDataInfo,
+    /// Dictation language tag ("en", "es")
+    pub normalizer_locale: String,
+    /// Newest sync protocol version spoken to the PIMS
+    pub sync_protocol_version: u32,
+}
+
+/// FFI-safe core and API version info.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiCoreVersion {
+    pub core_version: String,
+    /// Version of the exported FFI surface
+    pub api_version: u32,
+    /// Oldest binding API version still served
+    pub min_supported_api_version: u32,
+    /// Old method names still exported as shims
+    pub deprecated_methods: Vec<FfiDeprecatedMethod>,
+    /// Build-time check of the exported surface against the shipped one
+    pub compatibility_report: String,
+}
+
+/// FFI-safe renamed method.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiDeprecatedMethod {
+    pub method: String,
+    pub replacement: String,
+    pub deprecated_in: u32,
+    /// First API version that may drop the old name
+    pub removable_in: u32,
+}
+
+impl From<compat::Deprecation> for FfiDeprecatedMethod {
+    fn from(d: compat::Deprecation) -> Self {
+        Self {
+            method: d.method.to_string(),
+            replacement: d.replacement.to_string(),
+            deprecated_in: d.deprecated_in,
+            removable_in: d.removable_in(),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lock the core's database, for setting up and inspecting state.
+    fn locked_db(core: &FuzzyDrugsCore) -> MutexGuard<'_, Database> {
+        core.db.lock().unwrap()
+    }
+
+    /// Mark `draft` reviewed, insert it, and commit it as a resumed commit.
+    fn commit_reviewed(core: &FuzzyDrugsCore, mut draft: EncounterDraft) -> FfiLeafCommit {
+        draft.status = DraftStatus::Reviewed;
+        locked_db(core).insert_draft(&draft).unwrap();
+        core.resume_pending_commit(draft.draft_id, "Dr. Smith".into())
+            .unwrap()
+    }
+
+    /// Commit an encounter for `patient_id` with one manual bag of LRS.
+    fn commit_manual_encounter(core: &FuzzyDrugsCore, patient_id: &str) -> FfiLeafCommit {
+        let mut draft = EncounterDraft::new(patient_id.to_string());
+        draft.add_manual_item("LRS-1L".into(), "LRS 1L".into(), 1.0, "bag".into(), None);
+        commit_reviewed(core, draft)
+    }
+
+    #[test]
+    fn test_poisoned_lock_recovered() {
+        let core = open_database_in_memory().unwrap();
+        let poisoner = Arc::clone(&core);
+        let _ = std::thread::spawn(move || {
+            let _db = poisoner.db.lock().unwrap();
+            panic!("simulated panic while holding the database lock");
+        })
+        .join();
+        assert!(core.db.is_poisoned());
+
+        // Later calls recover instead of failing
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        assert!(core.get_patient(patient.local_id).unwrap().is_some());
+        assert!(!core.db.is_poisoned());
+
+        let health = core.get_health_status(100);
+        assert_eq!(health.state, "recovered");
+        assert_eq!(health.poison_recoveries, 1);
+        assert!(health.integrity_ok);
+    }
+
+    #[test]
+    fn test_stuck_lock_reported() {
+        let core = open_database_in_memory().unwrap();
+        let held = locked_db(&core);
+        let health = core.get_health_status(20);
+        drop(held);
+
+        assert_eq!(health.state, "degraded");
+        assert!(!health.lock_available);
+        assert_eq!(core.get_health_status(100).state, "healthy");
+    }
+
+    #[test]
+    fn test_resume_pending_commit() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mut draft = EncounterDraft::new(patient.local_id);
+        draft.add_manual_item("LRS-1L".into(), "LRS 1L".into(), 1.0, "bag".into(), None);
+        draft.status = DraftStatus::Reviewed;
+        draft.updated_at = "2024-01-01T00:00:00Z".into();
+        locked_db(&core).insert_draft(&draft).unwrap();
+
+        let summary = core.get_dashboard_summary(30).unwrap();
+        assert_eq!(summary.pending_commits.len(), 1);
+        assert_eq!(summary.pending_commits[0].draft_id, draft.draft_id);
+        assert!(summary.pending_commits[0].leaf_hash.is_none());
+
+        let commit = core
+            .resume_pending_commit(draft.draft_id.clone(), "Dr. Smith".into())
+            .unwrap();
+        assert_eq!(commit.leaf_count, 1);
+        assert!(core
+            .get_dashboard_summary(30)
+            .unwrap()
+            .pending_commits
+            .is_empty());
+        assert!(core.get_dashboard_summary(30).unwrap().has_unsynced_changes);
+        assert!(matches!(
+            core.resume_pending_commit(draft.draft_id, "Dr. Smith".into()),
+            Err(FuzzyDrugsError::Conflict { reason, .. }) if reason == "not_awaiting_commit"
+        ));
+    }
+
+    #[test]
+    fn test_core_version_and_shims() {
+        let version = get_core_version();
+        assert_eq!(version.api_version, compat::API_VERSION);
+        assert!(is_api_version_supported(version.min_supported_api_version));
+        assert!(!is_api_version_supported(version.api_version + 1));
+        assert!(!version.compatibility_report.contains("ERROR"));
+        assert!(version
+            .deprecated_methods
+            .iter()
+            .any(|d| d.method == "get_pending_review_drafts"));
+
+        // Bindings from before the rename are no longer served, so the old
+        // name is gone
+        assert!(!is_api_version_supported(1));
+        assert!(version
+            .compatibility_report
+            .contains("removed: get_pending_review_drafts"));
+    }
+
+    struct TestExtractor;
+
+    impl FfiMentionExtractor for TestExtractor {
+        fn extract(&self, transcript: String) -> Result<Vec<FfiDrugMention>, FuzzyDrugsError> {
+            let start = transcript
+                .find("rimadyl")
+                .ok_or_else(|| FuzzyDrugsError::ExtractionError("model offline".into()))?;
+            Ok(vec![FfiDrugMention {
+                raw_text: "rimadyl 100mg PO".into(),
+                drug_name: "rimadyl".into(),
+                dose: Some(100.0),
+                unit: Some("mg".into()),
+                route: Some("PO".into()),
+                species: None,
+                start_offset: start as u32,
+                end_offset: (start + 16) as u32,
+            }])
+        }
+    }
+
+    #[test]
+    fn test_process_transcript() {
+        let core = open_database_in_memory().unwrap();
+        let mut item = CatalogItem::new("CARP-100".into(), "Carprofen 100mg tablets".into());
+        item.aliases = vec!["rimadyl".into()];
+        item.species = vec!["canine".into()];
+        item.routes = vec!["PO".into()];
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft = core.create_draft(patient.local_id).unwrap();
+        let transcript = "Sent home with rimadyl 100mg PO BID.".to_string();
+
+        // No extractor configured yet
+        assert!(matches!(
+            core.process_transcript(draft.draft_id.clone(), transcript.clone()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        core.set_mention_extractor(Arc::new(TestExtractor)).unwrap();
+        let processed = core
+            .process_transcript(draft.draft_id.clone(), transcript.clone())
+            .unwrap();
+        assert!(processed.unmatched_drugs.is_empty());
+        assert_eq!(processed.draft.status, "PendingReview");
+        assert_eq!(processed.draft.transcript, transcript);
+        assert_eq!(processed.draft.pending_review_count, 1);
+        let stored = locked_db(&core)
+            .get_draft(&draft.draft_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.resolved_items[0].top_candidate.sku, "CARP-100");
+        assert_eq!(
+            stored.resolved_items[0].disposition,
+            Some(models::DispositionType::Dispensed)
+        );
+
+        assert!(matches!(
+            core.process_transcript(draft.draft_id, "No drugs today.".into()),
+            Err(FuzzyDrugsError::ExtractionError(msg)) if msg == "model offline"
+        ));
+        assert!(matches!(
+            core.process_transcript("missing".into(), transcript),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_users_stamp_committed_encounters() {
+        let core = open_database_in_memory().unwrap();
+        let smith = FfiUser {
+            user_id: "jsmith".into(),
+            name: "Dr. Smith".into(),
+            license_number: Some("VET-12345".into()),
+            license_state: Some("CA".into()),
+            dea_number: Some("AB1234563".into()),
+            roles: vec![],
+        };
+        let bad_dea = FfiUser {
+            dea_number: Some("AB123".into()),
+            ..smith.clone()
+        };
+        assert!(matches!(
+            core.upsert_user(bad_dea),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        core.upsert_user(smith).unwrap();
+        assert_eq!(core.list_users().unwrap().len(), 1);
+
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let commit = core
+            .commit_encounter(FfiReviewedEncounter {
+                draft_id: "draft-1".into(),
+                patient_id: patient.local_id,
+                patient_server_id: None,
+                transcript: String::new(),
+                line_items: vec![],
+                reviewed_by: "jsmith".into(),
+                notes: None,
+            })
+            .unwrap();
+        let payload = MerkleTree::new(&locked_db(&core))
+            .get_leaf_payload(&commit.leaf_hash)
+            .unwrap()
+            .unwrap();
+        let encounter: ReviewedEncounter = serde_json::from_str(&payload).unwrap();
+        assert_eq!(
+            encounter.prescriber.unwrap().license_state.as_deref(),
+            Some("CA")
+        );
+
+        core.delete_user("jsmith".into()).unwrap();
+        assert!(matches!(
+            core.delete_user("jsmith".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_process_transcript_rule_based() {
+        let core = open_database_in_memory().unwrap();
+        let mut item = CatalogItem::new("CARP-100".into(), "Carprofen 100mg tablets".into());
+        item.species = vec!["canine".into()];
+        item.routes = vec!["PO".into()];
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft = core.create_draft(patient.local_id).unwrap();
+
+        core.use_rule_based_extractor().unwrap();
+        let processed = core
+            .process_transcript(
+                draft.draft_id,
+                "Sent home with rimadyl 100mg PO BID.".into(),
+            )
+            .unwrap();
+        assert!(processed.unmatched_drugs.is_empty());
+        let stored = locked_db(&core)
+            .get_draft(&processed.draft.draft_id)
+            .unwrap();
+        let resolved = &stored.unwrap().resolved_items[0];
+        assert_eq!(resolved.top_candidate.sku, "CARP-100");
+        assert_eq!(resolved.mention.normalized_name, "carprofen");
+        assert_eq!(resolved.mention.normalized_dose, Some(100.0));
+        assert_eq!(resolved.mention.normalized_route.as_deref(), Some("PO"));
+    }
+
+    #[test]
+    fn test_commit_draws_down_stock() {
+        let core = open_database_in_memory().unwrap();
+        let item = CatalogItem::new("CARP-100".into(), "Carprofen 100mg tablets".into());
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        assert!(core.get_stock_level("CARP-100".into()).unwrap().is_none());
+        assert!(matches!(
+            core.adjust_stock("CARP-100".into(), -5.0, "encounter".into(), String::new()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            core.adjust_stock("MISSING".into(), 5.0, "received".into(), String::new()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+        let level = core
+            .adjust_stock("CARP-100".into(), 30.0, "received".into(), "PO 42".into())
+            .unwrap();
+        assert_eq!(level.quantity_on_hand, 30.0);
+        core.set_reorder_point("CARP-100".into(), Some(28.0))
+            .unwrap();
+
+        // 200 mg given is two tablets; the prescription isn't drawn from stock
+        let line = |quantity: f64, disposition: &str| FfiLineItem {
+            sku: "CARP-100".into(),
+            name: "Carprofen 100mg tablets".into(),
+            quantity,
+            unit: "mg".into(),
+            route: Some("PO".into()),
+            original_mention: String::new(),
+            controlled_schedule: None,
+            schedule: vec![],
+            disposition: Some(disposition.into()),
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        };
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let commit = core
+            .commit_encounter(FfiReviewedEncounter {
+                draft_id: "draft-1".into(),
+                patient_id: patient.local_id,
+                patient_server_id: None,
+                transcript: String::new(),
+                line_items: vec![line(200.0, "administered"), line(1400.0, "prescribed")],
+                reviewed_by: "Dr. Smith".into(),
+                notes: None,
+            })
+            .unwrap();
+
+        let level = core.get_stock_level("CARP-100".into()).unwrap().unwrap();
+        assert_eq!(level.quantity_on_hand, 28.0);
+        assert!(level.is_low);
+        assert_eq!(core.list_low_stock_items().unwrap().len(), 1);
+        let adjustments = core.list_stock_adjustments("CARP-100".into(), 10).unwrap();
+        assert_eq!(adjustments[0].reason, "encounter");
+        assert_eq!(adjustments[0].reference, Some(commit.leaf_hash));
+
+        let level = core
+            .record_stock_count("CARP-100".into(), 40.0, "Monthly count".into())
+            .unwrap();
+        assert!(!level.is_low);
+        assert_eq!(
+            core.list_stock_adjustments("CARP-100".into(), 1).unwrap()[0].delta,
+            12.0
+        );
+    }
+    #[test]
+    fn test_lot_tracking() {
+        let core = open_database_in_memory().unwrap();
+        let mut item = CatalogItem::new("HYDRO-2".into(), "Hydromorphone 2mg/mL".into());
+        item.controlled_schedule = Some(ControlledSchedule::CII);
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        core.upsert_service_item(FfiServiceItem {
+            code: "VAC-RABIES".into(),
+            name: "Rabies Vaccine".into(),
+            aliases: vec![],
+            kind: "vaccine".into(),
+            unit_price: None,
+            tax_code: None,
+            active: true,
+            antigen: None,
+            booster_interval_days: None,
+        })
+        .unwrap();
+        let lot = |number: &str, expires: &str| FfiInventoryLot {
+            sku: "HYDRO-2".into(),
+            lot_number: number.into(),
+            expiration_date: expires.into(),
+        };
+        assert!(matches!(
+            core.upsert_inventory_lot(lot("H77", "12/31/2999")),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        core.upsert_inventory_lot(lot("H77", "2999-12-31")).unwrap();
+        core.upsert_inventory_lot(lot("H01", "2001-01-31")).unwrap();
+        assert_eq!(
+            core.list_inventory_lots("HYDRO-2".into(), false)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            core.list_inventory_lots("HYDRO-2".into(), true)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft = core.create_draft(patient.local_id.clone()).unwrap();
+        let draft_id = draft.draft_id;
+        core.use_rule_based_extractor().unwrap();
+        let processed = core
+            .process_transcript(
+                draft_id.clone(),
+                "Gave hydromorphone 0.1 mg IV and a rabies vaccine.".into(),
+            )
+            .unwrap();
+        // The vaccine is flagged at once; the drug once it is accepted
+        assert!(processed.draft.service_items[0].requires_lot);
+        assert_eq!(processed.draft.missing_lot_count, 1);
+        core.approve_item(draft_id.clone(), 0).unwrap();
+        let updated = core
+            .confirm_controlled_item(draft_id.clone(), 0, "Dr. Smith".into())
+            .unwrap();
+        assert_eq!(updated.missing_lot_count, 2);
+
+        assert!(matches!(
+            core.set_item_lot(draft_id.clone(), 0, " ".into(), None),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            core.set_item_lot(draft_id.clone(), 0, "H01".into(), None),
+            Err(FuzzyDrugsError::InvalidInput(msg)) if msg.contains("expired")
+        ));
+        let updated = core
+            .set_item_lot(draft_id.clone(), 0, " H77 ".into(), None)
+            .unwrap();
+        assert_eq!(updated.missing_lot_count, 1);
+        let updated = core
+            .set_service_lot(draft_id.clone(), 0, "R9".into(), Some("2999-06-30".into()))
+            .unwrap();
+        assert_eq!(updated.missing_lot_count, 0);
+
+        // Lots recorded on the draft carry onto the committed line items
+        let stored = locked_db(&core).get_draft(&draft_id).unwrap().unwrap();
+        let encounter = ReviewedEncounter::from_draft(&stored, "Dr. Smith".into()).unwrap();
+        let line_items = encounter
+            .line_items
+            .into_iter()
+            .map(|item| FfiLineItem {
+                lot_number: None,
+                expiration_date: None,
+                ..item.into()
+            })
+            .collect();
+        let commit = core
+            .commit_encounter(FfiReviewedEncounter {
+                draft_id,
+                patient_id: patient.local_id,
+                patient_server_id: None,
+                transcript: String::new(),
+                line_items,
+                reviewed_by: "Dr. Smith".into(),
+                notes: None,
+            })
+            .unwrap();
+        let payload = MerkleTree::new(&locked_db(&core))
+            .get_leaf_payload(&commit.leaf_hash)
+            .unwrap()
+            .unwrap();
+        let encounter: ReviewedEncounter = serde_json::from_str(&payload).unwrap();
+        let lots: Vec<_> = encounter
+            .line_items
+            .iter()
+            .map(|i| (i.lot_number.as_deref(), i.expiration_date.as_deref()))
+            .collect();
+        assert_eq!(
+            lots,
+            [
+                (Some("H77"), Some("2999-12-31")),
+                (Some("R9"), Some("2999-06-30"))
+            ]
+        );
+
+        core.delete_inventory_lot("HYDRO-2".into(), "H01".into())
+            .unwrap();
+        assert!(matches!(
+            core.delete_inventory_lot("HYDRO-2".into(), "H01".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_patient_allergy_blocks_commit_until_overridden() {
+        let core = open_database_in_memory().unwrap();
+        let amoxicillin = CatalogItem::new("AMOX-250".into(), "Amoxicillin 250mg".into());
+        locked_db(&core).upsert_catalog_item(&amoxicillin).unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let allergy = |substance: &str| FfiPatientAllergy {
+            substance: substance.into(),
+            reaction: Some("hives".into()),
+        };
+        assert!(matches!(
+            core.set_patient_allergies(patient.local_id.clone(), vec![allergy(" ")]),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        let updated = core
+            .set_patient_allergies(patient.local_id.clone(), vec![allergy(" Amoxicillin ")])
+            .unwrap();
+        assert_eq!(updated.allergies[0].substance, "Amoxicillin");
+
+        let draft_id = core
+            .create_draft(patient.local_id.clone())
+            .unwrap()
+            .draft_id;
+        core.use_rule_based_extractor().unwrap();
+        let processed = core
+            .process_transcript(draft_id.clone(), "Dispensed amoxicillin 250 mg PO.".into())
+            .unwrap();
+        assert!(processed.draft.has_safety_warnings);
+        assert!(matches!(
+            core.override_allergy_warning(draft_id.clone(), 5, "Dr. Smith".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+        let approved = core.approve_item(draft_id.clone(), 0).unwrap();
+        assert_eq!(approved.pending_review_count, 1);
+
+        // The encounter can't be committed while the allergy stands
+        let mut stored = locked_db(&core).get_draft(&draft_id).unwrap().unwrap();
+        assert!(stored.resolved_items[0].requires_allergy_override());
+        stored.resolved_items[0].override_allergy("Dr. Smith".into());
+        let encounter = ReviewedEncounter::from_draft(&stored, "Dr. Smith".into()).unwrap();
+        let reviewed = FfiReviewedEncounter {
+            draft_id: draft_id.clone(),
+            patient_id: patient.local_id,
+            patient_server_id: None,
+            transcript: String::new(),
+            line_items: encounter.line_items.into_iter().map(|i| i.into()).collect(),
+            reviewed_by: "Dr. Smith".into(),
+            notes: None,
+        };
+        assert!(matches!(
+            core.commit_encounter(reviewed.clone()),
+            Err(FuzzyDrugsError::InvalidInput(msg)) if msg.contains("allergy")
+        ));
+
+        let overridden = core
+            .override_allergy_warning(draft_id, 0, "Dr. Smith".into())
+            .unwrap();
+        assert_eq!(overridden.pending_review_count, 0);
+        core.commit_encounter(reviewed).unwrap();
+    }
+
+    #[test]
+    fn test_allergies_checked_against_committed_sku() {
+        let core = open_database_in_memory().unwrap();
+        let mut amoxicillin = CatalogItem::new("AMOX-250".into(), "Amoxicillin 250mg".into());
+        amoxicillin.aliases = vec!["amoxicillin".into()];
+        let cephalexin = CatalogItem::new("CEPH-500".into(), "Cephalexin 500mg".into());
+        {
+            let db = locked_db(&core);
+            db.upsert_catalog_item(&amoxicillin).unwrap();
+            db.upsert_catalog_item(&cephalexin).unwrap();
+        }
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft_id = core
+            .create_draft(patient.local_id.clone())
+            .unwrap()
+            .draft_id;
+        core.use_rule_based_extractor().unwrap();
+        core.process_transcript(draft_id.clone(), "Dispensed amoxicillin 250 mg PO.".into())
+            .unwrap();
+        core.approve_item(draft_id.clone(), 0).unwrap();
+        let line = FfiLineItem {
+            sku: "CEPH-500".into(),
+            name: "Cephalexin 500mg".into(),
+            quantity: 1.0,
+            unit: "tablet".into(),
+            route: None,
+            original_mention: String::new(),
+            controlled_schedule: None,
+            schedule: vec![],
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        };
+        core.add_manual_item(draft_id.clone(), line).unwrap();
+        assert!(matches!(
+            core.override_manual_allergy_warning(draft_id.clone(), 0, "Dr. Smith".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        // Allergies recorded after staging still block the commit, for the
+        // transcript item and the manual item alike
+        let allergy = |substance: &str| FfiPatientAllergy {
+            substance: substance.into(),
+            reaction: None,
+        };
+        core.set_patient_allergies(
+            patient.local_id,
+            vec![allergy("amoxicillin"), allergy("cephalexin")],
+        )
+        .unwrap();
+        let reviewed = || {
+            let stored = locked_db(&core).get_draft(&draft_id).unwrap().unwrap();
+            let encounter = ReviewedEncounter::from_draft(&stored, "Dr. Smith".into()).unwrap();
+            FfiReviewedEncounter {
+                draft_id: draft_id.clone(),
+                patient_id: stored.patient_id,
+                patient_server_id: None,
+                transcript: String::new(),
+                line_items: encounter.line_items.into_iter().map(|i| i.into()).collect(),
+                reviewed_by: "Dr. Smith".into(),
+                notes: None,
+            }
+        };
+        assert!(matches!(
+            core.commit_encounter(reviewed()),
+            Err(FuzzyDrugsError::InvalidInput(msg)) if msg.starts_with("2 item(s)")
+        ));
+
+        core.override_allergy_warning(draft_id.clone(), 0, "Dr. Smith".into())
+            .unwrap();
+        core.override_manual_allergy_warning(draft_id.clone(), 0, "Dr. Lee".into())
+            .unwrap();
+        let commit = core.commit_encounter(reviewed()).unwrap();
+        let payload = MerkleTree::new(&locked_db(&core))
+            .get_leaf_payload(&commit.leaf_hash)
+            .unwrap()
+            .unwrap();
+        let encounter: ReviewedEncounter = serde_json::from_str(&payload).unwrap();
+        let overrides: Vec<_> = encounter
+            .line_items
+            .iter()
+            .map(|i| i.allergy_overridden_by.as_deref())
+            .collect();
+        assert_eq!(overrides, vec![Some("Dr. Smith"), Some("Dr. Lee")]);
+    }
+
+    #[test]
+    fn test_withdrawal_dates_for_food_animals() {
+        let core = open_database_in_memory().unwrap();
+        let mut item = CatalogItem::new("OXY-200".into(), "Oxytetracycline 200mg/mL".into());
+        item.withdrawal_times = vec![models::WithdrawalTime {
+            species: "bovine".into(),
+            meat_days: Some(28),
+            milk_hours: Some(96),
+        }];
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        let stored = core.get_catalog_item("OXY-200".into()).unwrap().unwrap();
+        assert_eq!(stored.withdrawal_times[0].meat_days, Some(28));
+        let mut canine = stored.clone();
+        canine.withdrawal_times[0].species = "canine".into();
+        assert!(matches!(
+            core.upsert_catalog_item(canine),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        let patient = core.create_patient("Daisy".into(), "cow".into()).unwrap();
+        let draft_id = core
+            .create_draft(patient.local_id.clone())
+            .unwrap()
+            .draft_id;
+        // Treated on March 1st, processed and reviewed later
+        core.db
+            .lock()
+            .unwrap()
+            .conn()
+            .execute(
+                "UPDATE encounter_drafts SET created_at = '2024-03-01T09:00:00Z' \
+                 WHERE draft_id = ?",
+                [&draft_id],
+            )
+            .unwrap();
+        core.use_rule_based_extractor().unwrap();
+        core.process_transcript(draft_id.clone(), "Gave oxytetracycline 20 mL IM.".into())
+            .unwrap();
+        let groups = core.get_draft_items(draft_id.clone()).unwrap();
+        let withdrawal = groups[0].items[0].item.withdrawal.clone().unwrap();
+        assert_eq!(withdrawal.last_dose_date, "2024-03-01");
+        assert_eq!(withdrawal.meat_end_date.as_deref(), Some("2024-03-29"));
+        assert_eq!(withdrawal.milk_end_date.as_deref(), Some("2024-03-05"));
+
+        // Sent home without a course length, it counts from a default course
+        let dispensed = core
+            .set_item_disposition(draft_id.clone(), 0, "dispensed".into())
+            .unwrap();
+        let withdrawal = |draft: &FfiEncounterDraft| {
+            let groups = core.get_draft_items(draft.draft_id.clone()).unwrap();
+            groups[0].items[0].item.withdrawal.clone().unwrap()
+        };
+        assert_eq!(withdrawal(&dispensed).last_dose_date, "2024-03-14");
+        let given = core
+            .set_item_disposition(draft_id.clone(), 0, "administered".into())
+            .unwrap();
+        assert_eq!(withdrawal(&given).last_dose_date, "2024-03-01");
+
+        // The committed withdrawal counts from the review date
+        core.approve_item(draft_id.clone(), 0).unwrap();
+        let commit = {
+            let db = locked_db(&core);
+            let stored = db.get_draft(&draft_id).unwrap().unwrap();
+            let mut encounter = ReviewedEncounter::from_draft(&stored, "Dr. Smith".into()).unwrap();
+            encounter.reviewed_at = "2024-03-02T15:00:00Z".into();
+            FuzzyDrugsCore::commit_reviewed(&db, Some(&stored), encounter).unwrap()
+        };
+
+        let json = core.export_compliance_json().unwrap();
+        assert!(json.contains("\"withdrawal_end_date\": \"2024-03-30\""));
+        let note = core
+            .render_summary(commit.leaf_hash, "text".into())
+            .unwrap();
+        assert!(note.contains("Withdrawal: meat until 2024-03-30, milk until 2024-03-06"));
+    }
+
+    /// `TestExtractor` that counts its calls.
+    #[derive(Default)]
+    struct CountingExtractor(std::sync::atomic::AtomicUsize);
+
+    impl FfiMentionExtractor for CountingExtractor {
+        fn extract(&self, transcript: String) -> Result<Vec<FfiDrugMention>, FuzzyDrugsError> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            TestExtractor.extract(transcript)
+        }
+    }
+
+    #[test]
+    fn test_process_transcript_cached_by_extractor_version() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let transcript = "Sent home with rimadyl 100mg PO BID.".to_string();
+        let process = || {
+            let draft = core.create_draft(patient.local_id.clone()).unwrap();
+            core.process_transcript(draft.draft_id, transcript.clone())
+                .unwrap()
+        };
+        let extractor = Arc::new(CountingExtractor::default());
+        let calls = || extractor.0.load(std::sync::atomic::Ordering::SeqCst);
+
+        // No version: every transcript is extracted
+        core.set_mention_extractor(extractor.clone()).unwrap();
+        process();
+        process();
+        assert_eq!(calls(), 2);
+
+        core.set_extractor_version(Some("model-1".into())).unwrap();
+        let first = process();
+        let second = process();
+        assert_eq!(calls(), 3);
+        assert_eq!(first.unmatched_drugs, second.unmatched_drugs);
+
+        // A new model version misses, and drops the old entries
+        core.set_extractor_version(Some("model-2".into())).unwrap();
+        process();
+        assert_eq!(calls(), 4);
+        core.set_extractor_version(Some("model-1".into())).unwrap();
+        process();
+        assert_eq!(calls(), 5);
+
+        // Replacing the extractor clears the version
+        core.set_mention_extractor(extractor.clone()).unwrap();
+        process();
+        assert_eq!(calls(), 6);
+
+        assert_eq!(core.clear_extraction_cache().unwrap(), 1);
+        assert!(matches!(
+            core.set_extractor_version(Some(" ".into())),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+    }
+
+    /// Finds rimadyl (via `TestExtractor`) plus nail trims and ear flushes.
+    struct TestEntityExtractor;
+
+    impl FfiEntityExtractor for TestEntityExtractor {
+        fn extract_entities(
+            &self,
+            transcript: String,
+        ) -> Result<FfiExtractedMentions, FuzzyDrugsError> {
+            let services = ["nail trim", "ear flush"]
+                .into_iter()
+                .filter_map(|name| {
+                    let start = transcript.find(name)?;
+                    Some(FfiServiceMention {
+                        raw_text: name.into(),
+                        service_name: name.into(),
+                        kind: Some("procedure".into()),
+                        quantity: None,
+                        start_offset: start as u32,
+                        end_offset: (start + name.len()) as u32,
+                    })
+                })
+                .collect();
+            Ok(FfiExtractedMentions {
+                drugs: TestExtractor.extract(transcript)?,
+                services,
+            })
+        }
+    }
+
+    #[test]
+    fn test_process_transcript_with_services() {
+        let core = open_database_in_memory().unwrap();
+        let mut item = CatalogItem::new("CARP-100".into(), "Carprofen 100mg tablets".into());
+        item.aliases = vec!["rimadyl".into()];
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        assert!(matches!(
+            core.upsert_service_item(FfiServiceItem {
+                code: "PROC-NAIL".into(),
+                name: "Nail Trim".into(),
+                aliases: vec![],
+                kind: "grooming".into(),
+                unit_price: Some(18.0),
+                tax_code: None,
+                active: true,
+                antigen: None,
+                booster_interval_days: None,
+            }),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        core.upsert_service_item(FfiServiceItem {
+            code: "PROC-NAIL".into(),
+            name: "Nail Trim".into(),
+            aliases: vec![],
+            kind: "procedure".into(),
+            unit_price: Some(18.0),
+            tax_code: None,
+            active: true,
+            antigen: None,
+            booster_interval_days: None,
+        })
+        .unwrap();
+        assert_eq!(core.list_service_items(true).unwrap().len(), 1);
+
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft = core.create_draft(patient.local_id).unwrap();
+        core.set_entity_extractor(Arc::new(TestEntityExtractor))
+            .unwrap();
+        let processed = core
+            .process_transcript(
+                draft.draft_id.clone(),
+                "Did a nail trim and an ear flush, sent home rimadyl 100mg PO.".into(),
+            )
+            .unwrap();
+
+        assert_eq!(processed.draft.pending_review_count, 1);
+        assert_eq!(processed.unmatched_services, vec!["ear flush".to_string()]);
+        assert_eq!(processed.draft.service_items.len(), 1);
+        assert_eq!(processed.draft.service_items[0].code, "PROC-NAIL");
+        assert_eq!(
+            processed.draft.service_items[0].original_mention,
+            "nail trim"
+        );
+
+        // Services commit as line items alongside the drugs
+        let stored = locked_db(&core)
+            .get_draft(&draft.draft_id)
+            .unwrap()
+            .unwrap();
+        let mut reviewed = stored.clone();
+        reviewed.resolved_items[0].review(ResolutionStatus::Approved);
+        let encounter = ReviewedEncounter::from_draft(&reviewed, "Dr. Smith".into()).unwrap();
+        let skus: Vec<&str> = encounter
+            .line_items
+            .iter()
+            .map(|i| i.sku.as_str())
+            .collect();
+        assert_eq!(skus, ["CARP-100", "PROC-NAIL"]);
+
+        let updated = core.remove_service_item(draft.draft_id.clone(), 0).unwrap();
+        assert!(updated.service_items.is_empty());
+        assert!(matches!(
+            core.remove_service_item(draft.draft_id, 0),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+        core.delete_service_item("PROC-NAIL".into()).unwrap();
+        assert!(matches!(
+            core.delete_service_item("PROC-NAIL".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_process_transcript_with_speakers() {
+        let core = open_database_in_memory().unwrap();
+        let mut item = CatalogItem::new("CARP-100".into(), "Carprofen 100mg tablets".into());
+        item.aliases = vec!["rimadyl".into()];
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft = core.create_draft(patient.local_id).unwrap();
+        core.set_mention_extractor(Arc::new(TestExtractor)).unwrap();
+        let transcript = "He's been getting rimadyl 100mg PO at home.".to_string();
+        let turn = |role: Option<&str>, end: usize| FfiSpeakerTurn {
+            speaker: "SPEAKER_01".into(),
+            role: role.map(str::to_string),
+            start_offset: 0,
+            end_offset: end as u32,
+        };
+
+        assert!(matches!(
+            core.process_transcript_with_speakers(
+                draft.draft_id.clone(),
+                transcript.clone(),
+                vec![turn(Some("cat"), transcript.len())]
+            ),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        let processed = core
+            .process_transcript_with_speakers(
+                draft.draft_id.clone(),
+                transcript.clone(),
+                vec![turn(Some("owner"), transcript.len())],
+            )
+            .unwrap();
+        assert_eq!(processed.draft.pending_review_count, 0);
+        assert_eq!(processed.draft.reported_medications.len(), 1);
+        assert_eq!(processed.draft.reported_medications[0].drug_name, "rimadyl");
+
+        // Turns with no role leave mentions as orders
+        let processed = core
+            .process_transcript_with_speakers(
+                draft.draft_id,
+                transcript.clone(),
+                vec![turn(None, transcript.len())],
+            )
+            .unwrap();
+        assert_eq!(processed.draft.pending_review_count, 1);
+        assert!(processed.draft.reported_medications.is_empty());
+    }
+
+    #[test]
+    fn test_item_review() {
+        let core = open_database_in_memory().unwrap();
+        for (sku, name) in [
+            ("CARP-100", "Carprofen 100mg tablets"),
+            ("CARP-75", "Carprofen 75mg tablets"),
+        ] {
+            let mut item = CatalogItem::new(sku.into(), name.into());
+            item.aliases = vec!["rimadyl".into()];
+            item.species = vec!["canine".into()];
+            locked_db(&core).upsert_catalog_item(&item).unwrap();
+        }
+        let mut lrs = CatalogItem::new("LRS-1L".into(), "Lactated Ringer's 1L".into());
+        lrs.species = vec!["canine".into()];
+        locked_db(&core).upsert_catalog_item(&lrs).unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft = core.create_draft(patient.local_id).unwrap();
+        core.set_mention_extractor(Arc::new(TestExtractor)).unwrap();
+        let id = core
+            .process_transcript(draft.draft_id, "Give rimadyl 100mg PO.".into())
+            .unwrap()
+            .draft
+            .draft_id;
+        let stored_item = |core: &FuzzyDrugsCore| {
+            let mut draft = locked_db(core).get_draft(&id).unwrap().unwrap();
+            draft.resolved_items.remove(0)
+        };
+        let final_sku = |core: &FuzzyDrugsCore| stored_item(core).final_sku().map(str::to_string);
+
+        assert_eq!(
+            core.approve_item(id.clone(), 0)
+                .unwrap()
+                .pending_review_count,
+            0
+        );
+        assert_eq!(final_sku(&core).as_deref(), Some("CARP-100"));
+
+        let alternative = stored_item(&core).alternatives[0].sku.clone();
+        core.select_alternative(id.clone(), 0, alternative.clone())
+            .unwrap();
+        assert_eq!(final_sku(&core), Some(alternative));
+        assert!(matches!(
+            core.select_alternative(id.clone(), 0, "LRS-1L".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        core.manual_override(id.clone(), 0, "LRS-1L".into())
+            .unwrap();
+        assert_eq!(final_sku(&core).as_deref(), Some("LRS-1L"));
+        assert!(matches!(
+            core.manual_override(id.clone(), 0, "NOPE".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+
+        let rejected = core.reject_item(id.clone(), 0).unwrap();
+        assert_eq!(rejected.pending_review_count, 0);
+        assert_eq!(final_sku(&core), None);
+        assert!(matches!(
+            core.approve_item(id, 5),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_update_draft_transcript() {
+        let core = open_database_in_memory().unwrap();
+        let mut item = CatalogItem::new("CARP-100".into(), "Carprofen 100mg tablets".into());
+        item.aliases = vec!["rimadyl".into()];
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let id = core.create_draft(patient.local_id).unwrap().draft_id;
+        core.set_mention_extractor(Arc::new(TestExtractor)).unwrap();
+        core.process_transcript(id.clone(), "Gave rimadyl 100mg PO.".into())
+            .unwrap();
+        core.approve_item(id.clone(), 0).unwrap();
+
+        // Text-only edit leaves the items alone
+        let edited = core
+            .update_draft_transcript(id.clone(), "Gave rimadyl 100mg PO today.".into(), false)
+            .unwrap();
+        assert_eq!(edited.draft.transcript, "Gave rimadyl 100mg PO today.");
+        assert_eq!(edited.draft.pending_review_count, 0);
+
+        // Re-resolving keeps the approval of the unchanged mention
+        let corrected = core
+            .update_draft_transcript(id.clone(), "We gave rimadyl 100mg PO.".into(), true)
+            .unwrap();
+        assert_eq!(corrected.draft.status, "PendingReview");
+        assert_eq!(corrected.draft.pending_review_count, 0);
+        let draft = locked_db(&core).get_draft(&id).unwrap().unwrap();
+        assert_eq!(draft.resolved_items[0].status, ResolutionStatus::Approved);
+        assert_eq!(draft.resolved_items[0].mention.original.start_offset, 8);
+    }
+
+    #[test]
+    fn test_discard_draft() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let id = core
+            .create_draft(patient.local_id.clone())
+            .unwrap()
+            .draft_id;
+
+        let commit = core
+            .discard_draft(id.clone(), "Dr. Smith".into(), "Wrong patient".into())
+            .unwrap();
+        assert_eq!(commit.leaf_count, 1);
+        assert!(locked_db(&core).get_draft(&id).unwrap().is_none());
+        assert!(matches!(
+            core.discard_draft(id, "Dr. Smith".into(), "Again".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+
+        // Reviewed drafts are awaiting commit
+        let mut reviewed = EncounterDraft::new(patient.local_id.clone());
+        reviewed.status = DraftStatus::Reviewed;
+        locked_db(&core).insert_draft(&reviewed).unwrap();
+        assert!(matches!(
+            core.discard_draft(reviewed.draft_id, "Dr. Smith".into(), "Oops".into()),
+            Err(FuzzyDrugsError::Conflict { reason, .. }) if reason == "already_reviewed"
+        ));
+
+        // Held drafts are kept, and no audit leaf is written
+        let held = core
+            .create_draft(patient.local_id.clone())
+            .unwrap()
+            .draft_id;
+        core.place_legal_hold(
+            "encounter".into(),
+            held.clone(),
+            "Claim #1234".into(),
+            "Dr. Smith".into(),
+        )
+        .unwrap();
+        assert!(matches!(
+            core.discard_draft(held.clone(), "Dr. Smith".into(), "Oops".into()),
+            Err(FuzzyDrugsError::Conflict { reason, .. }) if reason == "legal_hold"
+        ));
+        assert!(locked_db(&core).get_draft(&held).unwrap().is_some());
+        assert_eq!(core.get_tree_stats().unwrap().leaf_count, 2);
+
+        // A rejected audit leaf leaves the draft in place
+        let kept = core.create_draft(patient.local_id).unwrap().draft_id;
+        locked_db(&core).set_limits(crate::limits::Limits {
+            max_leaf_payload_bytes: 256,
+            ..Default::default()
+        });
+        assert!(matches!(
+            core.discard_draft(kept.clone(), "Dr. Smith".into(), "x".repeat(512)),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        assert!(locked_db(&core).get_draft(&kept).unwrap().is_some());
+        assert_eq!(core.get_tree_stats().unwrap().leaf_count, 2);
+    }
+
+    #[test]
+    fn test_patient_history() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let other = core
+            .create_patient("Bella".into(), "feline".into())
+            .unwrap();
+        let mut reviewed = EncounterDraft::new(patient.local_id.clone());
+        reviewed.add_manual_item("LRS-1L".into(), "LRS 1L".into(), 1.0, "bag".into(), None);
+        let commit = commit_reviewed(&core, reviewed.clone());
+        let open = core.create_draft(patient.local_id.clone()).unwrap();
+        core.create_draft(other.local_id).unwrap();
+
+        let drafts = core
+            .list_drafts_for_patient(patient.local_id.clone())
+            .unwrap();
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].draft_id, open.draft_id);
+        assert_eq!(drafts[0].status, "Recording");
+
+        let encounters = core
+            .list_committed_encounters_for_patient(patient.local_id)
+            .unwrap();
+        assert_eq!(encounters.len(), 1);
+        assert_eq!(encounters[0].draft_id, reviewed.draft_id);
+        assert_eq!(encounters[0].leaf_hash, commit.leaf_hash);
+        assert_eq!(encounters[0].reviewed_by, "Dr. Smith");
+        assert_eq!(encounters[0].line_item_count, 1);
+
+        assert!(matches!(
+            core.list_drafts_for_patient("missing".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_catalog_management() {
+        let core = open_database_in_memory().unwrap();
+        for (sku, name) in [("LRS-1L", "LRS 1L"), ("CARP-100", "Carprofen 100mg")] {
+            let item = CatalogItem::new(sku.into(), name.into());
+            locked_db(&core).upsert_catalog_item(&item).unwrap();
+        }
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        commit_manual_encounter(&core, &patient.local_id);
+
+        let page = core.list_catalog_items(false, 0, 1).unwrap();
+        assert_eq!(page.total_count, 2);
+        assert_eq!(page.items[0].sku, "CARP-100");
+        assert_eq!(
+            core.list_catalog_items(false, 1, 1).unwrap().items[0].sku,
+            "LRS-1L"
+        );
+        assert!(matches!(
+            core.list_catalog_items(false, 0, 0),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        // Billed on a committed encounter: deactivate, don't delete
+        assert!(matches!(
+            core.delete_catalog_item("LRS-1L".into()),
+            Err(FuzzyDrugsError::Conflict { subject_id, reason, .. })
+                if subject_id == "LRS-1L" && reason == "in_use"
+        ));
+        core.deactivate_catalog_item("LRS-1L".into()).unwrap();
+        let active = core.list_catalog_items(true, 0, 10).unwrap();
+        assert_eq!(active.total_count, 1);
+
+        core.delete_catalog_item("CARP-100".into()).unwrap();
+        assert!(matches!(
+            core.delete_catalog_item("CARP-100".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+        assert!(matches!(
+            core.deactivate_catalog_item("CARP-100".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_inclusion_proof() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mut leaf_hashes = Vec::new();
+        for _ in 0..3 {
+            let commit = commit_manual_encounter(&core, &patient.local_id);
+            leaf_hashes.push(commit.leaf_hash);
+        }
+
+        let proof = core.get_inclusion_proof(leaf_hashes[2].clone()).unwrap();
+        assert_eq!(proof.leaf_index, 2);
+        assert_eq!(
+            Some(proof.root_hash.clone()),
+            core.get_tree_stats().unwrap().root_hash
+        );
+        assert_eq!(proof.audit_path.len(), 2);
+        assert!(verify_inclusion_proof(proof.clone()));
+
+        let mut tampered = proof.clone();
+        tampered.audit_path[0].hash = leaf_hashes[0].clone();
+        assert!(!verify_inclusion_proof(tampered));
+        let mut malformed = proof;
+        malformed.audit_path[0].position = "up".into();
+        assert!(!verify_inclusion_proof(malformed));
+
+        assert!(matches!(
+            core.get_inclusion_proof("missing".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_incremental_exports() {
+        let core = open_database_in_memory().unwrap();
+        assert!(matches!(
+            core.export_tree_since(None),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mut roots = Vec::new();
+        for _ in 0..2 {
+            let commit = commit_manual_encounter(&core, &patient.local_id);
+            roots.push(commit.root_hash);
+        }
+
+        let compliance: serde_json::Value = serde_json::from_str(
+            &core
+                .export_compliance_since_root(Some(roots[0].clone()))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(compliance["encounters"].as_array().unwrap().len(), 1);
+        assert_eq!(compliance["metadata"]["root_hash"], roots[1].as_str());
+
+        let tree: serde_json::Value =
+            serde_json::from_str(&core.export_tree_since(Some(roots[1].clone())).unwrap()).unwrap();
+        assert!(tree["nodes"].as_array().unwrap().is_empty());
+
+        let billing: serde_json::Value = serde_json::from_str(
+            &core
+                .export_billing_since("2000-01-01T00:00:00+02:00".into())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(billing["total_items"], 2);
+        let billing: serde_json::Value = serde_json::from_str(
+            &core
+                .export_billing_since("2999-01-01 00:00:00".into())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(billing["total_items"], 0);
+        assert!(matches!(
+            core.export_billing_since("yesterday".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_fhir_exports() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mut leaves = Vec::new();
+        for _ in 0..2 {
+            let commit = commit_manual_encounter(&core, &patient.local_id);
+            leaves.push(commit.leaf_hash);
+        }
+
+        let bundle: serde_json::Value =
+            serde_json::from_str(&core.export_fhir_encounter(leaves[0].clone()).unwrap()).unwrap();
+        assert_eq!(bundle["resourceType"], "Bundle");
+        let entries = bundle["entry"].as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0]["resource"]["subject"]["identifier"]["value"],
+            patient.local_id.as_str()
+        );
+
+        let all: serde_json::Value =
+            serde_json::from_str(&core.export_fhir_bundle(None).unwrap()).unwrap();
+        assert_eq!(all["entry"].as_array().unwrap().len(), 2);
+        let selected: serde_json::Value = serde_json::from_str(
+            &core
+                .export_fhir_bundle(Some(vec![leaves[1].clone()]))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(selected["entry"].as_array().unwrap().len(), 1);
+
+        assert!(matches!(
+            core.export_fhir_encounter("missing".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_billing_filtered() {
+        let core = open_database_in_memory().unwrap();
+        let today = chrono::Utc::now().format("%Y-%m-%dT00:00:00Z").to_string();
+        for name in ["Max", "Bella"] {
+            let patient = core.create_patient(name.into(), "canine".into()).unwrap();
+            commit_manual_encounter(&core, &patient.local_id);
+        }
+        let max = core.search_patients("Max".into(), 1).unwrap().remove(0);
+
+        let filter = FfiBillingFilter {
+            patient_id: Some(max.local_id.clone()),
+            start: Some(today),
+            ..Default::default()
+        };
+        let csv = core
+            .export_billing_filtered(filter.clone(), "csv".into())
+            .unwrap();
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.contains(&max.local_id));
+
+        let json = core
+            .export_billing_filtered(
+                FfiBillingFilter {
+                    reviewed_by: Some("Dr. Jones".into()),
+                    ..Default::default()
+                },
+                "json".into(),
+            )
+            .unwrap();
+        let batch: export::BatchBillingExport = serde_json::from_str(&json).unwrap();
+        assert!(batch.encounters.is_empty());
+
+        let bad_date = FfiBillingFilter {
+            end: Some("tomorrow".into()),
+            ..filter
+        };
+        assert!(matches!(
+            core.export_billing_filtered(bad_date, "csv".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_committed_drugs_join_active_medications() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mut draft = EncounterDraft::new(patient.local_id.clone());
+        draft.add_manual_item(
+            "CARP-100".into(),
+            "Carprofen 100mg".into(),
+            1.0,
+            "tab".into(),
+            None,
+        );
+        commit_reviewed(&core, draft.clone());
+
+        let manual = core
+            .add_patient_medication(
+                patient.local_id.clone(),
+                " Levothyroxine ".into(),
+                None,
+                "2025-06-01".into(),
+                None,
+                Some("From previous clinic".into()),
+            )
+            .unwrap();
+        assert_eq!(manual.name, "Levothyroxine");
+        assert!(matches!(
+            core.add_patient_medication(
+                "missing".into(),
+                "Levothyroxine".into(),
+                None,
+                "2025-06-01".into(),
+                None,
+                None,
+            ),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+
+        let active = core
+            .list_active_medications(patient.local_id.clone(), None)
+            .unwrap();
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].source, "encounter");
+        assert_eq!(active[0].draft_id.as_ref(), Some(&draft.draft_id));
+
+        let course_end = active[0].end_date.clone().unwrap();
+        let after = chrono::NaiveDate::parse_from_str(&course_end, "%Y-%m-%d").unwrap()
+            + chrono::Duration::days(1);
+        let later = core
+            .list_active_medications(patient.local_id.clone(), Some(after.to_string()))
+            .unwrap();
+        assert_eq!(later.len(), 1);
+        assert!(matches!(
+            core.list_active_medications(patient.local_id.clone(), Some("soon".into())),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        assert!(matches!(
+            core.set_medication_end_date(manual.id, Some("2025-01-01".into())),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        core.set_medication_end_date(manual.id, Some("2025-12-31".into()))
+            .unwrap();
+        assert_eq!(
+            core.list_active_medications(patient.local_id.clone(), None)
+                .unwrap()
+                .len(),
+            1
+        );
+        core.delete_patient_medication(manual.id).unwrap();
+        assert_eq!(
+            core.list_patient_medications(patient.local_id)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_vaccinations_recorded_on_commit_and_come_due() {
+        let core = open_database_in_memory().unwrap();
+        let rabies = FfiServiceItem {
+            code: "VAC-RABIES".into(),
+            name: "Rabies Vaccine".into(),
+            aliases: vec![],
+            kind: "vaccine".into(),
+            unit_price: None,
+            tax_code: None,
+            active: true,
+            antigen: Some("rabies".into()),
+            booster_interval_days: Some(365),
+        };
+        core.upsert_service_item(rabies.clone()).unwrap();
+        core.upsert_service_item(FfiServiceItem {
+            code: "PROC-NAIL".into(),
+            name: "Nail Trim".into(),
+            kind: "procedure".into(),
+            antigen: None,
+            booster_interval_days: None,
+            ..rabies
+        })
+        .unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+
+        let mut draft = EncounterDraft::new(patient.local_id.clone());
+        for (code, name) in [("VAC-RABIES", "Rabies Vaccine"), ("PROC-NAIL", "Nail Trim")] {
+            draft.add_manual_item(code.into(), name.into(), 1.0, "each".into(), None);
+        }
+        commit_reviewed(&core, draft.clone());
+
+        let history = core
+            .list_patient_vaccinations(patient.local_id.clone())
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].antigen, "rabies");
+        assert_eq!(history[0].draft_id.as_ref(), Some(&draft.draft_id));
+        // Committed drugs go on the medication list; vaccines don't
+        assert!(core
+            .list_patient_medications(patient.local_id.clone())
+            .unwrap()
+            .is_empty());
+
+        let next_due = history[0].next_due_date.clone().unwrap();
+        assert!(core.list_due_vaccinations(None, 30).unwrap().is_empty());
+        let due = core
+            .list_due_vaccinations(Some(next_due.clone()), 0)
+            .unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].days_until_due, 0);
+        assert!(!due[0].overdue);
+
+        // An outside dose recorded later resets the due date
+        assert!(matches!(
+            core.record_vaccination(
+                patient.local_id.clone(),
+                "PROC-NAIL".into(),
+                next_due.clone(),
+                None,
+                None,
+            ),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        let outside = core
+            .record_vaccination(
+                patient.local_id.clone(),
+                "VAC-RABIES".into(),
+                next_due.clone(),
+                Some("R99".into()),
+                Some("Other Clinic".into()),
+            )
+            .unwrap();
+        assert!(core
+            .list_due_vaccinations(Some(next_due), 0)
+            .unwrap()
+            .is_empty());
+        core.delete_vaccination(outside.id).unwrap();
+        assert!(matches!(
+            core.delete_vaccination(outside.id),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_visit_groups_drafts_and_rolls_up_billing() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let other = core
+            .create_patient("Bella".into(), "feline".into())
+            .unwrap();
+        assert!(matches!(
+            core.create_visit(patient.local_id.clone(), "March 1".into(), None, None),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        let visit = core
+            .create_visit(
+                patient.local_id.clone(),
+                "2026-03-01".into(),
+                Some("Limping".into()),
+                None,
+            )
+            .unwrap();
+        let visit = core
+            .update_visit(FfiVisit {
+                attending_vet: Some("Dr. Smith".into()),
+                ..visit
+            })
+            .unwrap();
+        assert_eq!(
+            core.list_patient_visits(patient.local_id.clone())
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let stray = EncounterDraft::new(other.local_id);
+        locked_db(&core).insert_draft(&stray).unwrap();
+        assert!(matches!(
+            core.set_draft_visit(stray.draft_id, Some(visit.visit_id.clone())),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        for sku in ["LRS-1L", "CARP-100"] {
+            let mut draft = EncounterDraft::new(patient.local_id.clone());
+            draft.add_manual_item(sku.into(), sku.into(), 1.0, "each".into(), None);
+            draft.status = DraftStatus::Reviewed;
+            locked_db(&core).insert_draft(&draft).unwrap();
+            core.set_draft_visit(draft.draft_id.clone(), Some(visit.visit_id.clone()))
+                .unwrap();
+            assert_eq!(
+                core.get_draft(draft.draft_id.clone())
+                    .unwrap()
+                    .unwrap()
+                    .visit_id,
+                Some(visit.visit_id.clone())
+            );
+            core.resume_pending_commit(draft.draft_id, "Dr. Smith".into())
+                .unwrap();
+        }
+
+        let billing = core.get_visit_billing(visit.visit_id.clone()).unwrap();
+        assert_eq!(billing.draft_ids.len(), 2);
+        assert_eq!(billing.leaf_hashes.len(), 2);
+        assert_eq!(billing.unpriced_items, 2);
+
+        core.delete_visit(visit.visit_id.clone()).unwrap();
+        assert!(core.get_visit(visit.visit_id.clone()).unwrap().is_none());
+        assert!(matches!(
+            core.get_visit_billing(visit.visit_id),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_clients_link_patients_and_filter_billing() {
+        let core = open_database_in_memory().unwrap();
+        let client = core.create_client(" Jane Doe ".into()).unwrap();
+        assert_eq!(client.name, "Jane Doe");
+        assert!(matches!(
+            core.create_client(" ".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        let updated = core
+            .update_client(FfiClient {
+                email: Some("jane@example.com".into()),
+                ..client.clone()
+            })
+            .unwrap();
+        assert_eq!(updated.email.as_deref(), Some("jane@example.com"));
+        assert_eq!(core.search_clients("jane".into(), 5).unwrap().len(), 1);
+
+        for name in ["Max", "Bella", "Rex"] {
+            let patient = core.create_patient(name.into(), "canine".into()).unwrap();
+            if name != "Rex" {
+                let linked = core
+                    .set_patient_client(patient.local_id.clone(), Some(client.client_id.clone()))
+                    .unwrap();
+                assert_eq!(linked.client_id.as_ref(), Some(&client.client_id));
+            }
+            commit_manual_encounter(&core, &patient.local_id);
+        }
+        let patients = core.list_client_patients(client.client_id.clone()).unwrap();
+        assert_eq!(patients.len(), 2);
+        let owner = locked_db(&core).get_patient(&patients[0].local_id).unwrap();
+        assert_eq!(owner.unwrap().owner_name.as_deref(), Some("Jane Doe"));
+        assert!(matches!(
+            core.set_patient_client(patients[0].local_id.clone(), Some("missing".into())),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+
+        let filter = FfiBillingFilter {
+            client_id: Some(client.client_id.clone()),
+            ..Default::default()
+        };
+        let json = core.export_billing_filtered(filter, "json".into()).unwrap();
+        let batch: export::BatchBillingExport = serde_json::from_str(&json).unwrap();
+        assert_eq!(batch.encounters.len(), 2);
+        assert_eq!(batch.clients.len(), 1);
+        assert_eq!(batch.clients[0].patient_ids.len(), 2);
+
+        core.delete_client(client.client_id.clone()).unwrap();
+        assert!(core
+            .list_client_patients(client.client_id)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_billing_csv_layout() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        commit_manual_encounter(&core, &patient.local_id);
+
+        let mut layout = core.get_billing_csv_layout().unwrap();
+        assert_eq!(layout.columns.len(), 13);
+        layout.columns = vec![
+            FfiCsvColumn {
+                column: "sku".into(),
+                header: Some("Item Code".into()),
+            },
+            FfiCsvColumn {
+                column: "quantity".into(),
+                header: None,
+            },
+        ];
+        layout.delimiter = "\t".into();
+        core.set_billing_csv_layout(layout.clone()).unwrap();
+        assert_eq!(
+            core.export_billing_csv().unwrap(),
+            "Item Code\tquantity\nLRS-1L\t1\n"
+        );
+
+        let unknown = FfiCsvLayout {
+            columns: vec![FfiCsvColumn {
+                column: "price".into(),
+                header: None,
+            }],
+            ..layout.clone()
+        };
+        let bad_delimiter = FfiCsvLayout {
+            delimiter: ",,".into(),
+            ..layout.clone()
+        };
+        let bad_date = FfiCsvLayout {
+            date_format: Some("%Q".into()),
+            ..layout
+        };
+        for bad in [unknown, bad_delimiter, bad_date] {
+            assert!(matches!(
+                core.set_billing_csv_layout(bad),
+                Err(FuzzyDrugsError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_sampling_settings() {
+        let core = open_database_in_memory().unwrap();
+        let defaults = core.get_sampling_settings().unwrap();
+        assert_eq!(defaults.temperature, 0.0);
+        assert_eq!(defaults.top_p, 1.0);
+
+        let settings = FfiSamplingSettings {
+            temperature: 0.7,
+            top_p: 0.95,
+            repeat_penalty: 1.1,
+            max_tokens: 256,
+            seed: 7,
+        };
+        core.set_sampling_settings(settings.clone()).unwrap();
+        let stored = core.get_sampling_settings().unwrap();
+        assert_eq!((stored.temperature, stored.seed), (0.7, 7));
+
+        let bad = FfiSamplingSettings {
+            top_p: 0.0,
+            ..settings
+        };
+        assert!(matches!(
+            core.set_sampling_settings(bad),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        assert_eq!(core.get_sampling_settings().unwrap().max_tokens, 256);
+    }
+
+    #[test]
+    fn test_tax_rates() {
+        let core = open_database_in_memory().unwrap();
+        assert!(core.get_tax_rates().unwrap().is_empty());
+
+        let rate = |code: &str, rate: f64| FfiTaxRate {
+            code: code.into(),
+            rate,
+        };
+        core.set_tax_rates(vec![rate("RETAIL", 0.0725), rate("FOOD", 0.0)])
+            .unwrap();
+        let codes: Vec<String> = core
+            .get_tax_rates()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.code)
+            .collect();
+        assert_eq!(codes, ["FOOD", "RETAIL"]);
+
+        for bad in [
+            vec![rate("RETAIL", 0.05), rate("RETAIL", 0.06)],
+            vec![rate("RETAIL", 0.05), rate("retail", 0.06)],
+            vec![rate("RETAIL", 7.25)],
+            vec![rate("", 0.05)],
+        ] {
+            assert!(matches!(
+                core.set_tax_rates(bad),
+                Err(FuzzyDrugsError::InvalidInput(_))
+            ));
+        }
+        assert_eq!(core.get_tax_rates().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_signed_export_verification() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        commit_manual_encounter(&core, &patient.local_id);
+
+        assert!(matches!(
+            core.set_export_signing_key(Some(vec![1; 16])),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        let public_key = core.set_export_signing_key(Some(vec![1; 32])).unwrap();
+        let json = core.export_compliance_json().unwrap();
+        let verification = verify_export(json.clone(), public_key.clone()).unwrap();
+        assert!(verification.is_valid);
+        assert_eq!(verification.signature_valid, Some(true));
+        assert_eq!(verification.leaf_count, 1);
+
+        let tampered = json.replace("Dr. Smith", "Dr. Jones");
+        assert!(
+            !verify_export(tampered, public_key.clone())
+                .unwrap()
+                .is_valid
+        );
+        // A self-keyed check vouches for nothing
+        let self_keyed = verify_export(json, None).unwrap();
+        assert!(self_keyed.body_hash_valid && !self_keyed.is_valid);
+
+        assert_eq!(core.set_export_signing_key(None).unwrap(), None);
+        let unsigned = verify_export(core.export_billing_json().unwrap(), None).unwrap();
+        assert!(unsigned.body_hash_valid);
+        assert!(!unsigned.is_valid);
+        assert_eq!(unsigned.signature_valid, None);
+        let unsigned = verify_export(core.export_billing_json().unwrap(), public_key).unwrap();
+        assert_eq!(unsigned.signature_valid, Some(false));
+
+        assert!(matches!(
+            verify_export("{}".into(), None),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_unbilled_batches() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        commit_manual_encounter(&core, &patient.local_id);
+
+        let first = core.export_unbilled("csv".into()).unwrap();
+        assert_eq!(first.encounter_count, 1);
+        assert!(first.data.contains("LRS-1L"));
+        let batch_id = first.batch_id.unwrap();
+        let again = core.export_unbilled("json".into()).unwrap();
+        assert_eq!(again.encounter_count, 0);
+        assert_eq!(again.batch_id, None);
+
+        let void = core.void_export_batch(batch_id.clone()).unwrap();
+        assert_eq!(void.status, "void");
+        assert!(matches!(
+            core.mark_export_batch_imported(batch_id),
+            Err(FuzzyDrugsError::Conflict { .. })
+        ));
+        let retry = core.export_unbilled("json".into()).unwrap();
+        assert_eq!(retry.encounter_count, 1);
+        let imported = core
+            .mark_export_batch_imported(retry.batch_id.unwrap())
+            .unwrap();
+        assert_eq!(imported.status, "imported");
+        assert_eq!(imported.leaf_hashes.len(), 1);
+        assert_eq!(core.list_export_batches().unwrap().len(), 2);
+
+        assert!(matches!(
+            core.void_export_batch("missing".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+        assert!(matches!(
+            core.export_unbilled("iif".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_render_summary() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mut draft = EncounterDraft::new(patient.local_id);
+        draft.add_manual_item(
+            "LRS-1L".into(),
+            "LRS 1L".into(),
+            1.0,
+            "bag".into(),
+            Some("IV".into()),
+        );
+        let commit = commit_reviewed(&core, draft);
+
+        let markdown = core
+            .render_summary(commit.leaf_hash.clone(), "markdown".into())
+            .unwrap();
+        assert!(markdown.contains("**Patient:** Max (canine)"));
+        assert!(markdown.contains("**Veterinarian:** Dr. Smith"));
+        assert!(markdown.contains("- **LRS 1L**: 1 bag, Intravenous\n"));
+        let text = core
+            .render_summary(commit.leaf_hash.clone(), "text".into())
+            .unwrap();
+        assert!(text.contains("Patient: Max (canine)\n"));
+
+        assert!(matches!(
+            core.render_summary(commit.leaf_hash, "html".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            core.render_summary("missing".into(), "text".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_deidentified_exports() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mut draft = EncounterDraft::new(patient.local_id.clone());
+        draft.transcript = "Max for Jane Doe, one bag of LRS".into();
+        draft.add_manual_item("LRS-1L".into(), "LRS 1L".into(), 1.0, "bag".into(), None);
+        commit_reviewed(&core, draft);
+
+        let compliance = core
+            .export_compliance_json_deidentified("hash".into())
+            .unwrap();
+        assert!(!compliance.contains(&patient.local_id));
+        assert!(!compliance.contains("Jane Doe"));
+        assert!(compliance.contains("\"redacted\": true"));
+        assert!(compliance.contains("LRS-1L"));
+        assert!(
+            verify_export(compliance.clone(), None)
+                .unwrap()
+                .body_hash_valid
+        );
+
+        let billing = core.export_billing_json_deidentified().unwrap();
+        assert!(!billing.contains(&patient.local_id));
+        assert!(billing.contains("\"redacted\": true"));
+        // Pseudonyms are stable across exports and exporters
+        let again = core
+            .export_compliance_json_deidentified("strip".into())
+            .unwrap();
+        let pseudonym = |json: &str| -> String {
+            let value: serde_json::Value = serde_json::from_str(json).unwrap();
+            value["encounters"][0]["encounter"]["patient_id"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(pseudonym(&compliance), pseudonym(&again));
+        assert!(billing.contains(&pseudonym(&again)));
+
+        assert!(matches!(
+            core.export_compliance_json_deidentified("blur".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_xlsx_exports() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        commit_manual_encounter(&core, &patient.local_id);
+
+        for result in [core.export_billing_xlsx(), core.export_compliance_xlsx()] {
+            #[cfg(feature = "xlsx")]
+            assert!(result.unwrap().starts_with(b"PK"));
+            #[cfg(not(feature = "xlsx"))]
+            assert!(matches!(result, Err(FuzzyDrugsError::InvalidInput(_))));
+        }
+    }
+
+    #[test]
+    fn test_export_audit_bundle() {
+        let core = open_database_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.zip").to_string_lossy().into_owned();
+        let (start, end) = ("2000-01-01T00:00:00Z", "2999-12-31T23:59:59Z");
+        let result = core.export_audit_bundle(path.clone(), start.into(), end.into());
+        assert!(matches!(result, Err(FuzzyDrugsError::InvalidInput(_))));
+
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let commit = commit_manual_encounter(&core, &patient.local_id);
+        core.set_export_signing_key(Some(vec![7; 32])).unwrap();
+
+        let result = core.export_audit_bundle(path.clone(), start.into(), end.into());
+        #[cfg(feature = "zip")]
+        {
+            let bundle = result.unwrap();
+            assert_eq!(bundle.encounter_count, 1);
+            assert_eq!(bundle.root_hash, commit.root_hash);
+            assert!(bundle.signed);
+            let zip = std::fs::read(&path).unwrap();
+            assert!(zip.starts_with(b"PK"));
+            assert_eq!(zip.len() as u64, bundle.bytes);
+        }
+        #[cfg(not(feature = "zip"))]
+        {
+            let _ = commit;
+            assert!(matches!(result, Err(FuzzyDrugsError::InvalidInput(_))));
+        }
+    }
+
+    #[test]
+    fn test_quickbooks_iif_export() {
+        let core = open_database_in_memory().unwrap();
+        let mut patient = Patient::new("Max".into(), "canine".into());
+        patient.owner_name = Some("Jane Doe".into());
+        locked_db(&core).insert_patient(&patient).unwrap();
+        commit_manual_encounter(&core, &patient.local_id);
+
+        let mut mapping = core.get_quickbooks_mapping().unwrap();
+        assert_eq!(mapping.income_account, "Sales");
+        mapping.items.push(FfiQuickBooksItem {
+            sku: "LRS-1L".into(),
+            item: "Fluids".into(),
+            account: Some("Hospital Income".into()),
+        });
+        core.set_quickbooks_mapping(mapping.clone()).unwrap();
+        assert_eq!(core.get_quickbooks_mapping().unwrap().items.len(), 1);
+
+        let iif = core.export_billing_iif().unwrap();
+        assert!(iif.contains("\tJane Doe\t"));
+        assert!(iif.contains("\tHospital Income\t"));
+        assert!(iif.contains("\tFluids\n"));
+
+        mapping.income_account = " ".into();
+        assert!(matches!(
+            core.set_quickbooks_mapping(mapping),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_controlled_confirmation_follows_committed_sku() {
+        let core = open_database_in_memory().unwrap();
+        let mut ketamine = models::CatalogItem::new("KET-100".into(), "Ketamine".into());
+        ketamine.controlled_schedule = Some(models::ControlledSchedule::CIII);
+        let carprofen = models::CatalogItem::new("CARP-100".into(), "Carprofen 100mg".into());
+        {
+            let db = locked_db(&core);
+            db.upsert_catalog_item(&ketamine).unwrap();
+            db.upsert_catalog_item(&carprofen).unwrap();
+        }
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft = core.create_draft(patient.local_id).unwrap();
+        {
+            let db = locked_db(&core);
+            let mention = FuzzyDrugsCore::ffi_mention("carprofen".into(), None, None, None, None);
+            let resolved = Resolver::new(&db)
+                .resolve(&mention, Some("canine"), None, None)
+                .unwrap();
+            let mut stored = db.get_draft(&draft.draft_id).unwrap().unwrap();
+            stored.resolved_items.push(resolved);
+            db.update_draft(&stored).unwrap();
+        }
+
+        // Overriding to a controlled SKU outside the candidates needs a
+        // confirmation, as does a controlled manual item
+        let updated = core
+            .manual_override(draft.draft_id.clone(), 0, "KET-100".into())
+            .unwrap();
+        assert_eq!(updated.controlled_item_count, 1);
+        let line = FfiLineItem {
+            sku: "KET-100".into(),
+            name: "Ketamine".into(),
+            quantity: 0.5,
+            unit: "mL".into(),
+            route: None,
+            original_mention: String::new(),
+            controlled_schedule: None,
+            schedule: vec![],
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        };
+        core.add_manual_item(draft.draft_id.clone(), line).unwrap();
+        core.confirm_controlled_item(draft.draft_id.clone(), 0, "Dr. Smith".into())
+            .unwrap();
+        let mut stored = locked_db(&core)
+            .get_draft(&draft.draft_id)
+            .unwrap()
+            .unwrap();
+        assert!(!stored.all_reviewed());
+        stored.status = DraftStatus::Reviewed;
+        locked_db(&core).update_draft(&stored).unwrap();
+        assert!(matches!(
+            core.resume_pending_commit(draft.draft_id.clone(), "Dr. Smith".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        core.confirm_controlled_manual_item(draft.draft_id.clone(), 0, "Dr. Smith".into())
+            .unwrap();
+        let commit = core
+            .resume_pending_commit(draft.draft_id.clone(), "Dr. Smith".into())
+            .unwrap();
+        let payload = MerkleTree::new(&locked_db(&core))
+            .get_leaf_payload(&commit.leaf_hash)
+            .unwrap()
+            .unwrap();
+        let encounter: ReviewedEncounter = serde_json::from_str(&payload).unwrap();
+        assert!(encounter.line_items.iter().all(|i| {
+            i.controlled_schedule == Some(models::ControlledSchedule::CIII)
+                && i.controlled_confirmed_by.as_deref() == Some("Dr. Smith")
+        }));
+    }
+
+    #[test]
+    fn test_export_controlled_substance_log() {
+        let core = open_database_in_memory().unwrap();
+        let mut ketamine = models::CatalogItem::new("KET-100".into(), "Ketamine".into());
+        ketamine.controlled_schedule = Some(models::ControlledSchedule::CIII);
+        core.db
+            .lock()
+            .unwrap()
+            .upsert_catalog_item(&ketamine)
+            .unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mut draft = EncounterDraft::new(patient.local_id);
+        draft
+            .add_manual_item("KET-100".into(), "Ketamine".into(), 0.5, "mL".into(), None)
+            .controlled_confirmed_by = Some("Dr. Smith".into());
+        draft.add_manual_item("LRS-1L".into(), "LRS 1L".into(), 1.0, "bag".into(), None);
+        commit_reviewed(&core, draft);
+
+        let balances = vec![FfiStockBalance {
+            sku: "KET-100".into(),
+            quantity: 10.0,
+        }];
+        let csv = core
+            .export_controlled_substance_log(
+                "2000-01-01T00:00:00Z".into(),
+                "2999-01-01T00:00:00Z".into(),
+                "csv".into(),
+                balances.clone(),
+            )
+            .unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains("Max,,Ketamine,KET-100,,C-III,0.5,mL,,9.5,Dr. Smith"));
+
+        assert!(matches!(
+            core.export_controlled_substance_log(
+                "yesterday".into(),
+                "2999-01-01T00:00:00Z".into(),
+                "csv".into(),
+                vec![],
+            ),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        let pdf = core.export_controlled_substance_log_pdf(
+            "2000-01-01T00:00:00Z".into(),
+            "2999-01-01T00:00:00Z".into(),
+            balances,
+        );
+        #[cfg(feature = "pdf")]
+        assert!(pdf.unwrap().starts_with(b"%PDF"));
+        #[cfg(not(feature = "pdf"))]
+        assert!(matches!(pdf, Err(FuzzyDrugsError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_export_invoice_pdf() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let commit = commit_manual_encounter(&core, &patient.local_id);
+
+        let result = core.export_invoice_pdf(commit.leaf_hash, Some("Valley Vet".into()));
+        #[cfg(feature = "pdf")]
+        assert!(result.unwrap().starts_with(b"%PDF"));
+        #[cfg(not(feature = "pdf"))]
+        assert!(matches!(result, Err(FuzzyDrugsError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_parse_since_timestamp() {
+        assert_eq!(
+            parse_since_timestamp("2024-01-15T10:00:00-05:00").unwrap(),
+            "2024-01-15 15:00:00"
+        );
+        assert_eq!(
+            parse_since_timestamp("2024-01-15 10:00:00").unwrap(),
+            "2024-01-15 10:00:00"
+        );
+        assert!(parse_since_timestamp("2024-01-15").is_err());
+    }
+
+    /// Host-app HTTP client for a PIMS that asks for the root node and
+    /// acknowledges whatever it's sent.
+    struct FakeSyncClient {
+        paths: Mutex<Vec<String>>,
+        unavailable: bool,
+    }
+
+    impl FfiSyncTransport for FakeSyncClient {
+        fn post(&self, path: String, body: String) -> Result<String, FuzzyDrugsError> {
+            self.paths.lock().unwrap().push(path.clone());
+            if self.unavailable {
+                return Err(FuzzyDrugsError::SyncError("HTTP 503".into()));
+            }
+            let reply = if path == merkle::CAPABILITIES_PATH {
+                serde_json::to_value(merkle::SyncCapabilities::local()).unwrap()
+            } else if path == merkle::PATIENT_PULL_PATH {
+                serde_json::json!({
+                    "patients": [{
+                        "server_id": "srv-Max",
+                        "name": "max",
+                        "species": "canine",
+                        "owner_name": "Jane Doe"
+                    }],
+                    "timestamp": "2024-01-15T12:00:00Z"
+                })
+            } else if path == merkle::PATIENT_UPSERT_PATH {
+                let patient: Patient = serde_json::from_str(&body).unwrap();
+                serde_json::json!({ "server_id": format!("srv-{}", patient.name) })
+            } else if path == merkle::SYNC_REQUEST_PATH {
+                let request: merkle::SyncRequest = serde_json::from_str(&body).unwrap();
+                serde_json::json!({ "missing_hashes": [request.root_hash], "server_root_hash": null })
+            } else {
+                let payload: merkle::SyncPayload = serde_json::from_str(&body).unwrap();
+                serde_json::json!({ "success": true, "new_root": payload.expected_root, "error": null })
+            };
+            Ok(reply.to_string())
+        }
+    }
+
+    #[test]
+    fn test_run_sync() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let commit = commit_manual_encounter(&core, &patient.local_id);
+
+        let down = Arc::new(FakeSyncClient {
+            paths: Mutex::new(vec![]),
+            unavailable: true,
+        });
+        assert!(matches!(
+            core.run_sync(down),
+            Err(FuzzyDrugsError::SyncError(msg)) if msg.contains("HTTP 503")
+        ));
+        assert!(core.has_unsynced_changes().unwrap());
+        let status = core.get_sync_status().unwrap();
+        assert_eq!(status.unsynced_leaf_count, 1);
+        assert_eq!(status.encounters_last_sync, None);
+        assert!(status.last_error.unwrap().contains("HTTP 503"));
+        assert!(status.last_error_at.is_some());
+        assert!(!status.server_root_matches);
+
+        let client = Arc::new(FakeSyncClient {
+            paths: Mutex::new(vec![]),
+            unavailable: false,
+        });
+        let report = core.run_sync(client.clone()).unwrap();
+        assert!(!report.up_to_date);
+        assert!(report.nodes_sent > 0);
+        assert_eq!(report.synced_root, Some(commit.root_hash));
+        assert!(!core.has_unsynced_changes().unwrap());
+        assert_eq!(
+            *client.paths.lock().unwrap(),
+            [
+                merkle::CAPABILITIES_PATH,
+                merkle::SYNC_REQUEST_PATH,
+                merkle::SYNC_PAYLOAD_PATH
+            ]
+        );
+        assert!(core.run_sync(client.clone()).unwrap().up_to_date);
+        assert_eq!(client.paths.lock().unwrap().len(), 3);
+        let status = core.get_sync_status().unwrap();
+        assert_eq!(status.unsynced_leaf_count, 0);
+        assert_eq!(status.last_error, None);
+        assert!(status.server_root_matches);
+        assert!(status.encounters_last_sync.is_some());
+        assert!(!status.conflict_open);
+        assert!(core.get_sync_conflict().unwrap().is_none());
+        assert!(matches!(
+            core.resolve_sync_conflict("local_wins".into()),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+        assert!(matches!(
+            core.resolve_sync_conflict("manual".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        #[cfg(not(feature = "http"))]
+        assert!(matches!(
+            core.run_http_sync("https://pims.example".into(), None),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+    }
+
+    /// Host-app HTTP client that commits another encounter while the PIMS
+    /// is handling the payload.
+    struct CommittingSyncClient {
+        core: Arc<FuzzyDrugsCore>,
+        patient_id: String,
+        inner: FakeSyncClient,
+        committed: Mutex<Option<String>>,
+    }
+
+    impl FfiSyncTransport for CommittingSyncClient {
+        fn post(&self, path: String, body: String) -> Result<String, FuzzyDrugsError> {
+            if path == merkle::SYNC_PAYLOAD_PATH {
+                assert!(
+                    self.core.db.try_lock().is_ok(),
+                    "database locked during sync"
+                );
+                let commit = commit_manual_encounter(&self.core, &self.patient_id);
+                *self.committed.lock().unwrap() = Some(commit.leaf_hash);
+            }
+            self.inner.post(path, body)
+        }
+    }
+
+    #[test]
+    fn test_sync_releases_database_lock() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let first = commit_manual_encounter(&core, &patient.local_id);
+
+        let client = Arc::new(CommittingSyncClient {
+            core: core.clone(),
+            patient_id: patient.local_id,
+            inner: FakeSyncClient {
+                paths: Mutex::new(vec![]),
+                unavailable: false,
+            },
+            committed: Mutex::new(None),
+        });
+        let report = core.run_sync(client.clone()).unwrap();
+        assert_eq!(report.synced_root, Some(first.root_hash));
+
+        // The encounter committed mid-sync wasn't part of it and stays queued
+        let second = client.committed.lock().unwrap().clone().unwrap();
+        let queued: Vec<_> = core
+            .list_sync_outbox()
+            .unwrap()
+            .into_iter()
+            .filter(|item| item.kind == "encounter_push")
+            .map(|item| item.subject_id)
+            .collect();
+        assert_eq!(queued, [second]);
+        assert!(core.has_unsynced_changes().unwrap());
+    }
+
+    #[test]
+    fn test_process_sync_outbox() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        core.set_patient_weight(patient.local_id.clone(), 30.0, None)
+            .unwrap();
+        let commit = commit_manual_encounter(&core, &patient.local_id);
+        let queued = core.list_sync_outbox().unwrap();
+        let kinds: Vec<_> = queued.iter().map(|i| i.kind.as_str()).collect();
+        assert_eq!(kinds, ["patient_upsert", "encounter_push"]);
+        assert_eq!(queued[1].subject_id, commit.leaf_hash);
+
+        let down = Arc::new(FakeSyncClient {
+            paths: Mutex::new(vec![]),
+            unavailable: true,
+        });
+        let report = core.process_sync_outbox(down).unwrap();
+        assert_eq!((report.succeeded, report.failed), (0, 2));
+        assert!(report.next_attempt_at.is_some());
+        let queued = core.list_sync_outbox().unwrap();
+        assert!(queued
+            .iter()
+            .all(|i| i.attempts == 1 && i.status == "pending"));
+        assert_eq!(
+            queued[0].last_error.as_deref(),
+            Some("Connection failed: HTTP 503")
+        );
+
+        // Retrying makes an operation due again straight away
+        let client = Arc::new(FakeSyncClient {
+            paths: Mutex::new(vec![]),
+            unavailable: false,
+        });
+        let retried = core.retry_sync_outbox_item(queued[0].id).unwrap();
+        assert_eq!(retried.attempts, 0);
+        let report = core.process_sync_outbox(client.clone()).unwrap();
+        assert_eq!((report.succeeded, report.failed), (1, 0));
+        assert_eq!(
+            *client.paths.lock().unwrap(),
+            [merkle::CAPABILITIES_PATH, merkle::PATIENT_UPSERT_PATH]
+        );
+        let linked = core.get_patient(patient.local_id).unwrap().unwrap();
+        assert_eq!(linked.server_id.as_deref(), Some("srv-Max"));
+
+        // A manual sync delivers the queued encounter
+        core.run_sync(client).unwrap();
+        assert!(core.list_sync_outbox().unwrap().is_empty());
+        assert!(matches!(
+            core.retry_sync_outbox_item(queued[1].id),
+            Err(FuzzyDrugsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_patient_sync() {
+        let core = open_database_in_memory().unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        {
+            let db = locked_db(&core);
+            let mut local = db.get_patient(&patient.local_id).unwrap().unwrap();
+            local.owner_name = Some("jane doe".into());
+            db.update_patient(&local).unwrap();
+        }
+        assert!(core.create_patient_sync_request().unwrap().since.is_none());
+
+        let client = Arc::new(FakeSyncClient {
+            paths: Mutex::new(vec![]),
+            unavailable: false,
+        });
+        let report = core.pull_patients(client).unwrap();
+        assert_eq!((report.created, report.linked), (0, 1));
+        let linked = core.get_patient(patient.local_id.clone()).unwrap().unwrap();
+        assert_eq!(linked.server_id.as_deref(), Some("srv-Max"));
+
+        let report = core
+            .apply_patient_delta(FfiPatientDelta {
+                patients: vec![FfiPatientSyncItem {
+                    server_id: "srv-Bella".into(),
+                    name: "Bella".into(),
+                    species: "feline".into(),
+                    breed: None,
+                    weight_kg: Some(4.2),
+                    date_of_birth: None,
+                    owner_name: Some("Jane Doe".into()),
+                }],
+                timestamp: "2024-01-16T12:00:00Z".into(),
+            })
+            .unwrap();
+        assert_eq!(report.created, 1);
+        assert_eq!(
+            core.create_patient_sync_request().unwrap().since.as_deref(),
+            Some("2024-01-16T12:00:00Z")
+        );
+        assert!(matches!(
+            core.apply_patient_delta(FfiPatientDelta {
+                patients: vec![],
+                timestamp: " ".into(),
+            }),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        // Encounters committed after linking carry the PIMS patient ID
+        let commit = commit_manual_encounter(&core, &patient.local_id);
+        let payload = MerkleTree::new(&locked_db(&core))
+            .get_leaf_payload(&commit.leaf_hash)
+            .unwrap()
+            .unwrap();
+        let encounter: ReviewedEncounter = serde_json::from_str(&payload).unwrap();
+        assert_eq!(encounter.patient_server_id.as_deref(), Some("srv-Max"));
+    }
+
+    #[test]
+    fn test_catalog_sync() {
+        let core = open_database_in_memory().unwrap();
+        assert!(core.create_catalog_sync_request().unwrap().since.is_none());
+        let item = CatalogItem::new("OLD-1".into(), "Discontinued".into());
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+
+        let sync_item = |schedule: Option<&str>| FfiCatalogSyncItem {
+            sku: "BUP-03".into(),
+            name: "Buprenorphine 0.3mg/mL".into(),
+            aliases: vec!["bupe".into()],
+            concentration: Some("0.3mg/mL".into()),
+            package_size: None,
+            species: vec!["canine".into(), "feline".into()],
+            routes: vec!["IV".into(), "IM".into()],
+            active: true,
+            server_id: "srv-42".into(),
+            components: vec![],
+            controlled_schedule: schedule.map(Into::into),
+            unit_price: Some(1.25),
+            markup: None,
+            minimum_charge: None,
+            tax_code: None,
+        };
+        let delta = |schedule| FfiCatalogDelta {
+            items: vec![sync_item(schedule)],
+            deactivated_skus: vec!["OLD-1".into()],
+            timestamp: "2024-01-15T12:00:00Z".into(),
+        };
+
+        assert!(matches!(
+            core.apply_catalog_delta(delta(Some("C-IX"))),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        assert!(core.get_catalog_item("BUP-03".into()).unwrap().is_none());
+
+        let report = core.apply_catalog_delta(delta(Some("CIII"))).unwrap();
+        assert_eq!(report.upserted, 1);
+        assert!(report.failures.is_empty());
+        let bupe = core.get_catalog_item("BUP-03".into()).unwrap().unwrap();
+        assert_eq!(bupe.controlled_schedule.as_deref(), Some("C-III"));
+        assert!(
+            !core
+                .get_catalog_item("OLD-1".into())
+                .unwrap()
+                .unwrap()
+                .active
+        );
+        assert_eq!(
+            core.create_catalog_sync_request().unwrap().since.as_deref(),
+            Some("2024-01-15T12:00:00Z")
+        );
+
+        // A row that fails is reported and asked for again next time
+        let mut unnamed = delta(None);
+        unnamed.items[0].name = String::new();
+        unnamed.timestamp = "2024-01-16T12:00:00Z".into();
+        let report = core.apply_catalog_delta(unnamed).unwrap();
+        assert_eq!(report.upserted, 0);
+        assert_eq!(report.failures[0].sku, "BUP-03");
+        assert_eq!(
+            core.create_catalog_sync_request().unwrap().retry_skus,
+            vec!["BUP-03".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_catalog_push() {
+        let core = open_database_in_memory().unwrap();
+        let mut pims = CatalogItem::new("CARP-100".into(), "Carprofen 100mg".into());
+        pims.server_id = Some("srv-1".into());
+        locked_db(&core).upsert_catalog_item(&pims).unwrap();
+        assert!(core.create_catalog_push_delta().unwrap().items.is_empty());
+
+        let house = CatalogItem::new("HOUSE-1".into(), "Compounded Gabapentin".into());
+        core.upsert_catalog_item(house.into()).unwrap();
+        core.deactivate_catalog_item("CARP-100".into()).unwrap();
+
+        let push = core.create_catalog_push_delta().unwrap();
+        let skus: Vec<_> = push.items.iter().map(|item| item.sku.as_str()).collect();
+        assert_eq!(skus, vec!["CARP-100", "HOUSE-1"]);
+        assert_eq!(push.items[0].server_id.as_deref(), Some("srv-1"));
+        assert_eq!(push.items[1].origin, "local");
+        assert!(push.items[1].server_id.is_none());
+
+        assert!(matches!(
+            core.apply_catalog_push_ack(FfiCatalogPushAck {
+                accepted: vec![],
+                timestamp: " ".into(),
+            }),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        let accepted = core
+            .apply_catalog_push_ack(FfiCatalogPushAck {
+                accepted: vec![FfiCatalogPushResult {
+                    sku: "HOUSE-1".into(),
+                    server_id: "srv-2".into(),
+                }],
+                timestamp: "2024-01-15T12:00:00Z".into(),
+            })
+            .unwrap();
+        assert_eq!(accepted, 1);
+        let push = core.create_catalog_push_delta().unwrap();
+        assert_eq!(push.items.len(), 1);
+        assert_eq!(push.items[0].sku, "CARP-100");
+    }
+
+    struct RecordingListener {
+        db: Arc<Mutex<Database>>,
+        events: Mutex<Vec<String>>,
+    }
+
+    impl RecordingListener {
+        fn record(&self, event: String) {
+            // Callbacks must run after the database lock is released
+            assert!(self.db.try_lock().is_ok(), "{} delivered under lock", event);
+            self.events.lock().unwrap().push(event);
+        }
+
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut *self.events.lock().unwrap())
+        }
+    }
+
+    impl FuzzyDrugsListener for RecordingListener {
+        fn on_draft_status_changed(&self, draft_id: String, status: String) {
+            self.record(format!("draft {} {}", draft_id, status));
+        }
+
+        fn on_encounter_committed(&self, draft_id: String, _leaf_hash: String) {
+            self.record(format!("committed {}", draft_id));
+        }
+
+        fn on_catalog_updated(&self, skus: Vec<String>) {
+            self.record(format!("catalog {}", skus.join(",")));
+        }
+
+        fn on_sync_state_changed(&self, has_unsynced_changes: bool) {
+            self.record(format!("unsynced {}", has_unsynced_changes));
+        }
+    }
+
+    #[test]
+    fn test_listener() {
+        let core = open_database_in_memory().unwrap();
+        let listener = Arc::new(RecordingListener {
+            db: core.db.clone(),
+            events: Mutex::new(Vec::new()),
+        });
+        core.set_listener(listener.clone()).unwrap();
+
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft = core.create_draft(patient.local_id.clone()).unwrap();
+        assert_eq!(
+            listener.take(),
+            vec![format!("draft {} Recording", draft.draft_id)]
+        );
+
+        let mut reviewed = EncounterDraft::new(patient.local_id.clone());
+        reviewed.add_manual_item("LRS-1L".into(), "LRS 1L".into(), 1.0, "bag".into(), None);
+        commit_reviewed(&core, reviewed.clone());
+        assert_eq!(
+            listener.take(),
+            vec![
+                format!("draft {} Committed", reviewed.draft_id),
+                format!("committed {}", reviewed.draft_id),
+                "unsynced true".to_string(),
+            ]
+        );
+
+        // Already unsynced: discarding adds a leaf but doesn't flip the state
+        core.discard_draft(
+            draft.draft_id.clone(),
+            "Dr. Smith".into(),
+            "Duplicate".into(),
+        )
+        .unwrap();
+        assert_eq!(
+            listener.take(),
+            vec![format!("draft {} Discarded", draft.draft_id)]
+        );
+
+        core.upsert_catalog_item(CatalogItem::new("CARP-100".into(), "Carprofen".into()).into())
+            .unwrap();
+        core.deactivate_catalog_item("CARP-100".into()).unwrap();
+        assert_eq!(
+            listener.take(),
+            vec!["catalog CARP-100", "catalog CARP-100"]
+        );
+
+        // Failed calls notify nothing
+        assert!(core.deactivate_catalog_item("NOPE".into()).is_err());
+        assert!(listener.take().is_empty());
+
+        core.clear_listener().unwrap();
+        core.delete_catalog_item("CARP-100".into()).unwrap();
+        assert!(listener.take().is_empty());
+    }
+
+    struct RecordingProgress {
+        steps: Mutex<Vec<(u64, u64)>>,
+        cancel_at: Option<(u64, Arc<FfiCancellationToken>)>,
+    }
+
+    impl FfiProgressListener for RecordingProgress {
+        fn on_progress(&self, processed: u64, total: u64) {
+            self.steps.lock().unwrap().push((processed, total));
+            if let Some((at, token)) = &self.cancel_at {
+                if processed == *at {
+                    token.cancel();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_progress_and_cancellation() {
+        let core = open_database_in_memory().unwrap();
+        let items: Vec<FfiCatalogItem> = [
+            ("CARP-100", "Carprofen 100mg tablets"),
+            ("MELOX-15", "Meloxicam 1.5mg/mL"),
+            ("LRS-1L", "Lactated Ringer's 1L"),
+        ]
+        .iter()
+        .map(|(sku, name)| CatalogItem::new(sku.to_string(), name.to_string()).into())
+        .collect();
+
+        // Cancelled partway: nothing is imported
+        let token = FfiCancellationToken::new();
+        let progress = Arc::new(RecordingProgress {
+            steps: Mutex::new(Vec::new()),
+            cancel_at: Some((1, token.clone())),
+        });
+        let result = core.import_catalog_items(items.clone(), Some(progress.clone()), Some(token));
+        assert!(matches!(result, Err(FuzzyDrugsError::Cancelled(_))));
+        assert_eq!(*progress.steps.lock().unwrap(), vec![(0, 3), (1, 3)]);
+        assert!(core.get_catalog_item("CARP-100".into()).unwrap().is_none());
+
+        let progress = Arc::new(RecordingProgress {
+            steps: Mutex::new(Vec::new()),
+            cancel_at: None,
+        });
+        assert_eq!(
+            core.import_catalog_items(items, Some(progress.clone()), None)
+                .unwrap(),
+            3
+        );
+        assert_eq!(progress.steps.lock().unwrap().last(), Some(&(3, 3)));
+
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mention = |name: &str| FfiDrugMention {
+            raw_text: name.into(),
+            drug_name: name.into(),
+            dose: None,
+            unit: None,
+            route: None,
+            species: None,
+            start_offset: 0,
+            end_offset: 0,
+        };
+        let batch = core
+            .resolve_mentions_for_patient(
+                patient.local_id.clone(),
+                vec![mention("carprofen"), mention("zzqx")],
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(batch.items.len(), 1);
+        assert_eq!(batch.unmatched_drugs, vec!["zzqx"]);
+
+        let token = FfiCancellationToken::new();
+        token.cancel();
+        let result = core.resolve_mentions_for_patient(
+            patient.local_id,
+            vec![mention("carprofen")],
+            None,
+            Some(token),
+        );
+        assert!(matches!(result, Err(FuzzyDrugsError::Cancelled(_))));
+
+        let token = FfiCancellationToken::new();
+        token.cancel();
+        let result = core.export_compliance_json_with_progress(None, Some(token));
+        assert!(matches!(result, Err(FuzzyDrugsError::Cancelled(_))));
+        assert!(core
+            .export_compliance_json_with_progress(None, None)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_escalated_approvals_checked_against_user_roles() {
+        let core = open_database_in_memory().unwrap();
+        let item = CatalogItem::new("FENT-50".into(), "Fentanyl 50mcg/mL".into());
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        core.upsert_escalation_rule("fentanyl".into(), "dvm-lead".into(), "Opioid".into())
+            .unwrap();
+        let user = |user_id: &str, role: &str| FfiUser {
+            user_id: user_id.into(),
+            name: user_id.into(),
+            license_number: None,
+            license_state: None,
+            dea_number: None,
+            roles: vec![role.into()],
+        };
+        core.upsert_user(user("lead", "dvm-lead")).unwrap();
+        core.upsert_user(user("tech", "technician")).unwrap();
+        let line = FfiLineItem {
+            sku: "FENT-50".into(),
+            name: "Fentanyl 50mcg/mL".into(),
+            quantity: 1.0,
+            unit: "mL".into(),
+            route: Some("IV".into()),
+            original_mention: String::new(),
+            controlled_schedule: None,
+            schedule: vec![],
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        };
+
+        // A manual item is approved on the draft and keeps ManualEntry
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let draft = core.create_draft(patient.local_id.clone()).unwrap();
+        core.add_manual_item(draft.draft_id.clone(), line.clone())
+            .unwrap();
+        let mut stored = locked_db(&core)
+            .get_draft(&draft.draft_id)
+            .unwrap()
+            .unwrap();
+        stored.status = DraftStatus::Reviewed;
+        locked_db(&core).update_draft(&stored).unwrap();
+        assert!(matches!(
+            core.resume_pending_commit(draft.draft_id.clone(), "lead".into()),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        // Claiming the role isn't enough: the user must hold it
+        assert!(matches!(
+            core.approve_escalated_manual_item(
+                draft.draft_id.clone(),
+                0,
+                "tech".into(),
+                "dvm-lead".into(),
+                "Post-op pain".into(),
+            ),
+            Err(FuzzyDrugsError::PermissionDenied { .. })
+        ));
+        core.approve_escalated_manual_item(
+            draft.draft_id.clone(),
+            0,
+            "lead".into(),
+            "dvm-lead".into(),
+            "Post-op pain".into(),
+        )
+        .unwrap();
+        let commit = core
+            .resume_pending_commit(draft.draft_id.clone(), "lead".into())
+            .unwrap();
+        let payload = MerkleTree::new(&locked_db(&core))
+            .get_leaf_payload(&commit.leaf_hash)
+            .unwrap()
+            .unwrap();
+        let encounter: ReviewedEncounter = serde_json::from_str(&payload).unwrap();
+        let committed = &encounter.line_items[0];
+        assert_eq!(committed.resolution_method, ResolutionMethod::ManualEntry);
+        assert_eq!(
+            committed.escalation_approval.as_ref().unwrap().approved_by,
+            "lead"
+        );
+
+        // Without a draft the approval comes with the line item
+        let encounter = |approval: Option<FfiEscalationApproval>| FfiReviewedEncounter {
+            draft_id: "no-draft".into(),
+            patient_id: patient.local_id.clone(),
+            patient_server_id: None,
+            transcript: String::new(),
+            line_items: vec![FfiLineItem {
+                escalation_approval: approval,
+                ..line.clone()
+            }],
+            reviewed_by: "lead".into(),
+            notes: None,
+        };
+        let approval = |approved_by: &str| FfiEscalationApproval {
+            approved_by: approved_by.into(),
+            role: "dvm-lead".into(),
+            reason: "Post-op pain".into(),
+            approved_at: String::new(),
+        };
+        assert!(matches!(
+            core.commit_encounter(encounter(None)),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            core.commit_encounter(encounter(Some(approval("tech")))),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        core.commit_encounter(encounter(Some(approval("lead"))))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_structured_errors() {
+        let core = open_database_in_memory().unwrap();
+        let item = CatalogItem::new("FENT-50".into(), "Fentanyl 50mcg/mL".into());
+        locked_db(&core).upsert_catalog_item(&item).unwrap();
+        core.upsert_escalation_rule("fentanyl".into(), "dvm-lead".into(), "Opioid".into())
+            .unwrap();
+
+        let result = core.resolve_mention("zzqx".into(), None, None, None, None, None, None, None);
+        assert!(matches!(
+            result,
+            Err(FuzzyDrugsError::NoCandidates { drug_name }) if drug_name == "zzqx"
+        ));
+
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+        let mut draft = EncounterDraft::new(patient.local_id.clone());
+        {
+            let db = locked_db(&core);
+            let mention = FuzzyDrugsCore::ffi_mention("fentanyl".into(), None, None, None, None);
+            let resolved = Resolver::new(&db)
+                .resolve(&mention, Some("canine"), None, None)
+                .unwrap();
+            draft.resolved_items.push(resolved);
+            db.insert_draft(&draft).unwrap();
+        }
+        let mut pat = models::User::new("pat".into(), "Pat".into());
+        pat.roles = vec!["technician".into()];
+        locked_db(&core).upsert_user(&pat).unwrap();
+        let result = core.approve_escalated_item(
+            draft.draft_id.clone(),
+            0,
+            "Pat".into(),
+            "technician".into(),
+            "Post-op pain".into(),
+        );
+        assert!(matches!(
+            result,
+            Err(FuzzyDrugsError::PermissionDenied { required_role, .. })
+                if required_role == "dvm-lead"
+        ));
+
+        core.place_legal_hold(
+            "patient".into(),
+            patient.local_id.clone(),
+            "Litigation".into(),
+            "Admin".into(),
+        )
+        .unwrap();
+        let result = core.place_legal_hold(
+            "patient".into(),
+            patient.local_id.clone(),
+            "Litigation".into(),
+            "Admin".into(),
+        );
+        match result {
+            Err(FuzzyDrugsError::Conflict {
+                subject_type,
+                subject_id,
+                reason,
+                ..
+            }) => {
+                assert_eq!(subject_type, "patient");
+                assert_eq!(subject_id, patient.local_id);
+                assert_eq!(reason, "already_held");
+            }
+            other => panic!("expected conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_open_database_with_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clinic.db").to_string_lossy().into_owned();
+
+        let mut scoring: FfiScoringConfig = models::ScoringConfig::default().into();
+        scoring.fts_candidate_limit = 50;
+        let core = open_database_with_options(
+            path.clone(),
+            FfiCoreOptions {
+                scoring: Some(scoring),
+                review_queue_order: Some("oldest".into()),
+                locale: Some("es-MX".into()),
+                system_id: Some("clinic-7".into()),
+                sync_conflict_strategy: Some("manual".into()),
+            },
+        )
+        .unwrap();
+        assert_eq!(core.get_capabilities().unwrap().normalizer_locale, "es");
+        let batch: serde_json::Value =
+            serde_json::from_str(&core.export_compliance_json().unwrap()).unwrap();
+        assert_eq!(batch["metadata"]["system_id"], "clinic-7");
+        drop(core);
+
+        // Stored settings survive a plain reopen
+        let core = open_database(path.clone()).unwrap();
+        let options = core.get_core_options().unwrap();
+        assert_eq!(options.scoring.unwrap().fts_candidate_limit, 50);
+        assert_eq!(options.review_queue_order.as_deref(), Some("oldest_first"));
+        assert_eq!(options.locale.as_deref(), Some("es"));
+        assert_eq!(options.system_id.as_deref(), Some("clinic-7"));
+        assert_eq!(options.sync_conflict_strategy.as_deref(), Some("manual"));
+        assert_eq!(core.get_capabilities().unwrap().normalizer_locale, "es");
+        drop(core);
+
+        // An invalid option stores nothing
+        let invalid = FfiCoreOptions {
+            scoring: None,
+            review_queue_order: Some("newest_first".into()),
+            locale: Some("klingon".into()),
+            system_id: None,
+            sync_conflict_strategy: None,
+        };
+        assert!(matches!(
+            open_database_with_options(path.clone(), invalid),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+        let core = open_database(path).unwrap();
+        let options = core.get_core_options().unwrap();
+        assert_eq!(options.review_queue_order.as_deref(), Some("oldest_first"));
+    }
+
+    #[derive(Default)]
+    struct RecordingLogSink(Mutex<Vec<(String, String, String)>>);
+
+    impl FfiLogSink for RecordingLogSink {
+        fn log(&self, level: String, target: String, message: String) {
+            self.0.lock().unwrap().push((level, target, message));
+        }
+    }
+
+    #[test]
+    fn test_log_sink() {
+        let core = open_database_in_memory().unwrap();
+        core.upsert_catalog_item(
+            CatalogItem::new("CARP-100".into(), "Carprofen 100mg tablets".into()).into(),
+        )
+        .unwrap();
+        let patient = core.create_patient("Max".into(), "canine".into()).unwrap();
+
+        assert!(matches!(
+            set_log_sink(Arc::new(RecordingLogSink::default()), "loud".into(), vec![]),
+            Err(FuzzyDrugsError::InvalidInput(_))
+        ));
+
+        let sink = Arc::new(RecordingLogSink::default());
+        set_log_sink(
+            sink.clone(),
+            "warn".into(),
+            vec![FfiModuleLevel {
+                module: "resolver".into(),
+                level: "trace".into(),
+            }],
+        )
+        .unwrap();
+        let mention = FfiDrugMention {
+            raw_text: "carprofen".into(),
+            drug_name: "carprofen".into(),
+            dose: None,
+            unit: None,
+            route: None,
+            species: None,
+            start_offset: 0,
+            end_offset: 0,
+        };
+        core.resolve_mentions_for_patient(patient.local_id, vec![mention], None, None)
+            .unwrap();
+        clear_log_sink();
+
+        // Other tests may log concurrently; look only at this resolution
+        let records = sink.0.lock().unwrap();
+        let carprofen: Vec<_> = records
+            .iter()
+            .filter(|(_, _, message)| message.contains("CARP-100"))
+            .collect();
+        assert!(carprofen
+            .iter()
+            .any(|(level, target, message)| level == "trace"
+                && target == "fuzzy_drugs_core::resolver::disambiguator"
+                && message.starts_with("Scored candidate sku=CARP-100")));
+        assert!(carprofen
+            .iter()
+            .any(|(level, _, message)| level == "debug" && message.starts_with("Top candidate")));
+        assert!(records.iter().all(|(level, _, _)| level != "info"));
     }
 }
@@ -0,0 +1,132 @@
+//! Size and sanity limits for transcripts and committed payloads.
+//!
+//! A runaway recording can produce a multi-megabyte transcript. These limits
+//! keep drafts, Merkle leaves, and NER input within reasonable bounds:
+//! - Transcripts above `transcript_inline_bytes` are compressed and stored in chunks
+//! - Transcripts above `max_transcript_bytes` are rejected outright
+//! - Leaf payloads above `max_leaf_payload_bytes` are rejected at commit time
+
+use serde::{Deserialize, Serialize};
+
+/// Configurable size limits.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Limits {
+    /// Hard cap on transcript size (bytes)
+    pub max_transcript_bytes: usize,
+    /// Transcripts larger than this are compressed and stored in chunks (bytes)
+    pub transcript_inline_bytes: usize,
+    /// Size of each stored transcript chunk (bytes, after compression)
+    pub transcript_chunk_bytes: usize,
+    /// Hard cap on a serialized Merkle leaf payload (bytes)
+    pub max_leaf_payload_bytes: usize,
+    /// Maximum line items in a single reviewed encounter
+    pub max_line_items: usize,
+    /// Maximum length of a single drug name (characters)
+    pub max_drug_name_chars: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_transcript_bytes: 4 * 1024 * 1024,
+            transcript_inline_bytes: 64 * 1024,
+            transcript_chunk_bytes: 64 * 1024,
+            max_leaf_payload_bytes: 1024 * 1024,
+            max_line_items: 200,
+            max_drug_name_chars: 256,
+        }
+    }
+}
+
+impl Limits {
+    /// Check a transcript against the hard size cap.
+    pub fn check_transcript(&self, transcript: &str) -> Result<(), String> {
+        if transcript.len() > self.max_transcript_bytes {
+            return Err(format!(
+                "transcript is {} bytes (max {})",
+                transcript.len(),
+                self.max_transcript_bytes
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether a transcript should be compressed and chunked rather than stored inline.
+    pub fn should_chunk_transcript(&self, transcript: &str) -> bool {
+        transcript.len() > self.transcript_inline_bytes
+    }
+
+    /// Check a serialized leaf payload and its line item count.
+    pub fn check_leaf_payload(&self, payload: &str, line_items: usize) -> Result<(), String> {
+        if payload.len() > self.max_leaf_payload_bytes {
+            return Err(format!(
+                "leaf payload is {} bytes (max {})",
+                payload.len(),
+                self.max_leaf_payload_bytes
+            ));
+        }
+        if line_items > self.max_line_items {
+            return Err(format!(
+                "encounter has {} line items (max {})",
+                line_items, self.max_line_items
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check a drug name before it is sent through resolution.
+    pub fn check_drug_name(&self, drug_name: &str) -> Result<(), String> {
+        let chars = drug_name.chars().count();
+        if chars > self.max_drug_name_chars {
+            return Err(format!(
+                "drug name is {} characters (max {})",
+                chars, self.max_drug_name_chars
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_limits() {
+        let limits = Limits {
+            max_transcript_bytes: 100,
+            transcript_inline_bytes: 10,
+            ..Limits::default()
+        };
+
+        assert!(limits.check_transcript("short").is_ok());
+        assert!(!limits.should_chunk_transcript("short"));
+
+        let medium = "x".repeat(50);
+        assert!(limits.check_transcript(&medium).is_ok());
+        assert!(limits.should_chunk_transcript(&medium));
+
+        let huge = "x".repeat(101);
+        assert!(limits.check_transcript(&huge).is_err());
+    }
+
+    #[test]
+    fn test_leaf_payload_limits() {
+        let limits = Limits {
+            max_leaf_payload_bytes: 20,
+            max_line_items: 2,
+            ..Limits::default()
+        };
+
+        assert!(limits.check_leaf_payload("{}", 1).is_ok());
+        assert!(limits.check_leaf_payload(&"x".repeat(21), 1).is_err());
+        assert!(limits.check_leaf_payload("{}", 3).is_err());
+    }
+
+    #[test]
+    fn test_drug_name_limit() {
+        let limits = Limits::default();
+        assert!(limits.check_drug_name("carprofen").is_ok());
+        assert!(limits.check_drug_name(&"a".repeat(257)).is_err());
+    }
+}
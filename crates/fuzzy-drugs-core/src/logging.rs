@@ -0,0 +1,247 @@
+//! Diagnostics logging for the host app.
+//!
+//! The core emits [`tracing`] events (resolution scores, pipeline status
+//! changes, commits, recoveries). A [`LogSink`] installed with [`set_sink`]
+//! receives the events that pass its per-module [`Targets`] filter, so the
+//! host app can show them or attach them to support tickets. Messages never
+//! include transcript text.
+
+use std::fmt::{self, Write as _};
+use std::sync::{Arc, Once, RwLock};
+
+use tracing::field::{Field, Visit};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::{Layer, Registry};
+
+/// Crate prefix added to short module names ("resolver").
+const CRATE_TARGET: &str = "fuzzy_drugs_core";
+
+/// Receives log records that pass the filter.
+pub trait LogSink: Send + Sync {
+    fn log(&self, level: Level, target: &str, message: &str);
+}
+
+/// Forwards events that pass `filter` to `sink`.
+pub struct SinkLayer {
+    sink: Arc<dyn LogSink>,
+    filter: Targets,
+}
+
+impl SinkLayer {
+    pub fn new(sink: Arc<dyn LogSink>, filter: Targets) -> Self {
+        Self { sink, filter }
+    }
+
+    fn would_log(&self, metadata: &Metadata<'_>) -> bool {
+        self.filter
+            .would_enable(metadata.target(), metadata.level())
+    }
+
+    fn log_event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        if !self.would_log(metadata) {
+            return;
+        }
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        self.sink
+            .log(*metadata.level(), metadata.target(), &message.finish());
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SinkLayer {
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        self.would_log(metadata)
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        self.log_event(event);
+    }
+}
+
+/// Renders the `message` field followed by `key=value` for other fields.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(self) -> String {
+        if self.message.is_empty() {
+            self.fields.trim_start().to_string()
+        } else {
+            self.message + &self.fields
+        }
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Parse a level name ("error", "warn", "info", "debug", "trace", "off").
+pub fn parse_level(s: &str) -> Option<LevelFilter> {
+    match s.trim().to_lowercase().as_str() {
+        "off" => Some(LevelFilter::OFF),
+        "error" => Some(LevelFilter::ERROR),
+        "warn" | "warning" => Some(LevelFilter::WARN),
+        "info" => Some(LevelFilter::INFO),
+        "debug" => Some(LevelFilter::DEBUG),
+        "trace" => Some(LevelFilter::TRACE),
+        _ => None,
+    }
+}
+
+/// Lowercase level name, as passed to the host app.
+pub fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::ERROR => "error",
+        Level::WARN => "warn",
+        Level::INFO => "info",
+        Level::DEBUG => "debug",
+        Level::TRACE => "trace",
+    }
+}
+
+/// Build a filter logging `default` and above, with per-module overrides.
+///
+/// Modules are targets such as "fuzzy_drugs_core::resolver"; a short name
+/// ("resolver", "merkle::sync") is taken as a module of this crate. A module
+/// filter also applies to its submodules.
+pub fn filter(default: LevelFilter, modules: &[(String, LevelFilter)]) -> Targets {
+    modules.iter().fold(
+        Targets::new().with_default(default),
+        |targets, (module, level)| {
+            let module = module.trim();
+            if module.starts_with(CRATE_TARGET) {
+                targets.with_target(module.to_string(), *level)
+            } else {
+                targets.with_target(format!("{}::{}", CRATE_TARGET, module), *level)
+            }
+        },
+    )
+}
+
+/// The installed sink; read on every event.
+static SINK: RwLock<Option<Arc<SinkLayer>>> = RwLock::new(None);
+static INSTALL: Once = Once::new();
+
+/// Global subscriber layer that forwards to the installed sink, if any.
+struct GlobalLayer;
+
+impl GlobalLayer {
+    fn current() -> Option<Arc<SinkLayer>> {
+        SINK.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for GlobalLayer {
+    /// The sink and filter can change at any time, so nothing is cached.
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        Self::current().is_some_and(|layer| layer.would_log(metadata))
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // Called outside the lock, so a sink may replace itself
+        if let Some(layer) = Self::current() {
+            layer.log_event(event);
+        }
+    }
+}
+
+/// Route core events to `sink`, replacing any previous sink.
+///
+/// Installs the process-wide subscriber on first use; if the embedding app
+/// already installed one, events go to that subscriber instead.
+pub fn set_sink(sink: Arc<dyn LogSink>, filter: Targets) {
+    INSTALL.call_once(|| {
+        let _ = tracing::subscriber::set_global_default(Registry::default().with(GlobalLayer));
+    });
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(SinkLayer::new(sink, filter)));
+}
+
+/// Stop forwarding events.
+pub fn clear_sink() {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<(Level, String, String)>>);
+
+    impl LogSink for RecordingSink {
+        fn log(&self, level: Level, target: &str, message: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((level, target.to_string(), message.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_sink_layer_filters_by_module() {
+        let sink = Arc::new(RecordingSink::default());
+        let targets = filter(
+            LevelFilter::WARN,
+            &[("resolver".to_string(), LevelFilter::DEBUG)],
+        );
+        let subscriber = Registry::default().with(SinkLayer::new(sink.clone(), targets));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!(target: "fuzzy_drugs_core::resolver::disambiguator", sku = "CARP-100", "Top candidate");
+            tracing::debug!(target: "fuzzy_drugs_core::merkle", "Dropped");
+            tracing::warn!(target: "fuzzy_drugs_core::merkle", "Kept");
+        });
+
+        let records = sink.0.lock().unwrap();
+        assert_eq!(
+            *records,
+            vec![
+                (
+                    Level::DEBUG,
+                    "fuzzy_drugs_core::resolver::disambiguator".to_string(),
+                    "Top candidate sku=CARP-100".to_string()
+                ),
+                (
+                    Level::WARN,
+                    "fuzzy_drugs_core::merkle".to_string(),
+                    "Kept".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level(" Warning "), Some(LevelFilter::WARN));
+        assert_eq!(parse_level("off"), Some(LevelFilter::OFF));
+        assert_eq!(parse_level("loud"), None);
+        assert_eq!(level_name(Level::INFO), "info");
+    }
+}
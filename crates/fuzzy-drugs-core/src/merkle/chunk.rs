@@ -0,0 +1,300 @@
+//! Chunked, compressed sync payloads.
+//!
+//! A large [`SyncPayload`] is split into [`SyncChunk`]s of at most N nodes,
+//! each compressed with a [`ChunkEncoding`] both sides support and sent on
+//! its own. The PIMS answers every chunk with a [`SyncChunkAck`] carrying a
+//! continuation token and the next chunk it wants; the last one carries the
+//! usual [`SyncAck`]. A [`ChunkProgress`] recorded after each chunk lets an
+//! interrupted upload resume where it stopped, as long as it's still the
+//! same payload (same expected root and nodes).
+
+use std::io::{Read, Write};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{MerkleError, MerkleResult, SyncAck, SyncNode, SyncPayload};
+
+/// Default number of nodes per chunk.
+pub const DEFAULT_SYNC_CHUNK_SIZE: usize = 500;
+
+/// Compression applied to a chunk's nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkEncoding {
+    /// Plain JSON
+    Identity,
+    /// Gzipped JSON
+    Gzip,
+    /// Zstandard-compressed JSON (`zstd` feature)
+    Zstd,
+}
+
+impl ChunkEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkEncoding::Identity => "identity",
+            ChunkEncoding::Gzip => "gzip",
+            ChunkEncoding::Zstd => "zstd",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "identity" => Some(ChunkEncoding::Identity),
+            "gzip" => Some(ChunkEncoding::Gzip),
+            "zstd" => Some(ChunkEncoding::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Compressed encodings this build supports, best first. These are also
+    /// advertised as protocol features.
+    pub fn supported() -> Vec<ChunkEncoding> {
+        let mut encodings = vec![];
+        if cfg!(feature = "zstd") {
+            encodings.push(ChunkEncoding::Zstd);
+        }
+        encodings.push(ChunkEncoding::Gzip);
+        encodings
+    }
+
+    /// Best encoding the negotiated `features` allow.
+    pub fn preferred(features: &[String]) -> ChunkEncoding {
+        Self::supported()
+            .into_iter()
+            .find(|encoding| features.iter().any(|f| f == encoding.as_str()))
+            .unwrap_or(ChunkEncoding::Identity)
+    }
+
+    fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            ChunkEncoding::Identity => Ok(data.to_vec()),
+            ChunkEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            ChunkEncoding::Zstd => zstd::encode_all(data, 0),
+            #[cfg(not(feature = "zstd"))]
+            ChunkEncoding::Zstd => Err(unsupported()),
+        }
+    }
+
+    fn decompress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            ChunkEncoding::Identity => Ok(data.to_vec()),
+            ChunkEncoding::Gzip => {
+                let mut decoded = Vec::new();
+                GzDecoder::new(data).read_to_end(&mut decoded)?;
+                Ok(decoded)
+            }
+            #[cfg(feature = "zstd")]
+            ChunkEncoding::Zstd => zstd::decode_all(data),
+            #[cfg(not(feature = "zstd"))]
+            ChunkEncoding::Zstd => Err(unsupported()),
+        }
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn unsupported() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "zstd support not compiled in (enable the `zstd` feature)",
+    )
+}
+
+/// One piece of a chunked [`SyncPayload`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncChunk {
+    /// Root the whole payload should produce; identifies the upload
+    pub expected_root: String,
+    /// Position of this chunk (from 0)
+    pub index: u32,
+    /// Number of chunks in the payload
+    pub total: u32,
+    /// Token from the previous [`SyncChunkAck`]; `None` starts an upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub continuation: Option<String>,
+    pub encoding: ChunkEncoding,
+    /// Base64 of the encoded JSON array of [`SyncNode`]s
+    pub data: String,
+    /// Mirrors [`SyncPayload::rebase`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rebase: bool,
+}
+
+impl SyncChunk {
+    /// Decode the chunk's nodes.
+    pub fn nodes(&self) -> MerkleResult<Vec<SyncNode>> {
+        let compressed = BASE64
+            .decode(&self.data)
+            .map_err(|e| MerkleError::InvalidChunk(e.to_string()))?;
+        let json = self
+            .encoding
+            .decompress(&compressed)
+            .map_err(|e| MerkleError::InvalidChunk(e.to_string()))?;
+        serde_json::from_slice(&json).map_err(|e| MerkleError::InvalidChunk(e.to_string()))
+    }
+}
+
+/// PIMS reply to a [`SyncChunk`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncChunkAck {
+    /// Token to send with the next chunk
+    pub continuation: String,
+    /// Chunk the PIMS wants next (0 if it lost the upload and needs it all)
+    pub next_index: u32,
+    /// Set once the last chunk is in and the payload applied
+    #[serde(default)]
+    pub complete: Option<SyncAck>,
+}
+
+/// Where an interrupted chunked upload stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkProgress {
+    pub expected_root: String,
+    /// Digest of the payload's node hashes, so a changed payload restarts
+    pub payload_digest: String,
+    pub chunk_size: usize,
+    pub next_index: u32,
+    pub continuation: String,
+}
+
+impl ChunkProgress {
+    /// Whether this progress belongs to `payload` chunked by `chunk_size`.
+    pub fn matches(&self, payload: &SyncPayload, chunk_size: usize) -> bool {
+        self.expected_root == payload.expected_root
+            && self.chunk_size == chunk_size
+            && self.payload_digest == payload.digest()
+    }
+}
+
+impl SyncPayload {
+    /// Number of chunks of `chunk_size` nodes.
+    pub fn chunk_count(&self, chunk_size: usize) -> u32 {
+        self.nodes.len().div_ceil(chunk_size.max(1)) as u32
+    }
+
+    /// Build chunk `index` of `chunk_size` nodes.
+    pub fn chunk(
+        &self,
+        index: u32,
+        chunk_size: usize,
+        encoding: ChunkEncoding,
+        continuation: Option<String>,
+    ) -> MerkleResult<SyncChunk> {
+        let chunk_size = chunk_size.max(1);
+        let start = (index as usize * chunk_size).min(self.nodes.len());
+        let end = (start + chunk_size).min(self.nodes.len());
+        let json = serde_json::to_vec(&self.nodes[start..end])?;
+        let encoded = encoding
+            .compress(&json)
+            .map_err(|e| MerkleError::InvalidChunk(e.to_string()))?;
+        Ok(SyncChunk {
+            expected_root: self.expected_root.clone(),
+            index,
+            total: self.chunk_count(chunk_size),
+            continuation,
+            encoding,
+            data: BASE64.encode(encoded),
+            rebase: self.rebase,
+        })
+    }
+
+    /// Digest of the node hashes, in order.
+    pub fn digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        for node in &self.nodes {
+            hasher.update(node.hash.as_bytes());
+            hasher.update(b"\n");
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_payload(count: usize) -> SyncPayload {
+        SyncPayload {
+            nodes: (0..count)
+                .map(|i| SyncNode {
+                    hash: format!("hash-{i}"),
+                    node_type: "leaf".to_string(),
+                    left_child: None,
+                    right_child: None,
+                    payload: Some(format!(r#"{{"draft_id":"draft-{i}"}}"#)),
+                })
+                .collect(),
+            expected_root: "root".to_string(),
+            rebase: false,
+        }
+    }
+
+    #[test]
+    fn test_chunks_round_trip() {
+        let payload = make_payload(5);
+        assert_eq!(payload.chunk_count(2), 3);
+        for encoding in [ChunkEncoding::Identity]
+            .into_iter()
+            .chain(ChunkEncoding::supported())
+        {
+            let hashes: Vec<String> = (0..3)
+                .flat_map(|i| {
+                    payload
+                        .chunk(i, 2, encoding, None)
+                        .unwrap()
+                        .nodes()
+                        .unwrap()
+                })
+                .map(|node| node.hash)
+                .collect();
+            let expected: Vec<String> = payload.nodes.iter().map(|n| n.hash.clone()).collect();
+            assert_eq!(hashes, expected);
+        }
+        let last = payload.chunk(2, 2, ChunkEncoding::Gzip, None).unwrap();
+        assert_eq!((last.index, last.total), (2, 3));
+
+        let mut corrupt = last;
+        corrupt.data = "not base64!".to_string();
+        assert!(matches!(corrupt.nodes(), Err(MerkleError::InvalidChunk(_))));
+    }
+
+    #[test]
+    fn test_preferred_encoding() {
+        assert_eq!(ChunkEncoding::preferred(&[]), ChunkEncoding::Identity);
+        assert_eq!(
+            ChunkEncoding::preferred(&["gzip".to_string(), "zstd".to_string()]),
+            if cfg!(feature = "zstd") {
+                ChunkEncoding::Zstd
+            } else {
+                ChunkEncoding::Gzip
+            }
+        );
+        assert_eq!(ChunkEncoding::parse("GZIP"), Some(ChunkEncoding::Gzip));
+    }
+
+    #[test]
+    fn test_progress_matches_same_payload_only() {
+        let payload = make_payload(4);
+        let progress = ChunkProgress {
+            expected_root: "root".to_string(),
+            payload_digest: payload.digest(),
+            chunk_size: 2,
+            next_index: 1,
+            continuation: "token".to_string(),
+        };
+        assert!(progress.matches(&payload, 2));
+        assert!(!progress.matches(&payload, 3));
+        assert!(!progress.matches(&make_payload(3), 2));
+    }
+}
@@ -0,0 +1,122 @@
+//! HTTP sync transport (`http` feature): JSON POSTs to the PIMS with a
+//! blocking reqwest client.
+
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::models::Patient;
+
+use super::{
+    PatientDelta, PatientSyncRequest, PatientUpsertAck, SyncAck, SyncCapabilities, SyncChunk,
+    SyncChunkAck, SyncPayload, SyncRequest, SyncResponse, SyncTransport, TransportError,
+    CAPABILITIES_PATH, PATIENT_PULL_PATH, PATIENT_UPSERT_PATH, SYNC_CHUNK_PATH, SYNC_PAYLOAD_PATH,
+    SYNC_REQUEST_PATH,
+};
+
+/// Default request timeout.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl From<reqwest::Error> for TransportError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            TransportError::InvalidResponse(e.to_string())
+        } else {
+            TransportError::Connection(e.to_string())
+        }
+    }
+}
+
+/// Sync transport that POSTs JSON to `{base_url}/sync/capabilities`,
+/// `{base_url}/sync/request`, `{base_url}/sync/payload`,
+/// `{base_url}/sync/payload/chunk`, `{base_url}/sync/patients`, and
+/// `{base_url}/sync/patients/changes`.
+pub struct HttpSyncTransport {
+    client: reqwest::blocking::Client,
+    base_url: String,
+    auth_token: Option<String>,
+}
+
+impl HttpSyncTransport {
+    /// Create a transport for the PIMS at `base_url` with the default
+    /// 30-second timeout.
+    pub fn new(base_url: impl Into<String>) -> Result<Self, TransportError> {
+        Self::with_timeout(base_url, DEFAULT_TIMEOUT)
+    }
+
+    /// Create a transport whose requests time out after `timeout`.
+    pub fn with_timeout(
+        base_url: impl Into<String>,
+        timeout: Duration,
+    ) -> Result<Self, TransportError> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .build()?;
+        Ok(Self {
+            client,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            auth_token: None,
+        })
+    }
+
+    /// Send `token` as a bearer token with every request.
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// POST `body` as JSON to `path` and parse the JSON reply.
+    fn post<B: Serialize, R: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<R, TransportError> {
+        let mut request = self
+            .client
+            .post(format!("{}/{}", self.base_url, path))
+            .json(body);
+        if let Some(token) = &self.auth_token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send()?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(TransportError::Status {
+                status: status.as_u16(),
+                body: response.text().unwrap_or_default(),
+            });
+        }
+        Ok(response.json()?)
+    }
+}
+
+impl SyncTransport for HttpSyncTransport {
+    fn capabilities(&self, local: &SyncCapabilities) -> Result<SyncCapabilities, TransportError> {
+        match self.post(CAPABILITIES_PATH, local) {
+            // Servers from before the handshake don't have the endpoint
+            Err(TransportError::Status { status: 404, .. }) => Ok(SyncCapabilities::legacy()),
+            result => result,
+        }
+    }
+
+    fn send_request(&self, request: &SyncRequest) -> Result<SyncResponse, TransportError> {
+        self.post(SYNC_REQUEST_PATH, request)
+    }
+
+    fn send_payload(&self, payload: &SyncPayload) -> Result<SyncAck, TransportError> {
+        self.post(SYNC_PAYLOAD_PATH, payload)
+    }
+
+    fn send_chunk(&self, chunk: &SyncChunk) -> Result<SyncChunkAck, TransportError> {
+        self.post(SYNC_CHUNK_PATH, chunk)
+    }
+
+    fn upsert_patient(&self, patient: &Patient) -> Result<PatientUpsertAck, TransportError> {
+        self.post(PATIENT_UPSERT_PATH, patient)
+    }
+
+    fn pull_patients(&self, request: &PatientSyncRequest) -> Result<PatientDelta, TransportError> {
+        self.post(PATIENT_PULL_PATH, request)
+    }
+}
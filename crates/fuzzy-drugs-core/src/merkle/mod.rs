@@ -1,9 +1,19 @@
 //! Merkle tree implementation for tamper-evident audit log.
 
-mod tree;
+mod chunk;
+#[cfg(feature = "http")]
+mod http;
 mod proof;
+mod server;
 mod sync;
+mod transport;
+mod tree;
 
-pub use tree::*;
+pub use chunk::*;
+#[cfg(feature = "http")]
+pub use http::*;
 pub use proof::*;
+pub use server::*;
 pub use sync::*;
+pub use transport::*;
+pub use tree::*;
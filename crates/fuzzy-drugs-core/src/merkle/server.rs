@@ -0,0 +1,545 @@
+//! PIMS side of the encounter sync protocol (library mode).
+//!
+//! A [`SyncServer`] keeps a mirror of one device's tree in its own
+//! [`Database`] and answers that device's [`SyncEngine`](super::SyncEngine):
+//! it reports which nodes it's missing, verifies every node it's sent
+//! against its hash, and only adopts a new root once the whole tree under it
+//! is present and extends the mirrored tree. A PIMS links this crate and
+//! routes its sync endpoints to the `handle_*` methods.
+//!
+//! The server learns child hashes from the nodes it receives, so it walks a
+//! new tree a level at a time: nodes are staged (`merkle_staging`) and the
+//! acknowledgment asks for the children it still lacks
+//! ([`SyncAck::missing_hashes`]) until the tree is complete.
+
+use std::collections::HashSet;
+
+use crate::db::{Database, MerkleNode, MerkleNodeType};
+
+use super::{
+    hash_data, ChunkEncoding, MerkleResult, MerkleTree, SyncAck, SyncCapabilities, SyncChunk,
+    SyncChunkAck, SyncNode, SyncPayload, SyncRequest, SyncResponse, FEATURE_CHUNKED_PAYLOAD,
+    FEATURE_REBASE, MIN_SYNC_PROTOCOL_VERSION, SYNC_PROTOCOL_VERSION,
+};
+
+/// Receiving end of the sync protocol, mirroring a device's tree.
+pub struct SyncServer<'a> {
+    db: &'a Database,
+}
+
+/// Where a walk of the tree under a root ended up.
+enum Walk {
+    /// Every node is present; these staged leaves are new, in tree order
+    Complete(Vec<MerkleNode>),
+    /// These nodes are still needed
+    Missing(Vec<String>),
+}
+
+impl<'a> SyncServer<'a> {
+    /// Create a server mirroring into `db`.
+    pub fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    /// What this server speaks, for the capabilities handshake.
+    pub fn capabilities() -> SyncCapabilities {
+        SyncCapabilities {
+            protocol_version: SYNC_PROTOCOL_VERSION,
+            min_protocol_version: MIN_SYNC_PROTOCOL_VERSION,
+            features: [FEATURE_REBASE, FEATURE_CHUNKED_PAYLOAD]
+                .into_iter()
+                .map(String::from)
+                .chain(
+                    ChunkEncoding::supported()
+                        .iter()
+                        .map(|e| e.as_str().to_string()),
+                )
+                .collect(),
+        }
+    }
+
+    /// Root of the mirrored tree.
+    pub fn root(&self) -> MerkleResult<Option<String>> {
+        Ok(self.db.get_merkle_root()?.root_hash)
+    }
+
+    /// Answer a [`SyncRequest`]: the nodes needed to verify the device's
+    /// root (nothing if mirrored, the root itself if unknown, or the rest of
+    /// a partly received tree) and the mirrored root.
+    pub fn handle_request(&self, request: &SyncRequest) -> MerkleResult<SyncResponse> {
+        let missing_hashes = match self.walk(&request.root_hash)? {
+            Walk::Complete(_) => vec![],
+            Walk::Missing(hashes) => hashes,
+        };
+        Ok(SyncResponse {
+            missing_hashes,
+            server_root_hash: self.root()?,
+        })
+    }
+
+    /// Ingest a [`SyncPayload`]. Nodes that don't match their hash, or a
+    /// tree that doesn't extend the mirror, are rejected with
+    /// `success: false` and leave the mirror unchanged.
+    pub fn handle_payload(&self, payload: &SyncPayload) -> MerkleResult<SyncAck> {
+        if payload.rebase {
+            return self.append_leaves(&payload.nodes);
+        }
+        if let Err(error) = self.stage(&payload.expected_root, &payload.nodes) {
+            return Ok(reject(error));
+        }
+        self.finish(&payload.expected_root)
+    }
+
+    /// Ingest one [`SyncChunk`]. Chunks are staged like payloads; the last
+    /// one finishes the upload. The continuation token is the expected root,
+    /// and a chunk lost along the way just shows up as missing nodes.
+    pub fn handle_chunk(&self, chunk: &SyncChunk) -> MerkleResult<SyncChunkAck> {
+        let nodes = chunk.nodes()?;
+        let continuation = chunk.expected_root.clone();
+        if chunk.rebase {
+            // Leaves are appended in order, so hold them until the last chunk
+            let error = nodes
+                .iter()
+                .find_map(|node| verify_node(node).err())
+                .or_else(|| {
+                    (chunk.index + 1 < chunk.total)
+                        .then(|| "Chunked rebase payloads aren't supported".to_string())
+                });
+            let complete = match error {
+                Some(error) => reject(error),
+                None => self.append_leaves(&nodes)?,
+            };
+            return Ok(SyncChunkAck {
+                continuation,
+                next_index: chunk.total,
+                complete: Some(complete),
+            });
+        }
+        if let Err(error) = self.stage(&chunk.expected_root, &nodes) {
+            return Ok(SyncChunkAck {
+                continuation,
+                next_index: chunk.total,
+                complete: Some(reject(error)),
+            });
+        }
+        if chunk.index + 1 < chunk.total {
+            return Ok(SyncChunkAck {
+                continuation,
+                next_index: chunk.index + 1,
+                complete: None,
+            });
+        }
+        Ok(SyncChunkAck {
+            continuation,
+            next_index: chunk.total,
+            complete: Some(self.finish(&chunk.expected_root)?),
+        })
+    }
+
+    /// Verify and stage nodes for the tree under `expected_root`, dropping
+    /// anything staged for other roots.
+    fn stage(&self, expected_root: &str, nodes: &[SyncNode]) -> Result<(), String> {
+        let verified = nodes
+            .iter()
+            .map(verify_node)
+            .collect::<Result<Vec<_>, _>>()?;
+        let result: MerkleResult<()> = (|| {
+            let tx = self
+                .db
+                .conn()
+                .unchecked_transaction()
+                .map_err(crate::db::DbError::from)?;
+            self.db.clear_staged_merkle_nodes(Some(expected_root))?;
+            for node in &verified {
+                if !self.db.merkle_node_exists(&node.hash)? {
+                    self.db.stage_merkle_node(expected_root, node)?;
+                }
+            }
+            tx.commit().map_err(crate::db::DbError::from)?;
+            Ok(())
+        })();
+        result.map_err(|e| e.to_string())
+    }
+
+    /// Adopt the tree under `expected_root` if it's complete, or ask for
+    /// what's missing.
+    fn finish(&self, expected_root: &str) -> MerkleResult<SyncAck> {
+        let new_leaves = match self.walk(expected_root)? {
+            Walk::Complete(leaves) => leaves,
+            Walk::Missing(hashes) => {
+                tracing::debug!(missing = hashes.len(), "Sync tree incomplete");
+                return Ok(SyncAck {
+                    success: false,
+                    new_root: None,
+                    error: Some(format!("{} nodes still needed", hashes.len())),
+                    missing_hashes: hashes,
+                });
+            }
+        };
+
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .map_err(crate::db::DbError::from)?;
+        let root = self.rebuild(&new_leaves)?;
+        if root != expected_root {
+            drop(tx);
+            self.db.clear_staged_merkle_nodes(None)?;
+            tracing::warn!(expected_root, mirrored_root = %root, "Sync tree doesn't extend the mirror");
+            return Ok(reject(
+                "Tree doesn't extend the mirrored tree; rebase onto it".to_string(),
+            ));
+        }
+        self.db.clear_staged_merkle_nodes(None)?;
+        tx.commit().map_err(crate::db::DbError::from)?;
+        tracing::info!(root = %root, new_leaves = new_leaves.len(), "Mirrored tree updated");
+        Ok(accept(root))
+    }
+
+    /// Append rebased leaves (skipping any already mirrored) onto the
+    /// mirrored tree.
+    fn append_leaves(&self, nodes: &[SyncNode]) -> MerkleResult<SyncAck> {
+        let mut leaves = Vec::new();
+        for node in nodes {
+            match verify_node(node) {
+                Ok(leaf) if leaf.node_type == MerkleNodeType::Leaf => {
+                    if !self.db.merkle_node_exists(&leaf.hash)? {
+                        leaves.push(leaf);
+                    }
+                }
+                Ok(_) => return Ok(reject("Rebase payloads carry only leaves".to_string())),
+                Err(error) => return Ok(reject(error)),
+            }
+        }
+
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .map_err(crate::db::DbError::from)?;
+        let root = match (leaves.is_empty(), self.root()?) {
+            (true, Some(root)) => root,
+            _ => self.rebuild(&leaves)?,
+        };
+        tx.commit().map_err(crate::db::DbError::from)?;
+        tracing::info!(root = %root, appended = leaves.len(), "Rebased leaves appended");
+        Ok(accept(root))
+    }
+
+    /// Insert `new_leaves` after the mirrored ones and rebuild the tree the
+    /// way the device does. Returns the new root.
+    fn rebuild(&self, new_leaves: &[MerkleNode]) -> MerkleResult<String> {
+        for leaf in new_leaves {
+            self.db
+                .insert_merkle_leaf(&leaf.hash, leaf.payload.as_deref().unwrap_or_default())?;
+        }
+        let leaves = self.db.get_all_leaf_hashes()?;
+        let (root, height) = MerkleTree::new(self.db).build_tree(&leaves)?;
+        self.db
+            .update_merkle_root(&root, height, leaves.len() as u32)?;
+        Ok(root)
+    }
+
+    /// Walk the tree under `root` through staged nodes, stopping at mirrored
+    /// ones (whose subtrees are complete).
+    fn walk(&self, root: &str) -> MerkleResult<Walk> {
+        let mut missing = Vec::new();
+        let mut new_leaves = Vec::new();
+        let mut seen = HashSet::new();
+        // Depth first, left to right, so leaves come out in tree order
+        let mut stack = vec![root.to_string()];
+        while let Some(hash) = stack.pop() {
+            if !seen.insert(hash.clone()) || self.db.merkle_node_exists(&hash)? {
+                continue;
+            }
+            match self.db.get_staged_merkle_node(&hash)? {
+                None => missing.push(hash),
+                Some(node) if node.node_type == MerkleNodeType::Leaf => new_leaves.push(node),
+                Some(node) => {
+                    stack.extend(node.right_child);
+                    stack.extend(node.left_child);
+                }
+            }
+        }
+        Ok(if missing.is_empty() {
+            Walk::Complete(new_leaves)
+        } else {
+            Walk::Missing(missing)
+        })
+    }
+}
+
+/// Check a received node against its hash.
+fn verify_node(node: &SyncNode) -> Result<MerkleNode, String> {
+    let (node_type, computed) = match node.node_type.as_str() {
+        "leaf" => {
+            let payload = node
+                .payload
+                .as_deref()
+                .ok_or_else(|| format!("Leaf {} has no payload", node.hash))?;
+            if node.left_child.is_some() || node.right_child.is_some() {
+                return Err(format!("Leaf {} has children", node.hash));
+            }
+            (MerkleNodeType::Leaf, hash_data(payload.as_bytes()))
+        }
+        "internal" => {
+            let left = node
+                .left_child
+                .as_deref()
+                .ok_or_else(|| format!("Internal node {} has no left child", node.hash))?;
+            if node.payload.is_some() {
+                return Err(format!("Internal node {} has a payload", node.hash));
+            }
+            // An odd node is paired with itself
+            let right = node.right_child.as_deref().unwrap_or(left);
+            (
+                MerkleNodeType::Internal,
+                hash_data(format!("{}{}", left, right).as_bytes()),
+            )
+        }
+        other => return Err(format!("Node {} has unknown type {}", node.hash, other)),
+    };
+    if computed != node.hash {
+        return Err(format!("Node {} doesn't match its content", node.hash));
+    }
+    Ok(MerkleNode {
+        hash: node.hash.clone(),
+        node_type,
+        left_child: node.left_child.clone(),
+        right_child: node.right_child.clone(),
+        payload: node.payload.clone(),
+        created_at: String::new(),
+    })
+}
+
+fn accept(root: String) -> SyncAck {
+    SyncAck {
+        success: true,
+        new_root: Some(root),
+        error: None,
+        missing_hashes: vec![],
+    }
+}
+
+fn reject(error: String) -> SyncAck {
+    tracing::warn!(error = %error, "Sync payload rejected");
+    SyncAck {
+        success: false,
+        new_root: None,
+        error: Some(error),
+        missing_hashes: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::{
+        PatientDelta, PatientSyncRequest, PatientUpsertAck, SyncEngine, SyncManager, SyncTransport,
+        TransportError,
+    };
+    use crate::models::{EncounterLineItem, Patient, ResolutionMethod, ReviewedEncounter};
+
+    fn make_encounter(id: &str) -> ReviewedEncounter {
+        ReviewedEncounter {
+            draft_id: id.to_string(),
+            patient_id: "patient-1".to_string(),
+            patient_server_id: None,
+            transcript: "Test transcript".to_string(),
+            line_items: vec![EncounterLineItem {
+                sku: "SKU001".to_string(),
+                name: "Test Drug".to_string(),
+                quantity: 1.0,
+                unit: "tablet".into(),
+                route: None,
+                original_mention: "test drug".to_string(),
+                resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                controlled_schedule: None,
+                source_spans: vec![],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
+            }],
+            reviewed_by: "Dr. Smith".to_string(),
+            reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+
+    /// Transport that hands every message straight to a server.
+    struct Loopback<'a>(SyncServer<'a>);
+
+    impl SyncTransport for Loopback<'_> {
+        fn capabilities(
+            &self,
+            _local: &SyncCapabilities,
+        ) -> Result<SyncCapabilities, TransportError> {
+            Ok(SyncServer::capabilities())
+        }
+
+        fn send_request(&self, request: &SyncRequest) -> Result<SyncResponse, TransportError> {
+            self.0
+                .handle_request(request)
+                .map_err(|e| TransportError::Rejected(e.to_string()))
+        }
+
+        fn send_payload(&self, payload: &SyncPayload) -> Result<SyncAck, TransportError> {
+            self.0
+                .handle_payload(payload)
+                .map_err(|e| TransportError::Rejected(e.to_string()))
+        }
+
+        fn send_chunk(&self, chunk: &SyncChunk) -> Result<SyncChunkAck, TransportError> {
+            self.0
+                .handle_chunk(chunk)
+                .map_err(|e| TransportError::Rejected(e.to_string()))
+        }
+
+        fn upsert_patient(&self, _patient: &Patient) -> Result<PatientUpsertAck, TransportError> {
+            Err(TransportError::Connection("not served".to_string()))
+        }
+
+        fn pull_patients(
+            &self,
+            _request: &PatientSyncRequest,
+        ) -> Result<PatientDelta, TransportError> {
+            Err(TransportError::Connection("not served".to_string()))
+        }
+    }
+
+    fn commit(db: &Database, ids: &[&str]) {
+        let tree = MerkleTree::new(db);
+        for id in ids {
+            tree.commit_encounter(&make_encounter(id)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_engine_syncs_into_mirror() {
+        let device = Database::open_in_memory().unwrap();
+        let mirror = Database::open_in_memory().unwrap();
+        let pims = Loopback(SyncServer::new(&mirror));
+        commit(
+            &device,
+            &["draft-1", "draft-2", "draft-3", "draft-4", "draft-5"],
+        );
+
+        // The whole tree arrives, a level per round
+        let report = SyncEngine::new(&device, &pims).run_sync().unwrap();
+        let root = device.get_merkle_root().unwrap().root_hash;
+        assert_eq!(report.synced_root, root);
+        assert_eq!(pims.0.root().unwrap(), root);
+        assert_eq!(
+            mirror.get_all_leaf_hashes().unwrap(),
+            device.get_all_leaf_hashes().unwrap()
+        );
+        assert_eq!(mirror.get_merkle_root().unwrap().leaf_count, 5);
+
+        // Later commits send only new nodes
+        commit(&device, &["draft-6", "draft-7"]);
+        let report = SyncEngine::new(&device, &pims).run_sync().unwrap();
+        assert!(report.nodes_sent < 10);
+        assert_eq!(
+            pims.0.root().unwrap(),
+            device.get_merkle_root().unwrap().root_hash
+        );
+        assert_eq!(
+            mirror.get_all_leaf_hashes().unwrap(),
+            device.get_all_leaf_hashes().unwrap()
+        );
+        assert!(!SyncManager::new(&device).has_unsynced_changes().unwrap());
+    }
+
+    #[test]
+    fn test_chunked_sync_into_mirror() {
+        let device = Database::open_in_memory().unwrap();
+        let mirror = Database::open_in_memory().unwrap();
+        let pims = Loopback(SyncServer::new(&mirror));
+        commit(&device, &["draft-1", "draft-2", "draft-3", "draft-4"]);
+
+        let report = SyncEngine::new(&device, &pims)
+            .with_chunk_size(1)
+            .run_sync()
+            .unwrap();
+        assert!(report.chunks_sent > 0);
+        assert_eq!(
+            pims.0.root().unwrap(),
+            device.get_merkle_root().unwrap().root_hash
+        );
+    }
+
+    #[test]
+    fn test_rejects_tampered_node() {
+        let device = Database::open_in_memory().unwrap();
+        let mirror = Database::open_in_memory().unwrap();
+        let server = SyncServer::new(&mirror);
+        commit(&device, &["draft-1"]);
+        let root = device.get_merkle_root().unwrap().root_hash.unwrap();
+        let mut node = SyncNode::from(device.get_merkle_node(&root).unwrap().unwrap());
+        node.payload = Some(node.payload.unwrap().replace("Test Drug", "Other Drug"));
+
+        let ack = server
+            .handle_payload(&SyncPayload {
+                nodes: vec![node],
+                expected_root: root.clone(),
+                rebase: false,
+            })
+            .unwrap();
+        assert!(!ack.success);
+        assert!(ack.error.unwrap().contains("doesn't match"));
+        assert_eq!(server.root().unwrap(), None);
+        assert_eq!(
+            server
+                .handle_request(&SyncRequest {
+                    root_hash: root.clone(),
+                    tree_height: 1,
+                    leaf_count: 1,
+                    protocol_version: None,
+                })
+                .unwrap()
+                .missing_hashes,
+            vec![root]
+        );
+    }
+
+    #[test]
+    fn test_rejects_rewritten_history_and_accepts_rebase() {
+        let mirror = Database::open_in_memory().unwrap();
+        let pims = Loopback(SyncServer::new(&mirror));
+        let first = Database::open_in_memory().unwrap();
+        commit(&first, &["draft-1", "draft-2"]);
+        SyncEngine::new(&first, &pims).run_sync().unwrap();
+        let mirrored = pims.0.root().unwrap();
+
+        // Another device's tree doesn't extend the mirror
+        let second = Database::open_in_memory().unwrap();
+        commit(&second, &["draft-9"]);
+        let result = SyncEngine::new(&second, &pims).run_sync();
+        assert!(matches!(
+            result,
+            Err(crate::merkle::MerkleError::Transport(
+                TransportError::Rejected(_)
+            ))
+        ));
+        assert_eq!(pims.0.root().unwrap(), mirrored);
+
+        // Rebased, its leaves are appended
+        let manager = SyncManager::new(&second);
+        let ack = pims
+            .0
+            .handle_payload(&manager.create_rebase_payload().unwrap())
+            .unwrap();
+        assert!(ack.success);
+        assert_eq!(mirror.get_merkle_root().unwrap().leaf_count, 3);
+        assert_eq!(ack.new_root, pims.0.root().unwrap());
+    }
+}
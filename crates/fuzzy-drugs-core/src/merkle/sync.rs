@@ -5,12 +5,131 @@
 //! 2. PIMS responds with list of missing node hashes
 //! 3. Local sends missing nodes
 //! 4. PIMS verifies and acknowledges new root
+//!
+//! If the PIMS reports a root other than the one it acknowledged last time
+//! (and other than ours, now or after an earlier commit), its tree has
+//! diverged; see [`SyncConflict`].
+//!
+//! Before step 1 the two sides exchange [`SyncCapabilities`] and settle on a
+//! [`NegotiatedProtocol`]. Messages are then shaped for that version (see
+//! [`SyncRequest::for_version`]) so a PIMS that predates a field never sees
+//! it. Version 1 is the original protocol; version 2 adds the version field
+//! itself, rebase payloads, and patient sync.
 
 use serde::{Deserialize, Serialize};
 
 use crate::db::{Database, MerkleNode, MerkleNodeType};
+use crate::models::{
+    CatalogOrigin, CatalogUpsertReport, ControlledSchedule, OutboxStatus, SyncConflictStrategy,
+};
+
+use super::{ChunkEncoding, ChunkProgress, MerkleError, MerkleResult, MerkleTree};
+
+/// Newest sync protocol version this build speaks.
+pub const SYNC_PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest sync protocol version this build still speaks.
+pub const MIN_SYNC_PROTOCOL_VERSION: u32 = 1;
 
-use super::{MerkleError, MerkleResult, MerkleTree};
+/// Optional protocol feature: rebase payloads ([`SyncPayload::rebase`]).
+pub const FEATURE_REBASE: &str = "rebase";
+
+/// Optional protocol feature: patient push and pull.
+pub const FEATURE_PATIENT_SYNC: &str = "patient_sync";
+
+/// Optional protocol feature: chunked payloads ([`SyncChunk`]). Chunk
+/// encodings are advertised as features too ("gzip", "zstd").
+pub const FEATURE_CHUNKED_PAYLOAD: &str = "chunked_payload";
+
+/// What one side of the sync speaks, exchanged in the handshake.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncCapabilities {
+    /// Newest protocol version supported
+    pub protocol_version: u32,
+    /// Oldest protocol version supported
+    #[serde(default = "legacy_protocol_version")]
+    pub min_protocol_version: u32,
+    /// Optional features supported (`FEATURE_*`); unknown ones are ignored
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+fn legacy_protocol_version() -> u32 {
+    1
+}
+
+impl SyncCapabilities {
+    /// This build's capabilities.
+    pub fn local() -> Self {
+        Self {
+            protocol_version: SYNC_PROTOCOL_VERSION,
+            min_protocol_version: MIN_SYNC_PROTOCOL_VERSION,
+            features: [
+                FEATURE_REBASE,
+                FEATURE_PATIENT_SYNC,
+                FEATURE_CHUNKED_PAYLOAD,
+            ]
+            .into_iter()
+            .map(String::from)
+            .chain(
+                ChunkEncoding::supported()
+                    .iter()
+                    .map(|e| e.as_str().to_string()),
+            )
+            .collect(),
+        }
+    }
+
+    /// A PIMS that predates the handshake: version 1, no features.
+    pub fn legacy() -> Self {
+        Self {
+            protocol_version: 1,
+            min_protocol_version: 1,
+            features: vec![],
+        }
+    }
+}
+
+/// Protocol both sides agreed on: the newest version both speak and the
+/// features both support.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NegotiatedProtocol {
+    pub version: u32,
+    pub features: Vec<String>,
+}
+
+impl NegotiatedProtocol {
+    /// Agree on a protocol, or describe why there is none.
+    pub fn negotiate(local: &SyncCapabilities, remote: &SyncCapabilities) -> Result<Self, String> {
+        let version = local.protocol_version.min(remote.protocol_version);
+        if version < local.min_protocol_version || version < remote.min_protocol_version {
+            return Err(format!(
+                "we speak versions {}-{}, the PIMS {}-{}",
+                local.min_protocol_version,
+                local.protocol_version,
+                remote.min_protocol_version,
+                remote.protocol_version
+            ));
+        }
+        // Every feature so far needs version 2
+        let features = if version >= 2 {
+            local
+                .features
+                .iter()
+                .filter(|f| remote.features.contains(f))
+                .cloned()
+                .collect()
+        } else {
+            vec![]
+        };
+        Ok(Self { version, features })
+    }
+
+    /// Whether both sides support `feature`.
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
 
 /// Request to initiate sync (sent to PIMS).
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +140,17 @@ pub struct SyncRequest {
     pub tree_height: u32,
     /// Local leaf count
     pub leaf_count: u32,
+    /// Negotiated protocol version; omitted for version 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u32>,
+}
+
+impl SyncRequest {
+    /// Shape the request for protocol `version`.
+    pub fn for_version(mut self, version: u32) -> Self {
+        self.protocol_version = (version >= 2).then_some(version);
+        self
+    }
 }
 
 /// Response from PIMS indicating missing nodes.
@@ -39,6 +169,20 @@ pub struct SyncPayload {
     pub nodes: Vec<SyncNode>,
     /// Expected new root after sync
     pub expected_root: String,
+    /// Rebase after a conflict: `nodes` are our leaves, to be appended onto
+    /// the PIMS tree instead of adopting `expected_root`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rebase: bool,
+}
+
+impl SyncPayload {
+    /// Check the payload can be sent under `protocol`.
+    pub fn check_protocol(&self, protocol: &NegotiatedProtocol) -> Result<(), String> {
+        if self.rebase && !protocol.supports(FEATURE_REBASE) {
+            return Err("the PIMS does not support rebase payloads".to_string());
+        }
+        Ok(())
+    }
 }
 
 /// A single node in the sync payload.
@@ -80,6 +224,75 @@ pub struct SyncAck {
     pub new_root: Option<String>,
     /// Error message if failed
     pub error: Option<String>,
+    /// Nodes the PIMS still needs before it can verify the expected root
+    /// (children of nodes just sent; version 2). Send them and wait for
+    /// the next acknowledgment.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_hashes: Vec<String>,
+}
+
+/// PIMS reply to a patient upsert.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatientUpsertAck {
+    /// PIMS ID of the patient, new or existing
+    pub server_id: String,
+}
+
+/// Snapshot of sync health for a status screen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    /// Timestamp of the last applied catalog delta (PIMS clock)
+    pub catalog_last_sync: Option<String>,
+    /// When the PIMS last acknowledged an encounter sync
+    pub encounters_last_sync: Option<String>,
+    /// Timestamp of the last applied patient delta (PIMS clock)
+    pub patients_last_sync: Option<String>,
+    /// Leaves committed since the last synced root
+    pub unsynced_leaf_count: u32,
+    /// Outbox operations awaiting (re)delivery
+    pub outbox_pending: u32,
+    /// Outbox operations out of attempts
+    pub outbox_failed: u32,
+    /// Error from the latest sync attempt, cleared when one succeeds
+    pub last_error: Option<String>,
+    pub last_error_at: Option<String>,
+    pub local_root: Option<String>,
+    /// PIMS root acknowledged by the last successful sync
+    pub server_root: Option<String>,
+    /// Whether the PIMS's root is ours (false after a rebase, since the PIMS
+    /// tree then holds leaves we don't)
+    pub server_root_matches: bool,
+    /// Whether encounter sync is blocked on a manual conflict
+    pub conflict_open: bool,
+}
+
+/// The PIMS tree diverged from the one last synced: it reported a root that
+/// is neither ours (current or earlier) nor the one it acknowledged last
+/// time (e.g. another device synced, or the PIMS was restored from backup).
+///
+/// The latest conflict is kept until the next one. Under the `Manual`
+/// strategy it stays open (`resolution` is `None`) and encounter sync stops
+/// until someone resolves it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncConflict {
+    /// Local root when the conflict was last seen
+    pub local_root: String,
+    /// PIMS root acknowledged by the last successful sync, if any
+    pub last_server_root: Option<String>,
+    /// Root the PIMS reported
+    pub server_root: String,
+    pub detected_at: String,
+    /// Clinic strategy when the conflict was detected
+    pub strategy: SyncConflictStrategy,
+    /// Strategy applied; `None` while awaiting manual resolution
+    pub resolution: Option<SyncConflictStrategy>,
+}
+
+impl SyncConflict {
+    /// Whether encounter sync is blocked on this conflict.
+    pub fn is_open(&self) -> bool {
+        self.resolution.is_none()
+    }
 }
 
 /// Full tree export for audit or cold storage.
@@ -122,6 +335,7 @@ impl<'a> SyncManager<'a> {
                 root_hash,
                 tree_height: root_state.tree_height,
                 leaf_count: root_state.leaf_count,
+                protocol_version: None,
             })),
             None => Ok(None), // Empty tree, nothing to sync
         }
@@ -139,18 +353,64 @@ impl<'a> SyncManager<'a> {
         Ok(SyncPayload {
             nodes: nodes.into_iter().map(SyncNode::from).collect(),
             expected_root,
+            rebase: false,
         })
     }
 
+    /// Create the payload for a rebase: our leaves committed since the last
+    /// sync (all of them if we never synced), oldest first, for the PIMS to
+    /// append onto its own tree.
+    pub fn create_rebase_payload(&self) -> MerkleResult<SyncPayload> {
+        let expected_root = self
+            .db
+            .get_merkle_root()?
+            .root_hash
+            .ok_or_else(|| MerkleError::InvalidState("No root hash".into()))?;
+        let since_last_sync = match self.get_last_synced_root()? {
+            Some(root) => self.db.get_nodes_after(&root)?,
+            None => None,
+        };
+        let nodes = match since_last_sync {
+            Some(nodes) => nodes,
+            None => {
+                let hashes = self.db.get_all_leaf_hashes()?;
+                let order: std::collections::HashMap<&str, usize> = hashes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, hash)| (hash.as_str(), i))
+                    .collect();
+                let mut nodes = self.db.get_nodes_by_hashes(&hashes)?;
+                nodes.sort_by_key(|node| order.get(node.hash.as_str()).copied());
+                nodes
+            }
+        };
+
+        Ok(SyncPayload {
+            nodes: nodes
+                .into_iter()
+                .filter(|node| node.node_type == MerkleNodeType::Leaf)
+                .map(SyncNode::from)
+                .collect(),
+            expected_root,
+            rebase: true,
+        })
+    }
+
+    /// Whether an earlier rebase left the PIMS tree forked from ours, so
+    /// every sync must be a rebase.
+    pub fn is_rebased(&self) -> MerkleResult<bool> {
+        Ok(self.get_last_server_root()? != self.get_last_synced_root()?)
+    }
+
     /// Handle sync acknowledgment from PIMS.
     pub fn handle_sync_ack(&self, ack: &SyncAck) -> MerkleResult<()> {
+        tracing::info!(success = ack.success, new_root = ?ack.new_root, "Sync acknowledged");
         if ack.success {
             if let Some(root) = &ack.new_root {
                 self.db.set_sync_state("last_synced_root", root)?;
-                self.db.set_sync_state(
-                    "encounters_last_sync",
-                    &chrono::Utc::now().to_rfc3339(),
-                )?;
+                self.db.set_sync_state("last_server_root", root)?;
+                self.db
+                    .set_sync_state("encounters_last_sync", &chrono::Utc::now().to_rfc3339())?;
             }
         }
         Ok(())
@@ -162,13 +422,192 @@ impl<'a> SyncManager<'a> {
         Ok(value.filter(|s| !s.is_empty()))
     }
 
+    /// PIMS root acknowledged by the last successful sync. Same as the last
+    /// synced root except after a rebase, where the PIMS root includes
+    /// leaves we don't have.
+    pub fn get_last_server_root(&self) -> MerkleResult<Option<String>> {
+        let value = self.db.get_sync_state("last_server_root")?;
+        match value.filter(|s| !s.is_empty()) {
+            Some(root) => Ok(Some(root)),
+            None => self.get_last_synced_root(),
+        }
+    }
+
+    /// Compare the root the PIMS reported against ours and the one it last
+    /// acknowledged. A root we had after an earlier commit only means the
+    /// PIMS is behind (e.g. an acknowledgment was lost), not that it
+    /// diverged. Doesn't record anything.
+    pub fn detect_divergence(
+        &self,
+        local_root: &str,
+        response: &SyncResponse,
+    ) -> MerkleResult<Option<SyncConflict>> {
+        let Some(server_root) = response.server_root_hash.as_deref() else {
+            return Ok(None);
+        };
+        let last_server_root = self.get_last_server_root()?;
+        if server_root == local_root
+            || last_server_root.as_deref() == Some(server_root)
+            || self.tree.is_earlier_root(server_root)?
+        {
+            return Ok(None);
+        }
+        let strategy = self.db.core_settings()?.sync_conflict_strategy;
+        Ok(Some(SyncConflict {
+            local_root: local_root.to_string(),
+            last_server_root,
+            server_root: server_root.to_string(),
+            detected_at: chrono::Utc::now().to_rfc3339(),
+            strategy,
+            resolution: (strategy != SyncConflictStrategy::Manual).then_some(strategy),
+        }))
+    }
+
+    /// Record a detected conflict and return it as it now stands. A conflict
+    /// already recorded for the same PIMS root keeps its detection time and
+    /// resolution (including a manual one).
+    pub fn record_conflict(&self, detected: SyncConflict) -> MerkleResult<SyncConflict> {
+        let conflict = match self.get_sync_conflict()? {
+            Some(previous) if previous.server_root == detected.server_root => SyncConflict {
+                local_root: detected.local_root,
+                ..previous
+            },
+            _ => {
+                tracing::warn!(
+                    server_root = %detected.server_root,
+                    last_server_root = ?detected.last_server_root,
+                    strategy = detected.strategy.as_str(),
+                    "PIMS tree diverged"
+                );
+                detected
+            }
+        };
+        self.db
+            .set_sync_state("sync_conflict", &serde_json::to_string(&conflict)?)?;
+        Ok(conflict)
+    }
+
+    /// The latest sync conflict, if any.
+    pub fn get_sync_conflict(&self) -> MerkleResult<Option<SyncConflict>> {
+        match self.db.get_sync_state("sync_conflict")? {
+            Some(json) if !json.is_empty() => Ok(Some(serde_json::from_str(&json)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Resolve an open conflict with `LocalWins` or `ServerRebase`; the next
+    /// sync applies it.
+    pub fn resolve_conflict(&self, strategy: SyncConflictStrategy) -> MerkleResult<SyncConflict> {
+        if strategy == SyncConflictStrategy::Manual {
+            return Err(MerkleError::InvalidState(
+                "A conflict can't be resolved manually again".into(),
+            ));
+        }
+        let mut conflict = self
+            .get_sync_conflict()?
+            .filter(SyncConflict::is_open)
+            .ok_or_else(|| MerkleError::InvalidState("No open sync conflict".into()))?;
+        conflict.resolution = Some(strategy);
+        self.db
+            .set_sync_state("sync_conflict", &serde_json::to_string(&conflict)?)?;
+        tracing::info!(strategy = strategy.as_str(), "Sync conflict resolved");
+        Ok(conflict)
+    }
+
+    /// Record our whole tree as synced after a rebase acknowledgment (whose
+    /// root is the PIMS's, not ours).
+    pub fn mark_rebased(&self, local_root: &str) -> MerkleResult<()> {
+        self.db.set_sync_state("last_synced_root", local_root)?;
+        Ok(())
+    }
+
+    /// Protocol agreed in the latest handshake with the PIMS.
+    pub fn get_server_protocol(&self) -> MerkleResult<Option<NegotiatedProtocol>> {
+        match self.db.get_sync_state("server_protocol")? {
+            Some(json) if !json.is_empty() => Ok(Some(serde_json::from_str(&json)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Record the protocol agreed in a handshake.
+    pub fn set_server_protocol(&self, protocol: &NegotiatedProtocol) -> MerkleResult<()> {
+        self.db
+            .set_sync_state("server_protocol", &serde_json::to_string(protocol)?)?;
+        Ok(())
+    }
+
+    /// Where the last interrupted chunked upload stopped.
+    pub fn get_chunk_progress(&self) -> MerkleResult<Option<ChunkProgress>> {
+        match self.db.get_sync_state("chunk_progress")? {
+            Some(json) if !json.is_empty() => Ok(Some(serde_json::from_str(&json)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Record chunked upload progress, or clear it with `None`.
+    pub fn set_chunk_progress(&self, progress: Option<&ChunkProgress>) -> MerkleResult<()> {
+        let json = match progress {
+            Some(progress) => serde_json::to_string(progress)?,
+            None => String::new(),
+        };
+        self.db.set_sync_state("chunk_progress", &json)?;
+        Ok(())
+    }
+
+    /// Record a failed sync attempt.
+    pub fn record_sync_error(&self, error: &str) -> MerkleResult<()> {
+        self.db.set_sync_state("last_sync_error", error)?;
+        self.db
+            .set_sync_state("last_sync_error_at", &chrono::Utc::now().to_rfc3339())?;
+        Ok(())
+    }
+
+    /// Clear the recorded error after a successful sync attempt.
+    pub fn clear_sync_error(&self) -> MerkleResult<()> {
+        self.db.set_sync_state("last_sync_error", "")?;
+        self.db.set_sync_state("last_sync_error_at", "")?;
+        Ok(())
+    }
+
+    /// Gather the sync status: last sync times, what's waiting, and the
+    /// latest error.
+    pub fn get_sync_status(&self) -> MerkleResult<SyncStatus> {
+        let state = |key: &str| -> MerkleResult<Option<String>> {
+            Ok(self.db.get_sync_state(key)?.filter(|s| !s.is_empty()))
+        };
+        let local_root = self.db.get_merkle_root()?.root_hash;
+        let last_synced = self.get_last_synced_root()?;
+        let unsynced_leaf_count = match &local_root {
+            Some(root) if last_synced.as_ref() == Some(root) => 0,
+            Some(_) => self.db.count_leaves_after(last_synced.as_deref())?,
+            None => 0,
+        };
+        let server_root = self.get_last_server_root()?;
+        Ok(SyncStatus {
+            catalog_last_sync: state("catalog_last_sync")?,
+            encounters_last_sync: state("encounters_last_sync")?,
+            patients_last_sync: state("patients_last_sync")?,
+            unsynced_leaf_count,
+            outbox_pending: self.db.count_outbox(OutboxStatus::Pending)?,
+            outbox_failed: self.db.count_outbox(OutboxStatus::Failed)?,
+            last_error: state("last_sync_error")?,
+            last_error_at: state("last_sync_error_at")?,
+            server_root_matches: local_root.is_some() && server_root == local_root,
+            local_root,
+            server_root,
+            conflict_open: self
+                .get_sync_conflict()?
+                .is_some_and(|conflict| conflict.is_open()),
+        })
+    }
+
     /// Check if there are unsynced changes.
     pub fn has_unsynced_changes(&self) -> MerkleResult<bool> {
         let current_root = self.db.get_merkle_root()?.root_hash;
         let last_synced = self.get_last_synced_root()?;
 
         match (current_root, last_synced) {
-            (None, _) => Ok(false), // Empty tree
+            (None, _) => Ok(false),      // Empty tree
             (Some(_), None) => Ok(true), // Never synced
             (Some(current), Some(last)) => Ok(current != last),
         }
@@ -200,15 +639,12 @@ impl<'a> SyncManager<'a> {
             .root_hash
             .ok_or_else(|| MerkleError::InvalidState("No root hash".into()))?;
 
+        // Nodes written after the old root; everything if it's unknown here
         let nodes = match since_root {
-            Some(old_root) => {
-                // Get timestamp of old root node
-                let old_node = self.db.get_merkle_node(old_root)?;
-                match old_node {
-                    Some(node) => self.db.get_nodes_since(&node.created_at)?,
-                    None => self.collect_all_nodes(&current_root)?,
-                }
-            }
+            Some(old_root) => match self.db.get_nodes_after(old_root)? {
+                Some(nodes) => nodes,
+                None => self.collect_all_nodes(&current_root)?,
+            },
             None => self.collect_all_nodes(&current_root)?,
         };
 
@@ -247,6 +683,10 @@ impl<'a> SyncManager<'a> {
 pub struct CatalogSyncRequest {
     /// Last sync timestamp (ISO 8601)
     pub since: Option<String>,
+    /// SKUs of earlier deltas that failed to apply, to send again whether or
+    /// not they changed since
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retry_skus: Vec<String>,
 }
 
 /// Catalog delta from PIMS.
@@ -272,6 +712,63 @@ pub struct CatalogSyncItem {
     pub routes: Vec<String>,
     pub active: bool,
     pub server_id: String,
+    #[serde(default)]
+    pub components: Vec<String>,
+    #[serde(default)]
+    pub controlled_schedule: Option<ControlledSchedule>,
+    #[serde(default)]
+    pub unit_price: Option<f64>,
+    #[serde(default)]
+    pub markup: Option<f64>,
+    #[serde(default)]
+    pub minimum_charge: Option<f64>,
+    #[serde(default)]
+    pub tax_code: Option<String>,
+}
+
+/// Catalog items changed on this device, for the PIMS.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogPushDelta {
+    pub items: Vec<CatalogPushItem>,
+}
+
+/// Catalog item in a push. New local items have no `server_id` yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogPushItem {
+    pub sku: String,
+    pub server_id: Option<String>,
+    pub origin: CatalogOrigin,
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub concentration: Option<String>,
+    pub package_size: Option<String>,
+    pub species: Vec<String>,
+    pub routes: Vec<String>,
+    pub active: bool,
+    pub components: Vec<String>,
+    pub controlled_schedule: Option<ControlledSchedule>,
+    pub unit_price: Option<f64>,
+    pub markup: Option<f64>,
+    pub minimum_charge: Option<f64>,
+    pub tax_code: Option<String>,
+}
+
+/// PIMS reply to a [`CatalogPushDelta`].
+///
+/// Items it rejected are left out of `accepted`; they stay dirty and go out
+/// again with the next push.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogPushAck {
+    pub accepted: Vec<CatalogPushResult>,
+    /// Server timestamp of the push
+    pub timestamp: String,
+}
+
+/// A pushed item the PIMS accepted, with its server ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogPushResult {
+    pub sku: String,
+    pub server_id: String,
 }
 
 impl SyncManager<'_> {
@@ -280,16 +777,58 @@ impl SyncManager<'_> {
         let since = self.db.get_sync_state("catalog_last_sync")?;
         Ok(CatalogSyncRequest {
             since: since.filter(|s| !s.is_empty()),
+            retry_skus: self.catalog_retry_skus()?,
         })
     }
 
+    /// SKUs that failed to apply and haven't been applied since.
+    fn catalog_retry_skus(&self) -> MerkleResult<Vec<String>> {
+        match self.db.get_sync_state("catalog_retry_skus")? {
+            Some(json) if !json.is_empty() => Ok(serde_json::from_str(&json)?),
+            _ => Ok(Vec::new()),
+        }
+    }
+
     /// Apply catalog delta from PIMS.
-    pub fn apply_catalog_delta(&self, delta: &CatalogDelta) -> MerkleResult<()> {
+    ///
+    /// Items with local changes not yet pushed keep them, and local items
+    /// the PIMS has never acknowledged are never deactivated by it.
+    ///
+    /// Items that fail validation or can't be written are skipped so the
+    /// rest still apply and the sync timestamp advances; they are returned
+    /// in the report, recorded as the sync status's last error, and kept as
+    /// `retry_skus` of the next sync request until a delta applies them.
+    /// Any other failure leaves the catalog and sync timestamp as they were,
+    /// so the same delta can be requested again.
+    pub fn apply_catalog_delta(&self, delta: &CatalogDelta) -> MerkleResult<CatalogUpsertReport> {
         use crate::models::CatalogItem;
 
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .map_err(crate::db::DbError::from)?;
+
         // Upsert items
+        let mut kept_local = 0;
+        let mut items = Vec::with_capacity(delta.items.len());
         for item in &delta.items {
-            let catalog_item = CatalogItem {
+            let existing = self.db.get_catalog_item(&item.sku)?;
+            if existing.as_ref().is_some_and(|existing| existing.dirty) {
+                kept_local += 1;
+                continue;
+            }
+            // Dose range and withdrawal times managed locally
+            let (dose_range, withdrawal_times, origin) = existing
+                .map(|existing| {
+                    (
+                        existing.dose_range,
+                        existing.withdrawal_times,
+                        existing.origin,
+                    )
+                })
+                .unwrap_or((None, Vec::new(), CatalogOrigin::Pims));
+            items.push(CatalogItem {
                 sku: item.sku.clone(),
                 name: item.name.clone(),
                 aliases: item.aliases.clone(),
@@ -297,23 +836,242 @@ impl SyncManager<'_> {
                 package_size: item.package_size.clone(),
                 species: item.species.clone(),
                 routes: item.routes.clone(),
-                dose_range: None, // Dose range managed locally
+                dose_range,
+                withdrawal_times,
                 active: item.active,
                 server_id: Some(item.server_id.clone()),
                 last_synced: Some(delta.timestamp.clone()),
-            };
-            self.db.upsert_catalog_item(&catalog_item)?;
+                components: item.components.clone(),
+                controlled_schedule: item.controlled_schedule,
+                unit_price: item.unit_price,
+                markup: item.markup,
+                minimum_charge: item.minimum_charge,
+                tax_code: item.tax_code.clone(),
+                origin,
+                dirty: false,
+            });
         }
+        // Bad items are skipped rather than holding back the whole delta
+        let report = self.db.upsert_catalog_items(&items, &())?;
 
         // Deactivate removed items
         for sku in &delta.deactivated_skus {
-            self.db.deactivate_catalog_item(sku)?;
+            let local_only = self.db.get_catalog_item(sku)?.is_some_and(|existing| {
+                existing.dirty
+                    || (existing.origin == CatalogOrigin::Local && existing.server_id.is_none())
+            });
+            if local_only {
+                kept_local += 1;
+            } else {
+                self.db.deactivate_catalog_item(sku)?;
+            }
         }
 
-        // Update sync timestamp
-        self.db.set_sync_state("catalog_last_sync", &delta.timestamp)?;
+        // Update sync timestamp. The timestamp moves past failed items, so
+        // they are asked for by SKU until one applies.
+        let failed = report.failures.iter().map(|f| f.sku.clone());
+        let mut retry: Vec<String> = self
+            .catalog_retry_skus()?
+            .into_iter()
+            .filter(|sku| {
+                !delta.items.iter().any(|item| &item.sku == sku)
+                    && !delta.deactivated_skus.contains(sku)
+            })
+            .chain(failed)
+            .collect();
+        retry.sort();
+        retry.dedup();
+        self.db
+            .set_sync_state("catalog_retry_skus", &serde_json::to_string(&retry)?)?;
+        self.db
+            .set_sync_state("catalog_last_sync", &delta.timestamp)?;
+        if let Some(summary) = report.failure_summary() {
+            self.record_sync_error(&summary)?;
+        }
 
-        Ok(())
+        tx.commit().map_err(crate::db::DbError::from)?;
+        tracing::info!(
+            upserted = report.upserted,
+            failed = report.failures.len(),
+            deactivated = delta.deactivated_skus.len(),
+            kept_local,
+            timestamp = %delta.timestamp,
+            "Applied catalog delta"
+        );
+        if let Some(summary) = report.failure_summary() {
+            tracing::warn!("{}", summary);
+        }
+        Ok(report)
+    }
+
+    /// Catalog items changed on this device since the last push (items
+    /// added at the clinic and local edits), to send to the PIMS.
+    pub fn create_catalog_push_delta(&self) -> MerkleResult<CatalogPushDelta> {
+        let items = self
+            .db
+            .list_dirty_catalog_items()?
+            .into_iter()
+            .map(|item| CatalogPushItem {
+                sku: item.sku,
+                server_id: item.server_id,
+                origin: item.origin,
+                name: item.name,
+                aliases: item.aliases,
+                concentration: item.concentration,
+                package_size: item.package_size,
+                species: item.species,
+                routes: item.routes,
+                active: item.active,
+                components: item.components,
+                controlled_schedule: item.controlled_schedule,
+                unit_price: item.unit_price,
+                markup: item.markup,
+                minimum_charge: item.minimum_charge,
+                tax_code: item.tax_code,
+            })
+            .collect();
+        Ok(CatalogPushDelta { items })
+    }
+
+    /// Apply the PIMS reply to a catalog push: accepted items are linked to
+    /// their server IDs and no longer dirty. Returns how many were accepted.
+    pub fn apply_catalog_push_ack(&self, ack: &CatalogPushAck) -> MerkleResult<usize> {
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .map_err(crate::db::DbError::from)?;
+
+        let mut accepted = 0;
+        for result in &ack.accepted {
+            if self.db.mark_catalog_item_pushed(
+                &result.sku,
+                Some(&result.server_id),
+                &ack.timestamp,
+            )? {
+                accepted += 1;
+            }
+        }
+
+        tx.commit().map_err(crate::db::DbError::from)?;
+        tracing::info!(accepted, timestamp = %ack.timestamp, "Applied catalog push ack");
+        Ok(accepted)
+    }
+}
+
+/// Patient sync for downloading PIMS patients changed since the last pull.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatientSyncRequest {
+    /// Last sync timestamp (ISO 8601)
+    pub since: Option<String>,
+}
+
+/// Patient delta from PIMS.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatientDelta {
+    /// Patients created or edited on the PIMS
+    pub patients: Vec<PatientSyncItem>,
+    /// Timestamp of this delta
+    pub timestamp: String,
+}
+
+/// Patient for sync.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatientSyncItem {
+    pub server_id: String,
+    pub name: String,
+    pub species: String,
+    #[serde(default)]
+    pub breed: Option<String>,
+    #[serde(default)]
+    pub weight_kg: Option<f64>,
+    #[serde(default)]
+    pub date_of_birth: Option<String>,
+    #[serde(default)]
+    pub owner_name: Option<String>,
+}
+
+/// What applying a [`PatientDelta`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatientDeltaReport {
+    /// PIMS patients new to this device
+    pub created: usize,
+    /// Already-linked patients updated from the PIMS
+    pub updated: usize,
+    /// Local patients matched as duplicates and linked to their PIMS ID
+    pub linked: usize,
+}
+
+impl SyncManager<'_> {
+    /// Create patient sync request.
+    pub fn create_patient_sync_request(&self) -> MerkleResult<PatientSyncRequest> {
+        let since = self.db.get_sync_state("patients_last_sync")?;
+        Ok(PatientSyncRequest {
+            since: since.filter(|s| !s.is_empty()),
+        })
+    }
+
+    /// Apply patient delta from PIMS.
+    ///
+    /// A patient already linked to the server ID takes the PIMS demographics
+    /// (weight only if the PIMS has one; notes stay local). An unlinked local
+    /// patient with the same name, owner, and date of birth (at least one of
+    /// the latter two recorded) is linked instead of duplicated, keeping its
+    /// own values where it has them. Anything else is created. All or nothing.
+    pub fn apply_patient_delta(&self, delta: &PatientDelta) -> MerkleResult<PatientDeltaReport> {
+        use crate::models::Patient;
+
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .map_err(crate::db::DbError::from)?;
+
+        let mut report = PatientDeltaReport::default();
+        for item in &delta.patients {
+            if let Some(mut patient) = self.db.get_patient_by_server_id(&item.server_id)? {
+                patient.name = item.name.clone();
+                patient.species = item.species.clone();
+                patient.breed = item.breed.clone();
+                patient.weight_kg = item.weight_kg.or(patient.weight_kg);
+                patient.date_of_birth = item.date_of_birth.clone();
+                patient.owner_name = item.owner_name.clone();
+                self.db.update_patient(&patient)?;
+                report.updated += 1;
+            } else if let Some(mut patient) = self.db.find_unlinked_patient_match(
+                &item.name,
+                item.owner_name.as_deref(),
+                item.date_of_birth.as_deref(),
+            )? {
+                patient.server_id = Some(item.server_id.clone());
+                patient.breed = patient.breed.or_else(|| item.breed.clone());
+                patient.weight_kg = patient.weight_kg.or(item.weight_kg);
+                self.db.update_patient(&patient)?;
+                report.linked += 1;
+            } else {
+                let mut patient = Patient::new(item.name.clone(), item.species.clone());
+                patient.server_id = Some(item.server_id.clone());
+                patient.breed = item.breed.clone();
+                patient.weight_kg = item.weight_kg;
+                patient.date_of_birth = item.date_of_birth.clone();
+                patient.owner_name = item.owner_name.clone();
+                self.db.insert_patient(&patient)?;
+                report.created += 1;
+            }
+        }
+
+        self.db
+            .set_sync_state("patients_last_sync", &delta.timestamp)?;
+
+        tx.commit().map_err(crate::db::DbError::from)?;
+        tracing::info!(
+            created = report.created,
+            updated = report.updated,
+            linked = report.linked,
+            timestamp = %delta.timestamp,
+            "Applied patient delta"
+        );
+        Ok(report)
     }
 }
 
@@ -336,14 +1094,27 @@ mod tests {
                 sku: "SKU001".to_string(),
                 name: "Test Drug".to_string(),
                 quantity: 10.0,
-                unit: "mg".to_string(),
+                unit: "mg".into(),
                 route: Some("PO".to_string()),
                 original_mention: "10mg test drug PO".to_string(),
                 resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                controlled_schedule: None,
+                source_spans: vec![],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
             }],
             reviewed_by: "Dr. Smith".to_string(),
             reviewed_at: "2024-01-15T10:00:00Z".to_string(),
             notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
         }
     }
 
@@ -392,6 +1163,7 @@ mod tests {
                 success: true,
                 new_root: Some(root),
                 error: None,
+                missing_hashes: vec![],
             })
             .unwrap();
 
@@ -419,7 +1191,35 @@ mod tests {
         let export = manager.export_full_tree().unwrap();
         assert_eq!(export.leaf_count, 3);
         assert!(!export.nodes.is_empty());
-        assert!(export.nodes.iter().filter(|n| n.node_type == "leaf").count() == 3);
+        assert!(
+            export
+                .nodes
+                .iter()
+                .filter(|n| n.node_type == "leaf")
+                .count()
+                == 3
+        );
+    }
+
+    #[test]
+    fn test_export_since() {
+        let db = setup_db();
+        let tree = MerkleTree::new(&db);
+        let manager = SyncManager::new(&db);
+
+        let first = tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        let second = tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+
+        // Committed within the same second as the old root
+        let export = manager.export_since(Some(&first.root_hash)).unwrap();
+        let leaves: Vec<_> = export
+            .nodes
+            .iter()
+            .filter(|n| n.node_type == "leaf")
+            .map(|n| n.hash.as_str())
+            .collect();
+        assert_eq!(leaves, vec![second.leaf_hash.as_str()]);
+        assert_eq!(export.root_hash, second.root_hash);
     }
 
     #[test]
@@ -443,6 +1243,12 @@ mod tests {
                 routes: vec!["PO".into()],
                 active: true,
                 server_id: "server-123".into(),
+                components: vec![],
+                controlled_schedule: None,
+                unit_price: Some(0.85),
+                markup: Some(0.3),
+                minimum_charge: None,
+                tax_code: Some("RETAIL".into()),
             }],
             deactivated_skus: vec![],
             timestamp: "2024-01-15T12:00:00Z".into(),
@@ -454,9 +1260,286 @@ mod tests {
         let item = db.get_catalog_item("NEW-SKU").unwrap().unwrap();
         assert_eq!(item.name, "New Drug 100mg");
         assert_eq!(item.server_id, Some("server-123".into()));
+        assert_eq!(item.unit_price, Some(0.85));
+        assert_eq!(item.markup, Some(0.3));
+        assert_eq!(item.tax_code, Some("RETAIL".into()));
 
         // Next sync request should have timestamp
         let request = manager.create_catalog_sync_request().unwrap();
         assert_eq!(request.since, Some("2024-01-15T12:00:00Z".into()));
+
+        // A bad item is skipped and reported; the rest of the delta applies
+        let mut bad = delta.clone();
+        bad.items[0].name = "Renamed".into();
+        bad.items.push(CatalogSyncItem {
+            sku: "NO-NAME".into(),
+            name: String::new(),
+            ..delta.items[0].clone()
+        });
+        bad.timestamp = "2024-01-16T12:00:00Z".into();
+        let report = manager.apply_catalog_delta(&bad).unwrap();
+        assert_eq!(report.upserted, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].sku, "NO-NAME");
+        assert_eq!(
+            db.get_catalog_item("NEW-SKU").unwrap().unwrap().name,
+            "Renamed"
+        );
+        assert!(db.get_catalog_item("NO-NAME").unwrap().is_none());
+        let request = manager.create_catalog_sync_request().unwrap();
+        assert_eq!(request.since, Some("2024-01-16T12:00:00Z".into()));
+        assert_eq!(request.retry_skus, vec!["NO-NAME".to_string()]);
+        let last_error = manager.get_sync_status().unwrap().last_error.unwrap();
+        assert!(last_error.contains("NO-NAME"), "{}", last_error);
+
+        // The failed SKU is asked for until a delta applies it
+        let mut other = delta.clone();
+        other.timestamp = "2024-01-17T12:00:00Z".into();
+        manager.apply_catalog_delta(&other).unwrap();
+        let request = manager.create_catalog_sync_request().unwrap();
+        assert_eq!(request.retry_skus, vec!["NO-NAME".to_string()]);
+        let mut fixed = bad.clone();
+        fixed.items[1].name = "No Name 5mg".into();
+        fixed.timestamp = "2024-01-18T12:00:00Z".into();
+        let report = manager.apply_catalog_delta(&fixed).unwrap();
+        assert!(report.failures.is_empty());
+        assert!(manager
+            .create_catalog_sync_request()
+            .unwrap()
+            .retry_skus
+            .is_empty());
+    }
+
+    #[test]
+    fn test_catalog_sync_keeps_local_dose_range() {
+        use crate::models::{CatalogItem, DoseRange};
+
+        let db = setup_db();
+        let manager = SyncManager::new(&db);
+        let mut local = CatalogItem::new("CARP-100".into(), "Carprofen".into());
+        local.dose_range = Some(DoseRange {
+            min_dose_per_kg: 2.0,
+            max_dose_per_kg: 4.4,
+            unit: "mg".into(),
+        });
+        db.upsert_catalog_item(&local).unwrap();
+
+        let delta = CatalogDelta {
+            items: vec![CatalogSyncItem {
+                sku: "CARP-100".into(),
+                name: "Carprofen 100mg".into(),
+                aliases: vec![],
+                concentration: None,
+                package_size: None,
+                species: vec!["canine".into()],
+                routes: vec!["PO".into()],
+                active: true,
+                server_id: "server-1".into(),
+                components: vec![],
+                controlled_schedule: None,
+                unit_price: None,
+                markup: None,
+                minimum_charge: None,
+                tax_code: None,
+            }],
+            deactivated_skus: vec![],
+            timestamp: "2024-01-15T12:00:00Z".into(),
+        };
+        manager.apply_catalog_delta(&delta).unwrap();
+
+        let item = db.get_catalog_item("CARP-100").unwrap().unwrap();
+        assert_eq!(item.name, "Carprofen 100mg");
+        assert_eq!(item.dose_range, local.dose_range);
+    }
+
+    #[test]
+    fn test_catalog_push_and_merge() {
+        use crate::models::CatalogItem;
+
+        let db = setup_db();
+        let manager = SyncManager::new(&db);
+        let mut house = CatalogItem::new("HOUSE-GABA".into(), "Gabapentin 50mg/mL susp".into());
+        house.origin = CatalogOrigin::Local;
+        house.dirty = true;
+        db.upsert_catalog_item(&house).unwrap();
+
+        let push = manager.create_catalog_push_delta().unwrap();
+        assert_eq!(push.items.len(), 1);
+        assert_eq!(push.items[0].sku, "HOUSE-GABA");
+        assert!(push.items[0].server_id.is_none());
+
+        // A delta before the push is acknowledged doesn't clobber or remove it
+        let sync_item = |name: &str| CatalogSyncItem {
+            sku: "HOUSE-GABA".into(),
+            name: name.into(),
+            aliases: vec![],
+            concentration: None,
+            package_size: None,
+            species: vec![],
+            routes: vec![],
+            active: true,
+            server_id: "srv-7".into(),
+            components: vec![],
+            controlled_schedule: None,
+            unit_price: None,
+            markup: None,
+            minimum_charge: None,
+            tax_code: None,
+        };
+        let delta = CatalogDelta {
+            items: vec![sync_item("Gabapentin (PIMS)")],
+            deactivated_skus: vec!["HOUSE-GABA".into()],
+            timestamp: "2024-01-15T12:00:00Z".into(),
+        };
+        manager.apply_catalog_delta(&delta).unwrap();
+        let item = db.get_catalog_item("HOUSE-GABA").unwrap().unwrap();
+        assert_eq!(item.name, "Gabapentin 50mg/mL susp");
+        assert!(item.active && item.dirty);
+
+        let accepted = manager
+            .apply_catalog_push_ack(&CatalogPushAck {
+                accepted: vec![CatalogPushResult {
+                    sku: "HOUSE-GABA".into(),
+                    server_id: "srv-7".into(),
+                }],
+                timestamp: "2024-01-15T12:05:00Z".into(),
+            })
+            .unwrap();
+        assert_eq!(accepted, 1);
+        assert!(manager
+            .create_catalog_push_delta()
+            .unwrap()
+            .items
+            .is_empty());
+
+        // Once acknowledged, later PIMS edits apply and the origin is kept
+        let delta = CatalogDelta {
+            items: vec![sync_item("Gabapentin 50mg/mL oral susp")],
+            deactivated_skus: vec![],
+            timestamp: "2024-01-16T12:00:00Z".into(),
+        };
+        manager.apply_catalog_delta(&delta).unwrap();
+        let item = db.get_catalog_item("HOUSE-GABA").unwrap().unwrap();
+        assert_eq!(item.name, "Gabapentin 50mg/mL oral susp");
+        assert_eq!(item.server_id.as_deref(), Some("srv-7"));
+        assert_eq!(item.origin, CatalogOrigin::Local);
+        assert!(!item.dirty);
+    }
+
+    #[test]
+    fn test_protocol_negotiation() {
+        let local = SyncCapabilities::local();
+        let agreed = NegotiatedProtocol::negotiate(&local, &local).unwrap();
+        assert_eq!(agreed.version, SYNC_PROTOCOL_VERSION);
+        assert!(agreed.supports(FEATURE_REBASE));
+
+        // An old PIMS gets version 1 messages and no features
+        let legacy = NegotiatedProtocol::negotiate(&local, &SyncCapabilities::legacy()).unwrap();
+        assert_eq!(legacy.version, 1);
+        assert!(!legacy.supports(FEATURE_PATIENT_SYNC));
+        let request = SyncRequest {
+            root_hash: "abc".into(),
+            tree_height: 1,
+            leaf_count: 1,
+            protocol_version: None,
+        };
+        let v1 = serde_json::to_value(request.clone().for_version(1)).unwrap();
+        assert!(v1.get("protocol_version").is_none());
+        let v2 = serde_json::to_value(request.for_version(2)).unwrap();
+        assert_eq!(v2["protocol_version"], 2);
+        let payload = SyncPayload {
+            nodes: vec![],
+            expected_root: "abc".into(),
+            rebase: true,
+        };
+        assert!(payload.check_protocol(&legacy).is_err());
+        assert!(payload.check_protocol(&agreed).is_ok());
+
+        // A newer PIMS: the shared version, its unknown features ignored
+        let newer = SyncCapabilities {
+            protocol_version: 5,
+            min_protocol_version: 2,
+            features: vec!["rebase".into(), "telepathy".into()],
+        };
+        let agreed = NegotiatedProtocol::negotiate(&local, &newer).unwrap();
+        assert_eq!(agreed.version, 2);
+        assert_eq!(agreed.features, vec!["rebase".to_string()]);
+        let too_new = SyncCapabilities {
+            protocol_version: 5,
+            min_protocol_version: 3,
+            features: vec![],
+        };
+        assert!(NegotiatedProtocol::negotiate(&local, &too_new).is_err());
+
+        // Missing fields from an older handshake reply default sensibly
+        let minimal: SyncCapabilities = serde_json::from_str(r#"{"protocol_version": 2}"#).unwrap();
+        assert_eq!(minimal.min_protocol_version, 1);
+        assert!(minimal.features.is_empty());
+    }
+
+    #[test]
+    fn test_patient_sync() {
+        use crate::models::Patient;
+
+        let db = setup_db();
+        let manager = SyncManager::new(&db);
+        assert!(manager
+            .create_patient_sync_request()
+            .unwrap()
+            .since
+            .is_none());
+
+        let mut local = Patient::new("Max".into(), "canine".into());
+        local.owner_name = Some("Jane Doe".into());
+        local.weight_kg = Some(30.0);
+        db.insert_patient(&local).unwrap();
+        let item = |server_id: &str, name: &str| PatientSyncItem {
+            server_id: server_id.into(),
+            name: name.into(),
+            species: "canine".into(),
+            breed: Some("Labrador".into()),
+            weight_kg: Some(28.0),
+            date_of_birth: None,
+            owner_name: Some("jane doe".into()),
+        };
+
+        let report = manager
+            .apply_patient_delta(&PatientDelta {
+                patients: vec![item("srv-1", "MAX"), item("srv-2", "Bella")],
+                timestamp: "2024-01-15T12:00:00Z".into(),
+            })
+            .unwrap();
+        assert_eq!(
+            report,
+            PatientDeltaReport {
+                created: 1,
+                updated: 0,
+                linked: 1,
+            }
+        );
+        let linked = db.get_patient(&local.local_id).unwrap().unwrap();
+        assert_eq!(linked.server_id.as_deref(), Some("srv-1"));
+        assert_eq!(linked.name, "Max");
+        assert_eq!(linked.weight_kg, Some(30.0));
+        assert_eq!(linked.breed.as_deref(), Some("Labrador"));
+        assert_eq!(db.list_patients().unwrap().len(), 2);
+        assert_eq!(
+            manager.create_patient_sync_request().unwrap().since,
+            Some("2024-01-15T12:00:00Z".into())
+        );
+
+        // Later edits on the PIMS update the linked patient
+        let mut renamed = item("srv-1", "Maximus");
+        renamed.weight_kg = None;
+        let report = manager
+            .apply_patient_delta(&PatientDelta {
+                patients: vec![renamed],
+                timestamp: "2024-01-16T12:00:00Z".into(),
+            })
+            .unwrap();
+        assert_eq!(report.updated, 1);
+        let updated = db.get_patient(&local.local_id).unwrap().unwrap();
+        assert_eq!(updated.name, "Maximus");
+        assert_eq!(updated.weight_kg, Some(30.0));
     }
 }
@@ -0,0 +1,1081 @@
+//! Transport for the encounter sync protocol, and the engine that drives a
+//! full round trip over it.
+//!
+//! A [`SyncTransport`] carries the two exchanges of the protocol in
+//! [`super::sync`]: the local root out and the PIMS's missing hashes back,
+//! then the missing nodes out and the acknowledgment back. It also carries
+//! patients both ways: upserts out, changed PIMS patients back. The
+//! [`SyncEngine`] does everything else, including working through the sync
+//! outbox. Over HTTP each exchange is a JSON POST to [`SYNC_REQUEST_PATH`],
+//! [`SYNC_PAYLOAD_PATH`], [`PATIENT_UPSERT_PATH`], or [`PATIENT_PULL_PATH`]
+//! under the PIMS base URL; see `HttpSyncTransport` (`http` feature).
+//! Payloads bigger than one chunk go to [`SYNC_CHUNK_PATH`] instead, one
+//! [`SyncChunk`] at a time (see [`super::chunk`]).
+//!
+//! The engine opens with a capabilities handshake at [`CAPABILITIES_PATH`]
+//! and only uses what the PIMS supports; a PIMS without the endpoint is
+//! treated as speaking protocol version 1.
+//!
+//! The engine reaches the database through a [`SyncStore`], one step at a
+//! time, and never while waiting on the transport, so a caller sharing the
+//! database behind a lock can release it for the network exchanges.
+
+use std::cell::OnceCell;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::db::Database;
+use crate::models::{
+    OutboxItem, OutboxKind, OutboxStatus, Patient, RetryPolicy, SyncConflictStrategy,
+};
+
+use super::{
+    ChunkEncoding, ChunkProgress, MerkleError, MerkleResult, NegotiatedProtocol, PatientDelta,
+    PatientDeltaReport, PatientSyncRequest, PatientUpsertAck, SyncAck, SyncCapabilities, SyncChunk,
+    SyncChunkAck, SyncConflict, SyncManager, SyncPayload, SyncRequest, SyncResponse,
+    DEFAULT_SYNC_CHUNK_SIZE, FEATURE_CHUNKED_PAYLOAD, FEATURE_PATIENT_SYNC,
+};
+
+/// Endpoint (relative to the PIMS base URL) that takes our
+/// [`SyncCapabilities`] and returns the PIMS's.
+pub const CAPABILITIES_PATH: &str = "sync/capabilities";
+
+/// Endpoint (relative to the PIMS base URL) that takes a [`SyncRequest`]
+/// and returns a [`SyncResponse`].
+pub const SYNC_REQUEST_PATH: &str = "sync/request";
+
+/// Endpoint (relative to the PIMS base URL) that takes a [`SyncPayload`]
+/// and returns a [`SyncAck`].
+pub const SYNC_PAYLOAD_PATH: &str = "sync/payload";
+
+/// Endpoint (relative to the PIMS base URL) that takes a [`SyncChunk`] and
+/// returns a [`SyncChunkAck`].
+pub const SYNC_CHUNK_PATH: &str = "sync/payload/chunk";
+
+/// Most follow-up payloads a PIMS may ask for in one sync. Each descends a
+/// level of the tree, so this covers trees far beyond any real clinic's.
+const MAX_SYNC_ROUNDS: usize = 64;
+
+/// Endpoint (relative to the PIMS base URL) that takes a [`Patient`] and
+/// returns a [`PatientUpsertAck`].
+pub const PATIENT_UPSERT_PATH: &str = "sync/patients";
+
+/// Endpoint (relative to the PIMS base URL) that takes a
+/// [`PatientSyncRequest`] and returns a [`PatientDelta`].
+pub const PATIENT_PULL_PATH: &str = "sync/patients/changes";
+
+/// Sync transport errors.
+#[derive(Error, Debug)]
+pub enum TransportError {
+    #[error("Connection failed: {0}")]
+    Connection(String),
+
+    #[error("PIMS returned HTTP {status}: {body}")]
+    Status { status: u16, body: String },
+
+    #[error("Invalid response from PIMS: {0}")]
+    InvalidResponse(String),
+
+    #[error("PIMS rejected sync: {0}")]
+    Rejected(String),
+
+    #[error("PIMS protocol incompatible: {0}")]
+    Incompatible(String),
+}
+
+impl From<serde_json::Error> for TransportError {
+    fn from(e: serde_json::Error) -> Self {
+        TransportError::InvalidResponse(e.to_string())
+    }
+}
+
+/// Carries sync messages to the PIMS and returns its replies.
+pub trait SyncTransport {
+    /// Exchange capabilities. A PIMS that predates the handshake should be
+    /// reported as [`SyncCapabilities::legacy`], not as an error.
+    fn capabilities(&self, local: &SyncCapabilities) -> Result<SyncCapabilities, TransportError>;
+
+    /// Send the local root; the PIMS replies with the hashes it's missing.
+    fn send_request(&self, request: &SyncRequest) -> Result<SyncResponse, TransportError>;
+
+    /// Send the missing nodes; the PIMS verifies them against the expected
+    /// root and acknowledges.
+    fn send_payload(&self, payload: &SyncPayload) -> Result<SyncAck, TransportError>;
+
+    /// Send one chunk of a payload; the PIMS says which chunk it wants next,
+    /// and acknowledges the payload with the last one.
+    fn send_chunk(&self, chunk: &SyncChunk) -> Result<SyncChunkAck, TransportError>;
+
+    /// Create or update a patient; the PIMS replies with its patient ID.
+    fn upsert_patient(&self, patient: &Patient) -> Result<PatientUpsertAck, TransportError>;
+
+    /// Fetch the PIMS patients created or edited since the request's
+    /// timestamp.
+    fn pull_patients(&self, request: &PatientSyncRequest) -> Result<PatientDelta, TransportError>;
+}
+
+/// Result of [`SyncEngine::run_sync`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// Nothing needed sending (empty tree, root already synced, or the PIMS
+    /// already had every node)
+    pub up_to_date: bool,
+    /// Nodes sent to the PIMS
+    pub nodes_sent: usize,
+    /// Chunks sent, if the nodes went out in chunks (0 otherwise)
+    pub chunks_sent: usize,
+    /// Root the PIMS acknowledged, if any
+    pub synced_root: Option<String>,
+    /// Divergence found (and resolved) during this sync
+    pub conflict: Option<SyncConflict>,
+}
+
+/// Result of [`SyncEngine::process_outbox`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutboxReport {
+    /// Operations that reached the PIMS (and left the outbox)
+    pub succeeded: usize,
+    /// Operations that failed this time
+    pub failed: usize,
+    /// Of those, operations now out of attempts
+    pub exhausted: usize,
+    /// Earliest next attempt among pending operations
+    pub next_attempt_at: Option<String>,
+}
+
+/// Lends the [`SyncEngine`] the database for one step of a sync.
+pub trait SyncStore {
+    /// Run `f` against the database.
+    fn with_db<R>(&self, f: impl FnOnce(&Database) -> MerkleResult<R>) -> MerkleResult<R>;
+}
+
+impl SyncStore for Database {
+    fn with_db<R>(&self, f: impl FnOnce(&Database) -> MerkleResult<R>) -> MerkleResult<R> {
+        f(self)
+    }
+}
+
+/// Runs the encounter sync protocol over a transport.
+pub struct SyncEngine<'a, T: SyncTransport + ?Sized, S: SyncStore + ?Sized = Database> {
+    store: &'a S,
+    transport: &'a T,
+    protocol: OnceCell<NegotiatedProtocol>,
+    chunk_size: usize,
+}
+
+impl<'a, T: SyncTransport + ?Sized, S: SyncStore + ?Sized> SyncEngine<'a, T, S> {
+    /// Create an engine syncing the database in `store` over `transport`.
+    pub fn new(store: &'a S, transport: &'a T) -> Self {
+        Self {
+            store,
+            transport,
+            protocol: OnceCell::new(),
+            chunk_size: DEFAULT_SYNC_CHUNK_SIZE,
+        }
+    }
+
+    /// Run `f` with a sync manager, holding the database only for the call.
+    fn manager<R>(&self, f: impl FnOnce(&SyncManager) -> MerkleResult<R>) -> MerkleResult<R> {
+        self.store.with_db(|db| f(&SyncManager::new(db)))
+    }
+
+    /// Send payloads in chunks of at most `chunk_size` nodes.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Agree on a protocol with the PIMS. The handshake runs once per
+    /// engine, and the result is recorded in sync state.
+    pub fn negotiate(&self) -> MerkleResult<NegotiatedProtocol> {
+        if let Some(protocol) = self.protocol.get() {
+            return Ok(protocol.clone());
+        }
+        let local = SyncCapabilities::local();
+        let remote = self.transport.capabilities(&local)?;
+        let protocol =
+            NegotiatedProtocol::negotiate(&local, &remote).map_err(TransportError::Incompatible)?;
+        self.manager(|manager| manager.set_server_protocol(&protocol))?;
+        Ok(self.protocol.get_or_init(|| protocol).clone())
+    }
+
+    /// Fail with [`TransportError::Incompatible`] unless the PIMS supports
+    /// `feature`.
+    fn require(&self, feature: &str) -> MerkleResult<()> {
+        if self.negotiate()?.supports(feature) {
+            Ok(())
+        } else {
+            Err(TransportError::Incompatible(format!("the PIMS does not support {feature}")).into())
+        }
+    }
+
+    /// Sync the tree: send the root, send the nodes the PIMS is missing,
+    /// and record the acknowledged root as synced.
+    ///
+    /// Skips the network when the tree is empty or its root is already
+    /// synced. A PIMS that acknowledges with `success: false` fails with
+    /// [`TransportError::Rejected`]; the last synced root is unchanged.
+    ///
+    /// If the PIMS tree has diverged, the conflict is recorded and handled
+    /// per the clinic's [`SyncConflictStrategy`](crate::models::SyncConflictStrategy):
+    /// a `ServerRebase` sends only our leaves, and an unresolved `Manual`
+    /// conflict fails with [`MerkleError::SyncConflict`] before anything is
+    /// sent. A rebase fails with [`TransportError::Incompatible`] if the
+    /// PIMS can't take one.
+    pub fn run_sync(&self) -> MerkleResult<SyncReport> {
+        let (request, unsynced) = self.manager(|manager| {
+            Ok((
+                manager.create_sync_request()?,
+                manager.has_unsynced_changes()?,
+            ))
+        })?;
+        let Some(request) = request else {
+            return Ok(SyncReport::up_to_date(None));
+        };
+        if !unsynced {
+            return Ok(SyncReport::up_to_date(Some(request.root_hash)));
+        }
+        self.track(self.push(request))
+    }
+
+    /// The network part of [`run_sync`](Self::run_sync).
+    fn push(&self, request: SyncRequest) -> MerkleResult<SyncReport> {
+        let protocol = self.negotiate()?;
+        let request = request.for_version(protocol.version);
+        let response = self.transport.send_request(&request)?;
+        if response.missing_hashes.is_empty()
+            && response.server_root_hash.as_deref() == Some(request.root_hash.as_str())
+        {
+            self.manager(|manager| {
+                manager.handle_sync_ack(&SyncAck {
+                    success: true,
+                    new_root: Some(request.root_hash.clone()),
+                    error: None,
+                    missing_hashes: vec![],
+                })
+            })?;
+            return Ok(SyncReport::up_to_date(Some(request.root_hash)));
+        }
+
+        let (conflict, rebase, payload) = self.manager(|manager| {
+            let conflict = match manager.detect_divergence(&request.root_hash, &response)? {
+                Some(detected) => Some(manager.record_conflict(detected)?),
+                None => None,
+            };
+            let rebase = match conflict.as_ref().map(|c| c.resolution) {
+                Some(None) => {
+                    return Err(MerkleError::SyncConflict {
+                        server_root: response.server_root_hash.clone().unwrap_or_default(),
+                    })
+                }
+                Some(Some(resolution)) => resolution == SyncConflictStrategy::ServerRebase,
+                None => manager.is_rebased()?,
+            };
+            let payload = if rebase {
+                manager.create_rebase_payload()?
+            } else {
+                manager.process_sync_response(&response)?
+            };
+            Ok((conflict, rebase, payload))
+        })?;
+        payload
+            .check_protocol(&protocol)
+            .map_err(TransportError::Incompatible)?;
+        let (mut ack, mut chunks_sent) = self.deliver(&payload, &protocol)?;
+        let mut nodes_sent = payload.nodes.len();
+        // The PIMS learns child hashes from the nodes it's sent, so it may
+        // ask for the tree a level at a time
+        let mut rounds = 0;
+        while !ack.missing_hashes.is_empty() {
+            rounds += 1;
+            if rounds > MAX_SYNC_ROUNDS {
+                return Err(TransportError::InvalidResponse(
+                    "PIMS kept asking for more nodes".to_string(),
+                )
+                .into());
+            }
+            let missing = std::mem::take(&mut ack.missing_hashes);
+            let more = self.manager(|manager| {
+                manager.process_sync_response(&SyncResponse {
+                    missing_hashes: missing.clone(),
+                    server_root_hash: None,
+                })
+            })?;
+            if more.nodes.len() < missing.len() {
+                return Err(TransportError::InvalidResponse(
+                    "PIMS asked for nodes we don't have".to_string(),
+                )
+                .into());
+            }
+            let (next, chunks) = self.deliver(&more, &protocol)?;
+            nodes_sent += more.nodes.len();
+            chunks_sent += chunks;
+            ack = next;
+        }
+        self.manager(|manager| manager.handle_sync_ack(&ack))?;
+        if !ack.success {
+            let reason = ack.error.unwrap_or_else(|| "no reason given".to_string());
+            return Err(TransportError::Rejected(reason).into());
+        }
+        if rebase {
+            self.manager(|manager| manager.mark_rebased(&request.root_hash))?;
+        }
+
+        Ok(SyncReport {
+            up_to_date: false,
+            nodes_sent,
+            chunks_sent,
+            synced_root: ack.new_root,
+            conflict,
+        })
+    }
+
+    /// Send `payload` whole, or in chunks if it's bigger than one chunk and
+    /// the PIMS takes them. Returns the acknowledgment and the chunks sent.
+    ///
+    /// Progress is recorded after every chunk, so an upload cut off midway
+    /// resumes from the chunk the PIMS last asked for, provided the payload
+    /// hasn't changed since.
+    fn deliver(
+        &self,
+        payload: &SyncPayload,
+        protocol: &NegotiatedProtocol,
+    ) -> MerkleResult<(SyncAck, usize)> {
+        if payload.nodes.len() <= self.chunk_size || !protocol.supports(FEATURE_CHUNKED_PAYLOAD) {
+            return Ok((self.transport.send_payload(payload)?, 0));
+        }
+        let encoding = ChunkEncoding::preferred(&protocol.features);
+        let total = payload.chunk_count(self.chunk_size);
+        let (mut index, mut continuation) = match self.manager(|m| m.get_chunk_progress())? {
+            Some(progress) if progress.matches(payload, self.chunk_size) => {
+                tracing::info!(
+                    next_index = progress.next_index,
+                    total,
+                    "Resuming chunked sync"
+                );
+                (progress.next_index, Some(progress.continuation))
+            }
+            _ => (0, None),
+        };
+
+        let digest = payload.digest();
+        let mut sent = 0;
+        loop {
+            // Every chunk once, plus one restart if the PIMS lost the upload
+            if sent >= 2 * total as usize {
+                return Err(TransportError::InvalidResponse(
+                    "PIMS never completed the chunked upload".to_string(),
+                )
+                .into());
+            }
+            let chunk = payload.chunk(index, self.chunk_size, encoding, continuation.take())?;
+            let reply = self.transport.send_chunk(&chunk)?;
+            sent += 1;
+            if let Some(ack) = reply.complete {
+                self.manager(|manager| manager.set_chunk_progress(None))?;
+                return Ok((ack, sent));
+            }
+            if reply.next_index >= total {
+                return Err(TransportError::InvalidResponse(format!(
+                    "PIMS asked for chunk {} of {}",
+                    reply.next_index, total
+                ))
+                .into());
+            }
+            let progress = ChunkProgress {
+                expected_root: payload.expected_root.clone(),
+                payload_digest: digest.clone(),
+                chunk_size: self.chunk_size,
+                next_index: reply.next_index,
+                continuation: reply.continuation.clone(),
+            };
+            self.manager(|manager| manager.set_chunk_progress(Some(&progress)))?;
+            index = reply.next_index;
+            continuation = Some(reply.continuation);
+        }
+    }
+
+    /// Pull PIMS patients changed since the last pull and apply them
+    /// (see [`SyncManager::apply_patient_delta`]).
+    pub fn pull_patients(&self) -> MerkleResult<PatientDeltaReport> {
+        self.track(self.require(FEATURE_PATIENT_SYNC).and_then(|()| {
+            let request = self.manager(|manager| manager.create_patient_sync_request())?;
+            let delta = self.transport.pull_patients(&request)?;
+            self.manager(|manager| manager.apply_patient_delta(&delta))
+        }))
+    }
+
+    /// Record the outcome of a network attempt for the sync status: sync
+    /// failures are kept until an attempt succeeds.
+    fn track<R>(&self, result: MerkleResult<R>) -> MerkleResult<R> {
+        match &result {
+            Ok(_) => self.manager(|manager| manager.clear_sync_error())?,
+            Err(e @ (MerkleError::Transport(_) | MerkleError::SyncConflict { .. })) => {
+                self.manager(|manager| manager.record_sync_error(&e.to_string()))?
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// Attempt every outbox operation due at `now`. Queued encounters go out
+    /// together in one [`run_sync`](Self::run_sync); patients are upserted
+    /// one at a time and linked to the PIMS ID they get back.
+    ///
+    /// Transport failures are recorded on the operations and retried per
+    /// `policy`; other errors abort the run.
+    pub fn process_outbox(
+        &self,
+        now: DateTime<Utc>,
+        policy: &RetryPolicy,
+    ) -> MerkleResult<OutboxReport> {
+        let (encounters, patients): (Vec<_>, Vec<_>) = self
+            .store
+            .with_db(|db| Ok(db.due_outbox_items(now)?))?
+            .into_iter()
+            .partition(|item| item.kind == OutboxKind::EncounterPush);
+
+        let mut report = OutboxReport::default();
+        if !encounters.is_empty() {
+            let result = self.run_sync().map(|_| ());
+            self.settle(&encounters, result, now, policy, &mut report)?;
+        }
+        for item in patients {
+            let result = self.upsert_patient(&item.subject_id);
+            self.settle(&[item], result, now, policy, &mut report)?;
+        }
+        report.next_attempt_at = self.store.with_db(|db| Ok(db.next_outbox_attempt()?))?;
+        Ok(report)
+    }
+
+    /// Push a queued patient. Patients deleted since are skipped.
+    fn upsert_patient(&self, local_id: &str) -> MerkleResult<()> {
+        let Some(patient) = self.store.with_db(|db| Ok(db.get_patient(local_id)?))? else {
+            return Ok(());
+        };
+        self.track(self.require(FEATURE_PATIENT_SYNC).and_then(|()| {
+            let ack = self.transport.upsert_patient(&patient)?;
+            self.store.with_db(|db| {
+                db.link_patient_server_id(local_id, &ack.server_id)?;
+                Ok(())
+            })
+        }))
+    }
+
+    /// Complete or reschedule `items` after an attempt.
+    fn settle(
+        &self,
+        items: &[OutboxItem],
+        result: MerkleResult<()>,
+        now: DateTime<Utc>,
+        policy: &RetryPolicy,
+        report: &mut OutboxReport,
+    ) -> MerkleResult<()> {
+        match result {
+            Ok(()) => {
+                self.store.with_db(|db| {
+                    for item in items {
+                        db.complete_outbox_item(item.id)?;
+                    }
+                    Ok(())
+                })?;
+                report.succeeded += items.len();
+            }
+            Err(MerkleError::Transport(e)) => {
+                let error = e.to_string();
+                tracing::warn!(error = %error, count = items.len(), "Sync attempt failed");
+                let exhausted = self.store.with_db(|db| {
+                    let mut exhausted = 0;
+                    for item in items {
+                        let item = db.fail_outbox_item(item.id, &error, now, policy)?;
+                        if item.status == OutboxStatus::Failed {
+                            exhausted += 1;
+                        }
+                    }
+                    Ok(exhausted)
+                })?;
+                report.exhausted += exhausted;
+                report.failed += items.len();
+            }
+            Err(MerkleError::SyncConflict { server_root }) => {
+                // Not the network's fault; wait for the conflict to be resolved
+                tracing::warn!(
+                    server_root = %server_root,
+                    count = items.len(),
+                    "Sync blocked on conflict"
+                );
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+}
+
+impl SyncReport {
+    fn up_to_date(synced_root: Option<String>) -> Self {
+        Self {
+            up_to_date: true,
+            nodes_sent: 0,
+            chunks_sent: 0,
+            synced_root,
+            conflict: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::merkle::{MerkleError, MerkleTree, PatientSyncItem, SyncNode};
+    use crate::models::{EncounterLineItem, ResolutionMethod, ReviewedEncounter};
+
+    fn make_encounter(id: &str) -> ReviewedEncounter {
+        ReviewedEncounter {
+            draft_id: id.to_string(),
+            patient_id: "patient-1".to_string(),
+            patient_server_id: None,
+            transcript: "Test transcript".to_string(),
+            line_items: vec![EncounterLineItem {
+                sku: "SKU001".to_string(),
+                name: "Test Drug".to_string(),
+                quantity: 1.0,
+                unit: "tablet".into(),
+                route: Some("PO".to_string()),
+                original_mention: "test drug".to_string(),
+                resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                controlled_schedule: None,
+                source_spans: vec![],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
+            }],
+            reviewed_by: "Dr. Smith".to_string(),
+            reviewed_at: "2024-01-15T10:00:00Z".to_string(),
+            notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
+        }
+    }
+
+    /// In-memory PIMS that stores every node it's sent.
+    #[derive(Default)]
+    struct FakePims {
+        nodes: RefCell<HashSet<String>>,
+        root: RefCell<Option<String>>,
+        reject: bool,
+        offline: bool,
+        legacy: bool,
+        requests: RefCell<usize>,
+        versions: RefCell<Vec<Option<u32>>>,
+        /// Nodes of the chunked upload in progress, and its token
+        upload: RefCell<(Vec<SyncNode>, Option<String>)>,
+        /// Chunk indexes received, in order
+        chunks: RefCell<Vec<u32>>,
+        /// Drop the connection when this chunk arrives (once)
+        drop_chunk: RefCell<Option<u32>>,
+    }
+
+    impl SyncTransport for FakePims {
+        fn capabilities(
+            &self,
+            local: &SyncCapabilities,
+        ) -> Result<SyncCapabilities, TransportError> {
+            if self.offline {
+                return Err(TransportError::Connection("offline".to_string()));
+            }
+            Ok(if self.legacy {
+                SyncCapabilities::legacy()
+            } else {
+                local.clone()
+            })
+        }
+
+        fn send_request(&self, request: &SyncRequest) -> Result<SyncResponse, TransportError> {
+            *self.requests.borrow_mut() += 1;
+            self.versions.borrow_mut().push(request.protocol_version);
+            if self.offline {
+                return Err(TransportError::Connection("offline".to_string()));
+            }
+            let missing_hashes = if self.nodes.borrow().contains(&request.root_hash) {
+                vec![]
+            } else {
+                vec![request.root_hash.clone()]
+            };
+            Ok(SyncResponse {
+                missing_hashes,
+                server_root_hash: self.root.borrow().clone(),
+            })
+        }
+
+        fn send_payload(&self, payload: &SyncPayload) -> Result<SyncAck, TransportError> {
+            if self.reject {
+                return Ok(SyncAck {
+                    success: false,
+                    new_root: None,
+                    error: Some("bad root".to_string()),
+                    missing_hashes: vec![],
+                });
+            }
+            let mut nodes = self.nodes.borrow_mut();
+            nodes.extend(payload.nodes.iter().map(|node| node.hash.clone()));
+            // A rebase appends the leaves onto our own tree
+            let new_root = if payload.rebase {
+                let root = self.root.borrow();
+                format!("{}+{}", root.as_deref().unwrap_or(""), payload.nodes.len())
+            } else {
+                payload.expected_root.clone()
+            };
+            *self.root.borrow_mut() = Some(new_root.clone());
+            Ok(SyncAck {
+                success: true,
+                new_root: Some(new_root),
+                error: None,
+                missing_hashes: vec![],
+            })
+        }
+
+        fn send_chunk(&self, chunk: &SyncChunk) -> Result<SyncChunkAck, TransportError> {
+            if *self.drop_chunk.borrow() == Some(chunk.index) {
+                self.drop_chunk.borrow_mut().take();
+                return Err(TransportError::Connection("dropped".to_string()));
+            }
+            self.chunks.borrow_mut().push(chunk.index);
+            let mut upload = self.upload.borrow_mut();
+            let continues = chunk.continuation.is_some() && chunk.continuation == upload.1;
+            if chunk.index == 0 {
+                *upload = (vec![], Some(format!("upload-{}", chunk.expected_root)));
+            } else if !continues {
+                // Unknown upload: start over
+                return Ok(SyncChunkAck {
+                    continuation: String::new(),
+                    next_index: 0,
+                    complete: None,
+                });
+            }
+            upload.0.extend(chunk.nodes().unwrap());
+            let continuation = upload.1.clone().unwrap();
+            if chunk.index + 1 < chunk.total {
+                return Ok(SyncChunkAck {
+                    continuation,
+                    next_index: chunk.index + 1,
+                    complete: None,
+                });
+            }
+            let payload = SyncPayload {
+                nodes: std::mem::take(&mut upload.0),
+                expected_root: chunk.expected_root.clone(),
+                rebase: chunk.rebase,
+            };
+            Ok(SyncChunkAck {
+                continuation,
+                next_index: chunk.total,
+                complete: Some(self.send_payload(&payload)?),
+            })
+        }
+
+        fn upsert_patient(&self, patient: &Patient) -> Result<PatientUpsertAck, TransportError> {
+            if self.offline {
+                return Err(TransportError::Connection("offline".to_string()));
+            }
+            Ok(PatientUpsertAck {
+                server_id: format!("srv-{}", patient.name),
+            })
+        }
+
+        fn pull_patients(
+            &self,
+            request: &PatientSyncRequest,
+        ) -> Result<PatientDelta, TransportError> {
+            if self.offline {
+                return Err(TransportError::Connection("offline".to_string()));
+            }
+            // One patient, sent on the first pull only
+            let patients = match request.since {
+                Some(_) => vec![],
+                None => vec![PatientSyncItem {
+                    server_id: "srv-Bella".to_string(),
+                    name: "Bella".to_string(),
+                    species: "feline".to_string(),
+                    breed: None,
+                    weight_kg: Some(4.2),
+                    date_of_birth: None,
+                    owner_name: None,
+                }],
+            };
+            Ok(PatientDelta {
+                patients,
+                timestamp: "2024-01-15T12:00:00Z".to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_run_sync_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let pims = FakePims::default();
+        let engine = SyncEngine::new(&db, &pims);
+        assert_eq!(engine.run_sync().unwrap(), SyncReport::up_to_date(None));
+        assert_eq!(*pims.requests.borrow(), 0);
+
+        let commit = MerkleTree::new(&db)
+            .commit_encounter(&make_encounter("draft-1"))
+            .unwrap();
+        let report = engine.run_sync().unwrap();
+        assert!(!report.up_to_date);
+        assert_eq!(report.nodes_sent, 1);
+        assert_eq!(
+            report.synced_root.as_deref(),
+            Some(commit.root_hash.as_str())
+        );
+        assert!(!SyncManager::new(&db).has_unsynced_changes().unwrap());
+
+        // Already synced: no network round trip
+        assert!(engine.run_sync().unwrap().up_to_date);
+        assert_eq!(*pims.requests.borrow(), 1);
+        assert_eq!(*pims.versions.borrow(), vec![Some(2)]);
+    }
+
+    #[test]
+    fn test_run_sync_legacy_pims() {
+        let db = Database::open_in_memory().unwrap();
+        let pims = FakePims {
+            legacy: true,
+            ..Default::default()
+        };
+        let engine = SyncEngine::new(&db, &pims);
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+
+        // Version 1 messages still sync encounters
+        engine.run_sync().unwrap();
+        assert_eq!(*pims.versions.borrow(), vec![None]);
+        let manager = SyncManager::new(&db);
+        assert_eq!(manager.get_server_protocol().unwrap().unwrap().version, 1);
+
+        // ...but features it doesn't know about are refused, not sent
+        assert!(matches!(
+            engine.pull_patients(),
+            Err(MerkleError::Transport(TransportError::Incompatible(_)))
+        ));
+        *pims.root.borrow_mut() = Some("restored-root".to_string());
+        db.set_core_settings(&crate::models::CoreSettings {
+            sync_conflict_strategy: SyncConflictStrategy::ServerRebase,
+            ..Default::default()
+        })
+        .unwrap();
+        tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+        assert!(matches!(
+            engine.run_sync(),
+            Err(MerkleError::Transport(TransportError::Incompatible(_)))
+        ));
+        assert!(manager.has_unsynced_changes().unwrap());
+    }
+
+    #[test]
+    fn test_run_sync_rejected() {
+        let db = Database::open_in_memory().unwrap();
+        MerkleTree::new(&db)
+            .commit_encounter(&make_encounter("draft-1"))
+            .unwrap();
+        let pims = FakePims {
+            reject: true,
+            ..Default::default()
+        };
+
+        let result = SyncEngine::new(&db, &pims).run_sync();
+        assert!(matches!(
+            result,
+            Err(MerkleError::Transport(TransportError::Rejected(reason))) if reason == "bad root"
+        ));
+        assert!(SyncManager::new(&db).has_unsynced_changes().unwrap());
+    }
+
+    #[test]
+    fn test_run_sync_diverged_local_wins() {
+        let db = Database::open_in_memory().unwrap();
+        let pims = FakePims {
+            root: RefCell::new(Some("other-device-root".to_string())),
+            ..Default::default()
+        };
+        let commit = MerkleTree::new(&db)
+            .commit_encounter(&make_encounter("draft-1"))
+            .unwrap();
+
+        let report = SyncEngine::new(&db, &pims).run_sync().unwrap();
+        let conflict = report.conflict.unwrap();
+        assert_eq!(conflict.server_root, "other-device-root");
+        assert_eq!(conflict.last_server_root, None);
+        assert_eq!(conflict.resolution, Some(SyncConflictStrategy::LocalWins));
+        assert_eq!(report.synced_root, Some(commit.root_hash));
+        let manager = SyncManager::new(&db);
+        assert!(!manager.has_unsynced_changes().unwrap());
+        assert_eq!(manager.get_sync_conflict().unwrap(), Some(conflict));
+    }
+
+    #[test]
+    fn test_run_sync_manual_conflict_then_rebase() {
+        let db = Database::open_in_memory().unwrap();
+        db.set_core_settings(&crate::models::CoreSettings {
+            sync_conflict_strategy: SyncConflictStrategy::Manual,
+            ..Default::default()
+        })
+        .unwrap();
+        let pims = FakePims::default();
+        let engine = SyncEngine::new(&db, &pims);
+        let manager = SyncManager::new(&db);
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        engine.run_sync().unwrap();
+
+        // The PIMS is restored from an older backup
+        *pims.root.borrow_mut() = Some("restored-root".to_string());
+        tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+        assert!(matches!(
+            engine.run_sync(),
+            Err(MerkleError::SyncConflict { server_root }) if server_root == "restored-root"
+        ));
+        assert!(manager.get_sync_conflict().unwrap().unwrap().is_open());
+        assert!(matches!(
+            engine.run_sync(),
+            Err(MerkleError::SyncConflict { .. })
+        ));
+        assert!(manager.has_unsynced_changes().unwrap());
+
+        manager
+            .resolve_conflict(SyncConflictStrategy::ServerRebase)
+            .unwrap();
+        let report = engine.run_sync().unwrap();
+        // Only the leaf committed since the last sync
+        assert_eq!(report.nodes_sent, 1);
+        assert_eq!(report.synced_root.as_deref(), Some("restored-root+1"));
+        assert!(!manager.get_sync_conflict().unwrap().unwrap().is_open());
+        assert!(!manager.has_unsynced_changes().unwrap());
+        assert!(manager.is_rebased().unwrap());
+
+        // Later syncs keep rebasing onto the PIMS tree without a new conflict
+        tree.commit_encounter(&make_encounter("draft-3")).unwrap();
+        let report = engine.run_sync().unwrap();
+        assert_eq!(report.conflict, None);
+        assert_eq!(report.nodes_sent, 1);
+        assert_eq!(report.synced_root.as_deref(), Some("restored-root+1+1"));
+        assert!(!manager.has_unsynced_changes().unwrap());
+    }
+
+    #[test]
+    fn test_run_sync_behind_is_not_conflict() {
+        let db = Database::open_in_memory().unwrap();
+        db.set_core_settings(&crate::models::CoreSettings {
+            sync_conflict_strategy: SyncConflictStrategy::Manual,
+            ..Default::default()
+        })
+        .unwrap();
+        let pims = FakePims::default();
+        let engine = SyncEngine::new(&db, &pims);
+        let tree = MerkleTree::new(&db);
+        tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        engine.run_sync().unwrap();
+
+        // The PIMS took a later root of ours but the acknowledgment was lost
+        let behind = tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+        *pims.root.borrow_mut() = Some(behind.root_hash);
+        let latest = tree.commit_encounter(&make_encounter("draft-3")).unwrap();
+        let report = engine.run_sync().unwrap();
+        assert_eq!(report.conflict, None);
+        assert_eq!(report.synced_root, Some(latest.root_hash));
+        let manager = SyncManager::new(&db);
+        assert_eq!(manager.get_sync_conflict().unwrap(), None);
+        assert!(!manager.has_unsynced_changes().unwrap());
+    }
+
+    fn make_nodes(count: usize) -> Vec<SyncNode> {
+        (0..count)
+            .map(|i| SyncNode {
+                hash: format!("hash-{i}"),
+                node_type: "leaf".to_string(),
+                left_child: None,
+                right_child: None,
+                payload: Some("{}".to_string()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunked_payload_resumes() {
+        let db = Database::open_in_memory().unwrap();
+        let pims = FakePims {
+            drop_chunk: RefCell::new(Some(2)),
+            ..Default::default()
+        };
+        let engine = SyncEngine::new(&db, &pims).with_chunk_size(2);
+        let protocol = engine.negotiate().unwrap();
+        let payload = SyncPayload {
+            nodes: make_nodes(5),
+            expected_root: "root-1".to_string(),
+            rebase: false,
+        };
+
+        // Cut off at the last chunk: progress points at it
+        assert!(matches!(
+            engine.deliver(&payload, &protocol),
+            Err(MerkleError::Transport(TransportError::Connection(_)))
+        ));
+        let manager = SyncManager::new(&db);
+        let progress = manager.get_chunk_progress().unwrap().unwrap();
+        assert_eq!(
+            (progress.next_index, progress.continuation.as_str()),
+            (2, "upload-root-1")
+        );
+
+        // The retry sends only what's left
+        let (ack, sent) = engine.deliver(&payload, &protocol).unwrap();
+        assert!(ack.success);
+        assert_eq!(sent, 1);
+        assert_eq!(*pims.chunks.borrow(), vec![0, 1, 2]);
+        assert_eq!(pims.nodes.borrow().len(), 5);
+        assert_eq!(manager.get_chunk_progress().unwrap(), None);
+
+        // A PIMS that lost the upload gets it again from the start
+        *pims.drop_chunk.borrow_mut() = Some(1);
+        engine.deliver(&payload, &protocol).unwrap_err();
+        pims.upload.borrow_mut().1 = None;
+        pims.chunks.borrow_mut().clear();
+        let (_, sent) = engine.deliver(&payload, &protocol).unwrap();
+        assert_eq!(sent, 4);
+        assert_eq!(*pims.chunks.borrow(), vec![1, 0, 1, 2]);
+
+        // Small payloads, and PIMSes without chunking, get one request
+        let small = SyncPayload {
+            nodes: make_nodes(2),
+            ..payload.clone()
+        };
+        assert_eq!(engine.deliver(&small, &protocol).unwrap().1, 0);
+        let legacy = NegotiatedProtocol {
+            version: 1,
+            features: vec![],
+        };
+        assert_eq!(engine.deliver(&payload, &legacy).unwrap().1, 0);
+    }
+
+    #[test]
+    fn test_chunked_progress_resets_for_new_payload() {
+        let db = Database::open_in_memory().unwrap();
+        let pims = FakePims {
+            drop_chunk: RefCell::new(Some(1)),
+            ..Default::default()
+        };
+        let engine = SyncEngine::new(&db, &pims).with_chunk_size(1);
+        let protocol = engine.negotiate().unwrap();
+        let payload = SyncPayload {
+            nodes: make_nodes(2),
+            expected_root: "root-1".to_string(),
+            rebase: false,
+        };
+        engine.deliver(&payload, &protocol).unwrap_err();
+
+        // A commit since changes the root: the stale upload is abandoned
+        let newer = SyncPayload {
+            nodes: make_nodes(3),
+            expected_root: "root-2".to_string(),
+            rebase: false,
+        };
+        let (ack, sent) = engine.deliver(&newer, &protocol).unwrap();
+        assert_eq!(ack.new_root.as_deref(), Some("root-2"));
+        assert_eq!(sent, 3);
+    }
+
+    #[test]
+    fn test_pull_patients() {
+        let db = Database::open_in_memory().unwrap();
+        let pims = FakePims::default();
+        let engine = SyncEngine::new(&db, &pims);
+
+        assert_eq!(engine.pull_patients().unwrap().created, 1);
+        let bella = db.get_patient_by_server_id("srv-Bella").unwrap().unwrap();
+        assert_eq!(bella.weight_kg, Some(4.2));
+        // The next pull asks only for changes since this one
+        assert_eq!(
+            engine.pull_patients().unwrap(),
+            PatientDeltaReport::default()
+        );
+        assert_eq!(db.list_patients().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_process_outbox_retries_until_online() {
+        let db = Database::open_in_memory().unwrap();
+        let now = Utc::now();
+        let policy = RetryPolicy::default();
+        let patient = Patient::new("Max".to_string(), "canine".to_string());
+        db.insert_patient(&patient).unwrap();
+        db.enqueue_outbox(OutboxKind::PatientUpsert, &patient.local_id, now)
+            .unwrap();
+        db.enqueue_outbox(OutboxKind::PatientUpsert, "deleted-patient", now)
+            .unwrap();
+        let commit = MerkleTree::new(&db)
+            .commit_encounter(&make_encounter("draft-1"))
+            .unwrap();
+        db.enqueue_outbox(OutboxKind::EncounterPush, &commit.leaf_hash, now)
+            .unwrap();
+
+        let offline = FakePims {
+            offline: true,
+            ..Default::default()
+        };
+        let report = SyncEngine::new(&db, &offline)
+            .process_outbox(now, &policy)
+            .unwrap();
+        assert_eq!(report.succeeded, 1); // the deleted patient
+        assert_eq!(report.failed, 2);
+        let retry_at =
+            (now + policy.delay_after(1)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        assert_eq!(report.next_attempt_at.as_deref(), Some(retry_at.as_str()));
+        let queued = db.list_outbox().unwrap();
+        assert!(queued.iter().all(|item| item.attempts == 1));
+        assert_eq!(
+            queued[0].last_error.as_deref(),
+            Some("Connection failed: offline")
+        );
+        let status = SyncManager::new(&db).get_sync_status().unwrap();
+        assert_eq!((status.outbox_pending, status.unsynced_leaf_count), (2, 1));
+        assert_eq!(
+            status.last_error.as_deref(),
+            Some("Connection failed: offline")
+        );
+
+        // Nothing is due until the backoff elapses
+        let online = FakePims::default();
+        let engine = SyncEngine::new(&db, &online);
+        let report = engine.process_outbox(now, &policy).unwrap();
+        assert_eq!(
+            report,
+            OutboxReport {
+                next_attempt_at: Some(retry_at),
+                ..Default::default()
+            }
+        );
+        let later = now + policy.delay_after(1);
+        let report = engine.process_outbox(later, &policy).unwrap();
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(report.next_attempt_at, None);
+        assert!(db.list_outbox().unwrap().is_empty());
+        let linked = db.get_patient(&patient.local_id).unwrap().unwrap();
+        assert_eq!(linked.server_id.as_deref(), Some("srv-Max"));
+        assert!(!SyncManager::new(&db).has_unsynced_changes().unwrap());
+        let status = SyncManager::new(&db).get_sync_status().unwrap();
+        assert_eq!(status.last_error, None);
+        assert_eq!((status.outbox_pending, status.unsynced_leaf_count), (0, 0));
+        assert!(status.server_root_matches);
+        assert!(status.encounters_last_sync.is_some());
+    }
+}
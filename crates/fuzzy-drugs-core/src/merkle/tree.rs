@@ -4,7 +4,7 @@ use sha2::{Digest, Sha256};
 use thiserror::Error;
 
 use crate::db::Database;
-use crate::models::ReviewedEncounter;
+use crate::models::{AuditEvent, ReviewedEncounter};
 
 use super::proof::MerkleProof;
 
@@ -22,6 +22,21 @@ pub enum MerkleError {
 
     #[error("Invalid tree state: {0}")]
     InvalidState(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Invalid sync chunk: {0}")]
+    InvalidChunk(String),
+
+    #[error(transparent)]
+    Cancelled(#[from] crate::progress::Cancelled),
+
+    #[error(transparent)]
+    Transport(#[from] super::TransportError),
+
+    #[error("PIMS tree diverged (server root {server_root}); resolve the sync conflict")]
+    SyncConflict { server_root: String },
 }
 
 pub type MerkleResult<T> = Result<T, MerkleError>;
@@ -53,25 +68,90 @@ impl<'a> MerkleTree<'a> {
     }
 
     /// Commit a reviewed encounter to the tree (append-only).
+    ///
+    /// The encounter is stamped with this device's ID, its patient's PIMS
+    /// ID (once the patient is linked), and the reviewer's credentials (if
+    /// `reviewed_by` names a user) unless it already carries them, so all
+    /// of them are part of the leaf hash.
     pub fn commit_encounter(&self, encounter: &ReviewedEncounter) -> MerkleResult<LeafCommit> {
+        let mut encounter = encounter.clone();
+        if encounter.device_id.is_none() {
+            encounter.device_id = Some(self.db.device_id()?);
+        }
+        if encounter.prescriber.is_none() {
+            encounter.prescriber = self
+                .db
+                .find_reviewer(&encounter.reviewed_by)?
+                .map(|user| user.prescriber());
+        }
+        if encounter.patient_server_id.is_none() {
+            encounter.patient_server_id = self
+                .db
+                .get_patient(&encounter.patient_id)?
+                .and_then(|p| p.server_id);
+        }
+
         // 1. Serialize encounter to canonical JSON
         let payload = encounter.to_canonical_json()?;
+        self.db
+            .limits()
+            .check_leaf_payload(&payload, encounter.line_items.len())
+            .map_err(MerkleError::PayloadTooLarge)?;
+
+        self.commit_payload(payload)
+    }
+
+    /// Commit an audit event (e.g., legal hold placed/released) as a leaf.
+    pub fn commit_audit_event(&self, event: &AuditEvent) -> MerkleResult<LeafCommit> {
+        let mut event = event.clone();
+        if event.device_id.is_none() {
+            event.device_id = Some(self.db.device_id()?);
+        }
+
+        let payload = event.to_canonical_json()?;
+        self.db
+            .limits()
+            .check_leaf_payload(&payload, 0)
+            .map_err(MerkleError::PayloadTooLarge)?;
+
+        self.commit_payload(payload)
+    }
 
+    /// Hashes of leaves holding encounters (audit leaves excluded), in order.
+    pub fn encounter_leaf_hashes(&self) -> MerkleResult<Vec<String>> {
+        let mut hashes = Vec::new();
+        for hash in self.db.get_all_leaf_hashes()? {
+            let payload = self.get_leaf_payload(&hash)?.unwrap_or_default();
+            if is_encounter_payload(&payload) {
+                hashes.push(hash);
+            }
+        }
+        Ok(hashes)
+    }
+
+    /// Commit result for a leaf already in the tree, against the current root.
+    pub fn leaf_commit(&self, leaf_hash: &str) -> MerkleResult<LeafCommit> {
+        let root_state = self.db.get_merkle_root()?;
+        let proof = self.generate_proof(leaf_hash)?;
+        Ok(LeafCommit {
+            leaf_hash: leaf_hash.to_string(),
+            root_hash: root_state.root_hash.unwrap_or_default(),
+            proof,
+            tree_height: root_state.tree_height,
+            leaf_count: root_state.leaf_count,
+        })
+    }
+
+    /// Append a serialized leaf and rebuild the tree.
+    fn commit_payload(&self, payload: String) -> MerkleResult<LeafCommit> {
         // 2. Create leaf hash
         let leaf_hash = hash_data(payload.as_bytes());
 
         // 3. Check if leaf already exists (idempotency)
         if self.db.merkle_node_exists(&leaf_hash)? {
             // Leaf already committed, return existing state
-            let root_state = self.db.get_merkle_root()?;
-            let proof = self.generate_proof(&leaf_hash)?;
-            return Ok(LeafCommit {
-                leaf_hash,
-                root_hash: root_state.root_hash.unwrap_or_default(),
-                proof,
-                tree_height: root_state.tree_height,
-                leaf_count: root_state.leaf_count,
-            });
+            tracing::debug!(leaf_hash = %leaf_hash, "Leaf already committed");
+            return self.leaf_commit(&leaf_hash);
         }
 
         // 4. Insert leaf node
@@ -89,6 +169,12 @@ impl<'a> MerkleTree<'a> {
 
         // 8. Generate proof for the new leaf
         let proof = self.generate_proof(&leaf_hash)?;
+        tracing::info!(
+            leaf_hash = %leaf_hash,
+            root_hash = %new_root,
+            leaf_count = all_leaves.len(),
+            "Committed leaf"
+        );
 
         Ok(LeafCommit {
             leaf_hash,
@@ -101,9 +187,11 @@ impl<'a> MerkleTree<'a> {
 
     /// Build/rebuild the tree from a list of leaf hashes.
     /// Returns (root_hash, height).
-    fn build_tree(&self, leaves: &[String]) -> MerkleResult<(String, u32)> {
+    pub(super) fn build_tree(&self, leaves: &[String]) -> MerkleResult<(String, u32)> {
         if leaves.is_empty() {
-            return Err(MerkleError::InvalidState("Cannot build tree with no leaves".into()));
+            return Err(MerkleError::InvalidState(
+                "Cannot build tree with no leaves".into(),
+            ));
         }
 
         if leaves.len() == 1 {
@@ -121,20 +209,15 @@ impl<'a> MerkleTree<'a> {
                 let left = &chunk[0];
                 let right = chunk.get(1);
 
-                let parent_hash = if let Some(r) = right {
-                    // Hash both children
-                    let combined = format!("{}{}", left, r);
-                    hash_data(combined.as_bytes())
-                } else {
-                    // Odd node - promote with self-hash
-                    let combined = format!("{}{}", left, left);
-                    hash_data(combined.as_bytes())
-                };
+                let parent_hash = parent_hash(left, right.map(String::as_str));
 
                 // Insert internal node if it doesn't exist
                 if !self.db.merkle_node_exists(&parent_hash)? {
-                    self.db
-                        .insert_merkle_internal(&parent_hash, left, right.map(|s| s.as_str()))?;
+                    self.db.insert_merkle_internal(
+                        &parent_hash,
+                        left,
+                        right.map(|s| s.as_str()),
+                    )?;
                 }
 
                 next_level.push(parent_hash);
@@ -234,6 +317,17 @@ impl<'a> MerkleTree<'a> {
         let node = self.db.get_merkle_node(hash)?;
         Ok(node.and_then(|n| n.payload))
     }
+
+    /// Whether `hash` was the root after one of our earlier commits, i.e. a
+    /// tree holding `hash` is behind ours rather than forked from it.
+    pub fn is_earlier_root(&self, hash: &str) -> MerkleResult<bool> {
+        // Earlier roots are all still stored as nodes
+        if !self.db.merkle_node_exists(hash)? {
+            return Ok(false);
+        }
+        let leaves = self.db.get_all_leaf_hashes()?;
+        Ok((1..leaves.len()).any(|count| root_of(&leaves[..count]) == hash))
+    }
 }
 
 /// Tree statistics.
@@ -244,6 +338,11 @@ pub struct TreeStats {
     pub leaf_count: u32,
 }
 
+/// Whether a leaf payload is an encounter (as opposed to an audit event).
+pub fn is_encounter_payload(payload: &str) -> bool {
+    AuditEvent::from_payload(payload).is_none()
+}
+
 /// Compute SHA-256 hash of data.
 pub fn hash_data(data: &[u8]) -> String {
     let mut hasher = Sha256::new();
@@ -252,14 +351,31 @@ pub fn hash_data(data: &[u8]) -> String {
     hex::encode(result)
 }
 
+/// Hash of an internal node. An odd node is promoted with a self-hash.
+fn parent_hash(left: &str, right: Option<&str>) -> String {
+    let combined = format!("{}{}", left, right.unwrap_or(left));
+    hash_data(combined.as_bytes())
+}
+
+/// Root of a tree over `leaves` (at least one), computed without storing
+/// any nodes.
+fn root_of(leaves: &[String]) -> String {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| parent_hash(&pair[0], pair.get(1).map(String::as_str)))
+            .collect();
+    }
+    level.swap_remove(0)
+}
+
 /// Verify a Merkle proof (standalone function for external use).
 pub fn verify_proof(proof: &MerkleProof) -> bool {
     let mut current_hash = proof.leaf_hash.clone();
 
-    for (sibling_hash, sibling_on_right) in proof
-        .proof_hashes
-        .iter()
-        .zip(proof.proof_directions.iter())
+    for (sibling_hash, sibling_on_right) in
+        proof.proof_hashes.iter().zip(proof.proof_directions.iter())
     {
         let combined = if *sibling_on_right {
             format!("{}{}", current_hash, sibling_hash)
@@ -275,7 +391,7 @@ pub fn verify_proof(proof: &MerkleProof) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{EncounterLineItem, ResolutionMethod};
+    use crate::models::{EncounterLineItem, HoldSubject, LegalHold, ResolutionMethod};
 
     fn setup_db() -> Database {
         Database::open_in_memory().unwrap()
@@ -291,14 +407,27 @@ mod tests {
                 sku: "SKU001".to_string(),
                 name: "Test Drug".to_string(),
                 quantity: 10.0,
-                unit: "mg".to_string(),
+                unit: "mg".into(),
                 route: Some("PO".to_string()),
                 original_mention: "10mg test drug PO".to_string(),
                 resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+                controlled_schedule: None,
+                source_spans: vec![],
+                schedule: None,
+                disposition: None,
+                lot_number: None,
+                expiration_date: None,
+                withdrawal: None,
+                escalation_approval: None,
+                controlled_confirmed_by: None,
+                allergy_overridden_by: None,
             }],
             reviewed_by: "Dr. Smith".to_string(),
             reviewed_at: "2024-01-15T10:00:00Z".to_string(),
             notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: None,
         }
     }
 
@@ -346,6 +475,28 @@ mod tests {
         assert_eq!(commit3.tree_height, 3); // 3 leaves need height 3
     }
 
+    #[test]
+    fn test_audit_leaves_excluded_from_encounters() {
+        let db = setup_db();
+        let tree = MerkleTree::new(&db);
+
+        tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        let hold = LegalHold::new(
+            HoldSubject::Encounter,
+            "draft-1".to_string(),
+            "Dispute".to_string(),
+            "Dr. Smith".to_string(),
+        );
+        let commit = tree
+            .commit_audit_event(&AuditEvent::legal_hold_placed(&hold))
+            .unwrap();
+
+        // Audit leaves are part of the tree but not encounters
+        assert_eq!(commit.leaf_count, 2);
+        assert!(tree.verify_proof(&commit.proof));
+        assert_eq!(tree.encounter_leaf_hashes().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_idempotent_commit() {
         let db = setup_db();
@@ -419,6 +570,87 @@ mod tests {
 
         assert_eq!(recovered.draft_id, "draft-1");
         assert_eq!(recovered.reviewed_by, "Dr. Smith");
+        assert_eq!(recovered.device_id, Some(db.device_id().unwrap()));
+    }
+
+    #[test]
+    fn test_commit_stamps_prescriber() {
+        let db = setup_db();
+        let tree = MerkleTree::new(&db);
+        let unknown = tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+
+        let mut smith = crate::models::User::new("jsmith".into(), "Dr. Smith".into());
+        smith.dea_number = Some("AB1234563".into());
+        db.upsert_user(&smith).unwrap();
+        let known = tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+
+        let prescriber = |leaf_hash: &str| {
+            let payload = tree.get_leaf_payload(leaf_hash).unwrap().unwrap();
+            serde_json::from_str::<ReviewedEncounter>(&payload)
+                .unwrap()
+                .prescriber
+        };
+        // Reviewers without a user record commit as before
+        assert_eq!(prescriber(&unknown.leaf_hash), None);
+        assert_eq!(prescriber(&known.leaf_hash), Some(smith.prescriber()));
+    }
+
+    #[test]
+    fn test_commit_backfills_patient_server_id() {
+        let db = setup_db();
+        let tree = MerkleTree::new(&db);
+        let patient = crate::models::Patient::new("Max".into(), "canine".into());
+        db.insert_patient(&patient).unwrap();
+        let mut encounter = make_encounter("draft-1");
+        encounter.patient_id = patient.local_id.clone();
+
+        let before = tree.commit_encounter(&encounter).unwrap();
+        db.link_patient_server_id(&patient.local_id, "srv-1")
+            .unwrap();
+        encounter.draft_id = "draft-2".into();
+        let after = tree.commit_encounter(&encounter).unwrap();
+
+        let server_id = |leaf_hash: &str| {
+            let payload = tree.get_leaf_payload(leaf_hash).unwrap().unwrap();
+            serde_json::from_str::<ReviewedEncounter>(&payload)
+                .unwrap()
+                .patient_server_id
+        };
+        assert_eq!(server_id(&before.leaf_hash), None);
+        assert_eq!(server_id(&after.leaf_hash).as_deref(), Some("srv-1"));
+    }
+
+    #[test]
+    fn test_oversized_payload_rejected() {
+        let mut db = setup_db();
+        db.set_limits(crate::limits::Limits {
+            max_leaf_payload_bytes: 256,
+            ..Default::default()
+        });
+        let tree = MerkleTree::new(&db);
+
+        let mut encounter = make_encounter("draft-1");
+        encounter.transcript = "x".repeat(512);
+        let result = tree.commit_encounter(&encounter);
+
+        assert!(matches!(result, Err(MerkleError::PayloadTooLarge(_))));
+        assert_eq!(tree.get_stats().unwrap().leaf_count, 0);
+    }
+
+    #[test]
+    fn test_is_earlier_root() {
+        let db = setup_db();
+        let tree = MerkleTree::new(&db);
+        let first = tree.commit_encounter(&make_encounter("draft-1")).unwrap();
+        let second = tree.commit_encounter(&make_encounter("draft-2")).unwrap();
+        let third = tree.commit_encounter(&make_encounter("draft-3")).unwrap();
+
+        assert!(tree.is_earlier_root(&first.root_hash).unwrap());
+        assert!(tree.is_earlier_root(&second.root_hash).unwrap());
+        assert!(!tree.is_earlier_root(&third.root_hash).unwrap());
+        // A stored node that was never a root
+        assert!(!tree.is_earlier_root(&third.leaf_hash).unwrap());
+        assert!(!tree.is_earlier_root("unknown").unwrap());
     }
 
     #[test]
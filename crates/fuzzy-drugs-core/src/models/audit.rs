@@ -0,0 +1,125 @@
+//! Audit events committed to the Merkle tree alongside encounters.
+//!
+//! Encounter leaves carry a `ReviewedEncounter`; audit leaves carry an
+//! `AuditEvent`. Readers that only want encounters skip audit leaves with
+//! [`AuditEvent::from_payload`].
+
+use serde::{Deserialize, Serialize};
+
+use super::legal_hold::{HoldSubject, LegalHold};
+
+/// Kind of audited action.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A legal hold was placed
+    LegalHoldPlaced,
+    /// A legal hold was released
+    LegalHoldReleased,
+    /// An uncommitted draft was discarded
+    DraftDiscarded,
+}
+
+/// An audited action, stored as a Merkle leaf payload.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEvent {
+    /// What happened
+    pub audit_action: AuditAction,
+    /// Kind of record acted on
+    pub subject_type: HoldSubject,
+    /// Patient local ID or encounter draft ID
+    pub subject_id: String,
+    /// Who performed the action
+    pub actor: String,
+    /// Stated reason
+    pub reason: String,
+    /// When the action happened
+    pub recorded_at: String,
+    /// Device that committed the event (stamped at commit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+}
+
+impl AuditEvent {
+    /// Event for placing a legal hold.
+    pub fn legal_hold_placed(hold: &LegalHold) -> Self {
+        Self {
+            audit_action: AuditAction::LegalHoldPlaced,
+            subject_type: hold.subject_type,
+            subject_id: hold.subject_id.clone(),
+            actor: hold.placed_by.clone(),
+            reason: hold.reason.clone(),
+            recorded_at: hold.placed_at.clone(),
+            device_id: None,
+        }
+    }
+
+    /// Event for releasing a legal hold.
+    pub fn legal_hold_released(
+        subject_type: HoldSubject,
+        subject_id: String,
+        released_by: String,
+        reason: String,
+    ) -> Self {
+        Self {
+            audit_action: AuditAction::LegalHoldReleased,
+            subject_type,
+            subject_id,
+            actor: released_by,
+            reason,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            device_id: None,
+        }
+    }
+
+    /// Event for discarding an uncommitted draft.
+    pub fn draft_discarded(draft_id: String, discarded_by: String, reason: String) -> Self {
+        Self {
+            audit_action: AuditAction::DraftDiscarded,
+            subject_type: HoldSubject::Encounter,
+            subject_id: draft_id,
+            actor: discarded_by,
+            reason,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            device_id: None,
+        }
+    }
+
+    /// Serialize to canonical JSON for hashing.
+    pub fn to_canonical_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Parse a leaf payload, returning `None` for non-audit (encounter) leaves.
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        serde_json::from_str(payload).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_payload_distinguished_from_encounter() {
+        let hold = LegalHold::new(
+            HoldSubject::Patient,
+            "patient-1".into(),
+            "Claim #1234".into(),
+            "Dr. Smith".into(),
+        );
+        let event = AuditEvent::legal_hold_placed(&hold);
+        let json = event.to_canonical_json().unwrap();
+        assert!(json.contains("\"audit_action\":\"legal_hold_placed\""));
+        assert_eq!(AuditEvent::from_payload(&json), Some(event));
+
+        let encounter = r#"{"draft_id":"d","patient_id":"p","patient_server_id":null,"transcript":"","line_items":[],"reviewed_by":"v","reviewed_at":"t","notes":null}"#;
+        assert!(AuditEvent::from_payload(encounter).is_none());
+
+        let discarded =
+            AuditEvent::draft_discarded("draft-1".into(), "Dr. Smith".into(), "Duplicate".into());
+        let json = discarded.to_canonical_json().unwrap();
+        assert!(json.contains("\"audit_action\":\"draft_discarded\""));
+        assert_eq!(discarded.subject_type, HoldSubject::Encounter);
+    }
+}
@@ -2,6 +2,10 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::dose::DoseExpression;
+use super::vocab::{Route, Species, Unit};
+use super::withdrawal::WithdrawalTime;
+
 /// A single item in the veterinary inventory catalog.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CatalogItem {
@@ -21,12 +25,214 @@ pub struct CatalogItem {
     pub routes: Vec<String>,
     /// Typical dose range for validation
     pub dose_range: Option<DoseRange>,
+    /// Meat/milk withdrawal periods by food-animal species
+    #[serde(default)]
+    pub withdrawal_times: Vec<WithdrawalTime>,
     /// Whether this item is currently active in inventory
     pub active: bool,
     /// PIMS server ID for sync
     pub server_id: Option<String>,
     /// Last sync timestamp
     pub last_synced: Option<String>,
+    /// Active ingredients of a combination product (e.g., ["amoxicillin", "clavulanate"])
+    #[serde(default)]
+    pub components: Vec<String>,
+    /// DEA controlled substance schedule (None if not controlled)
+    #[serde(default)]
+    pub controlled_schedule: Option<ControlledSchedule>,
+    /// Client price per dispensing unit (tablet, capsule, mL); per
+    /// administration for items without a dispensing breakdown
+    #[serde(default)]
+    pub unit_price: Option<f64>,
+    /// Markup on `unit_price` as a fraction (0.25 = 25%)
+    #[serde(default)]
+    pub markup: Option<f64>,
+    /// Smallest charge for a line of this item (e.g. a dispensing minimum)
+    #[serde(default)]
+    pub minimum_charge: Option<f64>,
+    /// Sales tax code, looked up in the clinic's tax rates (None if untaxed)
+    #[serde(default)]
+    pub tax_code: Option<String>,
+    /// Where the item was first created
+    #[serde(default)]
+    pub origin: CatalogOrigin,
+    /// Changed on this device since the last catalog push
+    #[serde(default)]
+    pub dirty: bool,
+}
+
+/// Where a catalog item was first created.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogOrigin {
+    /// Came down in a PIMS catalog delta (or an import)
+    #[default]
+    Pims,
+    /// Added at the clinic (e.g. a house-compounded item)
+    Local,
+}
+
+impl CatalogOrigin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CatalogOrigin::Pims => "pims",
+            CatalogOrigin::Local => "local",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "pims" => Some(CatalogOrigin::Pims),
+            "local" => Some(CatalogOrigin::Local),
+            _ => None,
+        }
+    }
+}
+
+/// How a catalog item is charged to the client.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Pricing {
+    /// Price per dispensing unit before markup
+    pub unit_price: Option<f64>,
+    /// Markup on `unit_price` as a fraction
+    pub markup: Option<f64>,
+    /// Smallest charge for a line
+    pub minimum_charge: Option<f64>,
+    /// Sales tax code
+    pub tax_code: Option<String>,
+}
+
+impl Pricing {
+    /// Price per unit after markup.
+    pub fn marked_up_unit_price(&self) -> Option<f64> {
+        self.unit_price
+            .map(|price| price * (1.0 + self.markup.unwrap_or(0.0)))
+    }
+
+    /// Charge for `quantity` units: the marked-up unit price times the
+    /// quantity, raised to the minimum charge and rounded to cents (see
+    /// [`round_cents`]), so totals are sums of what each line shows. `None`
+    /// if the item has neither a price nor a minimum.
+    pub fn charge(&self, quantity: f64) -> Option<f64> {
+        let extended = self.marked_up_unit_price().map(|price| price * quantity);
+        let charge = match (extended, self.minimum_charge) {
+            (Some(extended), Some(minimum)) => Some(extended.max(minimum)),
+            (extended, minimum) => extended.or(minimum),
+        };
+        charge.map(round_cents)
+    }
+}
+
+/// Round a money amount to cents, halves away from zero ($0.125 → $0.13).
+///
+/// The nudge keeps amounts like 1.005, which are a hair under the half in
+/// binary, rounding the way they read.
+pub fn round_cents(amount: f64) -> f64 {
+    let cents = amount * 100.0;
+    (cents + 1e-7 * cents.signum()).round() / 100.0
+}
+
+/// Minimal catalog match for type-ahead pickers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CatalogSuggestion {
+    /// SKU
+    pub sku: String,
+    /// Product name
+    pub name: String,
+    /// Strength/concentration (e.g., "100mg", "10mg/mL")
+    pub strength: Option<String>,
+}
+
+/// Outcome of a bulk catalog upsert.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CatalogUpsertReport {
+    /// Items written
+    pub upserted: usize,
+    /// Items skipped, in input order
+    pub failures: Vec<CatalogUpsertFailure>,
+}
+
+/// A catalog item a bulk upsert couldn't write.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CatalogUpsertFailure {
+    pub sku: String,
+    pub error: String,
+}
+
+impl CatalogUpsertReport {
+    /// "2 catalog item(s) failed: A (reason); B (reason)", or None if every
+    /// item was written.
+    pub fn failure_summary(&self) -> Option<String> {
+        if self.failures.is_empty() {
+            return None;
+        }
+        let failures: Vec<String> = self
+            .failures
+            .iter()
+            .map(|f| format!("{} ({})", f.sku, f.error))
+            .collect();
+        Some(format!(
+            "{} catalog item(s) failed: {}",
+            failures.len(),
+            failures.join("; ")
+        ))
+    }
+}
+
+/// DEA controlled substance schedule.
+///
+/// Schedule I substances have no accepted medical use and never appear in a
+/// clinic catalog, so only C-II through C-V are represented.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ControlledSchedule {
+    /// High abuse potential (e.g., fentanyl, hydromorphone)
+    #[serde(rename = "C-II")]
+    CII,
+    /// Moderate abuse potential (e.g., ketamine, buprenorphine)
+    #[serde(rename = "C-III")]
+    CIII,
+    /// Low abuse potential (e.g., diazepam, butorphanol, tramadol)
+    #[serde(rename = "C-IV")]
+    CIV,
+    /// Lowest abuse potential (e.g., pregabalin)
+    #[serde(rename = "C-V")]
+    CV,
+}
+
+impl ControlledSchedule {
+    /// DEA notation ("C-II" ... "C-V").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ControlledSchedule::CII => "C-II",
+            ControlledSchedule::CIII => "C-III",
+            ControlledSchedule::CIV => "C-IV",
+            ControlledSchedule::CV => "C-V",
+        }
+    }
+
+    /// Parse a schedule ("C-II", "CII", "II", "2", "schedule 2", ...).
+    pub fn parse(s: &str) -> Option<Self> {
+        let cleaned: String = s
+            .to_uppercase()
+            .replace("SCHEDULE", "")
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect();
+        let numeral = cleaned.strip_prefix('C').unwrap_or(&cleaned);
+        match numeral {
+            "II" | "2" => Some(ControlledSchedule::CII),
+            "III" | "3" => Some(ControlledSchedule::CIII),
+            "IV" | "4" => Some(ControlledSchedule::CIV),
+            "V" | "5" => Some(ControlledSchedule::CV),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ControlledSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Dose range for plausibility checking.
@@ -36,8 +242,8 @@ pub struct DoseRange {
     pub min_dose_per_kg: f64,
     /// Maximum dose per kg body weight
     pub max_dose_per_kg: f64,
-    /// Unit for dose (e.g., "mg", "mL")
-    pub unit: String,
+    /// Unit for dose (e.g., "mg", "mL"), per kg
+    pub unit: Unit,
 }
 
 impl CatalogItem {
@@ -52,21 +258,79 @@ impl CatalogItem {
             species: Vec::new(),
             routes: Vec::new(),
             dose_range: None,
+            withdrawal_times: Vec::new(),
             active: true,
             server_id: None,
             last_synced: None,
+            components: Vec::new(),
+            controlled_schedule: None,
+            unit_price: None,
+            markup: None,
+            minimum_charge: None,
+            tax_code: None,
+            origin: CatalogOrigin::Pims,
+            dirty: false,
         }
     }
 
+    /// Check the SKU and name are present and the withdrawal times are
+    /// valid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sku.trim().is_empty() {
+            return Err("Catalog item SKU can't be empty".into());
+        }
+        if self.name.trim().is_empty() {
+            return Err(format!("Catalog item {} needs a name", self.sku));
+        }
+        self.withdrawal_times
+            .iter()
+            .try_for_each(WithdrawalTime::validate)
+    }
+
+    /// This item's unit price, markup, minimum charge, and tax code.
+    pub fn pricing(&self) -> Pricing {
+        Pricing {
+            unit_price: self.unit_price,
+            markup: self.markup,
+            minimum_charge: self.minimum_charge,
+            tax_code: self.tax_code.clone(),
+        }
+    }
+
+    /// Whether this item is a DEA controlled substance.
+    pub fn is_controlled(&self) -> bool {
+        self.controlled_schedule.is_some()
+    }
+
+    /// Whether this item is a combination product (two or more components).
+    pub fn is_combination(&self) -> bool {
+        self.component_names().len() > 1
+    }
+
+    /// Lowercased component names.
+    ///
+    /// Uses the explicit `components` list if set, otherwise derives them from
+    /// a combination-style name ("Amoxicillin-Clavulanate 250mg" →
+    /// ["amoxicillin", "clavulanate"]). Single-drug names yield one component.
+    pub fn component_names(&self) -> Vec<String> {
+        if !self.components.is_empty() {
+            return self
+                .components
+                .iter()
+                .map(|c| c.trim().to_lowercase())
+                .filter(|c| !c.is_empty())
+                .collect();
+        }
+        split_components(&self.name)
+    }
+
     /// Check if this item is compatible with a given species.
     pub fn is_species_compatible(&self, species: &str) -> bool {
         if self.species.is_empty() {
             return true; // No restriction means all species
         }
-        let species_lower = species.to_lowercase();
-        self.species
-            .iter()
-            .any(|s| s.to_lowercase() == species_lower)
+        let species = Species::parse(species);
+        self.species.iter().any(|s| Species::parse(s) == species)
     }
 
     /// Check if this item is compatible with a given route.
@@ -74,21 +338,71 @@ impl CatalogItem {
         if self.routes.is_empty() {
             return true; // No restriction means all routes
         }
-        let route_upper = route.to_uppercase();
-        self.routes.iter().any(|r| r.to_uppercase() == route_upper)
+        let route = Route::parse(route);
+        self.routes.iter().any(|r| Route::parse(r) == route)
+    }
+
+    /// Withdrawal periods for a patient of `species`; `None` unless it is a
+    /// food animal the item has periods for.
+    pub fn withdrawal_time(&self, species: &str) -> Option<&WithdrawalTime> {
+        let species = Species::parse(species);
+        if !species.is_food_animal() {
+            return None;
+        }
+        self.withdrawal_times
+            .iter()
+            .find(|time| Species::parse(&time.species) == species)
     }
 
     /// Check if a dose is within plausible range for given weight.
-    pub fn is_dose_plausible(&self, dose: f64, unit: &str, weight_kg: f64) -> Option<bool> {
+    pub fn is_dose_plausible(&self, dose: f64, unit: &Unit, weight_kg: f64) -> Option<bool> {
+        self.is_dose_expression_plausible(&DoseExpression::from_dose(dose, unit), Some(weight_kg))
+    }
+
+    /// Check if a dose expression is within the plausible range.
+    ///
+    /// Per-kg doses compare directly; absolute doses need the weight. The
+    /// dose is converted to the range's unit ("mcg" to "mg"). `None` when
+    /// there is no range, the units don't convert (mg and mL), or the dose
+    /// is a rate.
+    pub fn is_dose_expression_plausible(
+        &self,
+        dose: &DoseExpression,
+        weight_kg: Option<f64>,
+    ) -> Option<bool> {
         let range = self.dose_range.as_ref()?;
-        if range.unit.to_lowercase() != unit.to_lowercase() {
-            return None; // Can't compare different units
-        }
-        let dose_per_kg = dose / weight_kg;
+        let dose_per_kg = dose.dose_per_kg(weight_kg)?;
+        let dose_per_kg = dose
+            .amount_unit()
+            .convert(dose_per_kg, &range.unit.amount)?;
         Some(dose_per_kg >= range.min_dose_per_kg && dose_per_kg <= range.max_dose_per_kg)
     }
 }
 
+/// Split a combination drug name into lowercased component names.
+///
+/// Components may be separated by "-", "/", "+", "&", "and", or "with". Only
+/// the text before the first digit is considered and only the leading word of
+/// each part is kept, so strengths ("1.5mg/mL") and dosage forms are dropped.
+pub fn split_components(name: &str) -> Vec<String> {
+    let lower = name.to_lowercase();
+    let drug_part = match lower.find(|c: char| c.is_ascii_digit()) {
+        Some(idx) => &lower[..idx],
+        None => &lower,
+    };
+    let lower = drug_part.replace(" and ", "+").replace(" with ", "+");
+
+    lower
+        .split(['-', '/', '+', '&'])
+        .filter_map(|part| {
+            part.split_whitespace()
+                .next()
+                .filter(|word| word.chars().all(|c| c.is_alphabetic()))
+                .map(|word| word.to_string())
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,6 +415,7 @@ mod tests {
         assert!(item.is_species_compatible("canine"));
         assert!(item.is_species_compatible("Canine"));
         assert!(item.is_species_compatible("feline"));
+        assert!(item.is_species_compatible("dog"));
         assert!(!item.is_species_compatible("equine"));
     }
 
@@ -119,6 +434,11 @@ mod tests {
         assert!(item.is_route_compatible("po"));
         assert!(item.is_route_compatible("IV"));
         assert!(!item.is_route_compatible("IM"));
+
+        // Synonyms match the canonical code
+        item.routes = vec!["SC".into()];
+        assert!(item.is_route_compatible("SQ"));
+        assert!(item.is_route_compatible("subq"));
     }
 
     #[test]
@@ -131,12 +451,105 @@ mod tests {
         });
 
         // 10kg dog, 30mg dose = 3mg/kg (within range)
-        assert_eq!(item.is_dose_plausible(30.0, "mg", 10.0), Some(true));
+        assert_eq!(item.is_dose_plausible(30.0, &"mg".into(), 10.0), Some(true));
 
         // 10kg dog, 100mg dose = 10mg/kg (above range)
-        assert_eq!(item.is_dose_plausible(100.0, "mg", 10.0), Some(false));
+        assert_eq!(
+            item.is_dose_plausible(100.0, &"mg".into(), 10.0),
+            Some(false)
+        );
 
         // Wrong unit
-        assert_eq!(item.is_dose_plausible(30.0, "mL", 10.0), None);
+        assert_eq!(item.is_dose_plausible(30.0, &"mL".into(), 10.0), None);
+
+        // Mass units convert: 30000 mcg is 30 mg
+        assert_eq!(
+            item.is_dose_plausible(30000.0, &"mcg".into(), 10.0),
+            Some(true)
+        );
+        item.dose_range.as_mut().unwrap().unit = "mcg".into();
+        assert_eq!(item.is_dose_plausible(0.003, &"mg".into(), 1.0), Some(true));
+        assert_eq!(
+            item.is_dose_plausible(3.0, &"mg/kg".into(), 1.0),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_component_names() {
+        let item = CatalogItem::new("SKU001".into(), "Amoxicillin-Clavulanate 250mg".into());
+        assert_eq!(item.component_names(), vec!["amoxicillin", "clavulanate"]);
+        assert!(item.is_combination());
+
+        let item = CatalogItem::new("SKU002".into(), "Trimethoprim/Sulfa 480mg tablets".into());
+        assert_eq!(item.component_names(), vec!["trimethoprim", "sulfa"]);
+
+        let item = CatalogItem::new("SKU003".into(), "Carprofen 100mg".into());
+        assert_eq!(item.component_names(), vec!["carprofen"]);
+        assert!(!item.is_combination());
+
+        // Strength-only parts ("1.5mg/mL") are not components
+        let item = CatalogItem::new("SKU004".into(), "Meloxicam 1.5mg/mL".into());
+        assert_eq!(item.component_names(), vec!["meloxicam"]);
+
+        // Explicit components take precedence over the name
+        let mut item = CatalogItem::new("SKU005".into(), "Clavamox 250mg".into());
+        item.components = vec!["Amoxicillin".into(), "Clavulanate".into()];
+        assert_eq!(item.component_names(), vec!["amoxicillin", "clavulanate"]);
+        assert!(item.is_combination());
+    }
+
+    #[test]
+    fn test_controlled_schedule_parse() {
+        assert_eq!(
+            ControlledSchedule::parse("C-II"),
+            Some(ControlledSchedule::CII)
+        );
+        assert_eq!(
+            ControlledSchedule::parse("ciii"),
+            Some(ControlledSchedule::CIII)
+        );
+        assert_eq!(
+            ControlledSchedule::parse("Schedule IV"),
+            Some(ControlledSchedule::CIV)
+        );
+        assert_eq!(ControlledSchedule::parse("5"), Some(ControlledSchedule::CV));
+        assert_eq!(ControlledSchedule::parse("C-I"), None);
+        assert_eq!(ControlledSchedule::parse(""), None);
+
+        assert_eq!(ControlledSchedule::CII.to_string(), "C-II");
+        assert_eq!(
+            serde_json::to_string(&ControlledSchedule::CIV).unwrap(),
+            "\"C-IV\""
+        );
+    }
+
+    #[test]
+    fn test_pricing_charge() {
+        let mut item = CatalogItem::new("CARP-100".into(), "Carprofen 100mg".into());
+        assert_eq!(item.pricing().charge(2.0), None);
+
+        item.unit_price = Some(2.0);
+        assert_eq!(item.pricing().charge(2.0), Some(4.0));
+
+        item.markup = Some(0.5);
+        assert_eq!(item.pricing().marked_up_unit_price(), Some(3.0));
+        assert_eq!(item.pricing().charge(2.0), Some(6.0));
+
+        item.minimum_charge = Some(10.0);
+        assert_eq!(item.pricing().charge(2.0), Some(10.0));
+        assert_eq!(item.pricing().charge(5.0), Some(15.0));
+
+        item.unit_price = None;
+        assert_eq!(item.pricing().charge(5.0), Some(10.0));
+
+        // Each charge is rounded to cents, halves up
+        item.minimum_charge = None;
+        item.markup = None;
+        item.unit_price = Some(0.335);
+        assert_eq!(item.pricing().charge(3.0), Some(1.01));
+        assert_eq!(item.pricing().charge(1.0), Some(0.34));
+        assert_eq!(round_cents(0.125), 0.13);
+        assert_eq!(round_cents(-0.125), -0.13);
     }
 }
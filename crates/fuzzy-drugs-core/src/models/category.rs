@@ -0,0 +1,233 @@
+//! Clinical categories for grouping draft items.
+//!
+//! A surgical encounter can produce a dozen suggestions. Reviewers scan them
+//! faster grouped the way they think about the case (induction drugs, pain
+//! control, antibiotics, fluids, then consumables), so each resolved item is
+//! placed in a category derived from its ingredients.
+
+use serde::{Deserialize, Serialize};
+
+use super::resolution::ResolvedItem;
+
+/// Clinical category of a resolved item.
+///
+/// Variants are declared in display order; `Ord` follows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClinicalCategory {
+    /// Induction, maintenance, sedation, and reversal agents
+    Anesthesia,
+    /// Opioids, NSAIDs, and adjunct analgesics
+    Analgesia,
+    Antibiotics,
+    /// Crystalloids and colloids
+    Fluids,
+    /// Medications outside the other categories
+    Other,
+    /// Consumables (catheters, syringes, bandages)
+    Supplies,
+}
+
+/// Ingredients mapped to their category. Lowercase, matched as whole words.
+const INGREDIENT_CATEGORIES: &[(&str, ClinicalCategory)] = &[
+    // Anesthesia
+    ("propofol", ClinicalCategory::Anesthesia),
+    ("alfaxalone", ClinicalCategory::Anesthesia),
+    ("ketamine", ClinicalCategory::Anesthesia),
+    ("tiletamine", ClinicalCategory::Anesthesia),
+    ("zolazepam", ClinicalCategory::Anesthesia),
+    ("etomidate", ClinicalCategory::Anesthesia),
+    ("isoflurane", ClinicalCategory::Anesthesia),
+    ("sevoflurane", ClinicalCategory::Anesthesia),
+    ("dexmedetomidine", ClinicalCategory::Anesthesia),
+    ("medetomidine", ClinicalCategory::Anesthesia),
+    ("xylazine", ClinicalCategory::Anesthesia),
+    ("acepromazine", ClinicalCategory::Anesthesia),
+    ("midazolam", ClinicalCategory::Anesthesia),
+    ("diazepam", ClinicalCategory::Anesthesia),
+    ("atipamezole", ClinicalCategory::Anesthesia),
+    ("lidocaine", ClinicalCategory::Anesthesia),
+    ("bupivacaine", ClinicalCategory::Anesthesia),
+    ("ropivacaine", ClinicalCategory::Anesthesia),
+    ("glycopyrrolate", ClinicalCategory::Anesthesia),
+    ("atropine", ClinicalCategory::Anesthesia),
+    // Analgesia
+    ("buprenorphine", ClinicalCategory::Analgesia),
+    ("butorphanol", ClinicalCategory::Analgesia),
+    ("methadone", ClinicalCategory::Analgesia),
+    ("morphine", ClinicalCategory::Analgesia),
+    ("hydromorphone", ClinicalCategory::Analgesia),
+    ("fentanyl", ClinicalCategory::Analgesia),
+    ("tramadol", ClinicalCategory::Analgesia),
+    ("gabapentin", ClinicalCategory::Analgesia),
+    ("amantadine", ClinicalCategory::Analgesia),
+    ("carprofen", ClinicalCategory::Analgesia),
+    ("meloxicam", ClinicalCategory::Analgesia),
+    ("deracoxib", ClinicalCategory::Analgesia),
+    ("firocoxib", ClinicalCategory::Analgesia),
+    ("robenacoxib", ClinicalCategory::Analgesia),
+    ("grapiprant", ClinicalCategory::Analgesia),
+    ("ketoprofen", ClinicalCategory::Analgesia),
+    ("flunixin", ClinicalCategory::Analgesia),
+    ("phenylbutazone", ClinicalCategory::Analgesia),
+    // Antibiotics
+    ("amoxicillin", ClinicalCategory::Antibiotics),
+    ("clavulanate", ClinicalCategory::Antibiotics),
+    ("ampicillin", ClinicalCategory::Antibiotics),
+    ("penicillin", ClinicalCategory::Antibiotics),
+    ("cefazolin", ClinicalCategory::Antibiotics),
+    ("cefovecin", ClinicalCategory::Antibiotics),
+    ("cephalexin", ClinicalCategory::Antibiotics),
+    ("cefpodoxime", ClinicalCategory::Antibiotics),
+    ("ceftiofur", ClinicalCategory::Antibiotics),
+    ("enrofloxacin", ClinicalCategory::Antibiotics),
+    ("marbofloxacin", ClinicalCategory::Antibiotics),
+    ("pradofloxacin", ClinicalCategory::Antibiotics),
+    ("doxycycline", ClinicalCategory::Antibiotics),
+    ("minocycline", ClinicalCategory::Antibiotics),
+    ("metronidazole", ClinicalCategory::Antibiotics),
+    ("clindamycin", ClinicalCategory::Antibiotics),
+    ("azithromycin", ClinicalCategory::Antibiotics),
+    ("trimethoprim", ClinicalCategory::Antibiotics),
+    ("sulfamethoxazole", ClinicalCategory::Antibiotics),
+    ("sulfadiazine", ClinicalCategory::Antibiotics),
+    ("gentamicin", ClinicalCategory::Antibiotics),
+    ("amikacin", ClinicalCategory::Antibiotics),
+    ("chloramphenicol", ClinicalCategory::Antibiotics),
+    // Fluids
+    ("lrs", ClinicalCategory::Fluids),
+    ("lactated", ClinicalCategory::Fluids),
+    ("ringer", ClinicalCategory::Fluids),
+    ("ringers", ClinicalCategory::Fluids),
+    ("saline", ClinicalCategory::Fluids),
+    ("nacl", ClinicalCategory::Fluids),
+    ("normosol", ClinicalCategory::Fluids),
+    ("plasmalyte", ClinicalCategory::Fluids),
+    ("hetastarch", ClinicalCategory::Fluids),
+    ("dextrose", ClinicalCategory::Fluids),
+];
+
+/// Words that mark a catalog item as a consumable rather than a drug.
+const SUPPLY_WORDS: &[&str] = &[
+    "catheter", "syringe", "needle", "bandage", "gauze", "suture", "collar", "cone", "gloves",
+    "tape", "drape", "sponge", "wrap",
+];
+
+impl ClinicalCategory {
+    /// All categories, in display order.
+    pub const ALL: [ClinicalCategory; 6] = [
+        ClinicalCategory::Anesthesia,
+        ClinicalCategory::Analgesia,
+        ClinicalCategory::Antibiotics,
+        ClinicalCategory::Fluids,
+        ClinicalCategory::Other,
+        ClinicalCategory::Supplies,
+    ];
+
+    /// Categorize a drug or product name.
+    ///
+    /// The first word with a known ingredient decides the category; names
+    /// with no known ingredient are supplies if they name a consumable.
+    pub fn for_name(name: &str) -> Option<Self> {
+        let lower = name.to_lowercase().replace("plasma-lyte", "plasmalyte");
+        if lower.contains("sodium chloride") {
+            return Some(ClinicalCategory::Fluids);
+        }
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        words
+            .iter()
+            .find_map(|word| {
+                INGREDIENT_CATEGORIES
+                    .iter()
+                    .find(|(ingredient, _)| ingredient == word)
+                    .map(|(_, category)| *category)
+            })
+            .or_else(|| {
+                words
+                    .iter()
+                    .any(|word| SUPPLY_WORDS.contains(word))
+                    .then_some(ClinicalCategory::Supplies)
+            })
+    }
+
+    /// Category of a resolved item.
+    ///
+    /// Uses the spoken drug name, then the chosen candidate (the top
+    /// candidate while pending); uncategorized items are `Other`.
+    pub fn for_item(item: &ResolvedItem) -> Self {
+        let candidate = item.final_candidate().unwrap_or(&item.top_candidate);
+        Self::for_name(&item.mention.normalized_name)
+            .or_else(|| Self::for_name(&candidate.name))
+            .unwrap_or(ClinicalCategory::Other)
+    }
+
+    /// Stable identifier ("anesthesia").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClinicalCategory::Anesthesia => "anesthesia",
+            ClinicalCategory::Analgesia => "analgesia",
+            ClinicalCategory::Antibiotics => "antibiotics",
+            ClinicalCategory::Fluids => "fluids",
+            ClinicalCategory::Other => "other",
+            ClinicalCategory::Supplies => "supplies",
+        }
+    }
+
+    /// Heading shown to reviewers ("Anesthesia").
+    pub fn label(&self) -> &'static str {
+        match self {
+            ClinicalCategory::Anesthesia => "Anesthesia",
+            ClinicalCategory::Analgesia => "Analgesia",
+            ClinicalCategory::Antibiotics => "Antibiotics",
+            ClinicalCategory::Fluids => "Fluids",
+            ClinicalCategory::Other => "Other medications",
+            ClinicalCategory::Supplies => "Supplies",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_name() {
+        assert_eq!(
+            ClinicalCategory::for_name("Propofol 10mg/mL"),
+            Some(ClinicalCategory::Anesthesia)
+        );
+        assert_eq!(
+            ClinicalCategory::for_name("buprenorphine"),
+            Some(ClinicalCategory::Analgesia)
+        );
+        assert_eq!(
+            ClinicalCategory::for_name("Amoxicillin-Clavulanate 250mg"),
+            Some(ClinicalCategory::Antibiotics)
+        );
+        assert_eq!(
+            ClinicalCategory::for_name("Lactated Ringer's 1L"),
+            Some(ClinicalCategory::Fluids)
+        );
+        assert_eq!(
+            ClinicalCategory::for_name("0.9% Sodium Chloride 1L"),
+            Some(ClinicalCategory::Fluids)
+        );
+        assert_eq!(
+            ClinicalCategory::for_name("IV Catheter 22g"),
+            Some(ClinicalCategory::Supplies)
+        );
+        assert_eq!(ClinicalCategory::for_name("maropitant"), None);
+    }
+
+    #[test]
+    fn test_display_order() {
+        let mut categories = ClinicalCategory::ALL.to_vec();
+        categories.reverse();
+        categories.sort();
+        assert_eq!(categories, ClinicalCategory::ALL);
+        assert_eq!(categories.last(), Some(&ClinicalCategory::Supplies));
+    }
+}
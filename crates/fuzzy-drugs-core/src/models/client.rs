@@ -0,0 +1,83 @@
+//! Clients: the owners patients belong to.
+//!
+//! A client can have several patients and is who the clinic bills, so
+//! billing exports group encounters by client. Patients keep their free-text
+//! `owner_name` for records that haven't been linked to a client.
+
+use serde::{Deserialize, Serialize};
+
+/// A clinic client (pet owner) and their contact details.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Client {
+    /// Local UUID
+    pub client_id: String,
+    /// PIMS client ID, if known
+    pub server_id: Option<String>,
+    /// Display name ("Jane Doe")
+    pub name: String,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    /// Mailing address, free text
+    pub address: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Client {
+    /// Create a new client with a fresh local ID.
+    pub fn new(name: String) -> Self {
+        let now = chrono::Utc::now().to_rfc3339();
+        Self {
+            client_id: uuid::Uuid::new_v4().to_string(),
+            server_id: None,
+            name,
+            phone: None,
+            email: None,
+            address: None,
+            notes: None,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    /// Check the name is present and the email address looks like one.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.client_id.trim().is_empty() {
+            return Err("Client ID can't be empty".into());
+        }
+        if self.name.trim().is_empty() {
+            return Err("Client name can't be empty".into());
+        }
+        if let Some(email) = &self.email {
+            let valid = email
+                .trim()
+                .split_once('@')
+                .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'));
+            if !valid {
+                return Err(format!("Invalid email address: {}", email));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_validation() {
+        let mut client = Client::new("Jane Doe".into());
+        assert_eq!(client.client_id.len(), 36);
+        assert!(client.validate().is_ok());
+
+        client.email = Some("jane@example.com".into());
+        assert!(client.validate().is_ok());
+        client.email = Some("jane.example.com".into());
+        assert!(client.validate().is_err());
+        client.email = None;
+        client.name = " ".into();
+        assert!(client.validate().is_err());
+    }
+}
@@ -0,0 +1,53 @@
+//! Device identity for this installation.
+//!
+//! Every database gets a durable identity when it is first opened. Its ID is
+//! stamped into each committed Merkle leaf and into export metadata, so
+//! multi-device sync, signing, and fleet audits can tell installations apart.
+
+use serde::{Deserialize, Serialize};
+
+/// Name given to a device until an admin renames it.
+pub const DEFAULT_DEVICE_NAME: &str = "Unnamed device";
+
+/// Fingerprint of a key held by the device.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyFingerprint {
+    /// What the key is for (e.g., "signing", "sync")
+    pub purpose: String,
+    /// Fingerprint (e.g., SHA-256 of the public key, hex)
+    pub fingerprint: String,
+    /// When the key was registered
+    pub added_at: String,
+}
+
+/// Identity and provisioning record for this installation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeviceIdentity {
+    /// Stable device ID (UUID), never changes once provisioned
+    pub device_id: String,
+    /// Human-readable name (e.g., "Exam Room 2 iPad")
+    pub device_name: String,
+    /// When the device was provisioned
+    pub provisioned_at: String,
+    /// Fingerprints of the device's keys, one per purpose
+    pub key_fingerprints: Vec<KeyFingerprint>,
+}
+
+impl DeviceIdentity {
+    /// Fresh identity with a new device ID.
+    pub fn new(device_name: String) -> Self {
+        Self {
+            device_id: uuid::Uuid::new_v4().to_string(),
+            device_name,
+            provisioned_at: chrono::Utc::now().to_rfc3339(),
+            key_fingerprints: Vec::new(),
+        }
+    }
+
+    /// Fingerprint registered for a purpose, if any.
+    pub fn fingerprint(&self, purpose: &str) -> Option<&KeyFingerprint> {
+        self.key_fingerprints
+            .iter()
+            .find(|k| k.purpose.eq_ignore_ascii_case(purpose))
+    }
+}
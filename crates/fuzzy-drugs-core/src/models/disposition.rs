@@ -0,0 +1,157 @@
+//! Administered-vs-dispensed disposition of line items.
+//!
+//! Billing treats a drug given in clinic differently from a take-home
+//! prescription. Dictation usually says which ("gave in hospital", "sent home
+//! with"), so the disposition is inferred from those cues and confirmed by the
+//! reviewer before commit.
+
+use serde::{Deserialize, Serialize};
+
+/// How a line item reached the patient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DispositionType {
+    /// Given in the clinic during the visit
+    AdministeredInClinic,
+    /// Sent home with the client from clinic stock
+    Dispensed,
+    /// Written as a prescription to be filled elsewhere
+    Prescribed,
+}
+
+/// Transcript cues for each disposition, lowercase. Prescription cues are
+/// checked first ("send a prescription home" is a prescription), then
+/// dispensing, then in-clinic administration.
+const DISPOSITION_CUES: &[(&str, DispositionType)] = &[
+    ("prescription", DispositionType::Prescribed),
+    ("prescribed", DispositionType::Prescribed),
+    ("prescribe", DispositionType::Prescribed),
+    ("script", DispositionType::Prescribed),
+    ("call in", DispositionType::Prescribed),
+    ("called in", DispositionType::Prescribed),
+    ("receta", DispositionType::Prescribed),
+    ("sent home", DispositionType::Dispensed),
+    ("send home", DispositionType::Dispensed),
+    ("sending home", DispositionType::Dispensed),
+    ("go home with", DispositionType::Dispensed),
+    ("going home with", DispositionType::Dispensed),
+    ("take home", DispositionType::Dispensed),
+    ("take-home", DispositionType::Dispensed),
+    ("to go home", DispositionType::Dispensed),
+    ("dispense", DispositionType::Dispensed),
+    ("dispensed", DispositionType::Dispensed),
+    ("para la casa", DispositionType::Dispensed),
+    ("gave", DispositionType::AdministeredInClinic),
+    ("given", DispositionType::AdministeredInClinic),
+    ("administered", DispositionType::AdministeredInClinic),
+    ("injected", DispositionType::AdministeredInClinic),
+    ("in hospital", DispositionType::AdministeredInClinic),
+    ("in clinic", DispositionType::AdministeredInClinic),
+    ("here today", DispositionType::AdministeredInClinic),
+    ("se le dio", DispositionType::AdministeredInClinic),
+    ("se aplicó", DispositionType::AdministeredInClinic),
+];
+
+impl DispositionType {
+    /// Parse "administered", "dispensed", or "prescribed" (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "administered" | "administered_in_clinic" | "in_clinic" => {
+                Some(DispositionType::AdministeredInClinic)
+            }
+            "dispensed" => Some(DispositionType::Dispensed),
+            "prescribed" => Some(DispositionType::Prescribed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DispositionType::AdministeredInClinic => "administered",
+            DispositionType::Dispensed => "dispensed",
+            DispositionType::Prescribed => "prescribed",
+        }
+    }
+
+    /// Infer a disposition from cue phrases in dictated text.
+    pub fn from_cues(text: &str) -> Option<Self> {
+        let words = format!(
+            " {} ",
+            text.to_lowercase()
+                .split(|c: char| !(c.is_alphanumeric() || c == '-'))
+                .filter(|w| !w.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        DISPOSITION_CUES
+            .iter()
+            .find(|(cue, _)| words.contains(&format!(" {} ", cue)))
+            .map(|(_, disposition)| *disposition)
+    }
+
+    /// Infer the disposition of a mention from the sentence around it.
+    ///
+    /// Cues usually sit outside the mention itself ("Sent home with carprofen
+    /// 75mg BID"), so the whole sentence containing `start..end` is checked.
+    pub fn for_span(transcript: &str, start: usize, end: usize) -> Option<Self> {
+        let start = start.min(transcript.len());
+        let end = end.clamp(start, transcript.len());
+        let (Some(before), Some(after)) = (transcript.get(..start), transcript.get(end..)) else {
+            return None;
+        };
+        let sentence_start = before
+            .char_indices()
+            .rev()
+            .find(|&(i, c)| ends_sentence(transcript, i, c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let sentence_end = after
+            .char_indices()
+            .find(|&(i, c)| ends_sentence(transcript, end + i, c))
+            .map_or(transcript.len(), |(i, _)| end + i);
+        Self::from_cues(&transcript[sentence_start..sentence_end])
+    }
+}
+
+/// Whether `c` at byte `i` ends a sentence (so "0.2 mL" doesn't split).
+fn ends_sentence(text: &str, i: usize, c: char) -> bool {
+    let next = text[i + c.len_utf8()..].chars().next();
+    matches!(c, '.' | '!' | '?' | ';' | '\n') && next.is_none_or(|n| n.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_cues() {
+        assert_eq!(
+            DispositionType::from_cues("Sent home with carprofen 75mg BID"),
+            Some(DispositionType::Dispensed)
+        );
+        assert_eq!(
+            DispositionType::from_cues("gave 0.2 mL buprenorphine in hospital"),
+            Some(DispositionType::AdministeredInClinic)
+        );
+        assert_eq!(
+            DispositionType::from_cues("sending a prescription home for gabapentin"),
+            Some(DispositionType::Prescribed)
+        );
+        assert_eq!(DispositionType::from_cues("carprofen 75mg PO"), None);
+        // Whole words only
+        assert_eq!(DispositionType::from_cues("forgave the delay"), None);
+    }
+
+    #[test]
+    fn test_for_span() {
+        let transcript = "Gave 0.5 mL cefazolin IV. Sent home with carprofen 75mg BID.";
+        let start = transcript.find("carprofen").unwrap();
+        assert_eq!(
+            DispositionType::for_span(transcript, start, start + 15),
+            Some(DispositionType::Dispensed)
+        );
+        let start = transcript.find("cefazolin").unwrap();
+        assert_eq!(
+            DispositionType::for_span(transcript, start, start + 9),
+            Some(DispositionType::AdministeredInClinic)
+        );
+    }
+}
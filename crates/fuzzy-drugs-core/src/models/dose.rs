@@ -0,0 +1,124 @@
+//! Dose expressions.
+//!
+//! A dictated dose is one of three things: a fixed amount ("100 mg"), a
+//! weight-based dose ("2 mg/kg"), or a rate ("3 mcg/kg/hr"). They compare
+//! differently against a catalog dose range, so the resolver works with the
+//! expression rather than a bare number and unit.
+
+use super::infusion::InfusionRate;
+use super::vocab::{DoseUnit, Unit};
+
+/// What a dictated dose means.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DoseExpression {
+    /// A fixed amount per administration ("100 mg")
+    Absolute { amount: f64, unit: DoseUnit },
+    /// An amount per kg of body weight per administration ("2 mg/kg")
+    PerKg { amount_per_kg: f64, unit: DoseUnit },
+    /// An amount per time, optionally per kg ("3 mcg/kg/hr", "50 mL/hr")
+    Rate(InfusionRate),
+}
+
+impl DoseExpression {
+    /// Build from a canonical dose and unit ("mg", "mg/kg").
+    ///
+    /// Rates are parsed by the normalizer into an [`InfusionRate`] instead.
+    pub fn from_dose(amount: f64, unit: &Unit) -> Self {
+        if unit.per_kg {
+            DoseExpression::PerKg {
+                amount_per_kg: amount,
+                unit: unit.amount.clone(),
+            }
+        } else {
+            DoseExpression::Absolute {
+                amount,
+                unit: unit.amount.clone(),
+            }
+        }
+    }
+
+    /// Amount unit, without the per-kg or per-time parts ("mg").
+    pub fn amount_unit(&self) -> DoseUnit {
+        match self {
+            DoseExpression::Absolute { unit, .. } | DoseExpression::PerKg { unit, .. } => {
+                unit.clone()
+            }
+            DoseExpression::Rate(rate) => DoseUnit::parse(&rate.unit),
+        }
+    }
+
+    /// Whether the dose scales with body weight.
+    pub fn is_weight_based(&self) -> bool {
+        match self {
+            DoseExpression::Absolute { .. } => false,
+            DoseExpression::PerKg { .. } => true,
+            DoseExpression::Rate(rate) => rate.per_kg,
+        }
+    }
+
+    /// Short label for the kind of dose: "absolute", "per_kg", or "rate".
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DoseExpression::Absolute { .. } => "absolute",
+            DoseExpression::PerKg { .. } => "per_kg",
+            DoseExpression::Rate(_) => "rate",
+        }
+    }
+
+    /// Amount given per administration, in `amount_unit`.
+    ///
+    /// `None` for weight-based doses without a weight, and for rates (which
+    /// have no single administration).
+    pub fn amount_per_dose(&self, weight_kg: Option<f64>) -> Option<f64> {
+        match self {
+            DoseExpression::Absolute { amount, .. } => Some(*amount),
+            DoseExpression::PerKg { amount_per_kg, .. } => weight_kg.map(|w| amount_per_kg * w),
+            DoseExpression::Rate(_) => None,
+        }
+    }
+
+    /// Dose per kg of body weight, for comparison against a dose range.
+    ///
+    /// Absolute doses need the patient's weight; per-kg doses don't. Rates
+    /// return `None`: a per-hour rate can't be compared to a per-dose range.
+    pub fn dose_per_kg(&self, weight_kg: Option<f64>) -> Option<f64> {
+        match self {
+            DoseExpression::Absolute { amount, .. } => {
+                weight_kg.filter(|w| *w > 0.0).map(|w| amount / w)
+            }
+            DoseExpression::PerKg { amount_per_kg, .. } => Some(*amount_per_kg),
+            DoseExpression::Rate(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dose_per_kg() {
+        let absolute = DoseExpression::from_dose(100.0, &"mg".into());
+        assert_eq!(absolute.kind(), "absolute");
+        assert_eq!(absolute.dose_per_kg(Some(25.0)), Some(4.0));
+        assert_eq!(absolute.dose_per_kg(None), None);
+        assert_eq!(absolute.amount_per_dose(None), Some(100.0));
+
+        let per_kg = DoseExpression::from_dose(2.0, &"mg/kg".into());
+        assert_eq!(per_kg.kind(), "per_kg");
+        assert_eq!(per_kg.amount_unit(), DoseUnit::Milligrams);
+        assert_eq!(per_kg.dose_per_kg(None), Some(2.0));
+        assert_eq!(per_kg.amount_per_dose(Some(30.0)), Some(60.0));
+        assert!(per_kg.is_weight_based());
+
+        let rate = DoseExpression::Rate(InfusionRate {
+            rate_per_hour: 0.003,
+            unit: "mg".into(),
+            per_kg: true,
+            duration_hours: None,
+            total_amount: None,
+        });
+        assert_eq!(rate.dose_per_kg(Some(20.0)), None);
+        assert_eq!(rate.amount_per_dose(Some(20.0)), None);
+    }
+}
@@ -1,8 +1,20 @@
 //! Encounter models for drafts and reviewed encounters.
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-use super::resolution::{ResolvedItem, ResolutionStatus};
+use super::catalog::ControlledSchedule;
+use super::category::ClinicalCategory;
+use super::disposition::DispositionType;
+use super::escalation::EscalationApproval;
+use super::interaction::InteractionWarning;
+use super::resolution::{DrugMention, ResolutionStatus, ResolvedItem, SourceSpan};
+use super::service::ServiceLineItem;
+use super::taper::TaperSchedule;
+use super::user::Prescriber;
+use super::vocab::Unit;
+use super::withdrawal::{last_dose_date, Withdrawal};
 
 /// Draft encounter status.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -30,6 +42,22 @@ pub struct EncounterDraft {
     pub transcript: String,
     /// Resolved drug items (pending vet review)
     pub resolved_items: Vec<ResolvedItem>,
+    /// Line items added by the vet that were not in the transcript
+    #[serde(default)]
+    pub manual_items: Vec<EncounterLineItem>,
+    /// Drug-drug interactions found among the items and active medications
+    #[serde(default)]
+    pub interaction_warnings: Vec<InteractionWarning>,
+    /// Medications the owner said the patient is on at home. Not orders, so
+    /// never resolved to SKUs or billed
+    #[serde(default)]
+    pub reported_medications: Vec<DrugMention>,
+    /// Procedures, vaccines, and diagnostics matched to the services catalog
+    #[serde(default)]
+    pub service_items: Vec<ServiceLineItem>,
+    /// Visit the draft belongs to, if linked
+    #[serde(default)]
+    pub visit_id: Option<String>,
     /// Draft status
     pub status: DraftStatus,
     /// Creation timestamp
@@ -47,6 +75,11 @@ impl EncounterDraft {
             patient_id,
             transcript: String::new(),
             resolved_items: Vec::new(),
+            manual_items: Vec::new(),
+            interaction_warnings: Vec::new(),
+            reported_medications: Vec::new(),
+            service_items: Vec::new(),
+            visit_id: None,
             status: DraftStatus::Recording,
             created_at: now.clone(),
             updated_at: now,
@@ -61,11 +94,14 @@ impl EncounterDraft {
             .count()
     }
 
-    /// Check if all items have been reviewed.
+    /// Check if all items have been reviewed, and controlled manual items
+    /// confirmed.
     pub fn all_reviewed(&self) -> bool {
-        self.resolved_items
-            .iter()
-            .all(|item| !item.needs_review())
+        self.resolved_items.iter().all(|item| !item.needs_review())
+            && self
+                .manual_items
+                .iter()
+                .all(|item| !item.requires_controlled_confirmation())
     }
 
     /// Get the lowest confidence score among pending items.
@@ -77,12 +113,200 @@ impl EncounterDraft {
             .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
     }
 
+    /// Whether any pending item has a species/breed safety warning.
+    pub fn has_safety_warnings(&self) -> bool {
+        self.resolved_items
+            .iter()
+            .any(|item| item.needs_review() && item.has_safety_warnings())
+    }
+
+    /// Accepted items (drugs and services) that require a lot number the
+    /// reviewer hasn't recorded yet. These are flagged, not blocked: the
+    /// lot may only be at hand after the visit.
+    pub fn missing_lot_count(&self) -> usize {
+        let drugs = self.resolved_items.iter().filter(|item| item.missing_lot());
+        let services = self.service_items.iter().filter(|item| item.missing_lot());
+        drugs.count() + services.count()
+    }
+
+    /// Whether any pending item has tied top candidates.
+    pub fn has_ambiguous_items(&self) -> bool {
+        self.resolved_items
+            .iter()
+            .any(|item| item.needs_review() && item.is_ambiguous())
+    }
+
+    /// Indices of items needing review, in the order the vet should see them.
+    ///
+    /// Items with safety warnings always come first, whatever their
+    /// confidence, then ambiguous items; the rest follow lowest confidence
+    /// first.
+    pub fn review_order(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = self
+            .resolved_items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.needs_review())
+            .map(|(index, _)| index)
+            .collect();
+        indices.sort_by(|&a, &b| {
+            let (a, b) = (&self.resolved_items[a], &self.resolved_items[b]);
+            b.has_safety_warnings()
+                .cmp(&a.has_safety_warnings())
+                .then_with(|| b.is_ambiguous().cmp(&a.is_ambiguous()))
+                .then_with(|| {
+                    a.top_candidate
+                        .confidence
+                        .partial_cmp(&b.top_candidate.confidence)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+        });
+        indices
+    }
+
+    /// Item indices grouped by clinical category.
+    ///
+    /// Groups follow [`ClinicalCategory`] display order and empty categories
+    /// are omitted; items keep transcript order within a group.
+    pub fn grouped_items(&self) -> Vec<(ClinicalCategory, Vec<usize>)> {
+        let mut groups: BTreeMap<ClinicalCategory, Vec<usize>> = BTreeMap::new();
+        for (index, item) in self.resolved_items.iter().enumerate() {
+            groups
+                .entry(ClinicalCategory::for_item(item))
+                .or_default()
+                .push(index);
+        }
+        groups.into_iter().collect()
+    }
+
+    /// Fill in dispositions the resolver couldn't infer from the mention
+    /// alone, using cues in the surrounding transcript sentence.
+    pub fn infer_dispositions(&mut self) {
+        for item in self.resolved_items.iter_mut() {
+            let span = item.mention.original.span();
+            if item.disposition.is_none() && span.end_offset > span.start_offset {
+                item.disposition =
+                    DispositionType::for_span(&self.transcript, span.start_offset, span.end_offset);
+            }
+        }
+        self.recount_withdrawals();
+    }
+
+    /// Count each item's withdrawal from the last dose of its course again,
+    /// after its disposition changed: a drug given once in clinic ends its
+    /// course on the treatment date, one sent home runs longer.
+    pub fn recount_withdrawals(&mut self) {
+        let treated = self.treatment_date();
+        for item in self.resolved_items.iter_mut() {
+            if let Some(withdrawal) = &item.withdrawal {
+                let last_dose =
+                    last_dose_date(treated, item.mention.taper.as_ref(), item.disposition);
+                item.withdrawal = Some(withdrawal.from_last_dose(last_dose));
+            }
+        }
+    }
+
+    /// Carry review decisions over from `previous` items whose mention text
+    /// is unchanged, e.g. after re-resolving a corrected transcript.
+    ///
+    /// A carried item keeps its decision, candidates, confirmations, and
+    /// disposition, with the new item's transcript offsets. Each previous
+    /// item is carried at most once; pending ones are not carried.
+    pub fn keep_reviews(&mut self, previous: Vec<ResolvedItem>) {
+        let mut reviewed: Vec<ResolvedItem> = previous
+            .into_iter()
+            .filter(|item| item.status != ResolutionStatus::PendingReview)
+            .collect();
+        for item in self.resolved_items.iter_mut() {
+            let Some(pos) = reviewed
+                .iter()
+                .position(|r| r.mention.original.raw_text == item.mention.original.raw_text)
+            else {
+                continue;
+            };
+            let mut kept = reviewed.remove(pos);
+            kept.mention.original = item.mention.original.clone();
+            kept.duplicate_mentions = std::mem::take(&mut item.duplicate_mentions);
+            // Warnings reflect the patient as it is now (e.g. a new allergy)
+            kept.safety_warnings = std::mem::take(&mut item.safety_warnings);
+            *item = kept;
+        }
+    }
+
+    /// Add a line item the vet entered by hand (not from the transcript).
+    ///
+    /// Returns the new item so callers can fill in catalog-derived fields.
+    pub fn add_manual_item(
+        &mut self,
+        sku: String,
+        name: String,
+        quantity: f64,
+        unit: String,
+        route: Option<String>,
+    ) -> &mut EncounterLineItem {
+        self.touch();
+        self.manual_items.push(EncounterLineItem {
+            sku,
+            name,
+            quantity,
+            unit: unit.into(),
+            route,
+            original_mention: String::new(),
+            resolution_method: ResolutionMethod::ManualEntry,
+            controlled_schedule: None,
+            source_spans: vec![],
+            schedule: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        });
+        self.manual_items.last_mut().expect("item was just pushed")
+    }
+
+    /// Indices of resolved items that are controlled substances.
+    pub fn controlled_item_indices(&self) -> Vec<usize> {
+        self.resolved_items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.controlled_schedule().is_some())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Day the encounter took place (the draft's creation date), which
+    /// withdrawal periods count from until review. Today if `created_at`
+    /// doesn't parse.
+    pub fn treatment_date(&self) -> chrono::NaiveDate {
+        chrono::DateTime::parse_from_rfc3339(&self.created_at)
+            .map(|t| t.date_naive())
+            .unwrap_or_else(|_| chrono::Utc::now().date_naive())
+    }
+
     /// Touch the updated_at timestamp.
     pub fn touch(&mut self) {
         self.updated_at = chrono::Utc::now().to_rfc3339();
     }
 }
 
+/// A reviewed draft whose commit never completed (e.g., the app was killed
+/// between review and commit).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PendingCommit {
+    pub draft_id: String,
+    pub patient_id: String,
+    /// When the draft was last updated (its review)
+    pub reviewed_at: String,
+    /// Items that will become line items
+    pub line_item_count: usize,
+    /// Encounter leaf already committed for this draft. When set, only the
+    /// draft's status was lost and resuming just marks it committed.
+    pub leaf_hash: Option<String>,
+}
+
 /// A reviewed encounter ready for Merkle tree commit.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ReviewedEncounter {
@@ -102,6 +326,16 @@ pub struct ReviewedEncounter {
     pub reviewed_at: String,
     /// Additional notes from vet
     pub notes: Option<String>,
+    /// Device that committed the encounter (stamped at commit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    /// Reviewer's license and DEA credentials (stamped at commit from the
+    /// users table)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prescriber: Option<Prescriber>,
+    /// Visit the encounter belongs to (copied from its draft at commit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visit_id: Option<String>,
 }
 
 /// A single line item in a reviewed encounter.
@@ -114,13 +348,52 @@ pub struct EncounterLineItem {
     /// Quantity/dose
     pub quantity: f64,
     /// Unit
-    pub unit: String,
+    pub unit: Unit,
     /// Route of administration
     pub route: Option<String>,
     /// Original mention text (for audit)
     pub original_mention: String,
     /// How this item was resolved
     pub resolution_method: ResolutionMethod,
+    /// DEA schedule, tagged for the controlled substance log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub controlled_schedule: Option<ControlledSchedule>,
+    /// Transcript spans this item was extracted from (several when repeated
+    /// mentions were merged); empty for manual entries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_spans: Vec<SourceSpan>,
+    /// Dosing phases when the item was dictated as a taper; `quantity` is
+    /// then the total over all phases
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<TaperSchedule>,
+    /// Given in clinic, dispensed, or prescribed (unset if never determined)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disposition: Option<DispositionType>,
+    /// Manufacturer lot given from (vaccines and controlled drugs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    /// Expiration date of the lot (YYYY-MM-DD)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration_date: Option<String>,
+    /// Meat/milk withdrawal the patient is under (food animals)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub withdrawal: Option<Withdrawal>,
+    /// Sign-off for an item with an escalation-restricted ingredient
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation_approval: Option<EscalationApproval>,
+    /// Reviewer who explicitly confirmed a controlled substance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub controlled_confirmed_by: Option<String>,
+    /// Reviewer who chose to give the item despite a recorded allergy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allergy_overridden_by: Option<String>,
+}
+
+impl EncounterLineItem {
+    /// Whether a controlled item hasn't been explicitly confirmed.
+    pub fn requires_controlled_confirmation(&self) -> bool {
+        self.controlled_schedule.is_some() && self.controlled_confirmed_by.is_none()
+    }
 }
 
 /// How a line item was resolved.
@@ -146,35 +419,14 @@ impl ReviewedEncounter {
         let line_items: Vec<EncounterLineItem> = draft
             .resolved_items
             .iter()
-            .filter_map(|item| {
-                let sku = item.final_sku()?;
-                let resolution_method = match &item.status {
-                    ResolutionStatus::Approved => ResolutionMethod::SystemApproved {
-                        confidence: item.top_candidate.confidence,
-                    },
-                    ResolutionStatus::AlternativeSelected { .. } => {
-                        ResolutionMethod::AlternativeSelected {
-                            original_confidence: item.top_candidate.confidence,
-                        }
-                    }
-                    ResolutionStatus::ManualOverride { .. } => ResolutionMethod::ManualOverride,
-                    _ => return None,
-                };
-
-                Some(EncounterLineItem {
-                    sku: sku.to_string(),
-                    name: item.top_candidate.name.clone(),
-                    quantity: item.mention.normalized_dose.unwrap_or(1.0),
-                    unit: item
-                        .mention
-                        .normalized_unit
-                        .clone()
-                        .unwrap_or_else(|| "unit".into()),
-                    route: item.mention.normalized_route.clone(),
-                    original_mention: item.mention.original.raw_text.clone(),
-                    resolution_method,
-                })
-            })
+            .filter_map(|item| item.to_line_item())
+            .chain(
+                draft
+                    .service_items
+                    .iter()
+                    .map(ServiceLineItem::to_line_item),
+            )
+            .chain(draft.manual_items.iter().cloned())
             .collect();
 
         Some(Self {
@@ -186,6 +438,9 @@ impl ReviewedEncounter {
             reviewed_by,
             reviewed_at: chrono::Utc::now().to_rfc3339(),
             notes: None,
+            device_id: None,
+            prescriber: None,
+            visit_id: draft.visit_id.clone(),
         })
     }
 
@@ -196,12 +451,75 @@ impl ReviewedEncounter {
     }
 }
 
+impl ResolvedItem {
+    /// The line item this resolution will commit, if approved.
+    pub fn to_line_item(&self) -> Option<EncounterLineItem> {
+        let sku = self.final_sku()?;
+        let resolution_method = match &self.status {
+            ResolutionStatus::Approved => ResolutionMethod::SystemApproved {
+                confidence: self.top_candidate.confidence,
+            },
+            ResolutionStatus::AlternativeSelected { .. } => ResolutionMethod::AlternativeSelected {
+                original_confidence: self.top_candidate.confidence,
+            },
+            ResolutionStatus::ManualOverride { .. } => ResolutionMethod::ManualOverride,
+            _ => return None,
+        };
+
+        // CRIs bill the total delivered, not the rate; tapers the total over
+        // all phases, not the first dose
+        let infusion_total = self
+            .mention
+            .infusion
+            .as_ref()
+            .and_then(|rate| Some((rate.total_amount?, Unit::parse(&rate.unit))));
+        let taper_total = self
+            .mention
+            .taper
+            .as_ref()
+            .and_then(|taper| Some((taper.total_amount()?, Unit::parse(taper.unit()?))));
+        let (quantity, unit) = infusion_total.or(taper_total).unwrap_or_else(|| {
+            (
+                self.mention.normalized_dose.unwrap_or(1.0),
+                self.mention
+                    .normalized_unit
+                    .clone()
+                    .unwrap_or_else(|| "unit".into()),
+            )
+        });
+
+        Some(EncounterLineItem {
+            sku: sku.to_string(),
+            name: self.top_candidate.name.clone(),
+            quantity,
+            unit,
+            route: self.mention.normalized_route.clone(),
+            original_mention: self.mention.original.raw_text.clone(),
+            resolution_method,
+            controlled_schedule: self.controlled_schedule(),
+            source_spans: self.source_spans(),
+            schedule: self.mention.taper.clone(),
+            disposition: self.disposition,
+            lot_number: self.lot_number.clone(),
+            expiration_date: self.expiration_date.clone(),
+            withdrawal: self
+                .withdrawal
+                .clone()
+                .filter(|_| sku == self.top_candidate.sku),
+            escalation_approval: self.escalation.as_ref().and_then(|e| e.approval.clone()),
+            controlled_confirmed_by: self.controlled_confirmed_by.clone(),
+            allergy_overridden_by: self.allergy_overridden_by.clone(),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::models::resolution::{
         DrugMention, NormalizedMention, ScoreBreakdown, ScoredCandidate,
     };
+    use crate::models::{SafetySeverity, SafetyWarning};
 
     fn make_test_draft() -> EncounterDraft {
         let mut draft = EncounterDraft::new("patient-123".into());
@@ -217,11 +535,16 @@ mod tests {
                 species: None,
                 start_offset: 5,
                 end_offset: 25,
+                field_spans: Default::default(),
+                extraction_confidence: None,
+                speaker: None,
             },
             normalized_name: "carprofen".into(),
             normalized_dose: Some(10.0),
             normalized_unit: Some("mg".into()),
             normalized_route: Some("PO".into()),
+            infusion: None,
+            taper: None,
         };
 
         let candidate = ScoredCandidate {
@@ -234,6 +557,8 @@ mod tests {
                 route_score: 1.0,
                 dose_score: 0.8,
             },
+            suggested_quantity: None,
+            controlled_schedule: None,
         };
 
         draft.resolved_items.push(ResolvedItem {
@@ -241,6 +566,17 @@ mod tests {
             top_candidate: candidate,
             alternatives: vec![],
             status: ResolutionStatus::Approved,
+            controlled_confirmed_by: None,
+            safety_warnings: vec![],
+            allergy_overridden_by: None,
+            duplicate_mentions: vec![],
+            tied_skus: vec![],
+            escalation: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            override_schedule: None,
         });
 
         draft.status = DraftStatus::Reviewed;
@@ -272,6 +608,31 @@ mod tests {
         assert_eq!(item.unit, "mg");
     }
 
+    #[test]
+    fn test_manual_items_committed() {
+        let mut draft = make_test_draft();
+        draft.add_manual_item(
+            "CERENIA-10".into(),
+            "Cerenia 10mg/mL".into(),
+            1.0,
+            "mL".into(),
+            Some("SQ".into()),
+        );
+
+        let reviewed = ReviewedEncounter::from_draft(&draft, "Dr. Smith".into()).unwrap();
+        assert_eq!(reviewed.line_items.len(), 2);
+        assert_eq!(reviewed.line_items[1].sku, "CERENIA-10");
+        assert_eq!(
+            reviewed.line_items[1].resolution_method,
+            ResolutionMethod::ManualEntry
+        );
+
+        // Unset fields stay out of the hashed JSON, as before they existed
+        let json = serde_json::to_value(&reviewed.line_items[1]).unwrap();
+        assert!(json.get("source_spans").is_none());
+        assert!(json.get("controlled_schedule").is_none());
+    }
+
     #[test]
     fn test_canonical_json_deterministic() {
         let draft = make_test_draft();
@@ -281,4 +642,193 @@ mod tests {
         let json2 = reviewed.to_canonical_json().unwrap();
         assert_eq!(json1, json2);
     }
+
+    #[test]
+    fn test_safety_warnings_reviewed_first() {
+        let mut draft = make_test_draft();
+        let mut low = draft.resolved_items[0].clone();
+        low.status = ResolutionStatus::PendingReview;
+        low.top_candidate.confidence = 0.3;
+        let mut flagged = low.clone();
+        flagged.top_candidate.confidence = 0.99;
+        flagged.safety_warnings.push(SafetyWarning {
+            sku: "PERM-65".into(),
+            drug: "permethrin".into(),
+            severity: SafetySeverity::Contraindicated,
+            reason: "fatal neurotoxicity in cats".into(),
+            blocking: false,
+        });
+        draft.resolved_items.push(low);
+        draft.resolved_items.push(flagged);
+
+        assert!(draft.has_safety_warnings());
+        assert_eq!(draft.review_order(), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_ambiguous_items_reviewed_before_low_confidence() {
+        let mut draft = make_test_draft();
+        let mut low = draft.resolved_items[0].clone();
+        low.status = ResolutionStatus::PendingReview;
+        low.top_candidate.confidence = 0.3;
+        let mut tied = low.clone();
+        tied.top_candidate.confidence = 0.9;
+        tied.tied_skus = vec!["CARP-75".into(), "CARP-100".into()];
+        draft.resolved_items.push(low);
+        draft.resolved_items.push(tied);
+
+        assert!(draft.has_ambiguous_items());
+        assert_eq!(draft.review_order(), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_infusion_bills_total_delivered() {
+        let mut draft = make_test_draft();
+        let item = &mut draft.resolved_items[0];
+        item.mention.normalized_dose = Some(0.003);
+        item.mention.normalized_unit = Some("mg/kg/hr".into());
+        item.mention.infusion = Some(crate::models::InfusionRate {
+            rate_per_hour: 0.003,
+            unit: "mg".into(),
+            per_kg: true,
+            duration_hours: Some(6.0),
+            total_amount: Some(0.36),
+        });
+
+        let line = draft.resolved_items[0].to_line_item().unwrap();
+        assert_eq!(line.quantity, 0.36);
+        assert_eq!(line.unit, "mg");
+
+        // Without a total (no weight or duration) the rate is kept as dictated
+        draft.resolved_items[0]
+            .mention
+            .infusion
+            .as_mut()
+            .unwrap()
+            .total_amount = None;
+        let line = draft.resolved_items[0].to_line_item().unwrap();
+        assert_eq!(line.unit, "mg/kg/hr");
+    }
+
+    #[test]
+    fn test_taper_bills_all_phases() {
+        let mut draft = make_test_draft();
+        let phase = |dose: f64, frequency: &str, doses_per_day: f64| crate::models::DosePhase {
+            dose,
+            unit: "mg".into(),
+            frequency: frequency.into(),
+            doses_per_day,
+            days: 5.0,
+        };
+        let taper = crate::models::TaperSchedule {
+            phases: vec![phase(20.0, "BID", 2.0), phase(10.0, "SID", 1.0)],
+        };
+        draft.resolved_items[0].mention.taper = Some(taper.clone());
+
+        let line = draft.resolved_items[0].to_line_item().unwrap();
+        assert_eq!(line.quantity, 250.0);
+        assert_eq!(line.unit, "mg");
+        assert_eq!(line.schedule, Some(taper));
+    }
+
+    #[test]
+    fn test_escalated_approval_recorded() {
+        let rule = crate::models::EscalationRule::new("carprofen", "dvm-lead", "Restricted");
+        let mut draft = make_test_draft();
+        let item = &mut draft.resolved_items[0];
+        item.escalation = Some(crate::models::Escalation::required(&rule));
+        assert!(item.needs_review());
+
+        item.escalation.as_mut().unwrap().approval = Some(crate::models::EscalationApproval {
+            approved_by: "Dr. Lead".into(),
+            role: "DVM-Lead".into(),
+            reason: "Post-op pain".into(),
+            approved_at: "2024-01-01T00:00:00Z".into(),
+        });
+        assert!(!item.needs_review());
+        assert!(item.escalation.as_ref().unwrap().is_approved_under(&rule));
+
+        // The approval is kept next to how the item was resolved
+        let line = item.to_line_item().unwrap();
+        assert!(matches!(
+            line.resolution_method,
+            ResolutionMethod::SystemApproved { .. }
+        ));
+        assert_eq!(line.escalation_approval.unwrap().approved_by, "Dr. Lead");
+    }
+
+    #[test]
+    fn test_grouped_items_by_category() {
+        let mut draft = make_test_draft();
+        let carprofen = draft.resolved_items[0].clone();
+        let mut propofol = carprofen.clone();
+        propofol.mention.normalized_name = "propofol".into();
+        let mut catheter = carprofen.clone();
+        catheter.mention.normalized_name = "catheter".into();
+        let mut maropitant = carprofen.clone();
+        maropitant.mention.normalized_name = "maropitant".into();
+        maropitant.top_candidate.name = "Cerenia 10mg/mL".into();
+        draft.resolved_items = vec![catheter, carprofen.clone(), maropitant, propofol, carprofen];
+
+        assert_eq!(
+            draft.grouped_items(),
+            vec![
+                (ClinicalCategory::Anesthesia, vec![3]),
+                (ClinicalCategory::Analgesia, vec![1, 4]),
+                (ClinicalCategory::Other, vec![2]),
+                (ClinicalCategory::Supplies, vec![0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dispositions_inferred_from_sentence() {
+        let mut draft = make_test_draft();
+        draft.transcript = "Gave 10mg of carprofen PO. Sent home with 10mg of carprofen PO".into();
+        let mut dispensed = draft.resolved_items[0].clone();
+        dispensed.mention.original.start_offset = 42;
+        dispensed.mention.original.end_offset = 62;
+        draft.resolved_items.push(dispensed);
+        // A reviewer's choice is never overwritten
+        draft.resolved_items[1].disposition = Some(DispositionType::Prescribed);
+
+        draft.infer_dispositions();
+        assert_eq!(
+            draft.resolved_items[0].disposition,
+            Some(DispositionType::AdministeredInClinic)
+        );
+        assert_eq!(
+            draft.resolved_items[1].disposition,
+            Some(DispositionType::Prescribed)
+        );
+
+        let line = draft.resolved_items[1].to_line_item().unwrap();
+        assert_eq!(line.disposition, Some(DispositionType::Prescribed));
+    }
+
+    #[test]
+    fn test_keep_reviews_for_unchanged_mentions() {
+        let previous = make_test_draft().resolved_items;
+
+        // Corrected transcript: same carprofen mention, shifted, plus a new one
+        let mut draft = make_test_draft();
+        draft.transcript = "Then give 10mg of carprofen PO and 5mg of carprofen PO".into();
+        let mut new_mention = draft.resolved_items[0].clone();
+        new_mention.mention.original.raw_text = "5mg of carprofen PO".into();
+        draft.resolved_items.push(new_mention);
+        for item in draft.resolved_items.iter_mut() {
+            item.status = ResolutionStatus::PendingReview;
+        }
+        draft.resolved_items[0].mention.original.start_offset = 10;
+        draft.resolved_items[0].mention.original.end_offset = 30;
+
+        draft.keep_reviews(previous);
+        assert_eq!(draft.resolved_items[0].status, ResolutionStatus::Approved);
+        assert_eq!(draft.resolved_items[0].mention.original.start_offset, 10);
+        assert_eq!(
+            draft.resolved_items[1].status,
+            ResolutionStatus::PendingReview
+        );
+        assert_eq!(draft.pending_review_count(), 1);
+    }
 }
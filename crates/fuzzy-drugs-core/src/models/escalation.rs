@@ -0,0 +1,86 @@
+//! Review escalation for restricted ingredients.
+//!
+//! Admins can mark ingredients (e.g., opioids, off-label chemotherapy) as
+//! requiring escalation. Items containing them can only be approved by a
+//! user holding the rule's role in the users table, always with a reason
+//! note, and the approval is recorded on the committed line item next to its
+//! resolution method.
+
+use serde::{Deserialize, Serialize};
+
+/// Admin-configured escalation rule for one ingredient.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EscalationRule {
+    /// Generic ingredient name, lowercase (e.g., "fentanyl")
+    pub ingredient: String,
+    /// Role allowed to approve items containing the ingredient
+    pub required_role: String,
+    /// Why the ingredient is restricted (shown to reviewers)
+    pub note: String,
+}
+
+impl EscalationRule {
+    /// Create a rule, normalizing the ingredient name.
+    pub fn new(ingredient: &str, required_role: &str, note: &str) -> Self {
+        Self {
+            ingredient: ingredient.trim().to_lowercase(),
+            required_role: required_role.trim().to_string(),
+            note: note.trim().to_string(),
+        }
+    }
+
+    /// Whether a reviewer with `role` may approve items under this rule.
+    pub fn permits(&self, role: &str) -> bool {
+        self.required_role.eq_ignore_ascii_case(role.trim())
+    }
+}
+
+/// Sign-off by an authorized reviewer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EscalationApproval {
+    /// Reviewer who approved
+    pub approved_by: String,
+    /// Role the reviewer approved under
+    pub role: String,
+    /// Required reason note
+    pub reason: String,
+    /// When the approval was recorded
+    pub approved_at: String,
+}
+
+impl EscalationApproval {
+    /// Whether the approval names the rule's role and gives a reason. The
+    /// approver must also hold the role (see [`User::has_role`](super::User::has_role)).
+    pub fn is_valid_under(&self, rule: &EscalationRule) -> bool {
+        rule.permits(&self.role) && !self.reason.trim().is_empty()
+    }
+}
+
+/// Escalation required for a resolved item, and its approval once given.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Escalation {
+    /// Restricted ingredient that triggered the escalation
+    pub ingredient: String,
+    /// Role required to approve
+    pub required_role: String,
+    /// Approval, once given
+    pub approval: Option<EscalationApproval>,
+}
+
+impl Escalation {
+    /// Unapproved escalation for a rule.
+    pub fn required(rule: &EscalationRule) -> Self {
+        Self {
+            ingredient: rule.ingredient.clone(),
+            required_role: rule.required_role.clone(),
+            approval: None,
+        }
+    }
+
+    /// Whether the escalation has an approval valid under `rule`.
+    pub fn is_approved_under(&self, rule: &EscalationRule) -> bool {
+        self.approval
+            .as_ref()
+            .is_some_and(|a| a.is_valid_under(rule))
+    }
+}
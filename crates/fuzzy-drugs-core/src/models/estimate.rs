@@ -0,0 +1,383 @@
+//! Provisional client price estimates for drafts still under review.
+//!
+//! The front desk wants a number before the vet finishes reviewing. Pending
+//! items are priced at their top candidate, with a range across plausible
+//! alternatives. Estimates are computed on demand and never committed to
+//! the Merkle tree.
+
+use serde::{Deserialize, Serialize};
+
+use super::catalog::round_cents;
+use super::encounter::EncounterDraft;
+use super::resolution::{ResolutionStatus, ResolvedItem, ScoredCandidate};
+use super::taper::TaperSchedule;
+use super::vocab::Unit;
+
+/// Alternatives within this confidence of the top candidate count as
+/// plausible when computing a pending item's price range.
+pub const PLAUSIBLE_ALTERNATIVE_MARGIN: f64 = 0.15;
+
+/// Price estimate for one draft item.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EstimateLine {
+    /// Index into `resolved_items` (None for services and manual additions)
+    pub item_index: Option<usize>,
+    /// SKU the expected price is for
+    pub sku: String,
+    /// Product name
+    pub name: String,
+    /// Price at the expected SKU (None if it has no price)
+    pub expected: Option<f64>,
+    /// Lowest price across plausible SKUs
+    pub low: Option<f64>,
+    /// Highest price across plausible SKUs
+    pub high: Option<f64>,
+    /// Whether the vet has already decided this item
+    pub settled: bool,
+}
+
+impl EstimateLine {
+    /// Whether no plausible SKU had a price.
+    pub fn is_unpriced(&self) -> bool {
+        self.low.is_none()
+    }
+}
+
+/// Provisional price range for a draft.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PriceEstimate {
+    /// Draft ID
+    pub draft_id: String,
+    /// Sum of expected prices
+    pub expected: f64,
+    /// Sum of lowest plausible prices
+    pub low: f64,
+    /// Sum of highest plausible prices
+    pub high: f64,
+    /// Per-item estimates (rejected items are omitted)
+    pub lines: Vec<EstimateLine>,
+    /// Items still awaiting review
+    pub pending_items: usize,
+    /// Items with no catalog price (excluded from the totals)
+    pub unpriced_items: usize,
+    /// Always true: an estimate is not a bill
+    pub provisional: bool,
+}
+
+impl PriceEstimate {
+    /// Estimate a draft's price.
+    ///
+    /// `charge` prices a quantity of a SKU (with the catalog's markup and
+    /// minimum charge). Quantities come from each candidate's suggested
+    /// dispensing quantity (one unit when there is none) and services use
+    /// their quantity. `dispense` converts a dose of a SKU, over a taper if
+    /// there is one, into dispensing units as commit does: manual additions
+    /// are priced through it (falling back to their quantity as entered),
+    /// and tapers for the whole course.
+    pub fn from_draft(
+        draft: &EncounterDraft,
+        dispense: impl Fn(&str, f64, &Unit, Option<&TaperSchedule>) -> Option<f64>,
+        charge: impl Fn(&str, f64) -> Option<f64>,
+    ) -> Self {
+        let mut lines: Vec<EstimateLine> = draft
+            .resolved_items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| item_line(index, item, &dispense, &charge))
+            .collect();
+
+        lines.extend(draft.service_items.iter().map(|service| {
+            let price = charge(&service.code, service.quantity);
+            EstimateLine {
+                item_index: None,
+                sku: service.code.clone(),
+                name: service.name.clone(),
+                expected: price,
+                low: price,
+                high: price,
+                settled: true,
+            }
+        }));
+        lines.extend(draft.manual_items.iter().map(|item| {
+            let units = dispense(&item.sku, item.quantity, &item.unit, item.schedule.as_ref())
+                .unwrap_or(item.quantity);
+            let price = charge(&item.sku, units);
+            EstimateLine {
+                item_index: None,
+                sku: item.sku.clone(),
+                name: item.name.clone(),
+                expected: price,
+                low: price,
+                high: price,
+                settled: true,
+            }
+        }));
+
+        Self {
+            draft_id: draft.draft_id.clone(),
+            expected: round_cents(lines.iter().filter_map(|l| l.expected).sum()),
+            low: round_cents(lines.iter().filter_map(|l| l.low).sum()),
+            high: round_cents(lines.iter().filter_map(|l| l.high).sum()),
+            pending_items: lines.iter().filter(|l| !l.settled).count(),
+            unpriced_items: lines.iter().filter(|l| l.is_unpriced()).count(),
+            lines,
+            provisional: true,
+        }
+    }
+}
+
+/// Estimate for a resolved item; `None` for rejected items.
+fn item_line(
+    index: usize,
+    item: &ResolvedItem,
+    dispense: &impl Fn(&str, f64, &Unit, Option<&TaperSchedule>) -> Option<f64>,
+    charge: &impl Fn(&str, f64) -> Option<f64>,
+) -> Option<EstimateLine> {
+    let price_of = |candidate: &ScoredCandidate| {
+        // A taper is dispensed for the whole course, not one dose
+        let course = item.mention.taper.as_ref().and_then(|taper| {
+            let unit = Unit::parse(taper.unit()?);
+            dispense(&candidate.sku, taper.total_amount()?, &unit, Some(taper))
+        });
+        let units = course.unwrap_or_else(|| {
+            candidate
+                .suggested_quantity
+                .as_ref()
+                .map_or(1.0, |q| q.per_dose)
+        });
+        charge(&candidate.sku, units)
+    };
+
+    match &item.status {
+        ResolutionStatus::Rejected => None,
+        ResolutionStatus::PendingReview => {
+            let top = &item.top_candidate;
+            let prices: Vec<f64> = std::iter::once(top)
+                .chain(
+                    item.alternatives
+                        .iter()
+                        .filter(|c| top.confidence - c.confidence <= PLAUSIBLE_ALTERNATIVE_MARGIN),
+                )
+                .filter_map(price_of)
+                .collect();
+            Some(EstimateLine {
+                item_index: Some(index),
+                sku: top.sku.clone(),
+                name: top.name.clone(),
+                expected: price_of(top),
+                low: prices.iter().copied().reduce(f64::min),
+                high: prices.iter().copied().reduce(f64::max),
+                settled: false,
+            })
+        }
+        _ => {
+            let sku = item.final_sku()?;
+            let price = match item.final_candidate() {
+                Some(candidate) => price_of(candidate),
+                None => charge(sku, 1.0),
+            };
+            Some(EstimateLine {
+                item_index: Some(index),
+                sku: sku.to_string(),
+                name: item
+                    .final_candidate()
+                    .map_or_else(|| sku.to_string(), |c| c.name.clone()),
+                expected: price,
+                low: price,
+                high: price,
+                settled: true,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        DrugMention, NormalizedMention, ScoreBreakdown, ServiceKind, ServiceLineItem,
+        ServiceMention, SuggestedQuantity,
+    };
+
+    fn candidate(sku: &str, confidence: f64, tablets: f64) -> ScoredCandidate {
+        ScoredCandidate {
+            sku: sku.into(),
+            name: sku.into(),
+            confidence,
+            score_breakdown: ScoreBreakdown {
+                name_score: 1.0,
+                species_score: 1.0,
+                route_score: 1.0,
+                dose_score: 1.0,
+            },
+            suggested_quantity: Some(SuggestedQuantity {
+                per_dose: tablets,
+                unit: "tablets".into(),
+                fraction_score: Some(1.0),
+            }),
+            controlled_schedule: None,
+        }
+    }
+
+    fn pending_item() -> ResolvedItem {
+        ResolvedItem {
+            mention: NormalizedMention {
+                original: DrugMention {
+                    raw_text: "carprofen 100 mg".into(),
+                    drug_name: "carprofen".into(),
+                    dose: Some(100.0),
+                    unit: Some("mg".into()),
+                    route: None,
+                    species: None,
+                    start_offset: 0,
+                    end_offset: 16,
+                    field_spans: Default::default(),
+                    extraction_confidence: None,
+                    speaker: None,
+                },
+                normalized_name: "carprofen".into(),
+                normalized_dose: Some(100.0),
+                normalized_unit: Some("mg".into()),
+                normalized_route: None,
+                infusion: None,
+                taper: None,
+            },
+            top_candidate: candidate("CARP-100", 0.9, 1.0),
+            alternatives: vec![
+                candidate("CARP-25", 0.8, 4.0),
+                candidate("CARP-INJ", 0.5, 1.0),
+            ],
+            status: ResolutionStatus::PendingReview,
+            controlled_confirmed_by: None,
+            safety_warnings: vec![],
+            allergy_overridden_by: None,
+            duplicate_mentions: vec![],
+            tied_skus: vec![],
+            escalation: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            override_schedule: None,
+        }
+    }
+
+    fn price(sku: &str, quantity: f64) -> Option<f64> {
+        let unit_price = match sku {
+            "CARP-100" => 2.0,
+            "CARP-25" => 0.75,
+            "CARP-INJ" => 40.0,
+            "VAC-RAB3" => 25.0,
+            _ => return None,
+        };
+        Some(unit_price * quantity)
+    }
+
+    fn no_dispensing(_: &str, _: f64, _: &Unit, _: Option<&TaperSchedule>) -> Option<f64> {
+        None
+    }
+
+    #[test]
+    fn test_taper_priced_for_course() {
+        let mut draft = EncounterDraft::new("patient-1".into());
+        let mut item = pending_item();
+        item.status = ResolutionStatus::Approved;
+        let phase = |dose: f64, days: f64| crate::models::DosePhase {
+            dose,
+            unit: "mg".into(),
+            frequency: "SID".into(),
+            doses_per_day: 1.0,
+            days,
+        };
+        item.mention.taper = Some(TaperSchedule {
+            phases: vec![phase(100.0, 5.0), phase(50.0, 4.0)],
+        });
+        draft.resolved_items.push(item);
+
+        // 5 + 2 tablets of CARP-100 rather than the one of a single dose
+        let estimate = PriceEstimate::from_draft(
+            &draft,
+            |sku, _, _, schedule| (sku == "CARP-100" && schedule.is_some()).then_some(7.0),
+            price,
+        );
+        assert_eq!(estimate.expected, 14.0);
+    }
+
+    #[test]
+    fn test_pending_item_range() {
+        let mut draft = EncounterDraft::new("patient-1".into());
+        draft.resolved_items.push(pending_item());
+
+        let estimate = PriceEstimate::from_draft(&draft, no_dispensing, price);
+        assert!(estimate.provisional);
+        assert_eq!(estimate.pending_items, 1);
+        assert_eq!(estimate.expected, 2.0);
+        // 4 × 25 mg tablets; the injectable is not a plausible alternative
+        assert_eq!(estimate.low, 2.0);
+        assert_eq!(estimate.high, 3.0);
+    }
+
+    #[test]
+    fn test_settled_services_and_manual_items() {
+        let mut draft = EncounterDraft::new("patient-1".into());
+        let mut item = pending_item();
+        item.status = ResolutionStatus::AlternativeSelected {
+            selected_sku: "CARP-25".into(),
+        };
+        draft.resolved_items.push(item);
+        let mut rejected = pending_item();
+        rejected.status = ResolutionStatus::Rejected;
+        draft.resolved_items.push(rejected);
+        draft.add_manual_item("GAUZE".into(), "Gauze".into(), 2.0, "each".into(), None);
+        draft.service_items.push(ServiceLineItem {
+            mention: ServiceMention {
+                raw_text: "rabies vaccine".into(),
+                service_name: "rabies vaccine".into(),
+                kind: Some(ServiceKind::Vaccine),
+                quantity: None,
+                start_offset: 20,
+                end_offset: 34,
+            },
+            code: "VAC-RAB3".into(),
+            name: "Rabies Vaccine 3yr".into(),
+            kind: ServiceKind::Vaccine,
+            quantity: 1.0,
+            confidence: 0.9,
+            lot_number: None,
+            expiration_date: None,
+        });
+
+        let estimate = PriceEstimate::from_draft(&draft, no_dispensing, price);
+        assert_eq!(estimate.lines.len(), 3);
+        assert_eq!(estimate.pending_items, 0);
+        assert_eq!(estimate.unpriced_items, 1);
+        assert_eq!(estimate.low, 28.0);
+        assert_eq!(estimate.high, 28.0);
+    }
+
+    #[test]
+    fn test_manual_item_priced_in_dispensing_units() {
+        let mut draft = EncounterDraft::new("patient-1".into());
+        draft.add_manual_item(
+            "CARP-25".into(),
+            "Carprofen 25mg".into(),
+            50.0,
+            "mg".into(),
+            None,
+        );
+
+        // 50 mg is two 25 mg tablets, as commit would dispense
+        let estimate = PriceEstimate::from_draft(
+            &draft,
+            |sku, quantity, unit, _| {
+                (sku == "CARP-25" && unit == &Unit::parse("mg")).then_some(quantity / 25.0)
+            },
+            price,
+        );
+        assert_eq!(estimate.expected, 1.5);
+        assert_eq!(
+            PriceEstimate::from_draft(&draft, no_dispensing, price).expected,
+            37.5
+        );
+    }
+}
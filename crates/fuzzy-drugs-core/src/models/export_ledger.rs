@@ -0,0 +1,82 @@
+//! Export ledger models.
+//!
+//! Each unbilled billing export is recorded as a batch listing the encounter
+//! leaves it included, so the next unbilled export skips them and the PIMS
+//! never imports the same charges twice.
+
+use serde::{Deserialize, Serialize};
+
+/// Where an export batch stands with the PIMS.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportBatchStatus {
+    /// Exported; waiting for the PIMS import to be confirmed
+    #[default]
+    Pending,
+    /// The PIMS imported the batch
+    Imported,
+    /// The batch was discarded; its encounters are unbilled again
+    Void,
+}
+
+impl ExportBatchStatus {
+    /// Database/FFI name ("pending", "imported", "void").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportBatchStatus::Pending => "pending",
+            ExportBatchStatus::Imported => "imported",
+            ExportBatchStatus::Void => "void",
+        }
+    }
+
+    /// Parse a status name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "pending" => Some(ExportBatchStatus::Pending),
+            "imported" => Some(ExportBatchStatus::Imported),
+            "void" => Some(ExportBatchStatus::Void),
+            _ => None,
+        }
+    }
+
+    /// Whether the batch's encounters count as billed.
+    pub fn is_billed(&self) -> bool {
+        !matches!(self, ExportBatchStatus::Void)
+    }
+}
+
+/// One recorded billing export.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportBatch {
+    pub batch_id: String,
+    pub status: ExportBatchStatus,
+    /// When the batch was exported
+    pub created_at: String,
+    /// When it was marked imported or void
+    pub status_changed_at: Option<String>,
+    /// Merkle leaf hashes of the encounters in the batch
+    pub leaf_hashes: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_round_trip() {
+        for status in [
+            ExportBatchStatus::Pending,
+            ExportBatchStatus::Imported,
+            ExportBatchStatus::Void,
+        ] {
+            assert_eq!(ExportBatchStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(
+            ExportBatchStatus::parse(" VOID "),
+            Some(ExportBatchStatus::Void)
+        );
+        assert_eq!(ExportBatchStatus::parse("billed"), None);
+        assert!(ExportBatchStatus::Pending.is_billed());
+        assert!(!ExportBatchStatus::Void.is_billed());
+    }
+}
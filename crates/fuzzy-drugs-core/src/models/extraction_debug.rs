@@ -0,0 +1,78 @@
+//! Raw LLM responses retained for diagnosing bad extractions.
+//!
+//! Off by default. When enabled, the exact model output behind a draft's
+//! extraction is stored (truncated to a size cap) and purged after a TTL.
+
+use serde::{Deserialize, Serialize};
+
+/// Opt-in retention settings for raw LLM responses.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExtractionDebugConfig {
+    /// Store raw responses at all
+    pub enabled: bool,
+    /// Responses longer than this are truncated (bytes)
+    pub max_response_bytes: usize,
+    /// How long a stored response is kept (hours)
+    pub retention_hours: u32,
+}
+
+impl Default for ExtractionDebugConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_response_bytes: 64 * 1024,
+            retention_hours: 72,
+        }
+    }
+}
+
+impl ExtractionDebugConfig {
+    /// Cut a response down to the size cap on a character boundary.
+    ///
+    /// Returns the retained text and whether anything was dropped.
+    pub fn truncate<'a>(&self, response: &'a str) -> (&'a str, bool) {
+        if response.len() <= self.max_response_bytes {
+            return (response, false);
+        }
+        let mut end = self.max_response_bytes;
+        while !response.is_char_boundary(end) {
+            end -= 1;
+        }
+        (&response[..end], true)
+    }
+}
+
+/// A stored raw model response for one draft's extraction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExtractionDebug {
+    /// Draft the extraction belongs to
+    pub draft_id: String,
+    /// Model identifier (e.g., "llama-3.2-1b-q4_k_m")
+    pub model: Option<String>,
+    /// Raw model output, possibly truncated
+    pub raw_response: String,
+    /// Size of the original response (bytes)
+    pub original_bytes: usize,
+    /// Whether `raw_response` was truncated
+    pub truncated: bool,
+    /// When the response was stored
+    pub recorded_at: String,
+    /// When the response will be purged
+    pub expires_at: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_on_char_boundary() {
+        let config = ExtractionDebugConfig {
+            max_response_bytes: 4,
+            ..Default::default()
+        };
+        assert_eq!(config.truncate("abc"), ("abc", false));
+        // "é" is two bytes; the cap falls inside it
+        assert_eq!(config.truncate("abcé"), ("abc", true));
+    }
+}
@@ -0,0 +1,83 @@
+//! Constant rate infusion (CRI) dosing.
+//!
+//! In-hospital CRIs are dictated as a rate, not a dose: "fentanyl 3 mcg/kg/hr
+//! for 6 hours". The amount actually delivered (and billed) depends on the
+//! patient's weight and the infusion duration.
+
+use serde::{Deserialize, Serialize};
+
+/// A rate-based dose (amount per time, optionally per kg).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InfusionRate {
+    /// Amount per hour in `unit` (per kg of body weight when `per_kg`)
+    pub rate_per_hour: f64,
+    /// Canonical amount unit ("mg", "mL", "units")
+    pub unit: String,
+    /// Whether the rate is weight-based
+    pub per_kg: bool,
+    /// Infusion duration, if dictated ("for 6 hours")
+    pub duration_hours: Option<f64>,
+    /// Total amount delivered, in `unit`; filled in at resolution once the
+    /// duration (and, for weight-based rates, the patient's weight) is known
+    #[serde(default)]
+    pub total_amount: Option<f64>,
+}
+
+impl InfusionRate {
+    /// Display unit for the rate (e.g., "mg/kg/hr", "mL/hr").
+    pub fn rate_unit(&self) -> String {
+        if self.per_kg {
+            format!("{}/kg/hr", self.unit)
+        } else {
+            format!("{}/hr", self.unit)
+        }
+    }
+
+    /// Amount delivered per hour for a patient, in `unit`.
+    ///
+    /// `None` for weight-based rates when the weight is unknown.
+    pub fn hourly_amount(&self, weight_kg: Option<f64>) -> Option<f64> {
+        if self.per_kg {
+            weight_kg.map(|w| self.rate_per_hour * w)
+        } else {
+            Some(self.rate_per_hour)
+        }
+    }
+
+    /// Total amount delivered over the infusion, in `unit`.
+    ///
+    /// `None` without a duration, or for weight-based rates without a weight.
+    pub fn total_delivered(&self, weight_kg: Option<f64>) -> Option<f64> {
+        Some(self.hourly_amount(weight_kg)? * self.duration_hours?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total_delivered() {
+        // Fentanyl 3 mcg/kg/hr for 6 hours in a 20 kg dog
+        let rate = InfusionRate {
+            rate_per_hour: 0.003,
+            unit: "mg".into(),
+            per_kg: true,
+            duration_hours: Some(6.0),
+            total_amount: None,
+        };
+        assert_eq!(rate.rate_unit(), "mg/kg/hr");
+        assert!((rate.total_delivered(Some(20.0)).unwrap() - 0.36).abs() < 1e-9);
+        assert_eq!(rate.total_delivered(None), None);
+
+        let fluids = InfusionRate {
+            rate_per_hour: 50.0,
+            unit: "mL".into(),
+            per_kg: false,
+            duration_hours: None,
+            total_amount: None,
+        };
+        assert_eq!(fluids.hourly_amount(None), Some(50.0));
+        assert_eq!(fluids.total_delivered(None), None);
+    }
+}
@@ -0,0 +1,139 @@
+//! Drug-drug interaction models.
+
+use serde::{Deserialize, Serialize};
+
+/// Prefix marking an interaction table key as a drug class (e.g., "class:nsaid").
+pub const DRUG_CLASS_PREFIX: &str = "class:";
+
+/// Severity of a drug-drug interaction.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InteractionSeverity {
+    /// Clinically minor; informational
+    Minor,
+    /// Monitor or adjust dosing
+    Moderate,
+    /// Avoid unless benefits outweigh risks
+    Major,
+    /// Do not combine
+    Contraindicated,
+}
+
+impl InteractionSeverity {
+    /// Lowercase name ("minor", "moderate", "major", "contraindicated").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InteractionSeverity::Minor => "minor",
+            InteractionSeverity::Moderate => "moderate",
+            InteractionSeverity::Major => "major",
+            InteractionSeverity::Contraindicated => "contraindicated",
+        }
+    }
+
+    /// Parse a severity name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "minor" => Some(InteractionSeverity::Minor),
+            "moderate" => Some(InteractionSeverity::Moderate),
+            "major" => Some(InteractionSeverity::Major),
+            "contraindicated" => Some(InteractionSeverity::Contraindicated),
+            _ => None,
+        }
+    }
+}
+
+/// An entry in the interaction table.
+///
+/// Each side is a lowercase generic drug name or a drug class
+/// (`"class:nsaid"`). Sides are stored in sorted order so a pair has one key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DrugInteraction {
+    /// First drug or class
+    pub drug_a: String,
+    /// Second drug or class
+    pub drug_b: String,
+    /// Severity
+    pub severity: InteractionSeverity,
+    /// Clinical note (e.g., "GI ulceration risk")
+    pub note: String,
+}
+
+impl DrugInteraction {
+    /// Create an entry, normalizing case and side order.
+    pub fn new(drug_a: &str, drug_b: &str, severity: InteractionSeverity, note: &str) -> Self {
+        let a = drug_a.trim().to_lowercase();
+        let b = drug_b.trim().to_lowercase();
+        let (drug_a, drug_b) = if a <= b { (a, b) } else { (b, a) };
+        Self {
+            drug_a,
+            drug_b,
+            severity,
+            note: note.to_string(),
+        }
+    }
+
+    /// Whether this entry covers the given pair of keys (in either order).
+    pub fn matches(&self, a: &str, b: &str) -> bool {
+        (self.drug_a == a && self.drug_b == b) || (self.drug_a == b && self.drug_b == a)
+    }
+}
+
+/// Where an interacting drug came from.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum InteractionSource {
+    /// A resolved item on the draft
+    DraftItem { item_index: usize },
+    /// An item the vet added to the draft by hand
+    ManualItem { manual_index: usize },
+    /// A medication the patient is already on
+    ActiveMedication,
+}
+
+/// An interaction found between two drugs, shown to the vet before approval.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InteractionWarning {
+    /// First drug (generic name)
+    pub drug_a: String,
+    /// Where the first drug came from
+    pub source_a: InteractionSource,
+    /// Second drug (generic name)
+    pub drug_b: String,
+    /// Where the second drug came from
+    pub source_b: InteractionSource,
+    /// Severity
+    pub severity: InteractionSeverity,
+    /// Clinical note
+    pub note: String,
+}
+
+impl InteractionWarning {
+    /// Display message, e.g. "meloxicam + prednisone: GI ulceration risk".
+    pub fn message(&self) -> String {
+        format!("{} + {}: {}", self.drug_a, self.drug_b, self.note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interaction_pair_normalized() {
+        let entry =
+            DrugInteraction::new("Prednisone", "meloxicam", InteractionSeverity::Major, "GI");
+        assert_eq!(entry.drug_a, "meloxicam");
+        assert_eq!(entry.drug_b, "prednisone");
+        assert!(entry.matches("prednisone", "meloxicam"));
+        assert!(!entry.matches("prednisone", "carprofen"));
+    }
+
+    #[test]
+    fn test_severity_order_and_parse() {
+        assert!(InteractionSeverity::Contraindicated > InteractionSeverity::Major);
+        assert!(InteractionSeverity::Moderate > InteractionSeverity::Minor);
+        assert_eq!(
+            InteractionSeverity::parse("Major"),
+            Some(InteractionSeverity::Major)
+        );
+        assert_eq!(InteractionSeverity::parse("severe"), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Legal hold models.
+//!
+//! A case under dispute is placed on legal hold: its patient record, drafts,
+//! and transcripts must not be deleted, archived, truncated, or redacted until
+//! the hold is released.
+
+use serde::{Deserialize, Serialize};
+
+/// What a legal hold applies to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldSubject {
+    /// A patient and everything recorded for them
+    Patient,
+    /// A single encounter, keyed by its draft ID
+    Encounter,
+}
+
+impl HoldSubject {
+    /// Database/FFI name ("patient", "encounter").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HoldSubject::Patient => "patient",
+            HoldSubject::Encounter => "encounter",
+        }
+    }
+
+    /// Parse a subject type name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "patient" => Some(HoldSubject::Patient),
+            "encounter" => Some(HoldSubject::Encounter),
+            _ => None,
+        }
+    }
+}
+
+/// An active legal hold.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LegalHold {
+    /// What kind of record is held
+    pub subject_type: HoldSubject,
+    /// Patient local ID or encounter draft ID
+    pub subject_id: String,
+    /// Why the hold was placed (e.g., case or claim reference)
+    pub reason: String,
+    /// Who placed the hold
+    pub placed_by: String,
+    /// When the hold was placed
+    pub placed_at: String,
+}
+
+impl LegalHold {
+    /// Create a hold placed now.
+    pub fn new(
+        subject_type: HoldSubject,
+        subject_id: String,
+        reason: String,
+        placed_by: String,
+    ) -> Self {
+        Self {
+            subject_type,
+            subject_id,
+            reason,
+            placed_by,
+            placed_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Manufacturer lots of stocked items.
+//!
+//! Vaccine and controlled-drug administrations must record the lot they came
+//! from and its expiration date. The clinic's lots on hand are kept per SKU
+//! (or vaccine service code) so the reviewer can pick one and the expiration
+//! date is filled in from it.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// A manufacturer lot of a catalog item or vaccine service.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InventoryLot {
+    /// Catalog SKU, or service code for vaccines
+    pub sku: String,
+    /// Lot number as printed on the vial or package
+    pub lot_number: String,
+    /// Expiration date (YYYY-MM-DD)
+    pub expiration_date: String,
+}
+
+impl InventoryLot {
+    pub fn new(sku: String, lot_number: String, expiration_date: String) -> Self {
+        Self {
+            sku,
+            lot_number,
+            expiration_date,
+        }
+    }
+
+    /// Check the SKU and lot number are present and the date is valid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sku.trim().is_empty() {
+            return Err("Lot SKU can't be empty".into());
+        }
+        if self.lot_number.trim().is_empty() {
+            return Err("Lot number can't be empty".into());
+        }
+        parse_expiration_date(&self.expiration_date).map(|_| ())
+    }
+
+    /// Whether the lot expired before `today`. Lots are good through their
+    /// expiration date; an unreadable date counts as expired.
+    pub fn is_expired(&self, today: NaiveDate) -> bool {
+        parse_expiration_date(&self.expiration_date).map_or(true, |date| date < today)
+    }
+}
+
+/// Parse a lot expiration date (YYYY-MM-DD).
+pub fn parse_expiration_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid expiration date (expected YYYY-MM-DD): {}", date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lot_validation_and_expiry() {
+        let lot = InventoryLot::new("VAC-RABIES".into(), "R1234".into(), "2026-06-30".into());
+        assert!(lot.validate().is_ok());
+        let day = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+        assert!(!lot.is_expired(day("2026-06-30")));
+        assert!(lot.is_expired(day("2026-07-01")));
+
+        let mut bad = lot.clone();
+        bad.expiration_date = "06/30/2026".into();
+        assert!(bad.validate().is_err());
+        assert!(bad.is_expired(day("2020-01-01")));
+        bad = lot;
+        bad.lot_number = " ".into();
+        assert!(bad.validate().is_err());
+    }
+}
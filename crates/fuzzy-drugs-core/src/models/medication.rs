@@ -0,0 +1,207 @@
+//! A patient's medication list.
+//!
+//! Drugs on committed encounters are added automatically, each running for
+//! its course: a taper's length, else [`DEFAULT_COURSE_DAYS`]. Staff add the
+//! rest by hand (long-term medications, drugs from another clinic), with an
+//! open end date if they're ongoing. Interaction checking treats whatever is
+//! active today as the patient's current medications.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::encounter::EncounterLineItem;
+use super::lot::parse_expiration_date;
+use super::taper::TaperSchedule;
+
+/// Course length (days) assumed for committed drugs dictated without one.
+pub const DEFAULT_COURSE_DAYS: i64 = 14;
+
+/// Length (days) of a course on `schedule`: the taper's length, else
+/// [`DEFAULT_COURSE_DAYS`].
+pub fn course_days(schedule: Option<&TaperSchedule>) -> i64 {
+    schedule
+        .map(|taper| taper.total_days().ceil() as i64)
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_COURSE_DAYS)
+}
+
+/// How a medication got on the list.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MedicationSource {
+    /// A line item on a committed encounter
+    Encounter,
+    /// Entered by staff
+    Manual,
+}
+
+impl MedicationSource {
+    /// Database/FFI name ("encounter" or "manual").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MedicationSource::Encounter => "encounter",
+            MedicationSource::Manual => "manual",
+        }
+    }
+
+    /// Parse a source name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "encounter" => Some(MedicationSource::Encounter),
+            "manual" => Some(MedicationSource::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// A medication a patient is or was on.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PatientMedication {
+    /// Row ID (0 until stored)
+    pub id: i64,
+    /// Patient local ID
+    pub patient_id: String,
+    /// Catalog SKU, if known
+    pub sku: Option<String>,
+    /// Drug name as recorded ("Carprofen 100mg")
+    pub name: String,
+    /// First day of the course (YYYY-MM-DD)
+    pub start_date: String,
+    /// Last day of the course (YYYY-MM-DD); None while ongoing
+    pub end_date: Option<String>,
+    pub source: MedicationSource,
+    /// Draft of the encounter it was committed on
+    pub draft_id: Option<String>,
+    pub notes: Option<String>,
+}
+
+impl PatientMedication {
+    /// A manually entered, ongoing medication.
+    pub fn new(patient_id: String, name: String, start_date: String) -> Self {
+        Self {
+            id: 0,
+            patient_id,
+            sku: None,
+            name,
+            start_date,
+            end_date: None,
+            source: MedicationSource::Manual,
+            draft_id: None,
+            notes: None,
+        }
+    }
+
+    /// The medication a committed line item starts on `start`, running for
+    /// the item's taper or [`DEFAULT_COURSE_DAYS`].
+    pub fn from_line_item(
+        patient_id: &str,
+        draft_id: &str,
+        item: &EncounterLineItem,
+        start: NaiveDate,
+    ) -> Self {
+        let end = start + chrono::Duration::days(course_days(item.schedule.as_ref()) - 1);
+        Self {
+            id: 0,
+            patient_id: patient_id.into(),
+            sku: Some(item.sku.clone()),
+            name: item.name.clone(),
+            start_date: start.format("%Y-%m-%d").to_string(),
+            end_date: Some(end.format("%Y-%m-%d").to_string()),
+            source: MedicationSource::Encounter,
+            draft_id: Some(draft_id.into()),
+            notes: None,
+        }
+    }
+
+    /// Check the name is present and the course dates are valid and in order.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Medication name can't be empty".into());
+        }
+        let start = parse_date(&self.start_date)?;
+        if let Some(end) = &self.end_date {
+            if parse_date(end)? < start {
+                return Err("Medication can't end before it starts".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the patient is on the medication on `date` (start and end
+    /// days included). Unreadable dates count as inactive.
+    pub fn is_active_on(&self, date: NaiveDate) -> bool {
+        let Ok(start) = parse_date(&self.start_date) else {
+            return false;
+        };
+        let ended = match &self.end_date {
+            Some(end) => parse_date(end).map_or(true, |end| end < date),
+            None => false,
+        };
+        start <= date && !ended
+    }
+}
+
+/// Parse a course or vaccination date (YYYY-MM-DD).
+pub(super) fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    parse_expiration_date(date).map_err(|_| format!("Invalid date (expected YYYY-MM-DD): {}", date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DosePhase, ResolutionMethod, TaperSchedule};
+
+    fn day(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_course_from_line_item() {
+        let mut item = EncounterLineItem {
+            sku: "PRED-5".into(),
+            name: "Prednisone 5mg".into(),
+            quantity: 1.0,
+            unit: "tablets".into(),
+            route: Some("PO".into()),
+            original_mention: "prednisone".into(),
+            resolution_method: ResolutionMethod::SystemApproved { confidence: 0.9 },
+            controlled_schedule: None,
+            source_spans: vec![],
+            schedule: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        };
+        let med = PatientMedication::from_line_item("p1", "d1", &item, day("2026-03-01"));
+        assert_eq!(med.end_date.as_deref(), Some("2026-03-14"));
+        assert!(med.is_active_on(day("2026-03-14")));
+        assert!(!med.is_active_on(day("2026-03-15")));
+        assert!(!med.is_active_on(day("2026-02-28")));
+
+        // A taper runs for its phases
+        let phase = |days| DosePhase {
+            dose: 5.0,
+            unit: "mg".into(),
+            frequency: "SID".into(),
+            doses_per_day: 1.0,
+            days,
+        };
+        item.schedule = Some(TaperSchedule {
+            phases: vec![phase(5.0), phase(4.5)],
+        });
+        let med = PatientMedication::from_line_item("p1", "d1", &item, day("2026-03-01"));
+        assert_eq!(med.end_date.as_deref(), Some("2026-03-10"));
+        assert!(med.validate().is_ok());
+
+        let mut manual = PatientMedication::new("p1".into(), "Levothyroxine".into(), "2025".into());
+        assert!(manual.validate().is_err());
+        manual.start_date = "2025-06-01".into();
+        assert!(manual.is_active_on(day("2030-01-01")));
+        manual.end_date = Some("2025-05-01".into());
+        assert!(manual.validate().is_err());
+    }
+}
@@ -1,11 +1,71 @@
 //! Domain models for the fuzzy-drugs system.
 
+mod audit;
 mod catalog;
+mod category;
+mod client;
+mod device;
+mod disposition;
+mod dose;
 mod encounter;
+mod escalation;
+mod estimate;
+mod export_ledger;
+mod extraction_debug;
+mod infusion;
+mod interaction;
+mod legal_hold;
+mod lot;
+mod medication;
+mod outbox;
 mod patient;
+mod preview;
 mod resolution;
+mod safety;
+mod scoring;
+mod service;
+mod settings;
+mod speaker;
+mod stock;
+mod taper;
+mod trace;
+mod user;
+mod vaccination;
+mod visit;
+mod vocab;
+mod withdrawal;
 
+pub use audit::*;
 pub use catalog::*;
+pub use category::*;
+pub use client::*;
+pub use device::*;
+pub use disposition::*;
+pub use dose::*;
 pub use encounter::*;
+pub use escalation::*;
+pub use estimate::*;
+pub use export_ledger::*;
+pub use extraction_debug::*;
+pub use infusion::*;
+pub use interaction::*;
+pub use legal_hold::*;
+pub use lot::*;
+pub use medication::*;
+pub use outbox::*;
 pub use patient::*;
+pub use preview::*;
 pub use resolution::*;
+pub use safety::*;
+pub use scoring::*;
+pub use service::*;
+pub use settings::*;
+pub use speaker::*;
+pub use stock::*;
+pub use taper::*;
+pub use trace::*;
+pub use user::*;
+pub use vaccination::*;
+pub use visit::*;
+pub use vocab::*;
+pub use withdrawal::*;
@@ -0,0 +1,147 @@
+//! Sync outbox models.
+//!
+//! Sync operations that must reach the PIMS (encounter pushes, patient
+//! upserts) are queued in an outbox and retried with exponential backoff,
+//! so a dropped connection delays them instead of losing them.
+
+use serde::{Deserialize, Serialize};
+
+/// What a queued sync operation sends.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxKind {
+    /// A committed encounter (subject: leaf hash), sent with the tree sync
+    EncounterPush,
+    /// A new or edited patient (subject: local ID)
+    PatientUpsert,
+}
+
+impl OutboxKind {
+    /// Database/FFI name ("encounter_push", "patient_upsert").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxKind::EncounterPush => "encounter_push",
+            OutboxKind::PatientUpsert => "patient_upsert",
+        }
+    }
+
+    /// Parse a kind name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "encounter_push" => Some(OutboxKind::EncounterPush),
+            "patient_upsert" => Some(OutboxKind::PatientUpsert),
+            _ => None,
+        }
+    }
+}
+
+/// Where a queued operation stands.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    /// Waiting for its next attempt
+    #[default]
+    Pending,
+    /// Out of attempts; retried only when asked
+    Failed,
+}
+
+impl OutboxStatus {
+    /// Database/FFI name ("pending", "failed").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Failed => "failed",
+        }
+    }
+
+    /// Parse a status name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "pending" => Some(OutboxStatus::Pending),
+            "failed" => Some(OutboxStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// One queued sync operation. Operations are removed once they succeed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutboxItem {
+    pub id: i64,
+    pub kind: OutboxKind,
+    /// Leaf hash or patient local ID, depending on `kind`
+    pub subject_id: String,
+    pub status: OutboxStatus,
+    /// Failed attempts so far
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// Earliest time of the next attempt (RFC 3339, UTC)
+    pub next_attempt_at: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Exponential backoff for outbox retries.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Delay after the first failure
+    pub base_delay_secs: u64,
+    /// Longest delay between attempts
+    pub max_delay_secs: u64,
+    /// Failures before an operation is marked failed
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay_secs: 30,
+            max_delay_secs: 3600,
+            max_attempts: 10,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the next attempt after `attempts` failures: the base
+    /// delay doubled per failure after the first, capped at the maximum.
+    pub fn delay_after(&self, attempts: u32) -> chrono::Duration {
+        let doublings = attempts.saturating_sub(1).min(32);
+        let secs = self
+            .base_delay_secs
+            .saturating_mul(1u64 << doublings)
+            .min(self.max_delay_secs);
+        chrono::Duration::seconds(secs as i64)
+    }
+
+    /// Whether `attempts` failures exhaust the policy.
+    pub fn is_exhausted(&self, attempts: u32) -> bool {
+        attempts >= self.max_attempts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let policy = RetryPolicy::default();
+        let delays: Vec<i64> = (1..=9)
+            .map(|attempts| policy.delay_after(attempts).num_seconds())
+            .collect();
+        assert_eq!(delays, [30, 60, 120, 240, 480, 960, 1920, 3600, 3600]);
+        assert_eq!(policy.delay_after(200).num_seconds(), 3600);
+        assert!(!policy.is_exhausted(9));
+        assert!(policy.is_exhausted(10));
+
+        for kind in [OutboxKind::EncounterPush, OutboxKind::PatientUpsert] {
+            assert_eq!(OutboxKind::parse(kind.as_str()), Some(kind));
+        }
+        for status in [OutboxStatus::Pending, OutboxStatus::Failed] {
+            assert_eq!(OutboxStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(OutboxKind::parse("catalog_pull"), None);
+    }
+}
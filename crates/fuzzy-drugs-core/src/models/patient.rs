@@ -2,6 +2,40 @@
 
 use serde::{Deserialize, Serialize};
 
+/// Pounds to kilograms conversion factor.
+pub const KG_PER_LB: f64 = 0.453_592_37;
+
+/// Unit a patient weight was recorded or dictated in.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WeightUnit {
+    Kilograms,
+    Pounds,
+    Grams,
+}
+
+impl WeightUnit {
+    /// Parse a spoken or written weight unit (e.g., "lbs", "pound", "kg").
+    pub fn parse(unit: &str) -> Option<Self> {
+        match unit.trim().to_lowercase().trim_end_matches('.') {
+            "kg" | "kgs" | "kilo" | "kilos" | "kilogram" | "kilograms" => {
+                Some(WeightUnit::Kilograms)
+            }
+            "lb" | "lbs" | "pound" | "pounds" | "#" => Some(WeightUnit::Pounds),
+            "g" | "gram" | "grams" => Some(WeightUnit::Grams),
+            _ => None,
+        }
+    }
+
+    /// Convert a weight in this unit to kilograms.
+    pub fn to_kg(self, weight: f64) -> f64 {
+        match self {
+            WeightUnit::Kilograms => weight,
+            WeightUnit::Pounds => weight * KG_PER_LB,
+            WeightUnit::Grams => weight / 1000.0,
+        }
+    }
+}
+
 /// A patient record with dual-ID support for offline-first sync.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Patient {
@@ -15,12 +49,19 @@ pub struct Patient {
     pub species: String,
     /// Breed
     pub breed: Option<String>,
-    /// Weight in kg (important for dose validation)
+    /// Weight in kg (important for dose validation). Always stored in kg;
+    /// use [`Patient::set_weight`] to record weights in other units.
     pub weight_kg: Option<f64>,
     /// Date of birth
     pub date_of_birth: Option<String>,
     /// Owner/client name
     pub owner_name: Option<String>,
+    /// Client (owner) record this patient belongs to, if linked
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Recorded drug and ingredient allergies
+    #[serde(default)]
+    pub allergies: Vec<PatientAllergy>,
     /// Additional notes
     pub notes: Option<String>,
     /// Creation timestamp
@@ -42,12 +83,19 @@ impl Patient {
             weight_kg: None,
             date_of_birth: None,
             owner_name: None,
+            client_id: None,
+            allergies: Vec::new(),
             notes: None,
             created_at: now.clone(),
             updated_at: now,
         }
     }
 
+    /// Set the patient weight from a value in any supported unit.
+    pub fn set_weight(&mut self, weight: f64, unit: WeightUnit) {
+        self.weight_kg = Some(unit.to_kg(weight));
+    }
+
     /// Check if this patient has been synced to server.
     pub fn is_synced(&self) -> bool {
         self.server_id.is_some()
@@ -59,6 +107,40 @@ impl Patient {
     }
 }
 
+/// A drug or active ingredient the patient is allergic to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PatientAllergy {
+    /// Generic drug or ingredient name ("amoxicillin")
+    pub substance: String,
+    /// Reaction seen, if known ("hives", "anaphylaxis")
+    #[serde(default)]
+    pub reaction: Option<String>,
+}
+
+impl PatientAllergy {
+    pub fn new(substance: String) -> Self {
+        Self {
+            substance,
+            reaction: None,
+        }
+    }
+
+    /// Check the substance is present.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.substance.trim().is_empty() {
+            return Err("Allergy substance can't be empty".into());
+        }
+        Ok(())
+    }
+
+    /// Whether this is an allergy to `ingredient` (ignoring case).
+    pub fn matches(&self, ingredient: &str) -> bool {
+        self.substance
+            .trim()
+            .eq_ignore_ascii_case(ingredient.trim())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,6 +154,35 @@ mod tests {
         assert_eq!(patient.local_id.len(), 36); // UUID format
     }
 
+    #[test]
+    fn test_weight_unit_parse() {
+        assert_eq!(WeightUnit::parse("lbs"), Some(WeightUnit::Pounds));
+        assert_eq!(WeightUnit::parse("Pounds"), Some(WeightUnit::Pounds));
+        assert_eq!(WeightUnit::parse("lb."), Some(WeightUnit::Pounds));
+        assert_eq!(WeightUnit::parse("kg"), Some(WeightUnit::Kilograms));
+        assert_eq!(WeightUnit::parse("grams"), Some(WeightUnit::Grams));
+        assert_eq!(WeightUnit::parse("stone"), None);
+    }
+
+    #[test]
+    fn test_set_weight_pounds() {
+        let mut patient = Patient::new("Max".into(), "canine".into());
+        patient.set_weight(60.0, WeightUnit::Pounds);
+        assert!((patient.weight_kg.unwrap() - 27.2155).abs() < 0.001);
+
+        patient.set_weight(30.0, WeightUnit::Kilograms);
+        assert_eq!(patient.weight_kg, Some(30.0));
+    }
+
+    #[test]
+    fn test_allergy_matches_ignoring_case() {
+        let allergy = PatientAllergy::new(" Amoxicillin ".into());
+        assert!(allergy.validate().is_ok());
+        assert!(allergy.matches("amoxicillin"));
+        assert!(!allergy.matches("clavulanate"));
+        assert!(PatientAllergy::new("".into()).validate().is_err());
+    }
+
     #[test]
     fn test_canonical_species() {
         let patient = Patient::new("Max".into(), "Canine".into());
@@ -0,0 +1,333 @@
+//! Pre-commit preview of a draft for vet sign-off.
+//!
+//! Once committed, a reviewed encounter is immortalized in the Merkle tree.
+//! The preview lines up what was said in the transcript with what will be
+//! written, so the sign-off screen can highlight every difference.
+
+use serde::{Deserialize, Serialize};
+
+use super::encounter::{EncounterDraft, EncounterLineItem};
+use super::resolution::{ResolutionStatus, ResolvedItem};
+use super::vocab::Unit;
+
+/// How a preview entry differs from the transcript.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PreviewChange {
+    /// Committed as spoken
+    Unchanged,
+    /// Added by the vet; has no transcript span
+    Added,
+    /// Mention rejected by the vet; will not be committed
+    Dropped,
+    /// Committed quantity/unit differs from the spoken dose
+    DoseChanged {
+        spoken_dose: Option<f64>,
+        spoken_unit: Option<String>,
+    },
+    /// Not yet reviewed; blocks commit
+    Pending,
+}
+
+/// A span of the transcript.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranscriptSpan {
+    /// Start offset in the transcript
+    pub start_offset: usize,
+    /// End offset in the transcript
+    pub end_offset: usize,
+    /// Text of the span
+    pub text: String,
+}
+
+/// One row of the commit preview.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PreviewEntry {
+    /// Index into `resolved_items` (None for services and manual additions)
+    pub item_index: Option<usize>,
+    /// Transcript span the entry came from (None for manual additions)
+    pub span: Option<TranscriptSpan>,
+    /// Line item that will be committed (None if dropped or pending)
+    pub line_item: Option<EncounterLineItem>,
+    /// Difference from the transcript
+    pub change: PreviewChange,
+}
+
+/// Structured diff of a draft's transcript against its final line items.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommitPreview {
+    /// Draft ID
+    pub draft_id: String,
+    /// Patient local ID
+    pub patient_id: String,
+    /// Full transcript text
+    pub transcript: String,
+    /// Entries in transcript order, followed by manual additions
+    pub entries: Vec<PreviewEntry>,
+    /// Whether every item has been reviewed
+    pub ready_to_commit: bool,
+}
+
+impl CommitPreview {
+    /// Build the preview for a draft.
+    pub fn from_draft(draft: &EncounterDraft) -> Self {
+        let mut transcript_entries: Vec<PreviewEntry> = draft
+            .resolved_items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| PreviewEntry {
+                item_index: Some(index),
+                span: Some(transcript_span(&draft.transcript, item)),
+                line_item: item.to_line_item(),
+                change: item_change(item),
+            })
+            .collect();
+        transcript_entries.extend(draft.service_items.iter().map(|service| {
+            PreviewEntry {
+                item_index: None,
+                span: Some(TranscriptSpan {
+                    start_offset: service.mention.start_offset,
+                    end_offset: service.mention.end_offset,
+                    text: draft
+                        .transcript
+                        .get(service.mention.start_offset..service.mention.end_offset)
+                        .map(str::to_string)
+                        .unwrap_or_else(|| service.mention.raw_text.clone()),
+                }),
+                line_item: Some(service.to_line_item()),
+                change: PreviewChange::Unchanged,
+            }
+        }));
+        transcript_entries.sort_by_key(|e| e.span.as_ref().map(|s| s.start_offset));
+
+        let manual_entries = draft.manual_items.iter().map(|line_item| PreviewEntry {
+            item_index: None,
+            span: None,
+            line_item: Some(line_item.clone()),
+            change: PreviewChange::Added,
+        });
+
+        Self {
+            draft_id: draft.draft_id.clone(),
+            patient_id: draft.patient_id.clone(),
+            transcript: draft.transcript.clone(),
+            entries: transcript_entries
+                .into_iter()
+                .chain(manual_entries)
+                .collect(),
+            ready_to_commit: draft.all_reviewed(),
+        }
+    }
+
+    /// Line items that will be committed.
+    pub fn committed_items(&self) -> Vec<&EncounterLineItem> {
+        self.entries
+            .iter()
+            .filter_map(|e| e.line_item.as_ref())
+            .collect()
+    }
+
+    /// Number of entries with the given kind of change.
+    pub fn count(&self, matches: impl Fn(&PreviewChange) -> bool) -> usize {
+        self.entries.iter().filter(|e| matches(&e.change)).count()
+    }
+}
+
+/// The transcript span for a mention, falling back to the raw mention text
+/// if the offsets don't fit the transcript.
+fn transcript_span(transcript: &str, item: &ResolvedItem) -> TranscriptSpan {
+    let original = &item.mention.original;
+    let text = transcript
+        .get(original.start_offset..original.end_offset)
+        .map(str::to_string)
+        .unwrap_or_else(|| original.raw_text.clone());
+
+    TranscriptSpan {
+        start_offset: original.start_offset,
+        end_offset: original.end_offset,
+        text,
+    }
+}
+
+/// Classify how a resolved item's committed line differs from what was spoken.
+///
+/// The line is compared with the normalized mention, so respelling a unit
+/// ("2 cc" committed as 2 mL) or converting it ("250 mcg" as 0.25 mg) is not
+/// a change. CRIs and tapers are compared by the total they imply.
+fn item_change(item: &ResolvedItem) -> PreviewChange {
+    match item.status {
+        ResolutionStatus::PendingReview => return PreviewChange::Pending,
+        ResolutionStatus::Rejected => return PreviewChange::Dropped,
+        _ => {}
+    }
+
+    let Some(line_item) = item.to_line_item() else {
+        return PreviewChange::Dropped;
+    };
+    let mention = &item.mention;
+    let spoken = match (&mention.infusion, &mention.taper) {
+        (Some(rate), _) => rate
+            .total_amount
+            .map(|total| (total, Unit::parse(&rate.unit))),
+        (None, Some(taper)) => taper.total_amount().zip(taper.unit().map(Unit::parse)),
+        (None, None) => mention.normalized_dose.zip(mention.normalized_unit.clone()),
+    };
+
+    let unchanged = spoken.is_some_and(|(dose, unit)| {
+        unit.per_kg == line_item.unit.per_kg
+            && unit.per_hour == line_item.unit.per_hour
+            && unit
+                .amount
+                .convert(dose, &line_item.unit.amount)
+                .is_some_and(|dose| (dose - line_item.quantity).abs() < 1e-9)
+    });
+
+    if unchanged {
+        PreviewChange::Unchanged
+    } else {
+        let original = &mention.original;
+        PreviewChange::DoseChanged {
+            spoken_dose: original.dose,
+            spoken_unit: original.unit.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DrugMention, NormalizedMention, ScoreBreakdown, ScoredCandidate};
+
+    fn make_item(
+        raw: &str,
+        start: usize,
+        spoken: (f64, &str),
+        normalized: (f64, &str),
+        status: ResolutionStatus,
+    ) -> ResolvedItem {
+        ResolvedItem {
+            mention: NormalizedMention {
+                original: DrugMention {
+                    raw_text: raw.into(),
+                    drug_name: "drug".into(),
+                    dose: Some(spoken.0),
+                    unit: Some(spoken.1.into()),
+                    route: None,
+                    species: None,
+                    start_offset: start,
+                    end_offset: start + raw.len(),
+                    field_spans: Default::default(),
+                    extraction_confidence: None,
+                    speaker: None,
+                },
+                normalized_name: "drug".into(),
+                normalized_dose: Some(normalized.0),
+                normalized_unit: Some(normalized.1.into()),
+                normalized_route: None,
+                infusion: None,
+                taper: None,
+            },
+            top_candidate: ScoredCandidate {
+                sku: "SKU001".into(),
+                name: "Drug".into(),
+                confidence: 0.9,
+                score_breakdown: ScoreBreakdown {
+                    name_score: 1.0,
+                    species_score: 1.0,
+                    route_score: 1.0,
+                    dose_score: 1.0,
+                },
+                suggested_quantity: None,
+                controlled_schedule: None,
+            },
+            alternatives: vec![],
+            status,
+            controlled_confirmed_by: None,
+            safety_warnings: vec![],
+            allergy_overridden_by: None,
+            duplicate_mentions: vec![],
+            tied_skus: vec![],
+            escalation: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            override_schedule: None,
+        }
+    }
+
+    #[test]
+    fn test_preview_classifies_changes() {
+        let mut draft = EncounterDraft::new("patient-1".into());
+        draft.transcript = "Gave 2 cc cerenia and 100 mg carprofen, skip the tramadol".into();
+        draft.resolved_items = vec![
+            make_item(
+                "100 mg carprofen",
+                22,
+                (100.0, "mg"),
+                (100.0, "mg"),
+                ResolutionStatus::Approved,
+            ),
+            make_item(
+                "2 cc cerenia",
+                5,
+                (2.0, "cc"),
+                (2.0, "mL"),
+                ResolutionStatus::Approved,
+            ),
+            make_item(
+                "tramadol",
+                49,
+                (50.0, "mg"),
+                (50.0, "mg"),
+                ResolutionStatus::Rejected,
+            ),
+        ];
+        draft.add_manual_item("FLUIDS".into(), "LRS 1L".into(), 1.0, "bag".into(), None);
+
+        draft.resolved_items[0].mention.normalized_dose = None;
+
+        let preview = CommitPreview::from_draft(&draft);
+        assert!(preview.ready_to_commit);
+        assert_eq!(preview.entries.len(), 4);
+
+        // Transcript order, then manual additions; "2 cc" committed as 2 mL
+        // is only respelled
+        let cerenia = &preview.entries[0];
+        assert_eq!(cerenia.span.as_ref().unwrap().text, "2 cc cerenia");
+        assert_eq!(cerenia.change, PreviewChange::Unchanged);
+        // Carprofen lost its dose in normalization and commits as 1 mg
+        assert_eq!(
+            preview.entries[1].change,
+            PreviewChange::DoseChanged {
+                spoken_dose: Some(100.0),
+                spoken_unit: Some("mg".into())
+            }
+        );
+        assert_eq!(preview.entries[1].line_item.as_ref().unwrap().quantity, 1.0);
+        assert_eq!(preview.entries[2].change, PreviewChange::Dropped);
+        assert!(preview.entries[2].line_item.is_none());
+        assert_eq!(preview.entries[3].change, PreviewChange::Added);
+        assert!(preview.entries[3].span.is_none());
+
+        assert_eq!(preview.committed_items().len(), 3);
+        assert_eq!(preview.count(|c| matches!(c, PreviewChange::Dropped)), 1);
+    }
+
+    #[test]
+    fn test_preview_pending_not_ready() {
+        let mut draft = EncounterDraft::new("patient-1".into());
+        draft.transcript = "carprofen".into();
+        draft.resolved_items = vec![make_item(
+            "carprofen",
+            0,
+            (1.0, "mg"),
+            (1.0, "mg"),
+            ResolutionStatus::PendingReview,
+        )];
+
+        let preview = CommitPreview::from_draft(&draft);
+        assert!(!preview.ready_to_commit);
+        assert_eq!(preview.entries[0].change, PreviewChange::Pending);
+        assert!(preview.committed_items().is_empty());
+    }
+}
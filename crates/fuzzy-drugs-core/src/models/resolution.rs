@@ -2,6 +2,18 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::catalog::ControlledSchedule;
+use super::disposition::DispositionType;
+use super::dose::DoseExpression;
+use super::escalation::Escalation;
+use super::infusion::InfusionRate;
+use super::safety::SafetyWarning;
+use super::scoring::ScoringConfig;
+use super::speaker::SpeakerRole;
+use super::taper::TaperSchedule;
+use super::vocab::Unit;
+use super::withdrawal::Withdrawal;
+
 /// Extracted drug mention from NER.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct DrugMention {
@@ -21,6 +33,58 @@ pub struct DrugMention {
     pub start_offset: usize,
     /// End position in transcript
     pub end_offset: usize,
+    /// Positions of the individual fields within the mention
+    #[serde(default)]
+    pub field_spans: FieldSpans,
+    /// How sure the extractor was of this mention (0.0 - 1.0), if it says
+    #[serde(default)]
+    pub extraction_confidence: Option<f64>,
+    /// Who said it, when the transcript came with speaker turns
+    #[serde(default)]
+    pub speaker: Option<SpeakerRole>,
+}
+
+/// A character range in the transcript.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SourceSpan {
+    /// Start position in transcript
+    pub start_offset: usize,
+    /// End position in transcript
+    pub end_offset: usize,
+}
+
+impl SourceSpan {
+    /// The transcript text covered by this span, if it is a valid range.
+    pub fn text<'t>(&self, transcript: &'t str) -> Option<&'t str> {
+        transcript.get(self.start_offset..self.end_offset)
+    }
+
+    /// Whether `other` lies entirely within this span.
+    pub fn contains(&self, other: &SourceSpan) -> bool {
+        self.start_offset <= other.start_offset && other.end_offset <= self.end_offset
+    }
+}
+
+/// Transcript positions of the tokens each mention field was read from, so a
+/// UI can highlight just the dose or route the system interpreted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FieldSpans {
+    pub drug: Option<SourceSpan>,
+    pub dose: Option<SourceSpan>,
+    pub unit: Option<SourceSpan>,
+    pub route: Option<SourceSpan>,
+    /// Dosing frequency ("BID", "twice daily")
+    pub frequency: Option<SourceSpan>,
+}
+
+impl DrugMention {
+    /// Where this mention sits in the transcript.
+    pub fn span(&self) -> SourceSpan {
+        SourceSpan {
+            start_offset: self.start_offset,
+            end_offset: self.end_offset,
+        }
+    }
 }
 
 /// Normalized drug mention after alias/unit conversion.
@@ -32,10 +96,29 @@ pub struct NormalizedMention {
     pub normalized_name: String,
     /// Normalized dose (after unit conversion)
     pub normalized_dose: Option<f64>,
-    /// Normalized unit (canonical form: mg, mL, etc.)
-    pub normalized_unit: Option<String>,
+    /// Normalized unit (canonical form: mg, mL, mg/kg, etc.)
+    pub normalized_unit: Option<Unit>,
     /// Normalized route (canonical form: PO, IV, IM, SQ, etc.)
     pub normalized_route: Option<String>,
+    /// Rate-based dose for constant rate infusions ("3 mcg/kg/hr for 6 hours")
+    #[serde(default)]
+    pub infusion: Option<InfusionRate>,
+    /// Dosing phases for a taper ("20mg BID for 5 days, then 10mg SID for 5 days")
+    #[serde(default)]
+    pub taper: Option<TaperSchedule>,
+}
+
+impl NormalizedMention {
+    /// The normalized dose as an absolute, per-kg, or rate expression.
+    pub fn dose_expression(&self) -> Option<DoseExpression> {
+        if let Some(rate) = &self.infusion {
+            return Some(DoseExpression::Rate(rate.clone()));
+        }
+        Some(DoseExpression::from_dose(
+            self.normalized_dose?,
+            self.normalized_unit.as_ref()?,
+        ))
+    }
 }
 
 /// A candidate SKU match with scoring breakdown.
@@ -49,28 +132,43 @@ pub struct ScoredCandidate {
     pub confidence: f64,
     /// Breakdown of scoring factors
     pub score_breakdown: ScoreBreakdown,
+    /// Suggested per-dose quantity of this product (tablets, capsules, or mL)
+    #[serde(default)]
+    pub suggested_quantity: Option<SuggestedQuantity>,
+    /// DEA schedule of the product, if controlled
+    #[serde(default)]
+    pub controlled_schedule: Option<ControlledSchedule>,
+}
+
+/// Suggested per-dose quantity for a candidate product.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SuggestedQuantity {
+    /// Amount per administration, in `unit`
+    pub per_dose: f64,
+    /// Dispensing unit ("tablets", "capsules", "mL")
+    pub unit: String,
+    /// How cleanly the dose splits into tablets (1.0 = one whole tablet);
+    /// `None` for liquids
+    pub fraction_score: Option<f64>,
 }
 
 /// Breakdown of how a candidate was scored.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ScoreBreakdown {
-    /// Name/alias match quality (0.0 - 1.0) - weight: 40%
+    /// Name/alias match quality (0.0 - 1.0) - default weight: 40%
     pub name_score: f64,
-    /// Species compatibility (0.0 - 1.0) - weight: 25%
+    /// Species compatibility (0.0 - 1.0) - default weight: 25%
     pub species_score: f64,
-    /// Route compatibility (0.0 - 1.0) - weight: 20%
+    /// Route compatibility (0.0 - 1.0) - default weight: 20%
     pub route_score: f64,
-    /// Dose plausibility (0.0 - 1.0) - weight: 15%
+    /// Dose plausibility (0.0 - 1.0) - default weight: 15%
     pub dose_score: f64,
 }
 
 impl ScoreBreakdown {
-    /// Calculate weighted confidence score.
+    /// Calculate weighted confidence score with the default weights.
     pub fn weighted_score(&self) -> f64 {
-        self.name_score * 0.40
-            + self.species_score * 0.25
-            + self.route_score * 0.20
-            + self.dose_score * 0.15
+        ScoringConfig::default().confidence(self)
     }
 }
 
@@ -85,6 +183,47 @@ pub struct ResolvedItem {
     pub alternatives: Vec<ScoredCandidate>,
     /// Resolution status
     pub status: ResolutionStatus,
+    /// Reviewer who explicitly confirmed a controlled substance
+    #[serde(default)]
+    pub controlled_confirmed_by: Option<String>,
+    /// Catalog DEA schedule of a chosen SKU that isn't among the candidates
+    /// (a manual override), looked up when it is chosen
+    #[serde(default)]
+    pub override_schedule: Option<ControlledSchedule>,
+    /// Species/breed contraindications and patient allergies for the top
+    /// candidate or alternatives
+    #[serde(default)]
+    pub safety_warnings: Vec<SafetyWarning>,
+    /// Reviewer who chose to give the item despite a blocking (allergy)
+    /// warning for its SKU
+    #[serde(default)]
+    pub allergy_overridden_by: Option<String>,
+    /// Repeats of the same mention merged into this item (kept for audit)
+    #[serde(default)]
+    pub duplicate_mentions: Vec<DrugMention>,
+    /// SKUs scoring within the ambiguity margin of each other, top candidate
+    /// first (e.g., Carprofen 75mg vs 100mg with no dose spoken); empty when
+    /// the top candidate is a clear winner
+    #[serde(default)]
+    pub tied_skus: Vec<String>,
+    /// Escalated review required for a restricted ingredient (None if the
+    /// item is unrestricted)
+    #[serde(default)]
+    pub escalation: Option<Escalation>,
+    /// Given in clinic, dispensed, or prescribed; inferred from transcript
+    /// cues and confirmed by the reviewer
+    #[serde(default)]
+    pub disposition: Option<DispositionType>,
+    /// Manufacturer lot the reviewer recorded (vaccines and controlled drugs)
+    #[serde(default)]
+    pub lot_number: Option<String>,
+    /// Expiration date of the lot (YYYY-MM-DD)
+    #[serde(default)]
+    pub expiration_date: Option<String>,
+    /// Meat/milk withdrawal for the top candidate, for food-animal patients
+    /// (counted from the day of resolution)
+    #[serde(default)]
+    pub withdrawal: Option<Withdrawal>,
 }
 
 /// Status of a drug resolution.
@@ -113,9 +252,145 @@ impl ResolvedItem {
         }
     }
 
+    /// The candidate matching the final SKU, if it was among the candidates.
+    pub fn final_candidate(&self) -> Option<&ScoredCandidate> {
+        let sku = self.final_sku()?;
+        std::iter::once(&self.top_candidate)
+            .chain(self.alternatives.iter())
+            .find(|c| c.sku == sku)
+    }
+
+    /// DEA schedule of the item as it stands (final choice, else top candidate).
+    pub fn controlled_schedule(&self) -> Option<ControlledSchedule> {
+        match self.final_sku() {
+            Some(_) => match self.final_candidate() {
+                Some(candidate) => candidate.controlled_schedule,
+                None => self.override_schedule,
+            },
+            None => self.top_candidate.controlled_schedule,
+        }
+    }
+
+    /// Whether a controlled item is accepted but not yet explicitly confirmed.
+    pub fn requires_controlled_confirmation(&self) -> bool {
+        self.final_sku().is_some()
+            && self.controlled_schedule().is_some()
+            && self.controlled_confirmed_by.is_none()
+    }
+
+    /// Record explicit reviewer confirmation of a controlled substance.
+    pub fn confirm_controlled(&mut self, reviewer: String) {
+        self.controlled_confirmed_by = Some(reviewer);
+    }
+
+    /// Record a review decision, returning whether the chosen SKU changed.
+    ///
+    /// The chosen SKU is the final SKU, or the top candidate while pending or
+    /// rejected. A controlled-substance confirmation and a recorded lot apply
+    /// to the SKU they were given for, so changing the SKU clears them, as
+    /// does an allergy override. The caller looks up the new SKU's
+    /// `override_schedule`.
+    pub fn review(&mut self, status: ResolutionStatus) -> bool {
+        let previous = self.chosen_sku().to_string();
+        self.status = status;
+        let changed = self.chosen_sku() != previous;
+        if changed {
+            self.controlled_confirmed_by = None;
+            self.override_schedule = None;
+            self.allergy_overridden_by = None;
+            self.lot_number = None;
+            self.expiration_date = None;
+        }
+        changed
+    }
+
+    fn chosen_sku(&self) -> &str {
+        self.final_sku().unwrap_or(&self.top_candidate.sku)
+    }
+
+    /// Whether an accepted item must have its lot recorded: controlled drugs
+    /// given or dispensed from stock (not ones only prescribed).
+    pub fn requires_lot(&self) -> bool {
+        self.final_sku().is_some()
+            && self.controlled_schedule().is_some()
+            && self.disposition != Some(DispositionType::Prescribed)
+    }
+
+    /// Whether this item requires a lot that hasn't been recorded.
+    pub fn missing_lot(&self) -> bool {
+        self.requires_lot() && self.lot_number.is_none()
+    }
+
+    /// Whether a restricted item is accepted but not yet approved by a
+    /// reviewer with the required role.
+    pub fn requires_escalation_approval(&self) -> bool {
+        self.final_sku().is_some()
+            && self
+                .escalation
+                .as_ref()
+                .is_some_and(|e| e.approval.is_none())
+    }
+
+    /// Whether any candidate for this item is flagged as unsafe for the patient.
+    pub fn has_safety_warnings(&self) -> bool {
+        !self.safety_warnings.is_empty()
+    }
+
+    /// Blocking warnings (recorded allergies) for the SKU as it stands
+    /// (final choice, else top candidate).
+    pub fn blocking_warnings(&self) -> impl Iterator<Item = &SafetyWarning> {
+        let sku = self.chosen_sku();
+        self.safety_warnings
+            .iter()
+            .filter(move |w| w.blocking && w.sku == sku)
+    }
+
+    /// Replace the blocking warnings for the SKU as it stands, e.g. after
+    /// checking it against the patient's allergies again.
+    pub fn replace_blocking_warnings(&mut self, warnings: Vec<SafetyWarning>) {
+        let sku = self.chosen_sku().to_string();
+        self.safety_warnings
+            .retain(|w| !(w.blocking && w.sku == sku));
+        self.safety_warnings.extend(warnings);
+    }
+
+    /// Whether an accepted item matches a recorded allergy that no reviewer
+    /// has overridden.
+    pub fn requires_allergy_override(&self) -> bool {
+        self.final_sku().is_some()
+            && self.allergy_overridden_by.is_none()
+            && self.blocking_warnings().next().is_some()
+    }
+
+    /// Record that `reviewer` chose to give the item despite its allergy
+    /// warnings.
+    pub fn override_allergy(&mut self, reviewer: String) {
+        self.allergy_overridden_by = Some(reviewer);
+    }
+
+    /// Whether the top candidates are too close to call.
+    pub fn is_ambiguous(&self) -> bool {
+        !self.tied_skus.is_empty()
+    }
+
+    /// Transcript spans of this mention and any merged repeats, in order.
+    pub fn source_spans(&self) -> Vec<SourceSpan> {
+        std::iter::once(&self.mention.original)
+            .chain(self.duplicate_mentions.iter())
+            .map(DrugMention::span)
+            .collect()
+    }
+
     /// Check if this item needs vet attention.
+    ///
+    /// Controlled substances stay pending until explicitly confirmed,
+    /// restricted items until escalated approval, and items matching an
+    /// allergy until overridden, even once the SKU has been approved.
     pub fn needs_review(&self) -> bool {
         matches!(self.status, ResolutionStatus::PendingReview)
+            || self.requires_controlled_confirmation()
+            || self.requires_escalation_approval()
+            || self.requires_allergy_override()
     }
 }
 
@@ -154,11 +429,16 @@ mod tests {
                 species: None,
                 start_offset: 0,
                 end_offset: 4,
+                field_spans: Default::default(),
+                extraction_confidence: None,
+                speaker: None,
             },
             normalized_name: "test".into(),
             normalized_dose: None,
             normalized_unit: None,
             normalized_route: None,
+            infusion: None,
+            taper: None,
         };
 
         let candidate = ScoredCandidate {
@@ -171,6 +451,8 @@ mod tests {
                 route_score: 1.0,
                 dose_score: 1.0,
             },
+            suggested_quantity: None,
+            controlled_schedule: None,
         };
 
         let mut item = ResolvedItem {
@@ -178,6 +460,17 @@ mod tests {
             top_candidate: candidate,
             alternatives: vec![],
             status: ResolutionStatus::PendingReview,
+            controlled_confirmed_by: None,
+            safety_warnings: vec![],
+            allergy_overridden_by: None,
+            duplicate_mentions: vec![],
+            tied_skus: vec![],
+            escalation: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            override_schedule: None,
         };
 
         assert!(item.needs_review());
@@ -192,4 +485,83 @@ mod tests {
         };
         assert_eq!(item.final_sku(), Some("SKU002"));
     }
+
+    #[test]
+    fn test_controlled_requires_confirmation() {
+        let candidate = |sku: &str, schedule| ScoredCandidate {
+            sku: sku.into(),
+            name: sku.into(),
+            confidence: 0.9,
+            score_breakdown: ScoreBreakdown {
+                name_score: 0.9,
+                species_score: 1.0,
+                route_score: 1.0,
+                dose_score: 1.0,
+            },
+            suggested_quantity: None,
+            controlled_schedule: schedule,
+        };
+        let mut item = ResolvedItem {
+            mention: NormalizedMention {
+                original: DrugMention {
+                    raw_text: "0.3 mg bupe".into(),
+                    drug_name: "bupe".into(),
+                    dose: Some(0.3),
+                    unit: Some("mg".into()),
+                    route: None,
+                    species: None,
+                    start_offset: 0,
+                    end_offset: 11,
+                    field_spans: Default::default(),
+                    extraction_confidence: None,
+                    speaker: None,
+                },
+                normalized_name: "buprenorphine".into(),
+                normalized_dose: Some(0.3),
+                normalized_unit: Some("mg".into()),
+                normalized_route: None,
+                infusion: None,
+                taper: None,
+            },
+            top_candidate: candidate("BUP-03", Some(ControlledSchedule::CIII)),
+            alternatives: vec![candidate("MELOX", None)],
+            status: ResolutionStatus::Approved,
+            controlled_confirmed_by: None,
+            safety_warnings: vec![],
+            allergy_overridden_by: None,
+            duplicate_mentions: vec![],
+            tied_skus: vec![],
+            escalation: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            override_schedule: None,
+        };
+
+        assert_eq!(item.controlled_schedule(), Some(ControlledSchedule::CIII));
+        assert!(item.needs_review());
+
+        item.confirm_controlled("Dr. Smith".into());
+        assert!(!item.needs_review());
+
+        // Selecting a non-controlled alternative needs no confirmation
+        item.controlled_confirmed_by = None;
+        item.status = ResolutionStatus::AlternativeSelected {
+            selected_sku: "MELOX".into(),
+        };
+        assert_eq!(item.controlled_schedule(), None);
+        assert!(!item.needs_review());
+
+        // Going back to the controlled SKU needs a fresh confirmation
+        item.confirm_controlled("Dr. Smith".into());
+        assert!(item.review(ResolutionStatus::Approved));
+        assert!(item.controlled_confirmed_by.is_none());
+        assert!(item.needs_review());
+
+        // Re-approving the same SKU keeps it
+        item.confirm_controlled("Dr. Smith".into());
+        assert!(!item.review(ResolutionStatus::Approved));
+        assert!(!item.needs_review());
+    }
 }
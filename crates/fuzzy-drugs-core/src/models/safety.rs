@@ -0,0 +1,46 @@
+//! Species/breed and allergy safety warnings attached to resolved items.
+
+use serde::{Deserialize, Serialize};
+
+/// How dangerous a flagged match is.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SafetySeverity {
+    /// Use with care (dose limits, monitoring)
+    Caution,
+    /// Do not give to this patient
+    Contraindicated,
+}
+
+impl SafetySeverity {
+    /// Lowercase name ("caution", "contraindicated").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SafetySeverity::Caution => "caution",
+            SafetySeverity::Contraindicated => "contraindicated",
+        }
+    }
+}
+
+/// A contraindication found for a candidate SKU and this patient.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SafetyWarning {
+    /// Candidate SKU the warning applies to
+    pub sku: String,
+    /// Offending drug (generic name)
+    pub drug: String,
+    /// Severity
+    pub severity: SafetySeverity,
+    /// Why it is dangerous (e.g., "fatal neurotoxicity in cats")
+    pub reason: String,
+    /// The item can't be committed on this SKU until a reviewer overrides
+    /// the warning (recorded patient allergies)
+    #[serde(default)]
+    pub blocking: bool,
+}
+
+impl SafetyWarning {
+    /// Display message, e.g. "permethrin: fatal neurotoxicity in cats".
+    pub fn message(&self) -> String {
+        format!("{}: {}", self.drug, self.reason)
+    }
+}
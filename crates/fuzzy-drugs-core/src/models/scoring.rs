@@ -0,0 +1,241 @@
+//! Disambiguator scoring configuration.
+//!
+//! A candidate's confidence is a weighted sum of its name, species, route,
+//! and dose scores. The weights and cut-offs are stored per clinic in the
+//! database so ranking can be tuned without an app release.
+//!
+//! When the extractor reports how sure it was of a mention, that is blended
+//! in as well (see [`ScoringConfig::blend_extraction`]), so a shaky mention
+//! can't produce a confident match.
+
+use serde::{Deserialize, Serialize};
+
+use super::resolution::ScoreBreakdown;
+use super::trace::ScoreFactor;
+
+/// Default minimum confidence to be considered a candidate.
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.20;
+
+/// Default number of candidates to retrieve from FTS5.
+pub const DEFAULT_FTS_CANDIDATE_LIMIT: usize = 20;
+
+/// Default number of alternatives to include in results.
+pub const DEFAULT_MAX_ALTERNATIVES: usize = 4;
+
+/// Default share of a candidate's confidence that depends on extraction
+/// confidence.
+pub const DEFAULT_EXTRACTION_WEIGHT: f64 = 0.5;
+
+/// Largest accepted FTS5 candidate limit.
+pub const MAX_FTS_CANDIDATE_LIMIT: usize = 200;
+
+/// Weights and limits used to rank catalog candidates.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ScoringConfig {
+    /// Weight of the name/alias match score
+    pub name_weight: f64,
+    /// Weight of the species compatibility score
+    pub species_weight: f64,
+    /// Weight of the route compatibility score
+    pub route_weight: f64,
+    /// Weight of the dose plausibility score
+    pub dose_weight: f64,
+    /// Candidates below this confidence are dropped
+    pub min_confidence: f64,
+    /// Candidates retrieved from full-text search before scoring
+    pub fts_candidate_limit: usize,
+    /// Alternatives returned after the top candidate
+    pub max_alternatives: usize,
+    /// How much a low extraction confidence pulls candidates down (0 ignores it)
+    pub extraction_weight: f64,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            name_weight: ScoreFactor::Name.weight(),
+            species_weight: ScoreFactor::Species.weight(),
+            route_weight: ScoreFactor::Route.weight(),
+            dose_weight: ScoreFactor::Dose.weight(),
+            min_confidence: DEFAULT_MIN_CONFIDENCE,
+            fts_candidate_limit: DEFAULT_FTS_CANDIDATE_LIMIT,
+            max_alternatives: DEFAULT_MAX_ALTERNATIVES,
+            extraction_weight: DEFAULT_EXTRACTION_WEIGHT,
+        }
+    }
+}
+
+impl ScoringConfig {
+    /// Weight of a factor in the confidence score.
+    pub fn weight(&self, factor: ScoreFactor) -> f64 {
+        match factor {
+            ScoreFactor::Name => self.name_weight,
+            ScoreFactor::Species => self.species_weight,
+            ScoreFactor::Route => self.route_weight,
+            ScoreFactor::Dose => self.dose_weight,
+        }
+    }
+
+    /// Weighted contribution of a factor to a candidate's confidence.
+    pub fn contribution(&self, breakdown: &ScoreBreakdown, factor: ScoreFactor) -> f64 {
+        breakdown.score(factor) * self.weight(factor)
+    }
+
+    /// Confidence score (0.0 - 1.0) for a breakdown.
+    pub fn confidence(&self, breakdown: &ScoreBreakdown) -> f64 {
+        ScoreFactor::ALL
+            .into_iter()
+            .map(|factor| self.contribution(breakdown, factor))
+            .sum()
+    }
+
+    /// Scale a candidate's `confidence` by how sure the extractor was of the
+    /// mention (`extraction`, 0.0 - 1.0). With weight `w` the result is
+    /// `confidence * (1 - w + w * extraction)`: a certain mention leaves the
+    /// score unchanged, and an uncertain one costs at most `w` of it.
+    pub fn blend_extraction(&self, confidence: f64, extraction: f64) -> f64 {
+        let extraction = if extraction.is_finite() {
+            extraction.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        confidence * (1.0 - self.extraction_weight + self.extraction_weight * extraction)
+    }
+
+    /// Factor contributing most to a breakdown's confidence.
+    pub fn dominant_factor(&self, breakdown: &ScoreBreakdown) -> ScoreFactor {
+        ScoreFactor::ALL
+            .into_iter()
+            .max_by(|a, b| {
+                self.contribution(breakdown, *a)
+                    .partial_cmp(&self.contribution(breakdown, *b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap_or(ScoreFactor::Name)
+    }
+
+    /// Check that the weights are non-negative and sum to 1 (so confidence
+    /// stays within 0.0 - 1.0) and that the limits are usable.
+    pub fn validate(&self) -> Result<(), String> {
+        for factor in ScoreFactor::ALL {
+            let weight = self.weight(factor);
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(format!("{} weight must be non-negative", factor));
+            }
+        }
+        let total: f64 = ScoreFactor::ALL.iter().map(|f| self.weight(*f)).sum();
+        if (total - 1.0).abs() > 1e-6 {
+            return Err(format!("Weights must sum to 1 (got {})", total));
+        }
+        if !(0.0..=1.0).contains(&self.min_confidence) {
+            return Err("Minimum confidence must be between 0 and 1".into());
+        }
+        if !(0.0..=1.0).contains(&self.extraction_weight) {
+            return Err("Extraction weight must be between 0 and 1".into());
+        }
+        if !(1..=MAX_FTS_CANDIDATE_LIMIT).contains(&self.fts_candidate_limit) {
+            return Err(format!(
+                "FTS candidate limit must be between 1 and {}",
+                MAX_FTS_CANDIDATE_LIMIT
+            ));
+        }
+        if self.max_alternatives >= self.fts_candidate_limit {
+            return Err("Alternatives must be fewer than the FTS candidate limit".into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_matches_factor_weights() {
+        let config = ScoringConfig::default();
+        assert!(config.validate().is_ok());
+
+        let breakdown = ScoreBreakdown {
+            name_score: 1.0,
+            species_score: 0.5,
+            route_score: 0.2,
+            dose_score: 0.8,
+        };
+        assert!((config.confidence(&breakdown) - breakdown.weighted_score()).abs() < 1e-12);
+        assert_eq!(
+            config.dominant_factor(&breakdown),
+            breakdown.dominant_factor()
+        );
+    }
+
+    #[test]
+    fn test_custom_weights() {
+        let config = ScoringConfig {
+            name_weight: 0.2,
+            species_weight: 0.2,
+            route_weight: 0.5,
+            dose_weight: 0.1,
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let breakdown = ScoreBreakdown {
+            name_score: 1.0,
+            species_score: 0.5,
+            route_score: 0.2,
+            dose_score: 1.0,
+        };
+        assert!((config.confidence(&breakdown) - 0.5).abs() < 1e-12);
+        assert_eq!(config.dominant_factor(&breakdown), ScoreFactor::Name);
+    }
+
+    #[test]
+    fn test_validate() {
+        let unbalanced = ScoringConfig {
+            name_weight: 0.5,
+            ..Default::default()
+        };
+        assert!(unbalanced.validate().unwrap_err().contains("sum to 1"));
+
+        let negative = ScoringConfig {
+            name_weight: 0.7,
+            dose_weight: -0.15,
+            ..Default::default()
+        };
+        assert!(negative.validate().is_err());
+
+        let no_candidates = ScoringConfig {
+            fts_candidate_limit: 0,
+            ..Default::default()
+        };
+        assert!(no_candidates.validate().is_err());
+
+        let too_many_alternatives = ScoringConfig {
+            max_alternatives: 20,
+            ..Default::default()
+        };
+        assert!(too_many_alternatives.validate().is_err());
+
+        let extraction_overweight = ScoringConfig {
+            extraction_weight: 1.5,
+            ..Default::default()
+        };
+        assert!(extraction_overweight.validate().is_err());
+    }
+
+    #[test]
+    fn test_blend_extraction() {
+        let config = ScoringConfig::default();
+        assert!((config.blend_extraction(0.8, 1.0) - 0.8).abs() < 1e-12);
+        assert!((config.blend_extraction(0.8, 0.5) - 0.6).abs() < 1e-12);
+        assert!((config.blend_extraction(0.8, 0.0) - 0.4).abs() < 1e-12);
+        assert!((config.blend_extraction(0.8, f64::NAN) - 0.4).abs() < 1e-12);
+
+        let ignored = ScoringConfig {
+            extraction_weight: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(ignored.blend_extraction(0.8, 0.1), 0.8);
+    }
+}
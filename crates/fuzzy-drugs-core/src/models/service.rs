@@ -0,0 +1,194 @@
+//! Non-drug services: procedures, vaccines, and diagnostics.
+//!
+//! Half of a visit's bill is often services ("nail trim", "rabies vaccine",
+//! "radiographs") rather than drugs. The extractor reports them as
+//! [`ServiceMention`]s, which are matched against the clinic's services
+//! catalog ([`ServiceItem`]) and land on the draft as [`ServiceLineItem`]s.
+
+use serde::{Deserialize, Serialize};
+
+use super::encounter::{EncounterLineItem, ResolutionMethod};
+use super::resolution::SourceSpan;
+
+/// What kind of service a mention or catalog entry is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceKind {
+    /// Exam, nail trim, dental cleaning, surgery
+    Procedure,
+    /// Rabies, DHPP, FVRCP, leptospirosis
+    Vaccine,
+    /// Radiographs, bloodwork, urinalysis, cytology
+    Diagnostic,
+}
+
+impl ServiceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceKind::Procedure => "procedure",
+            ServiceKind::Vaccine => "vaccine",
+            ServiceKind::Diagnostic => "diagnostic",
+        }
+    }
+
+    /// Parse a kind name (case-insensitive), or a common synonym.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "procedure" | "treatment" | "surgery" => Some(ServiceKind::Procedure),
+            "vaccine" | "vaccination" => Some(ServiceKind::Vaccine),
+            "diagnostic" | "test" | "lab" | "imaging" => Some(ServiceKind::Diagnostic),
+            _ => None,
+        }
+    }
+}
+
+/// An entry in the clinic's services catalog.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceItem {
+    /// Service code - unique identifier (e.g., "PROC-NAIL")
+    pub code: String,
+    /// Service name as billed
+    pub name: String,
+    /// Alternative names for matching (e.g., ["x-rays", "rads"])
+    pub aliases: Vec<String>,
+    pub kind: ServiceKind,
+    /// Client price per unit
+    pub unit_price: Option<f64>,
+    /// Sales tax code (None if untaxed)
+    pub tax_code: Option<String>,
+    /// Whether the service is currently offered
+    pub active: bool,
+    /// Antigen a vaccine protects against ("rabies"), shared by its 1- and
+    /// 3-year products; the service name if unset
+    #[serde(default)]
+    pub antigen: Option<String>,
+    /// Days from a vaccine dose until the next is due
+    #[serde(default)]
+    pub booster_interval_days: Option<u32>,
+}
+
+impl ServiceItem {
+    pub fn new(code: String, name: String, kind: ServiceKind) -> Self {
+        Self {
+            code,
+            name,
+            aliases: Vec::new(),
+            kind,
+            unit_price: None,
+            tax_code: None,
+            active: true,
+            antigen: None,
+            booster_interval_days: None,
+        }
+    }
+
+    /// The antigen a vaccination with this service is recorded under.
+    pub fn antigen(&self) -> &str {
+        self.antigen.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// A service mention found by the extractor.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceMention {
+    /// Raw text as spoken/transcribed
+    pub raw_text: String,
+    /// Extracted service name ("nail trim")
+    pub service_name: String,
+    /// Kind, if the extractor said
+    pub kind: Option<ServiceKind>,
+    /// How many were done ("two views"), if said
+    pub quantity: Option<f64>,
+    /// Start position in transcript
+    pub start_offset: usize,
+    /// End position in transcript
+    pub end_offset: usize,
+}
+
+impl ServiceMention {
+    /// Where this mention sits in the transcript.
+    pub fn span(&self) -> SourceSpan {
+        SourceSpan {
+            start_offset: self.start_offset,
+            end_offset: self.end_offset,
+        }
+    }
+}
+
+/// A service mention matched to the services catalog, on a draft.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceLineItem {
+    /// The mention it was matched from
+    pub mention: ServiceMention,
+    /// Matched service code
+    pub code: String,
+    /// Matched service name
+    pub name: String,
+    pub kind: ServiceKind,
+    /// Quantity billed (the mention's, or 1)
+    pub quantity: f64,
+    /// Name similarity of the match (0.0 - 1.0)
+    pub confidence: f64,
+    /// Manufacturer lot the reviewer recorded (required for vaccines)
+    #[serde(default)]
+    pub lot_number: Option<String>,
+    /// Expiration date of the lot (YYYY-MM-DD)
+    #[serde(default)]
+    pub expiration_date: Option<String>,
+}
+
+impl ServiceLineItem {
+    /// The line item this service commits as.
+    pub fn to_line_item(&self) -> EncounterLineItem {
+        EncounterLineItem {
+            sku: self.code.clone(),
+            name: self.name.clone(),
+            quantity: self.quantity,
+            unit: "each".into(),
+            route: None,
+            original_mention: self.mention.raw_text.clone(),
+            resolution_method: ResolutionMethod::SystemApproved {
+                confidence: self.confidence,
+            },
+            controlled_schedule: None,
+            source_spans: vec![self.mention.span()],
+            schedule: None,
+            disposition: None,
+            lot_number: self.lot_number.clone(),
+            expiration_date: self.expiration_date.clone(),
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
+        }
+    }
+
+    /// Whether a lot must be recorded: vaccines are tracked by lot.
+    pub fn requires_lot(&self) -> bool {
+        self.kind == ServiceKind::Vaccine
+    }
+
+    /// Whether this item requires a lot that hasn't been recorded.
+    pub fn missing_lot(&self) -> bool {
+        self.requires_lot() && self.lot_number.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(
+            ServiceKind::parse("Vaccination"),
+            Some(ServiceKind::Vaccine)
+        );
+        assert_eq!(ServiceKind::parse(" lab "), Some(ServiceKind::Diagnostic));
+        assert_eq!(
+            ServiceKind::parse("procedure").unwrap().as_str(),
+            "procedure"
+        );
+        assert_eq!(ServiceKind::parse("diagnosis"), None);
+    }
+}
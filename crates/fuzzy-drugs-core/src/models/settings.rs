@@ -0,0 +1,522 @@
+//! Clinic settings chosen when the database is opened.
+//!
+//! Scoring weights live in their own table (see [`super::ScoringConfig`]);
+//! everything else the host app configures at open time is stored here so
+//! it survives restarts.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Order of the pending review queue.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewQueueOrder {
+    /// Safety warnings first, then ambiguous matches, then lowest confidence
+    #[default]
+    Attention,
+    /// Most recently updated first
+    NewestFirst,
+    /// Least recently updated first
+    OldestFirst,
+}
+
+impl ReviewQueueOrder {
+    /// Database/FFI name ("attention", "newest_first", "oldest_first").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReviewQueueOrder::Attention => "attention",
+            ReviewQueueOrder::NewestFirst => "newest_first",
+            ReviewQueueOrder::OldestFirst => "oldest_first",
+        }
+    }
+
+    /// Parse an order name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "attention" => Some(ReviewQueueOrder::Attention),
+            "newest_first" | "newest" => Some(ReviewQueueOrder::NewestFirst),
+            "oldest_first" | "oldest" => Some(ReviewQueueOrder::OldestFirst),
+            _ => None,
+        }
+    }
+}
+
+/// What an encounter sync does when the PIMS tree has diverged from the
+/// one last synced (see `merkle::SyncConflict`).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncConflictStrategy {
+    /// Push the local tree as usual; the PIMS appends what it's missing
+    #[default]
+    LocalWins,
+    /// Push only the local leaves for the PIMS to re-append onto its own
+    /// tree, and take its root as the new baseline
+    ServerRebase,
+    /// Stop syncing until someone picks one of the other strategies
+    Manual,
+}
+
+impl SyncConflictStrategy {
+    /// Database/FFI name ("local_wins", "server_rebase", "manual").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncConflictStrategy::LocalWins => "local_wins",
+            SyncConflictStrategy::ServerRebase => "server_rebase",
+            SyncConflictStrategy::Manual => "manual",
+        }
+    }
+
+    /// Parse a strategy name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "local_wins" | "local" => Some(SyncConflictStrategy::LocalWins),
+            "server_rebase" | "rebase" => Some(SyncConflictStrategy::ServerRebase),
+            "manual" => Some(SyncConflictStrategy::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// Persisted clinic settings (other than scoring).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoreSettings {
+    /// How pending drafts are ordered for review
+    pub review_queue_order: ReviewQueueOrder,
+    /// Dictation language tag ("en", "es"); `None` keeps the normalizer's
+    pub locale: Option<String>,
+    /// System identifier stamped on compliance exports
+    pub system_id: Option<String>,
+    /// How encounter sync handles a diverged PIMS tree
+    pub sync_conflict_strategy: SyncConflictStrategy,
+}
+
+/// QuickBooks item and income account for one SKU.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuickBooksItem {
+    /// Item name in the QuickBooks item list
+    pub item: String,
+    /// Income account; `None` uses the mapping's default
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// How billing exports map onto a QuickBooks company file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuickBooksMapping {
+    /// Receivable account each invoice is posted to
+    pub receivable_account: String,
+    /// Income account for items without their own
+    pub income_account: String,
+    /// Liability account sales tax is credited to
+    #[serde(default = "default_tax_account")]
+    pub tax_account: String,
+    /// Per-SKU overrides; unmapped SKUs use the SKU as the item name
+    #[serde(default)]
+    pub items: BTreeMap<String, QuickBooksItem>,
+}
+
+fn default_tax_account() -> String {
+    "Sales Tax Payable".to_string()
+}
+
+impl Default for QuickBooksMapping {
+    /// QuickBooks' standard account names.
+    fn default() -> Self {
+        Self {
+            receivable_account: "Accounts Receivable".to_string(),
+            income_account: "Sales".to_string(),
+            tax_account: default_tax_account(),
+            items: BTreeMap::new(),
+        }
+    }
+}
+
+impl QuickBooksMapping {
+    /// QuickBooks item name for `sku`.
+    pub fn item_name<'a>(&'a self, sku: &'a str) -> &'a str {
+        self.items.get(sku).map_or(sku, |item| item.item.as_str())
+    }
+
+    /// Income account for `sku`.
+    pub fn income_account_for(&self, sku: &str) -> &str {
+        self.items
+            .get(sku)
+            .and_then(|item| item.account.as_deref())
+            .unwrap_or(&self.income_account)
+    }
+}
+
+/// A field a billing CSV column can hold.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvColumn {
+    DraftId,
+    PatientId,
+    PatientServerId,
+    /// Client (owner) the patient is linked to
+    ClientId,
+    Sku,
+    Description,
+    Quantity,
+    Unit,
+    Route,
+    RouteDescription,
+    ControlledSchedule,
+    Disposition,
+    UnitPrice,
+    Amount,
+    TaxCode,
+    Tax,
+    ReviewedBy,
+    ReviewedAt,
+    ExportedAt,
+    MerkleHash,
+    DeviceId,
+    /// Reviewer's state license number
+    PrescriberLicense,
+    /// Reviewer's DEA registration number
+    PrescriberDea,
+}
+
+impl CsvColumn {
+    /// Every column, in declaration order.
+    pub const ALL: [CsvColumn; 23] = [
+        CsvColumn::DraftId,
+        CsvColumn::PatientId,
+        CsvColumn::PatientServerId,
+        CsvColumn::ClientId,
+        CsvColumn::Sku,
+        CsvColumn::Description,
+        CsvColumn::Quantity,
+        CsvColumn::Unit,
+        CsvColumn::Route,
+        CsvColumn::RouteDescription,
+        CsvColumn::ControlledSchedule,
+        CsvColumn::Disposition,
+        CsvColumn::UnitPrice,
+        CsvColumn::Amount,
+        CsvColumn::TaxCode,
+        CsvColumn::Tax,
+        CsvColumn::ReviewedBy,
+        CsvColumn::ReviewedAt,
+        CsvColumn::ExportedAt,
+        CsvColumn::MerkleHash,
+        CsvColumn::DeviceId,
+        CsvColumn::PrescriberLicense,
+        CsvColumn::PrescriberDea,
+    ];
+
+    /// Database/FFI name, also the default header ("draft_id", "sku", ...).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CsvColumn::DraftId => "draft_id",
+            CsvColumn::PatientId => "patient_id",
+            CsvColumn::PatientServerId => "patient_server_id",
+            CsvColumn::ClientId => "client_id",
+            CsvColumn::Sku => "sku",
+            CsvColumn::Description => "description",
+            CsvColumn::Quantity => "quantity",
+            CsvColumn::Unit => "unit",
+            CsvColumn::Route => "route",
+            CsvColumn::RouteDescription => "route_description",
+            CsvColumn::ControlledSchedule => "controlled_schedule",
+            CsvColumn::Disposition => "disposition",
+            CsvColumn::UnitPrice => "unit_price",
+            CsvColumn::Amount => "amount",
+            CsvColumn::TaxCode => "tax_code",
+            CsvColumn::Tax => "tax",
+            CsvColumn::ReviewedBy => "reviewed_by",
+            CsvColumn::ReviewedAt => "reviewed_at",
+            CsvColumn::ExportedAt => "exported_at",
+            CsvColumn::MerkleHash => "merkle_hash",
+            CsvColumn::DeviceId => "device_id",
+            CsvColumn::PrescriberLicense => "prescriber_license",
+            CsvColumn::PrescriberDea => "prescriber_dea",
+        }
+    }
+
+    /// Parse a column name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_lowercase();
+        Self::ALL.into_iter().find(|column| column.as_str() == s)
+    }
+}
+
+/// One column of a billing CSV.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CsvLayoutColumn {
+    pub column: CsvColumn,
+    /// Header text; `None` uses the column name
+    #[serde(default)]
+    pub header: Option<String>,
+}
+
+impl CsvLayoutColumn {
+    /// Header text for this column.
+    pub fn header(&self) -> &str {
+        self.header.as_deref().unwrap_or(self.column.as_str())
+    }
+}
+
+/// Column selection and formatting of billing CSV exports, so each PIMS
+/// importer gets the file it expects.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CsvLayout {
+    /// Columns in output order
+    pub columns: Vec<CsvLayoutColumn>,
+    /// Field separator (e.g. ',', ';', or tab)
+    pub delimiter: char,
+    /// strftime format for timestamps; `None` keeps RFC 3339
+    #[serde(default)]
+    pub date_format: Option<String>,
+}
+
+impl Default for CsvLayout {
+    /// The original billing CSV columns.
+    fn default() -> Self {
+        let columns = [
+            CsvColumn::DraftId,
+            CsvColumn::PatientId,
+            CsvColumn::Sku,
+            CsvColumn::Description,
+            CsvColumn::Quantity,
+            CsvColumn::Unit,
+            CsvColumn::Route,
+            CsvColumn::ReviewedBy,
+            CsvColumn::ReviewedAt,
+            CsvColumn::MerkleHash,
+            CsvColumn::ControlledSchedule,
+            CsvColumn::RouteDescription,
+            CsvColumn::Disposition,
+        ];
+        Self {
+            columns: columns
+                .into_iter()
+                .map(|column| CsvLayoutColumn {
+                    column,
+                    header: None,
+                })
+                .collect(),
+            delimiter: ',',
+            date_format: None,
+        }
+    }
+}
+
+impl CsvLayout {
+    /// Check the layout can produce a readable CSV.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.columns.is_empty() {
+            return Err("CSV layout needs at least one column".into());
+        }
+        if matches!(self.delimiter, '"' | '\n' | '\r') {
+            return Err(format!("Invalid CSV delimiter: {:?}", self.delimiter));
+        }
+        if let Some(format) = &self.date_format {
+            let invalid = chrono::format::StrftimeItems::new(format)
+                .any(|item| matches!(item, chrono::format::Item::Error));
+            if format.is_empty() || invalid {
+                return Err(format!("Invalid date format: {:?}", format));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Clinic sales tax rates by catalog tax code.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaxRates {
+    /// Rate per tax code as a fraction (0.0725 is 7.25%)
+    #[serde(default)]
+    pub rates: BTreeMap<String, f64>,
+}
+
+impl TaxRates {
+    /// Rate for `code`, matched case-insensitively. Codes without a rate
+    /// aren't taxed.
+    pub fn rate(&self, code: &str) -> Option<f64> {
+        let code = code.trim();
+        self.rates
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(code))
+            .map(|(_, rate)| *rate)
+    }
+
+    /// Check every code is named once (ignoring case) and every rate is
+    /// between 0 and 1.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen = std::collections::BTreeSet::new();
+        for (code, rate) in &self.rates {
+            if code.trim().is_empty() {
+                return Err("Tax code can't be empty".into());
+            }
+            if !seen.insert(code.trim().to_lowercase()) {
+                return Err(format!("Duplicate tax code: {}", code));
+            }
+            if !(0.0..=1.0).contains(rate) {
+                return Err(format!("Invalid tax rate for {}: {}", code, rate));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Default cap on tokens generated per extraction.
+pub const DEFAULT_SAMPLING_MAX_TOKENS: u32 = 1024;
+
+/// How the extraction model samples its output. The defaults are
+/// deterministic (greedy, fixed seed), which is what clinical use wants;
+/// evaluation runs may raise the temperature.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SamplingSettings {
+    /// 0.0 samples greedily; higher is more random
+    pub temperature: f64,
+    /// Nucleus sampling: only the most likely tokens covering this much
+    /// probability are kept (1.0 keeps all)
+    pub top_p: f64,
+    /// Divides the score of recently generated tokens (1.0 is off)
+    pub repeat_penalty: f64,
+    /// Most tokens to generate per extraction
+    pub max_tokens: u32,
+    /// Seed for non-greedy sampling
+    pub seed: u32,
+}
+
+impl Default for SamplingSettings {
+    fn default() -> Self {
+        Self {
+            temperature: 0.0,
+            top_p: 1.0,
+            repeat_penalty: 1.0,
+            max_tokens: DEFAULT_SAMPLING_MAX_TOKENS,
+            seed: 0,
+        }
+    }
+}
+
+impl SamplingSettings {
+    /// Check every parameter is in range.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.temperature.is_finite() || self.temperature < 0.0 {
+            return Err(format!("Invalid temperature: {}", self.temperature));
+        }
+        if !(self.top_p > 0.0 && self.top_p <= 1.0) {
+            return Err(format!("Invalid top-p: {}", self.top_p));
+        }
+        if !self.repeat_penalty.is_finite() || self.repeat_penalty <= 0.0 {
+            return Err(format!("Invalid repeat penalty: {}", self.repeat_penalty));
+        }
+        if self.max_tokens == 0 {
+            return Err("max_tokens must be at least 1".into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling_settings_validate() {
+        assert!(SamplingSettings::default().validate().is_ok());
+        let bad: [fn(&mut SamplingSettings); 6] = [
+            |s| s.temperature = -0.1,
+            |s| s.temperature = f64::NAN,
+            |s| s.top_p = 0.0,
+            |s| s.top_p = 1.5,
+            |s| s.repeat_penalty = 0.0,
+            |s| s.max_tokens = 0,
+        ];
+        for change in bad {
+            let mut settings = SamplingSettings::default();
+            change(&mut settings);
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+        // Missing fields keep their defaults
+        let settings: SamplingSettings = serde_json::from_str(r#"{"temperature":0.7}"#).unwrap();
+        assert_eq!(settings.temperature, 0.7);
+        assert_eq!(settings.top_p, 1.0);
+        assert_eq!(settings.max_tokens, DEFAULT_SAMPLING_MAX_TOKENS);
+    }
+
+    #[test]
+    fn test_review_queue_order_round_trip() {
+        for order in [
+            ReviewQueueOrder::Attention,
+            ReviewQueueOrder::NewestFirst,
+            ReviewQueueOrder::OldestFirst,
+        ] {
+            assert_eq!(ReviewQueueOrder::parse(order.as_str()), Some(order));
+        }
+        assert_eq!(
+            ReviewQueueOrder::parse(" Oldest "),
+            Some(ReviewQueueOrder::OldestFirst)
+        );
+        assert_eq!(ReviewQueueOrder::parse("random"), None);
+    }
+
+    #[test]
+    fn test_quickbooks_mapping_lookup() {
+        let mut mapping = QuickBooksMapping::default();
+        mapping.items.insert(
+            "CARP-100".to_string(),
+            QuickBooksItem {
+                item: "Carprofen 100mg tab".to_string(),
+                account: Some("Pharmacy Income".to_string()),
+            },
+        );
+        assert_eq!(mapping.item_name("CARP-100"), "Carprofen 100mg tab");
+        assert_eq!(mapping.income_account_for("CARP-100"), "Pharmacy Income");
+        assert_eq!(mapping.item_name("LRS-1L"), "LRS-1L");
+        assert_eq!(mapping.income_account_for("LRS-1L"), "Sales");
+    }
+
+    #[test]
+    fn test_csv_layout_validate() {
+        for column in CsvColumn::ALL {
+            assert_eq!(CsvColumn::parse(column.as_str()), Some(column));
+        }
+        assert_eq!(CsvColumn::parse(" SKU "), Some(CsvColumn::Sku));
+        assert_eq!(CsvColumn::parse("price"), None);
+
+        let layout = CsvLayout::default();
+        assert!(layout.validate().is_ok());
+        let with = |delimiter: char, date_format: Option<&str>| CsvLayout {
+            delimiter,
+            date_format: date_format.map(str::to_string),
+            ..layout.clone()
+        };
+        assert!(with('\t', Some("%m/%d/%Y")).validate().is_ok());
+        assert!(with('"', None).validate().is_err());
+        assert!(with(',', Some("%Q")).validate().is_err());
+        assert!(CsvLayout {
+            columns: vec![],
+            ..layout
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_tax_rates() {
+        let mut rates = TaxRates::default();
+        rates.rates.insert("RETAIL".to_string(), 0.0725);
+        rates.rates.insert("EXEMPT".to_string(), 0.0);
+        assert!(rates.validate().is_ok());
+        assert_eq!(rates.rate("retail "), Some(0.0725));
+        assert_eq!(rates.rate("EXEMPT"), Some(0.0));
+        assert_eq!(rates.rate("FOOD"), None);
+
+        rates.rates.insert("BAD".to_string(), 7.25);
+        assert!(rates.validate().is_err());
+        rates.rates.remove("BAD");
+        rates.rates.insert("Retail".to_string(), 0.06);
+        assert!(rates.validate().is_err());
+        rates.rates.remove("Retail");
+        rates.rates.insert(" ".to_string(), 0.05);
+        assert!(rates.validate().is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Speaker attribution for drug mentions.
+//!
+//! Exam-room recordings pick up the owner as well as the clinician, and
+//! owners name drugs too ("she's on Apoquel at home"). When the transcript
+//! comes with speaker turns (from diarization), each mention is tagged with
+//! the role of whoever said it, so owner-reported medications are kept apart
+//! from the clinician's orders.
+
+use serde::{Deserialize, Serialize};
+
+use super::resolution::DrugMention;
+
+/// Who said a mention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeakerRole {
+    /// Veterinarian or technician; their mentions are orders
+    Clinician,
+    /// Client; their mentions are medications reported from home
+    Owner,
+}
+
+impl SpeakerRole {
+    /// Parse "clinician" or "owner" (case-insensitive), or a common synonym.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "clinician" | "vet" | "veterinarian" | "doctor" | "tech" | "technician" => {
+                Some(SpeakerRole::Clinician)
+            }
+            "owner" | "client" => Some(SpeakerRole::Owner),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpeakerRole::Clinician => "clinician",
+            SpeakerRole::Owner => "owner",
+        }
+    }
+}
+
+/// A stretch of the transcript spoken by one person.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeakerTurn {
+    /// Diarization label ("SPEAKER_00")
+    pub speaker: String,
+    /// Role of the speaker, if known
+    pub role: Option<SpeakerRole>,
+    /// Start position in transcript
+    pub start_offset: usize,
+    /// End position in transcript
+    pub end_offset: usize,
+}
+
+impl SpeakerTurn {
+    /// Whether the turn covers transcript position `offset`.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start_offset <= offset && offset < self.end_offset
+    }
+}
+
+/// Tag each mention with the role of the turn it starts in. Mentions that
+/// already have a speaker, or fall in no turn with a known role, are left
+/// alone.
+pub fn assign_speakers(mentions: &mut [DrugMention], turns: &[SpeakerTurn]) {
+    for mention in mentions.iter_mut().filter(|m| m.speaker.is_none()) {
+        mention.speaker = turns
+            .iter()
+            .find(|turn| turn.contains(mention.start_offset))
+            .and_then(|turn| turn.role);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mention(name: &str, start: usize) -> DrugMention {
+        DrugMention {
+            raw_text: name.into(),
+            drug_name: name.into(),
+            dose: None,
+            unit: None,
+            route: None,
+            species: None,
+            start_offset: start,
+            end_offset: start + name.len(),
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_role() {
+        assert_eq!(SpeakerRole::parse("Vet"), Some(SpeakerRole::Clinician));
+        assert_eq!(SpeakerRole::parse(" client "), Some(SpeakerRole::Owner));
+        assert_eq!(SpeakerRole::parse("owner").unwrap().as_str(), "owner");
+        assert_eq!(SpeakerRole::parse("SPEAKER_01"), None);
+    }
+
+    #[test]
+    fn test_assign_speakers() {
+        // "She's on apoquel at home. | Let's start cytopoint today."
+        let turns = [
+            SpeakerTurn {
+                speaker: "SPEAKER_01".into(),
+                role: Some(SpeakerRole::Owner),
+                start_offset: 0,
+                end_offset: 26,
+            },
+            SpeakerTurn {
+                speaker: "SPEAKER_00".into(),
+                role: Some(SpeakerRole::Clinician),
+                start_offset: 26,
+                end_offset: 56,
+            },
+            SpeakerTurn {
+                speaker: "SPEAKER_02".into(),
+                role: None,
+                start_offset: 56,
+                end_offset: 80,
+            },
+        ];
+        let mut mentions = vec![
+            mention("apoquel", 9),
+            mention("cytopoint", 39),
+            mention("carprofen", 60),
+            mention("metacam", 90),
+        ];
+        mentions[3].speaker = Some(SpeakerRole::Clinician);
+        assign_speakers(&mut mentions, &turns);
+
+        let roles: Vec<Option<SpeakerRole>> = mentions.iter().map(|m| m.speaker).collect();
+        assert_eq!(
+            roles,
+            [
+                Some(SpeakerRole::Owner),
+                Some(SpeakerRole::Clinician),
+                None,
+                Some(SpeakerRole::Clinician),
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Stock levels of catalog items.
+//!
+//! Quantity on hand is counted in the item's dispensing units (tablets,
+//! capsules, mL; one per administration otherwise). It is only tracked for
+//! items that have been received or counted; committed encounters draw
+//! tracked items down, and every change is kept as a [`StockAdjustment`].
+
+use serde::{Deserialize, Serialize};
+
+/// Why an item's quantity on hand changed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StockAdjustmentReason {
+    /// Stock received from a supplier
+    Received,
+    /// Physical count; the delta corrects the recorded quantity
+    Count,
+    /// Broken, spilled, or otherwise wasted
+    Waste,
+    /// Removed past its expiration date
+    Expired,
+    /// Returned to the supplier
+    Returned,
+    /// Given or dispensed on a committed encounter
+    Encounter,
+}
+
+impl StockAdjustmentReason {
+    /// Database/FFI name ("received", "count", "waste", ...).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StockAdjustmentReason::Received => "received",
+            StockAdjustmentReason::Count => "count",
+            StockAdjustmentReason::Waste => "waste",
+            StockAdjustmentReason::Expired => "expired",
+            StockAdjustmentReason::Returned => "returned",
+            StockAdjustmentReason::Encounter => "encounter",
+        }
+    }
+
+    /// Parse a reason name (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "received" => Some(StockAdjustmentReason::Received),
+            "count" => Some(StockAdjustmentReason::Count),
+            "waste" => Some(StockAdjustmentReason::Waste),
+            "expired" => Some(StockAdjustmentReason::Expired),
+            "returned" => Some(StockAdjustmentReason::Returned),
+            "encounter" => Some(StockAdjustmentReason::Encounter),
+            _ => None,
+        }
+    }
+}
+
+/// An item's stock level.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StockLevel {
+    pub sku: String,
+    pub name: String,
+    /// Quantity on hand, in dispensing units (may go negative when usage
+    /// outruns recorded receipts)
+    pub quantity_on_hand: f64,
+    /// Quantity at or below which the item should be reordered
+    pub reorder_point: Option<f64>,
+}
+
+impl StockLevel {
+    /// Whether the item is at or below its reorder point.
+    pub fn is_low(&self) -> bool {
+        self.reorder_point
+            .is_some_and(|point| self.quantity_on_hand <= point)
+    }
+}
+
+/// A recorded change to an item's quantity on hand.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StockAdjustment {
+    pub id: i64,
+    pub sku: String,
+    /// Change in quantity (negative for usage and losses)
+    pub delta: f64,
+    /// Quantity on hand after the change
+    pub quantity_after: f64,
+    pub reason: StockAdjustmentReason,
+    pub note: String,
+    /// Leaf hash of the encounter, for encounter usage
+    pub reference: Option<String>,
+    pub created_at: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reason_names_and_low_stock() {
+        for reason in [
+            StockAdjustmentReason::Received,
+            StockAdjustmentReason::Count,
+            StockAdjustmentReason::Waste,
+            StockAdjustmentReason::Expired,
+            StockAdjustmentReason::Returned,
+            StockAdjustmentReason::Encounter,
+        ] {
+            assert_eq!(StockAdjustmentReason::parse(reason.as_str()), Some(reason));
+        }
+        let received = StockAdjustmentReason::parse(" Received ");
+        assert_eq!(received, Some(StockAdjustmentReason::Received));
+        assert_eq!(StockAdjustmentReason::parse("stolen"), None);
+
+        let mut level = StockLevel {
+            sku: "CARP-100".into(),
+            name: "Carprofen 100mg".into(),
+            quantity_on_hand: 30.0,
+            reorder_point: None,
+        };
+        assert!(!level.is_low());
+        level.reorder_point = Some(30.0);
+        assert!(level.is_low());
+        level.quantity_on_hand = 31.0;
+        assert!(!level.is_low());
+    }
+}
@@ -0,0 +1,92 @@
+//! Tapering schedules.
+//!
+//! Steroid courses are often dictated as a taper: "prednisone 20mg BID for 5
+//! days, then 10mg SID for 5 days". Each phase has its own dose and frequency,
+//! so the amount dispensed is the sum over the phases, not the first dose.
+
+use serde::{Deserialize, Serialize};
+
+/// One phase of a tapering schedule.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DosePhase {
+    /// Dose per administration, in `unit`
+    pub dose: f64,
+    /// Canonical amount unit ("mg")
+    pub unit: String,
+    /// Frequency as dictated ("BID")
+    pub frequency: String,
+    /// Administrations per day
+    pub doses_per_day: f64,
+    /// Phase length in days
+    pub days: f64,
+}
+
+impl DosePhase {
+    /// Amount given over the phase, in `unit`.
+    pub fn total_amount(&self) -> f64 {
+        self.dose * self.doses_per_day * self.days
+    }
+}
+
+/// Ordered dosing phases of a taper.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaperSchedule {
+    pub phases: Vec<DosePhase>,
+}
+
+impl TaperSchedule {
+    /// Amount unit shared by every phase; `None` if the phases differ.
+    pub fn unit(&self) -> Option<&str> {
+        let unit = self.phases.first()?.unit.as_str();
+        self.phases
+            .iter()
+            .all(|phase| phase.unit == unit)
+            .then_some(unit)
+    }
+
+    /// Total amount over all phases, in [`unit`](Self::unit).
+    pub fn total_amount(&self) -> Option<f64> {
+        self.unit()?;
+        Some(self.phases.iter().map(DosePhase::total_amount).sum())
+    }
+
+    /// Length of the whole course in days.
+    pub fn total_days(&self) -> f64 {
+        self.phases.iter().map(|phase| phase.days).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phase(dose: f64, unit: &str, doses_per_day: f64, days: f64) -> DosePhase {
+        DosePhase {
+            dose,
+            unit: unit.into(),
+            frequency: String::new(),
+            doses_per_day,
+            days,
+        }
+    }
+
+    #[test]
+    fn test_total_amount() {
+        // 20mg BID x5d, 10mg SID x5d, 5mg EOD x6d
+        let taper = TaperSchedule {
+            phases: vec![
+                phase(20.0, "mg", 2.0, 5.0),
+                phase(10.0, "mg", 1.0, 5.0),
+                phase(5.0, "mg", 0.5, 6.0),
+            ],
+        };
+        assert_eq!(taper.unit(), Some("mg"));
+        assert_eq!(taper.total_amount(), Some(265.0));
+        assert_eq!(taper.total_days(), 16.0);
+
+        let mixed = TaperSchedule {
+            phases: vec![phase(20.0, "mg", 2.0, 5.0), phase(1.0, "tablets", 1.0, 5.0)],
+        };
+        assert_eq!(mixed.total_amount(), None);
+    }
+}
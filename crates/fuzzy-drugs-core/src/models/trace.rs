@@ -0,0 +1,286 @@
+//! Resolution traces for the "why this match" screen.
+//!
+//! A trace records how the resolver got from a spoken mention to its ranked
+//! candidates: which aliases fired, what was searched, and how every
+//! candidate scored on each factor.
+
+use serde::{Deserialize, Serialize};
+
+use super::resolution::ScoreBreakdown;
+use super::scoring::ScoringConfig;
+
+/// One of the weighted scoring factors.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ScoreFactor {
+    Name,
+    Species,
+    Route,
+    Dose,
+}
+
+impl ScoreFactor {
+    /// All factors, in weight order.
+    pub const ALL: [ScoreFactor; 4] = [
+        ScoreFactor::Name,
+        ScoreFactor::Species,
+        ScoreFactor::Route,
+        ScoreFactor::Dose,
+    ];
+
+    /// Default weight of this factor in the confidence score (see
+    /// [`ScoringConfig`] for a clinic's configured weights).
+    pub fn weight(&self) -> f64 {
+        match self {
+            ScoreFactor::Name => 0.40,
+            ScoreFactor::Species => 0.25,
+            ScoreFactor::Route => 0.20,
+            ScoreFactor::Dose => 0.15,
+        }
+    }
+
+    /// Lowercase name ("name", "species", "route", "dose").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScoreFactor::Name => "name",
+            ScoreFactor::Species => "species",
+            ScoreFactor::Route => "route",
+            ScoreFactor::Dose => "dose",
+        }
+    }
+}
+
+impl std::fmt::Display for ScoreFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ScoreBreakdown {
+    /// Raw score (0.0 - 1.0) for a factor.
+    pub fn score(&self, factor: ScoreFactor) -> f64 {
+        match factor {
+            ScoreFactor::Name => self.name_score,
+            ScoreFactor::Species => self.species_score,
+            ScoreFactor::Route => self.route_score,
+            ScoreFactor::Dose => self.dose_score,
+        }
+    }
+
+    /// Weighted contribution of a factor to the confidence score, with the
+    /// default weights.
+    pub fn contribution(&self, factor: ScoreFactor) -> f64 {
+        ScoringConfig::default().contribution(self, factor)
+    }
+
+    /// Factor contributing most to the confidence score, with the default
+    /// weights.
+    pub fn dominant_factor(&self) -> ScoreFactor {
+        ScoringConfig::default().dominant_factor(self)
+    }
+}
+
+/// A normalizer alias that rewrote the spoken drug name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AliasHit {
+    /// Name as spoken
+    pub spoken: String,
+    /// Canonical name it expanded to
+    pub canonical: String,
+}
+
+/// How one catalog item scored.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CandidateTrace {
+    /// Catalog SKU
+    pub sku: String,
+    /// Display name
+    pub name: String,
+    /// Weighted confidence
+    pub confidence: f64,
+    /// Per-factor scores
+    pub score_breakdown: ScoreBreakdown,
+    /// Factor contributing most to the confidence
+    pub dominant_factor: ScoreFactor,
+    /// Catalog alias that matched the search name exactly, if any
+    pub matched_alias: Option<String>,
+    /// Dropped for falling below the minimum confidence
+    pub below_threshold: bool,
+}
+
+/// Catalog search performed for a mention.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SearchTrace {
+    /// Name searched for
+    pub search_term: String,
+    /// FTS5 MATCH expression sent to SQLite
+    pub fts_query: String,
+    /// Whether the normalized name found nothing and the spoken name was used
+    pub used_fallback: bool,
+    /// Every retrieved candidate, highest confidence first
+    pub candidates: Vec<CandidateTrace>,
+}
+
+/// Full explanation of one resolution.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResolutionTrace {
+    /// Drug name as spoken
+    pub spoken_name: String,
+    /// Drug name after normalization
+    pub normalized_name: String,
+    /// Aliases applied during normalization
+    pub aliases_fired: Vec<AliasHit>,
+    /// Catalog search and candidate scores
+    pub search: SearchTrace,
+    /// Factor that most separated the top candidate from the runner-up
+    /// (None with a single candidate or when the top leads on no factor)
+    pub deciding_factor: Option<ScoreFactor>,
+}
+
+impl ResolutionTrace {
+    /// Build a trace, working out the deciding factor from the candidates
+    /// under the weights they were scored with.
+    pub fn new(
+        spoken_name: String,
+        normalized_name: String,
+        aliases_fired: Vec<AliasHit>,
+        search: SearchTrace,
+        config: &ScoringConfig,
+    ) -> Self {
+        let deciding_factor = match search.candidates.as_slice() {
+            [top, runner_up, ..] => {
+                deciding_factor(config, &top.score_breakdown, &runner_up.score_breakdown)
+            }
+            _ => None,
+        };
+        Self {
+            spoken_name,
+            normalized_name,
+            aliases_fired,
+            search,
+            deciding_factor,
+        }
+    }
+
+    /// Plain-text rendering for display.
+    pub fn render(&self) -> String {
+        let mut lines = Vec::new();
+
+        lines.push(format!("Heard \"{}\"", self.spoken_name));
+        for alias in &self.aliases_fired {
+            lines.push(format!("  alias: {} → {}", alias.spoken, alias.canonical));
+        }
+        if self.normalized_name != self.spoken_name.to_lowercase() {
+            lines.push(format!("  normalized to \"{}\"", self.normalized_name));
+        }
+
+        let fallback = if self.search.used_fallback {
+            " (fallback to spoken name)"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "Searched \"{}\" as `{}`{}",
+            self.search.search_term, self.search.fts_query, fallback
+        ));
+
+        for (rank, candidate) in self.search.candidates.iter().enumerate() {
+            let b = &candidate.score_breakdown;
+            let mut line = format!(
+                "{}. {} ({}) {:.0}% — name {:.2}, species {:.2}, route {:.2}, dose {:.2}; mostly {}",
+                rank + 1,
+                candidate.name,
+                candidate.sku,
+                candidate.confidence * 100.0,
+                b.name_score,
+                b.species_score,
+                b.route_score,
+                b.dose_score,
+                candidate.dominant_factor,
+            );
+            if let Some(alias) = &candidate.matched_alias {
+                line.push_str(&format!("; alias \"{}\"", alias));
+            }
+            if candidate.below_threshold {
+                line.push_str(" [below threshold]");
+            }
+            lines.push(line);
+        }
+
+        if let Some(factor) = self.deciding_factor {
+            lines.push(format!("Top match won on {}", factor));
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Factor with the largest weighted lead of `top` over `runner_up`.
+fn deciding_factor(
+    config: &ScoringConfig,
+    top: &ScoreBreakdown,
+    runner_up: &ScoreBreakdown,
+) -> Option<ScoreFactor> {
+    ScoreFactor::ALL
+        .into_iter()
+        .map(|f| {
+            let lead = config.contribution(top, f) - config.contribution(runner_up, f);
+            (f, lead)
+        })
+        .filter(|(_, lead)| *lead > 1e-9)
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(f, _)| f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(sku: &str, breakdown: ScoreBreakdown) -> CandidateTrace {
+        CandidateTrace {
+            sku: sku.into(),
+            name: sku.into(),
+            confidence: breakdown.weighted_score(),
+            dominant_factor: breakdown.dominant_factor(),
+            score_breakdown: breakdown,
+            matched_alias: None,
+            below_threshold: false,
+        }
+    }
+
+    #[test]
+    fn test_deciding_factor() {
+        let top = ScoreBreakdown {
+            name_score: 1.0,
+            species_score: 1.0,
+            route_score: 1.0,
+            dose_score: 0.6,
+        };
+        let runner_up = ScoreBreakdown {
+            route_score: 0.2,
+            ..top.clone()
+        };
+        assert_eq!(top.dominant_factor(), ScoreFactor::Name);
+
+        let trace = ResolutionTrace::new(
+            "Rimadyl".into(),
+            "carprofen".into(),
+            vec![AliasHit {
+                spoken: "rimadyl".into(),
+                canonical: "carprofen".into(),
+            }],
+            SearchTrace {
+                search_term: "carprofen".into(),
+                fts_query: "carprofen*".into(),
+                used_fallback: false,
+                candidates: vec![candidate("CARP-100", top), candidate("CARP-INJ", runner_up)],
+            },
+            &ScoringConfig::default(),
+        );
+        assert_eq!(trace.deciding_factor, Some(ScoreFactor::Route));
+
+        let rendered = trace.render();
+        assert!(rendered.contains("alias: rimadyl → carprofen"));
+        assert!(rendered.contains("`carprofen*`"));
+        assert!(rendered.contains("Top match won on route"));
+    }
+}
@@ -0,0 +1,124 @@
+//! Clinic staff who review encounters, and their prescribing credentials.
+//!
+//! Compliance and DEA exports need the veterinarian's state license and,
+//! for controlled drugs, DEA registration. They are kept per [`User`] and
+//! copied onto each [`ReviewedEncounter`](super::ReviewedEncounter) as a
+//! [`Prescriber`] when it is committed, so the Merkle leaf records the
+//! credentials in force at the time.
+
+use serde::{Deserialize, Serialize};
+
+/// A clinic user who can review and sign encounters.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct User {
+    /// Identifier the host passes as `reviewed_by`
+    pub user_id: String,
+    /// Display name ("Dr. Jane Smith")
+    pub name: String,
+    /// State veterinary license number
+    pub license_number: Option<String>,
+    /// Licensing state or province ("CA")
+    pub license_state: Option<String>,
+    /// DEA registration number, for controlled substances
+    pub dea_number: Option<String>,
+    /// Roles held, for escalation approvals ("dvm-lead", "pharmacist")
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl User {
+    pub fn new(user_id: String, name: String) -> Self {
+        Self {
+            user_id,
+            name,
+            license_number: None,
+            license_state: None,
+            dea_number: None,
+            roles: Vec::new(),
+        }
+    }
+
+    /// Whether the user holds `role` (ignoring case).
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles
+            .iter()
+            .any(|r| r.eq_ignore_ascii_case(role.trim()))
+    }
+
+    /// Check the IDs are present and the DEA number is well formed.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.user_id.trim().is_empty() {
+            return Err("User ID can't be empty".into());
+        }
+        if self.name.trim().is_empty() {
+            return Err("User name can't be empty".into());
+        }
+        if let Some(dea) = &self.dea_number {
+            if !is_valid_dea_number(dea) {
+                return Err(format!("Invalid DEA number: {}", dea));
+            }
+        }
+        Ok(())
+    }
+
+    /// The credentials stamped on encounters this user reviews.
+    pub fn prescriber(&self) -> Prescriber {
+        Prescriber {
+            user_id: self.user_id.clone(),
+            name: self.name.clone(),
+            license_number: self.license_number.clone(),
+            license_state: self.license_state.clone(),
+            dea_number: self.dea_number.clone(),
+        }
+    }
+}
+
+/// Prescriber credentials as recorded on a committed encounter.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Prescriber {
+    pub user_id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_state: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dea_number: Option<String>,
+}
+
+/// Whether `dea` is a DEA registration number: two letters and seven
+/// digits, the last a check digit (the sum of the 1st, 3rd, and 5th digits
+/// plus twice the sum of the 2nd, 4th, and 6th, mod 10).
+pub fn is_valid_dea_number(dea: &str) -> bool {
+    let dea = dea.trim().as_bytes();
+    if dea.len() != 9 || !dea[..2].iter().all(u8::is_ascii_alphabetic) {
+        return false;
+    }
+    if !dea[2..].iter().all(u8::is_ascii_digit) {
+        return false;
+    }
+    let digit = |i: usize| u32::from(dea[2 + i] - b'0');
+    let sum = digit(0) + digit(2) + digit(4) + 2 * (digit(1) + digit(3) + digit(5));
+    sum % 10 == digit(6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dea_number_check_digit() {
+        assert!(is_valid_dea_number("AB1234563"));
+        assert!(is_valid_dea_number(" ab1234563 "));
+        assert!(!is_valid_dea_number("AB1234564"));
+        assert!(!is_valid_dea_number("A11234563"));
+        assert!(!is_valid_dea_number("AB123456"));
+
+        let mut user = User::new("jsmith".into(), "Dr. Smith".into());
+        assert!(user.validate().is_ok());
+        user.dea_number = Some("AB1234564".into());
+        assert!(user.validate().is_err());
+        user.dea_number = Some("AB1234563".into());
+        assert_eq!(user.prescriber().dea_number.as_deref(), Some("AB1234563"));
+    }
+}
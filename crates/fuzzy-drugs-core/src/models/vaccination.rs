@@ -0,0 +1,140 @@
+//! Vaccination records.
+//!
+//! Vaccines are dictated and billed like any other service, but the clinic
+//! also has to know when each patient's next dose is due. Committing an
+//! encounter records a [`Vaccination`] for every line item whose service is a
+//! vaccine, with the next-due date from the service's booster interval.
+//! Doses given elsewhere are entered by hand. A patient is due for an antigen
+//! when their latest dose of it has a next-due date on or before the day
+//! asked about.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::encounter::EncounterLineItem;
+use super::medication::parse_date;
+use super::service::ServiceItem;
+
+/// A vaccine dose given to a patient.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Vaccination {
+    /// Row ID (0 until stored)
+    pub id: i64,
+    /// Patient local ID
+    pub patient_id: String,
+    /// Antigen ("rabies"), from the vaccine's service
+    pub antigen: String,
+    /// Service code of the vaccine given
+    pub code: String,
+    /// Vaccine name as billed
+    pub name: String,
+    /// Date given (YYYY-MM-DD)
+    pub administered_on: String,
+    pub lot_number: Option<String>,
+    /// Expiration date of the lot (YYYY-MM-DD)
+    pub expiration_date: Option<String>,
+    /// When the next dose is due (YYYY-MM-DD); None if no booster is needed
+    pub next_due_date: Option<String>,
+    /// Veterinarian who gave or signed off on the dose
+    pub administered_by: Option<String>,
+    /// Draft of the encounter it was committed on; None if entered by hand
+    pub draft_id: Option<String>,
+}
+
+impl Vaccination {
+    /// A dose of the vaccine `service` given on `date`, due again after the
+    /// service's booster interval.
+    pub fn new(patient_id: String, service: &ServiceItem, date: NaiveDate) -> Self {
+        let next_due = service
+            .booster_interval_days
+            .map(|days| date + chrono::Duration::days(i64::from(days)));
+        Self {
+            id: 0,
+            patient_id,
+            antigen: service.antigen().to_string(),
+            code: service.code.clone(),
+            name: service.name.clone(),
+            administered_on: date.format("%Y-%m-%d").to_string(),
+            lot_number: None,
+            expiration_date: None,
+            next_due_date: next_due.map(|d| d.format("%Y-%m-%d").to_string()),
+            administered_by: None,
+            draft_id: None,
+        }
+    }
+
+    /// The dose a committed vaccine line item records.
+    pub fn from_line_item(
+        patient_id: &str,
+        draft_id: &str,
+        service: &ServiceItem,
+        item: &EncounterLineItem,
+        date: NaiveDate,
+    ) -> Self {
+        Self {
+            lot_number: item.lot_number.clone(),
+            expiration_date: item.expiration_date.clone(),
+            draft_id: Some(draft_id.into()),
+            ..Self::new(patient_id.into(), service, date)
+        }
+    }
+
+    /// Check the antigen is present and the dates are valid and in order.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.antigen.trim().is_empty() {
+            return Err("Vaccination antigen can't be empty".into());
+        }
+        let given = parse_date(&self.administered_on)?;
+        if let Some(due) = &self.next_due_date {
+            if parse_date(due)? < given {
+                return Err("Next dose can't be due before this one was given".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Days from `today` until the next dose is due (negative once overdue);
+    /// None if no booster is due or the date is unreadable.
+    pub fn days_until_due(&self, today: NaiveDate) -> Option<i64> {
+        let due = parse_date(self.next_due_date.as_deref()?).ok()?;
+        Some((due - today).num_days())
+    }
+
+    /// Whether the next dose was due before `today`.
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        self.days_until_due(today).is_some_and(|days| days < 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ServiceKind;
+
+    #[test]
+    fn test_next_due_from_booster_interval() {
+        let mut rabies = ServiceItem::new(
+            "VAC-RAB3".into(),
+            "Rabies 3-Year".into(),
+            ServiceKind::Vaccine,
+        );
+        rabies.antigen = Some("rabies".into());
+        rabies.booster_interval_days = Some(1095);
+        let day = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+
+        let dose = Vaccination::new("p1".into(), &rabies, day("2026-03-01"));
+        assert_eq!(dose.antigen, "rabies");
+        assert_eq!(dose.next_due_date.as_deref(), Some("2029-02-28"));
+        assert!(dose.validate().is_ok());
+        assert_eq!(dose.days_until_due(day("2029-02-27")), Some(1));
+        assert!(!dose.is_overdue(day("2029-02-28")));
+        assert!(dose.is_overdue(day("2029-03-01")));
+
+        // Without an interval nothing comes due, and the name is the antigen
+        rabies.antigen = None;
+        rabies.booster_interval_days = None;
+        let dose = Vaccination::new("p1".into(), &rabies, day("2026-03-01"));
+        assert_eq!(dose.antigen, "Rabies 3-Year");
+        assert!(!dose.is_overdue(day("2040-01-01")));
+    }
+}
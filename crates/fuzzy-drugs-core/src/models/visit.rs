@@ -0,0 +1,67 @@
+//! Visits (appointments).
+//!
+//! One trip to the clinic can produce several drafts: the exam dictated in
+//! the room, a procedure later in the day, a take-home prescription at
+//! discharge. A [`Visit`] groups them. Drafts are linked to a visit, the
+//! link is copied onto each encounter when it is committed, and billing can
+//! then be rolled up per visit.
+
+use serde::{Deserialize, Serialize};
+
+use super::medication::parse_date;
+
+/// A patient's visit to the clinic.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Visit {
+    /// Unique visit ID
+    pub visit_id: String,
+    /// Patient local ID
+    pub patient_id: String,
+    /// Day of the visit (YYYY-MM-DD)
+    pub visit_date: String,
+    /// Reason for the visit ("annual wellness", "limping")
+    pub reason: Option<String>,
+    /// Attending veterinarian
+    pub attending_vet: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Visit {
+    pub fn new(patient_id: String, visit_date: String) -> Self {
+        let now = chrono::Utc::now().to_rfc3339();
+        Self {
+            visit_id: uuid::Uuid::new_v4().to_string(),
+            patient_id,
+            visit_date,
+            reason: None,
+            attending_vet: None,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    /// Check the patient is set and the date is valid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.patient_id.trim().is_empty() {
+            return Err("Visit patient can't be empty".into());
+        }
+        parse_date(&self.visit_date).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visit_validation() {
+        let mut visit = Visit::new("p1".into(), "2026-03-01".into());
+        assert!(visit.validate().is_ok());
+        visit.visit_date = "March 1".into();
+        assert!(visit.validate().is_err());
+        visit.visit_date = "2026-03-01".into();
+        visit.patient_id = " ".into();
+        assert!(visit.validate().is_err());
+    }
+}
@@ -0,0 +1,537 @@
+//! Typed species, route, and dose unit vocabularies.
+//!
+//! Species, routes, and units arrive as free text ("SQ", "SC", "subq";
+//! "dog", "Canine"). Comparing them as strings causes case and synonym bugs,
+//! so consumers parse them into these enums and compare those. Species and
+//! routes serialize as their canonical string ("canine", "SQ") and parse any
+//! known synonym; unknown values are kept in `Other` rather than rejected.
+//! A [`Unit`] serializes as it was spelled ("cc" stays "cc"), since line items
+//! are hashed into the Merkle tree, and compares by its canonical form.
+//!
+//! Units also know what they measure ([`UnitDimension`]), so "mcg" and "mg"
+//! convert into each other while "mg" and "mL" never compare. Dose fields
+//! ([`Unit`], [`DoseUnit`]) are typed; species and route fields stay
+//! `String` for storage and sync compatibility, so use the `parse` shims (or
+//! `From<&str>`) at those comparison sites.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Patient or product species.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Species {
+    Canine,
+    Feline,
+    Equine,
+    Bovine,
+    Ovine,
+    Caprine,
+    Porcine,
+    /// Unrecognized species, lowercased
+    Other(String),
+}
+
+impl Species {
+    /// Parse a species name or synonym ("dog", "K9", "feline", "gato").
+    pub fn parse(s: &str) -> Self {
+        let lower = s.trim().to_lowercase();
+        match lower.as_str() {
+            "canine" | "dog" | "dogs" | "k9" | "canis" | "perro" | "canino" => Species::Canine,
+            "feline" | "cat" | "cats" | "felis" | "gato" | "felino" => Species::Feline,
+            "equine" | "horse" | "horses" | "equus" | "caballo" | "equino" => Species::Equine,
+            "bovine" | "cow" | "cows" | "cattle" | "bos" | "vaca" | "bovino" => Species::Bovine,
+            "ovine" | "sheep" | "ovis" | "oveja" | "ovino" => Species::Ovine,
+            "caprine" | "goat" | "goats" | "capra" | "cabra" | "caprino" => Species::Caprine,
+            "porcine" | "pig" | "pigs" | "swine" | "sus" | "cerdo" | "porcino" => Species::Porcine,
+            _ => Species::Other(lower),
+        }
+    }
+
+    /// Whether the species is raised for meat or milk, so treated animals
+    /// are subject to withdrawal times.
+    pub fn is_food_animal(&self) -> bool {
+        matches!(
+            self,
+            Species::Equine
+                | Species::Bovine
+                | Species::Ovine
+                | Species::Caprine
+                | Species::Porcine
+        )
+    }
+
+    /// Canonical name ("canine").
+    pub fn as_str(&self) -> &str {
+        match self {
+            Species::Canine => "canine",
+            Species::Feline => "feline",
+            Species::Equine => "equine",
+            Species::Bovine => "bovine",
+            Species::Ovine => "ovine",
+            Species::Caprine => "caprine",
+            Species::Porcine => "porcine",
+            Species::Other(s) => s,
+        }
+    }
+}
+
+/// Route of administration.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Route {
+    /// Oral (PO)
+    Oral,
+    /// Intravenous (IV)
+    Intravenous,
+    /// Intramuscular (IM)
+    Intramuscular,
+    /// Subcutaneous (SQ)
+    Subcutaneous,
+    /// Topical (TOP)
+    Topical,
+    /// Ophthalmic (OPH)
+    Ophthalmic,
+    /// Otic (OT)
+    Otic,
+    /// Rectal (PR)
+    Rectal,
+    /// Intranasal (IN)
+    Intranasal,
+    /// Transdermal (TD)
+    Transdermal,
+    /// Unrecognized route, uppercased
+    Other(String),
+}
+
+impl Route {
+    /// Parse a route code or phrase ("SQ", "SC", "subq", "orally", "i.v.").
+    pub fn parse(s: &str) -> Self {
+        let lower = s.trim().to_lowercase();
+        match lower.as_str() {
+            "po" | "oral" | "orally" | "by mouth" | "per os" => Route::Oral,
+            "iv" | "i.v." | "intravenous" | "intravenously" => Route::Intravenous,
+            "im" | "i.m." | "intramuscular" | "intramuscularly" => Route::Intramuscular,
+            "sq" | "sc" | "subq" | "sub-q" | "s.c." | "subcutaneous" | "subcutaneously" => {
+                Route::Subcutaneous
+            }
+            "top" | "topical" | "topically" => Route::Topical,
+            "oph" | "ophthalmic" | "ophthalmically" => Route::Ophthalmic,
+            "ot" | "otic" => Route::Otic,
+            "pr" | "rectal" | "rectally" | "per rectum" => Route::Rectal,
+            "in" | "intranasal" | "intranasally" => Route::Intranasal,
+            "td" | "transdermal" | "transdermally" => Route::Transdermal,
+            _ => Route::Other(lower.to_uppercase()),
+        }
+    }
+
+    /// Canonical code ("PO", "SQ"), as produced by the normalizer.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Route::Oral => "PO",
+            Route::Intravenous => "IV",
+            Route::Intramuscular => "IM",
+            Route::Subcutaneous => "SQ",
+            Route::Topical => "TOP",
+            Route::Ophthalmic => "OPH",
+            Route::Otic => "OT",
+            Route::Rectal => "PR",
+            Route::Intranasal => "IN",
+            Route::Transdermal => "TD",
+            Route::Other(s) => s,
+        }
+    }
+}
+
+/// What a unit measures. Amounts convert only within a dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnitDimension {
+    /// mcg, mg, g
+    Mass,
+    /// mL, L
+    Volume,
+    /// Tablets, capsules
+    Count,
+    /// Units and IU of biological activity
+    Activity,
+    /// Mass per volume (mcg/mL, mg/mL)
+    Concentration,
+    /// Unrecognized unit; matches only itself
+    Unknown,
+}
+
+/// Unit of a dose amount.
+///
+/// Synonyms map to one unit ("cc" → mL); [`DoseUnit::convert`] scales
+/// amounts between units of the same dimension. Not serialized on its own;
+/// stored units are a [`Unit`], which keeps the spelling.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DoseUnit {
+    Milligrams,
+    Micrograms,
+    Grams,
+    Milliliters,
+    Liters,
+    Units,
+    InternationalUnits,
+    Tablets,
+    Capsules,
+    MilligramsPerMilliliter,
+    MicrogramsPerMilliliter,
+    /// Unrecognized unit, lowercased
+    Other(String),
+}
+
+impl DoseUnit {
+    /// Parse a unit or synonym ("mg", "cc", "mils", "tabs", "IU", "mg/mL").
+    pub fn parse(s: &str) -> Self {
+        let mut lower = s.trim().to_lowercase();
+        if lower.contains('/') {
+            lower.retain(|c| !c.is_whitespace());
+        }
+        match lower.as_str() {
+            "mg" | "mgs" | "milligram" | "milligrams" => DoseUnit::Milligrams,
+            "mcg" | "µg" | "ug" | "microgram" | "micrograms" => DoseUnit::Micrograms,
+            "g" | "gram" | "grams" => DoseUnit::Grams,
+            "ml" | "cc" | "mil" | "mils" | "milliliter" | "milliliters" => DoseUnit::Milliliters,
+            "l" | "liter" | "liters" => DoseUnit::Liters,
+            "unit" | "units" | "u" => DoseUnit::Units,
+            "iu" => DoseUnit::InternationalUnits,
+            "tab" | "tabs" | "tablet" | "tablets" => DoseUnit::Tablets,
+            "cap" | "caps" | "capsule" | "capsules" => DoseUnit::Capsules,
+            "mg/ml" | "mg/cc" => DoseUnit::MilligramsPerMilliliter,
+            "mcg/ml" | "µg/ml" | "ug/ml" | "mcg/cc" => DoseUnit::MicrogramsPerMilliliter,
+            _ => DoseUnit::Other(lower),
+        }
+    }
+
+    /// What the unit measures.
+    pub fn dimension(&self) -> UnitDimension {
+        match self {
+            DoseUnit::Milligrams | DoseUnit::Micrograms | DoseUnit::Grams => UnitDimension::Mass,
+            DoseUnit::Milliliters | DoseUnit::Liters => UnitDimension::Volume,
+            DoseUnit::Tablets | DoseUnit::Capsules => UnitDimension::Count,
+            DoseUnit::Units | DoseUnit::InternationalUnits => UnitDimension::Activity,
+            DoseUnit::MilligramsPerMilliliter | DoseUnit::MicrogramsPerMilliliter => {
+                UnitDimension::Concentration
+            }
+            DoseUnit::Other(_) => UnitDimension::Unknown,
+        }
+    }
+
+    /// Size relative to the dimension's base unit (mg, mL, mg/mL); `None`
+    /// for units that don't scale (tablets, IU).
+    fn scale(&self) -> Option<f64> {
+        match self {
+            DoseUnit::Micrograms | DoseUnit::MicrogramsPerMilliliter => Some(0.001),
+            DoseUnit::Milligrams | DoseUnit::Milliliters | DoseUnit::MilligramsPerMilliliter => {
+                Some(1.0)
+            }
+            DoseUnit::Grams | DoseUnit::Liters => Some(1000.0),
+            _ => None,
+        }
+    }
+
+    /// Convert `amount` of this unit into `to` ("250 mcg" → 0.25 mg).
+    ///
+    /// `None` across dimensions (mg and mL never convert) and between
+    /// different count or activity units.
+    pub fn convert(&self, amount: f64, to: &DoseUnit) -> Option<f64> {
+        if self == to {
+            return Some(amount);
+        }
+        if self.dimension() != to.dimension() {
+            return None;
+        }
+        Some(amount * self.scale()? / to.scale()?)
+    }
+
+    /// Canonical spelling ("mg", "mL", "tablets").
+    pub fn as_str(&self) -> &str {
+        match self {
+            DoseUnit::Milligrams => "mg",
+            DoseUnit::Micrograms => "mcg",
+            DoseUnit::Grams => "g",
+            DoseUnit::Milliliters => "mL",
+            DoseUnit::Liters => "L",
+            DoseUnit::Units => "units",
+            DoseUnit::InternationalUnits => "IU",
+            DoseUnit::Tablets => "tablets",
+            DoseUnit::Capsules => "capsules",
+            DoseUnit::MilligramsPerMilliliter => "mg/mL",
+            DoseUnit::MicrogramsPerMilliliter => "mcg/mL",
+            DoseUnit::Other(s) => s,
+        }
+    }
+}
+
+/// A dose unit with its weight and time qualifiers: "mg", "mg/kg",
+/// "mcg/kg/hr", "mL/hr".
+///
+/// This is the unit on normalized mentions, line items, and dose ranges. It
+/// parses any spelling ("MG/KG", "cc/hr") and compares by the canonical
+/// form, but serializes as the text it was parsed from, so stored and hashed
+/// JSON reads back byte for byte. [`Display`](fmt::Display) is canonical. A
+/// time other than per hour is left in the amount as an unknown unit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct Unit {
+    /// Amount unit ("mcg" in "mcg/kg/hr")
+    pub amount: DoseUnit,
+    /// Per kg of body weight
+    pub per_kg: bool,
+    /// Per hour, for infusion rates
+    pub per_hour: bool,
+    /// Text the unit was parsed from; `None` when built from a [`DoseUnit`]
+    spelling: Option<String>,
+}
+
+impl Unit {
+    /// Parse a unit with optional "/kg" and "/hr" qualifiers.
+    pub fn parse(s: &str) -> Self {
+        let mut amount = s.trim();
+        let per_hour = match strip_per(amount, &["hr", "h", "hour"]) {
+            Some(rest) => {
+                amount = rest;
+                true
+            }
+            None => false,
+        };
+        let per_kg = match strip_per(amount, &["kg"]) {
+            Some(rest) => {
+                amount = rest;
+                true
+            }
+            None => false,
+        };
+        Self {
+            amount: DoseUnit::parse(amount),
+            per_kg,
+            per_hour,
+            spelling: Some(s.trim().to_string()),
+        }
+    }
+
+    /// What the amount measures.
+    pub fn dimension(&self) -> UnitDimension {
+        self.amount.dimension()
+    }
+
+    /// The unit as written ("cc/hr"), or the canonical form if it wasn't
+    /// parsed from text.
+    pub fn spelling(&self) -> String {
+        self.spelling.clone().unwrap_or_else(|| self.to_string())
+    }
+}
+
+impl PartialEq for Unit {
+    fn eq(&self, other: &Self) -> bool {
+        self.amount == other.amount
+            && self.per_kg == other.per_kg
+            && self.per_hour == other.per_hour
+    }
+}
+
+impl Eq for Unit {}
+
+impl std::hash::Hash for Unit {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.amount.hash(state);
+        self.per_kg.hash(state);
+        self.per_hour.hash(state);
+    }
+}
+
+/// The text before a trailing "/<suffix>", if it ends with one of `suffixes`.
+fn strip_per<'a>(s: &'a str, suffixes: &[&str]) -> Option<&'a str> {
+    let (rest, per) = s.rsplit_once('/')?;
+    let per = per.trim();
+    suffixes
+        .iter()
+        .any(|suffix| per.eq_ignore_ascii_case(suffix))
+        .then(|| rest.trim_end())
+}
+
+impl From<DoseUnit> for Unit {
+    fn from(amount: DoseUnit) -> Self {
+        Self {
+            amount,
+            per_kg: false,
+            per_hour: false,
+            spelling: None,
+        }
+    }
+}
+
+impl From<&str> for Unit {
+    fn from(s: &str) -> Self {
+        Self::parse(s)
+    }
+}
+
+impl From<String> for Unit {
+    fn from(s: String) -> Self {
+        Self::parse(&s)
+    }
+}
+
+impl From<Unit> for String {
+    fn from(unit: Unit) -> Self {
+        unit.spelling()
+    }
+}
+
+impl PartialEq<&str> for Unit {
+    fn eq(&self, other: &&str) -> bool {
+        *self == Unit::parse(other)
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.amount.as_str())?;
+        if self.per_kg {
+            f.write_str("/kg")?;
+        }
+        if self.per_hour {
+            f.write_str("/hr")?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! string_conversions {
+    ($ty:ty) => {
+        impl From<&str> for $ty {
+            fn from(s: &str) -> Self {
+                Self::parse(s)
+            }
+        }
+
+        impl From<String> for $ty {
+            fn from(s: String) -> Self {
+                Self::parse(&s)
+            }
+        }
+
+        impl From<$ty> for String {
+            fn from(value: $ty) -> Self {
+                value.as_str().to_string()
+            }
+        }
+
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
+string_conversions!(Species);
+string_conversions!(Route);
+string_conversions!(DoseUnit);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_synonyms() {
+        assert_eq!(Route::parse("SQ"), Route::Subcutaneous);
+        assert_eq!(Route::parse("SC"), Route::Subcutaneous);
+        assert_eq!(Route::parse("subq"), Route::Subcutaneous);
+        assert_eq!(Route::parse(" po "), Route::Oral);
+        assert_eq!(
+            Route::parse("intraosseous"),
+            Route::Other("INTRAOSSEOUS".into())
+        );
+        assert_eq!(Route::parse("sc").to_string(), "SQ");
+    }
+
+    #[test]
+    fn test_species_and_units() {
+        assert_eq!(Species::parse("Dog"), Species::Canine);
+        assert_eq!(Species::parse("FELINE"), Species::Feline);
+        assert_eq!(Species::parse("Ferret"), Species::Other("ferret".into()));
+        assert!(Species::parse("cattle").is_food_animal());
+        assert!(Species::parse("Goat").is_food_animal());
+        assert!(!Species::parse("feline").is_food_animal());
+        assert_eq!(DoseUnit::parse("cc"), DoseUnit::Milliliters);
+        assert_eq!(DoseUnit::parse("ML").as_str(), "mL");
+    }
+
+    #[test]
+    fn test_unit_dimensions_and_conversion() {
+        let mcg = DoseUnit::parse("µg");
+        assert_eq!(mcg.dimension(), UnitDimension::Mass);
+        assert_eq!(mcg.convert(250.0, &DoseUnit::Milligrams), Some(0.25));
+        assert_eq!(
+            DoseUnit::parse("L").convert(0.5, &DoseUnit::Milliliters),
+            Some(500.0)
+        );
+        assert_eq!(
+            DoseUnit::parse("mg / ml"),
+            DoseUnit::MilligramsPerMilliliter
+        );
+        assert_eq!(
+            DoseUnit::parse("mcg/mL").convert(500.0, &DoseUnit::MilligramsPerMilliliter),
+            Some(0.5)
+        );
+        // mg and mL never compare, nor do different count units
+        assert_eq!(
+            DoseUnit::Milligrams.convert(1.0, &DoseUnit::Milliliters),
+            None
+        );
+        assert_eq!(
+            DoseUnit::Milligrams.convert(1.0, &DoseUnit::MilligramsPerMilliliter),
+            None
+        );
+        assert_eq!(DoseUnit::Tablets.convert(1.0, &DoseUnit::Capsules), None);
+        assert_eq!(
+            DoseUnit::Tablets.convert(2.0, &DoseUnit::parse("tabs")),
+            Some(2.0)
+        );
+
+        let rate = Unit::parse("MCG/KG/HR");
+        assert_eq!(rate.amount, DoseUnit::Micrograms);
+        assert!(rate.per_kg && rate.per_hour);
+        assert_eq!(rate.to_string(), "mcg/kg/hr");
+        assert_eq!(Unit::parse("cc/hr").to_string(), "mL/hr");
+        assert_eq!(
+            Unit::parse("mg/ml").dimension(),
+            UnitDimension::Concentration
+        );
+        assert_eq!(Unit::parse("mg/kg/min").to_string(), "mg/kg/min");
+        assert_eq!(Unit::parse("Tabs"), "tablets");
+    }
+
+    #[test]
+    fn test_canonical_serde() {
+        assert_eq!(
+            serde_json::to_string(&Route::parse("subq")).unwrap(),
+            "\"SQ\""
+        );
+        let route: Route = serde_json::from_str("\"sc\"").unwrap();
+        assert_eq!(route, Route::Subcutaneous);
+        let species: Vec<Species> = serde_json::from_str(r#"["dog", "ferret"]"#).unwrap();
+        assert_eq!(
+            species,
+            vec![Species::Canine, Species::Other("ferret".into())]
+        );
+        assert_eq!(
+            serde_json::to_string(&species).unwrap(),
+            r#"["canine","ferret"]"#
+        );
+        // Units compare canonically but keep their spelling
+        let unit: Unit = serde_json::from_str("\"CC/kg\"").unwrap();
+        assert_eq!(unit, "mL/kg");
+        assert_eq!(unit.to_string(), "mL/kg");
+        assert_eq!(serde_json::to_string(&unit).unwrap(), "\"CC/kg\"");
+        assert_eq!(
+            serde_json::to_string(&Unit::from(DoseUnit::Milliliters)).unwrap(),
+            "\"mL\""
+        );
+    }
+}
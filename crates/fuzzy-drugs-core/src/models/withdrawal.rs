@@ -0,0 +1,186 @@
+//! Meat and milk withdrawal times.
+//!
+//! A treated food animal can't go to slaughter, or have its milk sold, until
+//! the drug has cleared. Catalog items carry a [`WithdrawalTime`] per
+//! species from the label. When a food-animal patient is given the item, it
+//! gets a [`Withdrawal`] with the dates each period ends, counted from the
+//! last dose of the course (see [`last_dose_date`]).
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::disposition::DispositionType;
+use super::medication::course_days;
+use super::taper::TaperSchedule;
+use super::vocab::Species;
+
+/// Withdrawal periods for a catalog item in one species.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WithdrawalTime {
+    /// Species the periods apply to ("bovine")
+    pub species: String,
+    /// Days before meat may be sold
+    pub meat_days: Option<u32>,
+    /// Hours before milk may be sold
+    pub milk_hours: Option<u32>,
+}
+
+impl WithdrawalTime {
+    /// Check the species is a food animal and at least one period is set.
+    pub fn validate(&self) -> Result<(), String> {
+        if !Species::parse(&self.species).is_food_animal() {
+            return Err(format!("{} is not a food-animal species", self.species));
+        }
+        if self.meat_days.is_none() && self.milk_hours.is_none() {
+            return Err("Withdrawal time needs a meat or milk period".into());
+        }
+        Ok(())
+    }
+}
+
+/// Withdrawal a treated patient is under for one item.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Withdrawal {
+    /// Species the periods were taken for
+    pub species: String,
+    pub meat_days: Option<u32>,
+    pub milk_hours: Option<u32>,
+    /// Last day of treatment, which the periods count from (YYYY-MM-DD)
+    pub last_dose_date: String,
+    /// Day the meat withdrawal ends (YYYY-MM-DD)
+    pub meat_end_date: Option<String>,
+    /// Day the milk withdrawal ends, with the hours rounded up to whole days
+    /// (YYYY-MM-DD)
+    pub milk_end_date: Option<String>,
+}
+
+impl Withdrawal {
+    /// The withdrawal for `time` after a last dose on `last_dose`.
+    pub fn new(time: &WithdrawalTime, last_dose: NaiveDate) -> Self {
+        let after_days = |days: u32| {
+            (last_dose + chrono::Duration::days(i64::from(days)))
+                .format("%Y-%m-%d")
+                .to_string()
+        };
+        Self {
+            species: Species::parse(&time.species).to_string(),
+            meat_days: time.meat_days,
+            milk_hours: time.milk_hours,
+            last_dose_date: last_dose.format("%Y-%m-%d").to_string(),
+            meat_end_date: time.meat_days.map(after_days),
+            milk_end_date: time.milk_hours.map(|hours| after_days(hours.div_ceil(24))),
+        }
+    }
+
+    /// The same periods counted from a different last dose.
+    pub fn from_last_dose(&self, last_dose: NaiveDate) -> Self {
+        let time = WithdrawalTime {
+            species: self.species.clone(),
+            meat_days: self.meat_days,
+            milk_hours: self.milk_hours,
+        };
+        Self::new(&time, last_dose)
+    }
+
+    /// The later of the meat and milk end dates.
+    pub fn end_date(&self) -> Option<&str> {
+        self.meat_end_date
+            .as_deref()
+            .into_iter()
+            .chain(self.milk_end_date.as_deref())
+            .max()
+    }
+}
+
+/// Last day of a course started on `start`: the end of its taper, or the
+/// same day for a drug given once in clinic. A drug sent home (or not known
+/// to have been given in clinic) without a taper is assumed to run
+/// [`DEFAULT_COURSE_DAYS`](super::DEFAULT_COURSE_DAYS), as on the
+/// medication list, so the withdrawal doesn't end before the last dose.
+pub fn last_dose_date(
+    start: NaiveDate,
+    schedule: Option<&TaperSchedule>,
+    disposition: Option<DispositionType>,
+) -> NaiveDate {
+    let days = match (schedule, disposition) {
+        (None, Some(DispositionType::AdministeredInClinic)) => 1,
+        _ => course_days(schedule),
+    };
+    start + chrono::Duration::days(days - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DosePhase;
+
+    fn day(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_withdrawal_end_dates() {
+        let time = WithdrawalTime {
+            species: "cattle".into(),
+            meat_days: Some(28),
+            milk_hours: Some(96),
+        };
+        assert!(time.validate().is_ok());
+        let withdrawal = Withdrawal::new(&time, day("2026-03-01"));
+        assert_eq!(withdrawal.species, "bovine");
+        assert_eq!(withdrawal.meat_end_date.as_deref(), Some("2026-03-29"));
+        assert_eq!(withdrawal.milk_end_date.as_deref(), Some("2026-03-05"));
+        assert_eq!(withdrawal.end_date(), Some("2026-03-29"));
+
+        // Milk hours round up to whole days
+        let milk_only = WithdrawalTime {
+            meat_days: None,
+            milk_hours: Some(60),
+            ..time.clone()
+        };
+        let withdrawal = Withdrawal::new(&milk_only, day("2026-03-01"));
+        assert_eq!(withdrawal.end_date(), Some("2026-03-04"));
+
+        let invalid = WithdrawalTime {
+            species: "canine".into(),
+            ..time
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_last_dose_date_follows_course() {
+        let given = Some(DispositionType::AdministeredInClinic);
+        assert_eq!(
+            last_dose_date(day("2026-03-01"), None, given),
+            day("2026-03-01")
+        );
+        // A course sent home without a length runs the default course
+        let dispensed = Some(DispositionType::Dispensed);
+        assert_eq!(
+            last_dose_date(day("2026-03-01"), None, dispensed),
+            day("2026-03-14")
+        );
+        assert_eq!(
+            last_dose_date(day("2026-03-01"), None, None),
+            day("2026-03-14")
+        );
+        let taper = TaperSchedule {
+            phases: vec![DosePhase {
+                dose: 1.0,
+                unit: "mL".into(),
+                frequency: "SID".into(),
+                doses_per_day: 1.0,
+                days: 5.0,
+            }],
+        };
+        assert_eq!(
+            last_dose_date(day("2026-03-01"), Some(&taper), given),
+            day("2026-03-05")
+        );
+        assert_eq!(
+            last_dose_date(day("2026-03-01"), Some(&taper), dispensed),
+            day("2026-03-05")
+        );
+    }
+}
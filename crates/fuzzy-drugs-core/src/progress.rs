@@ -0,0 +1,90 @@
+//! Progress reporting and cancellation for long-running operations.
+//!
+//! Full compliance exports, bulk catalog imports, and batch resolution can
+//! take tens of seconds on large datasets. These operations take a
+//! [`Progress`] and call [`Progress::step`] after each item; returning
+//! [`Cancelled`] stops the operation, which then discards or rolls back its
+//! partial work.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use thiserror::Error;
+
+/// The operation was cancelled before it finished.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Operation cancelled")]
+pub struct Cancelled;
+
+/// Receives progress of a long-running operation.
+pub trait Progress {
+    /// Called once before the first item (`processed` = 0) and after each
+    /// item. Returning `Err(Cancelled)` stops the operation.
+    fn step(&self, processed: usize, total: usize) -> Result<(), Cancelled>;
+}
+
+/// No reporting, never cancelled.
+impl Progress for () {
+    fn step(&self, _processed: usize, _total: usize) -> Result<(), Cancelled> {
+        Ok(())
+    }
+}
+
+/// A closure taking `(processed, total)`.
+impl<F> Progress for F
+where
+    F: Fn(usize, usize) -> Result<(), Cancelled>,
+{
+    fn step(&self, processed: usize, total: usize) -> Result<(), Cancelled> {
+        self(processed, total)
+    }
+}
+
+/// Cancellation flag shared between the caller and a running operation.
+#[derive(Debug, Default)]
+pub struct CancellationFlag(AtomicBool);
+
+impl CancellationFlag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the operation to stop at the next item.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// `Err(Cancelled)` once cancelled.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Checks the flag only; for callers with nothing to report.
+impl Progress for CancellationFlag {
+    fn step(&self, _processed: usize, _total: usize) -> Result<(), Cancelled> {
+        self.check()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_flag() {
+        let flag = CancellationFlag::new();
+        assert_eq!(flag.step(0, 3), Ok(()));
+        flag.cancel();
+        assert!(flag.is_cancelled());
+        assert_eq!(flag.step(1, 3), Err(Cancelled));
+        assert_eq!(().step(1, 3), Ok(()));
+    }
+}
@@ -0,0 +1,198 @@
+//! Species and breed contraindication rules.
+//!
+//! Some matches are dangerous rather than merely low-confidence: permethrin
+//! for a cat, ivermectin for an MDR1 collie. Rules are checked against every
+//! candidate during resolution and attach [`SafetyWarning`]s to the item.
+
+use crate::models::{CatalogItem, SafetySeverity, SafetyWarning, Species};
+
+/// Breeds commonly carrying the MDR1 (ABCB1) mutation.
+const MDR1_BREEDS: &[&str] = &[
+    "collie",
+    "australian shepherd",
+    "shetland sheepdog",
+    "sheltie",
+    "old english sheepdog",
+    "english shepherd",
+    "german shepherd",
+    "long-haired whippet",
+    "silken windhound",
+    "mcnab",
+];
+
+/// A contraindication for a drug in a species and/or breeds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContraindicationRule {
+    /// Generic drug name (lowercase)
+    pub drug: String,
+    /// Species the rule applies to (empty = any species)
+    pub species: Vec<String>,
+    /// Breed substrings the rule applies to (empty = any breed)
+    pub breeds: Vec<String>,
+    /// Severity
+    pub severity: SafetySeverity,
+    /// Why the drug is dangerous
+    pub reason: String,
+}
+
+impl ContraindicationRule {
+    /// Rule for a drug in one species.
+    pub fn for_species(drug: &str, species: &str, severity: SafetySeverity, reason: &str) -> Self {
+        Self {
+            drug: drug.to_lowercase(),
+            species: vec![species.to_lowercase()],
+            breeds: Vec::new(),
+            severity,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Rule for a drug in specific breeds of any species.
+    pub fn for_breeds(drug: &str, breeds: &[&str], severity: SafetySeverity, reason: &str) -> Self {
+        Self {
+            drug: drug.to_lowercase(),
+            species: Vec::new(),
+            breeds: breeds.iter().map(|b| b.to_lowercase()).collect(),
+            severity,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Whether the rule applies to a patient.
+    ///
+    /// Breed rules only fire when the breed is known.
+    fn applies_to(&self, species: Option<&str>, breed: Option<&str>) -> bool {
+        let species_ok = self.species.is_empty()
+            || species.is_some_and(|s| {
+                let species = Species::parse(s);
+                self.species
+                    .iter()
+                    .any(|rule| Species::parse(rule) == species)
+            });
+        let breed_ok = self.breeds.is_empty()
+            || breed.is_some_and(|b| {
+                let b = b.to_lowercase();
+                self.breeds.iter().any(|rule| b.contains(rule.as_str()))
+            });
+        species_ok && breed_ok
+    }
+}
+
+/// Set of contraindication rules.
+#[derive(Debug, Clone, Default)]
+pub struct ContraindicationRules {
+    rules: Vec<ContraindicationRule>,
+}
+
+impl ContraindicationRules {
+    /// Create an empty rule set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The compiled-in rule set.
+    pub fn builtin() -> Self {
+        use SafetySeverity::*;
+
+        let mut rules = Self::new();
+        rules.add(ContraindicationRule::for_species(
+            "permethrin",
+            "feline",
+            Contraindicated,
+            "fatal neurotoxicity in cats",
+        ));
+        rules.add(ContraindicationRule::for_species(
+            "acetaminophen",
+            "feline",
+            Contraindicated,
+            "methemoglobinemia and hepatotoxicity in cats",
+        ));
+        rules.add(ContraindicationRule::for_species(
+            "enrofloxacin",
+            "feline",
+            Caution,
+            "retinal degeneration above 5 mg/kg/day in cats",
+        ));
+        rules.add(ContraindicationRule::for_species(
+            "xylitol",
+            "canine",
+            Contraindicated,
+            "hypoglycemia and hepatic necrosis in dogs",
+        ));
+        for drug in ["ivermectin", "loperamide", "moxidectin"] {
+            rules.add(ContraindicationRule::for_breeds(
+                drug,
+                MDR1_BREEDS,
+                Caution,
+                "MDR1 (ABCB1) breed: neurotoxicity risk; confirm genotype and dose",
+            ));
+        }
+        rules
+    }
+
+    /// Add a rule.
+    pub fn add(&mut self, rule: ContraindicationRule) {
+        self.rules.push(rule);
+    }
+
+    /// Warnings for a catalog item given to a patient.
+    pub fn check(
+        &self,
+        item: &CatalogItem,
+        patient_species: Option<&str>,
+        patient_breed: Option<&str>,
+    ) -> Vec<SafetyWarning> {
+        let drugs = item.component_names();
+        self.rules
+            .iter()
+            .filter(|rule| drugs.contains(&rule.drug))
+            .filter(|rule| rule.applies_to(patient_species, patient_breed))
+            .map(|rule| SafetyWarning {
+                sku: item.sku.clone(),
+                drug: rule.drug.clone(),
+                severity: rule.severity,
+                reason: rule.reason.clone(),
+                blocking: false,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_species_rule() {
+        let rules = ContraindicationRules::builtin();
+        let item = CatalogItem::new("PERM-65".into(), "Permethrin 65% spot-on".into());
+
+        let warnings = rules.check(&item, Some("feline"), None);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, SafetySeverity::Contraindicated);
+        assert_eq!(
+            warnings[0].message(),
+            "permethrin: fatal neurotoxicity in cats"
+        );
+
+        assert!(rules.check(&item, Some("canine"), None).is_empty());
+        assert!(rules.check(&item, None, None).is_empty());
+    }
+
+    #[test]
+    fn test_breed_rule() {
+        let rules = ContraindicationRules::builtin();
+        let item = CatalogItem::new("IVER-1".into(), "Ivermectin 1% injection".into());
+
+        assert_eq!(
+            rules
+                .check(&item, Some("canine"), Some("Rough Collie"))
+                .len(),
+            1
+        );
+        assert!(rules
+            .check(&item, Some("canine"), Some("Labrador"))
+            .is_empty());
+        assert!(rules.check(&item, Some("canine"), None).is_empty());
+    }
+}
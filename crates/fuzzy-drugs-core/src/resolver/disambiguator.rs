@@ -1,36 +1,73 @@
 //! SKU disambiguation using multi-factor scoring.
 //!
-//! Scoring weights:
+//! Default scoring weights (configurable per clinic, see [`ScoringConfig`]):
 //! - Name/alias match quality: 40%
 //! - Species compatibility: 25%
 //! - Route compatibility: 20%
 //! - Dose plausibility: 15%
+//!
+//! When a dose range can't decide plausibility, the dose score falls back to
+//! how cleanly the dose splits into the product's tablets (see [`super::dispensing`]).
 
 use strsim::{jaro_winkler, normalized_levenshtein};
 
-use crate::db::Database;
-use crate::models::{CatalogItem, NormalizedMention, ScoreBreakdown, ScoredCandidate};
-
-use super::ResolverResult;
-
-/// Number of candidates to retrieve from FTS5.
-const FTS_CANDIDATE_LIMIT: usize = 20;
-
-/// Number of alternatives to include in results.
-const MAX_ALTERNATIVES: usize = 4;
+use crate::db::{escape_fts_query, Database};
+use crate::models::{
+    split_components, CandidateTrace, CatalogItem, DoseExpression, NormalizedMention,
+    PatientAllergy, SafetySeverity, SafetyWarning, ScoreBreakdown, ScoredCandidate, ScoringConfig,
+    SearchTrace,
+};
 
-/// Minimum confidence to be considered a candidate.
-const MIN_CONFIDENCE: f64 = 0.20;
+use super::{ContraindicationRules, DispensingCalculator, ResolverResult};
 
 /// Disambiguator for resolving mentions to SKUs.
 pub struct Disambiguator<'a> {
     db: &'a Database,
+    config: ScoringConfig,
+    dispensing: DispensingCalculator,
+    contraindications: ContraindicationRules,
 }
 
 impl<'a> Disambiguator<'a> {
-    /// Create a new disambiguator.
-    pub fn new(db: &'a Database) -> Self {
-        Self { db }
+    /// Create a new disambiguator with the given scoring weights and limits.
+    pub fn new(db: &'a Database, config: ScoringConfig) -> Self {
+        Self {
+            db,
+            config,
+            dispensing: DispensingCalculator::new(),
+            contraindications: ContraindicationRules::builtin(),
+        }
+    }
+
+    /// The scoring weights and limits in use.
+    pub fn config(&self) -> &ScoringConfig {
+        &self.config
+    }
+
+    /// Check candidates against the species/breed contraindication rules
+    /// and the patient's recorded allergies.
+    ///
+    /// A candidate with an ingredient the patient is allergic to gets a
+    /// blocking contraindicated warning per matching ingredient.
+    pub fn safety_warnings<'c>(
+        &self,
+        candidates: impl IntoIterator<Item = &'c ScoredCandidate>,
+        patient_species: Option<&str>,
+        patient_breed: Option<&str>,
+        allergies: &[PatientAllergy],
+    ) -> ResolverResult<Vec<SafetyWarning>> {
+        let mut warnings = Vec::new();
+        for candidate in candidates {
+            if let Some(item) = self.db.get_catalog_item(&candidate.sku)? {
+                warnings.extend(self.contraindications.check(
+                    &item,
+                    patient_species,
+                    patient_breed,
+                ));
+                warnings.extend(allergy_warnings(&item, allergies));
+            }
+        }
+        Ok(warnings)
     }
 
     /// Disambiguate a normalized mention to find best SKU matches.
@@ -42,27 +79,86 @@ impl<'a> Disambiguator<'a> {
         patient_species: Option<&str>,
         patient_weight_kg: Option<f64>,
     ) -> ResolverResult<(ScoredCandidate, Vec<ScoredCandidate>)> {
-        // 1. Retrieve candidates via FTS5
-        let candidates = self
-            .db
-            .search_catalog(&mention.normalized_name, FTS_CANDIDATE_LIMIT)?;
+        let (candidates, _, _) = self.retrieve(mention)?;
+        self.score_and_rank(candidates, mention, patient_species, patient_weight_kg)
+    }
 
-        if candidates.is_empty() {
-            // Try searching with original drug name as fallback
-            let fallback_candidates = self
-                .db
-                .search_catalog(&mention.original.drug_name, FTS_CANDIDATE_LIMIT)?;
+    /// Score every retrieved candidate for the "why this match" trace.
+    ///
+    /// Unlike [`Self::disambiguate`], candidates below the minimum confidence
+    /// and beyond the alternatives limit are kept (and flagged).
+    pub fn trace_search(
+        &self,
+        mention: &NormalizedMention,
+        patient_species: Option<&str>,
+        patient_weight_kg: Option<f64>,
+    ) -> ResolverResult<SearchTrace> {
+        let (candidates, search_term, used_fallback) = self.retrieve(mention)?;
 
-            if fallback_candidates.is_empty() {
-                return Err(super::ResolverError::NoCandidates(
-                    mention.normalized_name.clone(),
-                ));
-            }
+        let mut traced: Vec<CandidateTrace> = candidates
+            .iter()
+            .map(|item| {
+                let scored =
+                    self.score_candidate(item, mention, patient_species, patient_weight_kg);
+                CandidateTrace {
+                    matched_alias: item
+                        .aliases
+                        .iter()
+                        .find(|a| a.eq_ignore_ascii_case(&mention.normalized_name))
+                        .cloned(),
+                    below_threshold: scored.confidence < self.config.min_confidence,
+                    dominant_factor: self.config.dominant_factor(&scored.score_breakdown),
+                    sku: scored.sku,
+                    name: scored.name,
+                    confidence: scored.confidence,
+                    score_breakdown: scored.score_breakdown,
+                }
+            })
+            .collect();
+        traced.sort_by(|a, b| {
+            b.confidence
+                .partial_cmp(&a.confidence)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
 
-            return self.score_and_rank(fallback_candidates, mention, patient_species, patient_weight_kg);
+        Ok(SearchTrace {
+            fts_query: escape_fts_query(search_term),
+            search_term: search_term.to_string(),
+            used_fallback,
+            candidates: traced,
+        })
+    }
+
+    /// Retrieve candidates via FTS5, falling back to the spoken drug name.
+    ///
+    /// Returns (candidates, name searched, whether the fallback was used).
+    fn retrieve<'m>(
+        &self,
+        mention: &'m NormalizedMention,
+    ) -> ResolverResult<(Vec<CatalogItem>, &'m str, bool)> {
+        let candidates = self
+            .db
+            .search_catalog(&mention.normalized_name, self.config.fts_candidate_limit)?;
+        if !candidates.is_empty() {
+            return Ok((candidates, &mention.normalized_name, false));
         }
+        tracing::debug!(
+            normalized = %mention.normalized_name,
+            spoken = %mention.original.drug_name,
+            "No FTS candidates for normalized name, retrying spoken name"
+        );
 
-        self.score_and_rank(candidates, mention, patient_species, patient_weight_kg)
+        // Try searching with original drug name as fallback
+        let fallback_candidates = self
+            .db
+            .search_catalog(&mention.original.drug_name, self.config.fts_candidate_limit)?;
+        if fallback_candidates.is_empty() {
+            tracing::debug!(normalized = %mention.normalized_name, "No catalog candidates");
+            return Err(super::ResolverError::NoCandidates(
+                mention.normalized_name.clone(),
+            ));
+        }
+        Ok((fallback_candidates, &mention.original.drug_name, true))
     }
 
     /// Score all candidates and return ranked results.
@@ -76,20 +172,50 @@ impl<'a> Disambiguator<'a> {
         let mut scored: Vec<ScoredCandidate> = candidates
             .into_iter()
             .map(|item| self.score_candidate(&item, mention, patient_species, patient_weight_kg))
-            .filter(|c| c.confidence >= MIN_CONFIDENCE)
+            .inspect(|c| {
+                tracing::trace!(
+                    sku = %c.sku,
+                    confidence = c.confidence,
+                    name = c.score_breakdown.name_score,
+                    species = c.score_breakdown.species_score,
+                    route = c.score_breakdown.route_score,
+                    dose = c.score_breakdown.dose_score,
+                    "Scored candidate"
+                )
+            })
+            .filter(|c| c.confidence >= self.config.min_confidence)
             .collect();
 
         if scored.is_empty() {
+            tracing::debug!(
+                normalized = %mention.normalized_name,
+                min_confidence = self.config.min_confidence,
+                "All candidates below minimum confidence"
+            );
             return Err(super::ResolverError::NoCandidates(
                 mention.normalized_name.clone(),
             ));
         }
 
         // Sort by confidence descending
-        scored.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+        scored.sort_by(|a, b| {
+            b.confidence
+                .partial_cmp(&a.confidence)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
 
         let top = scored.remove(0);
-        let alternatives: Vec<ScoredCandidate> = scored.into_iter().take(MAX_ALTERNATIVES).collect();
+        let alternatives: Vec<ScoredCandidate> = scored
+            .into_iter()
+            .take(self.config.max_alternatives)
+            .collect();
+        tracing::debug!(
+            normalized = %mention.normalized_name,
+            sku = %top.sku,
+            confidence = top.confidence,
+            alternatives = alternatives.len(),
+            "Top candidate"
+        );
 
         Ok((top, alternatives))
     }
@@ -102,23 +228,29 @@ impl<'a> Disambiguator<'a> {
         patient_species: Option<&str>,
         patient_weight_kg: Option<f64>,
     ) -> ScoredCandidate {
+        let dose = mention.dose_expression();
+        // Weight-based doses are dispensed as the amount for this patient
+        let suggested_quantity = dose.as_ref().and_then(|d| {
+            let amount = d.amount_per_dose(patient_weight_kg)?;
+            self.dispensing
+                .suggest(item, amount, &d.amount_unit().into())
+        });
+        let fraction_score = suggested_quantity.as_ref().and_then(|q| q.fraction_score);
+
         let breakdown = ScoreBreakdown {
             name_score: self.score_name_match(item, &mention.normalized_name),
             species_score: self.score_species(item, patient_species),
             route_score: self.score_route(item, mention.normalized_route.as_deref()),
-            dose_score: self.score_dose(
-                item,
-                mention.normalized_dose,
-                mention.normalized_unit.as_deref(),
-                patient_weight_kg,
-            ),
+            dose_score: self.score_dose(item, dose.as_ref(), patient_weight_kg, fraction_score),
         };
 
         ScoredCandidate {
             sku: item.sku.clone(),
             name: item.name.clone(),
-            confidence: breakdown.weighted_score(),
+            confidence: self.config.confidence(&breakdown),
             score_breakdown: breakdown,
+            suggested_quantity,
+            controlled_schedule: item.controlled_schedule,
         }
     }
 
@@ -126,6 +258,22 @@ impl<'a> Disambiguator<'a> {
     fn score_name_match(&self, item: &CatalogItem, query: &str) -> f64 {
         let query_lower = query.to_lowercase();
 
+        // Combination products: match on components regardless of separator or order
+        let query_components = split_components(&query_lower);
+        let item_components = item.component_names();
+        if item_components.len() > 1 {
+            if query_components.len() > 1 {
+                let score = component_match(&query_components, &item_components);
+                if score > 0.0 {
+                    return score;
+                }
+            } else if item_components.contains(&query_lower) {
+                // A single component names only part of the product, so
+                // single-drug products for that component rank above it
+                return 0.85;
+            }
+        }
+
         // Check exact match on name
         if item.name.to_lowercase().contains(&query_lower) {
             return 1.0;
@@ -187,22 +335,25 @@ impl<'a> Disambiguator<'a> {
     }
 
     /// Score dose plausibility (0.0 - 1.0).
+    ///
+    /// `fraction_score` (how cleanly the dose splits into this product's
+    /// tablets) is used when the dose range can't decide.
+    ///
+    /// Per-kg doses are checked against the range without needing the weight;
+    /// rates can't be compared to a per-dose range and score neutrally.
     fn score_dose(
         &self,
         item: &CatalogItem,
-        dose: Option<f64>,
-        unit: Option<&str>,
+        dose: Option<&DoseExpression>,
         weight_kg: Option<f64>,
+        fraction_score: Option<f64>,
     ) -> f64 {
-        match (dose, unit, weight_kg) {
-            (Some(d), Some(u), Some(w)) => {
-                match item.is_dose_plausible(d, u, w) {
-                    Some(true) => 1.0,
-                    Some(false) => 0.3, // Out of range but might be intentional
-                    None => 0.6,        // Can't compare - unknown
-                }
-            }
-            _ => 0.6, // Missing data - moderate score
+        let plausible = dose.and_then(|d| item.is_dose_expression_plausible(d, weight_kg));
+        match (plausible, fraction_score) {
+            (Some(true), _) => 1.0,
+            (Some(false), _) => 0.3, // Out of range but might be intentional
+            (None, Some(fraction)) => fraction,
+            (None, None) => 0.6, // Can't compare or missing data - moderate score
         }
     }
 }
@@ -217,6 +368,55 @@ fn fuzzy_match(a: &str, b: &str) -> f64 {
     jw * 0.6 + lev * 0.4
 }
 
+/// Minimum per-component similarity to count as the same ingredient.
+const COMPONENT_MATCH_THRESHOLD: f64 = 0.85;
+
+/// Score a multi-component query against a combination product's components.
+///
+/// Every query component must fuzzily match some item component; the score is
+/// their mean similarity, discounted when the item has extra components.
+/// Returns 0.0 if any query component is unmatched.
+fn component_match(query: &[String], item: &[String]) -> f64 {
+    let mut total = 0.0;
+    for q in query {
+        let best = item.iter().map(|c| fuzzy_match(q, c)).fold(0.0, f64::max);
+        if best < COMPONENT_MATCH_THRESHOLD {
+            return 0.0;
+        }
+        total += best;
+    }
+
+    let mean = total / query.len() as f64;
+    if query.len() >= item.len() {
+        mean
+    } else {
+        mean * 0.9
+    }
+}
+
+/// Blocking warnings for the ingredients of `item` that match a recorded
+/// allergy.
+/// Blocking warnings for each of `item`'s ingredients the patient is
+/// allergic to.
+pub fn allergy_warnings(item: &CatalogItem, allergies: &[PatientAllergy]) -> Vec<SafetyWarning> {
+    let mut warnings = Vec::new();
+    for ingredient in item.component_names() {
+        if let Some(allergy) = allergies.iter().find(|a| a.matches(&ingredient)) {
+            warnings.push(SafetyWarning {
+                sku: item.sku.clone(),
+                drug: ingredient,
+                severity: SafetySeverity::Contraindicated,
+                reason: match &allergy.reaction {
+                    Some(reaction) => format!("recorded patient allergy ({})", reaction),
+                    None => "recorded patient allergy".to_string(),
+                },
+                blocking: true,
+            });
+        }
+    }
+    warnings
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,8 +445,10 @@ mod tests {
         db.upsert_catalog_item(&item1b).unwrap();
 
         // Meloxicam
-        let mut item2 =
-            CatalogItem::new("MELOX-15".into(), "Meloxicam 1.5mg/mL oral suspension".into());
+        let mut item2 = CatalogItem::new(
+            "MELOX-15".into(),
+            "Meloxicam 1.5mg/mL oral suspension".into(),
+        );
         item2.aliases = vec!["metacam".into()];
         item2.species = vec!["canine".into(), "feline".into()];
         item2.routes = vec!["PO".into()];
@@ -262,7 +464,12 @@ mod tests {
         db
     }
 
-    fn make_mention(drug: &str, dose: Option<f64>, unit: Option<&str>, route: Option<&str>) -> NormalizedMention {
+    fn make_mention(
+        drug: &str,
+        dose: Option<f64>,
+        unit: Option<&str>,
+        route: Option<&str>,
+    ) -> NormalizedMention {
         NormalizedMention {
             original: DrugMention {
                 raw_text: "test".into(),
@@ -273,18 +480,23 @@ mod tests {
                 species: None,
                 start_offset: 0,
                 end_offset: 4,
+                field_spans: Default::default(),
+                extraction_confidence: None,
+                speaker: None,
             },
             normalized_name: drug.into(),
             normalized_dose: dose,
             normalized_unit: unit.map(|s| s.into()),
             normalized_route: route.map(|s| s.into()),
+            infusion: None,
+            taper: None,
         }
     }
 
     #[test]
     fn test_exact_name_match() {
         let db = setup_db();
-        let disambiguator = Disambiguator::new(&db);
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
 
         let mention = make_mention("carprofen", Some(100.0), Some("mg"), Some("PO"));
         let (top, _) = disambiguator
@@ -299,7 +511,7 @@ mod tests {
     #[test]
     fn test_alias_match() {
         let db = setup_db();
-        let disambiguator = Disambiguator::new(&db);
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
 
         let mention = make_mention("rimadyl", Some(100.0), Some("mg"), Some("PO"));
         let (top, _) = disambiguator
@@ -314,7 +526,7 @@ mod tests {
     #[test]
     fn test_species_scoring() {
         let db = setup_db();
-        let disambiguator = Disambiguator::new(&db);
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
 
         // Carprofen is canine-only
         let mention = make_mention("carprofen", None, None, None);
@@ -332,10 +544,37 @@ mod tests {
         assert_eq!(top_feline.score_breakdown.species_score, 0.1);
     }
 
+    #[test]
+    fn test_scoring_config() {
+        let db = setup_db();
+        let mention = make_mention("carprofen", None, None, None);
+
+        let (top, alternatives) = Disambiguator::new(&db, ScoringConfig::default())
+            .disambiguate(&mention, Some("canine"), None)
+            .unwrap();
+        assert!(top.confidence < 1.0);
+        assert_eq!(alternatives.len(), 1);
+
+        // Name match only, no alternatives
+        let config = ScoringConfig {
+            name_weight: 1.0,
+            species_weight: 0.0,
+            route_weight: 0.0,
+            dose_weight: 0.0,
+            max_alternatives: 0,
+            ..Default::default()
+        };
+        let (top, alternatives) = Disambiguator::new(&db, config)
+            .disambiguate(&mention, Some("canine"), None)
+            .unwrap();
+        assert_eq!(top.confidence, 1.0);
+        assert!(alternatives.is_empty());
+    }
+
     #[test]
     fn test_route_scoring() {
         let db = setup_db();
-        let disambiguator = Disambiguator::new(&db);
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
 
         // Acepromazine - IV, IM, SQ routes
         let mention = make_mention("acepromazine", None, None, Some("IM"));
@@ -353,7 +592,7 @@ mod tests {
     #[test]
     fn test_dose_scoring() {
         let db = setup_db();
-        let disambiguator = Disambiguator::new(&db);
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
 
         // 30kg dog, 100mg carprofen = 3.3mg/kg (within 2-4.4 range)
         let mention = make_mention("carprofen", Some(100.0), Some("mg"), Some("PO"));
@@ -377,10 +616,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_weight_based_dose_scoring() {
+        let db = setup_db();
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
+        let carp_100 = |mention: &NormalizedMention, weight: Option<f64>| {
+            let (top, alternatives) = disambiguator
+                .disambiguate(mention, Some("canine"), weight)
+                .unwrap();
+            std::iter::once(top)
+                .chain(alternatives)
+                .find(|c| c.sku == "CARP-100")
+                .unwrap()
+        };
+
+        // Per-kg doses are checked against the range without a weight
+        let mention = make_mention("carprofen", Some(2.2), Some("mg/kg"), Some("PO"));
+        assert_eq!(carp_100(&mention, None).score_breakdown.dose_score, 1.0);
+        let mention = make_mention("carprofen", Some(10.0), Some("mg/kg"), Some("PO"));
+        assert_eq!(carp_100(&mention, None).score_breakdown.dose_score, 0.3);
+
+        // With a weight, the per-kg dose is dispensed as tablets
+        let mention = make_mention("carprofen", Some(3.3), Some("mg/kg"), Some("PO"));
+        let candidate = carp_100(&mention, Some(30.0));
+        let quantity = candidate.suggested_quantity.unwrap();
+        assert!((quantity.per_dose - 1.0).abs() < 0.05);
+    }
+
     #[test]
     fn test_alternatives_returned() {
         let db = setup_db();
-        let disambiguator = Disambiguator::new(&db);
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
 
         // Search for carprofen - should find multiple variants
         let mention = make_mention("carprofen", None, None, None);
@@ -395,10 +661,87 @@ mod tests {
             alternatives.iter().all(|a| a.confidence <= top.confidence),
             "Expected all alternatives to have confidence <= top ({}), but found: {:?}",
             top.confidence,
-            alternatives.iter().map(|a| (a.sku.clone(), a.confidence)).collect::<Vec<_>>()
+            alternatives
+                .iter()
+                .map(|a| (a.sku.clone(), a.confidence))
+                .collect::<Vec<_>>()
         );
     }
 
+    #[test]
+    fn test_prefers_whole_tablet_strength() {
+        let db = Database::open_in_memory().unwrap();
+        for mg in [25, 75, 100] {
+            let mut item = CatalogItem::new(
+                format!("CARP-{}", mg),
+                format!("Carprofen {}mg tablets", mg),
+            );
+            item.species = vec!["canine".into()];
+            item.routes = vec!["PO".into()];
+            db.upsert_catalog_item(&item).unwrap();
+        }
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
+
+        let mention = make_mention("carprofen", Some(75.0), Some("mg"), Some("PO"));
+        let (top, alternatives) = disambiguator
+            .disambiguate(&mention, Some("canine"), None)
+            .unwrap();
+
+        assert_eq!(top.sku, "CARP-75");
+        let quantity = top.suggested_quantity.unwrap();
+        assert_eq!(quantity.per_dose, 1.0);
+        assert_eq!(quantity.unit, "tablets");
+
+        let carp_25 = alternatives.iter().find(|a| a.sku == "CARP-25").unwrap();
+        assert_eq!(carp_25.suggested_quantity.as_ref().unwrap().per_dose, 3.0);
+    }
+
+    #[test]
+    fn test_combination_component_match() {
+        let db = Database::open_in_memory().unwrap();
+
+        let mut clavamox = CatalogItem::new("CLAV-250".into(), "Clavamox 250mg tablets".into());
+        clavamox.components = vec!["amoxicillin".into(), "clavulanate".into()];
+        db.upsert_catalog_item(&clavamox).unwrap();
+
+        let amox = CatalogItem::new("AMOX-250".into(), "Amoxicillin 250mg tablets".into());
+        db.upsert_catalog_item(&amox).unwrap();
+
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
+
+        // Normalized combination name matches the brand-named product
+        let mention = make_mention("amoxicillin-clavulanate", None, None, None);
+        let (top, _) = disambiguator.disambiguate(&mention, None, None).unwrap();
+        assert_eq!(top.sku, "CLAV-250");
+        assert!(top.score_breakdown.name_score > 0.99);
+
+        // Separator and order don't matter
+        let mention = make_mention("clavulanate/amoxicillin", None, None, None);
+        let (top, _) = disambiguator.disambiguate(&mention, None, None).unwrap();
+        assert_eq!(top.sku, "CLAV-250");
+
+        // A single component still finds the combo, but prefers the single drug
+        let mention = make_mention("amoxicillin", None, None, None);
+        let (top, alternatives) = disambiguator.disambiguate(&mention, None, None).unwrap();
+        assert_eq!(top.sku, "AMOX-250");
+        let combo = alternatives.iter().find(|a| a.sku == "CLAV-250").unwrap();
+        assert_eq!(combo.score_breakdown.name_score, 0.85);
+
+        let mention = make_mention("clavulanate", None, None, None);
+        let (top, _) = disambiguator.disambiguate(&mention, None, None).unwrap();
+        assert_eq!(top.sku, "CLAV-250");
+    }
+
+    #[test]
+    fn test_component_match() {
+        let item = vec!["amoxicillin".to_string(), "clavulanate".to_string()];
+        let both = vec!["clavulanate".to_string(), "amoxicillin".to_string()];
+        assert!(component_match(&both, &item) > 0.99);
+
+        let other = vec!["amoxicillin".to_string(), "trimethoprim".to_string()];
+        assert_eq!(component_match(&other, &item), 0.0);
+    }
+
     #[test]
     fn test_fuzzy_match() {
         // Test the fuzzy matching function
@@ -410,7 +753,7 @@ mod tests {
     #[test]
     fn test_no_candidates_error() {
         let db = setup_db();
-        let disambiguator = Disambiguator::new(&db);
+        let disambiguator = Disambiguator::new(&db, ScoringConfig::default());
 
         let mention = make_mention("nonexistentdrug12345", None, None, None);
         let result = disambiguator.disambiguate(&mention, None, None);
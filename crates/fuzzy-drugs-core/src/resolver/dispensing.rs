@@ -0,0 +1,433 @@
+//! Product strength parsing and dispense-quantity computation.
+//!
+//! When a vet says "carprofen 75mg BID" and the catalog carries 25/75/100mg
+//! tablets, the strength that yields whole (or half) tablets is preferred and
+//! the per-dose tablet count is attached to the candidate.
+
+use crate::models::{CatalogItem, DoseUnit, SuggestedQuantity, TaperSchedule, Unit, UnitDimension};
+
+/// Parsed product strength (e.g., "100mg" or "1.5mg/mL").
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strength {
+    /// Active ingredient amount, in `unit`
+    pub amount: f64,
+    /// Mass unit (mg, mcg, g)
+    pub unit: DoseUnit,
+    /// Volume (mL) the amount is dissolved in, for liquids
+    pub per_ml: Option<f64>,
+}
+
+impl Strength {
+    /// Amount in mg (mass units only).
+    pub fn amount_mg(&self) -> Option<f64> {
+        self.unit.convert(self.amount, &DoseUnit::Milligrams)
+    }
+}
+
+/// Dosage form of a catalog item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DosageForm {
+    Tablet,
+    Capsule,
+    Liquid,
+    Unknown,
+}
+
+/// Parse the first strength expression in a string.
+///
+/// Accepts "100mg", "75 mg", "1.5mg/mL", "10 mg / ml", "50mcg".
+pub fn parse_strength(text: &str) -> Option<Strength> {
+    let lower = text.to_lowercase();
+    let chars: Vec<char> = lower.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+
+        // Number
+        let start = i;
+        while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+            i += 1;
+        }
+        let number: String = chars[start..i].iter().collect();
+        let Ok(amount) = number.parse::<f64>() else {
+            continue;
+        };
+
+        // Unit
+        let mut j = skip_spaces(&chars, i);
+        let unit_start = j;
+        while j < chars.len() && chars[j].is_alphabetic() {
+            j += 1;
+        }
+        let unit: String = chars[unit_start..j].iter().collect();
+        let unit = DoseUnit::parse(&unit);
+        if unit.dimension() != UnitDimension::Mass {
+            continue;
+        }
+
+        // Optional "/mL" or "/ 5mL"
+        let mut per_ml = None;
+        let k = skip_spaces(&chars, j);
+        if k < chars.len() && chars[k] == '/' {
+            let mut m = skip_spaces(&chars, k + 1);
+            let vol_start = m;
+            while m < chars.len() && (chars[m].is_ascii_digit() || chars[m] == '.') {
+                m += 1;
+            }
+            let volume: f64 = if m > vol_start {
+                chars[vol_start..m]
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .unwrap_or(1.0)
+            } else {
+                1.0
+            };
+            let m = skip_spaces(&chars, m);
+            let rest: String = chars[m..].iter().take(2).collect();
+            if rest == "ml" || rest.starts_with("cc") {
+                per_ml = Some(volume);
+            }
+        }
+
+        return Some(Strength {
+            amount,
+            unit,
+            per_ml,
+        });
+    }
+
+    None
+}
+
+fn skip_spaces(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && chars[i] == ' ' {
+        i += 1;
+    }
+    i
+}
+
+/// Determine the strength of a catalog item (concentration first, then name).
+pub fn item_strength(item: &CatalogItem) -> Option<Strength> {
+    item.concentration
+        .as_deref()
+        .and_then(parse_strength)
+        .or_else(|| parse_strength(&item.name))
+}
+
+/// Determine the dosage form of a catalog item from its name and package size.
+pub fn dosage_form(item: &CatalogItem) -> DosageForm {
+    let text = format!(
+        "{} {}",
+        item.name.to_lowercase(),
+        item.package_size.as_deref().unwrap_or("").to_lowercase()
+    );
+    if text.contains("tablet") || text.contains(" tab") || text.contains("chewable") {
+        DosageForm::Tablet
+    } else if text.contains("capsule") || text.contains(" cap") {
+        DosageForm::Capsule
+    } else if text.contains("/ml")
+        || text.contains("suspension")
+        || text.contains("solution")
+        || text.contains("injection")
+        || text.contains("injectable")
+    {
+        DosageForm::Liquid
+    } else {
+        DosageForm::Unknown
+    }
+}
+
+/// Dispensing calculator.
+#[derive(Debug, Clone, Default)]
+pub struct DispensingCalculator;
+
+impl DispensingCalculator {
+    /// Create a new calculator.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Suggest the per-dose quantity of `item` for a dose in mass units.
+    ///
+    /// Returns tablets/capsules for solid forms and mL for liquids. `None`
+    /// for per-kg doses and rates, which aren't an amount to dispense.
+    pub fn suggest(&self, item: &CatalogItem, dose: f64, unit: &Unit) -> Option<SuggestedQuantity> {
+        if unit.per_kg || unit.per_hour {
+            return None;
+        }
+        let form = dosage_form(item);
+
+        // Dose already expressed in dispensing units
+        match (form, &unit.amount) {
+            (DosageForm::Tablet, DoseUnit::Tablets) | (DosageForm::Capsule, DoseUnit::Capsules) => {
+                return Some(SuggestedQuantity {
+                    per_dose: dose,
+                    unit: unit.amount.to_string(),
+                    fraction_score: tablet_fraction_score(dose),
+                });
+            }
+            (DosageForm::Liquid, amount) if amount.dimension() == UnitDimension::Volume => {
+                return Some(SuggestedQuantity {
+                    per_dose: amount.convert(dose, &DoseUnit::Milliliters)?,
+                    unit: DoseUnit::Milliliters.to_string(),
+                    fraction_score: None,
+                });
+            }
+            _ => {}
+        }
+
+        let dose_mg = unit.amount.convert(dose, &DoseUnit::Milligrams)?;
+        let strength = item_strength(item)?;
+        let strength_mg = strength.amount_mg()?;
+        if strength_mg <= 0.0 {
+            return None;
+        }
+
+        match (form, strength.per_ml) {
+            (DosageForm::Tablet, None) | (DosageForm::Capsule, None) => {
+                let count = dose_mg / strength_mg;
+                let unit = if form == DosageForm::Tablet {
+                    "tablets"
+                } else {
+                    "capsules"
+                };
+                Some(SuggestedQuantity {
+                    per_dose: round_to(count, 0.25),
+                    unit: unit.into(),
+                    fraction_score: tablet_fraction_score(count),
+                })
+            }
+            (_, Some(per_ml)) => {
+                let ml = dose_mg / strength_mg * per_ml;
+                Some(SuggestedQuantity {
+                    per_dose: round_to(ml, 0.01),
+                    unit: "mL".into(),
+                    fraction_score: None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Total whole tablets/capsules to dispense for a course.
+    pub fn dispense_quantity(&self, per_dose: f64, doses_per_day: f64, days: f64) -> f64 {
+        let total = per_dose * doses_per_day * days;
+        // Guard against float noise (e.g., 0.5 * 2 * 7 = 7.000000001)
+        (total - 1e-9).ceil().max(0.0)
+    }
+
+    /// Total whole tablets/capsules (or mL) of `item` to dispense for a
+    /// taper, summed over the phases.
+    ///
+    /// `None` if any phase's dose can't be expressed in the item's units.
+    pub fn dispense_taper(&self, item: &CatalogItem, taper: &TaperSchedule) -> Option<f64> {
+        let mut total = 0.0;
+        for phase in &taper.phases {
+            let per_dose = self
+                .suggest(item, phase.dose, &Unit::parse(&phase.unit))?
+                .per_dose;
+            total += per_dose * phase.doses_per_day * phase.days;
+        }
+        Some((total - 1e-9).ceil().max(0.0))
+    }
+
+    /// Stock of `item` a committed line draws down, in dispensing units: the
+    /// per-dose suggestion, or for a taper ([`dispense_taper`](Self::dispense_taper))
+    /// the whole course.
+    pub fn dispense_line(
+        &self,
+        item: &CatalogItem,
+        quantity: f64,
+        unit: &Unit,
+        schedule: Option<&TaperSchedule>,
+    ) -> Option<SuggestedQuantity> {
+        let Some(taper) = schedule else {
+            return self.suggest(item, quantity, unit);
+        };
+        let first = taper.phases.first()?;
+        let unit = self
+            .suggest(item, first.dose, &Unit::parse(&first.unit))?
+            .unit;
+        Some(SuggestedQuantity {
+            per_dose: self.dispense_taper(item, taper)?,
+            unit,
+            fraction_score: None,
+        })
+    }
+}
+
+/// Doses per day for common frequency abbreviations (SID, BID, q8h, ...).
+pub fn doses_per_day(frequency: &str) -> Option<f64> {
+    let f = frequency.trim().to_lowercase().replace(['.', ' '], "");
+    match f.as_str() {
+        "sid" | "qd" | "q24h" | "once" | "oncedaily" | "daily" => Some(1.0),
+        "bid" | "q12h" | "twicedaily" => Some(2.0),
+        "tid" | "q8h" | "threetimesdaily" => Some(3.0),
+        "qid" | "q6h" | "fourtimesdaily" => Some(4.0),
+        "eod" | "q48h" | "everyotherday" => Some(0.5),
+        _ => None,
+    }
+}
+
+/// Score how cleanly a tablet count splits (1.0 = one whole tablet).
+///
+/// Whole tablets are preferred over halves, halves over quarters; anything
+/// else requires compounding or guesswork.
+fn tablet_fraction_score(count: f64) -> Option<f64> {
+    if count <= 0.0 || !count.is_finite() {
+        return None;
+    }
+    let is_multiple = |step: f64| ((count / step) - (count / step).round()).abs() < 0.02;
+
+    Some(if (count - 1.0).abs() < 0.02 {
+        1.0
+    } else if is_multiple(1.0) && count <= 2.0 {
+        0.9
+    } else if is_multiple(1.0) {
+        0.8
+    } else if is_multiple(0.5) {
+        0.75
+    } else if is_multiple(0.25) {
+        0.5
+    } else {
+        0.3
+    })
+}
+
+fn round_to(value: f64, step: f64) -> f64 {
+    (value / step).round() * step
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tablet(sku: &str, mg: u32) -> CatalogItem {
+        CatalogItem::new(sku.into(), format!("Carprofen {}mg tablets", mg))
+    }
+
+    #[test]
+    fn test_parse_strength() {
+        let s = parse_strength("Carprofen 100mg tablets").unwrap();
+        assert_eq!(s.amount, 100.0);
+        assert_eq!(s.unit, DoseUnit::Milligrams);
+        assert_eq!(s.per_ml, None);
+
+        let s = parse_strength("Meloxicam 1.5mg/mL oral suspension").unwrap();
+        assert_eq!(s.amount, 1.5);
+        assert_eq!(s.per_ml, Some(1.0));
+
+        let s = parse_strength("Amoxicillin 250 mg / 5 mL").unwrap();
+        assert_eq!(s.amount, 250.0);
+        assert_eq!(s.per_ml, Some(5.0));
+
+        let s = parse_strength("Levothyroxine 800 mcg").unwrap();
+        assert_eq!(s.amount_mg(), Some(0.8));
+
+        assert!(parse_strength("Cerenia injection").is_none());
+        assert!(parse_strength("50 tablets").is_none());
+    }
+
+    #[test]
+    fn test_suggest_prefers_whole_tablets() {
+        let calc = DispensingCalculator::new();
+
+        let q75 = calc
+            .suggest(&tablet("C75", 75), 75.0, &"mg".into())
+            .unwrap();
+        assert_eq!(q75.per_dose, 1.0);
+        assert_eq!(q75.unit, "tablets");
+        assert_eq!(q75.fraction_score, Some(1.0));
+
+        let q25 = calc
+            .suggest(&tablet("C25", 25), 75.0, &"mg".into())
+            .unwrap();
+        assert_eq!(q25.per_dose, 3.0);
+        assert_eq!(q25.fraction_score, Some(0.8));
+
+        let q100 = calc
+            .suggest(&tablet("C100", 100), 75.0, &"mg".into())
+            .unwrap();
+        assert_eq!(q100.per_dose, 0.75);
+        assert_eq!(q100.fraction_score, Some(0.5));
+
+        let half = calc
+            .suggest(&tablet("C100", 100), 50.0, &"mg".into())
+            .unwrap();
+        assert_eq!(half.per_dose, 0.5);
+        assert_eq!(half.fraction_score, Some(0.75));
+    }
+
+    #[test]
+    fn test_suggest_liquid_volume() {
+        let calc = DispensingCalculator::new();
+        let item = CatalogItem::new("MELOX".into(), "Meloxicam 1.5mg/mL oral suspension".into());
+
+        let q = calc.suggest(&item, 3.0, &"mg".into()).unwrap();
+        assert_eq!(q.per_dose, 2.0);
+        assert_eq!(q.unit, "mL");
+        assert_eq!(q.fraction_score, None);
+
+        // 3000 mcg is 3 mg; a per-kg dose is not an amount to dispense
+        assert_eq!(
+            calc.suggest(&item, 3000.0, &"mcg".into()).unwrap().per_dose,
+            2.0
+        );
+        assert_eq!(calc.suggest(&item, 0.1, &"mg/kg".into()), None);
+        assert_eq!(
+            calc.suggest(&item, 0.002, &"L".into()).unwrap().per_dose,
+            2.0
+        );
+    }
+
+    #[test]
+    fn test_dispense_quantity() {
+        let calc = DispensingCalculator::new();
+        assert_eq!(calc.dispense_quantity(1.0, 2.0, 14.0), 28.0);
+        assert_eq!(calc.dispense_quantity(0.5, 2.0, 7.0), 7.0);
+        assert_eq!(calc.dispense_quantity(0.75, 1.0, 5.0), 4.0);
+    }
+
+    #[test]
+    fn test_dispense_taper() {
+        let calc = DispensingCalculator::new();
+        let item = CatalogItem::new("PRED-5".into(), "Prednisone 5mg tablets".into());
+        let phase = |dose: f64, doses_per_day: f64, days: f64| crate::models::DosePhase {
+            dose,
+            unit: "mg".into(),
+            frequency: String::new(),
+            doses_per_day,
+            days,
+        };
+        let taper = TaperSchedule {
+            phases: vec![
+                phase(20.0, 2.0, 5.0),
+                phase(10.0, 1.0, 5.0),
+                phase(2.5, 1.0, 3.0),
+            ],
+        };
+
+        // 40 + 10 + 1.5 tablets
+        assert_eq!(calc.dispense_taper(&item, &taper), Some(52.0));
+        // A committed taper line draws down the course, not its total in mg
+        let line = calc
+            .dispense_line(&item, 257.5, &"mg".into(), Some(&taper))
+            .unwrap();
+        assert_eq!((line.per_dose, line.unit.as_str()), (52.0, "tablets"));
+        let single = calc.dispense_line(&item, 20.0, &"mg".into(), None).unwrap();
+        assert_eq!(single.per_dose, 4.0);
+    }
+
+    #[test]
+    fn test_doses_per_day() {
+        assert_eq!(doses_per_day("BID"), Some(2.0));
+        assert_eq!(doses_per_day("q8h"), Some(3.0));
+        assert_eq!(doses_per_day("s.i.d."), Some(1.0));
+        assert_eq!(doses_per_day("sometimes"), None);
+    }
+}
@@ -0,0 +1,44 @@
+//! Pluggable mention extraction (the NER step).
+//!
+//! The resolver starts from drug mentions; finding them in a transcript is
+//! done by an LLM in the host app or the `fuzzy-drugs-llm` crate. Anything
+//! that can turn a transcript into mentions implements [`MentionExtractor`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{DrugMention, ServiceMention};
+
+use super::ResolverResult;
+
+/// Everything an extractor found in a transcript.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExtractedMentions {
+    pub drugs: Vec<DrugMention>,
+    /// Procedures, vaccines, and diagnostics
+    pub services: Vec<ServiceMention>,
+}
+
+/// Extracts drug mentions from a transcript.
+pub trait MentionExtractor: Send + Sync {
+    /// Find the drug mentions in `transcript`, with offsets into it.
+    ///
+    /// Failures are reported as `ResolverError::Extraction`.
+    fn extract(&self, transcript: &str) -> ResolverResult<Vec<DrugMention>>;
+
+    /// Find drug and service mentions in one pass. Extractors that only
+    /// know drugs keep the default, which finds no services.
+    fn extract_all(&self, transcript: &str) -> ResolverResult<ExtractedMentions> {
+        Ok(ExtractedMentions {
+            drugs: self.extract(transcript)?,
+            services: Vec::new(),
+        })
+    }
+
+    /// Identifies the model and prompt behind this extractor's output. When
+    /// set, results are cached per transcript under it
+    /// (`Database::cache_extraction`), so it must change whenever the model
+    /// or prompt does. The default, `None`, disables caching.
+    fn version(&self) -> Option<String> {
+        None
+    }
+}
@@ -2,14 +2,36 @@
 //!
 //! Pipeline: NER Extraction → Normalization → Disambiguation → Review Queue
 
-mod normalizer;
+mod contraindications;
 mod disambiguator;
+mod dispensing;
+mod extractor;
+mod normalizer;
+mod normalizer_data;
+mod numbers;
+mod rules;
+mod services;
+mod spanish;
+mod species;
 
-pub use normalizer::*;
+pub use contraindications::*;
 pub use disambiguator::*;
+pub use dispensing::*;
+pub use extractor::{ExtractedMentions, MentionExtractor};
+pub use normalizer::*;
+pub use normalizer_data::*;
+pub use numbers::{parse_number_words, parse_spoken_number};
+pub use rules::RuleBasedExtractor;
+pub use services::{match_service, SERVICE_MATCH_THRESHOLD};
+pub use spanish::NormalizerLocale;
+pub use species::{infer_species, species_for_breed};
 
 use crate::db::Database;
-use crate::models::{DrugMention, ResolvedItem, ResolutionStatus};
+use crate::models::{
+    last_dose_date, DispositionType, DraftStatus, DrugMention, EncounterDraft, Escalation,
+    NormalizedMention, Patient, PatientAllergy, ResolutionStatus, ResolutionTrace, ResolvedItem,
+    ScoredCandidate, ScoringConfig, ServiceMention, SpeakerRole, Withdrawal,
+};
 use thiserror::Error;
 
 /// Resolver errors.
@@ -20,16 +42,29 @@ pub enum ResolverError {
 
     #[error("No candidates found for: {0}")]
     NoCandidates(String),
+
+    #[error("Extraction failed: {0}")]
+    Extraction(String),
 }
 
 pub type ResolverResult<T> = Result<T, ResolverError>;
 
+/// Default window (characters between mention starts) within which a repeated
+/// mention ("so that's 100 milligrams of carprofen") is merged into the first.
+pub const DEFAULT_DEDUP_WINDOW_CHARS: usize = 300;
+
+/// Default confidence margin within which candidates count as tied.
+pub const DEFAULT_AMBIGUITY_MARGIN: f64 = 0.02;
+
 /// Main resolver that coordinates the full pipeline.
 pub struct Resolver<'a> {
-    #[allow(dead_code)]
     db: &'a Database,
     normalizer: Normalizer,
     disambiguator: Disambiguator<'a>,
+    dedup_window_chars: usize,
+    ambiguity_margin: f64,
+    allergies: Vec<PatientAllergy>,
+    treatment_date: Option<chrono::NaiveDate>,
 }
 
 impl<'a> Resolver<'a> {
@@ -38,44 +73,391 @@ impl<'a> Resolver<'a> {
         Self {
             db,
             normalizer: Normalizer::new(),
-            disambiguator: Disambiguator::new(db),
+            disambiguator: Disambiguator::new(db, ScoringConfig::default()),
+            dedup_window_chars: DEFAULT_DEDUP_WINDOW_CHARS,
+            ambiguity_margin: DEFAULT_AMBIGUITY_MARGIN,
+            allergies: Vec::new(),
+            treatment_date: None,
+        }
+    }
+
+    /// Create a resolver with a custom normalizer (e.g., loaded from JSON data).
+    pub fn with_normalizer(db: &'a Database, normalizer: Normalizer) -> Self {
+        Self {
+            db,
+            normalizer,
+            disambiguator: Disambiguator::new(db, ScoringConfig::default()),
+            dedup_window_chars: DEFAULT_DEDUP_WINDOW_CHARS,
+            ambiguity_margin: DEFAULT_AMBIGUITY_MARGIN,
+            allergies: Vec::new(),
+            treatment_date: None,
         }
     }
 
+    /// Set the duplicate-mention window (0 merges only mentions at the same offset).
+    pub fn with_dedup_window(mut self, chars: usize) -> Self {
+        self.dedup_window_chars = chars;
+        self
+    }
+
+    /// Set the confidence margin within which candidates are reported as tied.
+    pub fn with_ambiguity_margin(mut self, margin: f64) -> Self {
+        self.ambiguity_margin = margin;
+        self
+    }
+
+    /// Check candidates against the patient's recorded allergies (see
+    /// [`Disambiguator::safety_warnings`]). [`Self::stage_transcript`] also
+    /// checks the allergies of the patient it is given.
+    pub fn with_allergies(mut self, allergies: Vec<PatientAllergy>) -> Self {
+        self.allergies = allergies;
+        self
+    }
+
+    /// Set the day the drugs were given, which withdrawal periods count
+    /// from (e.g., the draft's [`EncounterDraft::treatment_date`]). Today if
+    /// unset.
+    pub fn with_treatment_date(mut self, date: chrono::NaiveDate) -> Self {
+        self.treatment_date = Some(date);
+        self
+    }
+
+    /// Set the scoring weights and limits (e.g., a clinic's stored config).
+    pub fn with_config(mut self, config: ScoringConfig) -> Self {
+        self.disambiguator = Disambiguator::new(self.db, config);
+        self
+    }
+
     /// Resolve a drug mention to SKU candidates.
-    pub fn resolve(&self, mention: &DrugMention, patient_species: Option<&str>, patient_weight_kg: Option<f64>) -> ResolverResult<ResolvedItem> {
+    ///
+    /// `patient_breed` is used for breed-specific safety checks (e.g., MDR1).
+    /// Without `patient_species`, the species is inferred from the breed or the
+    /// mention text ("the golden retriever", "the DSH").
+    pub fn resolve(
+        &self,
+        mention: &DrugMention,
+        patient_species: Option<&str>,
+        patient_weight_kg: Option<f64>,
+        patient_breed: Option<&str>,
+    ) -> ResolverResult<ResolvedItem> {
         // Step 1: Normalize the mention
         let normalized = self.normalizer.normalize(mention);
+        let patient_species = infer_patient_species(
+            patient_species,
+            patient_breed,
+            std::slice::from_ref(mention),
+        );
+        self.resolve_normalized(
+            normalized,
+            patient_species,
+            patient_weight_kg,
+            patient_breed,
+            &self.allergies,
+        )
+    }
 
-        // Step 2: Disambiguate to find best SKU matches
-        let (top_candidate, alternatives) = self.disambiguator.disambiguate(
-            &normalized,
+    /// Resolve a drug mention and explain the result.
+    ///
+    /// The trace lists the aliases that fired, the FTS query, and every
+    /// retrieved candidate's score breakdown (including those that did not
+    /// make the cut), for a "why this match" screen.
+    pub fn resolve_with_trace(
+        &self,
+        mention: &DrugMention,
+        patient_species: Option<&str>,
+        patient_weight_kg: Option<f64>,
+        patient_breed: Option<&str>,
+    ) -> ResolverResult<(ResolvedItem, ResolutionTrace)> {
+        let normalized = self.normalizer.normalize(mention);
+        let patient_species = infer_patient_species(
+            patient_species,
+            patient_breed,
+            std::slice::from_ref(mention),
+        );
+        let search =
+            self.disambiguator
+                .trace_search(&normalized, patient_species, patient_weight_kg)?;
+        let trace = ResolutionTrace::new(
+            mention.drug_name.clone(),
+            normalized.normalized_name.clone(),
+            self.normalizer
+                .alias_hit(&mention.drug_name)
+                .into_iter()
+                .collect(),
+            search,
+            self.disambiguator.config(),
+        );
+
+        let item = self.resolve_normalized(
+            normalized,
             patient_species,
             patient_weight_kg,
+            patient_breed,
+            &self.allergies,
         )?;
+        Ok((item, trace))
+    }
+
+    /// Resolve an already-normalized mention (steps 2-6 of the pipeline).
+    fn resolve_normalized(
+        &self,
+        normalized: NormalizedMention,
+        patient_species: Option<&str>,
+        patient_weight_kg: Option<f64>,
+        patient_breed: Option<&str>,
+        allergies: &[PatientAllergy],
+    ) -> ResolverResult<ResolvedItem> {
+        let mut normalized = normalized;
+        if let Some(rate) = normalized.infusion.as_mut() {
+            rate.total_amount = rate.total_delivered(patient_weight_kg);
+        }
+
+        // Step 2: Disambiguate to find best SKU matches
+        let (mut top_candidate, mut alternatives) =
+            self.disambiguator
+                .disambiguate(&normalized, patient_species, patient_weight_kg)?;
 
-        // Step 3: Create resolved item (always pending review)
-        Ok(ResolvedItem {
+        // A mention the extractor was unsure of can't yield a sure match.
+        // Applied after the minimum-confidence cut so the item still shows
+        // up for review.
+        if let Some(extraction) = normalized.original.extraction_confidence {
+            let config = self.disambiguator.config();
+            for candidate in std::iter::once(&mut top_candidate).chain(alternatives.iter_mut()) {
+                candidate.confidence = config.blend_extraction(candidate.confidence, extraction);
+            }
+        }
+
+        // Step 3: Flag candidates that are unsafe for this patient
+        let safety_warnings = self.disambiguator.safety_warnings(
+            std::iter::once(&top_candidate).chain(alternatives.iter()),
+            patient_species,
+            patient_breed,
+            allergies,
+        )?;
+
+        // Step 4: Flag ties rather than silently picking one
+        let tied_skus = self.tied_skus(&top_candidate, &alternatives);
+
+        // Step 5: Create resolved item (always pending review)
+        let disposition = DispositionType::from_cues(&normalized.original.raw_text);
+        let withdrawal = match patient_species {
+            Some(species) => {
+                self.withdrawal(&top_candidate.sku, species, &normalized, disposition)?
+            }
+            None => None,
+        };
+        let mut item = ResolvedItem {
             mention: normalized,
             top_candidate,
             alternatives,
             status: ResolutionStatus::PendingReview,
-        })
+            controlled_confirmed_by: None,
+            safety_warnings,
+            allergy_overridden_by: None,
+            duplicate_mentions: Vec::new(),
+            tied_skus,
+            escalation: None,
+            disposition,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal,
+            override_schedule: None,
+        };
+
+        // Step 6: Flag restricted ingredients that need escalated approval
+        item.escalation = self
+            .db
+            .item_escalation_rule(&item)?
+            .map(|rule| Escalation::required(&rule));
+        if !item.safety_warnings.is_empty()
+            || !item.tied_skus.is_empty()
+            || item.escalation.is_some()
+        {
+            tracing::debug!(
+                sku = %item.top_candidate.sku,
+                safety_warnings = item.safety_warnings.len(),
+                tied = ?item.tied_skus,
+                escalation = item.escalation.is_some(),
+                "Resolved item flagged for review"
+            );
+        }
+
+        Ok(item)
+    }
+
+    /// Withdrawal for giving `sku` to a patient of `species` on the
+    /// treatment date, through the end of the mention's course (see
+    /// [`last_dose_date`]).
+    fn withdrawal(
+        &self,
+        sku: &str,
+        species: &str,
+        normalized: &NormalizedMention,
+        disposition: Option<DispositionType>,
+    ) -> ResolverResult<Option<Withdrawal>> {
+        let Some(item) = self.db.get_catalog_item(sku)? else {
+            return Ok(None);
+        };
+        let treated = self
+            .treatment_date
+            .unwrap_or_else(|| chrono::Utc::now().date_naive());
+        Ok(item.withdrawal_time(species).map(|time| {
+            let last_dose = last_dose_date(treated, normalized.taper.as_ref(), disposition);
+            Withdrawal::new(time, last_dose)
+        }))
+    }
+
+    /// SKUs within the ambiguity margin of the top candidate, top first.
+    ///
+    /// Empty unless at least one alternative ties with the top candidate.
+    fn tied_skus(&self, top: &ScoredCandidate, alternatives: &[ScoredCandidate]) -> Vec<String> {
+        let tied: Vec<String> = alternatives
+            .iter()
+            .filter(|c| top.confidence - c.confidence <= self.ambiguity_margin)
+            .map(|c| c.sku.clone())
+            .collect();
+        if tied.is_empty() {
+            return tied;
+        }
+        std::iter::once(top.sku.clone()).chain(tied).collect()
     }
 
     /// Resolve multiple mentions from a transcript.
+    ///
+    /// Repeats of the same mention (same normalized name, dose, unit, and
+    /// route within the dedup window) are merged into the first occurrence,
+    /// so one administration is not billed twice. The repeats are kept on the
+    /// item as `duplicate_mentions`, preserving their source offsets.
     pub fn resolve_all(
         &self,
         mentions: &[DrugMention],
         patient_species: Option<&str>,
         patient_weight_kg: Option<f64>,
+        patient_breed: Option<&str>,
     ) -> Vec<ResolverResult<ResolvedItem>> {
-        mentions
-            .iter()
-            .map(|m| self.resolve(m, patient_species, patient_weight_kg))
+        self.resolve_all_with_allergies(
+            mentions,
+            patient_species,
+            patient_weight_kg,
+            patient_breed,
+            &self.allergies,
+        )
+    }
+
+    /// [`Self::resolve_all`], checking `allergies`.
+    fn resolve_all_with_allergies(
+        &self,
+        mentions: &[DrugMention],
+        patient_species: Option<&str>,
+        patient_weight_kg: Option<f64>,
+        patient_breed: Option<&str>,
+        allergies: &[PatientAllergy],
+    ) -> Vec<ResolverResult<ResolvedItem>> {
+        let patient_species = infer_patient_species(patient_species, patient_breed, mentions);
+        let mut groups: Vec<(NormalizedMention, Vec<DrugMention>)> = Vec::new();
+        for mention in mentions {
+            let normalized = self.normalizer.normalize(mention);
+            let existing = groups.iter_mut().find(|(first, repeats)| {
+                let last = repeats.last().unwrap_or(&first.original);
+                is_repeat(first, &normalized)
+                    && last.start_offset.abs_diff(mention.start_offset) <= self.dedup_window_chars
+            });
+            match existing {
+                Some((_, repeats)) => repeats.push(mention.clone()),
+                None => groups.push((normalized, Vec::new())),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(normalized, repeats)| {
+                let mut item = self.resolve_normalized(
+                    normalized,
+                    patient_species,
+                    patient_weight_kg,
+                    patient_breed,
+                    allergies,
+                )?;
+                item.duplicate_mentions = repeats;
+                Ok(item)
+            })
             .collect()
     }
 
+    /// Resolve a transcript's mentions onto a draft for review.
+    ///
+    /// Replaces the draft's transcript and resolved items (manual items are
+    /// kept), infers each item's disposition from its sentence, and moves the
+    /// draft to `PendingReview`. Mentions with no catalog match are skipped
+    /// and their names returned, so the reviewer can add them by hand.
+    /// Mentions said by the owner are home medications, not orders: they go
+    /// to the draft's `reported_medications` unresolved. The patient's
+    /// recorded allergies are checked along with any set on the resolver.
+    pub fn stage_transcript(
+        &self,
+        draft: &mut EncounterDraft,
+        transcript: String,
+        mentions: &[DrugMention],
+        patient: Option<&Patient>,
+    ) -> ResolverResult<Vec<String>> {
+        let species = patient.map(Patient::canonical_species);
+        let (reported, orders): (Vec<DrugMention>, Vec<DrugMention>) = mentions
+            .iter()
+            .cloned()
+            .partition(|m| m.speaker == Some(SpeakerRole::Owner));
+        let allergies: Vec<PatientAllergy> = self
+            .allergies
+            .iter()
+            .chain(patient.into_iter().flat_map(|p| &p.allergies))
+            .cloned()
+            .collect();
+        let mut resolved_items = Vec::new();
+        let mut unmatched = Vec::new();
+        for result in self.resolve_all_with_allergies(
+            &orders,
+            species.as_deref(),
+            patient.and_then(|p| p.weight_kg),
+            patient.and_then(|p| p.breed.as_deref()),
+            &allergies,
+        ) {
+            match result {
+                Ok(item) => resolved_items.push(item),
+                Err(ResolverError::NoCandidates(name)) => unmatched.push(name),
+                Err(e) => return Err(e),
+            }
+        }
+
+        draft.transcript = transcript;
+        draft.resolved_items = resolved_items;
+        draft.reported_medications = reported;
+        draft.infer_dispositions();
+        draft.status = DraftStatus::PendingReview;
+        draft.touch();
+        Ok(unmatched)
+    }
+
+    /// Match a transcript's service mentions to the services catalog and
+    /// put them on the draft, replacing its service items. Mentions with no
+    /// match are skipped and their names returned.
+    pub fn stage_services(
+        &self,
+        draft: &mut EncounterDraft,
+        mentions: &[ServiceMention],
+    ) -> ResolverResult<Vec<String>> {
+        let services = self.db.list_service_items(true)?;
+        let mut items = Vec::new();
+        let mut unmatched = Vec::new();
+        for mention in mentions {
+            match match_service(&services, mention) {
+                Some(item) => items.push(item),
+                None => unmatched.push(mention.service_name.clone()),
+            }
+        }
+        draft.service_items = items;
+        draft.touch();
+        Ok(unmatched)
+    }
+
     /// Get the normalizer for direct access.
     pub fn normalizer(&self) -> &Normalizer {
         &self.normalizer
@@ -87,10 +469,43 @@ impl<'a> Resolver<'a> {
     }
 }
 
+/// Patient species, or one inferred from the breed, then from the mentions'
+/// extracted species and text. Mentions naming different species infer none.
+fn infer_patient_species<'s>(
+    patient_species: Option<&'s str>,
+    patient_breed: Option<&str>,
+    mentions: &[DrugMention],
+) -> Option<&'s str> {
+    patient_species
+        .or_else(|| patient_breed.and_then(species_for_breed))
+        .or_else(|| {
+            let text = mentions
+                .iter()
+                .flat_map(|m| m.species.iter().chain(std::iter::once(&m.raw_text)))
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" ");
+            infer_species(&text)
+        })
+}
+
+/// Whether two normalized mentions describe the same administration.
+fn is_repeat(a: &NormalizedMention, b: &NormalizedMention) -> bool {
+    let same_dose = match (a.normalized_dose, b.normalized_dose) {
+        (Some(x), Some(y)) => (x - y).abs() < 1e-9,
+        (None, None) => true,
+        _ => false,
+    };
+    a.normalized_name.eq_ignore_ascii_case(&b.normalized_name)
+        && same_dose
+        && a.normalized_unit == b.normalized_unit
+        && a.normalized_route == b.normalized_route
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::CatalogItem;
+    use crate::models::{assign_speakers, CatalogItem, ServiceItem, ServiceKind, SpeakerTurn};
 
     fn setup_db_with_catalog() -> Database {
         let db = Database::open_in_memory().unwrap();
@@ -102,7 +517,10 @@ mod tests {
         item1.routes = vec!["PO".into()];
         db.upsert_catalog_item(&item1).unwrap();
 
-        let mut item2 = CatalogItem::new("MELOX-15".into(), "Meloxicam 1.5mg/mL oral suspension".into());
+        let mut item2 = CatalogItem::new(
+            "MELOX-15".into(),
+            "Meloxicam 1.5mg/mL oral suspension".into(),
+        );
         item2.aliases = vec!["metacam".into()];
         item2.species = vec!["canine".into(), "feline".into()];
         item2.routes = vec!["PO".into()];
@@ -131,15 +549,92 @@ mod tests {
             species: None,
             start_offset: 5,
             end_offset: 21,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
         };
 
-        let result = resolver.resolve(&mention, Some("canine"), Some(30.0)).unwrap();
+        let result = resolver
+            .resolve(&mention, Some("canine"), Some(30.0), None)
+            .unwrap();
 
         assert_eq!(result.top_candidate.sku, "CARP-100");
         assert!(result.top_candidate.confidence > 0.5);
         assert!(matches!(result.status, ResolutionStatus::PendingReview));
     }
 
+    #[test]
+    fn test_extraction_confidence_lowers_item_confidence() {
+        let db = setup_db_with_catalog();
+        let resolver = Resolver::new(&db);
+
+        let mut mention = DrugMention {
+            raw_text: "Give rimadyl 100mg PO".into(),
+            drug_name: "rimadyl".into(),
+            dose: Some(100.0),
+            unit: Some("mg".into()),
+            route: Some("PO".into()),
+            species: None,
+            start_offset: 5,
+            end_offset: 21,
+            field_spans: Default::default(),
+            extraction_confidence: Some(1.0),
+            speaker: None,
+        };
+        let sure = resolver
+            .resolve(&mention, Some("canine"), Some(30.0), None)
+            .unwrap();
+        mention.extraction_confidence = None;
+        let unknown = resolver
+            .resolve(&mention, Some("canine"), Some(30.0), None)
+            .unwrap();
+        assert_eq!(
+            sure.top_candidate.confidence,
+            unknown.top_candidate.confidence
+        );
+
+        mention.extraction_confidence = Some(0.2);
+        let shaky = resolver
+            .resolve(&mention, Some("canine"), Some(30.0), None)
+            .unwrap();
+        assert_eq!(shaky.top_candidate.sku, "CARP-100");
+        let expected =
+            ScoringConfig::default().blend_extraction(sure.top_candidate.confidence, 0.2);
+        assert!((shaky.top_candidate.confidence - expected).abs() < 1e-12);
+        assert!(shaky.top_candidate.confidence < sure.top_candidate.confidence);
+    }
+
+    #[test]
+    fn test_resolve_with_trace() {
+        let db = setup_db_with_catalog();
+        let resolver = Resolver::new(&db);
+
+        let mention = DrugMention {
+            raw_text: "Give rimadyl 100mg PO".into(),
+            drug_name: "Rimadyl".into(),
+            dose: Some(100.0),
+            unit: Some("mg".into()),
+            route: Some("PO".into()),
+            species: None,
+            start_offset: 5,
+            end_offset: 21,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        let (item, trace) = resolver
+            .resolve_with_trace(&mention, Some("canine"), Some(30.0), None)
+            .unwrap();
+
+        assert_eq!(trace.aliases_fired.len(), 1);
+        assert_eq!(trace.aliases_fired[0].canonical, "carprofen");
+        assert_eq!(trace.search.fts_query, "carprofen*");
+        assert!(!trace.search.used_fallback);
+        assert_eq!(trace.search.candidates[0].sku, item.top_candidate.sku);
+        assert!(trace.render().contains("CARP-100"));
+    }
+
     #[test]
     fn test_resolve_normalized_alias() {
         let db = setup_db_with_catalog();
@@ -150,14 +645,19 @@ mod tests {
             raw_text: "Give ace 0.5cc IM".into(),
             drug_name: "ace".into(),
             dose: Some(0.5),
-            unit: Some("cc".into()),  // Should normalize to mL
+            unit: Some("cc".into()), // Should normalize to mL
             route: Some("IM".into()),
             species: None,
             start_offset: 5,
             end_offset: 17,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
         };
 
-        let result = resolver.resolve(&mention, Some("canine"), Some(20.0)).unwrap();
+        let result = resolver
+            .resolve(&mention, Some("canine"), Some(20.0), None)
+            .unwrap();
 
         // Should find acepromazine
         assert_eq!(result.top_candidate.sku, "ACE-10");
@@ -165,4 +665,474 @@ mod tests {
         // Unit should be normalized
         assert_eq!(result.mention.normalized_unit, Some("mL".into()));
     }
+
+    #[test]
+    fn test_resolve_flags_escalation() {
+        let db = setup_db_with_catalog();
+        db.upsert_escalation_rule(&crate::models::EscalationRule::new(
+            "carprofen",
+            "dvm-lead",
+            "Restricted NSAID",
+        ))
+        .unwrap();
+        let resolver = Resolver::new(&db);
+
+        let mention = DrugMention {
+            raw_text: "Give rimadyl 100mg PO".into(),
+            drug_name: "rimadyl".into(),
+            dose: Some(100.0),
+            unit: Some("mg".into()),
+            route: Some("PO".into()),
+            species: None,
+            start_offset: 5,
+            end_offset: 21,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        let mut result = resolver
+            .resolve(&mention, Some("canine"), Some(30.0), None)
+            .unwrap();
+        let escalation = result.escalation.clone().unwrap();
+        assert_eq!(escalation.ingredient, "carprofen");
+        assert_eq!(escalation.required_role, "dvm-lead");
+
+        result.status = ResolutionStatus::Approved;
+        assert!(result.requires_escalation_approval());
+        assert!(result.needs_review());
+    }
+
+    #[test]
+    fn test_resolve_flags_species_contraindication() {
+        let db = setup_db_with_catalog();
+        let mut item = CatalogItem::new("PERM-65".into(), "Permethrin 65% spot-on".into());
+        item.routes = vec!["topical".into()];
+        db.upsert_catalog_item(&item).unwrap();
+        let resolver = Resolver::new(&db);
+
+        let mention = DrugMention {
+            raw_text: "permethrin spot-on".into(),
+            drug_name: "permethrin".into(),
+            dose: None,
+            unit: None,
+            route: None,
+            species: None,
+            start_offset: 0,
+            end_offset: 18,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        let result = resolver
+            .resolve(&mention, Some("feline"), None, None)
+            .unwrap();
+        assert_eq!(result.top_candidate.sku, "PERM-65");
+        assert_eq!(result.safety_warnings.len(), 1);
+        assert_eq!(result.safety_warnings[0].sku, "PERM-65");
+
+        let result = resolver
+            .resolve(&mention, Some("canine"), None, None)
+            .unwrap();
+        assert!(result.safety_warnings.is_empty());
+    }
+
+    #[test]
+    fn test_withdrawal_counts_from_treatment_date() {
+        use crate::models::WithdrawalTime;
+
+        let db = setup_db_with_catalog();
+        let mut ace = db.get_catalog_item("ACE-10").unwrap().unwrap();
+        ace.withdrawal_times = vec![WithdrawalTime {
+            species: "equine".into(),
+            meat_days: Some(7),
+            milk_hours: Some(36),
+        }];
+        db.upsert_catalog_item(&ace).unwrap();
+        let treated = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let resolver = Resolver::new(&db).with_treatment_date(treated);
+
+        let mention = DrugMention {
+            raw_text: "gave ace 10mg IM".into(),
+            drug_name: "ace".into(),
+            dose: Some(10.0),
+            unit: Some("mg".into()),
+            route: Some("IM".into()),
+            species: None,
+            start_offset: 0,
+            end_offset: 16,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        let result = resolver
+            .resolve(&mention, Some("equine"), Some(450.0), None)
+            .unwrap();
+        assert_eq!(result.top_candidate.sku, "ACE-10");
+        let withdrawal = result.withdrawal.unwrap();
+        assert_eq!(withdrawal.last_dose_date, "2024-03-01");
+        assert_eq!(withdrawal.meat_end_date.as_deref(), Some("2024-03-08"));
+        assert_eq!(withdrawal.milk_end_date.as_deref(), Some("2024-03-03"));
+
+        // Not known to be a single treatment: counted from a default course
+        let unknown = DrugMention {
+            raw_text: "ace 10mg IM".into(),
+            end_offset: 11,
+            ..mention.clone()
+        };
+        let result = resolver
+            .resolve(&unknown, Some("equine"), Some(450.0), None)
+            .unwrap();
+        let withdrawal = result.withdrawal.unwrap();
+        assert_eq!(withdrawal.last_dose_date, "2024-03-14");
+        assert_eq!(withdrawal.meat_end_date.as_deref(), Some("2024-03-21"));
+
+        // Not a food animal
+        let result = resolver
+            .resolve(&mention, Some("canine"), Some(30.0), None)
+            .unwrap();
+        assert!(result.withdrawal.is_none());
+    }
+
+    #[test]
+    fn test_resolve_infers_species() {
+        let db = setup_db_with_catalog();
+        let mut item = CatalogItem::new("PERM-65".into(), "Permethrin 65% spot-on".into());
+        item.routes = vec!["topical".into()];
+        db.upsert_catalog_item(&item).unwrap();
+        let resolver = Resolver::new(&db);
+
+        let mention = DrugMention {
+            raw_text: "permethrin spot-on for the DSH".into(),
+            drug_name: "permethrin".into(),
+            dose: None,
+            unit: None,
+            route: None,
+            species: None,
+            start_offset: 0,
+            end_offset: 30,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        // Inferred from the mention text
+        let result = resolver.resolve(&mention, None, None, None).unwrap();
+        assert_eq!(result.safety_warnings.len(), 1);
+
+        // Inferred from the breed
+        let mention = DrugMention {
+            raw_text: "rimadyl 100mg PO".into(),
+            drug_name: "rimadyl".into(),
+            dose: Some(100.0),
+            unit: Some("mg".into()),
+            route: Some("PO".into()),
+            species: None,
+            start_offset: 0,
+            end_offset: 16,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+        let result = resolver
+            .resolve(&mention, None, Some(30.0), Some("Golden Retriever"))
+            .unwrap();
+        assert_eq!(result.top_candidate.score_breakdown.species_score, 1.0);
+
+        // A supplied species always wins
+        let result = resolver
+            .resolve(
+                &mention,
+                Some("feline"),
+                Some(30.0),
+                Some("Golden Retriever"),
+            )
+            .unwrap();
+        assert!(result.top_candidate.score_breakdown.species_score < 1.0);
+    }
+
+    #[test]
+    fn test_resolve_all_merges_repeated_mentions() {
+        let db = setup_db_with_catalog();
+        let resolver = Resolver::new(&db);
+
+        let mention = |raw: &str, dose: f64, start: usize| DrugMention {
+            raw_text: raw.into(),
+            drug_name: "carprofen".into(),
+            dose: Some(dose),
+            unit: Some("mg".into()),
+            route: Some("PO".into()),
+            species: None,
+            start_offset: start,
+            end_offset: start + raw.len(),
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+        let mentions = vec![
+            mention("100 milligrams of carprofen PO", 100.0, 5),
+            mention("so that's 100 milligrams of carprofen PO", 100.0, 60),
+            mention("75 milligrams of carprofen PO", 75.0, 120),
+        ];
+
+        let items: Vec<ResolvedItem> = resolver
+            .resolve_all(&mentions, Some("canine"), Some(30.0), None)
+            .into_iter()
+            .collect::<ResolverResult<_>>()
+            .unwrap();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].duplicate_mentions.len(), 1);
+        let starts: Vec<usize> = items[0]
+            .source_spans()
+            .iter()
+            .map(|s| s.start_offset)
+            .collect();
+        assert_eq!(starts, vec![5, 60]);
+        assert!(items[1].duplicate_mentions.is_empty());
+
+        // Outside the window, the repeat stays a separate item
+        let items = Resolver::new(&db).with_dedup_window(10).resolve_all(
+            &mentions,
+            Some("canine"),
+            Some(30.0),
+            None,
+        );
+        assert_eq!(items.len(), 3);
+    }
+
+    #[test]
+    fn test_tied_candidates_flagged_ambiguous() {
+        let db = Database::open_in_memory().unwrap();
+        for (sku, name) in [
+            ("CARP-75", "Carprofen 75mg tablets"),
+            ("CARP-100", "Carprofen 100mg tablets"),
+        ] {
+            let mut item = CatalogItem::new(sku.into(), name.into());
+            item.species = vec!["canine".into()];
+            item.routes = vec!["PO".into()];
+            db.upsert_catalog_item(&item).unwrap();
+        }
+
+        // No dose spoken: nothing separates the strengths
+        let mention = DrugMention {
+            raw_text: "carprofen".into(),
+            drug_name: "carprofen".into(),
+            dose: None,
+            unit: None,
+            route: Some("PO".into()),
+            species: None,
+            start_offset: 0,
+            end_offset: 9,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        let result = Resolver::new(&db)
+            .resolve(&mention, Some("canine"), None, None)
+            .unwrap();
+        assert!(result.is_ambiguous());
+        assert_eq!(result.tied_skus.len(), 2);
+        assert_eq!(result.tied_skus[0], result.top_candidate.sku);
+
+        let result = Resolver::new(&db)
+            .with_ambiguity_margin(-1.0)
+            .resolve(&mention, Some("canine"), None, None)
+            .unwrap();
+        assert!(!result.is_ambiguous());
+    }
+
+    #[test]
+    fn test_stage_transcript() {
+        let db = setup_db_with_catalog();
+        let resolver = Resolver::new(&db);
+        let transcript = "Sent home with rimadyl 100mg PO. Started zyloprim 50mg.";
+
+        let mention = |drug: &str, raw: &str| {
+            let start = transcript.find(raw).unwrap();
+            DrugMention {
+                raw_text: raw.into(),
+                drug_name: drug.into(),
+                dose: None,
+                unit: None,
+                route: None,
+                species: None,
+                start_offset: start,
+                end_offset: start + raw.len(),
+                field_spans: Default::default(),
+                extraction_confidence: None,
+                speaker: None,
+            }
+        };
+        let mentions = vec![
+            mention("rimadyl", "rimadyl 100mg PO"),
+            mention("zyloprim", "zyloprim 50mg"),
+        ];
+        let mut patient = Patient::new("Max".into(), "Canine".into());
+        patient.weight_kg = Some(30.0);
+        let mut draft = EncounterDraft::new(patient.local_id.clone());
+        draft.add_manual_item("LRS-1L".into(), "LRS 1L".into(), 1.0, "bag".into(), None);
+
+        let unmatched = resolver
+            .stage_transcript(&mut draft, transcript.into(), &mentions, Some(&patient))
+            .unwrap();
+
+        assert_eq!(unmatched, vec!["zyloprim".to_string()]);
+        assert_eq!(draft.transcript, transcript);
+        assert_eq!(draft.status, DraftStatus::PendingReview);
+        assert_eq!(draft.resolved_items.len(), 1);
+        assert_eq!(draft.resolved_items[0].top_candidate.sku, "CARP-100");
+        assert_eq!(
+            draft.resolved_items[0].disposition,
+            Some(DispositionType::Dispensed)
+        );
+        assert_eq!(draft.manual_items.len(), 1);
+        assert!(draft.reported_medications.is_empty());
+    }
+
+    #[test]
+    fn test_patient_allergy_blocks_matching_item() {
+        let db = setup_db_with_catalog();
+        let transcript = "Sent home with rimadyl 100mg PO.";
+        let mention = DrugMention {
+            raw_text: "rimadyl 100mg PO".into(),
+            drug_name: "rimadyl".into(),
+            dose: None,
+            unit: None,
+            route: None,
+            species: None,
+            start_offset: 15,
+            end_offset: 31,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+        let mut patient = Patient::new("Max".into(), "canine".into());
+        patient.allergies = vec![PatientAllergy {
+            substance: "Carprofen".into(),
+            reaction: Some("vomiting".into()),
+        }];
+        let mut draft = EncounterDraft::new(patient.local_id.clone());
+        Resolver::new(&db)
+            .stage_transcript(
+                &mut draft,
+                transcript.into(),
+                std::slice::from_ref(&mention),
+                Some(&patient),
+            )
+            .unwrap();
+
+        let item = &mut draft.resolved_items[0];
+        let warning = item.blocking_warnings().next().unwrap();
+        assert_eq!(warning.drug, "carprofen");
+        assert_eq!(
+            warning.message(),
+            "carprofen: recorded patient allergy (vomiting)"
+        );
+        item.review(ResolutionStatus::Approved);
+        assert!(item.requires_allergy_override());
+        assert!(item.needs_review());
+        item.override_allergy("Dr. Smith".into());
+        assert!(!item.needs_review());
+
+        // Set on the resolver directly
+        let resolver = Resolver::new(&db).with_allergies(patient.allergies.clone());
+        let item = resolver
+            .resolve(&mention, Some("canine"), None, None)
+            .unwrap();
+        assert!(item.safety_warnings.iter().any(|w| w.blocking));
+        let item = Resolver::new(&db)
+            .resolve(&mention, Some("canine"), None, None)
+            .unwrap();
+        assert!(item.safety_warnings.is_empty());
+    }
+
+    #[test]
+    fn test_stage_transcript_keeps_owner_mentions_apart() {
+        let db = setup_db_with_catalog();
+        let resolver = Resolver::new(&db);
+        let transcript = "She's on metacam at home. Let's give rimadyl 100mg PO.";
+        let turns = [
+            SpeakerTurn {
+                speaker: "SPEAKER_01".into(),
+                role: Some(SpeakerRole::Owner),
+                start_offset: 0,
+                end_offset: 26,
+            },
+            SpeakerTurn {
+                speaker: "SPEAKER_00".into(),
+                role: Some(SpeakerRole::Clinician),
+                start_offset: 26,
+                end_offset: transcript.len(),
+            },
+        ];
+        let mut mentions: Vec<DrugMention> = ["metacam", "rimadyl"]
+            .into_iter()
+            .map(|drug| {
+                let start = transcript.find(drug).unwrap();
+                DrugMention {
+                    raw_text: drug.into(),
+                    drug_name: drug.into(),
+                    dose: None,
+                    unit: None,
+                    route: None,
+                    species: None,
+                    start_offset: start,
+                    end_offset: start + drug.len(),
+                    field_spans: Default::default(),
+                    extraction_confidence: None,
+                    speaker: None,
+                }
+            })
+            .collect();
+        assign_speakers(&mut mentions, &turns);
+
+        let patient = Patient::new("Max".into(), "Canine".into());
+        let mut draft = EncounterDraft::new(patient.local_id.clone());
+        resolver
+            .stage_transcript(&mut draft, transcript.into(), &mentions, Some(&patient))
+            .unwrap();
+
+        assert_eq!(draft.resolved_items.len(), 1);
+        assert_eq!(draft.resolved_items[0].top_candidate.sku, "CARP-100");
+        assert_eq!(draft.reported_medications.len(), 1);
+        assert_eq!(draft.reported_medications[0].drug_name, "metacam");
+        assert_eq!(
+            draft.reported_medications[0].speaker,
+            Some(SpeakerRole::Owner)
+        );
+    }
+
+    #[test]
+    fn test_stage_services() {
+        let db = setup_db_with_catalog();
+        db.upsert_service_item(&ServiceItem::new(
+            "PROC-NAIL".into(),
+            "Nail Trim".into(),
+            ServiceKind::Procedure,
+        ))
+        .unwrap();
+        let resolver = Resolver::new(&db);
+        let mention = |name: &str| ServiceMention {
+            raw_text: name.into(),
+            service_name: name.into(),
+            kind: None,
+            quantity: None,
+            start_offset: 0,
+            end_offset: name.len(),
+        };
+
+        let mut draft = EncounterDraft::new("patient".into());
+        let unmatched = resolver
+            .stage_services(&mut draft, &[mention("nail trim"), mention("ear flush")])
+            .unwrap();
+
+        assert_eq!(unmatched, vec!["ear flush".to_string()]);
+        assert_eq!(draft.service_items.len(), 1);
+        assert_eq!(draft.service_items[0].code, "PROC-NAIL");
+        assert_eq!(draft.service_items[0].quantity, 1.0);
+    }
 }
@@ -4,19 +4,37 @@
 //! - Unit conversion (cc→mL, mcg→mg, etc.)
 //! - Alias expansion (ace→acepromazine, metacam→meloxicam)
 //! - Route canonicalization (orally→PO, subcutaneously→SQ)
+//! - Patient weight normalization (lbs→kg)
+//! - Weight-based doses (500 mcg/kg → 0.5 mg/kg)
+//! - Infusion rates (3 mcg/kg/hr for 6 hours → 0.003 mg/kg/hr over 6 h)
+//! - Spanish dictation when the locale is Spanish (see [`super::spanish`])
+//! - Transcript offsets of each field, for highlighting
 
 use std::collections::HashMap;
 
-use crate::models::{DrugMention, NormalizedMention};
+use crate::models::{
+    AliasHit, DosePhase, DrugMention, FieldSpans, InfusionRate, NormalizedMention, Route,
+    SourceSpan, TaperSchedule, Unit, WeightUnit,
+};
+
+use super::dispensing::doses_per_day;
+use super::numbers;
+use super::spanish::{self, NormalizerLocale};
+use super::NormalizerDataInfo;
 
 /// Normalizer for drug mentions.
+#[derive(Debug, Clone)]
 pub struct Normalizer {
     /// Alias map: spoken name → canonical name
-    aliases: HashMap<String, String>,
+    pub(super) aliases: HashMap<String, String>,
     /// Unit conversions: non-standard → standard
-    unit_conversions: HashMap<String, (String, f64)>, // (canonical_unit, multiplier)
+    pub(super) unit_conversions: HashMap<String, (String, f64)>, // (canonical_unit, multiplier)
     /// Route canonicalization: spoken → standard abbreviation
-    route_map: HashMap<String, String>,
+    pub(super) route_map: HashMap<String, String>,
+    /// Version and checksum of the loaded data
+    pub(super) data_info: NormalizerDataInfo,
+    /// Dictation language
+    pub(super) locale: NormalizerLocale,
 }
 
 impl Default for Normalizer {
@@ -32,7 +50,58 @@ impl Normalizer {
             aliases: Self::default_aliases(),
             unit_conversions: Self::default_unit_conversions(),
             route_map: Self::default_routes(),
+            data_info: NormalizerDataInfo::builtin(),
+            locale: NormalizerLocale::English,
+        }
+    }
+
+    /// Create a normalizer with no mappings (for fully file-driven data).
+    pub(super) fn empty() -> Self {
+        Self {
+            aliases: HashMap::new(),
+            unit_conversions: HashMap::new(),
+            route_map: HashMap::new(),
+            data_info: NormalizerDataInfo::builtin(),
+            locale: NormalizerLocale::English,
+        }
+    }
+
+    /// Create a normalizer for a dictation language.
+    pub fn for_locale(locale: NormalizerLocale) -> Self {
+        let mut normalizer = Self::new();
+        normalizer.set_locale(locale);
+        normalizer
+    }
+
+    /// Dictation language.
+    pub fn locale(&self) -> NormalizerLocale {
+        self.locale
+    }
+
+    /// Switch the dictation language.
+    ///
+    /// Spanish layers Spanish names, unit words, and route phrases over the
+    /// current tables (English terms keep working for bilingual clinics) and
+    /// enables spoken-number doses. Switching back to English disables
+    /// spoken-number parsing but leaves the added entries in place.
+    pub fn set_locale(&mut self, locale: NormalizerLocale) {
+        if locale == NormalizerLocale::Spanish {
+            for (alias, canonical) in spanish::spanish_aliases() {
+                self.aliases.entry(alias).or_insert(canonical);
+            }
+            for (unit, conversion) in spanish::spanish_unit_conversions() {
+                self.unit_conversions.entry(unit).or_insert(conversion);
+            }
+            for (spoken, canonical) in spanish::spanish_routes() {
+                self.route_map.entry(spoken).or_insert(canonical);
+            }
         }
+        self.locale = locale;
+    }
+
+    /// Version and checksum of the active alias/unit/route data.
+    pub fn data_info(&self) -> &NormalizerDataInfo {
+        &self.data_info
     }
 
     /// Normalize a drug mention.
@@ -40,35 +109,314 @@ impl Normalizer {
         // Normalize drug name via alias expansion
         let normalized_name = self.expand_alias(&mention.drug_name);
 
+        // Dictation often reaches us with the dose still in words ("two point
+        // five mils", "half a tablet", "diez miligramos"), and Spanish
+        // dictation with the route only in the text
+        let (dose, unit) = match mention.dose {
+            None => match self.spoken_dose(&mention.raw_text) {
+                Some((dose, unit)) => (Some(dose), Some(unit)),
+                None => (None, mention.unit.clone()),
+            },
+            dose => (dose, mention.unit.clone()),
+        };
+        let route = match (&mention.route, self.locale) {
+            (None, NormalizerLocale::Spanish) => spoken_route(&mention.raw_text),
+            _ => mention.route.clone(),
+        };
+
+        // Infusion rates carry the rate as the dose and a compound unit
+        let infusion = match (&unit, dose) {
+            (Some(unit), Some(dose)) => self.parse_rate_unit(unit, dose).map(|rate| InfusionRate {
+                duration_hours: parse_duration_hours(&mention.raw_text),
+                ..rate
+            }),
+            _ => None,
+        };
+
         // Normalize unit and convert dose
-        let (normalized_unit, normalized_dose) = if let (Some(unit), Some(dose)) =
-            (&mention.unit, mention.dose)
-        {
-            let (canonical_unit, multiplier) = self.convert_unit(unit);
+        let (normalized_unit, normalized_dose) = if let Some(rate) = &infusion {
+            (Some(rate.rate_unit()), Some(rate.rate_per_hour))
+        } else if let (Some(unit), Some(dose)) = (&unit, dose) {
+            let (canonical_unit, multiplier) = self.convert_compound_unit(unit);
             (Some(canonical_unit), Some(dose * multiplier))
         } else {
-            (mention.unit.clone(), mention.dose)
+            (unit, dose)
         };
 
         // Normalize route
-        let normalized_route = mention.route.as_ref().map(|r| self.canonicalize_route(r));
+        let normalized_route = route.as_ref().map(|r| self.canonicalize_route(r));
 
         NormalizedMention {
             original: mention.clone(),
             normalized_name,
             normalized_dose,
-            normalized_unit,
+            normalized_unit: normalized_unit.map(Unit::from),
             normalized_route,
+            infusion,
+            taper: self.parse_taper(&mention.raw_text),
+        }
+    }
+
+    /// Find a spoken dose ("two point five mils", "half a tablet", "diez
+    /// miligramos") in dictated text.
+    ///
+    /// Returns the value and the unit word as spoken.
+    fn spoken_dose(&self, text: &str) -> Option<(f64, String)> {
+        let cleaned = clean_words(text);
+        let words: Vec<&str> = cleaned.split_whitespace().collect();
+
+        for start in 0..words.len() {
+            let Some((value, consumed)) = numbers::parse_number(self.locale, &words[start..])
+            else {
+                continue;
+            };
+            let mut rest = &words[start + consumed..];
+            while let [filler, tail @ ..] = rest {
+                if !numbers::FILLER_WORDS.contains(filler) {
+                    break;
+                }
+                rest = tail;
+            }
+            // Two-word units first ("centímetros cúbicos")
+            for len in [2, 1] {
+                if rest.len() < len {
+                    continue;
+                }
+                let unit = rest[..len].join(" ");
+                if self.unit_conversions.contains_key(&unit) {
+                    return Some((value, unit));
+                }
+            }
+        }
+        None
+    }
+
+    /// Parse a rate unit ("mcg/kg/hr", "mL/hr", "mg/kg/min") into an hourly
+    /// infusion rate in canonical amount units.
+    ///
+    /// Returns `None` for ordinary (non-rate) units.
+    pub fn parse_rate_unit(&self, unit: &str, dose: f64) -> Option<InfusionRate> {
+        let unit = compound_unit_text(unit);
+        let parts: Vec<&str> = unit.split('/').map(str::trim).collect();
+        let (amount, per_kg, time) = match parts.as_slice() {
+            [amount, kg, time] if kg.eq_ignore_ascii_case("kg") => (*amount, true, *time),
+            [amount, time] => (*amount, false, *time),
+            _ => return None,
+        };
+        let hours = time_unit_hours(time)?;
+        let (canonical_unit, multiplier) = self.convert_unit(amount);
+
+        Some(InfusionRate {
+            rate_per_hour: dose * multiplier / hours,
+            unit: canonical_unit,
+            per_kg,
+            duration_hours: None,
+            total_amount: None,
+        })
+    }
+
+    /// Convert a unit, including weight-based units ("mcg/kg" → "mg/kg"), to
+    /// canonical form with multiplier.
+    pub fn convert_compound_unit(&self, unit: &str) -> (String, f64) {
+        let compound = compound_unit_text(unit);
+        let parts: Vec<&str> = compound.split('/').map(str::trim).collect();
+        match parts.as_slice() {
+            [amount, kg] if kg.eq_ignore_ascii_case("kg") => {
+                let (canonical_unit, multiplier) = self.convert_unit(amount);
+                (format!("{}/kg", canonical_unit), multiplier)
+            }
+            _ => self.convert_unit(unit),
+        }
+    }
+
+    /// Split a tapering dictation ("20mg BID for 5 days, then 10mg SID for 5
+    /// days") into ordered phases with canonical dose units.
+    ///
+    /// Phases are separated by "then". Returns `None` unless there are at
+    /// least two phases and each has a dose followed by a duration. Phases
+    /// after the first must open with their dose (after a connective such as
+    /// "taper to"), so "then" moving on to another drug or an instruction is
+    /// not a taper. A phase without a frequency keeps the previous phase's.
+    pub fn parse_taper(&self, text: &str) -> Option<TaperSchedule> {
+        let words: Vec<&str> = text
+            .split_whitespace()
+            .map(|w| w.trim_matches([',', ';']).trim_end_matches('.'))
+            .filter(|w| !w.is_empty())
+            .collect();
+        let segments: Vec<&[&str]> = words
+            .split(|w| w.eq_ignore_ascii_case("then"))
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        if segments.len() < 2 {
+            return None;
+        }
+
+        let mut phases = Vec::new();
+        let mut frequency: Option<(String, f64)> = None;
+        for (n, segment) in segments.iter().enumerate() {
+            let (at, dose, unit) = self.phase_dose(segment)?;
+            let continues = segment[..at]
+                .iter()
+                .all(|w| PHASE_CONNECTIVES.contains(&w.to_lowercase().as_str()));
+            if n > 0 && !continues {
+                return None;
+            }
+            if let Some(found) = phase_frequency(segment) {
+                frequency = Some(found);
+            }
+            let (frequency, doses_per_day) = frequency.clone()?;
+            let days = parse_duration_hours(&segment[at..].join(" "))? / 24.0;
+            phases.push(DosePhase {
+                dose,
+                unit,
+                frequency,
+                doses_per_day,
+                days,
+            });
+        }
+        Some(TaperSchedule { phases })
+    }
+
+    /// First dose ("20mg", "10 mg", "1.5 tablets") in a taper phase, in
+    /// canonical units, with the index of the word it starts at.
+    fn phase_dose(&self, words: &[&str]) -> Option<(usize, f64, String)> {
+        for (i, word) in words.iter().enumerate() {
+            let split = word
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(word.len());
+            let (number, unit) = word.split_at(split);
+            let Ok(dose) = number.parse::<f64>() else {
+                continue;
+            };
+            let unit = match unit {
+                "" => words.get(i + 1).copied().unwrap_or_default(),
+                unit => unit,
+            };
+            if self.unit_conversions.contains_key(&unit.to_lowercase()) {
+                let (canonical_unit, multiplier) = self.convert_unit(unit);
+                return Some((i, dose * multiplier, canonical_unit));
+            }
+        }
+        None
+    }
+
+    /// Locate the drug, dose, unit, route, and frequency of a mention in the
+    /// transcript, for highlighting the tokens that were interpreted.
+    ///
+    /// Spans supplied by NER are kept only if they fall inside the mention
+    /// and their text reads as the field; the rest are found by searching the
+    /// mention's text. If the mention's offsets don't point at its raw text,
+    /// the raw text is looked up in the transcript instead.
+    pub fn field_spans(&self, transcript: &str, mention: &DrugMention) -> FieldSpans {
+        let Some(window) = mention_window(transcript, mention) else {
+            return FieldSpans::default();
+        };
+        let keep = |span: Option<SourceSpan>, reads_as: &dyn Fn(&str) -> bool| {
+            span.filter(|s| s.start_offset < s.end_offset && window.contains(s))
+                .filter(|s| s.text(transcript).is_some_and(reads_as))
+        };
+        let words = word_spans(transcript, window);
+        let given = &mention.field_spans;
+
+        // Same fallbacks as `normalize`: spoken doses and Spanish routes
+        let (dose, unit) = match mention.dose {
+            None => match self.spoken_dose(&mention.raw_text) {
+                Some((dose, unit)) => (Some(dose), Some(unit)),
+                None => (None, mention.unit.clone()),
+            },
+            dose => (dose, mention.unit.clone()),
+        };
+        let route = match (&mention.route, self.locale) {
+            (None, NormalizerLocale::Spanish) => spoken_route(&mention.raw_text),
+            _ => mention.route.clone(),
+        };
+
+        let drug = keep(given.drug, &|t| same_words(t, &mention.drug_name))
+            .or_else(|| find_phrase(transcript, window, &mention.drug_name));
+        let dose = dose.and_then(|dose| {
+            keep(given.dose, &|t| self.reads_as_number(t, dose))
+                .or_else(|| self.find_number(&words, dose))
+        });
+        // The unit normally follows the dose ("5 mg of ... mg/kg")
+        let after_dose = SourceSpan {
+            start_offset: dose.map_or(window.start_offset, |d| d.end_offset),
+            end_offset: window.end_offset,
+        };
+        let unit = unit.as_deref().and_then(|unit| {
+            keep(given.unit, &|t| same_words(t, unit))
+                .or_else(|| find_phrase(transcript, after_dose, unit))
+                .or_else(|| find_phrase(transcript, window, unit))
+        });
+        let route = route.as_deref().and_then(|route| {
+            keep(given.route, &|t| same_words(t, route))
+                .or_else(|| find_phrase(transcript, window, route))
+        });
+        let frequency = keep(given.frequency, &|t| doses_per_day(t).is_some())
+            .or_else(|| find_frequency(&words));
+
+        FieldSpans {
+            drug,
+            dose,
+            unit,
+            route,
+            frequency,
         }
     }
 
+    /// Whether `text` is exactly a number equal to `value` ("100", "two
+    /// point five").
+    fn reads_as_number(&self, text: &str, value: f64) -> bool {
+        let cleaned = clean_words(text);
+        let words: Vec<&str> = cleaned.split_whitespace().collect();
+        matches!(
+            numbers::parse_number(self.locale, &words),
+            Some((n, consumed)) if consumed == words.len() && (n - value).abs() < 1e-9
+        )
+    }
+
+    /// Span of the first number equal to `value` among `words`.
+    fn find_number(&self, words: &[(String, SourceSpan)], value: f64) -> Option<SourceSpan> {
+        // "100mg" reads as its leading number
+        let readings: Vec<(&str, SourceSpan)> = words
+            .iter()
+            .map(|(word, span)| {
+                let digits = word
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(word.len());
+                if digits == 0 {
+                    return (word.as_str(), *span);
+                }
+                let number = SourceSpan {
+                    start_offset: span.start_offset,
+                    end_offset: span.start_offset + digits,
+                };
+                (&word[..digits], number)
+            })
+            .collect();
+        let texts: Vec<&str> = readings.iter().map(|(word, _)| *word).collect();
+        (0..texts.len()).find_map(|start| {
+            let (n, consumed) = numbers::parse_number(self.locale, &texts[start..])?;
+            ((n - value).abs() < 1e-9).then(|| SourceSpan {
+                start_offset: readings[start].1.start_offset,
+                end_offset: readings[start + consumed - 1].1.end_offset,
+            })
+        })
+    }
+
     /// Expand a drug alias to its canonical name.
     pub fn expand_alias(&self, name: &str) -> String {
         let lower = name.to_lowercase();
-        self.aliases
-            .get(&lower)
-            .cloned()
-            .unwrap_or_else(|| lower)
+        self.aliases.get(&lower).cloned().unwrap_or(lower)
+    }
+
+    /// The alias mapping applied to a name, if any rewrote it.
+    pub fn alias_hit(&self, name: &str) -> Option<AliasHit> {
+        let lower = name.to_lowercase();
+        let canonical = self.aliases.get(&lower)?;
+        (*canonical != lower).then(|| AliasHit {
+            spoken: lower.clone(),
+            canonical: canonical.clone(),
+        })
     }
 
     /// Convert a unit to canonical form with multiplier.
@@ -77,16 +425,29 @@ impl Normalizer {
         self.unit_conversions
             .get(&lower)
             .cloned()
-            .unwrap_or_else(|| (lower, 1.0))
+            .unwrap_or((lower, 1.0))
     }
 
     /// Canonicalize a route of administration.
+    ///
+    /// Falls back to [`Route`]'s synonyms, then the uppercased route.
     pub fn canonicalize_route(&self, route: &str) -> String {
         let lower = route.to_lowercase();
         self.route_map
             .get(&lower)
             .cloned()
-            .unwrap_or_else(|| route.to_uppercase())
+            .unwrap_or_else(|| Route::parse(route).to_string())
+    }
+
+    /// Normalize a patient weight to kg.
+    ///
+    /// A missing unit is treated as kg. Returns `None` for unrecognized units
+    /// rather than guessing, since a wrong weight skews dose plausibility.
+    pub fn normalize_weight_kg(&self, weight: f64, unit: Option<&str>) -> Option<f64> {
+        match unit {
+            None => Some(weight),
+            Some(u) => WeightUnit::parse(u).map(|wu| wu.to_kg(weight)),
+        }
     }
 
     /// Add a custom alias mapping.
@@ -108,7 +469,7 @@ impl Normalizer {
     }
 
     /// Default drug alias mappings.
-    fn default_aliases() -> HashMap<String, String> {
+    pub(super) fn default_aliases() -> HashMap<String, String> {
         let mut map = HashMap::new();
 
         // NSAIDs
@@ -206,17 +567,24 @@ impl Normalizer {
     }
 
     /// Default unit conversions.
-    fn default_unit_conversions() -> HashMap<String, (String, f64)> {
+    pub(super) fn default_unit_conversions() -> HashMap<String, (String, f64)> {
         let mut map = HashMap::new();
 
         // Volume
         map.insert("cc".into(), ("mL".into(), 1.0));
         map.insert("ml".into(), ("mL".into(), 1.0));
+        map.insert("mil".into(), ("mL".into(), 1.0));
+        map.insert("mils".into(), ("mL".into(), 1.0));
+        map.insert("milliliter".into(), ("mL".into(), 1.0));
+        map.insert("milliliters".into(), ("mL".into(), 1.0));
         map.insert("l".into(), ("mL".into(), 1000.0));
         map.insert("liter".into(), ("mL".into(), 1000.0));
         map.insert("liters".into(), ("mL".into(), 1000.0));
 
         // Mass
+        map.insert("mg".into(), ("mg".into(), 1.0));
+        map.insert("milligram".into(), ("mg".into(), 1.0));
+        map.insert("milligrams".into(), ("mg".into(), 1.0));
         map.insert("mcg".into(), ("mg".into(), 0.001));
         map.insert("microgram".into(), ("mg".into(), 0.001));
         map.insert("micrograms".into(), ("mg".into(), 0.001));
@@ -228,21 +596,24 @@ impl Normalizer {
 
         // Units (keep as-is but standardize)
         map.insert("unit".into(), ("units".into(), 1.0));
+        map.insert("units".into(), ("units".into(), 1.0));
         map.insert("iu".into(), ("IU".into(), 1.0));
 
         // Tablets/capsules
         map.insert("tab".into(), ("tablets".into(), 1.0));
         map.insert("tabs".into(), ("tablets".into(), 1.0));
         map.insert("tablet".into(), ("tablets".into(), 1.0));
+        map.insert("tablets".into(), ("tablets".into(), 1.0));
         map.insert("cap".into(), ("capsules".into(), 1.0));
         map.insert("caps".into(), ("capsules".into(), 1.0));
         map.insert("capsule".into(), ("capsules".into(), 1.0));
+        map.insert("capsules".into(), ("capsules".into(), 1.0));
 
         map
     }
 
     /// Default route mappings.
-    fn default_routes() -> HashMap<String, String> {
+    pub(super) fn default_routes() -> HashMap<String, String> {
         let mut map = HashMap::new();
 
         // Oral
@@ -312,9 +683,238 @@ impl Normalizer {
     }
 }
 
+/// Lowercase text with punctuation replaced by spaces.
+fn clean_words(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .map(|w| w.trim_end_matches('.'))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Where a mention sits in the transcript: its offsets if they cover its raw
+/// text, else the first occurrence of the raw text, else its offsets if they
+/// are at least a valid range.
+fn mention_window(transcript: &str, mention: &DrugMention) -> Option<SourceSpan> {
+    let span = mention.span();
+    let raw = mention.raw_text.as_str();
+    if !raw.is_empty() {
+        if span.text(transcript) == Some(raw) {
+            return Some(span);
+        }
+        if let Some(start) = transcript.find(raw) {
+            return Some(SourceSpan {
+                start_offset: start,
+                end_offset: start + raw.len(),
+            });
+        }
+    }
+    (span.start_offset < span.end_offset && span.text(transcript).is_some()).then_some(span)
+}
+
+/// Lowercase words of the transcript within `window`, with their spans.
+/// A '.' only joins digits ("0.5"), so "b.i.d." gives "b", "i", "d".
+fn word_spans(transcript: &str, window: SourceSpan) -> Vec<(String, SourceSpan)> {
+    let Some(text) = window.text(transcript) else {
+        return Vec::new();
+    };
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let in_word = |i: usize| {
+        let (_, c) = chars[i];
+        c.is_alphanumeric()
+            || (c == '.'
+                && i > 0
+                && chars[i - 1].1.is_ascii_digit()
+                && chars.get(i + 1).is_some_and(|(_, n)| n.is_ascii_digit()))
+    };
+
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !in_word(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && in_word(i) {
+            i += 1;
+        }
+        let begin = chars[start].0;
+        let end = chars.get(i).map_or(text.len(), |(offset, _)| *offset);
+        words.push((
+            text[begin..end].to_lowercase(),
+            SourceSpan {
+                start_offset: window.start_offset + begin,
+                end_offset: window.start_offset + end,
+            },
+        ));
+    }
+    words
+}
+
+/// Span of the first occurrence of `phrase` within `window`, ignoring ASCII
+/// case and not inside a longer word ("ace" doesn't match "acepromazine").
+fn find_phrase(transcript: &str, window: SourceSpan, phrase: &str) -> Option<SourceSpan> {
+    let text = window.text(transcript)?;
+    let needle = phrase.trim().to_ascii_lowercase();
+    if needle.is_empty() {
+        return None;
+    }
+    // ASCII lowercasing keeps byte offsets unchanged
+    let haystack = text.to_ascii_lowercase();
+    haystack.match_indices(&needle).find_map(|(i, _)| {
+        let end = i + needle.len();
+        let before = haystack[..i].chars().next_back();
+        let after = haystack[end..].chars().next();
+        (!before.is_some_and(char::is_alphabetic) && !after.is_some_and(char::is_alphabetic))
+            .then_some(SourceSpan {
+                start_offset: window.start_offset + i,
+                end_offset: window.start_offset + end,
+            })
+    })
+}
+
+/// Span of the first frequency ("BID", "twice daily", "b.i.d.") among `words`.
+fn find_frequency(words: &[(String, SourceSpan)]) -> Option<SourceSpan> {
+    (0..words.len()).find_map(|start| {
+        (1..=3.min(words.len() - start)).rev().find_map(|len| {
+            let phrase: Vec<&str> = words[start..start + len]
+                .iter()
+                .map(|(word, _)| word.as_str())
+                .collect();
+            doses_per_day(&phrase.join(" ")).map(|_| SourceSpan {
+                start_offset: words[start].1.start_offset,
+                end_offset: words[start + len - 1].1.end_offset,
+            })
+        })
+    })
+}
+
+/// Whether two phrases have the same words, ignoring case and spacing.
+fn same_words(a: &str, b: &str) -> bool {
+    a.split_whitespace()
+        .map(str::to_lowercase)
+        .eq(b.split_whitespace().map(str::to_lowercase))
+}
+
+/// Find the longest Spanish route phrase in dictated text.
+fn spoken_route(text: &str) -> Option<String> {
+    let padded = format!(" {} ", clean_words(text));
+    spanish::spanish_routes()
+        .into_keys()
+        .filter(|phrase| padded.contains(&format!(" {} ", phrase)))
+        .max_by_key(|phrase| phrase.chars().count())
+}
+
+/// Spell a compound unit with slashes ("mcg per kg per hour" → "mcg/kg/hour",
+/// "mL / hr" → "mL/hr").
+fn compound_unit_text(unit: &str) -> String {
+    unit.split_whitespace()
+        .map(|w| {
+            if w.eq_ignore_ascii_case("per") {
+                "/"
+            } else {
+                w
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split('/')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Words that may lead a later taper phase before its dose ("and taper to
+/// 10mg").
+const PHASE_CONNECTIVES: &[&str] = &[
+    "and",
+    "taper",
+    "tapering",
+    "reduce",
+    "reducing",
+    "decrease",
+    "decreasing",
+    "drop",
+    "down",
+    "to",
+    "give",
+    "at",
+];
+
+/// Length of a time unit in hours.
+fn time_unit_hours(unit: &str) -> Option<f64> {
+    match unit.to_lowercase().as_str() {
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(1.0),
+        "min" | "mins" | "minute" | "minutes" => Some(1.0 / 60.0),
+        "d" | "day" | "days" => Some(24.0),
+        _ => None,
+    }
+}
+
+/// Frequency ("BID", "q12h", "twice daily") in a taper phase, as dictated,
+/// with its doses per day.
+fn phase_frequency(words: &[&str]) -> Option<(String, f64)> {
+    (0..words.len()).find_map(|start| {
+        (1..=3.min(words.len() - start)).rev().find_map(|len| {
+            let phrase = words[start..start + len].join(" ");
+            doses_per_day(&phrase).map(|per_day| (phrase, per_day))
+        })
+    })
+}
+
+/// Find an infusion duration in dictated text ("for 6 hours", "over 30 min",
+/// "for 12h", "for six hours", "over half an hour").
+fn parse_duration_hours(text: &str) -> Option<f64> {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+
+    for (i, word) in words.iter().enumerate() {
+        if *word != "for" && *word != "over" {
+            continue;
+        }
+        let Some(next) = words.get(i + 1) else {
+            continue;
+        };
+
+        // "for 6 hours", "over half an hour"
+        if let Some((value, consumed)) = numbers::parse_number_words(&words[i + 1..]) {
+            if let Some(hours) = words[i + 1 + consumed..]
+                .iter()
+                .find(|w| !numbers::FILLER_WORDS.contains(w))
+                .and_then(|u| time_unit_hours(u.trim_end_matches(['.', ','])))
+            {
+                return Some(value * hours);
+            }
+            continue;
+        }
+
+        // "for 6h", "over 30min"
+        let token = next.trim_end_matches(['.', ',']);
+        let split = token
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(token.len());
+        let (number, unit) = token.split_at(split);
+        if let (Ok(value), Some(hours)) = (number.parse::<f64>(), time_unit_hours(unit)) {
+            return Some(value * hours);
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::{DoseExpression, DoseUnit};
 
     #[test]
     fn test_expand_alias() {
@@ -328,6 +928,12 @@ mod tests {
 
         // Unknown names pass through lowercase
         assert_eq!(normalizer.expand_alias("SomeNewDrug"), "somenewdrug");
+
+        assert_eq!(
+            normalizer.alias_hit("Rimadyl").unwrap().canonical,
+            "carprofen"
+        );
+        assert!(normalizer.alias_hit("digoxin").is_none());
     }
 
     #[test]
@@ -379,6 +985,9 @@ mod tests {
             species: None,
             start_offset: 0,
             end_offset: 25,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
         };
 
         let normalized = normalizer.normalize(&mention);
@@ -402,6 +1011,9 @@ mod tests {
             species: None,
             start_offset: 0,
             end_offset: 17,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
         };
 
         let normalized = normalizer.normalize(&mention);
@@ -425,6 +1037,9 @@ mod tests {
             species: None,
             start_offset: 0,
             end_offset: 11,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
         };
 
         let normalized = normalizer.normalize(&mention);
@@ -433,6 +1048,18 @@ mod tests {
         assert_eq!(normalized.normalized_unit, Some("mg".into()));
     }
 
+    #[test]
+    fn test_normalize_weight() {
+        let normalizer = Normalizer::new();
+
+        let kg = normalizer.normalize_weight_kg(60.0, Some("lbs")).unwrap();
+        assert!((kg - 27.2155).abs() < 0.001);
+
+        assert_eq!(normalizer.normalize_weight_kg(30.0, Some("kg")), Some(30.0));
+        assert_eq!(normalizer.normalize_weight_kg(30.0, None), Some(30.0));
+        assert_eq!(normalizer.normalize_weight_kg(30.0, Some("furlongs")), None);
+    }
+
     #[test]
     fn test_custom_alias() {
         let mut normalizer = Normalizer::new();
@@ -440,4 +1067,270 @@ mod tests {
 
         assert_eq!(normalizer.expand_alias("customdrug"), "realdrugname");
     }
+
+    #[test]
+    fn test_weight_based_dose() {
+        let normalizer = Normalizer::new();
+        let mention = DrugMention {
+            drug_name: "buprenorphine".into(),
+            dose: Some(20.0),
+            unit: Some("mcg per kg".into()),
+            route: Some("IV".into()),
+            species: None,
+            raw_text: "buprenorphine 20 mcg per kg IV".into(),
+            start_offset: 0,
+            end_offset: 30,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        let normalized = normalizer.normalize(&mention);
+        assert!(normalized.infusion.is_none());
+        assert_eq!(normalized.normalized_unit, Some("mg/kg".into()));
+        assert!((normalized.normalized_dose.unwrap() - 0.02).abs() < 1e-12);
+        assert!(matches!(
+            normalized.dose_expression(),
+            Some(DoseExpression::PerKg { ref unit, .. }) if *unit == DoseUnit::Milligrams
+        ));
+        assert_eq!(normalizer.convert_compound_unit("cc"), ("mL".into(), 1.0));
+    }
+
+    #[test]
+    fn test_parse_taper() {
+        let normalizer = Normalizer::new();
+        let taper = normalizer
+            .parse_taper(
+                "prednisone 20mg BID for 5 days, then 10 mg SID for 5 days, then 5mg for 4 days",
+            )
+            .unwrap();
+
+        assert_eq!(taper.phases.len(), 3);
+        assert_eq!(taper.phases[0].dose, 20.0);
+        assert_eq!(taper.phases[0].frequency, "BID");
+        assert_eq!(taper.phases[0].doses_per_day, 2.0);
+        assert_eq!(taper.phases[1].dose, 10.0);
+        assert_eq!(taper.phases[1].days, 5.0);
+        // Frequency carries over when not repeated
+        assert_eq!(taper.phases[2].frequency, "SID");
+        assert_eq!(taper.total_amount(), Some(270.0));
+
+        // A single course is not a taper, and phases need a duration
+        assert!(normalizer
+            .parse_taper("prednisone 20mg BID for 5 days")
+            .is_none());
+        assert!(normalizer
+            .parse_taper("carprofen 100mg PO then cefazolin 500mg IV")
+            .is_none());
+        // "then" moving on to another drug or an instruction
+        assert!(normalizer
+            .parse_taper("carprofen 100mg BID for 5 days then cefazolin 500mg IV for 3 days")
+            .is_none());
+        assert!(normalizer
+            .parse_taper("for 3 days carprofen 100mg BID then recheck 10mg dose")
+            .is_none());
+        let tapered = normalizer
+            .parse_taper("pred 20mg BID for 5 days, then taper to 10mg SID for 5 days")
+            .unwrap();
+        assert_eq!(tapered.phases[1].dose, 10.0);
+
+        let mention = DrugMention {
+            raw_text: "pred 1000 mcg twice daily for 3 days then 500 mcg daily for 3 days".into(),
+            drug_name: "pred".into(),
+            dose: Some(1000.0),
+            unit: Some("mcg".into()),
+            route: None,
+            species: None,
+            start_offset: 0,
+            end_offset: 0,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+        let taper = normalizer.normalize(&mention).taper.unwrap();
+        assert_eq!(taper.unit(), Some("mg"));
+        assert_eq!(taper.phases[0].frequency, "twice daily");
+        assert_eq!(taper.total_amount(), Some(7.5));
+    }
+
+    #[test]
+    fn test_field_spans() {
+        let normalizer = Normalizer::new();
+        let transcript = "Stable overnight. Give 100mg of carprofen twice daily by mouth. \
+                          Then two point five mils of cerenia SQ.";
+        let text = |span: Option<SourceSpan>| span.and_then(|s| s.text(transcript));
+
+        // Offsets that miss the raw text are corrected, and a NER span that
+        // doesn't read as its field is replaced
+        let mut mention = DrugMention {
+            raw_text: "100mg of carprofen twice daily by mouth".into(),
+            drug_name: "carprofen".into(),
+            dose: Some(100.0),
+            unit: Some("mg".into()),
+            route: Some("by mouth".into()),
+            species: None,
+            start_offset: 0,
+            end_offset: 10,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+        mention.field_spans.drug = Some(SourceSpan {
+            start_offset: 0,
+            end_offset: 6,
+        });
+        let spans = normalizer.field_spans(transcript, &mention);
+        assert_eq!(text(spans.drug), Some("carprofen"));
+        assert_eq!(text(spans.dose), Some("100"));
+        assert_eq!(text(spans.unit), Some("mg"));
+        assert_eq!(text(spans.route), Some("by mouth"));
+        assert_eq!(text(spans.frequency), Some("twice daily"));
+
+        // Spoken doses
+        let start = transcript.find("two point").unwrap();
+        let raw_text = "two point five mils of cerenia SQ";
+        let mention = DrugMention {
+            raw_text: raw_text.into(),
+            drug_name: "cerenia".into(),
+            dose: None,
+            unit: None,
+            route: Some("SQ".into()),
+            species: None,
+            start_offset: start,
+            end_offset: start + raw_text.len(),
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+        let spans = normalizer.field_spans(transcript, &mention);
+        assert_eq!(text(spans.dose), Some("two point five"));
+        assert_eq!(text(spans.unit), Some("mils"));
+        assert_eq!(text(spans.route), Some("SQ"));
+        assert_eq!(spans.frequency, None);
+
+        // Unknown text gives no spans
+        let mention = DrugMention {
+            raw_text: "ace 0.5 mg".into(),
+            drug_name: "ace".into(),
+            start_offset: 0,
+            end_offset: 0,
+            ..mention
+        };
+        assert_eq!(
+            normalizer.field_spans(transcript, &mention),
+            FieldSpans::default()
+        );
+    }
+
+    #[test]
+    fn test_infusion_rate() {
+        let normalizer = Normalizer::new();
+        let mention = DrugMention {
+            drug_name: "fentanyl".into(),
+            dose: Some(3.0),
+            unit: Some("mcg/kg/hr".into()),
+            route: Some("IV".into()),
+            species: None,
+            raw_text: "fentanyl 3 mcg/kg/hr for 6 hours".into(),
+            start_offset: 0,
+            end_offset: 32,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        let normalized = normalizer.normalize(&mention);
+        let rate = normalized.infusion.unwrap();
+        assert!((rate.rate_per_hour - 0.003).abs() < 1e-12);
+        assert_eq!(rate.unit, "mg");
+        assert!(rate.per_kg);
+        assert_eq!(rate.duration_hours, Some(6.0));
+        assert_eq!(normalized.normalized_unit, Some("mg/kg/hr".into()));
+
+        // Per-minute rates are expressed hourly
+        let rate = normalizer.parse_rate_unit("mL/min", 2.0).unwrap();
+        assert_eq!(rate.rate_per_hour, 120.0);
+        assert!(!rate.per_kg);
+
+        // Plain units are not rates
+        assert!(normalizer.parse_rate_unit("mg", 2.0).is_none());
+        assert!(normalizer.parse_rate_unit("mg/kg", 2.0).is_none());
+
+        let rate = normalizer
+            .parse_rate_unit("mcg per kg per minute", 1.0)
+            .unwrap();
+        assert!((rate.rate_per_hour - 0.06).abs() < 1e-12);
+        assert!(rate.per_kg);
+
+        assert_eq!(parse_duration_hours("over 30 min"), Some(0.5));
+        assert_eq!(parse_duration_hours("for 12h."), Some(12.0));
+        assert_eq!(parse_duration_hours("for pain"), None);
+        assert_eq!(parse_duration_hours("for six hours"), Some(6.0));
+        assert_eq!(parse_duration_hours("over half an hour"), Some(0.5));
+    }
+
+    #[test]
+    fn test_spoken_dose() {
+        let normalizer = Normalizer::new();
+        let mention = |raw_text: &str| DrugMention {
+            raw_text: raw_text.into(),
+            drug_name: "carprofen".into(),
+            dose: None,
+            unit: None,
+            route: None,
+            species: None,
+            start_offset: 0,
+            end_offset: raw_text.len(),
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        let normalized = normalizer.normalize(&mention("two point five mils of carprofen"));
+        assert_eq!(normalized.normalized_dose, Some(2.5));
+        assert_eq!(normalized.normalized_unit, Some("mL".into()));
+
+        let normalized = normalizer.normalize(&mention("half a tablet of carprofen"));
+        assert_eq!(normalized.normalized_dose, Some(0.5));
+        assert_eq!(normalized.normalized_unit, Some("tablets".into()));
+
+        let normalized = normalizer.normalize(&mention("carprofen one and a half tabs PO"));
+        assert_eq!(normalized.normalized_dose, Some(1.5));
+
+        // Numbers without a unit are not doses
+        let normalized = normalizer.normalize(&mention("carprofen for two weeks"));
+        assert_eq!(normalized.normalized_dose, None);
+    }
+
+    #[test]
+    fn test_spanish_dictation() {
+        let normalizer = Normalizer::for_locale(NormalizerLocale::Spanish);
+        let mention = DrugMention {
+            raw_text: "dale diez miligramos de carprofeno por vía oral".into(),
+            drug_name: "carprofeno".into(),
+            dose: None,
+            unit: None,
+            route: None,
+            species: None,
+            start_offset: 0,
+            end_offset: 47,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
+        };
+
+        let normalized = normalizer.normalize(&mention);
+        assert_eq!(normalized.normalized_name, "carprofen");
+        assert_eq!(normalized.normalized_dose, Some(10.0));
+        assert_eq!(normalized.normalized_unit, Some("mg".into()));
+        assert_eq!(normalized.normalized_route.as_deref(), Some("PO"));
+
+        // English terms still work
+        assert_eq!(normalizer.expand_alias("rimadyl"), "carprofen");
+        assert_eq!(normalizer.canonicalize_route("subcutánea"), "SQ");
+
+        // English locale leaves spoken numbers alone
+        let english = Normalizer::new().normalize(&mention);
+        assert_eq!(english.normalized_dose, None);
+    }
 }
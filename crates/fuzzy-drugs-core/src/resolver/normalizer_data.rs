@@ -0,0 +1,284 @@
+//! Versioned normalizer data loaded from JSON resources.
+//!
+//! The alias/unit/route maps are compiled into [`Normalizer`], but apps can ship
+//! updated data as a JSON file without a crate release:
+//!
+//! ```json
+//! {
+//!   "version": "2024.06.1",
+//!   "aliases": { "rimadyl": "carprofen" },
+//!   "units": { "cc": { "unit": "mL", "multiplier": 1.0 } },
+//!   "routes": { "orally": "PO" },
+//!   "locale": "es"
+//! }
+//! ```
+//!
+//! File entries are layered over the compiled-in set unless `extend_builtin` is
+//! false. If a file is missing or invalid, the compiled-in set is used.
+//! `locale` selects the clinic's dictation language (default English).
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::merkle::hash_data;
+
+use super::{Normalizer, NormalizerLocale};
+
+/// Version reported for the compiled-in data set.
+pub const BUILTIN_NORMALIZER_DATA_VERSION: &str = "builtin-2";
+
+/// Normalizer data loading errors.
+#[derive(Error, Debug)]
+pub enum NormalizerDataError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid normalizer data: {0}")]
+    Invalid(String),
+}
+
+/// A unit conversion entry.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UnitConversion {
+    /// Canonical unit (e.g., "mg", "mL")
+    pub unit: String,
+    /// Multiplier from the spoken unit to the canonical unit
+    pub multiplier: f64,
+}
+
+/// Serializable normalizer data set.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NormalizerData {
+    /// Data set version (e.g., "2024.06.1")
+    pub version: String,
+    /// Whether entries are layered over the compiled-in set
+    #[serde(default = "default_true")]
+    pub extend_builtin: bool,
+    /// Spoken name → canonical name
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// Spoken unit → canonical unit and multiplier
+    #[serde(default)]
+    pub units: BTreeMap<String, UnitConversion>,
+    /// Spoken route → canonical abbreviation
+    #[serde(default)]
+    pub routes: BTreeMap<String, String>,
+    /// Dictation language tag ("en", "es"); English when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Where the active normalizer data came from.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum NormalizerDataSource {
+    /// Compiled into the crate
+    Builtin,
+    /// Loaded from a JSON resource
+    File,
+}
+
+/// Version and checksum of the active normalizer data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NormalizerDataInfo {
+    /// Data set version
+    pub version: String,
+    /// SHA-256 of the data (raw file bytes, or canonical JSON for builtin)
+    pub checksum: String,
+    /// Source of the data
+    pub source: NormalizerDataSource,
+}
+
+impl NormalizerDataInfo {
+    /// Info for the compiled-in data set (computed once).
+    pub fn builtin() -> Self {
+        static BUILTIN: OnceLock<NormalizerDataInfo> = OnceLock::new();
+        BUILTIN
+            .get_or_init(|| {
+                let data = NormalizerData::builtin();
+                let canonical = serde_json::to_string(&data).unwrap_or_default();
+                NormalizerDataInfo {
+                    version: BUILTIN_NORMALIZER_DATA_VERSION.to_string(),
+                    checksum: hash_data(canonical.as_bytes()),
+                    source: NormalizerDataSource::Builtin,
+                }
+            })
+            .clone()
+    }
+}
+
+impl NormalizerData {
+    /// The compiled-in data set.
+    pub fn builtin() -> Self {
+        Self {
+            version: BUILTIN_NORMALIZER_DATA_VERSION.to_string(),
+            extend_builtin: false,
+            aliases: Normalizer::default_aliases().into_iter().collect(),
+            units: Normalizer::default_unit_conversions()
+                .into_iter()
+                .map(|(k, (unit, multiplier))| (k, UnitConversion { unit, multiplier }))
+                .collect(),
+            routes: Normalizer::default_routes().into_iter().collect(),
+            locale: None,
+        }
+    }
+
+    /// Parse and validate a JSON data set.
+    pub fn from_json(json: &str) -> Result<Self, NormalizerDataError> {
+        let data: NormalizerData = serde_json::from_str(json)?;
+        data.validate()?;
+        Ok(data)
+    }
+
+    /// Validate entries (non-empty version, positive finite multipliers).
+    pub fn validate(&self) -> Result<(), NormalizerDataError> {
+        if self.version.trim().is_empty() {
+            return Err(NormalizerDataError::Invalid("missing version".into()));
+        }
+        for (from, conv) in &self.units {
+            if !conv.multiplier.is_finite() || conv.multiplier <= 0.0 {
+                return Err(NormalizerDataError::Invalid(format!(
+                    "unit '{}' has invalid multiplier {}",
+                    from, conv.multiplier
+                )));
+            }
+        }
+        if let Some(locale) = &self.locale {
+            if NormalizerLocale::parse(locale).is_none() {
+                return Err(NormalizerDataError::Invalid(format!(
+                    "unsupported locale '{}'",
+                    locale
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Normalizer {
+    /// Build a normalizer from a JSON data set.
+    pub fn from_json(json: &str) -> Result<Self, NormalizerDataError> {
+        let data = NormalizerData::from_json(json)?;
+        let info = NormalizerDataInfo {
+            version: data.version.clone(),
+            checksum: hash_data(json.as_bytes()),
+            source: NormalizerDataSource::File,
+        };
+
+        let mut normalizer = if data.extend_builtin {
+            Normalizer::new()
+        } else {
+            Normalizer::empty()
+        };
+        for (alias, canonical) in &data.aliases {
+            normalizer.add_alias(alias, canonical);
+        }
+        for (from, conv) in &data.units {
+            // Keep canonical unit casing as written ("mL", "IU")
+            normalizer
+                .unit_conversions
+                .insert(from.to_lowercase(), (conv.unit.clone(), conv.multiplier));
+        }
+        for (spoken, canonical) in &data.routes {
+            normalizer.add_route(spoken, canonical);
+        }
+        if let Some(locale) = data.locale.as_deref().and_then(NormalizerLocale::parse) {
+            normalizer.set_locale(locale);
+        }
+        normalizer.data_info = info;
+        Ok(normalizer)
+    }
+
+    /// Load a normalizer from a JSON resource file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, NormalizerDataError> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json)
+    }
+
+    /// Load from a JSON resource, falling back to the compiled-in set.
+    pub fn from_file_or_builtin<P: AsRef<Path>>(path: P) -> Self {
+        Self::from_file(path).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"{
+        "version": "2024.06.1",
+        "aliases": { "newbrand": "newgeneric", "rimadyl": "carprofen" },
+        "units": { "dram": { "unit": "mL", "multiplier": 3.7 } },
+        "routes": { "into the vein": "IV" }
+    }"#;
+
+    #[test]
+    fn test_from_json_extends_builtin() {
+        let normalizer = Normalizer::from_json(SAMPLE).unwrap();
+
+        assert_eq!(normalizer.expand_alias("newbrand"), "newgeneric");
+        // Builtin entries are kept
+        assert_eq!(normalizer.expand_alias("metacam"), "meloxicam");
+        assert_eq!(normalizer.convert_unit("dram"), ("mL".to_string(), 3.7));
+        assert_eq!(normalizer.canonicalize_route("into the vein"), "IV");
+
+        let info = normalizer.data_info();
+        assert_eq!(info.version, "2024.06.1");
+        assert_eq!(info.source, NormalizerDataSource::File);
+        assert_eq!(info.checksum, hash_data(SAMPLE.as_bytes()));
+    }
+
+    #[test]
+    fn test_from_json_replace_builtin() {
+        let json = r#"{"version": "1", "extend_builtin": false, "aliases": {"x": "y"}}"#;
+        let normalizer = Normalizer::from_json(json).unwrap();
+
+        assert_eq!(normalizer.expand_alias("x"), "y");
+        assert_eq!(normalizer.expand_alias("metacam"), "metacam");
+    }
+
+    #[test]
+    fn test_locale_from_json() {
+        let json = r#"{"version": "clinic-7", "locale": "es-MX"}"#;
+        let normalizer = Normalizer::from_json(json).unwrap();
+        assert_eq!(normalizer.locale(), NormalizerLocale::Spanish);
+        assert_eq!(normalizer.expand_alias("carprofeno"), "carprofen");
+
+        let json = r#"{"version": "1", "locale": "klingon"}"#;
+        assert!(Normalizer::from_json(json).is_err());
+    }
+
+    #[test]
+    fn test_invalid_data_rejected() {
+        let json = r#"{"version": "1", "units": {"x": {"unit": "mg", "multiplier": 0}}}"#;
+        assert!(Normalizer::from_json(json).is_err());
+
+        let json = r#"{"version": "", "aliases": {}}"#;
+        assert!(Normalizer::from_json(json).is_err());
+    }
+
+    #[test]
+    fn test_missing_file_falls_back_to_builtin() {
+        let normalizer = Normalizer::from_file_or_builtin("/nonexistent/normalizer.json");
+        assert_eq!(normalizer.expand_alias("rimadyl"), "carprofen");
+        assert_eq!(normalizer.data_info().source, NormalizerDataSource::Builtin);
+    }
+
+    #[test]
+    fn test_builtin_checksum_stable() {
+        assert_eq!(
+            NormalizerDataInfo::builtin().checksum,
+            NormalizerDataInfo::builtin().checksum
+        );
+    }
+}
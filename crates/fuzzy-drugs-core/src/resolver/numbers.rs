@@ -0,0 +1,245 @@
+//! Spoken number parsing.
+//!
+//! Transcription often leaves quantities in words: "two point five mils",
+//! "half a tablet", "one and a half tabs". These helpers turn spelled-out
+//! numbers and fractions into values. Spanish number words live in
+//! `spanish.rs`.
+
+use super::spanish;
+use super::NormalizerLocale;
+
+/// Words allowed between a spoken quantity and its unit
+/// ("half of a tablet", "dos miligramos de ...").
+pub(super) const FILLER_WORDS: [&str; 5] = ["a", "an", "of", "the", "de"];
+
+/// Value of a single English number word.
+fn number_word(word: &str) -> Option<f64> {
+    let value = match word {
+        "zero" => 0,
+        "one" => 1,
+        "two" => 2,
+        "three" => 3,
+        "four" => 4,
+        "five" => 5,
+        "six" => 6,
+        "seven" => 7,
+        "eight" => 8,
+        "nine" => 9,
+        "ten" => 10,
+        "eleven" => 11,
+        "twelve" => 12,
+        "thirteen" => 13,
+        "fourteen" => 14,
+        "fifteen" => 15,
+        "sixteen" => 16,
+        "seventeen" => 17,
+        "eighteen" => 18,
+        "nineteen" => 19,
+        "twenty" => 20,
+        "thirty" => 30,
+        "forty" => 40,
+        "fifty" => 50,
+        "sixty" => 60,
+        "seventy" => 70,
+        "eighty" => 80,
+        "ninety" => 90,
+        _ => return None,
+    };
+    Some(value as f64)
+}
+
+/// Value of a fraction word ("half", "quarters", ...).
+fn fraction_word(word: &str) -> Option<f64> {
+    match word {
+        "half" | "halves" => Some(0.5),
+        "third" | "thirds" => Some(1.0 / 3.0),
+        "quarter" | "quarters" | "fourth" | "fourths" => Some(0.25),
+        "eighth" | "eighths" => Some(0.125),
+        _ => None,
+    }
+}
+
+/// Whether `value` can extend the number built so far ("twenty" + "five",
+/// "one hundred" + "fifty"), rather than starting a new one.
+fn extends(current: f64, value: f64) -> bool {
+    current == 0.0
+        || (value < 10.0 && current % 10.0 == 0.0)
+        || (value < 100.0 && current % 100.0 == 0.0)
+}
+
+/// Parse a spoken English number at the start of `words`.
+///
+/// Handles compounds ("twenty five", "one hundred and fifty"), fractions
+/// ("half", "a quarter", "three quarters"), mixed numbers ("one and a half"),
+/// decimals ("two point five", "point two five"), and digits. Words must be
+/// lowercase. Returns the value and the number of words consumed.
+pub fn parse_number_words(words: &[&str]) -> Option<(f64, usize)> {
+    // "a half", "an eighth"
+    if let [article, next, ..] = words {
+        if matches!(*article, "a" | "an") {
+            if let Some(fraction) = fraction_word(next) {
+                return Some((fraction, 2));
+            }
+        }
+    }
+
+    let mut total = 0.0;
+    let mut current = 0.0;
+    let mut consumed = 0;
+    let mut seen = false;
+
+    while consumed < words.len() {
+        let word = words[consumed];
+        if let Some(value) = number_word(word).or_else(|| word.parse::<f64>().ok()) {
+            if seen && !extends(current, value) {
+                break;
+            }
+            current += value;
+            seen = true;
+            consumed += 1;
+        } else if word == "hundred" && seen {
+            current *= 100.0;
+            consumed += 1;
+        } else if word == "thousand" && seen {
+            total += current * 1000.0;
+            current = 0.0;
+            consumed += 1;
+        } else if let Some(fraction) = fraction_word(word) {
+            // "half", or a numerator: "three quarters"
+            let numerator = if seen { total + current } else { 1.0 };
+            return Some((numerator * fraction, consumed + 1));
+        } else if word == "and" && seen {
+            let rest = &words[consumed + 1..];
+            match parse_number_words(rest) {
+                // "one and a half"
+                Some((fraction, n)) if fraction > 0.0 && fraction < 1.0 => {
+                    return Some((total + current + fraction, consumed + 1 + n));
+                }
+                // "one hundred and fifty"
+                Some(_) if current % 100.0 == 0.0 || current == 0.0 => consumed += 1,
+                _ => break,
+            }
+        } else if word == "point" {
+            let (fraction, digits) = parse_decimal_digits(&words[consumed + 1..]);
+            if digits == 0 {
+                break;
+            }
+            return Some((total + current + fraction, consumed + 1 + digits));
+        } else {
+            break;
+        }
+    }
+
+    seen.then_some((total + current, consumed))
+}
+
+/// Digits after "point", spoken one at a time ("five", "oh five") or as one
+/// number ("twenty five" → .25).
+fn parse_decimal_digits(words: &[&str]) -> (f64, usize) {
+    let mut digits = String::new();
+    let mut consumed = 0;
+    for word in words {
+        let digit = match *word {
+            "oh" | "o" => Some(0.0),
+            w => number_word(w).or_else(|| w.parse::<f64>().ok()),
+        };
+        match digit {
+            Some(value) if value.fract() == 0.0 && value < 10.0 => {
+                digits.push_str(&(value as u64).to_string());
+                consumed += 1;
+            }
+            _ => break,
+        }
+    }
+
+    if consumed == 0 {
+        if let Some((value, n)) = parse_number_words(words) {
+            if value.fract() == 0.0 {
+                digits = (value as u64).to_string();
+                consumed = n;
+            }
+        }
+    }
+
+    match format!("0.{}", digits).parse::<f64>() {
+        Ok(fraction) if consumed > 0 => (fraction, consumed),
+        _ => (0.0, 0),
+    }
+}
+
+/// Parse text that is entirely a spoken number ("two point five",
+/// "one and a half", "2.5"). Returns `None` if anything else is left over.
+pub fn parse_spoken_number(text: &str) -> Option<f64> {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|w| !w.is_empty())
+        .collect();
+    match parse_number_words(&words) {
+        Some((value, consumed)) if consumed == words.len() => Some(value),
+        _ => None,
+    }
+}
+
+/// Parse a spoken number in the given locale. Spanish dictation still
+/// falls back to English number words.
+pub(super) fn parse_number(locale: NormalizerLocale, words: &[&str]) -> Option<(f64, usize)> {
+    match locale {
+        NormalizerLocale::English => parse_number_words(words),
+        NormalizerLocale::Spanish => {
+            spanish::parse_number(words).or_else(|| parse_number_words(words))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Option<(f64, usize)> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        parse_number_words(&words)
+    }
+
+    #[test]
+    fn test_parse_number_words() {
+        assert_eq!(parse("two point five mils"), Some((2.5, 3)));
+        assert_eq!(parse("half a tablet"), Some((0.5, 1)));
+        assert_eq!(parse("a half tablet"), Some((0.5, 2)));
+        assert_eq!(parse("one and a half tabs"), Some((1.5, 4)));
+        assert_eq!(parse("three quarters of a tablet"), Some((0.75, 2)));
+        assert_eq!(parse("twenty five mg"), Some((25.0, 2)));
+        assert_eq!(parse("one hundred and fifty mg"), Some((150.0, 4)));
+        assert_eq!(parse("two thousand units"), Some((2000.0, 2)));
+        assert_eq!(parse("point two five ml"), Some((0.25, 3)));
+        assert_eq!(parse("zero point oh five"), Some((0.05, 4)));
+        assert_eq!(parse("two point twenty five"), Some((2.25, 4)));
+        assert_eq!(parse("2 and a half"), Some((2.5, 4)));
+        assert_eq!(parse("carprofen"), None);
+        // A new number, not a compound
+        assert_eq!(parse("five twenty"), Some((5.0, 1)));
+    }
+
+    #[test]
+    fn test_parse_spoken_number() {
+        assert_eq!(parse_spoken_number("Two point five"), Some(2.5));
+        assert_eq!(parse_spoken_number("twenty-five"), Some(25.0));
+        assert_eq!(parse_spoken_number("2.5"), Some(2.5));
+        assert_eq!(parse_spoken_number("two mils"), None);
+    }
+
+    #[test]
+    fn test_locale_fallback() {
+        let words = ["dos", "tabletas"];
+        assert_eq!(
+            parse_number(NormalizerLocale::Spanish, &words),
+            Some((2.0, 1))
+        );
+        assert_eq!(parse_number(NormalizerLocale::English, &words), None);
+        let words = ["two", "tabs"];
+        assert_eq!(
+            parse_number(NormalizerLocale::Spanish, &words),
+            Some((2.0, 1))
+        );
+    }
+}
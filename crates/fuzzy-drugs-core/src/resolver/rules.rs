@@ -0,0 +1,534 @@
+//! Rule-based mention extraction, for installs without an NER model.
+//!
+//! [`RuleBasedExtractor`] finds drug names from the normalizer's alias
+//! dictionary and the catalog, then reads the dose ("75 mg", "75mg", "two
+//! point five mils") and route ("PO", "by mouth") from the same clause. It
+//! misses drugs that are in neither list and phrasing the patterns don't
+//! cover, but it needs no model download, so the pipeline keeps working
+//! (with more items left for review) until a model is installed.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::models::{
+    CatalogItem, DrugMention, FieldSpans, ServiceItem, ServiceKind, ServiceMention, SourceSpan,
+};
+
+use super::numbers::{self, FILLER_WORDS};
+use super::{ExtractedMentions, MentionExtractor, Normalizer, NormalizerLocale, ResolverResult};
+
+/// Catalog names shorter than this aren't used as drug names; they match
+/// too much ordinary speech.
+const MIN_TERM_LEN: usize = 3;
+
+/// A word of the transcript, with its byte range and lowercase text.
+#[derive(Debug)]
+struct Token {
+    start: usize,
+    end: usize,
+    lower: String,
+    /// Index of the clause (split at sentence ends, commas, semicolons,
+    /// newlines) the word is in
+    clause: usize,
+}
+
+/// Split `text` into words. Periods and hyphens inside a word are kept
+/// ("2.5", "i.v", "sub-q"); a period ending one ends the clause.
+fn tokenize(text: &str) -> Vec<Token> {
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '.' | '-' | '\'');
+    let mut tokens = Vec::new();
+    let mut clause = 0;
+    let mut run_start = None;
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        if is_word(c) {
+            run_start.get_or_insert(i);
+            continue;
+        }
+        if let Some(start) = run_start.take() {
+            let run = &text[start..i];
+            let body = run.trim_end_matches(|c: char| !c.is_alphanumeric());
+            let lead = body
+                .char_indices()
+                .find(|&(j, c)| {
+                    c.is_alphanumeric()
+                        || (c == '.' && body[j + 1..].starts_with(|d: char| d.is_ascii_digit()))
+                })
+                .map_or(body.len(), |(j, _)| j);
+            if lead < body.len() {
+                tokens.push(Token {
+                    start: start + lead,
+                    end: start + body.len(),
+                    lower: body[lead..].to_lowercase(),
+                    clause,
+                });
+            }
+            if run[body.len()..].contains('.') {
+                clause += 1;
+            }
+        }
+        if matches!(c, ',' | ';' | ':' | '!' | '?' | '\n') {
+            clause += 1;
+        }
+    }
+    tokens
+}
+
+/// Phrases to look for, keyed by their lowercase words joined with spaces.
+#[derive(Debug, Clone)]
+struct Phrases<V> {
+    map: HashMap<String, V>,
+    max_words: usize,
+}
+
+impl<V> Phrases<V> {
+    fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            max_words: 0,
+        }
+    }
+
+    /// `phrase`'s lowercase words joined with spaces, and how many there are.
+    fn key(phrase: &str) -> (String, usize) {
+        let words: Vec<String> = tokenize(phrase).into_iter().map(|t| t.lower).collect();
+        (words.join(" "), words.len())
+    }
+
+    fn insert(&mut self, phrase: &str, value: V) {
+        let (key, words) = Self::key(phrase);
+        if words > 0 {
+            self.max_words = self.max_words.max(words);
+            self.map.insert(key, value);
+        }
+    }
+
+    fn contains(&self, phrase: &str) -> bool {
+        self.map.contains_key(&Self::key(phrase).0)
+    }
+
+    /// The longest phrase starting at `tokens[i]` within one clause, as
+    /// its word count and value.
+    fn match_at(&self, tokens: &[Token], i: usize) -> Option<(usize, &V)> {
+        let clause = tokens.get(i)?.clause;
+        let available = tokens[i..]
+            .iter()
+            .take(self.max_words)
+            .take_while(|t| t.clause == clause)
+            .count();
+        (1..=available).rev().find_map(|n| {
+            let key = tokens[i..i + n]
+                .iter()
+                .map(|t| t.lower.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.map.get(&key).map(|value| (n, value))
+        })
+    }
+
+    /// Non-overlapping matches in `tokens[range]`, left to right, longest
+    /// first, as token ranges.
+    fn find_all(&self, tokens: &[Token], range: Range<usize>) -> Vec<(Range<usize>, &V)> {
+        let mut found = Vec::new();
+        let mut i = range.start;
+        while i < range.end {
+            match self.match_at(&tokens[..range.end], i) {
+                Some((n, value)) => {
+                    found.push((i..i + n, value));
+                    i += n;
+                }
+                None => i += 1,
+            }
+        }
+        found
+    }
+}
+
+/// The transcript range covered by `tokens[range]`.
+fn span(tokens: &[Token], range: &Range<usize>) -> SourceSpan {
+    SourceSpan {
+        start_offset: tokens[range.start].start,
+        end_offset: tokens[range.end - 1].end,
+    }
+}
+
+/// The words of a catalog name before its strength or size ("Carprofen
+/// 75mg Tablets" → "carprofen").
+fn leading_words(name: &str) -> String {
+    tokenize(name)
+        .into_iter()
+        .take_while(|t| !t.lower.contains(|c: char| c.is_ascii_digit()))
+        .map(|t| t.lower)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A dose read from the transcript.
+struct Dose {
+    value: f64,
+    /// Index of the word after the dose
+    end: usize,
+    value_span: SourceSpan,
+    unit_span: SourceSpan,
+}
+
+/// Finds drug and service mentions with word lists and dose/route patterns
+/// instead of a model.
+///
+/// Drug names come from the normalizer's aliases (brand and generic) and,
+/// with [`with_catalog`](Self::with_catalog), the clinic's catalog; units
+/// and routes come from the normalizer. Every occurrence of a name is a
+/// mention. Names are reported as spoken, for the normalizer to expand.
+/// Mentions carry field spans but no extraction confidence.
+#[derive(Debug, Clone)]
+pub struct RuleBasedExtractor {
+    drugs: Phrases<()>,
+    units: Phrases<()>,
+    routes: Phrases<()>,
+    services: Phrases<ServiceKind>,
+    locale: NormalizerLocale,
+}
+
+impl RuleBasedExtractor {
+    /// An extractor using `normalizer`'s aliases, units, routes, and
+    /// dictation language.
+    pub fn new(normalizer: &Normalizer) -> Self {
+        let mut drugs = Phrases::new();
+        for (alias, canonical) in &normalizer.aliases {
+            drugs.insert(alias, ());
+            drugs.insert(canonical, ());
+        }
+        let mut routes = Phrases::new();
+        for spoken in normalizer.route_map.keys() {
+            routes.insert(spoken, ());
+        }
+        let mut units = Phrases::new();
+        for unit in normalizer.unit_conversions.keys() {
+            if !routes.contains(unit) {
+                units.insert(unit, ());
+            }
+        }
+        Self {
+            drugs,
+            units,
+            routes,
+            services: Phrases::new(),
+            locale: normalizer.locale,
+        }
+    }
+
+    /// Also look for the names, aliases, and ingredients of the active
+    /// items in `items`.
+    pub fn with_catalog(mut self, items: &[CatalogItem]) -> Self {
+        for item in items.iter().filter(|i| i.active) {
+            let names = std::iter::once(leading_words(&item.name))
+                .chain(item.aliases.iter().cloned())
+                .chain(item.components.iter().cloned());
+            for name in names {
+                let usable = name.trim().chars().count() >= MIN_TERM_LEN
+                    && !self.units.contains(&name)
+                    && !self.routes.contains(&name);
+                if usable {
+                    self.drugs.insert(&name, ());
+                }
+            }
+        }
+        self
+    }
+
+    /// Look for the names and aliases of the active services in `services`.
+    pub fn with_services(mut self, services: &[ServiceItem]) -> Self {
+        for service in services.iter().filter(|s| s.active) {
+            let names = std::iter::once(leading_words(&service.name))
+                .chain(service.aliases.iter().cloned());
+            for name in names {
+                self.services.insert(&name, service.kind);
+            }
+        }
+        self
+    }
+
+    /// Find the drug and service mentions in `transcript`.
+    pub fn extract_mentions(&self, transcript: &str) -> ExtractedMentions {
+        let tokens = tokenize(transcript);
+        let names: Vec<Range<usize>> = self
+            .drugs
+            .find_all(&tokens, 0..tokens.len())
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect();
+
+        let mut drugs = Vec::with_capacity(names.len());
+        // Words up to here belong to the previous mention
+        let mut claimed = 0;
+        for (n, name) in names.iter().enumerate() {
+            // The clause around the name, stopping at neighbouring mentions
+            let clause = tokens[name.start].clause;
+            let next_start = names.get(n + 1).map_or(tokens.len(), |next| next.start);
+            let before = (claimed..name.start).find(|&i| tokens[i].clause == clause);
+            let after = (name.end..next_start).take_while(|&i| tokens[i].clause == clause);
+            let before = before.unwrap_or(name.start)..name.start;
+            let after = name.end..after.last().map_or(name.end, |i| i + 1);
+            let (mention, used) = self.drug_mention(transcript, &tokens, name, before, after);
+            drugs.push(mention);
+            claimed = used;
+        }
+
+        let services = self
+            .services
+            .find_all(&tokens, 0..tokens.len())
+            .into_iter()
+            .map(|(range, kind)| {
+                let span = span(&tokens, &range);
+                let raw_text = transcript[span.start_offset..span.end_offset].to_string();
+                ServiceMention {
+                    service_name: raw_text.clone(),
+                    raw_text,
+                    kind: Some(*kind),
+                    quantity: None,
+                    start_offset: span.start_offset,
+                    end_offset: span.end_offset,
+                }
+            })
+            .collect();
+
+        ExtractedMentions { drugs, services }
+    }
+
+    /// The mention for the name at `tokens[name]`, with the dose and route
+    /// taken from after the name if said there, else from before it, and
+    /// the end of the last word it used.
+    fn drug_mention(
+        &self,
+        transcript: &str,
+        tokens: &[Token],
+        name: &Range<usize>,
+        before: Range<usize>,
+        after: Range<usize>,
+    ) -> (DrugMention, usize) {
+        let dose = self
+            .doses(tokens, after.clone())
+            .into_iter()
+            .next()
+            .or_else(|| self.doses(tokens, before.clone()).pop());
+        let route = self
+            .routes
+            .find_all(tokens, after)
+            .into_iter()
+            .next()
+            .or_else(|| self.routes.find_all(tokens, before).pop())
+            .map(|(range, _)| range);
+        let used = [
+            Some(name.end),
+            dose.as_ref().map(|d| d.end),
+            route.as_ref().map(|r| r.end),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(name.end);
+        let route = route.map(|range| span(tokens, &range));
+
+        let drug = span(tokens, name);
+        let text = |span: SourceSpan| transcript[span.start_offset..span.end_offset].to_string();
+        let field_spans = FieldSpans {
+            drug: Some(drug),
+            dose: dose.as_ref().map(|d| d.value_span),
+            unit: dose.as_ref().map(|d| d.unit_span),
+            route,
+            frequency: None,
+        };
+        let spans = [Some(drug), field_spans.dose, field_spans.unit, route];
+        let start_offset = spans.iter().flatten().map(|s| s.start_offset).min();
+        let end_offset = spans.iter().flatten().map(|s| s.end_offset).max();
+        let (start_offset, end_offset) = (
+            start_offset.unwrap_or(drug.start_offset),
+            end_offset.unwrap_or(drug.end_offset),
+        );
+
+        let mention = DrugMention {
+            raw_text: transcript[start_offset..end_offset].to_string(),
+            drug_name: text(drug),
+            dose: dose.as_ref().map(|d| d.value),
+            unit: dose.map(|d| text(d.unit_span)),
+            route: route.map(text),
+            species: None,
+            start_offset,
+            end_offset,
+            field_spans,
+            extraction_confidence: None,
+            speaker: None,
+        };
+        (mention, used)
+    }
+
+    /// Doses in `tokens[range]`, left to right: a number and a unit, either
+    /// run together ("75mg") or as words ("75 mg", "half a tablet").
+    fn doses(&self, tokens: &[Token], range: Range<usize>) -> Vec<Dose> {
+        let words: Vec<&str> = tokens[..range.end]
+            .iter()
+            .map(|t| t.lower.as_str())
+            .collect();
+        let mut doses = Vec::new();
+        let mut i = range.start;
+        while i < range.end {
+            if let Some(dose) = self.attached_dose(&tokens[i], i) {
+                doses.push(dose);
+                i += 1;
+                continue;
+            }
+            let Some((value, len)) = numbers::parse_number(self.locale, &words[i..]) else {
+                i += 1;
+                continue;
+            };
+            let mut unit_at = i + len;
+            while unit_at < range.end && FILLER_WORDS.contains(&words[unit_at]) {
+                unit_at += 1;
+            }
+            match self.units.match_at(&tokens[..range.end], unit_at) {
+                Some((unit_len, ())) => {
+                    let unit = unit_at..unit_at + unit_len;
+                    doses.push(Dose {
+                        value,
+                        end: unit.end,
+                        value_span: span(tokens, &(i..i + len)),
+                        unit_span: span(tokens, &unit),
+                    });
+                    i = unit.end;
+                }
+                None => i += len,
+            }
+        }
+        doses
+    }
+
+    /// A number with its unit attached ("75mg", "2.5ml").
+    fn attached_dose(&self, token: &Token, i: usize) -> Option<Dose> {
+        let split = token
+            .lower
+            .find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let value = token.lower[..split].parse::<f64>().ok()?;
+        self.units.map.get(&token.lower[split..])?;
+        let at = token.start + split;
+        Some(Dose {
+            value,
+            end: i + 1,
+            value_span: SourceSpan {
+                start_offset: token.start,
+                end_offset: at,
+            },
+            unit_span: SourceSpan {
+                start_offset: at,
+                end_offset: token.end,
+            },
+        })
+    }
+}
+
+impl MentionExtractor for RuleBasedExtractor {
+    fn extract(&self, transcript: &str) -> ResolverResult<Vec<DrugMention>> {
+        Ok(self.extract_mentions(transcript).drugs)
+    }
+
+    fn extract_all(&self, transcript: &str) -> ResolverResult<ExtractedMentions> {
+        Ok(self.extract_mentions(transcript))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extractor() -> RuleBasedExtractor {
+        let mut carprofen = CatalogItem::new("CARP75".into(), "Carprofen 75mg Tablets".into());
+        carprofen.aliases = vec!["carpro".into()];
+        let mut inactive = CatalogItem::new("OLD".into(), "Gabapentin 100mg".into());
+        inactive.active = false;
+        let services = [ServiceItem::new(
+            "VAC-RAB3".into(),
+            "Rabies Vaccine 3yr".into(),
+            ServiceKind::Vaccine,
+        )];
+        RuleBasedExtractor::new(&Normalizer::new())
+            .with_catalog(&[carprofen, inactive])
+            .with_services(&services)
+    }
+
+    #[test]
+    fn test_extract_doses_and_routes() {
+        let transcript = "Give Rimadyl 75mg PO twice daily. Then 0.5 ml of dex IV, \
+                          and two point five mils cerenia sub-q.";
+        let drugs = extractor().extract_mentions(transcript).drugs;
+        assert_eq!(drugs.len(), 3);
+
+        let rimadyl = &drugs[0];
+        assert_eq!(rimadyl.drug_name, "Rimadyl");
+        assert_eq!(
+            (rimadyl.dose, rimadyl.unit.as_deref()),
+            (Some(75.0), Some("mg"))
+        );
+        assert_eq!(rimadyl.route.as_deref(), Some("PO"));
+        assert_eq!(rimadyl.raw_text, "Rimadyl 75mg PO");
+        let dose = rimadyl.field_spans.dose.unwrap();
+        assert_eq!(dose.text(transcript), Some("75"));
+
+        // Dose before the name, route after; the comma ends the clause
+        let dex = &drugs[1];
+        assert_eq!(dex.drug_name, "dex");
+        assert_eq!((dex.dose, dex.unit.as_deref()), (Some(0.5), Some("ml")));
+        assert_eq!(dex.route.as_deref(), Some("IV"));
+        assert_eq!(dex.raw_text, "0.5 ml of dex IV");
+
+        let cerenia = &drugs[2];
+        assert_eq!(
+            (cerenia.dose, cerenia.unit.as_deref()),
+            (Some(2.5), Some("mils"))
+        );
+        assert_eq!(cerenia.route.as_deref(), Some("sub-q"));
+        assert_eq!(
+            cerenia.span().text(transcript),
+            Some("two point five mils cerenia sub-q")
+        );
+    }
+
+    #[test]
+    fn test_extract_catalog_names_and_boundaries() {
+        let extractor = extractor();
+        let found = |t: &str| -> Vec<String> {
+            extractor
+                .extract_mentions(t)
+                .drugs
+                .into_iter()
+                .map(|d| d.drug_name)
+                .collect()
+        };
+
+        // Catalog names, aliases, every occurrence
+        assert_eq!(
+            found("carprofen today, carpro tomorrow"),
+            ["carprofen", "carpro"]
+        );
+        // Whole words only; inactive catalog items aren't names
+        assert!(found("She paced all night on gabapentin").is_empty());
+        assert_eq!(found("Ace, 0.2 mg/kg IM"), ["Ace"]);
+        // A dose belongs to the nearest name, not its neighbour's
+        let drugs = extractor
+            .extract_mentions("metacam 1.5 mg then baytril")
+            .drugs;
+        assert_eq!(drugs[1].dose, None);
+        assert_eq!(drugs[1].route, None);
+    }
+
+    #[test]
+    fn test_extract_services() {
+        let mentions = extractor().extract_mentions("Rabies vaccine given, nail trim declined.");
+        assert_eq!(mentions.services.len(), 1);
+        let rabies = &mentions.services[0];
+        assert_eq!(rabies.service_name, "Rabies vaccine");
+        assert_eq!(rabies.kind, Some(ServiceKind::Vaccine));
+        assert_eq!((rabies.start_offset, rabies.end_offset), (0, 14));
+        assert!(mentions.drugs.is_empty());
+    }
+}
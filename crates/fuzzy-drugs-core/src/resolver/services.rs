@@ -0,0 +1,165 @@
+//! Matching service mentions against the services catalog.
+//!
+//! Services catalogs are short (tens to a few hundred entries) and service
+//! names don't carry doses or routes, so matching is plain name similarity
+//! over each entry's name and aliases, with no candidate list for review.
+
+use strsim::{jaro_winkler, normalized_levenshtein};
+
+use crate::models::{ServiceItem, ServiceLineItem, ServiceMention};
+
+/// Minimum name similarity for a mention to match a service.
+pub const SERVICE_MATCH_THRESHOLD: f64 = 0.85;
+
+/// Similarity given when one name is a leading run of the other's words
+/// ("radiographs" in "Radiographs (2 views)").
+const PREFIX_MATCH_SCORE: f64 = 0.9;
+
+/// Score multiplier when the mention's kind differs from the service's.
+const KIND_MISMATCH_PENALTY: f64 = 0.9;
+
+/// Match a mention to the best active service scoring at least
+/// [`SERVICE_MATCH_THRESHOLD`]. Ties go to the earlier service.
+pub fn match_service(
+    services: &[ServiceItem],
+    mention: &ServiceMention,
+) -> Option<ServiceLineItem> {
+    let query = normalize_service_name(&mention.service_name);
+    if query.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(&ServiceItem, f64)> = None;
+    for service in services.iter().filter(|s| s.active) {
+        let mut score = std::iter::once(&service.name)
+            .chain(&service.aliases)
+            .map(|name| name_similarity(&query, &normalize_service_name(name)))
+            .fold(0.0, f64::max);
+        if mention.kind.is_some_and(|kind| kind != service.kind) {
+            score *= KIND_MISMATCH_PENALTY;
+        }
+        if score >= SERVICE_MATCH_THRESHOLD && best.is_none_or(|(_, b)| score > b) {
+            best = Some((service, score));
+        }
+    }
+
+    best.map(|(service, score)| ServiceLineItem {
+        mention: mention.clone(),
+        code: service.code.clone(),
+        name: service.name.clone(),
+        kind: service.kind,
+        quantity: mention.quantity.filter(|q| *q > 0.0).unwrap_or(1.0),
+        confidence: score,
+        lot_number: None,
+        expiration_date: None,
+    })
+}
+
+/// Lowercase words with punctuation dropped ("X-Rays (2)" → "x rays 2").
+fn normalize_service_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Similarity of two normalized names.
+fn name_similarity(a: &str, b: &str) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let fuzzy = jaro_winkler(a, b) * 0.6 + normalized_levenshtein(a, b) * 0.4;
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let word_prefix = longer.starts_with(shorter)
+        && matches!(longer[shorter.len()..].chars().next(), None | Some(' '));
+    if word_prefix {
+        fuzzy.max(PREFIX_MATCH_SCORE)
+    } else {
+        fuzzy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ServiceKind;
+
+    fn mention(name: &str, kind: Option<ServiceKind>) -> ServiceMention {
+        ServiceMention {
+            raw_text: name.into(),
+            service_name: name.into(),
+            kind,
+            quantity: None,
+            start_offset: 0,
+            end_offset: name.len(),
+        }
+    }
+
+    fn catalog() -> Vec<ServiceItem> {
+        let mut rads = ServiceItem::new(
+            "DX-RAD2".into(),
+            "Radiographs (2 views)".into(),
+            ServiceKind::Diagnostic,
+        );
+        rads.aliases = vec!["x-rays".into()];
+        let mut old_nails = ServiceItem::new(
+            "PROC-NAIL-OLD".into(),
+            "Nail Trim".into(),
+            ServiceKind::Procedure,
+        );
+        old_nails.active = false;
+        vec![
+            ServiceItem::new(
+                "VAC-RAB3".into(),
+                "Rabies Vaccine 3yr".into(),
+                ServiceKind::Vaccine,
+            ),
+            ServiceItem::new(
+                "VAC-DHPP".into(),
+                "DHPP Vaccine".into(),
+                ServiceKind::Vaccine,
+            ),
+            rads,
+            old_nails,
+            ServiceItem::new(
+                "PROC-NAIL".into(),
+                "Nail trim".into(),
+                ServiceKind::Procedure,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_match_service() {
+        let services = catalog();
+        let code = |name: &str| match_service(&services, &mention(name, None)).map(|s| s.code);
+
+        assert_eq!(code("rabies vaccine").as_deref(), Some("VAC-RAB3"));
+        assert_eq!(code("X-rays").as_deref(), Some("DX-RAD2"));
+        assert_eq!(code("radiographs").as_deref(), Some("DX-RAD2"));
+        // Inactive entries are skipped
+        assert_eq!(code("nail trim").as_deref(), Some("PROC-NAIL"));
+        // "vaccine" alone doesn't say which
+        assert_eq!(code("vaccine"), None);
+        assert_eq!(code("dental cleaning"), None);
+        assert_eq!(code(" - "), None);
+    }
+
+    #[test]
+    fn test_match_service_quantity_and_kind() {
+        let services = catalog();
+        let mut two = mention("nail trims", Some(ServiceKind::Procedure));
+        two.quantity = Some(2.0);
+        let item = match_service(&services, &two).unwrap();
+        assert_eq!(item.quantity, 2.0);
+        assert_eq!(item.kind, ServiceKind::Procedure);
+        assert!(item.confidence >= SERVICE_MATCH_THRESHOLD);
+
+        // The wrong kind costs enough to drop a borderline match
+        let prefix = mention("radiographs", Some(ServiceKind::Diagnostic));
+        assert!(match_service(&services, &prefix).is_some());
+        let wrong_kind = mention("radiographs", Some(ServiceKind::Vaccine));
+        assert!(match_service(&services, &wrong_kind).is_none());
+    }
+}
@@ -0,0 +1,328 @@
+//! Spanish-language normalization data.
+//!
+//! Bilingual clinics dictate in Spanish ("dale diez miligramos de carprofeno
+//! por vía oral"). These tables are layered over the English ones when the
+//! normalizer locale is Spanish, so mixed dictation still resolves.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Language the normalizer expects dictation in.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum NormalizerLocale {
+    #[default]
+    English,
+    /// Spanish, with English terms still recognized
+    Spanish,
+}
+
+impl NormalizerLocale {
+    /// Parse a language tag ("en", "en-US", "es", "es-MX", "spanish", ...).
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_lowercase();
+        let primary = s.split(['-', '_']).next().unwrap_or("");
+        match primary {
+            "en" | "english" => Some(NormalizerLocale::English),
+            "es" | "spanish" | "español" => Some(NormalizerLocale::Spanish),
+            _ => None,
+        }
+    }
+
+    /// Primary language tag ("en", "es").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NormalizerLocale::English => "en",
+            NormalizerLocale::Spanish => "es",
+        }
+    }
+}
+
+/// Spanish generic names → canonical (English) names.
+pub(super) fn spanish_aliases() -> HashMap<String, String> {
+    [
+        ("carprofeno", "carprofen"),
+        ("acepromacina", "acepromazine"),
+        ("dexmedetomidina", "dexmedetomidine"),
+        ("medetomidina", "medetomidine"),
+        ("atipamezol", "atipamezole"),
+        ("butorfanol", "butorphanol"),
+        ("buprenorfina", "buprenorphine"),
+        ("fentanilo", "fentanyl"),
+        ("ketamina", "ketamine"),
+        ("amoxicilina", "amoxicillin"),
+        ("amoxicilina con clavulánico", "amoxicillin-clavulanate"),
+        ("amoxicilina-clavulánico", "amoxicillin-clavulanate"),
+        ("enrofloxacina", "enrofloxacin"),
+        ("marbofloxacina", "marbofloxacin"),
+        ("cefalexina", "cephalexin"),
+        ("cefovecina", "cefovecin"),
+        ("doxiciclina", "doxycycline"),
+        ("metronidazol", "metronidazole"),
+        ("dexametasona", "dexamethasone"),
+        ("metilprednisolona", "methylprednisolone"),
+        ("prednisona", "prednisone"),
+        ("prednisolona", "prednisolone"),
+        ("triamcinolona", "triamcinolone"),
+        ("ivermectina", "ivermectin"),
+        ("milbemicina", "milbemycin"),
+        ("selamectina", "selamectin"),
+        ("pirantel", "pyrantel"),
+        ("fenbendazol", "fenbendazole"),
+        ("furosemida", "furosemide"),
+        ("digoxina", "digoxin"),
+        ("metoclopramida", "metoclopramide"),
+        ("famotidina", "famotidine"),
+        ("omeprazol", "omeprazole"),
+        ("sucralfato", "sucralfate"),
+        ("fenobarbital", "phenobarbital"),
+        ("bromuro de potasio", "potassium-bromide"),
+        ("zonisamida", "zonisamide"),
+        ("levotiroxina", "levothyroxine"),
+        ("metimazol", "methimazole"),
+        ("clomipramina", "clomipramine"),
+        ("fluoxetina", "fluoxetine"),
+        ("trazodona", "trazodone"),
+        ("gabapentina", "gabapentin"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect()
+}
+
+/// Spanish unit words → (canonical unit, multiplier).
+pub(super) fn spanish_unit_conversions() -> HashMap<String, (String, f64)> {
+    [
+        ("miligramo", "mg", 1.0),
+        ("miligramos", "mg", 1.0),
+        ("microgramo", "mg", 0.001),
+        ("microgramos", "mg", 0.001),
+        ("gramo", "mg", 1000.0),
+        ("gramos", "mg", 1000.0),
+        ("mililitro", "mL", 1.0),
+        ("mililitros", "mL", 1.0),
+        ("centímetro cúbico", "mL", 1.0),
+        ("centímetros cúbicos", "mL", 1.0),
+        ("litro", "mL", 1000.0),
+        ("litros", "mL", 1000.0),
+        ("unidad", "units", 1.0),
+        ("unidades", "units", 1.0),
+        ("tableta", "tablets", 1.0),
+        ("tabletas", "tablets", 1.0),
+        ("comprimido", "tablets", 1.0),
+        ("comprimidos", "tablets", 1.0),
+        ("pastilla", "tablets", 1.0),
+        ("pastillas", "tablets", 1.0),
+        ("cápsula", "capsules", 1.0),
+        ("cápsulas", "capsules", 1.0),
+    ]
+    .into_iter()
+    .map(|(k, unit, m)| (k.to_string(), (unit.to_string(), m)))
+    .collect()
+}
+
+/// Spanish route phrases → canonical abbreviations.
+pub(super) fn spanish_routes() -> HashMap<String, String> {
+    [
+        ("oral", "PO"),
+        ("vía oral", "PO"),
+        ("por vía oral", "PO"),
+        ("por la boca", "PO"),
+        ("por boca", "PO"),
+        ("intravenosa", "IV"),
+        ("intravenoso", "IV"),
+        ("vía intravenosa", "IV"),
+        ("por vía intravenosa", "IV"),
+        ("endovenosa", "IV"),
+        ("en la vena", "IV"),
+        ("intramuscular", "IM"),
+        ("vía intramuscular", "IM"),
+        ("por vía intramuscular", "IM"),
+        ("en el músculo", "IM"),
+        ("subcutánea", "SQ"),
+        ("subcutáneo", "SQ"),
+        ("vía subcutánea", "SQ"),
+        ("por vía subcutánea", "SQ"),
+        ("debajo de la piel", "SQ"),
+        ("tópica", "TOP"),
+        ("tópico", "TOP"),
+        ("vía tópica", "TOP"),
+        ("oftálmica", "OPH"),
+        ("en el ojo", "OPH"),
+        ("en los ojos", "OPH"),
+        ("ótica", "OT"),
+        ("en el oído", "OT"),
+        ("en los oídos", "OT"),
+        ("vía rectal", "PR"),
+        ("por vía rectal", "PR"),
+        ("intranasal", "IN"),
+        ("en la nariz", "IN"),
+        ("transdérmica", "TD"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect()
+}
+
+/// Value of a single Spanish number word.
+fn number_word(word: &str) -> Option<f64> {
+    let value = match word {
+        "cero" => 0,
+        "un" | "uno" | "una" => 1,
+        "dos" => 2,
+        "tres" => 3,
+        "cuatro" => 4,
+        "cinco" => 5,
+        "seis" => 6,
+        "siete" => 7,
+        "ocho" => 8,
+        "nueve" => 9,
+        "diez" => 10,
+        "once" => 11,
+        "doce" => 12,
+        "trece" => 13,
+        "catorce" => 14,
+        "quince" => 15,
+        "dieciséis" | "dieciseis" => 16,
+        "diecisiete" => 17,
+        "dieciocho" => 18,
+        "diecinueve" => 19,
+        "veinte" => 20,
+        "veintiuno" | "veintiún" | "veintiuna" => 21,
+        "veintidós" | "veintidos" => 22,
+        "veintitrés" | "veintitres" => 23,
+        "veinticuatro" => 24,
+        "veinticinco" => 25,
+        "veintiséis" | "veintiseis" => 26,
+        "veintisiete" => 27,
+        "veintiocho" => 28,
+        "veintinueve" => 29,
+        "treinta" => 30,
+        "cuarenta" => 40,
+        "cincuenta" => 50,
+        "sesenta" => 60,
+        "setenta" => 70,
+        "ochenta" => 80,
+        "noventa" => 90,
+        "cien" | "ciento" => 100,
+        "doscientos" | "doscientas" => 200,
+        "trescientos" | "trescientas" => 300,
+        "cuatrocientos" | "cuatrocientas" => 400,
+        "quinientos" | "quinientas" => 500,
+        "seiscientos" | "seiscientas" => 600,
+        "setecientos" | "setecientas" => 700,
+        "ochocientos" | "ochocientas" => 800,
+        "novecientos" | "novecientas" => 900,
+        _ => return None,
+    };
+    Some(value as f64)
+}
+
+/// Parse a spoken Spanish number at the start of `words`.
+///
+/// Handles compounds ("ciento veinticinco", "treinta y dos", "dos mil"),
+/// halves ("uno y medio", "media"), decimals ("cero punto cinco", "dos coma
+/// cinco"), and digits. Returns the value and the number of words consumed.
+pub(super) fn parse_number(words: &[&str]) -> Option<(f64, usize)> {
+    let mut total = 0.0;
+    let mut current = 0.0;
+    let mut consumed = 0;
+    let mut seen = false;
+
+    while consumed < words.len() {
+        let word = words[consumed];
+        if let Some(value) = number_word(word).or_else(|| word.parse::<f64>().ok()) {
+            current += value;
+            seen = true;
+            consumed += 1;
+        } else if word == "mil" {
+            total += if current == 0.0 {
+                1000.0
+            } else {
+                current * 1000.0
+            };
+            current = 0.0;
+            seen = true;
+            consumed += 1;
+        } else if (word == "medio" || word == "media") && !seen {
+            // "media tableta"
+            return Some((0.5, consumed + 1));
+        } else if word == "y" && seen {
+            match words.get(consumed + 1).copied() {
+                Some("medio") | Some("media") => {
+                    return Some((total + current + 0.5, consumed + 2));
+                }
+                Some(next) if number_word(next).is_some() => consumed += 1,
+                _ => break,
+            }
+        } else if (word == "punto" || word == "coma") && seen {
+            let (fraction, digits) = parse_decimal_digits(&words[consumed + 1..]);
+            if digits == 0 {
+                break;
+            }
+            return Some((total + current + fraction, consumed + 1 + digits));
+        } else {
+            break;
+        }
+    }
+
+    seen.then_some((total + current, consumed))
+}
+
+/// Digits after a decimal point, spoken one at a time ("cinco", "dos cinco")
+/// or as one number ("veinticinco" → .25).
+fn parse_decimal_digits(words: &[&str]) -> (f64, usize) {
+    let mut digits = String::new();
+    let mut consumed = 0;
+    for word in words {
+        match number_word(word).or_else(|| word.parse::<f64>().ok()) {
+            Some(value) if value.fract() == 0.0 => {
+                digits.push_str(&(value as u64).to_string());
+                consumed += 1;
+            }
+            _ => break,
+        }
+    }
+    match format!("0.{}", digits).parse::<f64>() {
+        Ok(fraction) if consumed > 0 => (fraction, consumed),
+        _ => (0.0, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Option<(f64, usize)> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        parse_number(&words)
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse("diez miligramos"), Some((10.0, 1)));
+        assert_eq!(parse("treinta y dos mg"), Some((32.0, 3)));
+        assert_eq!(parse("ciento veinticinco"), Some((125.0, 2)));
+        assert_eq!(parse("dos mil"), Some((2000.0, 2)));
+        assert_eq!(parse("uno y medio comprimidos"), Some((1.5, 3)));
+        assert_eq!(parse("media tableta"), Some((0.5, 1)));
+        assert_eq!(parse("cero punto cinco ml"), Some((0.5, 3)));
+        assert_eq!(parse("dos coma veinticinco"), Some((2.25, 3)));
+        assert_eq!(parse("carprofeno"), None);
+        // "y" not followed by a number ends the number
+        assert_eq!(parse("diez y luego"), Some((10.0, 1)));
+    }
+
+    #[test]
+    fn test_parse_locale() {
+        assert_eq!(
+            NormalizerLocale::parse("es-MX"),
+            Some(NormalizerLocale::Spanish)
+        );
+        assert_eq!(
+            NormalizerLocale::parse("EN"),
+            Some(NormalizerLocale::English)
+        );
+        assert_eq!(NormalizerLocale::parse("fr"), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! Species inference from breed names and free text.
+//!
+//! Dictation often names the patient by breed ("the golden retriever", "the
+//! DSH") rather than species. When the patient's species isn't supplied, the
+//! resolver infers it from the breed or the mention text so species scoring
+//! doesn't fall back to its uninformative default.
+
+/// Breed, abbreviation, and species words mapped to catalog species.
+///
+/// Terms are lowercase and matched as whole words; multi-word terms are
+/// matched before their parts ("golden retriever" before "retriever").
+const SPECIES_TERMS: &[(&str, &str)] = &[
+    // Canine
+    ("dog", "canine"),
+    ("dogs", "canine"),
+    ("puppy", "canine"),
+    ("pup", "canine"),
+    ("canine", "canine"),
+    ("k9", "canine"),
+    ("labrador", "canine"),
+    ("golden retriever", "canine"),
+    ("retriever", "canine"),
+    ("german shepherd", "canine"),
+    ("australian shepherd", "canine"),
+    ("aussie", "canine"),
+    ("border collie", "canine"),
+    ("collie", "canine"),
+    ("sheltie", "canine"),
+    ("shetland sheepdog", "canine"),
+    ("beagle", "canine"),
+    ("bulldog", "canine"),
+    ("french bulldog", "canine"),
+    ("frenchie", "canine"),
+    ("poodle", "canine"),
+    ("doodle", "canine"),
+    ("goldendoodle", "canine"),
+    ("labradoodle", "canine"),
+    ("dachshund", "canine"),
+    ("chihuahua", "canine"),
+    ("yorkie", "canine"),
+    ("yorkshire terrier", "canine"),
+    ("terrier", "canine"),
+    ("pit bull", "canine"),
+    ("pitbull", "canine"),
+    ("boxer", "canine"),
+    ("rottweiler", "canine"),
+    ("husky", "canine"),
+    ("corgi", "canine"),
+    ("shih tzu", "canine"),
+    ("schnauzer", "canine"),
+    ("greyhound", "canine"),
+    ("whippet", "canine"),
+    ("great dane", "canine"),
+    ("mastiff", "canine"),
+    ("doberman", "canine"),
+    ("pug", "canine"),
+    ("spaniel", "canine"),
+    ("pointer", "canine"),
+    ("heeler", "canine"),
+    ("pomeranian", "canine"),
+    ("maltese", "canine"),
+    ("havanese", "canine"),
+    ("bichon", "canine"),
+    ("perro", "canine"),
+    // Feline
+    ("cat", "feline"),
+    ("cats", "feline"),
+    ("kitten", "feline"),
+    ("kitty", "feline"),
+    ("feline", "feline"),
+    ("dsh", "feline"),
+    ("dmh", "feline"),
+    ("dlh", "feline"),
+    ("domestic shorthair", "feline"),
+    ("domestic medium hair", "feline"),
+    ("domestic longhair", "feline"),
+    ("domestic short hair", "feline"),
+    ("domestic long hair", "feline"),
+    ("siamese", "feline"),
+    ("persian", "feline"),
+    ("maine coon", "feline"),
+    ("ragdoll", "feline"),
+    ("bengal", "feline"),
+    ("sphynx", "feline"),
+    ("british shorthair", "feline"),
+    ("russian blue", "feline"),
+    ("abyssinian", "feline"),
+    ("gato", "feline"),
+    // Equine
+    ("horse", "equine"),
+    ("mare", "equine"),
+    ("gelding", "equine"),
+    ("stallion", "equine"),
+    ("foal", "equine"),
+    ("colt", "equine"),
+    ("filly", "equine"),
+    ("pony", "equine"),
+    ("equine", "equine"),
+    ("quarter horse", "equine"),
+    ("thoroughbred", "equine"),
+    ("arabian", "equine"),
+    ("appaloosa", "equine"),
+    ("warmblood", "equine"),
+    ("caballo", "equine"),
+];
+
+/// Longest term, in words, in [`SPECIES_TERMS`].
+const MAX_TERM_WORDS: usize = 3;
+
+/// Species for a breed name ("Golden Retriever" → "canine").
+///
+/// Accepts full breed strings such as "Labrador mix" or "DSH".
+pub fn species_for_breed(breed: &str) -> Option<&'static str> {
+    infer_species(breed)
+}
+
+/// Infer species from free text ("give the DSH 0.1 mL").
+///
+/// Returns `None` when nothing in the text implies a species, or when the
+/// text names more than one species.
+pub fn infer_species(text: &str) -> Option<&'static str> {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let mut found: Option<&'static str> = None;
+    let mut i = 0;
+    while i < words.len() {
+        let hit = (1..=MAX_TERM_WORDS.min(words.len() - i))
+            .rev()
+            .find_map(|n| term_species(&words[i..i + n].join(" ")).map(|s| (s, n)));
+        match hit {
+            Some((species, n)) => {
+                if found.is_some_and(|f| f != species) {
+                    return None;
+                }
+                found = Some(species);
+                i += n;
+            }
+            None => i += 1,
+        }
+    }
+    found
+}
+
+fn term_species(term: &str) -> Option<&'static str> {
+    SPECIES_TERMS
+        .iter()
+        .find(|(t, _)| *t == term)
+        .map(|(_, species)| *species)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_species_for_breed() {
+        assert_eq!(species_for_breed("Golden Retriever"), Some("canine"));
+        assert_eq!(species_for_breed("Labrador mix"), Some("canine"));
+        assert_eq!(species_for_breed("DSH"), Some("feline"));
+        assert_eq!(species_for_breed("Maine Coon"), Some("feline"));
+        assert_eq!(species_for_breed("Quarter Horse"), Some("equine"));
+        assert_eq!(species_for_breed("Unknown"), None);
+    }
+
+    #[test]
+    fn test_infer_species_from_text() {
+        assert_eq!(
+            infer_species("give the golden retriever 100mg carprofen"),
+            Some("canine")
+        );
+        assert_eq!(
+            infer_species("the DSH gets 0.1 mL buprenorphine"),
+            Some("feline")
+        );
+        assert_eq!(infer_species("carprofen 100mg PO"), None);
+        // Conflicting species are not guessed
+        assert_eq!(infer_species("the dog and the cat"), None);
+        // Whole words only
+        assert_eq!(infer_species("catheter placed"), None);
+    }
+}
@@ -14,14 +14,27 @@ fn make_encounter(id: &str, patient: &str) -> ReviewedEncounter {
             sku: "SKU001".to_string(),
             name: "Test Drug 100mg".to_string(),
             quantity: 10.0,
-            unit: "mg".to_string(),
+            unit: "mg".into(),
             route: Some("PO".to_string()),
             original_mention: "10mg test drug PO".to_string(),
             resolution_method: ResolutionMethod::SystemApproved { confidence: 0.95 },
+            controlled_schedule: None,
+            source_spans: vec![],
+            schedule: None,
+            disposition: None,
+            lot_number: None,
+            expiration_date: None,
+            withdrawal: None,
+            escalation_approval: None,
+            controlled_confirmed_by: None,
+            allergy_overridden_by: None,
         }],
         reviewed_by: "Dr. Smith".to_string(),
         reviewed_at: chrono::Utc::now().to_rfc3339(),
         notes: None,
+        device_id: None,
+        prescriber: None,
+        visit_id: None,
     }
 }
 
@@ -196,6 +209,7 @@ fn test_deterministic_hashing() {
     let tree2 = MerkleTree::new(&db2);
 
     // Same encounter should produce same hash in different databases
+    // (device ID fixed, otherwise each database stamps its own)
     let encounter = ReviewedEncounter {
         draft_id: "draft-1".to_string(),
         patient_id: "patient-1".to_string(),
@@ -205,6 +219,9 @@ fn test_deterministic_hashing() {
         reviewed_by: "Dr. Smith".to_string(),
         reviewed_at: "2024-01-15T10:00:00Z".to_string(), // Fixed timestamp
         notes: None,
+        device_id: Some("device-1".to_string()),
+        prescriber: None,
+        visit_id: None,
     };
 
     let commit1 = tree1.commit_encounter(&encounter).unwrap();
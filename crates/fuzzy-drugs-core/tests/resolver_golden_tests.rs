@@ -161,7 +161,8 @@ fn test_golden_cases() {
 
     for case in get_golden_cases() {
         let mention = DrugMention {
-            raw_text: format!("{} {} {}",
+            raw_text: format!(
+                "{} {} {}",
                 case.input_dose.map(|d| d.to_string()).unwrap_or_default(),
                 case.input_drug_name,
                 case.input_route.unwrap_or("")
@@ -173,13 +174,17 @@ fn test_golden_cases() {
             species: None,
             start_offset: 0,
             end_offset: 0,
+            field_spans: Default::default(),
+            extraction_confidence: None,
+            speaker: None,
         };
 
         let normalized = normalizer.normalize(&mention);
 
         assert_eq!(
             normalized.normalized_name, case.expected_name,
-            "Case {}: name mismatch", case.id
+            "Case {}: name mismatch",
+            case.id
         );
 
         if let Some(expected_dose) = case.expected_dose {
@@ -187,18 +192,24 @@ fn test_golden_cases() {
             assert!(
                 (actual_dose - expected_dose).abs() < 0.001,
                 "Case {}: dose mismatch - expected {}, got {}",
-                case.id, expected_dose, actual_dose
+                case.id,
+                expected_dose,
+                actual_dose
             );
         }
 
         assert_eq!(
-            normalized.normalized_unit.as_deref(), case.expected_unit,
-            "Case {}: unit mismatch", case.id
+            normalized.normalized_unit.map(String::from).as_deref(),
+            case.expected_unit,
+            "Case {}: unit mismatch",
+            case.id
         );
 
         assert_eq!(
-            normalized.normalized_route.as_deref(), case.expected_route,
-            "Case {}: route mismatch", case.id
+            normalized.normalized_route.as_deref(),
+            case.expected_route,
+            "Case {}: route mismatch",
+            case.id
         );
     }
 }
@@ -296,7 +307,9 @@ fn test_all_unit_conversions() {
         assert!(
             (mult - expected_mult).abs() < 0.0001,
             "Unit {} multiplier should be {}, got {}",
-            from, expected_mult, mult
+            from,
+            expected_mult,
+            mult
         );
     }
 }
@@ -0,0 +1,318 @@
+//! Inference runtimes behind one extraction pipeline.
+//!
+//! Some deployment targets can't ship llama.cpp. A [`NerBackend`] only turns
+//! a prompt into generated text (plus per-token log-probabilities);
+//! everything around that — prompts, chunking, repair and retry, confidence,
+//! streaming previews — is shared by [`run_extraction`], so every runtime
+//! produces the same [`NerOutput`]. Backends:
+//!
+//! - `Extractor` (`llm` feature): llama.cpp, with grammar-constrained
+//!   sampling
+//! - `CandleBackend` (`candle` feature): pure Rust, no native library;
+//!   output is unconstrained and leans on repair
+
+use fuzzy_drugs_core::resolver::{ExtractedMentions, ResolverError, ResolverResult};
+use sha2::{Digest, Sha256};
+
+use crate::chunk::{extract_chunked, ChunkListener};
+use crate::confidence::{assign_confidence, TokenLogprobs};
+use crate::extraction::{to_service_mentions, ExtractionResult, NerOutput};
+use crate::llama::ExtractorConfig;
+use crate::prompts::JSON_GRAMMAR;
+use crate::repair::extract_with_retry;
+use crate::stream::{take_utf8, ExtractionListener, MentionStream};
+
+/// A model runtime that can complete an extraction prompt.
+pub trait NerBackend: Send + Sync {
+    /// Short runtime name for logs ("llama.cpp", "candle").
+    fn name(&self) -> &'static str;
+
+    /// Prompt, chunking, and generation settings.
+    fn config(&self) -> &ExtractorConfig;
+
+    /// Run `prompt` until the model ends its answer, feeding each token to
+    /// a [`TokenSink`] over `listener`. Fails with
+    /// [`ContextOverflow`](crate::ExtractionError::ContextOverflow) if the
+    /// prompt doesn't fit and [`Truncated`](crate::ExtractionError::Truncated)
+    /// if `max_tokens` runs out first.
+    fn generate(
+        &self,
+        prompt: &str,
+        listener: &dyn ExtractionListener,
+    ) -> ExtractionResult<(String, TokenLogprobs)>;
+}
+
+/// Extract the mentions in `transcript` with `backend`.
+///
+/// Output is repaired if needed; if that fails the prompt is run once more
+/// (see [`extract_with_retry`]), streaming to the same listener. Each
+/// mention's confidence comes from its tokens' log-probabilities (see
+/// [`crate::confidence`]). Long transcripts are run chunk by chunk, with
+/// offsets (streamed ones too) on the full transcript.
+pub fn run_extraction(
+    backend: &dyn NerBackend,
+    transcript: &str,
+    listener: &dyn ExtractionListener,
+) -> ExtractionResult<NerOutput> {
+    let config = backend.config();
+    extract_chunked(transcript, &config.chunking, |chunk| {
+        let listener = ChunkListener::new(listener, chunk);
+        let mut last = (String::new(), TokenLogprobs::new());
+        let mut output =
+            extract_with_retry(chunk.text, &config.prompt, config.few_shot, |prompt| {
+                last = backend.generate(prompt, &listener)?;
+                Ok(last.0.clone())
+            })?;
+        assign_confidence(&mut output, &last.0, &last.1);
+        Ok(output)
+    })
+}
+
+/// [`run_extraction`] for the core pipeline's `MentionExtractor::extract_all`.
+pub fn extract_mentions(
+    backend: &dyn NerBackend,
+    transcript: &str,
+) -> ResolverResult<ExtractedMentions> {
+    let output = run_extraction(backend, transcript, &())
+        .map_err(|e| ResolverError::Extraction(e.to_string()))?;
+    Ok(ExtractedMentions {
+        services: to_service_mentions(&output),
+        drugs: output.mentions.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Version of `backend`'s output, for the core's extraction cache
+/// (`MentionExtractor::version`): the runtime name plus a hash of the
+/// configuration (model path, generation, chunking, prompt options), of
+/// the model file's size and modification time, and of the prompt and
+/// grammar text. A model file replaced in place or any prompt change gives
+/// a new version; hashing the weights themselves would mean reading
+/// gigabytes per transcript.
+pub fn extractor_version(backend: &dyn NerBackend) -> String {
+    let config = backend.config();
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(config).unwrap_or_default());
+    let model_file = std::fs::metadata(&config.model_path)
+        .ok()
+        .map(|m| (m.len(), m.modified().ok()));
+    hasher.update(format!("{:?}", model_file));
+    hasher.update(config.prompt.build("", config.few_shot));
+    hasher.update(JSON_GRAMMAR);
+    format!(
+        "{}:{}",
+        backend.name(),
+        &hex::encode(hasher.finalize())[..16]
+    )
+}
+
+/// Collects a backend's generated tokens: the output text, each token's
+/// log-probability, and streaming to a listener (text as it decodes,
+/// mentions as their objects close).
+pub struct TokenSink<'a> {
+    listener: &'a dyn ExtractionListener,
+    stream: MentionStream,
+    logprobs: TokenLogprobs,
+    /// Bytes of a character split across tokens
+    pending: Vec<u8>,
+    generated_bytes: usize,
+}
+
+impl<'a> TokenSink<'a> {
+    pub fn new(listener: &'a dyn ExtractionListener) -> Self {
+        Self {
+            listener,
+            stream: MentionStream::new(),
+            logprobs: TokenLogprobs::new(),
+            pending: Vec::new(),
+            generated_bytes: 0,
+        }
+    }
+
+    /// Add one generated token's bytes and, if known, its log-probability.
+    /// Fails if the listener cancels.
+    pub fn push(&mut self, bytes: &[u8], logprob: Option<f64>) -> ExtractionResult<()> {
+        self.generated_bytes += bytes.len();
+        if let Some(logprob) = logprob {
+            self.logprobs.push(self.generated_bytes, logprob);
+        }
+        self.pending.extend_from_slice(bytes);
+        if let Some(piece) = take_utf8(&mut self.pending) {
+            self.listener.on_token(&piece)?;
+            for (span, mut mention) in self.stream.push_spanned(&piece) {
+                mention.confidence = self.logprobs.span_confidence(span);
+                self.listener.on_mention(&mention)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the output's top-level JSON object has closed.
+    pub fn is_complete(&self) -> bool {
+        self.stream.is_complete()
+    }
+
+    /// The whole output (a trailing partial character included, lossily)
+    /// and its token log-probabilities.
+    pub fn finish(self) -> (String, TokenLogprobs) {
+        let mut output = self.stream.text().to_string();
+        output.push_str(&String::from_utf8_lossy(&self.pending));
+        (output, self.logprobs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::extraction::{ExtractionError, RawMention};
+    use crate::prompts::PromptBuilder;
+    use crate::stream::Cancelled;
+
+    /// Replays canned answers, one per prompt, a few bytes per token.
+    struct ScriptedBackend {
+        config: ExtractorConfig,
+        answers: Mutex<Vec<&'static str>>,
+    }
+
+    impl ScriptedBackend {
+        fn new(answers: &[&'static str]) -> Self {
+            Self {
+                config: ExtractorConfig::new("scripted"),
+                answers: Mutex::new(answers.iter().rev().copied().collect()),
+            }
+        }
+    }
+
+    impl NerBackend for ScriptedBackend {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn config(&self) -> &ExtractorConfig {
+            &self.config
+        }
+
+        fn generate(
+            &self,
+            _prompt: &str,
+            listener: &dyn ExtractionListener,
+        ) -> ExtractionResult<(String, TokenLogprobs)> {
+            let answer = self.answers.lock().unwrap().pop().expect("no answer left");
+            let mut sink = TokenSink::new(listener);
+            for token in answer.as_bytes().chunks(3) {
+                sink.push(token, Some(-0.1))?;
+            }
+            Ok(sink.finish())
+        }
+    }
+
+    #[derive(Default)]
+    struct Collect {
+        mentions: Mutex<Vec<RawMention>>,
+        cancel: bool,
+    }
+
+    impl ExtractionListener for Collect {
+        fn on_token(&self, _text: &str) -> Result<(), Cancelled> {
+            if self.cancel {
+                Err(Cancelled)
+            } else {
+                Ok(())
+            }
+        }
+
+        fn on_mention(&self, mention: &RawMention) -> Result<(), Cancelled> {
+            self.mentions.lock().unwrap().push(mention.clone());
+            Ok(())
+        }
+    }
+
+    const TRANSCRIPT: &str = "Give rimadyl 75 mg PO and trim her nails.";
+    const ANSWER: &str = concat!(
+        r#"{"mentions":[{"raw_text":"rimadyl 75 mg PO","drug_name":"rimadyl","dose":75,"#,
+        r#""unit":"mg","route":"PO","start_offset":5,"end_offset":21}],"#,
+        r#""services":[{"raw_text":"trim her nails","service_name":"nail trim","#,
+        r#""kind":"procedure","quantity":null,"start_offset":26,"end_offset":40}]}"#,
+    );
+
+    #[test]
+    fn test_run_extraction_streams_and_scores() {
+        let backend = ScriptedBackend::new(&[ANSWER]);
+        let listener = Collect::default();
+        let output = run_extraction(&backend, TRANSCRIPT, &listener).unwrap();
+
+        assert_eq!(output.mentions.len(), 1);
+        assert_eq!(output.mentions[0].drug_name, "rimadyl");
+        let confidence = output.mentions[0].confidence.unwrap();
+        assert!((confidence - (-0.1_f64).exp()).abs() < 1e-9);
+        assert_eq!(output.services.len(), 1);
+
+        let streamed = listener.mentions.lock().unwrap();
+        assert_eq!(streamed.len(), 1);
+        assert_eq!(streamed[0].confidence, output.mentions[0].confidence);
+
+        let backend = ScriptedBackend::new(&[ANSWER]);
+        let mentions = extract_mentions(&backend, TRANSCRIPT).unwrap();
+        assert_eq!(mentions.drugs[0].drug_name, "rimadyl");
+        assert_eq!(mentions.services[0].service_name, "nail trim");
+    }
+
+    #[test]
+    fn test_run_extraction_retries_and_cancels() {
+        // Unrepairable first answer; the retry prompt gets the good one
+        let backend = ScriptedBackend::new(&["I found rimadyl.", ANSWER]);
+        let output = run_extraction(&backend, TRANSCRIPT, &()).unwrap();
+        assert_eq!(output.mentions.len(), 1);
+
+        let backend = ScriptedBackend::new(&[ANSWER]);
+        let listener = Collect {
+            cancel: true,
+            ..Default::default()
+        };
+        let err = run_extraction(&backend, TRANSCRIPT, &listener).unwrap_err();
+        assert!(matches!(err, ExtractionError::Cancelled(_)));
+    }
+
+    #[test]
+    fn test_extractor_version() {
+        let backend = ScriptedBackend::new(&[]);
+        let version = extractor_version(&backend);
+        assert!(version.starts_with("scripted:"));
+        assert_eq!(version, extractor_version(&ScriptedBackend::new(&[])));
+
+        let mut hinted = ScriptedBackend::new(&[]);
+        hinted.config.prompt = PromptBuilder::new().with_alias_hint("carp", "carprofen");
+        assert_ne!(extractor_version(&hinted), version);
+        let mut sampled = ScriptedBackend::new(&[]);
+        sampled.config.generation.temperature = 0.7;
+        assert_ne!(extractor_version(&sampled), version);
+
+        // A model file replaced in place with one of the same size
+        let dir = tempfile::tempdir().unwrap();
+        let mut on_disk = ScriptedBackend::new(&[]);
+        on_disk.config.model_path = dir.path().join("model.gguf");
+        std::fs::write(&on_disk.config.model_path, b"weights-1").unwrap();
+        let first = extractor_version(&on_disk);
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&on_disk.config.model_path)
+            .unwrap();
+        std::io::Write::write_all(&mut &file, b"weights-2").unwrap();
+        file.set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_ne!(extractor_version(&on_disk), first);
+    }
+
+    #[test]
+    fn test_token_sink_holds_split_characters() {
+        let mut sink = TokenSink::new(&());
+        let bytes = "é}".as_bytes();
+        sink.push(&bytes[..1], None).unwrap();
+        sink.push(&bytes[1..], Some(-0.5)).unwrap();
+        assert!(!sink.is_complete());
+        let (text, logprobs) = sink.finish();
+        assert_eq!(text, "é}");
+        assert_eq!(logprobs.len(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+//! candle extraction backend (`candle` feature).
+//!
+//! [`CandleBackend`] runs the same GGUF Llama model as
+//! [`Extractor`](crate::Extractor) in pure Rust, for targets that can't ship
+//! llama.cpp. candle has no grammar-constrained sampling, so generation stops
+//! as soon as the top-level JSON object closes (or at an end token) and
+//! anything malformed is left to repair and retry. The tokenizer comes from a
+//! separate `tokenizer.json`. Runs on the CPU; `gpu_layers` and `threads` in
+//! the [`ExtractorConfig`] are ignored.
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::Mutex;
+
+use candle_core::quantized::gguf_file;
+use candle_core::{DType, Device, Tensor};
+use candle_transformers::generation::{LogitsProcessor, Sampling};
+use candle_transformers::models::quantized_llama::ModelWeights;
+use candle_transformers::utils::apply_repeat_penalty;
+use fuzzy_drugs_core::models;
+use fuzzy_drugs_core::resolver::{ExtractedMentions, MentionExtractor, ResolverResult};
+use tokenizers::Tokenizer;
+
+use crate::backend::{extract_mentions, extractor_version, run_extraction, NerBackend, TokenSink};
+use crate::confidence::{logprob_of, TokenLogprobs};
+use crate::extraction::{ExtractionError, ExtractionResult, NerOutput};
+use crate::llama::{ExtractorConfig, REPEAT_PENALTY_LAST_N};
+use crate::stream::ExtractionListener;
+
+/// Tokens that end an answer, in the chat templates we've shipped models with.
+const END_TOKENS: &[&str] = &["<|end|>", "<|eot_id|>", "<|end_of_text|>", "</s>"];
+
+/// NER extraction with a local GGUF model, without llama.cpp.
+///
+/// The weights hold one KV cache, so extractions on one backend run one at a
+/// time; each starts the cache over.
+pub struct CandleBackend {
+    model: Mutex<ModelWeights>,
+    tokenizer: Tokenizer,
+    end_tokens: Vec<u32>,
+    device: Device,
+    config: ExtractorConfig,
+}
+
+impl CandleBackend {
+    /// Load the model named by `config` and the tokenizer at `tokenizer_path`.
+    pub fn load(
+        config: ExtractorConfig,
+        tokenizer_path: impl AsRef<Path>,
+    ) -> ExtractionResult<Self> {
+        config.validate()?;
+        let model_load = |path: &Path, reason: String| ExtractionError::ModelLoad {
+            path: path.display().to_string(),
+            reason,
+        };
+        let model_path = config.model_path.as_path();
+        let tokenizer_path = tokenizer_path.as_ref();
+        for path in [model_path, tokenizer_path] {
+            if !path.is_file() {
+                return Err(model_load(path, "file not found".into()));
+            }
+        }
+
+        let device = Device::Cpu;
+        let mut file = File::open(model_path).map_err(|e| model_load(model_path, e.to_string()))?;
+        let content = gguf_file::Content::read(&mut file)
+            .map_err(|e| model_load(model_path, e.to_string()))?;
+        let model = ModelWeights::from_gguf(content, &mut file, &device)
+            .map_err(|e| model_load(model_path, e.to_string()))?;
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| model_load(tokenizer_path, e.to_string()))?;
+        let end_tokens = END_TOKENS
+            .iter()
+            .filter_map(|token| tokenizer.token_to_id(token))
+            .collect();
+
+        Ok(Self {
+            model: Mutex::new(model),
+            tokenizer,
+            end_tokens,
+            device,
+            config,
+        })
+    }
+
+    /// Extract the drug mentions in `transcript`.
+    pub fn extract(&self, transcript: &str) -> ExtractionResult<NerOutput> {
+        self.extract_streaming(transcript, &())
+    }
+
+    /// Like [`extract`](Self::extract), reporting generated text and
+    /// completed mentions to `listener` as they arrive (see
+    /// [`run_extraction`]).
+    pub fn extract_streaming(
+        &self,
+        transcript: &str,
+        listener: &dyn ExtractionListener,
+    ) -> ExtractionResult<NerOutput> {
+        run_extraction(self, transcript, listener)
+    }
+}
+
+impl NerBackend for CandleBackend {
+    fn name(&self) -> &'static str {
+        "candle"
+    }
+
+    fn config(&self) -> &ExtractorConfig {
+        &self.config
+    }
+
+    /// Run `prompt` until the JSON object closes or an end token,
+    /// recording the log-probability of each generated token.
+    fn generate(
+        &self,
+        prompt: &str,
+        listener: &dyn ExtractionListener,
+    ) -> ExtractionResult<(String, TokenLogprobs)> {
+        let inference = |e: &dyn std::fmt::Display| ExtractionError::Inference(e.to_string());
+        let config = &self.config;
+        let generation = &config.generation;
+
+        let tokens = self
+            .tokenizer
+            .encode(prompt, true)
+            .map_err(|e| inference(&e))?
+            .get_ids()
+            .to_vec();
+        config.check_prompt_fits(tokens.len())?;
+
+        let temperature = f64::from(generation.temperature);
+        let sampling = if generation.temperature <= 0.0 {
+            Sampling::ArgMax
+        } else if generation.top_p < 1.0 {
+            Sampling::TopP {
+                p: f64::from(generation.top_p),
+                temperature,
+            }
+        } else {
+            Sampling::All { temperature }
+        };
+        let mut sampler = LogitsProcessor::from_sampling(u64::from(generation.seed), sampling);
+
+        let mut model = self.model.lock().unwrap_or_else(|e| e.into_inner());
+        let forward =
+            |model: &mut ModelWeights, tokens: &[u32], pos: usize| -> candle_core::Result<Tensor> {
+                let input = Tensor::new(tokens, &self.device)?.unsqueeze(0)?;
+                model.forward(&input, pos)?.squeeze(0)?.to_dtype(DType::F32)
+            };
+        // Position 0 starts the KV cache over
+        let mut logits = forward(&mut model, &tokens, 0).map_err(|e| inference(&e))?;
+
+        let mut sink = TokenSink::new(listener);
+        let mut generated = Vec::new();
+        let mut decoded_len = 0;
+        for pos in tokens.len()..tokens.len() + generation.max_tokens as usize {
+            let penalized = if generation.repeat_penalty == 1.0 {
+                logits.clone()
+            } else {
+                let recent = &generated[generated.len().saturating_sub(REPEAT_PENALTY_LAST_N)..];
+                apply_repeat_penalty(&logits, generation.repeat_penalty, recent)
+                    .map_err(|e| inference(&e))?
+            };
+            let token = sampler.sample(&penalized).map_err(|e| inference(&e))?;
+            if self.end_tokens.contains(&token) {
+                return Ok(sink.finish());
+            }
+            let logits_vec = logits.to_vec1::<f32>().map_err(|e| inference(&e))?;
+            let logprob = logprob_of(&logits_vec, token as usize);
+
+            // Tokens don't decode on their own (byte-level pieces, leading
+            // spaces), so decode everything generated and pass on what's new.
+            // A token that ends mid-character adds nothing until the next one.
+            generated.push(token);
+            let text = self
+                .tokenizer
+                .decode(&generated, true)
+                .map_err(|e| inference(&e))?;
+            let piece = match text.get(decoded_len..) {
+                Some(piece) if !piece.ends_with('\u{FFFD}') => piece,
+                _ => "",
+            };
+            decoded_len += piece.len();
+            sink.push(piece.as_bytes(), logprob)?;
+            if sink.is_complete() {
+                return Ok(sink.finish());
+            }
+
+            logits = forward(&mut model, &[token], pos).map_err(|e| inference(&e))?;
+        }
+
+        Err(ExtractionError::Truncated {
+            max_tokens: generation.max_tokens,
+        })
+    }
+}
+
+/// Lets the candle backend feed the core pipeline (`Resolver::stage_transcript`).
+impl MentionExtractor for CandleBackend {
+    fn extract(&self, transcript: &str) -> ResolverResult<Vec<models::DrugMention>> {
+        Ok(extract_mentions(self, transcript)?.drugs)
+    }
+
+    fn extract_all(&self, transcript: &str) -> ResolverResult<ExtractedMentions> {
+        extract_mentions(self, transcript)
+    }
+
+    fn version(&self) -> Option<String> {
+        Some(extractor_version(self))
+    }
+}
@@ -0,0 +1,435 @@
+//! Transcript chunking for dictations longer than the context window.
+//!
+//! [`chunk_transcript`] splits a transcript on sentence boundaries into
+//! pieces of at most [`ChunkConfig::max_bytes`], each overlapping the
+//! previous one by up to [`ChunkConfig::overlap_bytes`] so a mention at a
+//! boundary is seen whole at least once. [`extract_chunked`] runs extraction
+//! per chunk, moves offsets back onto the full transcript, and drops the
+//! duplicates found twice in an overlap.
+
+use std::ops::Range;
+
+use fuzzy_drugs_core::models::SourceSpan;
+use serde::{Deserialize, Serialize};
+
+use crate::extraction::{ExtractionError, ExtractionResult, NerOutput, RawMention, RawService};
+use crate::stream::{Cancelled, ExtractionListener};
+
+/// Default largest chunk, in bytes (about 1,500 tokens of English).
+pub const DEFAULT_CHUNK_BYTES: usize = 6000;
+
+/// Default overlap between consecutive chunks, in bytes.
+pub const DEFAULT_CHUNK_OVERLAP: usize = 400;
+
+/// How to split long transcripts. Sizes are in bytes of UTF-8 text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkConfig {
+    /// Largest chunk sent to the model
+    pub max_bytes: usize,
+    /// Most text repeated at the start of the next chunk (whole sentences)
+    pub overlap_bytes: usize,
+}
+
+impl Default for ChunkConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_CHUNK_BYTES,
+            overlap_bytes: DEFAULT_CHUNK_OVERLAP,
+        }
+    }
+}
+
+impl ChunkConfig {
+    pub fn validate(&self) -> ExtractionResult<()> {
+        if self.max_bytes == 0 {
+            return Err(ExtractionError::Config(
+                "chunk size must be at least 1".into(),
+            ));
+        }
+        if self.overlap_bytes >= self.max_bytes {
+            return Err(ExtractionError::Config(format!(
+                "chunk overlap ({}) must be smaller than the chunk size ({})",
+                self.overlap_bytes, self.max_bytes
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// One piece of a transcript.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TranscriptChunk<'a> {
+    /// Byte offset of the chunk in the full transcript
+    pub start: usize,
+    pub text: &'a str,
+}
+
+impl TranscriptChunk<'_> {
+    /// Byte offset just past the chunk in the full transcript.
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    /// Move a mention's offsets (chunk-relative, as the model gives them)
+    /// onto the full transcript, clamping them to the chunk first.
+    pub fn remap(&self, mention: &mut RawMention) {
+        let clamp = |offset: usize| self.start + offset.min(self.text.len());
+        mention.start_offset = clamp(mention.start_offset);
+        mention.end_offset = clamp(mention.end_offset).max(mention.start_offset);
+
+        let spans = &mut mention.field_spans;
+        for span in [
+            &mut spans.drug,
+            &mut spans.dose,
+            &mut spans.unit,
+            &mut spans.route,
+            &mut spans.frequency,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *span = SourceSpan {
+                start_offset: clamp(span.start_offset),
+                end_offset: clamp(span.end_offset),
+            };
+        }
+    }
+
+    /// Move a service mention's offsets onto the full transcript, like
+    /// [`remap`](Self::remap).
+    pub fn remap_service(&self, service: &mut RawService) {
+        let clamp = |offset: usize| self.start + offset.min(self.text.len());
+        service.start_offset = clamp(service.start_offset);
+        service.end_offset = clamp(service.end_offset).max(service.start_offset);
+    }
+}
+
+/// Split `transcript` into chunks of at most `config.max_bytes`,
+/// ending on sentence boundaries where possible. A transcript that fits is
+/// returned as one chunk.
+pub fn chunk_transcript<'a>(transcript: &'a str, config: &ChunkConfig) -> Vec<TranscriptChunk<'a>> {
+    let max_bytes = config.max_bytes.max(1);
+    if transcript.len() <= max_bytes {
+        return vec![TranscriptChunk {
+            start: 0,
+            text: transcript,
+        }];
+    }
+
+    let pieces: Vec<Range<usize>> = sentences(transcript)
+        .into_iter()
+        .flat_map(|sentence| split_long(transcript, sentence, max_bytes))
+        .collect();
+
+    let mut chunks = Vec::new();
+    let mut first = 0;
+    while first < pieces.len() {
+        let start = pieces[first].start;
+        let mut next = first;
+        while next < pieces.len() && pieces[next].end - start <= max_bytes {
+            next += 1;
+        }
+        // A piece that doesn't fit on its own still makes a chunk
+        let next = next.max(first + 1);
+        let end = pieces[next - 1].end;
+        chunks.push(TranscriptChunk {
+            start,
+            text: &transcript[start..end],
+        });
+        if next == pieces.len() {
+            break;
+        }
+
+        // Repeat trailing sentences that fit in the overlap, always moving forward
+        let mut overlap = next;
+        while overlap > first + 1 && end - pieces[overlap - 1].start <= config.overlap_bytes {
+            overlap -= 1;
+        }
+        first = overlap;
+    }
+    chunks
+}
+
+/// Contiguous sentence ranges: each ends after `.`, `!`, or `?` followed by
+/// whitespace, or after a line break.
+fn sentences(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let boundary = match c {
+            '\n' => true,
+            '.' | '!' | '?' => chars.peek().is_some_and(|(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+        if boundary {
+            // Keep the following whitespace with this sentence
+            let mut end = i + c.len_utf8();
+            while let Some(&(j, next)) = chars.peek() {
+                if !next.is_whitespace() {
+                    break;
+                }
+                end = j + next.len_utf8();
+                chars.next();
+            }
+            ranges.push(start..end);
+            start = end;
+        }
+    }
+    if start < text.len() {
+        ranges.push(start..text.len());
+    }
+    ranges
+}
+
+/// Split a sentence longer than `max_bytes` at whitespace (or, failing
+/// that, at a character boundary).
+fn split_long(text: &str, range: Range<usize>, max_bytes: usize) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = range.start;
+    while range.end - start > max_bytes {
+        let mut limit = start + max_bytes;
+        while !text.is_char_boundary(limit) {
+            limit -= 1;
+        }
+        let end = match text[start..limit].rfind(char::is_whitespace) {
+            Some(space) if space > 0 => start + space + 1,
+            _ if limit > start => limit,
+            // A single character wider than the chunk
+            _ => start + text[start..].chars().next().map_or(1, char::len_utf8),
+        };
+        pieces.push(start..end);
+        start = end;
+    }
+    pieces.push(start..range.end);
+    pieces
+}
+
+/// Run `extract` over each chunk of `transcript` and merge the results:
+/// offsets are moved onto the full transcript, and a mention found in two
+/// chunks (same drug, overlapping spans) is kept once, preferring the more
+/// confident or longer copy. Services are merged the same way, keeping the
+/// longer copy. Both come back in transcript order.
+pub fn extract_chunked(
+    transcript: &str,
+    config: &ChunkConfig,
+    mut extract: impl FnMut(TranscriptChunk<'_>) -> ExtractionResult<NerOutput>,
+) -> ExtractionResult<NerOutput> {
+    config.validate()?;
+    let mut mentions: Vec<RawMention> = Vec::new();
+    let mut services: Vec<RawService> = Vec::new();
+    for chunk in chunk_transcript(transcript, config) {
+        let output = extract(chunk)?;
+        for mut service in output.services {
+            chunk.remap_service(&mut service);
+            let found = services.iter_mut().find(|kept| {
+                overlaps(
+                    (kept.start_offset, kept.end_offset),
+                    (service.start_offset, service.end_offset),
+                ) && same_name(&kept.service_name, &service.service_name)
+            });
+            match found {
+                Some(kept) if span_len(&service) > span_len(kept) => *kept = service,
+                Some(_) => {}
+                None => services.push(service),
+            }
+        }
+        for mut mention in output.mentions {
+            chunk.remap(&mut mention);
+            match mentions
+                .iter_mut()
+                .find(|kept| is_duplicate(kept, &mention))
+            {
+                Some(kept) if prefer(&mention, kept) => *kept = mention,
+                Some(_) => {}
+                None => mentions.push(mention),
+            }
+        }
+    }
+    mentions.sort_by_key(|m| (m.start_offset, m.end_offset));
+    services.sort_by_key(|s| (s.start_offset, s.end_offset));
+    Ok(NerOutput { mentions, services })
+}
+
+fn is_duplicate(a: &RawMention, b: &RawMention) -> bool {
+    overlaps(
+        (a.start_offset, a.end_offset),
+        (b.start_offset, b.end_offset),
+    ) && same_name(&a.drug_name, &b.drug_name)
+}
+
+/// Whether two spans overlap (or are the same empty span).
+fn overlaps(a: (usize, usize), b: (usize, usize)) -> bool {
+    (a.0 < b.1 && b.0 < a.1) || a == b
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+fn span_len(service: &RawService) -> usize {
+    service.end_offset - service.start_offset
+}
+
+/// Whether `candidate` should replace its duplicate `kept`.
+fn prefer(candidate: &RawMention, kept: &RawMention) -> bool {
+    match (candidate.confidence, kept.confidence) {
+        (Some(a), Some(b)) if a != b => a > b,
+        _ => candidate.end_offset - candidate.start_offset > kept.end_offset - kept.start_offset,
+    }
+}
+
+/// Forwards to another listener with streamed mentions moved onto the full
+/// transcript.
+pub struct ChunkListener<'a> {
+    listener: &'a dyn ExtractionListener,
+    chunk: TranscriptChunk<'a>,
+}
+
+impl<'a> ChunkListener<'a> {
+    pub fn new(listener: &'a dyn ExtractionListener, chunk: TranscriptChunk<'a>) -> Self {
+        Self { listener, chunk }
+    }
+}
+
+impl ExtractionListener for ChunkListener<'_> {
+    fn on_token(&self, text: &str) -> Result<(), Cancelled> {
+        self.listener.on_token(text)
+    }
+
+    fn on_mention(&self, mention: &RawMention) -> Result<(), Cancelled> {
+        let mut mention = mention.clone();
+        self.chunk.remap(&mut mention);
+        self.listener.on_mention(&mention)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::MockExtractor;
+
+    fn config(max_bytes: usize, overlap_bytes: usize) -> ChunkConfig {
+        ChunkConfig {
+            max_bytes,
+            overlap_bytes,
+        }
+    }
+
+    #[test]
+    fn test_chunks_end_on_sentences_and_overlap() {
+        let transcript = "Patient is bright. Give carprofen 100mg PO. Recheck in two weeks. \
+                          Owner asked about metacam. Continue gabapentin nightly.";
+        let chunks = chunk_transcript(transcript, &config(60, 30));
+        assert!(chunks.len() > 1);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.text.len() <= 60, "{:?}", chunk);
+            assert_eq!(&transcript[chunk.start..chunk.end()], chunk.text);
+            if i + 1 < chunks.len() {
+                let ends_sentence = chunk.text.trim_end().ends_with('.');
+                assert!(ends_sentence, "{:?}", chunk);
+                // Each chunk starts within (or right after) the previous one
+                assert!(chunks[i + 1].start <= chunk.end());
+                assert!(chunks[i + 1].start > chunk.start);
+            }
+        }
+        assert_eq!(chunks.first().unwrap().start, 0);
+        assert_eq!(chunks.last().unwrap().end(), transcript.len());
+
+        // Short transcripts are left whole
+        let whole = chunk_transcript("Give carprofen.", &ChunkConfig::default());
+        assert_eq!(whole.len(), 1);
+    }
+
+    #[test]
+    fn test_long_sentence_is_split() {
+        let transcript = "µ".repeat(10) + &" word".repeat(20);
+        let chunks = chunk_transcript(&transcript, &config(16, 4));
+        for chunk in &chunks {
+            assert!(
+                !chunk.text.is_empty() && chunk.text.len() <= 16,
+                "{:?}",
+                chunk
+            );
+        }
+        assert_eq!(chunks.last().unwrap().end(), transcript.len());
+    }
+
+    #[test]
+    fn test_extract_chunked_remaps_and_dedupes() {
+        let transcript = "Patient is bright and alert today. Give carprofen 100mg PO. \
+                          Radiographs in two weeks. Send home metacam for the cat.";
+        let config = config(70, 40);
+        let chunks = chunk_transcript(transcript, &config);
+        assert!(chunks.len() > 2);
+
+        let mut calls = 0;
+        let output = extract_chunked(transcript, &config, |chunk| {
+            calls += 1;
+            Ok(MockExtractor::extract(chunk.text))
+        })
+        .unwrap();
+        assert_eq!(calls, chunks.len());
+
+        // Carprofen sits in an overlap but is reported once, at its real offset
+        let names: Vec<&str> = output
+            .mentions
+            .iter()
+            .map(|m| m.drug_name.as_str())
+            .collect();
+        assert_eq!(names, ["carprofen", "meloxicam"]);
+        let starts: Vec<usize> = output.mentions.iter().map(|m| m.start_offset).collect();
+        assert_eq!(
+            starts,
+            [
+                transcript.find("carprofen").unwrap(),
+                transcript.find("metacam").unwrap()
+            ]
+        );
+        for mention in &output.mentions {
+            let drug = mention.field_spans.drug.as_ref().unwrap();
+            assert_eq!(drug.start_offset, mention.start_offset);
+            assert_eq!(drug.end_offset, mention.end_offset);
+        }
+        let services: Vec<usize> = output.services.iter().map(|s| s.start_offset).collect();
+        assert_eq!(services, [transcript.find("Radiographs").unwrap()]);
+
+        assert!(extract_chunked(
+            transcript,
+            &ChunkConfig {
+                max_bytes: 10,
+                overlap_bytes: 10
+            },
+            |_| { unreachable!() }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_chunk_listener_remaps_mentions() {
+        use std::cell::RefCell;
+
+        struct Collect(RefCell<Vec<usize>>);
+        impl ExtractionListener for Collect {
+            fn on_token(&self, _text: &str) -> Result<(), Cancelled> {
+                Ok(())
+            }
+            fn on_mention(&self, mention: &RawMention) -> Result<(), Cancelled> {
+                self.0.borrow_mut().push(mention.start_offset);
+                Ok(())
+            }
+        }
+
+        let transcript = "Intro. Give carprofen.";
+        let collect = Collect(RefCell::new(Vec::new()));
+        let chunk = TranscriptChunk {
+            start: 7,
+            text: &transcript[7..],
+        };
+        let mention = MockExtractor::extract(chunk.text).mentions.remove(0);
+        ChunkListener::new(&collect, chunk)
+            .on_mention(&mention)
+            .unwrap();
+        assert_eq!(*collect.0.borrow(), [12]);
+    }
+}
@@ -0,0 +1,125 @@
+//! Per-mention confidence from token log-probabilities.
+//!
+//! While generating, the extractor records the log-probability the model
+//! gave each token it produced ([`TokenLogprobs`]). A mention's confidence
+//! is the geometric mean probability of the tokens making up its JSON
+//! object, i.e. `exp(mean logprob)`: 1.0 when the model never hesitated,
+//! lower when it was choosing between alternatives.
+
+use std::ops::Range;
+
+use crate::extraction::NerOutput;
+use crate::stream::MentionStream;
+
+/// Log-probabilities of generated tokens, keyed by where each token ends in
+/// the output text (in bytes).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenLogprobs {
+    tokens: Vec<(usize, f64)>,
+}
+
+impl TokenLogprobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a token covering the output up to byte `end`.
+    pub fn push(&mut self, end: usize, logprob: f64) {
+        self.tokens.push((end, logprob));
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Confidence (0.0 - 1.0) of the output bytes in `span`: the geometric
+    /// mean probability of every token overlapping it. `None` if no
+    /// recorded token does.
+    pub fn span_confidence(&self, span: Range<usize>) -> Option<f64> {
+        let mut start = 0;
+        let mut sum = 0.0;
+        let mut count = 0;
+        for &(end, logprob) in &self.tokens {
+            if start < span.end && end > span.start {
+                sum += logprob;
+                count += 1;
+            }
+            start = end;
+        }
+        (count > 0).then(|| (sum / count as f64).exp().clamp(0.0, 1.0))
+    }
+}
+
+/// Log-probability of `token` under raw `logits` (a log-softmax over the
+/// vocabulary). `None` if the token is out of range or the logits are
+/// unusable.
+pub fn logprob_of(logits: &[f32], token: usize) -> Option<f64> {
+    let logit = f64::from(*logits.get(token)?);
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max) as f64;
+    if !max.is_finite() {
+        return None;
+    }
+    let sum: f64 = logits.iter().map(|&l| (f64::from(l) - max).exp()).sum();
+    let logprob = logit - max - sum.ln();
+    logprob.is_finite().then_some(logprob)
+}
+
+/// Set each mention's confidence from the tokens of its object in `text`
+/// (the generated output it was parsed from). Mentions are matched to
+/// objects by drug name and raw text, in order; ones with no complete
+/// object (rebuilt by repair) are left without a confidence.
+pub fn assign_confidence(output: &mut NerOutput, text: &str, logprobs: &TokenLogprobs) {
+    let mut objects = MentionStream::new().push_spanned(text);
+    for mention in &mut output.mentions {
+        let found = objects.iter().position(|(_, object)| {
+            object.drug_name == mention.drug_name && object.raw_text == mention.raw_text
+        });
+        if let Some(index) = found {
+            let (span, _) = objects.remove(index);
+            mention.confidence = logprobs.span_confidence(span);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::parse_ner_output;
+
+    #[test]
+    fn test_logprob_of() {
+        let logits = [2.0_f32, 2.0, f32::NEG_INFINITY];
+        assert!((logprob_of(&logits, 0).unwrap() - 0.5_f64.ln()).abs() < 1e-9);
+        assert_eq!(logprob_of(&logits, 2), None);
+        assert_eq!(logprob_of(&logits, 3), None);
+        assert_eq!(logprob_of(&[], 0), None);
+    }
+
+    #[test]
+    fn test_assign_confidence_per_mention() {
+        let first = r#"{"raw_text":"carprofen","drug_name":"carprofen","dose":null,"unit":null,"route":null,"species":null,"start_offset":0,"end_offset":9}"#;
+        let second = r#"{"raw_text":"metacam","drug_name":"metacam","dose":null,"unit":null,"route":null,"species":null,"start_offset":14,"end_offset":21}"#;
+        let text = format!(r#"{{"mentions":[{},{}]}}"#, first, second);
+
+        // One token per object: sure of the first, unsure of the second
+        let first_end = text.find(first).unwrap() + first.len();
+        let mut logprobs = TokenLogprobs::new();
+        logprobs.push(first_end, 0.0);
+        logprobs.push(text.len(), 0.25_f64.ln());
+
+        let mut output = parse_ner_output(&text).unwrap();
+        assign_confidence(&mut output, &text, &logprobs);
+        assert_eq!(output.mentions[0].confidence, Some(1.0));
+        assert!((output.mentions[1].confidence.unwrap() - 0.25).abs() < 1e-9);
+
+        // A mention the output doesn't contain gets none
+        let mut other = parse_ner_output(&format!(r#"{{"mentions":[{}]}}"#, second)).unwrap();
+        other.mentions[0].drug_name = "meloxicam".into();
+        assign_confidence(&mut other, &text, &logprobs);
+        assert_eq!(other.mentions[0].confidence, None);
+    }
+}
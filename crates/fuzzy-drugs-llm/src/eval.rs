@@ -0,0 +1,489 @@
+//! Extraction quality evaluation against golden transcripts.
+//!
+//! Before shipping a model update, run it over a directory of fixtures
+//! (transcripts with the mentions a reviewer expects) and compare. Each
+//! fixture is a JSON file:
+//!
+//! ```json
+//! {
+//!   "transcript": "Give 100mg carprofen PO",
+//!   "expected": [{"drug_name": "carprofen", "dose": 100, "unit": "mg", "route": "PO"}]
+//! }
+//! ```
+//!
+//! Extracted mentions are paired with expected ones by drug name, then
+//! precision, recall, and F1 are computed per field (name, dose, unit,
+//! route), per fixture and over the whole set. The [`EvalReport`] serializes
+//! to JSON for comparing runs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::backend::{run_extraction, NerBackend};
+use crate::extraction::{ExtractionResult, NerOutput, RawMention};
+
+/// Doses closer than this count as equal.
+const DOSE_TOLERANCE: f64 = 1e-6;
+
+/// Evaluation errors.
+#[derive(Error, Debug)]
+pub enum EvalError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid fixture {file}: {reason}")]
+    Fixture { file: String, reason: String },
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type EvalResult<T> = Result<T, EvalError>;
+
+/// A mention a golden transcript should yield.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpectedMention {
+    pub drug_name: String,
+    #[serde(default)]
+    pub dose: Option<f64>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub route: Option<String>,
+}
+
+/// A transcript and its expected mentions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoldenTranscript {
+    /// Fixture file stem; filled in by [`load_fixtures`]
+    #[serde(default)]
+    pub name: String,
+    pub transcript: String,
+    pub expected: Vec<ExpectedMention>,
+}
+
+/// Load every `*.json` fixture in `dir`, in file name order.
+pub fn load_fixtures(dir: &Path) -> EvalResult<Vec<GoldenTranscript>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let fixture_error = |reason: String| EvalError::Fixture {
+                file: path.display().to_string(),
+                reason,
+            };
+            let mut fixture: GoldenTranscript = serde_json::from_slice(&fs::read(path)?)
+                .map_err(|e| fixture_error(e.to_string()))?;
+            if fixture
+                .expected
+                .iter()
+                .any(|m| m.drug_name.trim().is_empty())
+            {
+                return Err(fixture_error(
+                    "expected mention with an empty drug_name".into(),
+                ));
+            }
+            let stem = path.file_stem().unwrap_or_default();
+            fixture.name = stem.to_string_lossy().into_owned();
+            Ok(fixture)
+        })
+        .collect()
+}
+
+/// True positive, false positive, and false negative counts for one field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldCounts {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+}
+
+impl FieldCounts {
+    /// Share of extracted values that were right; 1.0 if none were
+    /// extracted.
+    pub fn precision(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// Share of expected values that were extracted; 1.0 if none were
+    /// expected.
+    pub fn recall(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+
+    pub fn f1(&self) -> f64 {
+        let (p, r) = (self.precision(), self.recall());
+        if p + r == 0.0 {
+            0.0
+        } else {
+            2.0 * p * r / (p + r)
+        }
+    }
+
+    fn add(&mut self, other: &FieldCounts) {
+        self.true_positives += other.true_positives;
+        self.false_positives += other.false_positives;
+        self.false_negatives += other.false_negatives;
+    }
+
+    /// Score one expected/extracted value pair (either may be absent).
+    fn record<T: Copy>(
+        &mut self,
+        expected: Option<T>,
+        extracted: Option<T>,
+        same: impl Fn(T, T) -> bool,
+    ) {
+        match (expected, extracted) {
+            (Some(e), Some(x)) if same(e, x) => self.true_positives += 1,
+            (Some(_), Some(_)) => {
+                self.false_positives += 1;
+                self.false_negatives += 1;
+            }
+            (Some(_), None) => self.false_negatives += 1,
+            (None, Some(_)) => self.false_positives += 1,
+            (None, None) => {}
+        }
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        1.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Counts with precision, recall, and F1, as reported.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldScore {
+    #[serde(flatten)]
+    pub counts: FieldCounts,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+impl From<FieldCounts> for FieldScore {
+    fn from(counts: FieldCounts) -> Self {
+        Self {
+            counts,
+            precision: counts.precision(),
+            recall: counts.recall(),
+            f1: counts.f1(),
+        }
+    }
+}
+
+/// Counts for each scored field.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct FieldTally {
+    name: FieldCounts,
+    dose: FieldCounts,
+    unit: FieldCounts,
+    route: FieldCounts,
+}
+
+impl FieldTally {
+    fn add(&mut self, other: &FieldTally) {
+        self.name.add(&other.name);
+        self.dose.add(&other.dose);
+        self.unit.add(&other.unit);
+        self.route.add(&other.route);
+    }
+}
+
+/// Scores for each field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldScores {
+    pub name: FieldScore,
+    pub dose: FieldScore,
+    pub unit: FieldScore,
+    pub route: FieldScore,
+}
+
+impl From<FieldTally> for FieldScores {
+    fn from(tally: FieldTally) -> Self {
+        Self {
+            name: tally.name.into(),
+            dose: tally.dose.into(),
+            unit: tally.unit.into(),
+            route: tally.route.into(),
+        }
+    }
+}
+
+/// How one fixture went.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixtureReport {
+    pub name: String,
+    pub expected: usize,
+    pub extracted: usize,
+    pub scores: FieldScores,
+    /// Why extraction failed (every expected mention then counts as missed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Results of a run over a fixture set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// Backend that extracted ("llama.cpp", "candle", ...)
+    pub backend: String,
+    /// When the run finished (RFC 3339)
+    pub created_at: String,
+    /// Over all fixtures (micro-averaged)
+    pub scores: FieldScores,
+    pub fixtures: Vec<FixtureReport>,
+    /// Fixtures whose extraction failed
+    pub failures: usize,
+}
+
+impl EvalReport {
+    pub fn to_json(&self) -> EvalResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn write_json(&self, path: &Path) -> EvalResult<()> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+/// Run `backend` over each fixture and score the results.
+pub fn evaluate_backend(backend: &dyn NerBackend, fixtures: &[GoldenTranscript]) -> EvalReport {
+    evaluate(backend.name(), fixtures, |transcript| {
+        run_extraction(backend, transcript, &())
+    })
+}
+
+/// Run `extract` over each fixture and score the results; `label` names
+/// the extractor in the report.
+pub fn evaluate(
+    label: &str,
+    fixtures: &[GoldenTranscript],
+    mut extract: impl FnMut(&str) -> ExtractionResult<NerOutput>,
+) -> EvalReport {
+    let mut total = FieldTally::default();
+    let mut failures = 0;
+    let fixtures = fixtures
+        .iter()
+        .map(|fixture| {
+            let (extracted, error) = match extract(&fixture.transcript) {
+                Ok(output) => (output.mentions, None),
+                Err(e) => {
+                    failures += 1;
+                    (Vec::new(), Some(e.to_string()))
+                }
+            };
+            let tally = score_fixture(&fixture.expected, &extracted);
+            total.add(&tally);
+            FixtureReport {
+                name: fixture.name.clone(),
+                expected: fixture.expected.len(),
+                extracted: extracted.len(),
+                scores: tally.into(),
+                error,
+            }
+        })
+        .collect();
+
+    EvalReport {
+        backend: label.to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        scores: total.into(),
+        fixtures,
+        failures,
+    }
+}
+
+/// Pair extracted mentions with expected ones by drug name (first
+/// unclaimed match, in order) and count each field.
+fn score_fixture(expected: &[ExpectedMention], extracted: &[RawMention]) -> FieldTally {
+    let mut tally = FieldTally::default();
+    let mut claimed = vec![false; extracted.len()];
+    for want in expected {
+        let found = extracted
+            .iter()
+            .enumerate()
+            .find(|(i, got)| !claimed[*i] && same_text(&want.drug_name, &got.drug_name));
+        let Some((i, got)) = found else {
+            tally.name.false_negatives += 1;
+            tally.dose.record(want.dose, None, same_dose);
+            tally.unit.record(want.unit.as_deref(), None, same_text);
+            tally.route.record(want.route.as_deref(), None, same_text);
+            continue;
+        };
+        claimed[i] = true;
+        tally.name.true_positives += 1;
+        tally.dose.record(want.dose, got.dose, same_dose);
+        tally
+            .unit
+            .record(want.unit.as_deref(), got.unit.as_deref(), same_text);
+        tally
+            .route
+            .record(want.route.as_deref(), got.route.as_deref(), same_text);
+    }
+    for got in extracted
+        .iter()
+        .zip(&claimed)
+        .filter(|(_, c)| !**c)
+        .map(|(m, _)| m)
+    {
+        tally.name.false_positives += 1;
+        tally.dose.record(None, got.dose, same_dose);
+        tally.unit.record(None, got.unit.as_deref(), same_text);
+        tally.route.record(None, got.route.as_deref(), same_text);
+    }
+    tally
+}
+
+fn same_text(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+fn same_dose(a: f64, b: f64) -> bool {
+    (a - b).abs() < DOSE_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::{ExtractionError, MockExtractor};
+
+    fn expected(
+        name: &str,
+        dose: Option<f64>,
+        unit: Option<&str>,
+        route: Option<&str>,
+    ) -> ExpectedMention {
+        ExpectedMention {
+            drug_name: name.into(),
+            dose,
+            unit: unit.map(Into::into),
+            route: route.map(Into::into),
+        }
+    }
+
+    fn counts(tp: usize, fp: usize, fn_: usize) -> FieldCounts {
+        FieldCounts {
+            true_positives: tp,
+            false_positives: fp,
+            false_negatives: fn_,
+        }
+    }
+
+    #[test]
+    fn test_load_fixtures() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("b_metacam.json"),
+            r#"{"transcript":"metacam","expected":[{"drug_name":"metacam"}]}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("a_carprofen.json"),
+            r#"{"transcript":"100mg carprofen PO","expected":[
+                {"drug_name":"carprofen","dose":100,"unit":"mg","route":"PO"}]}"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("notes.txt"), "not a fixture").unwrap();
+
+        let fixtures = load_fixtures(dir.path()).unwrap();
+        let names: Vec<&str> = fixtures.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a_carprofen", "b_metacam"]);
+        assert_eq!(fixtures[0].expected[0].dose, Some(100.0));
+        assert_eq!(fixtures[1].expected[0].route, None);
+
+        fs::write(dir.path().join("c_bad.json"), r#"{"transcript":"x"}"#).unwrap();
+        let err = load_fixtures(dir.path()).unwrap_err();
+        assert!(matches!(err, EvalError::Fixture { ref file, .. } if file.ends_with("c_bad.json")));
+    }
+
+    #[test]
+    fn test_score_fixture_fields() {
+        let expected = [
+            expected("carprofen", Some(100.0), Some("mg"), Some("PO")),
+            expected("cerenia", Some(1.0), Some("mL"), Some("SQ")),
+            expected("metacam", None, None, None),
+        ];
+        let extracted = crate::parse_ner_output(
+            r#"{"mentions":[
+            {"raw_text":"","drug_name":"Carprofen","dose":100,"unit":"MG","route":"IV","species":null,"start_offset":0,"end_offset":0},
+            {"raw_text":"","drug_name":"cerenia","dose":0.5,"unit":"mL","route":null,"species":null,"start_offset":0,"end_offset":0},
+            {"raw_text":"","drug_name":"baytril","dose":null,"unit":null,"route":"PO","species":null,"start_offset":0,"end_offset":0}]}"#,
+        )
+        .unwrap()
+        .mentions;
+
+        let tally = score_fixture(&expected, &extracted);
+        // metacam missed, baytril spurious
+        assert_eq!(tally.name, counts(2, 1, 1));
+        // cerenia's dose is wrong: both a false positive and a false negative
+        assert_eq!(tally.dose, counts(1, 1, 1));
+        assert_eq!(tally.unit, counts(2, 0, 0));
+        // carprofen wrong route, cerenia missing route, baytril spurious route
+        assert_eq!(tally.route, counts(0, 2, 2));
+
+        let name = FieldScore::from(tally.name);
+        assert!((name.precision - 2.0 / 3.0).abs() < 1e-9);
+        assert!((name.recall - 2.0 / 3.0).abs() < 1e-9);
+        assert!((name.f1 - 2.0 / 3.0).abs() < 1e-9);
+        let route = FieldScore::from(tally.route);
+        assert_eq!((route.precision, route.recall, route.f1), (0.0, 0.0, 0.0));
+        // Nothing expected or extracted is a perfect score
+        assert_eq!(FieldCounts::default().f1(), 1.0);
+    }
+
+    #[test]
+    fn test_evaluate_report() {
+        let fixtures = vec![
+            GoldenTranscript {
+                name: "carprofen".into(),
+                transcript: "Give 100mg carprofen PO".into(),
+                expected: vec![expected("carprofen", Some(100.0), Some("mg"), Some("PO"))],
+            },
+            GoldenTranscript {
+                name: "broken".into(),
+                transcript: "cerenia".into(),
+                expected: vec![expected("cerenia", None, None, None)],
+            },
+        ];
+        let report = evaluate("mock", &fixtures, |transcript| {
+            if transcript == "cerenia" {
+                return Err(ExtractionError::Inference("decode failed".into()));
+            }
+            Ok(MockExtractor::extract(transcript))
+        });
+
+        assert_eq!(report.backend, "mock");
+        assert_eq!(report.failures, 1);
+        assert_eq!(report.fixtures[0].scores.dose.f1, 1.0);
+        assert_eq!(
+            report.fixtures[1].error.as_deref(),
+            Some("LLM inference error: decode failed")
+        );
+        assert_eq!(report.scores.name.counts.false_negatives, 1);
+        assert_eq!(report.scores.name.recall, 0.5);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["scores"]["name"]["true_positives"], 1);
+        assert_eq!(json["scores"]["name"]["precision"], 1.0);
+        assert_eq!(json["fixtures"][1]["name"], "broken");
+        assert!(json["fixtures"][0].get("error").is_none());
+    }
+}
@@ -1,5 +1,9 @@
 //! Drug mention extraction from LLM output.
 
+use fuzzy_drugs_core::models::{self, FieldSpans, SourceSpan};
+use fuzzy_drugs_core::resolver::{
+    parse_spoken_number, ExtractedMentions, MentionExtractor, ResolverResult,
+};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -14,6 +18,24 @@ pub enum ExtractionError {
 
     #[error("LLM inference error: {0}")]
     Inference(String),
+
+    #[error("Failed to load model {path}: {reason}")]
+    ModelLoad { path: String, reason: String },
+
+    #[error("Invalid extractor configuration: {0}")]
+    Config(String),
+
+    #[error("Grammar error: {0}")]
+    Grammar(String),
+
+    #[error("Prompt is {tokens} tokens but only {limit} fit in the context")]
+    ContextOverflow { tokens: usize, limit: usize },
+
+    #[error("Output not finished within {max_tokens} tokens")]
+    Truncated { max_tokens: u32 },
+
+    #[error("Extraction cancelled")]
+    Cancelled(#[from] crate::stream::Cancelled),
 }
 
 pub type ExtractionResult<T> = Result<T, ExtractionError>;
@@ -22,6 +44,9 @@ pub type ExtractionResult<T> = Result<T, ExtractionError>;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NerOutput {
     pub mentions: Vec<RawMention>,
+    /// Procedures, vaccines, and diagnostics (absent from older outputs)
+    #[serde(default)]
+    pub services: Vec<RawService>,
 }
 
 /// A raw drug mention extracted by the LLM.
@@ -35,14 +60,39 @@ pub struct RawMention {
     pub species: Option<String>,
     pub start_offset: usize,
     pub end_offset: usize,
+    /// Offsets of the drug, dose, unit, route, and frequency tokens, when the
+    /// model gives them. Unvalidated; see `Normalizer::field_spans`.
+    #[serde(default)]
+    pub field_spans: FieldSpans,
+    /// How sure the model was of this mention (0.0 - 1.0), from the
+    /// log-probabilities of its tokens; filled in by the extractor, not
+    /// generated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+}
+
+/// A raw service mention (procedure, vaccine, or diagnostic) extracted by
+/// the LLM.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawService {
+    pub raw_text: String,
+    pub service_name: String,
+    /// "procedure", "vaccine", or "diagnostic"
+    pub kind: Option<String>,
+    pub quantity: Option<f64>,
+    pub start_offset: usize,
+    pub end_offset: usize,
 }
 
 /// Parse LLM output JSON into structured mentions.
+///
+/// Strict: any malformed JSON fails. Model output goes through
+/// [`parse_ner_output_repaired`](crate::repair::parse_ner_output_repaired).
 pub fn parse_ner_output(json: &str) -> ExtractionResult<NerOutput> {
     // Try to find JSON in the response (in case LLM adds extra text)
-    let json_start = json.find('{').ok_or_else(|| {
-        ExtractionError::InvalidFormat("No JSON object found in response".into())
-    })?;
+    let json_start = json
+        .find('{')
+        .ok_or_else(|| ExtractionError::InvalidFormat("No JSON object found in response".into()))?;
     let json_end = json.rfind('}').ok_or_else(|| {
         ExtractionError::InvalidFormat("No closing brace found in response".into())
     })?;
@@ -67,10 +117,24 @@ pub fn to_drug_mentions(ner_output: &NerOutput) -> Vec<DrugMention> {
             species: m.species.clone(),
             start_offset: m.start_offset,
             end_offset: m.end_offset,
+            field_spans: m.field_spans.clone(),
+            extraction_confidence: m.confidence,
+            speaker: None,
         })
         .collect()
 }
 
+/// Convert raw service mentions to the core's format. Kinds the core
+/// doesn't recognize become unknown.
+pub fn to_service_mentions(ner_output: &NerOutput) -> Vec<models::ServiceMention> {
+    ner_output
+        .services
+        .iter()
+        .cloned()
+        .map(Into::into)
+        .collect()
+}
+
 /// Drug mention in resolver-compatible format.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DrugMention {
@@ -82,6 +146,12 @@ pub struct DrugMention {
     pub species: Option<String>,
     pub start_offset: usize,
     pub end_offset: usize,
+    #[serde(default)]
+    pub field_spans: FieldSpans,
+    #[serde(default)]
+    pub extraction_confidence: Option<f64>,
+    #[serde(default)]
+    pub speaker: Option<models::SpeakerRole>,
 }
 
 /// Mock extractor for testing without actual LLM inference.
@@ -122,7 +192,9 @@ impl MockExtractor {
                 let end_pos = pos + pattern.len();
 
                 mentions.push(RawMention {
-                    raw_text: transcript[pos.saturating_sub(10)..std::cmp::min(end_pos + 10, transcript.len())].to_string(),
+                    raw_text: transcript
+                        [pos.saturating_sub(10)..std::cmp::min(end_pos + 10, transcript.len())]
+                        .to_string(),
                     drug_name,
                     dose,
                     unit,
@@ -130,11 +202,88 @@ impl MockExtractor {
                     species: None,
                     start_offset: pos,
                     end_offset: end_pos,
+                    field_spans: FieldSpans {
+                        drug: Some(SourceSpan {
+                            start_offset: pos,
+                            end_offset: end_pos,
+                        }),
+                        ..Default::default()
+                    },
+                    confidence: None,
                 });
             }
         }
 
-        NerOutput { mentions }
+        let services = [
+            ("nail trim", "procedure"),
+            ("rabies vaccine", "vaccine"),
+            ("radiographs", "diagnostic"),
+        ]
+        .into_iter()
+        .filter_map(|(pattern, kind)| {
+            let pos = transcript_lower.find(pattern)?;
+            Some(RawService {
+                raw_text: transcript[pos..pos + pattern.len()].to_string(),
+                service_name: pattern.to_string(),
+                kind: Some(kind.to_string()),
+                quantity: None,
+                start_offset: pos,
+                end_offset: pos + pattern.len(),
+            })
+        })
+        .collect();
+
+        NerOutput { mentions, services }
+    }
+}
+
+/// Lets the mock extractor feed the core pipeline (`Resolver::stage_transcript`).
+impl MentionExtractor for MockExtractor {
+    fn extract(&self, transcript: &str) -> ResolverResult<Vec<models::DrugMention>> {
+        Ok(MockExtractor::extract(transcript)
+            .mentions
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    fn extract_all(&self, transcript: &str) -> ResolverResult<ExtractedMentions> {
+        let output = MockExtractor::extract(transcript);
+        Ok(ExtractedMentions {
+            services: to_service_mentions(&output),
+            drugs: output.mentions.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+impl From<RawMention> for models::DrugMention {
+    fn from(m: RawMention) -> Self {
+        Self {
+            raw_text: m.raw_text,
+            drug_name: m.drug_name,
+            dose: m.dose,
+            unit: m.unit,
+            route: m.route,
+            species: m.species,
+            start_offset: m.start_offset,
+            end_offset: m.end_offset,
+            field_spans: m.field_spans,
+            extraction_confidence: m.confidence,
+            speaker: None,
+        }
+    }
+}
+
+impl From<RawService> for models::ServiceMention {
+    fn from(s: RawService) -> Self {
+        Self {
+            raw_text: s.raw_text,
+            service_name: s.service_name,
+            kind: s.kind.as_deref().and_then(models::ServiceKind::parse),
+            quantity: s.quantity,
+            start_offset: s.start_offset,
+            end_offset: s.end_offset,
+        }
     }
 }
 
@@ -177,7 +326,55 @@ fn extract_dose(text: &str) -> (Option<f64>, Option<String>) {
         }
     }
 
-    (None, None)
+    // Spelled-out quantities: "two point five mils", "half a tablet"
+    extract_spoken_dose(&words)
+}
+
+/// Unit words accepted after a spelled-out quantity.
+const SPOKEN_UNITS: [&str; 18] = [
+    "mg",
+    "milligram",
+    "milligrams",
+    "mcg",
+    "ml",
+    "mil",
+    "mils",
+    "milliliter",
+    "milliliters",
+    "cc",
+    "units",
+    "tablet",
+    "tablets",
+    "tab",
+    "tabs",
+    "capsule",
+    "capsules",
+    "g",
+];
+
+/// Spelled-out dose ending the text before a drug name ("give half a tablet of").
+fn extract_spoken_dose(words: &[&str]) -> (Option<f64>, Option<String>) {
+    let mut end = words.len();
+    while end > 0 && words[end - 1] == "of" {
+        end -= 1;
+    }
+    let Some(unit) = end.checked_sub(1).map(|i| words[i]) else {
+        return (None, None);
+    };
+    if !SPOKEN_UNITS.contains(&unit) {
+        return (None, None);
+    }
+
+    // "half of a tablet"
+    end -= 1;
+    while end > 0 && matches!(words[end - 1], "a" | "an" | "of") {
+        end -= 1;
+    }
+
+    // Earliest start gives the longest number ("one and a half", not "a half")
+    (0..end)
+        .find_map(|start| parse_spoken_number(&words[start..end].join(" ")))
+        .map_or((None, None), |dose| (Some(dose), Some(unit.to_string())))
 }
 
 /// Simple route extraction from text after drug name.
@@ -240,6 +437,9 @@ mod tests {
         assert_eq!(output.mentions[0].dose, Some(100.0));
         assert_eq!(output.mentions[0].unit, Some("mg".to_string()));
         assert_eq!(output.mentions[0].route, Some("PO".to_string()));
+
+        let drug = output.mentions[0].field_spans.drug.unwrap();
+        assert_eq!(&transcript[drug.start_offset..drug.end_offset], "carprofen");
     }
 
     #[test]
@@ -252,6 +452,47 @@ mod tests {
         assert_eq!(output.mentions[0].drug_name, "carprofen");
     }
 
+    #[test]
+    fn test_mock_extractor_as_mention_extractor() {
+        let extractor: &dyn MentionExtractor = &MockExtractor;
+        let mentions = extractor.extract("Give 100mg carprofen orally").unwrap();
+
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(mentions[0].drug_name, "carprofen");
+        assert_eq!(mentions[0].dose, Some(100.0));
+    }
+
+    #[test]
+    fn test_mock_extractor_services() {
+        let extractor: &dyn MentionExtractor = &MockExtractor;
+        let extracted = extractor
+            .extract_all("Rabies vaccine today, then 100mg carprofen PO")
+            .unwrap();
+
+        assert_eq!(extracted.drugs.len(), 1);
+        assert_eq!(extracted.services.len(), 1);
+        assert_eq!(extracted.services[0].service_name, "rabies vaccine");
+        assert_eq!(
+            extracted.services[0].kind,
+            Some(models::ServiceKind::Vaccine)
+        );
+        assert_eq!(extracted.services[0].start_offset, 0);
+    }
+
+    #[test]
+    fn test_parse_ner_output_services() {
+        let json = r#"{"mentions":[],"services":[{"raw_text":"nail trim","service_name":"nail trim","kind":"procedure","quantity":null,"start_offset":4,"end_offset":13}]}"#;
+
+        let output = parse_ner_output(json).unwrap();
+        assert_eq!(output.services.len(), 1);
+        let services = to_service_mentions(&output);
+        assert_eq!(services[0].kind, Some(models::ServiceKind::Procedure));
+
+        // Outputs from before services were extracted still parse
+        let output = parse_ner_output(r#"{"mentions":[]}"#).unwrap();
+        assert!(output.services.is_empty());
+    }
+
     #[test]
     fn test_mock_extractor_multiple() {
         let transcript = "Give carprofen and also metacam";
@@ -262,10 +503,32 @@ mod tests {
 
     #[test]
     fn test_extract_dose() {
-        assert_eq!(extract_dose("give 100mg"), (Some(100.0), Some("mg".to_string())));
-        assert_eq!(extract_dose("give 0.5 ml"), (Some(0.5), Some("ml".to_string())));
-        assert_eq!(extract_dose("give 2cc"), (Some(2.0), Some("cc".to_string())));
+        assert_eq!(
+            extract_dose("give 100mg"),
+            (Some(100.0), Some("mg".to_string()))
+        );
+        assert_eq!(
+            extract_dose("give 0.5 ml"),
+            (Some(0.5), Some("ml".to_string()))
+        );
+        assert_eq!(
+            extract_dose("give 2cc"),
+            (Some(2.0), Some("cc".to_string()))
+        );
         assert_eq!(extract_dose("give the dog"), (None, None));
+        assert_eq!(
+            extract_dose("give two point five mils of"),
+            (Some(2.5), Some("mils".to_string()))
+        );
+        assert_eq!(
+            extract_dose("give her half a tablet of"),
+            (Some(0.5), Some("tablet".to_string()))
+        );
+        assert_eq!(
+            extract_dose("one and a half tabs"),
+            (Some(1.5), Some("tabs".to_string()))
+        );
+        assert_eq!(extract_dose("give the two dogs"), (None, None));
     }
 
     #[test]
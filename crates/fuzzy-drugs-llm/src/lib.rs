@@ -3,8 +3,30 @@
 //! This crate provides Named Entity Recognition (NER) for veterinary drug mentions
 //! using Llama 3.2 models via llama.cpp bindings.
 
-pub mod prompts;
+pub mod backend;
+#[cfg(feature = "candle")]
+pub mod candle;
+pub mod chunk;
+pub mod confidence;
+pub mod eval;
 pub mod extraction;
+pub mod llama;
+pub mod model_manager;
+pub mod prompts;
+pub mod repair;
+pub mod stream;
+pub mod transcribe;
 
+pub use backend::*;
+#[cfg(feature = "candle")]
+pub use candle::CandleBackend;
+pub use chunk::*;
+pub use confidence::*;
+pub use eval::*;
 pub use extraction::*;
+pub use llama::*;
+pub use model_manager::*;
 pub use prompts::*;
+pub use repair::*;
+pub use stream::*;
+pub use transcribe::*;
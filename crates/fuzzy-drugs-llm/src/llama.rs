@@ -0,0 +1,461 @@
+//! llama.cpp extraction engine.
+//!
+//! [`Extractor`] (`llm` feature) loads a GGUF model once and runs the NER
+//! prompt against each transcript, with sampling constrained by
+//! [`JSON_GRAMMAR`](crate::prompts::JSON_GRAMMAR) so the output parses as a
+//! [`NerOutput`](crate::NerOutput). Transcripts too long for one prompt
+//! are extracted in overlapping chunks (see [`crate::chunk`]). It is the
+//! llama.cpp [`NerBackend`](crate::NerBackend). [`ExtractorConfig`] and
+//! [`GenerationParams`] are always available (and shared by every backend),
+//! so hosts can build and validate configuration without the native library.
+
+use std::path::PathBuf;
+
+use fuzzy_drugs_core::models::SamplingSettings;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::ChunkConfig;
+use crate::extraction::{ExtractionError, ExtractionResult};
+use crate::prompts::PromptBuilder;
+
+/// Default context window, in tokens.
+pub const DEFAULT_CONTEXT_SIZE: u32 = 4096;
+
+/// Default cap on generated tokens per transcript.
+pub const DEFAULT_MAX_TOKENS: u32 = 1024;
+
+/// How many recent tokens the repeat penalty looks back over.
+pub const REPEAT_PENALTY_LAST_N: usize = 64;
+
+/// Sampling and length limits for one generation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationParams {
+    /// Most tokens to generate before giving up
+    pub max_tokens: u32,
+    /// 0.0 samples greedily (deterministic)
+    pub temperature: f32,
+    /// Nucleus sampling cutoff for non-greedy sampling (1.0 is off)
+    #[serde(default = "one")]
+    pub top_p: f32,
+    /// Penalty on the last [`REPEAT_PENALTY_LAST_N`] tokens (1.0 is off).
+    /// JSON output repeats its keys, so keep this close to 1.
+    #[serde(default = "one")]
+    pub repeat_penalty: f32,
+    /// Seed for non-greedy sampling
+    pub seed: u32,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+impl Default for GenerationParams {
+    fn default() -> Self {
+        Self {
+            max_tokens: DEFAULT_MAX_TOKENS,
+            temperature: 0.0,
+            top_p: 1.0,
+            repeat_penalty: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+/// The sampling parameters stored in the core's settings
+/// (`Database::sampling_settings`).
+impl From<&SamplingSettings> for GenerationParams {
+    fn from(settings: &SamplingSettings) -> Self {
+        Self {
+            max_tokens: settings.max_tokens,
+            temperature: settings.temperature as f32,
+            top_p: settings.top_p as f32,
+            repeat_penalty: settings.repeat_penalty as f32,
+            seed: settings.seed,
+        }
+    }
+}
+
+/// How to load the model and run extraction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtractorConfig {
+    /// GGUF model file
+    pub model_path: PathBuf,
+    /// Context window in tokens (prompt plus output)
+    pub context_size: u32,
+    /// Layers to offload to the GPU (0 = CPU only)
+    pub gpu_layers: u32,
+    /// Worker threads; `None` lets llama.cpp decide
+    pub threads: Option<u32>,
+    /// Include the few-shot examples in the prompt
+    pub few_shot: bool,
+    /// Clinic alias hints, extra examples, and locale
+    #[serde(default)]
+    pub prompt: PromptBuilder,
+    pub generation: GenerationParams,
+    /// How long transcripts are split
+    #[serde(default)]
+    pub chunking: ChunkConfig,
+}
+
+impl ExtractorConfig {
+    /// Defaults for the model at `model_path`.
+    pub fn new(model_path: impl Into<PathBuf>) -> Self {
+        Self {
+            model_path: model_path.into(),
+            context_size: DEFAULT_CONTEXT_SIZE,
+            gpu_layers: 0,
+            threads: None,
+            few_shot: true,
+            prompt: PromptBuilder::default(),
+            generation: GenerationParams::default(),
+            chunking: ChunkConfig::default(),
+        }
+    }
+
+    /// Reject settings llama.cpp would fail on (or silently misbehave with).
+    pub fn validate(&self) -> ExtractionResult<()> {
+        let generation = &self.generation;
+        if generation.max_tokens == 0 {
+            return Err(ExtractionError::Config(
+                "max_tokens must be at least 1".into(),
+            ));
+        }
+        if generation.max_tokens >= self.context_size {
+            return Err(ExtractionError::Config(format!(
+                "max_tokens ({}) must be smaller than the context size ({})",
+                generation.max_tokens, self.context_size
+            )));
+        }
+        if !generation.temperature.is_finite() || generation.temperature < 0.0 {
+            return Err(ExtractionError::Config(format!(
+                "temperature must be a non-negative number, got {}",
+                generation.temperature
+            )));
+        }
+        if !(generation.top_p > 0.0 && generation.top_p <= 1.0) {
+            return Err(ExtractionError::Config(format!(
+                "top_p must be in (0, 1], got {}",
+                generation.top_p
+            )));
+        }
+        if !generation.repeat_penalty.is_finite() || generation.repeat_penalty <= 0.0 {
+            return Err(ExtractionError::Config(format!(
+                "repeat_penalty must be a positive number, got {}",
+                generation.repeat_penalty
+            )));
+        }
+        if self.threads == Some(0) {
+            return Err(ExtractionError::Config("threads must be at least 1".into()));
+        }
+        self.prompt.validate()?;
+        self.chunking.validate()
+    }
+
+    /// Fail with [`ExtractionError::ContextOverflow`] unless a prompt of
+    /// `prompt_tokens` leaves room for `max_tokens` of output.
+    pub fn check_prompt_fits(&self, prompt_tokens: usize) -> ExtractionResult<()> {
+        let limit = self.context_size.saturating_sub(self.generation.max_tokens) as usize;
+        if prompt_tokens > limit {
+            return Err(ExtractionError::ContextOverflow {
+                tokens: prompt_tokens,
+                limit,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "llm")]
+pub use engine::Extractor;
+
+#[cfg(feature = "llm")]
+mod engine {
+    use std::num::NonZeroU32;
+    use std::sync::{Mutex, OnceLock};
+
+    use fuzzy_drugs_core::models;
+    use fuzzy_drugs_core::resolver::{ExtractedMentions, MentionExtractor, ResolverResult};
+    use llama_cpp_2::context::params::LlamaContextParams;
+    use llama_cpp_2::llama_backend::LlamaBackend;
+    use llama_cpp_2::llama_batch::LlamaBatch;
+    use llama_cpp_2::model::params::LlamaModelParams;
+    use llama_cpp_2::model::{AddBos, LlamaModel, Special};
+    use llama_cpp_2::sampling::LlamaSampler;
+
+    use super::{ExtractorConfig, REPEAT_PENALTY_LAST_N};
+    use crate::backend::{
+        extract_mentions, extractor_version, run_extraction, NerBackend, TokenSink,
+    };
+    use crate::confidence::{logprob_of, TokenLogprobs};
+    use crate::extraction::{ExtractionError, ExtractionResult, NerOutput};
+    use crate::prompts::JSON_GRAMMAR;
+    use crate::stream::ExtractionListener;
+
+    /// llama.cpp may only be initialized once per process.
+    fn backend() -> ExtractionResult<&'static LlamaBackend> {
+        static BACKEND: OnceLock<LlamaBackend> = OnceLock::new();
+        static INIT: Mutex<()> = Mutex::new(());
+
+        let _guard = INIT.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(backend) = BACKEND.get() {
+            return Ok(backend);
+        }
+        let backend =
+            LlamaBackend::init().map_err(|e| ExtractionError::Inference(e.to_string()))?;
+        Ok(BACKEND.get_or_init(|| backend))
+    }
+
+    /// NER extraction with a local GGUF model.
+    ///
+    /// The model is loaded once; each [`extract`](Self::extract) call gets a
+    /// fresh context, so one extractor can serve several threads.
+    pub struct Extractor {
+        backend: &'static LlamaBackend,
+        model: LlamaModel,
+        config: ExtractorConfig,
+    }
+
+    impl Extractor {
+        /// Load the model named by `config`.
+        pub fn load(config: ExtractorConfig) -> ExtractionResult<Self> {
+            config.validate()?;
+            let model_load = |reason: String| ExtractionError::ModelLoad {
+                path: config.model_path.display().to_string(),
+                reason,
+            };
+            if !config.model_path.is_file() {
+                return Err(model_load("file not found".into()));
+            }
+
+            let backend = backend()?;
+            let params = LlamaModelParams::default().with_n_gpu_layers(config.gpu_layers);
+            let model = LlamaModel::load_from_file(backend, &config.model_path, &params)
+                .map_err(|e| model_load(e.to_string()))?;
+            Ok(Self {
+                backend,
+                model,
+                config,
+            })
+        }
+
+        pub fn config(&self) -> &ExtractorConfig {
+            &self.config
+        }
+
+        /// Extract the drug mentions in `transcript`.
+        pub fn extract(&self, transcript: &str) -> ExtractionResult<NerOutput> {
+            self.extract_streaming(transcript, &())
+        }
+
+        /// Like [`extract`](Self::extract), reporting generated text and
+        /// completed mentions to `listener` as they arrive. The listener can
+        /// cancel, which fails with [`ExtractionError::Cancelled`]. See
+        /// [`run_extraction`] for repair, retry, confidence, and chunking.
+        pub fn extract_streaming(
+            &self,
+            transcript: &str,
+            listener: &dyn ExtractionListener,
+        ) -> ExtractionResult<NerOutput> {
+            run_extraction(self, transcript, listener)
+        }
+    }
+
+    impl NerBackend for Extractor {
+        fn name(&self) -> &'static str {
+            "llama.cpp"
+        }
+
+        fn config(&self) -> &ExtractorConfig {
+            &self.config
+        }
+
+        /// Run `prompt` to an end-of-generation token under the JSON grammar,
+        /// recording the log-probability of each generated token.
+        fn generate(
+            &self,
+            prompt: &str,
+            listener: &dyn ExtractionListener,
+        ) -> ExtractionResult<(String, TokenLogprobs)> {
+            let inference = |e: &dyn std::fmt::Display| ExtractionError::Inference(e.to_string());
+            let config = &self.config;
+            let generation = &config.generation;
+
+            let tokens = self
+                .model
+                .str_to_token(prompt, AddBos::Always)
+                .map_err(|e| inference(&e))?;
+            config.check_prompt_fits(tokens.len())?;
+
+            let mut ctx_params =
+                LlamaContextParams::default().with_n_ctx(NonZeroU32::new(config.context_size));
+            if let Some(threads) = config.threads {
+                ctx_params = ctx_params
+                    .with_n_threads(threads as i32)
+                    .with_n_threads_batch(threads as i32);
+            }
+            let mut ctx = self
+                .model
+                .new_context(
+                    self.backend,
+                    ctx_params.with_n_batch(tokens.len().max(512) as u32),
+                )
+                .map_err(|e| inference(&e))?;
+
+            let grammar = LlamaSampler::grammar(&self.model, JSON_GRAMMAR, "root")
+                .map_err(|e| ExtractionError::Grammar(e.to_string()))?;
+            let mut samplers = vec![grammar];
+            if generation.repeat_penalty != 1.0 {
+                samplers.push(LlamaSampler::penalties(
+                    REPEAT_PENALTY_LAST_N as i32,
+                    generation.repeat_penalty,
+                    0.0,
+                    0.0,
+                ));
+            }
+            if generation.temperature <= 0.0 {
+                samplers.push(LlamaSampler::greedy());
+            } else {
+                if generation.top_p < 1.0 {
+                    samplers.push(LlamaSampler::top_p(generation.top_p, 1));
+                }
+                samplers.push(LlamaSampler::temp(generation.temperature));
+                samplers.push(LlamaSampler::dist(generation.seed));
+            }
+            let mut sampler = LlamaSampler::chain_simple(samplers);
+
+            let mut batch = LlamaBatch::new(tokens.len().max(1), 1);
+            let last = tokens.len() as i32 - 1;
+            for (pos, token) in (0_i32..).zip(tokens) {
+                batch
+                    .add(token, pos, &[0], pos == last)
+                    .map_err(|e| inference(&e))?;
+            }
+            ctx.decode(&mut batch).map_err(|e| inference(&e))?;
+
+            let mut sink = TokenSink::new(listener);
+            let mut pos = batch.n_tokens();
+            for _ in 0..generation.max_tokens {
+                let idx = batch.n_tokens() - 1;
+                let token = sampler.sample(&ctx, idx);
+                sampler.accept(token);
+                if self.model.is_eog_token(token) {
+                    return Ok(sink.finish());
+                }
+                let bytes = self
+                    .model
+                    .token_to_bytes(token, Special::Tokenize)
+                    .map_err(|e| inference(&e))?;
+                // Raw logits: the model's own belief, before the grammar
+                let logprob = logprob_of(ctx.get_logits_ith(idx), token.0 as usize);
+                sink.push(&bytes, logprob)?;
+
+                batch.clear();
+                batch
+                    .add(token, pos, &[0], true)
+                    .map_err(|e| inference(&e))?;
+                pos += 1;
+                ctx.decode(&mut batch).map_err(|e| inference(&e))?;
+            }
+
+            Err(ExtractionError::Truncated {
+                max_tokens: generation.max_tokens,
+            })
+        }
+    }
+
+    /// Lets the llama.cpp extractor feed the core pipeline (`Resolver::stage_transcript`).
+    impl MentionExtractor for Extractor {
+        fn extract(&self, transcript: &str) -> ResolverResult<Vec<models::DrugMention>> {
+            Ok(extract_mentions(self, transcript)?.drugs)
+        }
+
+        fn extract_all(&self, transcript: &str) -> ResolverResult<ExtractedMentions> {
+            extract_mentions(self, transcript)
+        }
+
+        fn version(&self) -> Option<String> {
+            Some(extractor_version(self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let config = ExtractorConfig::new("/models/llama-3.2-1b-q4.gguf");
+        assert!(config.validate().is_ok());
+        assert_eq!(config.generation.temperature, 0.0);
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_params() {
+        let mut config = ExtractorConfig::new("model.gguf");
+        config.generation.max_tokens = config.context_size;
+        assert!(matches!(config.validate(), Err(ExtractionError::Config(_))));
+
+        let mut config = ExtractorConfig::new("model.gguf");
+        config.generation.temperature = -0.5;
+        assert!(matches!(config.validate(), Err(ExtractionError::Config(_))));
+
+        let mut config = ExtractorConfig::new("model.gguf");
+        config.generation.top_p = 0.0;
+        assert!(matches!(config.validate(), Err(ExtractionError::Config(_))));
+
+        let mut config = ExtractorConfig::new("model.gguf");
+        config.generation.repeat_penalty = f32::NAN;
+        assert!(matches!(config.validate(), Err(ExtractionError::Config(_))));
+
+        let mut config = ExtractorConfig::new("model.gguf");
+        config.threads = Some(0);
+        assert!(matches!(config.validate(), Err(ExtractionError::Config(_))));
+
+        let mut config = ExtractorConfig::new("model.gguf");
+        config.prompt = PromptBuilder::new().with_locale("en GB");
+        assert!(matches!(config.validate(), Err(ExtractionError::Config(_))));
+
+        let mut config = ExtractorConfig::new("model.gguf");
+        config.chunking.overlap_bytes = config.chunking.max_bytes;
+        assert!(matches!(config.validate(), Err(ExtractionError::Config(_))));
+    }
+
+    #[test]
+    fn test_generation_params_from_settings() {
+        let defaults = GenerationParams::from(&SamplingSettings::default());
+        assert_eq!(defaults, GenerationParams::default());
+
+        let settings = SamplingSettings {
+            temperature: 0.7,
+            top_p: 0.9,
+            seed: 42,
+            ..Default::default()
+        };
+        let params = GenerationParams::from(&settings);
+        assert_eq!(
+            (params.temperature, params.top_p, params.seed),
+            (0.7, 0.9, 42)
+        );
+
+        // Configs saved before top-p and the repeat penalty existed
+        let old: GenerationParams =
+            serde_json::from_str(r#"{"max_tokens":512,"temperature":0.0,"seed":0}"#).unwrap();
+        assert_eq!((old.top_p, old.repeat_penalty), (1.0, 1.0));
+    }
+
+    #[test]
+    fn test_check_prompt_fits() {
+        let mut config = ExtractorConfig::new("model.gguf");
+        config.context_size = 2048;
+        config.generation.max_tokens = 512;
+        assert!(config.check_prompt_fits(1536).is_ok());
+        assert!(matches!(
+            config.check_prompt_fits(1537),
+            Err(ExtractionError::ContextOverflow {
+                tokens: 1537,
+                limit: 1536
+            })
+        ));
+    }
+}
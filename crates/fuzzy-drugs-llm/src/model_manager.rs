@@ -0,0 +1,619 @@
+//! Model file management.
+//!
+//! A [`ModelManager`] owns a directory of GGUF models. It records the
+//! installed model in `installed.json`, verifies the file against its
+//! SHA-256, installs new versions from a local file or (`download` feature)
+//! the [`ModelSpec::url`], and reads GGUF metadata (quantization, trained
+//! context length) so the app can gate features on what is installed.
+//!
+//! Installs are staged to a `.part` file and hashed while copying; only a
+//! file matching the spec's checksum replaces the installed model.
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use fuzzy_drugs_core::progress::{Cancelled, Progress};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::llama::{ExtractorConfig, DEFAULT_CONTEXT_SIZE};
+
+/// Manifest of the installed model, inside the model directory.
+pub const INSTALLED_MANIFEST: &str = "installed.json";
+
+/// Model management errors.
+#[derive(Error, Debug)]
+pub enum ModelError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("No model installed")]
+    NotInstalled,
+
+    #[error("Checksum mismatch for {file}: expected {expected}, got {actual}")]
+    Checksum {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Invalid GGUF file: {0}")]
+    InvalidGguf(String),
+
+    #[error("Download failed: {0}")]
+    Download(String),
+
+    #[error("Model install cancelled")]
+    Cancelled(#[from] Cancelled),
+}
+
+pub type ModelResult<T> = Result<T, ModelError>;
+
+/// A published model version (from the app's configuration or a release
+/// manifest).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelSpec {
+    /// Stable model name (e.g. "llama-3.2-1b-instruct-ner")
+    pub id: String,
+    pub version: String,
+    /// Where to download the GGUF file
+    pub url: String,
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    /// File size, for download progress when the server doesn't send one
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+}
+
+impl ModelSpec {
+    /// File name the model is installed under.
+    pub fn file_name(&self) -> String {
+        let safe = |s: &str| -> String {
+            s.chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect()
+        };
+        format!("{}-{}.gguf", safe(&self.id), safe(&self.version))
+    }
+}
+
+/// What the GGUF header says about a model.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelMetadata {
+    /// `general.architecture` (e.g. "llama")
+    pub architecture: Option<String>,
+    /// `general.name`
+    pub name: Option<String>,
+    /// Context length the model was trained for
+    pub context_length: Option<u32>,
+    /// Quantization from `general.file_type` (e.g. "Q4_K_M")
+    pub quantization: Option<String>,
+}
+
+/// The installed model, as recorded in [`INSTALLED_MANIFEST`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledModel {
+    pub spec: ModelSpec,
+    pub file_name: String,
+    pub size_bytes: u64,
+    /// RFC 3339 install time
+    pub installed_at: String,
+    pub metadata: ModelMetadata,
+}
+
+/// Whether a usable model is installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelStatus {
+    NotInstalled,
+    /// Recorded in the manifest but the file is gone
+    Missing(InstalledModel),
+    /// The file no longer matches its checksum
+    Corrupt(InstalledModel),
+    Ready(InstalledModel),
+}
+
+/// Tracks, verifies, and installs the model in one directory.
+pub struct ModelManager {
+    dir: PathBuf,
+}
+
+impl ModelManager {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The installed model according to the manifest (the file isn't checked).
+    pub fn installed(&self) -> ModelResult<Option<InstalledModel>> {
+        match fs::read(self.dir.join(INSTALLED_MANIFEST)) {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Path of the installed model file.
+    pub fn model_path(&self) -> ModelResult<PathBuf> {
+        let installed = self.installed()?.ok_or(ModelError::NotInstalled)?;
+        Ok(self.dir.join(installed.file_name))
+    }
+
+    /// Re-hash the installed file and report whether it can be used. Reads
+    /// the whole file; call at startup or after an update, not per request.
+    pub fn status(&self) -> ModelResult<ModelStatus> {
+        let Some(installed) = self.installed()? else {
+            return Ok(ModelStatus::NotInstalled);
+        };
+        let path = self.dir.join(&installed.file_name);
+        if !path.is_file() {
+            return Ok(ModelStatus::Missing(installed));
+        }
+        let actual = sha256_file(&path)?;
+        if actual.eq_ignore_ascii_case(&installed.spec.sha256) {
+            Ok(ModelStatus::Ready(installed))
+        } else {
+            Ok(ModelStatus::Corrupt(installed))
+        }
+    }
+
+    /// Whether `spec` differs from what is installed.
+    pub fn needs_update(&self, spec: &ModelSpec) -> ModelResult<bool> {
+        Ok(self.installed()?.is_none_or(|installed| {
+            installed.spec.version != spec.version
+                || !installed.spec.sha256.eq_ignore_ascii_case(&spec.sha256)
+        }))
+    }
+
+    /// Install `spec` from a local copy of its file.
+    pub fn install_file(&self, spec: &ModelSpec, source: &Path) -> ModelResult<InstalledModel> {
+        let file = File::open(source)?;
+        let total = file.metadata()?.len();
+        let staged = self.stage(spec, file, Some(total), &())?;
+        self.finish_install(spec, staged)
+    }
+
+    /// Download `spec.url` and install it, reporting bytes received to
+    /// `progress` (which can cancel).
+    #[cfg(feature = "download")]
+    pub fn download(
+        &self,
+        spec: &ModelSpec,
+        progress: &dyn Progress,
+    ) -> ModelResult<InstalledModel> {
+        let response = reqwest::blocking::Client::builder()
+            .timeout(None)
+            .build()
+            .and_then(|client| client.get(&spec.url).send())
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| ModelError::Download(e.to_string()))?;
+        let total = response.content_length().or(spec.size_bytes);
+        let staged = self.stage(spec, response, total, progress)?;
+        self.finish_install(spec, staged)
+    }
+
+    /// Extractor settings for the installed model, with the context window
+    /// capped at what the model was trained for.
+    pub fn extractor_config(&self) -> ModelResult<ExtractorConfig> {
+        let installed = self.installed()?.ok_or(ModelError::NotInstalled)?;
+        let mut config = ExtractorConfig::new(self.dir.join(&installed.file_name));
+        if let Some(trained) = installed.metadata.context_length {
+            config.context_size = trained.min(DEFAULT_CONTEXT_SIZE);
+        }
+        Ok(config)
+    }
+
+    /// Copy `source` to a `.part` file, hashing as it goes, and check the
+    /// result against the spec's checksum.
+    fn stage(
+        &self,
+        spec: &ModelSpec,
+        mut source: impl Read,
+        total: Option<u64>,
+        progress: &dyn Progress,
+    ) -> ModelResult<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let staged = self.dir.join(format!("{}.part", spec.file_name()));
+        let total_steps = total.unwrap_or(0) as usize;
+
+        let result = (|| -> ModelResult<String> {
+            let mut out = File::create(&staged)?;
+            let mut hasher = Sha256::new();
+            let mut buf = vec![0u8; 64 * 1024];
+            let mut done = 0usize;
+            progress.step(0, total_steps)?;
+            loop {
+                let n = source.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+                out.write_all(&buf[..n])?;
+                done += n;
+                progress.step(done, total_steps.max(done))?;
+            }
+            out.sync_all()?;
+            Ok(hex::encode(hasher.finalize()))
+        })();
+
+        let checked = result.and_then(|actual| {
+            if actual.eq_ignore_ascii_case(&spec.sha256) {
+                Ok(())
+            } else {
+                Err(ModelError::Checksum {
+                    file: spec.file_name(),
+                    expected: spec.sha256.clone(),
+                    actual,
+                })
+            }
+        });
+        if let Err(e) = checked {
+            let _ = fs::remove_file(&staged);
+            return Err(e);
+        }
+        Ok(staged)
+    }
+
+    /// Move a verified staged file into place, record it, and remove the
+    /// previously installed file.
+    fn finish_install(&self, spec: &ModelSpec, staged: PathBuf) -> ModelResult<InstalledModel> {
+        let metadata = match read_gguf_metadata(&staged) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                let _ = fs::remove_file(&staged);
+                return Err(e);
+            }
+        };
+        let file_name = spec.file_name();
+        let path = self.dir.join(&file_name);
+        fs::rename(&staged, &path)?;
+
+        let previous = self.installed()?;
+        let installed = InstalledModel {
+            spec: spec.clone(),
+            file_name,
+            size_bytes: fs::metadata(&path)?.len(),
+            installed_at: chrono::Utc::now().to_rfc3339(),
+            metadata,
+        };
+        let manifest = self.dir.join(INSTALLED_MANIFEST);
+        let tmp = manifest.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&installed)?)?;
+        fs::rename(&tmp, &manifest)?;
+
+        if let Some(previous) = previous.filter(|p| p.file_name != installed.file_name) {
+            let _ = fs::remove_file(self.dir.join(previous.file_name));
+        }
+        Ok(installed)
+    }
+}
+
+/// Hex SHA-256 of a file.
+pub fn sha256_file(path: &Path) -> ModelResult<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+
+/// Longest string value read from a GGUF header.
+const MAX_GGUF_STRING: u64 = 1 << 20;
+
+/// Read the architecture, name, context length, and quantization from a
+/// GGUF file's key-value header.
+pub fn read_gguf_metadata(path: &Path) -> ModelResult<ModelMetadata> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let invalid = |msg: &str| ModelError::InvalidGguf(msg.to_string());
+
+    let mut magic = [0u8; 4];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|_| invalid("file too short"))?;
+    if &magic != GGUF_MAGIC {
+        return Err(invalid("missing GGUF magic"));
+    }
+    let version = read_u32(&mut reader)?;
+    if version < 2 {
+        return Err(ModelError::InvalidGguf(format!(
+            "unsupported version {}",
+            version
+        )));
+    }
+    let _tensor_count = read_u64(&mut reader)?;
+    let kv_count = read_u64(&mut reader)?;
+
+    let mut metadata = ModelMetadata::default();
+    let mut context_lengths = Vec::new();
+    for _ in 0..kv_count {
+        let key = read_string(&mut reader)?;
+        let value_type = read_u32(&mut reader)?;
+        match (key.as_str(), value_type) {
+            ("general.architecture", GGUF_STRING) => {
+                metadata.architecture = Some(read_string(&mut reader)?)
+            }
+            ("general.name", GGUF_STRING) => metadata.name = Some(read_string(&mut reader)?),
+            ("general.file_type", GGUF_U32) => {
+                let file_type = read_u32(&mut reader)?;
+                metadata.quantization = Some(file_type_name(file_type));
+            }
+            (key, GGUF_U32) if key.ends_with(".context_length") => {
+                context_lengths.push((key.to_string(), read_u32(&mut reader)?));
+            }
+            (key, GGUF_U64) if key.ends_with(".context_length") => {
+                let length = read_u64(&mut reader)?;
+                context_lengths.push((key.to_string(), length.min(u32::MAX as u64) as u32));
+            }
+            _ => skip_value(&mut reader, value_type)?,
+        }
+    }
+
+    let arch_key = metadata
+        .architecture
+        .as_ref()
+        .map(|arch| format!("{}.context_length", arch));
+    metadata.context_length = context_lengths
+        .iter()
+        .find(|(key, _)| Some(key) == arch_key.as_ref())
+        .or(context_lengths.first())
+        .map(|(_, length)| *length);
+    Ok(metadata)
+}
+
+const GGUF_U32: u32 = 4;
+const GGUF_STRING: u32 = 8;
+const GGUF_ARRAY: u32 = 9;
+const GGUF_U64: u32 = 10;
+
+/// llama.cpp `llama_ftype` names.
+fn file_type_name(file_type: u32) -> String {
+    match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        32 => "BF16",
+        other => return format!("type {}", other),
+    }
+    .to_string()
+}
+
+fn read_u32(reader: &mut impl Read) -> ModelResult<u32> {
+    let mut buf = [0u8; 4];
+    reader
+        .read_exact(&mut buf)
+        .map_err(|_| ModelError::InvalidGguf("truncated header".into()))?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> ModelResult<u64> {
+    let mut buf = [0u8; 8];
+    reader
+        .read_exact(&mut buf)
+        .map_err(|_| ModelError::InvalidGguf("truncated header".into()))?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string(reader: &mut impl Read) -> ModelResult<String> {
+    let len = read_u64(reader)?;
+    if len > MAX_GGUF_STRING {
+        return Err(ModelError::InvalidGguf(format!("string of {} bytes", len)));
+    }
+    let mut buf = vec![0u8; len as usize];
+    reader
+        .read_exact(&mut buf)
+        .map_err(|_| ModelError::InvalidGguf("truncated header".into()))?;
+    String::from_utf8(buf).map_err(|_| ModelError::InvalidGguf("string is not UTF-8".into()))
+}
+
+/// Byte size of fixed-width GGUF value types.
+fn fixed_size(value_type: u32) -> Option<u64> {
+    match value_type {
+        0 | 1 | 7 => Some(1),
+        2 | 3 => Some(2),
+        4..=6 => Some(4),
+        10..=12 => Some(8),
+        _ => None,
+    }
+}
+
+/// Skip a value we don't need (tokenizer vocabularies are large arrays).
+fn skip_value<R: Read + Seek>(reader: &mut R, value_type: u32) -> ModelResult<()> {
+    if let Some(size) = fixed_size(value_type) {
+        reader.seek(SeekFrom::Current(size as i64))?;
+        return Ok(());
+    }
+    match value_type {
+        GGUF_STRING => {
+            let len = read_u64(reader)?;
+            reader.seek(SeekFrom::Current(len as i64))?;
+        }
+        GGUF_ARRAY => {
+            let element_type = read_u32(reader)?;
+            let count = read_u64(reader)?;
+            match fixed_size(element_type) {
+                Some(size) => {
+                    reader.seek(SeekFrom::Current(count.saturating_mul(size) as i64))?;
+                }
+                None => {
+                    for _ in 0..count {
+                        skip_value(reader, element_type)?;
+                    }
+                }
+            }
+        }
+        other => {
+            return Err(ModelError::InvalidGguf(format!(
+                "unknown value type {}",
+                other
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gguf_string(out: &mut Vec<u8>, s: &str) {
+        out.extend((s.len() as u64).to_le_bytes());
+        out.extend(s.as_bytes());
+    }
+
+    /// A header-only GGUF file: llama, 131072 context, Q4_K_M, with a
+    /// string array and a float in between.
+    fn fake_gguf() -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(GGUF_MAGIC);
+        out.extend(3u32.to_le_bytes());
+        out.extend(0u64.to_le_bytes());
+        out.extend(6u64.to_le_bytes());
+
+        gguf_string(&mut out, "general.architecture");
+        out.extend(GGUF_STRING.to_le_bytes());
+        gguf_string(&mut out, "llama");
+
+        gguf_string(&mut out, "tokenizer.ggml.tokens");
+        out.extend(GGUF_ARRAY.to_le_bytes());
+        out.extend(GGUF_STRING.to_le_bytes());
+        out.extend(3u64.to_le_bytes());
+        for token in ["<s>", "carprofen", "</s>"] {
+            gguf_string(&mut out, token);
+        }
+
+        gguf_string(&mut out, "llama.rope.freq_base");
+        out.extend(6u32.to_le_bytes());
+        out.extend(500000f32.to_le_bytes());
+
+        gguf_string(&mut out, "general.name");
+        out.extend(GGUF_STRING.to_le_bytes());
+        gguf_string(&mut out, "Llama 3.2 1B Instruct");
+
+        gguf_string(&mut out, "llama.context_length");
+        out.extend(GGUF_U32.to_le_bytes());
+        out.extend(131072u32.to_le_bytes());
+
+        gguf_string(&mut out, "general.file_type");
+        out.extend(GGUF_U32.to_le_bytes());
+        out.extend(15u32.to_le_bytes());
+        out
+    }
+
+    fn spec_for(bytes: &[u8], version: &str) -> ModelSpec {
+        ModelSpec {
+            id: "llama-3.2-1b-ner".into(),
+            version: version.into(),
+            url: "https://models.example/llama.gguf".into(),
+            sha256: hex::encode(Sha256::digest(bytes)),
+            size_bytes: Some(bytes.len() as u64),
+        }
+    }
+
+    #[test]
+    fn test_read_gguf_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        fs::write(&path, fake_gguf()).unwrap();
+
+        let metadata = read_gguf_metadata(&path).unwrap();
+        assert_eq!(metadata.architecture.as_deref(), Some("llama"));
+        assert_eq!(metadata.name.as_deref(), Some("Llama 3.2 1B Instruct"));
+        assert_eq!(metadata.context_length, Some(131072));
+        assert_eq!(metadata.quantization.as_deref(), Some("Q4_K_M"));
+
+        fs::write(&path, b"not a model").unwrap();
+        assert!(matches!(
+            read_gguf_metadata(&path),
+            Err(ModelError::InvalidGguf(_))
+        ));
+    }
+
+    #[test]
+    fn test_install_verify_and_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ModelManager::new(dir.path().join("models"));
+        assert_eq!(manager.status().unwrap(), ModelStatus::NotInstalled);
+        assert!(matches!(
+            manager.model_path(),
+            Err(ModelError::NotInstalled)
+        ));
+
+        let bytes = fake_gguf();
+        let source = dir.path().join("download.gguf");
+        fs::write(&source, &bytes).unwrap();
+        let v1 = spec_for(&bytes, "1");
+        assert!(manager.needs_update(&v1).unwrap());
+
+        let installed = manager.install_file(&v1, &source).unwrap();
+        assert_eq!(installed.metadata.quantization.as_deref(), Some("Q4_K_M"));
+        assert!(matches!(manager.status().unwrap(), ModelStatus::Ready(_)));
+        assert!(!manager.needs_update(&v1).unwrap());
+        assert_eq!(
+            manager.extractor_config().unwrap().context_size,
+            DEFAULT_CONTEXT_SIZE
+        );
+
+        // A bad checksum leaves the installed model alone
+        let mut bad = spec_for(&bytes, "2");
+        bad.sha256 = "00".repeat(32);
+        assert!(matches!(
+            manager.install_file(&bad, &source),
+            Err(ModelError::Checksum { .. })
+        ));
+        assert_eq!(manager.installed().unwrap().unwrap().spec.version, "1");
+        assert!(!dir
+            .path()
+            .join("models")
+            .join(format!("{}.part", bad.file_name()))
+            .exists());
+
+        // A new version replaces the old file
+        let v2 = spec_for(&bytes, "2");
+        manager.install_file(&v2, &source).unwrap();
+        assert!(!manager.dir().join(v1.file_name()).exists());
+        assert_eq!(
+            manager.model_path().unwrap(),
+            manager.dir().join(v2.file_name())
+        );
+
+        // Tampering shows up on the next status check
+        fs::write(manager.model_path().unwrap(), b"GGUF tampered").unwrap();
+        assert!(matches!(manager.status().unwrap(), ModelStatus::Corrupt(_)));
+        fs::remove_file(manager.model_path().unwrap()).unwrap();
+        assert!(matches!(manager.status().unwrap(), ModelStatus::Missing(_)));
+    }
+}
@@ -1,9 +1,16 @@
 //! NER prompts for veterinary drug extraction.
 //!
 //! These prompts are designed for Llama 3.2-1B with JSON grammar constraints.
+//! [`PromptBuilder`] adds a clinic's own abbreviations, examples, and locale
+//! on top of the defaults.
 
-/// System prompt for veterinary NER.
-pub const SYSTEM_PROMPT: &str = r#"You are a veterinary medical assistant that extracts drug information from clinical transcripts.
+use fuzzy_drugs_core::models::CatalogItem;
+use serde::{Deserialize, Serialize};
+
+use crate::extraction::{parse_ner_output, ExtractionError, ExtractionResult};
+
+/// Opening of the system prompt: the task and the mention fields.
+const SYSTEM_INTRO: &str = r#"You are a veterinary medical assistant that extracts drugs and billable services from clinical transcripts.
 
 Extract drug mentions with the following information:
 - drug_name: The name of the drug (brand name, generic name, or common abbreviation)
@@ -12,20 +19,34 @@ Extract drug mentions with the following information:
 - route: Route of administration (orally, IV, IM, subcutaneously, etc.)
 - species: Target species if mentioned (canine, feline, equine, etc.)
 
-Common veterinary drug abbreviations:
-- ace = acepromazine
-- metacam = meloxicam
-- rimadyl = carprofen
-- cerenia = maropitant
-- convenia = cefovecin
-- baytril = enrofloxacin
+Common veterinary drug abbreviations:"#;
+
+/// Services section and the output instruction that close the system prompt.
+const SYSTEM_SERVICES: &str = r#"Also extract services that are not drugs:
+- procedure: exams, nail trims, dental cleanings, surgeries
+- vaccine: rabies, DHPP, FVRCP, leptospirosis, bordetella
+- diagnostic: radiographs (x-rays), bloodwork, urinalysis, cytology"#;
 
-Output JSON with "mentions" array containing extracted drug mentions."#;
+const SYSTEM_OUTPUT: &str =
+    r#"Output JSON with a "mentions" array of drug mentions and a "services" array of services."#;
+
+/// Abbreviations every prompt lists, as (spoken, meaning).
+pub const DEFAULT_ALIAS_HINTS: &[(&str, &str)] = &[
+    ("ace", "acepromazine"),
+    ("metacam", "meloxicam"),
+    ("rimadyl", "carprofen"),
+    ("cerenia", "maropitant"),
+    ("convenia", "cefovecin"),
+    ("baytril", "enrofloxacin"),
+];
+
+/// Most clinic alias hints put in one prompt; each costs context.
+pub const MAX_ALIAS_HINTS: usize = 60;
 
 /// User prompt template for NER extraction.
 pub fn make_extraction_prompt(transcript: &str) -> String {
     format!(
-        r#"Extract all drug mentions from this veterinary clinical transcript:
+        r#"Extract all drug mentions and services from this veterinary clinical transcript:
 
 "{}"
 
@@ -37,7 +58,15 @@ Return a JSON object with a "mentions" array. Each mention should have:
 - route: Route of administration (null if not specified)
 - species: Target species (null if not specified)
 - start_offset: Character position where the mention starts
-- end_offset: Character position where the mention ends"#,
+- end_offset: Character position where the mention ends
+
+and a "services" array of procedures, vaccines, and diagnostics. Each service should have:
+- raw_text: The exact text containing the service
+- service_name: The service name ("nail trim", "rabies vaccine", "radiographs")
+- kind: "procedure", "vaccine", or "diagnostic"
+- quantity: How many (number only, null if not specified)
+- start_offset: Character position where the service starts
+- end_offset: Character position where the service ends"#,
         transcript
     )
 }
@@ -45,8 +74,9 @@ Return a JSON object with a "mentions" array. Each mention should have:
 /// JSON grammar constraint for llama.cpp to ensure valid output format.
 pub const JSON_GRAMMAR: &str = r#"
 root ::= object
-object ::= "{" ws "\"mentions\"" ws ":" ws mentions ws "}"
+object ::= "{" ws "\"mentions\"" ws ":" ws mentions ws "," ws "\"services\"" ws ":" ws services ws "}"
 mentions ::= "[" ws (mention (ws "," ws mention)*)? ws "]"
+services ::= "[" ws (service (ws "," ws service)*)? ws "]"
 mention ::= "{" ws
     "\"raw_text\"" ws ":" ws string ws "," ws
     "\"drug_name\"" ws ":" ws string ws "," ws
@@ -57,6 +87,14 @@ mention ::= "{" ws
     "\"start_offset\"" ws ":" ws number ws "," ws
     "\"end_offset\"" ws ":" ws number ws
 "}"
+service ::= "{" ws
+    "\"raw_text\"" ws ":" ws string ws "," ws
+    "\"service_name\"" ws ":" ws string ws "," ws
+    "\"kind\"" ws ":" ws ("\"procedure\"" | "\"vaccine\"" | "\"diagnostic\"") ws "," ws
+    "\"quantity\"" ws ":" ws (number | "null") ws "," ws
+    "\"start_offset\"" ws ":" ws number ws "," ws
+    "\"end_offset\"" ws ":" ws number ws
+"}"
 string ::= "\"" ([^"\\] | "\\" .)* "\""
 number ::= "-"? [0-9]+ ("." [0-9]+)?
 ws ::= [ \t\n]*
@@ -66,46 +104,254 @@ ws ::= [ \t\n]*
 pub const FEW_SHOT_EXAMPLES: &[(&str, &str)] = &[
     (
         "Give the dog 100mg of carprofen twice daily by mouth",
-        r#"{"mentions":[{"raw_text":"100mg of carprofen twice daily by mouth","drug_name":"carprofen","dose":100,"unit":"mg","route":"by mouth","species":"dog","start_offset":13,"end_offset":52}]}"#
+        r#"{"mentions":[{"raw_text":"100mg of carprofen twice daily by mouth","drug_name":"carprofen","dose":100,"unit":"mg","route":"by mouth","species":"dog","start_offset":13,"end_offset":52}],"services":[]}"#,
     ),
     (
         "Administer 0.5cc of acepromazine IM before surgery",
-        r#"{"mentions":[{"raw_text":"0.5cc of acepromazine IM","drug_name":"acepromazine","dose":0.5,"unit":"cc","route":"IM","species":null,"start_offset":11,"end_offset":35}]}"#
+        r#"{"mentions":[{"raw_text":"0.5cc of acepromazine IM","drug_name":"acepromazine","dose":0.5,"unit":"cc","route":"IM","species":null,"start_offset":11,"end_offset":35}],"services":[]}"#,
     ),
     (
         "The cat needs metacam and also some cerenia for nausea",
-        r#"{"mentions":[{"raw_text":"metacam","drug_name":"metacam","dose":null,"unit":null,"route":null,"species":"cat","start_offset":13,"end_offset":20},{"raw_text":"cerenia for nausea","drug_name":"cerenia","dose":null,"unit":null,"route":null,"species":"cat","start_offset":35,"end_offset":53}]}"#
+        r#"{"mentions":[{"raw_text":"metacam","drug_name":"metacam","dose":null,"unit":null,"route":null,"species":"cat","start_offset":13,"end_offset":20},{"raw_text":"cerenia for nausea","drug_name":"cerenia","dose":null,"unit":null,"route":null,"species":"cat","start_offset":35,"end_offset":53}],"services":[]}"#,
+    ),
+    (
+        "Rabies vaccine today, trim her nails, and 0.5mL of cerenia SQ",
+        r#"{"mentions":[{"raw_text":"0.5mL of cerenia SQ","drug_name":"cerenia","dose":0.5,"unit":"mL","route":"SQ","species":null,"start_offset":42,"end_offset":61}],"services":[{"raw_text":"Rabies vaccine","service_name":"rabies vaccine","kind":"vaccine","quantity":null,"start_offset":0,"end_offset":14},{"raw_text":"trim her nails","service_name":"nail trim","kind":"procedure","quantity":null,"start_offset":22,"end_offset":36}]}"#,
     ),
 ];
 
-/// Build a complete prompt with system context and few-shot examples.
-pub fn build_full_prompt(transcript: &str, include_examples: bool) -> String {
-    let mut prompt = String::new();
+/// Added to the request when the first answer couldn't be parsed.
+pub const RETRY_INSTRUCTION: &str = "Your previous answer could not be parsed. Respond with ONLY the JSON object: \
+every mention must have all eight fields and every service all six (null when unknown), no trailing commas, \
+and no text before or after the object.";
 
-    // System context
-    prompt.push_str("<|system|>\n");
-    prompt.push_str(SYSTEM_PROMPT);
-    prompt.push_str("\n<|end|>\n");
+/// A clinic abbreviation or house name and what it means
+/// ("dex sp" = "dexamethasone sodium phosphate").
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AliasHint {
+    /// As spoken
+    pub alias: String,
+    /// What it refers to
+    pub name: String,
+}
 
-    // Few-shot examples
-    if include_examples {
-        for (input, output) in FEW_SHOT_EXAMPLES {
-            prompt.push_str("<|user|>\n");
-            prompt.push_str(&make_extraction_prompt(input));
-            prompt.push_str("\n<|end|>\n");
-            prompt.push_str("<|assistant|>\n");
-            prompt.push_str(output);
-            prompt.push_str("\n<|end|>\n");
+/// A transcript and the output the model should give for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FewShotExample {
+    pub input: String,
+    /// [`NerOutput`] JSON, offsets into `input`
+    pub output: String,
+}
+
+/// Builds extraction prompts, with a clinic's alias hints, extra few-shot
+/// examples, and locale on top of the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PromptBuilder {
+    /// Listed after (and overriding) [`DEFAULT_ALIAS_HINTS`]
+    #[serde(default)]
+    pub alias_hints: Vec<AliasHint>,
+    /// Shown after [`FEW_SHOT_EXAMPLES`] when examples are included
+    #[serde(default)]
+    pub examples: Vec<FewShotExample>,
+    /// BCP 47 tag of the dictation language ("en-GB", "es-MX")
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+impl PromptBuilder {
+    /// The default prompt.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_alias_hint(mut self, alias: impl Into<String>, name: impl Into<String>) -> Self {
+        self.alias_hints.push(AliasHint {
+            alias: alias.into(),
+            name: name.into(),
+        });
+        self
+    }
+
+    /// Hint each active catalog item's aliases ("rimadyl" = "Carprofen
+    /// 100mg Chewable"), up to [`MAX_ALIAS_HINTS`] in all. Aliases that only
+    /// differ from the name in case are skipped.
+    pub fn with_catalog_hints(mut self, catalog: &[CatalogItem]) -> Self {
+        let hints = catalog
+            .iter()
+            .filter(|item| item.active)
+            .flat_map(|item| item.aliases.iter().map(move |alias| (alias, &item.name)))
+            .filter(|(alias, name)| !alias.trim().is_empty() && !alias.eq_ignore_ascii_case(name));
+        for (alias, name) in hints {
+            if self.alias_hints.len() >= MAX_ALIAS_HINTS {
+                break;
+            }
+            if !self
+                .alias_hints
+                .iter()
+                .any(|h| h.alias.eq_ignore_ascii_case(alias))
+            {
+                self = self.with_alias_hint(alias.trim(), name.as_str());
+            }
+        }
+        self
+    }
+
+    pub fn with_example(mut self, input: impl Into<String>, output: impl Into<String>) -> Self {
+        self.examples.push(FewShotExample {
+            input: input.into(),
+            output: output.into(),
+        });
+        self
+    }
+
+    pub fn with_locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    /// Reject hints and examples that would mislead the model: empty
+    /// hints, examples whose output doesn't parse or whose offsets don't
+    /// point at their `raw_text`, and malformed locale tags.
+    pub fn validate(&self) -> ExtractionResult<()> {
+        let config = |msg: String| Err(ExtractionError::Config(msg));
+        if let Some(hint) = self
+            .alias_hints
+            .iter()
+            .find(|h| h.alias.trim().is_empty() || h.name.trim().is_empty())
+        {
+            return config(format!(
+                "empty alias hint: {:?} = {:?}",
+                hint.alias, hint.name
+            ));
+        }
+        for example in &self.examples {
+            let output = parse_ner_output(&example.output).map_err(|e| {
+                ExtractionError::Config(format!("few-shot example output doesn't parse: {}", e))
+            })?;
+            let spans = output
+                .mentions
+                .iter()
+                .map(|m| (m.start_offset, m.end_offset, &m.raw_text))
+                .chain(
+                    output
+                        .services
+                        .iter()
+                        .map(|s| (s.start_offset, s.end_offset, &s.raw_text)),
+                );
+            for (start, end, raw_text) in spans {
+                if example.input.get(start..end) != Some(raw_text.as_str()) {
+                    return config(format!(
+                        "few-shot example offsets {}..{} don't match {:?}",
+                        start, end, raw_text
+                    ));
+                }
+            }
+        }
+        if let Some(locale) = &self.locale {
+            let valid = locale.split(['-', '_']).all(|part| {
+                (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric())
+            });
+            if !valid {
+                return config(format!("invalid locale tag {:?}", locale));
+            }
+        }
+        Ok(())
+    }
+
+    /// The system prompt: task, alias hints, services, and locale.
+    pub fn system_prompt(&self) -> String {
+        let mut prompt = String::from(SYSTEM_INTRO);
+        let overridden = |alias: &str| {
+            self.alias_hints
+                .iter()
+                .any(|h| h.alias.eq_ignore_ascii_case(alias))
+        };
+        let defaults = DEFAULT_ALIAS_HINTS
+            .iter()
+            .filter(|(alias, _)| !overridden(alias));
+        for (alias, name) in defaults {
+            prompt.push_str(&format!("\n- {} = {}", alias, name));
+        }
+        for hint in &self.alias_hints {
+            prompt.push_str(&format!("\n- {} = {}", hint.alias.trim(), hint.name.trim()));
+        }
+        prompt.push_str("\n\n");
+        prompt.push_str(SYSTEM_SERVICES);
+        if let Some(locale) = &self.locale {
+            prompt.push_str(&format!(
+                "\n\nTranscripts are dictated in the {} locale. Keep drug and service names \
+                 as spoken, and write numbers with a period as the decimal separator \
+                 (\"0,5\" is 0.5).",
+                locale
+            ));
+        }
+        prompt.push_str("\n\n");
+        prompt.push_str(SYSTEM_OUTPUT);
+        prompt
+    }
+
+    /// A complete prompt for `transcript`, with the few-shot examples if
+    /// `include_examples`.
+    pub fn build(&self, transcript: &str, include_examples: bool) -> String {
+        self.assemble(&make_extraction_prompt(transcript), include_examples)
+    }
+
+    /// Like [`build`](Self::build), with [`RETRY_INSTRUCTION`] appended to
+    /// the request.
+    pub fn build_retry(&self, transcript: &str, include_examples: bool) -> String {
+        let request = format!(
+            "{}\n\n{}",
+            make_extraction_prompt(transcript),
+            RETRY_INSTRUCTION
+        );
+        self.assemble(&request, include_examples)
+    }
+
+    fn assemble(&self, request: &str, include_examples: bool) -> String {
+        let mut prompt = String::new();
+
+        // System context
+        prompt.push_str("<|system|>\n");
+        prompt.push_str(&self.system_prompt());
+        prompt.push_str("\n<|end|>\n");
+
+        // Few-shot examples
+        if include_examples {
+            let examples = FEW_SHOT_EXAMPLES.iter().copied().chain(
+                self.examples
+                    .iter()
+                    .map(|e| (e.input.as_str(), e.output.as_str())),
+            );
+            for (input, output) in examples {
+                prompt.push_str("<|user|>\n");
+                prompt.push_str(&make_extraction_prompt(input));
+                prompt.push_str("\n<|end|>\n");
+                prompt.push_str("<|assistant|>\n");
+                prompt.push_str(output);
+                prompt.push_str("\n<|end|>\n");
+            }
         }
+
+        // Actual request
+        prompt.push_str("<|user|>\n");
+        prompt.push_str(request);
+        prompt.push_str("\n<|end|>\n");
+        prompt.push_str("<|assistant|>\n");
+
+        prompt
     }
+}
 
-    // Actual request
-    prompt.push_str("<|user|>\n");
-    prompt.push_str(&make_extraction_prompt(transcript));
-    prompt.push_str("\n<|end|>\n");
-    prompt.push_str("<|assistant|>\n");
+/// Build a complete prompt with the default system context and few-shot
+/// examples.
+pub fn build_full_prompt(transcript: &str, include_examples: bool) -> String {
+    PromptBuilder::default().build(transcript, include_examples)
+}
 
-    prompt
+/// Like [`build_full_prompt`], with [`RETRY_INSTRUCTION`] appended to the
+/// request.
+pub fn build_retry_prompt(transcript: &str, include_examples: bool) -> String {
+    PromptBuilder::default().build_retry(transcript, include_examples)
 }
 
 #[cfg(test)]
@@ -118,6 +364,20 @@ mod tests {
         assert!(prompt.contains("Give 10mg carprofen PO"));
         assert!(prompt.contains("drug_name"));
         assert!(prompt.contains("mentions"));
+        assert!(prompt.contains("service_name"));
+    }
+
+    #[test]
+    fn test_few_shot_services_match_input() {
+        for (input, output) in FEW_SHOT_EXAMPLES {
+            let parsed = crate::extraction::parse_ner_output(output).unwrap();
+            for service in &parsed.services {
+                assert_eq!(
+                    &input[service.start_offset..service.end_offset],
+                    service.raw_text
+                );
+            }
+        }
     }
 
     #[test]
@@ -129,6 +389,14 @@ mod tests {
         assert!(prompt.contains("Test transcript"));
     }
 
+    #[test]
+    fn test_retry_prompt() {
+        let prompt = build_retry_prompt("Test transcript", false);
+        assert!(prompt.contains("Test transcript"));
+        assert!(prompt.contains(RETRY_INSTRUCTION));
+        assert!(prompt.ends_with("<|assistant|>\n"));
+    }
+
     #[test]
     fn test_full_prompt_without_examples() {
         let prompt = build_full_prompt("Test transcript", false);
@@ -136,4 +404,54 @@ mod tests {
         assert!(!prompt.contains("100mg of carprofen")); // No examples
         assert!(prompt.contains("Test transcript"));
     }
+
+    #[test]
+    fn test_prompt_builder_hints_and_locale() {
+        let mut carprofen = CatalogItem::new("CARP100".into(), "Carprofen 100mg".into());
+        carprofen.aliases = vec!["rimadyl".into(), "novox".into(), "CARPROFEN 100MG".into()];
+        let mut retired = CatalogItem::new("OLD".into(), "Old Drug".into());
+        retired.aliases = vec!["oldie".into()];
+        retired.active = false;
+
+        let prompts = PromptBuilder::new()
+            .with_alias_hint("dex sp", "dexamethasone sodium phosphate")
+            .with_catalog_hints(&[carprofen, retired])
+            .with_locale("es-MX");
+        let system = prompts.system_prompt();
+        assert!(system.contains("- dex sp = dexamethasone sodium phosphate"));
+        assert!(system.contains("- novox = Carprofen 100mg"));
+        // The clinic's meaning replaces the default one
+        assert!(system.contains("- rimadyl = Carprofen 100mg"));
+        assert!(!system.contains("- rimadyl = carprofen"));
+        assert!(system.contains("- ace = acepromazine"));
+        assert!(!system.contains("CARPROFEN 100MG"));
+        assert!(!system.contains("oldie"));
+        assert!(system.contains("es-MX locale"));
+        assert!(prompts.validate().is_ok());
+
+        let default = PromptBuilder::default().system_prompt();
+        assert!(default.contains("- rimadyl = carprofen"));
+        assert!(!default.contains("locale"));
+    }
+
+    #[test]
+    fn test_prompt_builder_examples() {
+        let input = "Give 2mL dex sp IV";
+        let output = r#"{"mentions":[{"raw_text":"2mL dex sp IV","drug_name":"dex sp","dose":2,"unit":"mL","route":"IV","species":null,"start_offset":5,"end_offset":18}],"services":[]}"#;
+        let prompts = PromptBuilder::new().with_example(input, output);
+        assert!(prompts.validate().is_ok());
+        assert!(prompts.build("Test transcript", true).contains(output));
+        assert!(!prompts.build("Test transcript", false).contains(output));
+
+        let shifted = output.replace(r#""start_offset":5"#, r#""start_offset":4"#);
+        let bad = PromptBuilder::new().with_example(input, shifted);
+        assert!(matches!(bad.validate(), Err(ExtractionError::Config(_))));
+        let unparsed = PromptBuilder::new().with_example(input, "{}");
+        assert!(matches!(
+            unparsed.validate(),
+            Err(ExtractionError::Config(_))
+        ));
+        let empty = PromptBuilder::new().with_alias_hint(" ", "carprofen");
+        assert!(matches!(empty.validate(), Err(ExtractionError::Config(_))));
+    }
 }
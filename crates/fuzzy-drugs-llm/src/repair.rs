@@ -0,0 +1,331 @@
+//! Repair of malformed model output.
+//!
+//! Small models sometimes stop mid-object, leave trailing commas, or emit a
+//! mention missing fields. [`parse_ner_output_repaired`] fixes up the JSON
+//! ([`repair_json`]), keeps the mentions that match the schema, and clamps
+//! their offsets to the transcript. [`extract_with_retry`] runs the prompt
+//! once more, with stricter instructions, when even that fails.
+
+use serde_json::Value;
+
+use crate::extraction::{ExtractionError, ExtractionResult, NerOutput, RawMention, RawService};
+use crate::prompts::PromptBuilder;
+
+/// Best-effort fix-up of truncated or sloppy JSON: drops text around the
+/// outermost object, trailing commas, and an incomplete member at the end,
+/// then closes an unterminated string and any open brackets. `None` if
+/// there is no object at all.
+pub fn repair_json(text: &str) -> Option<String> {
+    let start = text.find('{')?;
+    let mut out = String::with_capacity(text.len() - start + 8);
+    let mut closers: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text[start..].chars() {
+        if in_string {
+            out.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' => {
+                closers.push('}');
+                out.push(c);
+            }
+            '[' => {
+                closers.push(']');
+                out.push(c);
+            }
+            '}' | ']' => {
+                // Mismatched closers are dropped
+                if closers.last() == Some(&c) {
+                    trim_trailing_comma(&mut out);
+                    closers.pop();
+                    out.push(c);
+                    if closers.is_empty() {
+                        break;
+                    }
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+
+    if in_string {
+        if escaped {
+            out.pop();
+        }
+        out.push('"');
+    }
+
+    // Drop an incomplete member or element at the end
+    loop {
+        out.truncate(out.trim_end().len());
+        if out.ends_with(',') {
+            out.pop();
+        } else if out.ends_with(':') {
+            out.pop();
+            out.truncate(out.trim_end().len());
+            match string_start(&out) {
+                Some(key_start) => out.truncate(key_start),
+                None => break,
+            }
+        } else if out.ends_with('"') {
+            // A key with no colon yet
+            let in_object = closers.last() == Some(&'}');
+            match string_start(&out) {
+                Some(key_start)
+                    if in_object && out[..key_start].trim_end().ends_with(['{', ',']) =>
+                {
+                    out.truncate(key_start)
+                }
+                _ => break,
+            }
+        } else if out.ends_with(|c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+        {
+            // A number or literal cut short ("nul", "12.")
+            let token_start = out
+                .rfind(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+')))
+                .map_or(0, |i| i + 1);
+            if serde_json::from_str::<Value>(&out[token_start..]).is_ok() {
+                break;
+            }
+            out.truncate(token_start);
+        } else {
+            break;
+        }
+    }
+
+    for closer in closers.iter().rev() {
+        trim_trailing_comma(&mut out);
+        out.push(*closer);
+    }
+    Some(out)
+}
+
+fn trim_trailing_comma(out: &mut String) {
+    let trimmed = out.trim_end();
+    if trimmed.ends_with(',') {
+        out.truncate(trimmed.len() - 1);
+    }
+}
+
+/// Start of the string literal that `out` ends with (its opening quote).
+fn string_start(out: &str) -> Option<usize> {
+    let bytes = out.as_bytes();
+    let mut i = bytes.len().checked_sub(1)?;
+    while i > 0 {
+        i -= 1;
+        if bytes[i] == b'"' {
+            let backslashes = bytes[..i].iter().rev().take_while(|&&b| b == b'\\').count();
+            if backslashes % 2 == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+/// Parse model output, repairing it if needed. Mentions and services that
+/// don't match the schema (or have no name) are dropped, and offsets are
+/// clamped to `transcript` on character boundaries. A missing `services`
+/// array means none.
+pub fn parse_ner_output_repaired(output: &str, transcript: &str) -> ExtractionResult<NerOutput> {
+    let start = output
+        .find('{')
+        .ok_or_else(|| ExtractionError::InvalidFormat("No JSON object found in response".into()))?;
+    let value: Value = match output.rfind('}') {
+        Some(end) if end > start => serde_json::from_str(&output[start..=end]).ok(),
+        _ => None,
+    }
+    .or_else(|| serde_json::from_str(&repair_json(output)?).ok())
+    .ok_or_else(|| ExtractionError::InvalidFormat("Response is not repairable JSON".into()))?;
+
+    let Some(Value::Array(items)) = value.get("mentions") else {
+        return Err(ExtractionError::InvalidFormat(
+            "Response has no \"mentions\" array".into(),
+        ));
+    };
+    let mentions = items
+        .iter()
+        .filter_map(|item| serde_json::from_value::<RawMention>(item.clone()).ok())
+        .filter(|mention| !mention.drug_name.trim().is_empty())
+        .map(|mut mention| {
+            (mention.start_offset, mention.end_offset) =
+                clamp_span(transcript, mention.start_offset, mention.end_offset);
+            mention
+        })
+        .collect();
+    let services = match value.get("services") {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| serde_json::from_value::<RawService>(item.clone()).ok())
+            .filter(|service| !service.service_name.trim().is_empty())
+            .map(|mut service| {
+                (service.start_offset, service.end_offset) =
+                    clamp_span(transcript, service.start_offset, service.end_offset);
+                service
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Ok(NerOutput { mentions, services })
+}
+
+/// Offsets clamped to `transcript` on character boundaries, with the end
+/// never before the start.
+fn clamp_span(transcript: &str, start: usize, end: usize) -> (usize, usize) {
+    let start = floor_char_boundary(transcript, start);
+    (start, floor_char_boundary(transcript, end).max(start))
+}
+
+fn floor_char_boundary(s: &str, offset: usize) -> usize {
+    let mut offset = offset.min(s.len());
+    while !s.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Run the extraction prompt from `prompts` through `generate` and parse
+/// the output; if it can't be repaired, try once more with the retry prompt
+/// ([`PromptBuilder::build_retry`]) before giving up. Only format errors
+/// (including running out of tokens) are retried.
+pub fn extract_with_retry(
+    transcript: &str,
+    prompts: &PromptBuilder,
+    few_shot: bool,
+    mut generate: impl FnMut(&str) -> ExtractionResult<String>,
+) -> ExtractionResult<NerOutput> {
+    let first = generate(&prompts.build(transcript, few_shot))
+        .and_then(|output| parse_ner_output_repaired(&output, transcript));
+    match first {
+        Err(
+            ExtractionError::InvalidFormat(_)
+            | ExtractionError::JsonParse(_)
+            | ExtractionError::Truncated { .. },
+        ) => generate(&prompts.build_retry(transcript, few_shot))
+            .and_then(|output| parse_ner_output_repaired(&output, transcript)),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MENTION: &str = r#"{"raw_text":"100mg carprofen","drug_name":"carprofen","dose":100,"unit":"mg","route":"PO","species":null,"start_offset":5,"end_offset":20}"#;
+
+    #[test]
+    fn test_repair_trailing_commas_and_truncation() {
+        let trailing = format!(r#"{{"mentions":[{},]}}"#, MENTION);
+        let repaired: Value = serde_json::from_str(&repair_json(&trailing).unwrap()).unwrap();
+        assert_eq!(repaired["mentions"].as_array().unwrap().len(), 1);
+
+        let cut_offs = [
+            format!(r#"{{"mentions":[{}, {{"raw_text":"meta"#, MENTION),
+            format!(
+                r#"{{"mentions":[{}, {{"raw_text":"metacam","drug_na"#,
+                MENTION
+            ),
+            format!(
+                r#"{{"mentions":[{}, {{"raw_text":"metacam","drug_name":"#,
+                MENTION
+            ),
+            format!(
+                r#"{{"mentions":[{}, {{"raw_text":"metacam","dose":nu"#,
+                MENTION
+            ),
+            format!(
+                r#"{{"mentions":[{}, {{"raw_text":"metacam","dose":1."#,
+                MENTION
+            ),
+            format!(r#"Sure! {{"mentions":[{}, "#, MENTION),
+        ];
+        for text in cut_offs {
+            let repaired = repair_json(&text).unwrap();
+            let value: Value = serde_json::from_str(&repaired)
+                .unwrap_or_else(|e| panic!("{} -> {}: {}", text, repaired, e));
+            assert_eq!(value["mentions"][0]["drug_name"], "carprofen", "{}", text);
+        }
+        assert_eq!(repair_json("no json here"), None);
+    }
+
+    #[test]
+    fn test_parse_repaired_validates_and_clamps() {
+        let transcript = "Give 100mg carprofen";
+        let output = format!(
+            r#"{{"mentions":[{}, {{"drug_name":"no offsets"}}, {{"raw_text":"x","drug_name":" ","dose":null,"unit":null,"route":null,"species":null,"start_offset":0,"end_offset":1}}, {{"raw_text":"carprofen","drug_name":"carprofen","dose":null,"unit":null,"route":null,"species":null,"start_offset":90,"end_offset":4"#,
+            MENTION
+        );
+        let parsed = parse_ner_output_repaired(&output, transcript).unwrap();
+        assert_eq!(parsed.mentions.len(), 2);
+        assert_eq!(parsed.mentions[0].end_offset, 20);
+        // Offsets past the end are clamped (and end never precedes start)
+        assert_eq!(parsed.mentions[1].start_offset, transcript.len());
+        assert_eq!(parsed.mentions[1].end_offset, transcript.len());
+
+        assert!(matches!(
+            parse_ner_output_repaired(r#"{"items":[]}"#, transcript),
+            Err(ExtractionError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_repaired_services() {
+        let transcript = "Nail trim and rads";
+        // Cut off mid-way through the second service
+        let output = r#"{"mentions":[],"services":[{"raw_text":"Nail trim","service_name":"nail trim","kind":"procedure","quantity":null,"start_offset":0,"end_offset":90},{"raw_text":"rads","service_name":"radiogr"#;
+        let parsed = parse_ner_output_repaired(output, transcript).unwrap();
+        assert_eq!(parsed.services.len(), 1);
+        assert_eq!(parsed.services[0].end_offset, transcript.len());
+
+        let parsed = parse_ner_output_repaired(r#"{"mentions":[]}"#, transcript).unwrap();
+        assert!(parsed.services.is_empty());
+    }
+
+    #[test]
+    fn test_extract_with_retry() {
+        let transcript = "Give 100mg carprofen";
+        let hinted = PromptBuilder::new().with_alias_hint("carp", "carprofen");
+        let mut prompts = Vec::new();
+        let output = extract_with_retry(transcript, &hinted, false, |prompt| {
+            prompts.push(prompt.to_string());
+            Ok(if prompts.len() == 1 {
+                "I could not find any drugs.".to_string()
+            } else {
+                format!(r#"{{"mentions":[{}]}}"#, MENTION)
+            })
+        })
+        .unwrap();
+        assert_eq!(output.mentions.len(), 1);
+        assert_eq!(prompts.len(), 2);
+        assert_ne!(prompts[0], prompts[1]);
+        assert!(prompts.iter().all(|p| p.contains("- carp = carprofen")));
+
+        // Cancellation and inference failures are not retried
+        let mut calls = 0;
+        let result = extract_with_retry(transcript, &PromptBuilder::default(), false, |_| {
+            calls += 1;
+            Err(ExtractionError::Inference("decode failed".into()))
+        });
+        assert!(matches!(result, Err(ExtractionError::Inference(_))));
+        assert_eq!(calls, 1);
+
+        // A second bad answer is surfaced
+        let result = extract_with_retry(transcript, &PromptBuilder::default(), false, |_| {
+            Ok("nope".to_string())
+        });
+        assert!(matches!(result, Err(ExtractionError::InvalidFormat(_))));
+    }
+}
@@ -0,0 +1,192 @@
+//! Streaming extraction output.
+//!
+//! Extracting a long dictation takes seconds. `extract_streaming` on each
+//! backend reports each piece of generated text to an
+//! [`ExtractionListener`] as it arrives, plus each mention as soon as its
+//! JSON object closes (found by a [`MentionStream`]). Returning [`Cancelled`]
+//! from either callback stops generation with
+//! [`ExtractionError::Cancelled`](crate::ExtractionError::Cancelled).
+
+use std::ops::Range;
+
+pub use fuzzy_drugs_core::progress::{CancellationFlag, Cancelled};
+
+use crate::extraction::RawMention;
+
+/// Receives extraction output while the model is still generating.
+pub trait ExtractionListener {
+    /// Called with each piece of generated text.
+    fn on_token(&self, text: &str) -> Result<(), Cancelled>;
+
+    /// Called with each mention once its JSON object is complete. These are
+    /// previews; the final [`NerOutput`](crate::NerOutput) is authoritative.
+    fn on_mention(&self, _mention: &RawMention) -> Result<(), Cancelled> {
+        Ok(())
+    }
+}
+
+/// No reporting, never cancelled.
+impl ExtractionListener for () {
+    fn on_token(&self, _text: &str) -> Result<(), Cancelled> {
+        Ok(())
+    }
+}
+
+/// Checks the flag only; for callers that just want to cancel.
+impl ExtractionListener for CancellationFlag {
+    fn on_token(&self, _text: &str) -> Result<(), Cancelled> {
+        self.check()
+    }
+}
+
+/// Incremental scanner over the generated JSON that picks out each mention
+/// object as soon as it closes.
+#[derive(Debug, Default)]
+pub struct MentionStream {
+    text: String,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    object_start: Option<usize>,
+    complete: bool,
+}
+
+impl MentionStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything pushed so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether the top-level object has closed. Backends without a grammar
+    /// stop generating here rather than waiting for an end token.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Append generated text; returns the mentions it completed.
+    pub fn push(&mut self, piece: &str) -> Vec<RawMention> {
+        self.push_spanned(piece)
+            .into_iter()
+            .map(|(_, mention)| mention)
+            .collect()
+    }
+
+    /// Like [`push`](Self::push), with where each mention's object sits in
+    /// [`text`](Self::text).
+    pub fn push_spanned(&mut self, piece: &str) -> Vec<(Range<usize>, RawMention)> {
+        let offset = self.text.len();
+        self.text.push_str(piece);
+
+        let mut mentions = Vec::new();
+        for (i, c) in piece.char_indices() {
+            let pos = offset + i;
+            if self.in_string {
+                match c {
+                    _ if self.escaped => self.escaped = false,
+                    '\\' => self.escaped = true,
+                    '"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' => self.in_string = true,
+                '{' | '[' => {
+                    // Objects directly inside a top-level array; those in
+                    // `"services"` don't parse as mentions and are skipped
+                    if c == '{' && self.depth == 2 {
+                        self.object_start = Some(pos);
+                    }
+                    self.depth += 1;
+                }
+                '}' | ']' => {
+                    if c == '}' && self.depth == 1 {
+                        self.complete = true;
+                    }
+                    self.depth = self.depth.saturating_sub(1);
+                    if c == '}' && self.depth == 2 {
+                        if let Some(start) = self.object_start.take() {
+                            // Incomplete or off-schema objects are left to the final parse
+                            if let Ok(mention) = serde_json::from_str(&self.text[start..=pos]) {
+                                mentions.push((start..pos + 1, mention));
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        mentions
+    }
+}
+
+/// Take the longest valid UTF-8 prefix of `pending`, leaving a character
+/// split across tokens for the next call.
+pub fn take_utf8(pending: &mut Vec<u8>) -> Option<String> {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(s) => s.len(),
+        Err(e) => e.valid_up_to(),
+    };
+    if valid == 0 {
+        return None;
+    }
+    let rest = pending.split_off(valid);
+    let text = std::mem::replace(pending, rest);
+    String::from_utf8(text).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mentions_emitted_as_objects_close() {
+        let output = r#"{"mentions":[{"raw_text":"100mg {carprofen}","drug_name":"carprofen","dose":100,"unit":"mg","route":"PO","species":null,"start_offset":0,"end_offset":15},{"raw_text":"metacam","drug_name":"metacam","dose":null,"unit":null,"route":null,"species":null,"start_offset":20,"end_offset":27}]}"#;
+
+        let mut stream = MentionStream::new();
+        let mut seen = Vec::new();
+        // Feed in small pieces, like tokens
+        let chars: Vec<char> = output.chars().collect();
+        for piece in chars.chunks(3) {
+            assert!(!stream.is_complete());
+            let piece: String = piece.iter().collect();
+            for mention in stream.push(&piece) {
+                seen.push((mention.drug_name, stream.text().len()));
+            }
+        }
+
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].0, "carprofen");
+        // The first mention arrives before the second one starts
+        let second = output.find(r#"{"raw_text":"metacam""#).unwrap();
+        assert!(seen[0].1 <= second + 3);
+        assert_eq!(seen[1].0, "metacam");
+        assert_eq!(stream.text(), output);
+        assert!(stream.is_complete());
+    }
+
+    #[test]
+    fn test_take_utf8_keeps_split_characters() {
+        let bytes = "5 µg".as_bytes();
+        let mut pending = bytes[..3].to_vec(); // "5 " plus half of "µ"
+        assert_eq!(take_utf8(&mut pending).as_deref(), Some("5 "));
+        assert_eq!(pending.len(), 1);
+        assert_eq!(take_utf8(&mut pending), None);
+
+        pending.extend_from_slice(&bytes[3..]);
+        assert_eq!(take_utf8(&mut pending).as_deref(), Some("µg"));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_cancellation_flag_listener() {
+        let flag = CancellationFlag::new();
+        assert!(flag.on_token("{").is_ok());
+        flag.cancel();
+        assert_eq!(flag.on_token("\"").unwrap_err(), Cancelled);
+    }
+}
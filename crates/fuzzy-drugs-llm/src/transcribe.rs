@@ -0,0 +1,487 @@
+//! Speech-to-text with whisper.cpp.
+//!
+//! [`Transcriber`] (`whisper` feature) runs a GGML Whisper model over a WAV
+//! recording and returns a [`TranscriptionResult`]: the transcript text plus
+//! segment and word timings, with each word's byte offsets in the text. That
+//! lets a mention's `start_offset..end_offset` be mapped back to the audio
+//! ([`TranscriptionResult::audio_span`]) for playback during review.
+//!
+//! The result types and the audio/word assembly helpers are always
+//! available so hosts with their own ASR (WhisperKit on iOS) can build the
+//! same result.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Sample rate Whisper models expect.
+pub const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Transcription errors.
+#[derive(Error, Debug)]
+pub enum TranscriptionError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Unsupported audio: {0}")]
+    Audio(String),
+
+    #[error("Failed to load model {path}: {reason}")]
+    ModelLoad { path: String, reason: String },
+
+    #[error("Transcription failed: {0}")]
+    Inference(String),
+}
+
+/// How to load the Whisper model and decode.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriberConfig {
+    /// GGML Whisper model file
+    pub model_path: PathBuf,
+    /// Spoken language ("en"); `None` detects it
+    pub language: Option<String>,
+    /// Worker threads; `None` lets whisper.cpp decide
+    pub threads: Option<u32>,
+    /// Run on the GPU when whisper.cpp was built with one
+    pub use_gpu: bool,
+}
+
+impl TranscriberConfig {
+    /// English, CPU-only defaults for the model at `model_path`.
+    pub fn new(model_path: impl Into<PathBuf>) -> Self {
+        Self {
+            model_path: model_path.into(),
+            language: Some("en".into()),
+            threads: None,
+            use_gpu: false,
+        }
+    }
+}
+
+/// A transcript with its timing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionResult {
+    pub text: String,
+    /// Detected or configured language code
+    pub language: Option<String>,
+    /// Length of the audio
+    pub duration_ms: u64,
+    pub segments: Vec<TranscriptSegment>,
+    /// Every word in `text`, in order
+    pub words: Vec<WordTiming>,
+}
+
+/// A stretch of speech Whisper decoded as one unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Byte range of the segment in the transcript text
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// When a word was spoken and where it is in the transcript text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Byte range of the word in the transcript text
+    pub start_offset: usize,
+    pub end_offset: usize,
+    /// Lowest token probability in the word (0.0 - 1.0)
+    pub probability: f32,
+}
+
+/// Where a stretch of transcript was spoken.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioSpan {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// One decoded token, as whisper.cpp reports it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenTiming {
+    /// Token text (may be part of a multi-byte character)
+    pub bytes: Vec<u8>,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub probability: f32,
+}
+
+impl TranscriptionResult {
+    /// Append a segment decoded from `tokens`. Words are split on
+    /// whitespace; each is timed by the tokens it came from, or by the
+    /// segment if the tokens don't decode as UTF-8.
+    pub fn push_segment(&mut self, start_ms: u64, end_ms: u64, tokens: &[TokenTiming]) {
+        let bytes: Vec<u8> = tokens
+            .iter()
+            .flat_map(|t| t.bytes.iter().copied())
+            .collect();
+        let (segment_text, exact) = match String::from_utf8(bytes) {
+            Ok(text) => (text, true),
+            Err(e) => (String::from_utf8_lossy(e.as_bytes()).into_owned(), false),
+        };
+        let leading = segment_text.len() - segment_text.trim_start().len();
+        let segment_text = segment_text.trim();
+        if segment_text.is_empty() {
+            return;
+        }
+
+        if !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        let base = self.text.len();
+        self.text.push_str(segment_text);
+        self.segments.push(TranscriptSegment {
+            start_ms,
+            end_ms,
+            start_offset: base,
+            end_offset: self.text.len(),
+        });
+
+        // Byte range of each token within the (untrimmed) segment text
+        let mut token_ranges = Vec::with_capacity(tokens.len());
+        let mut pos = 0;
+        for token in tokens {
+            token_ranges.push(pos..pos + token.bytes.len());
+            pos += token.bytes.len();
+        }
+
+        let mut offset = 0;
+        for word in segment_text.split_whitespace() {
+            let start = offset + segment_text[offset..].find(word).unwrap_or(0);
+            let end = start + word.len();
+            offset = end;
+
+            let (raw_start, raw_end) = (leading + start, leading + end);
+            let covering: Vec<&TokenTiming> = tokens
+                .iter()
+                .zip(&token_ranges)
+                .filter(|(_, range)| exact && range.start < raw_end && range.end > raw_start)
+                .map(|(token, _)| token)
+                .collect();
+            let timing = match (covering.first(), covering.last()) {
+                (Some(first), Some(last)) => WordTiming {
+                    word: word.to_string(),
+                    start_ms: first.start_ms,
+                    end_ms: last.end_ms.max(first.start_ms),
+                    start_offset: base + start,
+                    end_offset: base + end,
+                    probability: covering.iter().map(|t| t.probability).fold(1.0, f32::min),
+                },
+                _ => WordTiming {
+                    word: word.to_string(),
+                    start_ms,
+                    end_ms,
+                    start_offset: base + start,
+                    end_offset: base + end,
+                    probability: 0.0,
+                },
+            };
+            self.words.push(timing);
+        }
+    }
+
+    /// When the transcript bytes `start_offset..end_offset` (a mention's
+    /// offsets) were spoken: from the first to the last word they touch.
+    /// `None` if they touch no word.
+    pub fn audio_span(&self, start_offset: usize, end_offset: usize) -> Option<AudioSpan> {
+        let mut words = self.words.iter().filter(|w| {
+            w.start_offset < end_offset.max(start_offset + 1) && w.end_offset > start_offset
+        });
+        let first = words.next()?;
+        let last = words.next_back().unwrap_or(first);
+        Some(AudioSpan {
+            start_ms: first.start_ms,
+            end_ms: last.end_ms,
+        })
+    }
+}
+
+/// Average interleaved channels down to mono.
+pub fn downmix(samples: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks(channels as usize)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+/// Linear resampling from `from_rate` to `to_rate`.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() || from_rate == 0 {
+        return samples.to_vec();
+    }
+    let len = (samples.len() as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let step = from_rate as f64 / to_rate as f64;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let frac = (pos - index as f64) as f32;
+            let a = samples[index.min(samples.len() - 1)];
+            let b = samples[(index + 1).min(samples.len() - 1)];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+#[cfg(feature = "whisper")]
+pub use engine::{load_wav, Transcriber};
+
+#[cfg(feature = "whisper")]
+mod engine {
+    use std::path::Path;
+
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+    use super::{
+        downmix, resample, TokenTiming, TranscriberConfig, TranscriptionError, TranscriptionResult,
+        WHISPER_SAMPLE_RATE,
+    };
+
+    /// Read a WAV file as 16 kHz mono samples, converting as needed.
+    pub fn load_wav(path: &Path) -> Result<Vec<f32>, TranscriptionError> {
+        let audio = |e: hound::Error| TranscriptionError::Audio(e.to_string());
+        let mut reader = hound::WavReader::open(path).map_err(audio)?;
+        let spec = reader.spec();
+        let samples: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader
+                .samples::<f32>()
+                .collect::<Result<_, _>>()
+                .map_err(audio)?,
+            hound::SampleFormat::Int => {
+                let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 / scale))
+                    .collect::<Result<_, _>>()
+                    .map_err(audio)?
+            }
+        };
+        let mono = downmix(&samples, spec.channels);
+        Ok(resample(&mono, spec.sample_rate, WHISPER_SAMPLE_RATE))
+    }
+
+    /// Speech-to-text with a local Whisper model.
+    pub struct Transcriber {
+        ctx: WhisperContext,
+        config: TranscriberConfig,
+    }
+
+    impl Transcriber {
+        /// Load the model named by `config`.
+        pub fn load(config: TranscriberConfig) -> Result<Self, TranscriptionError> {
+            let model_load = |reason: String| TranscriptionError::ModelLoad {
+                path: config.model_path.display().to_string(),
+                reason,
+            };
+            let path = config
+                .model_path
+                .to_str()
+                .ok_or_else(|| model_load("path is not UTF-8".into()))?;
+            if !config.model_path.is_file() {
+                return Err(model_load("file not found".into()));
+            }
+            let mut params = WhisperContextParameters::default();
+            params.use_gpu = config.use_gpu;
+            let ctx = WhisperContext::new_with_params(path, params)
+                .map_err(|e| model_load(e.to_string()))?;
+            Ok(Self { ctx, config })
+        }
+
+        pub fn config(&self) -> &TranscriberConfig {
+            &self.config
+        }
+
+        /// Transcribe the WAV file at `path`.
+        pub fn transcribe(
+            &self,
+            path: impl AsRef<Path>,
+        ) -> Result<TranscriptionResult, TranscriptionError> {
+            self.transcribe_samples(&load_wav(path.as_ref())?)
+        }
+
+        /// Transcribe 16 kHz mono samples.
+        pub fn transcribe_samples(
+            &self,
+            samples: &[f32],
+        ) -> Result<TranscriptionResult, TranscriptionError> {
+            let inference =
+                |e: whisper_rs::WhisperError| TranscriptionError::Inference(e.to_string());
+            let mut state = self.ctx.create_state().map_err(inference)?;
+
+            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+            // "auto" detects the language before decoding
+            params.set_language(Some(self.config.language.as_deref().unwrap_or("auto")));
+            params.set_token_timestamps(true);
+            params.set_print_special(false);
+            params.set_print_progress(false);
+            params.set_print_realtime(false);
+            params.set_print_timestamps(false);
+            if let Some(threads) = self.config.threads {
+                params.set_n_threads(threads as i32);
+            }
+            state.full(params, samples).map_err(inference)?;
+
+            let mut result = TranscriptionResult {
+                language: match &self.config.language {
+                    Some(language) => Some(language.clone()),
+                    None => state
+                        .full_lang_id_from_state()
+                        .ok()
+                        .and_then(whisper_rs::get_lang_str)
+                        .map(str::to_string),
+                },
+                duration_ms: samples.len() as u64 * 1000 / WHISPER_SAMPLE_RATE as u64,
+                ..Default::default()
+            };
+
+            // Timestamps are in 10 ms units; special tokens (timestamps,
+            // markers) sort after end-of-text
+            let eot = self.ctx.token_eot();
+            for segment in 0..state.full_n_segments().map_err(inference)? {
+                let mut tokens = Vec::new();
+                for token in 0..state.full_n_tokens(segment).map_err(inference)? {
+                    let data = state
+                        .full_get_token_data(segment, token)
+                        .map_err(inference)?;
+                    if data.id >= eot {
+                        continue;
+                    }
+                    let bytes = self
+                        .ctx
+                        .token_to_cstr(data.id)
+                        .map_err(inference)?
+                        .to_bytes();
+                    tokens.push(TokenTiming {
+                        bytes: bytes.to_vec(),
+                        start_ms: data.t0.max(0) as u64 * 10,
+                        end_ms: data.t1.max(0) as u64 * 10,
+                        probability: data.p,
+                    });
+                }
+                let start_ms = state
+                    .full_get_segment_t0(segment)
+                    .map_err(inference)?
+                    .max(0) as u64
+                    * 10;
+                let end_ms = state
+                    .full_get_segment_t1(segment)
+                    .map_err(inference)?
+                    .max(0) as u64
+                    * 10;
+                result.push_segment(start_ms, end_ms, &tokens);
+            }
+            Ok(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(text: &str, start_ms: u64, end_ms: u64) -> TokenTiming {
+        TokenTiming {
+            bytes: text.as_bytes().to_vec(),
+            start_ms,
+            end_ms,
+            probability: 0.9,
+        }
+    }
+
+    #[test]
+    fn test_words_timed_by_their_tokens() {
+        let mut result = TranscriptionResult::default();
+        result.push_segment(
+            0,
+            2000,
+            &[
+                token(" Give", 0, 300),
+                token(" carp", 300, 600),
+                token("rof", 600, 800),
+                token("en", 800, 1000),
+                token(" 100", 1100, 1400),
+                token("mg.", 1400, 2000),
+            ],
+        );
+        result.push_segment(2500, 3000, &[token(" Recheck", 2500, 3000)]);
+
+        assert_eq!(result.text, "Give carprofen 100mg. Recheck");
+        let words: Vec<&str> = result.words.iter().map(|w| w.word.as_str()).collect();
+        assert_eq!(words, ["Give", "carprofen", "100mg.", "Recheck"]);
+        for word in &result.words {
+            assert_eq!(&result.text[word.start_offset..word.end_offset], word.word);
+        }
+        assert_eq!(
+            (result.words[1].start_ms, result.words[1].end_ms),
+            (300, 1000)
+        );
+        assert_eq!(result.segments[1].start_offset, 22);
+
+        // A mention's offsets map back to when it was said
+        let start = result.text.find("carprofen").unwrap();
+        let span = result
+            .audio_span(start, result.text.find(" Recheck").unwrap())
+            .unwrap();
+        assert_eq!(
+            span,
+            AudioSpan {
+                start_ms: 300,
+                end_ms: 2000
+            }
+        );
+        assert_eq!(
+            result.audio_span(result.text.len(), result.text.len()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_character_split_across_tokens() {
+        let micro = "µ".as_bytes();
+        let mut result = TranscriptionResult::default();
+        result.push_segment(
+            1000,
+            2000,
+            &[
+                token(" 5", 1000, 1200),
+                TokenTiming {
+                    bytes: vec![b' ', micro[0]],
+                    start_ms: 1200,
+                    end_ms: 1400,
+                    probability: 0.5,
+                },
+                TokenTiming {
+                    bytes: vec![micro[1], b'g'],
+                    start_ms: 1400,
+                    end_ms: 1600,
+                    probability: 0.5,
+                },
+            ],
+        );
+        assert_eq!(result.text, "5 µg");
+        assert_eq!(result.words[1].word, "µg");
+        assert_eq!(result.words[1].start_ms, 1200);
+        assert_eq!(result.words[1].probability, 0.5);
+    }
+
+    #[test]
+    fn test_audio_conversion() {
+        assert_eq!(downmix(&[0.5, -0.5, 1.0, 0.0], 2), [0.0, 0.5]);
+        assert_eq!(downmix(&[0.25, 0.5], 1), [0.25, 0.5]);
+
+        let samples: Vec<f32> = (0..48).map(|i| i as f32).collect();
+        let down = resample(&samples, 48_000, WHISPER_SAMPLE_RATE);
+        assert_eq!(down.len(), 16);
+        assert_eq!(down[1], 3.0);
+        assert_eq!(resample(&samples, 16_000, 16_000), samples);
+    }
+}
@@ -51,7 +51,7 @@ final class AppState: ObservableObject {
     }
 
     func refreshPendingCount() {
-        // pendingReviewCount = (try? core?.getPendingReviewDrafts().count) ?? 0
+        // pendingReviewCount = (try? core?.listPendingReviewDrafts().count) ?? 0
         pendingReviewCount = 3 // Placeholder
     }
 
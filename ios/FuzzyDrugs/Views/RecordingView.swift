@@ -187,7 +187,7 @@ struct RecordingView: View {
 
             // Create draft and resolve mentions via Rust core
             // let draft = try appState.core?.createDraft(patientId: selectedPatient!.id)
-            // ... resolve mentions and update draft
+            // try appState.core?.processTranscript(draftId: draft.draftId, transcript: result.text)
 
             appState.refreshPendingCount()
         } catch {
@@ -90,7 +90,7 @@ struct ReviewQueueView: View {
 
     private func refreshDrafts() {
         // In actual implementation:
-        // drafts = (try? appState.core?.getPendingReviewDrafts().map { ... }) ?? []
+        // drafts = (try? appState.core?.listPendingReviewDrafts().map { ... }) ?? []
 
         // Mock data
         drafts = [